@@ -0,0 +1,167 @@
+//! 性能基准：在RamDisk上测量顺序/随机读写吞吐量、块分配速率、目录插入速率和路径查找延迟，
+//! 排除宿主文件系统和磁盘的影响，用于发现纯Rust实现中的性能退化。
+//!
+//! 运行：cargo bench --no-default-features --features "use-rust std"
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use lwext4_arce::{DummyHal, Ext4Filesystem, FsConfig, InodeType, MkfsOptions, RamDisk, RandomSource};
+
+type Fs = Ext4Filesystem<DummyHal, RamDisk>;
+
+const ROOT_INO: u32 = 2;
+const BLOCK_SIZE: u64 = 4096;
+/// 基准镜像大小
+const IMAGE_SIZE: usize = 64 << 20;
+/// 读写测试的文件大小
+const FILE_SIZE: u64 = 8 << 20;
+/// 顺序读写每次调用的字节数
+const CHUNK: usize = 64 << 10;
+/// 随机读写每轮的次数（每次一个块）
+const RANDOM_OPS: u64 = 256;
+/// 目录插入测试每轮创建的文件数
+const DIR_ENTRIES: usize = 500;
+
+/// 固定种子的xorshift随机数（镜像和随机偏移在每次运行时相同）
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+impl RandomSource for XorShift {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            *b = self.next() as u8;
+        }
+    }
+}
+
+/// 在新的RamDisk上格式化并挂载（不创建日志，只测量文件系统本身）
+fn new_fs() -> Fs {
+    let opts = MkfsOptions {
+        journal: false,
+        ..Default::default()
+    };
+    Fs::mkfs(RamDisk::new(IMAGE_SIZE), &opts, FsConfig::default(), &mut XorShift(1)).unwrap()
+}
+
+/// 创建写满FILE_SIZE字节的文件
+fn filled_file(fs: &mut Fs, name: &str) -> u32 {
+    let ino = fs.create(ROOT_INO, name, InodeType::RegularFile, 0o644).unwrap();
+    let chunk = vec![0x5a; CHUNK];
+    for offset in (0..FILE_SIZE).step_by(CHUNK) {
+        fs.write_at(ino, &chunk, offset).unwrap();
+    }
+    ino
+}
+
+fn bench_read_write(c: &mut Criterion) {
+    let mut fs = new_fs();
+    let file = filled_file(&mut fs, "data");
+    let mut chunk = vec![0u8; CHUNK];
+    let mut block = vec![0u8; BLOCK_SIZE as usize];
+    let mut rng = XorShift(7);
+
+    let mut group = c.benchmark_group("io");
+    group.throughput(Throughput::Bytes(FILE_SIZE));
+    group.bench_function("seq_read", |b| {
+        b.iter(|| {
+            for offset in (0..FILE_SIZE).step_by(CHUNK) {
+                fs.read_at(file, &mut chunk, offset).unwrap();
+            }
+            black_box(&chunk);
+        })
+    });
+    // 新文件的顺序写入，包括块分配；每轮结束时删除文件
+    group.bench_function("seq_write_new", |b| {
+        b.iter(|| {
+            filled_file(&mut fs, "new");
+            fs.unlink(ROOT_INO, "new").unwrap();
+        })
+    });
+    group.bench_function("seq_overwrite", |b| {
+        b.iter(|| {
+            for offset in (0..FILE_SIZE).step_by(CHUNK) {
+                fs.write_at(file, &chunk, offset).unwrap();
+            }
+        })
+    });
+
+    group.throughput(Throughput::Bytes(RANDOM_OPS * BLOCK_SIZE));
+    let blocks = FILE_SIZE / BLOCK_SIZE;
+    group.bench_function("random_read", |b| {
+        b.iter(|| {
+            for _ in 0..RANDOM_OPS {
+                let offset = rng.next() % blocks * BLOCK_SIZE;
+                fs.read_at(file, &mut block, offset).unwrap();
+            }
+            black_box(&block);
+        })
+    });
+    group.bench_function("random_write", |b| {
+        b.iter(|| {
+            for _ in 0..RANDOM_OPS {
+                let offset = rng.next() % blocks * BLOCK_SIZE;
+                fs.write_at(file, &block, offset).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_alloc(c: &mut Criterion) {
+    let mut fs = new_fs();
+    let block = vec![1u8; BLOCK_SIZE as usize];
+
+    // 隔块写入稀疏文件：每次写入都分配一个新块并新增一个extent
+    let mut group = c.benchmark_group("alloc");
+    group.throughput(Throughput::Elements(RANDOM_OPS));
+    group.bench_function("sparse_blocks", |b| {
+        b.iter(|| {
+            let ino = fs.create(ROOT_INO, "sparse", InodeType::RegularFile, 0o644).unwrap();
+            for i in 0..RANDOM_OPS {
+                fs.write_at_sparse(ino, &block, i * 2 * BLOCK_SIZE).unwrap();
+            }
+            fs.unlink(ROOT_INO, "sparse").unwrap();
+        })
+    });
+    group.finish();
+}
+
+fn bench_dir(c: &mut Criterion) {
+    let mut fs = new_fs();
+    let names: Vec<String> = (0..DIR_ENTRIES).map(|i| format!("file-{i:05}")).collect();
+
+    let mut group = c.benchmark_group("dir");
+    group.throughput(Throughput::Elements(DIR_ENTRIES as u64));
+    group.bench_function("insert", |b| {
+        b.iter(|| {
+            let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+            for name in &names {
+                fs.create(dir, name.as_str(), InodeType::RegularFile, 0o644).unwrap();
+            }
+            fs.remove_dir_all("/dir").unwrap();
+        })
+    });
+    group.finish();
+
+    // 5层目录下的文件，最后一层有DIR_ENTRIES个兄弟条目
+    let leaf = fs.create_dir_all("/usr/share/doc/lwext4/examples", 0o755).unwrap();
+    for name in &names {
+        fs.create(leaf, name.as_str(), InodeType::RegularFile, 0o644).unwrap();
+    }
+    let path = format!("/usr/share/doc/lwext4/examples/{}", names[DIR_ENTRIES / 2]);
+    c.bench_function("lookup/deep_path", |b| {
+        b.iter(|| black_box(fs.resolve_path(black_box(&path)).unwrap()))
+    });
+}
+
+criterion_group!(benches, bench_read_write, bench_alloc, bench_dir);
+criterion_main!(benches);
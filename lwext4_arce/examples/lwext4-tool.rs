@@ -0,0 +1,439 @@
+//! lwext4-tool：基于纯 Rust 实现操作 ext4 镜像文件的命令行工具。
+//!
+//! 用法：
+//! ```text
+//! lwext4-tool <image> ls [path]
+//! lwext4-tool <image> cat <path>
+//! lwext4-tool <image> stat <path>
+//! lwext4-tool <image> cp-in <host-file> <path>
+//! lwext4-tool <image> cp-out <path> <host-file>
+//! lwext4-tool <image> mkdir <path>
+//! lwext4-tool <image> rm <path>
+//! lwext4-tool <image> df
+//! lwext4-tool <image> du [path]
+//! lwext4-tool <image> defrag <path>
+//! lwext4-tool <image> freefrag
+//! lwext4-tool <image> layout
+//! lwext4-tool <image> tune label <name>
+//! lwext4-tool <image> tune reserved <percent>
+//! lwext4-tool <image> tune csum on|off
+//! lwext4-tool <image> tune uuid random|<uuid>
+//! lwext4-tool <image> mkfs [label]
+//! ```
+
+use std::{
+    env,
+    io::{self, Write},
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lwext4_arce::{
+    Ext4Error, Ext4ErrorKind, Ext4Filesystem, Ext4Result, FileAttr, FileBlockDevice, FsConfig,
+    InodeType, MkfsOptions, StdRandom, SystemHal,
+};
+
+/// 根目录的inode编号
+const ROOT_INO: u32 = 2;
+/// 拷贝文件时使用的缓冲区大小
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+/// 使用宿主系统时间的HAL
+struct StdHal;
+
+impl SystemHal for StdHal {
+    fn now() -> Option<Duration> {
+        SystemTime::now().duration_since(UNIX_EPOCH).ok()
+    }
+}
+
+type Fs = Ext4Filesystem<StdHal, FileBlockDevice>;
+
+/// 将路径拆分为各级名称（忽略空段和"."）
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".")
+}
+
+/// 从根目录开始逐级解析路径，返回inode编号
+fn resolve(fs: &mut Fs, path: &str) -> Ext4Result<u32> {
+    let mut ino = ROOT_INO;
+    for name in components(path) {
+        ino = fs.lookup(ino, name)?.entry().ino();
+    }
+    Ok(ino)
+}
+
+/// 将宿主I/O错误转换为Ext4Error（保留errno，没有errno时按io::ErrorKind转换）
+fn host_error(op: &'static str) -> impl FnOnce(io::Error) -> Ext4Error {
+    move |err| match err.raw_os_error() {
+        Some(code) => Ext4Error::new(code, op),
+        None => {
+            let kind = match err.kind() {
+                io::ErrorKind::NotFound => Ext4ErrorKind::NotFound,
+                io::ErrorKind::PermissionDenied => Ext4ErrorKind::NotPermitted,
+                io::ErrorKind::AlreadyExists => Ext4ErrorKind::AlreadyExists,
+                io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => Ext4ErrorKind::InvalidInput,
+                io::ErrorKind::OutOfMemory => Ext4ErrorKind::NoMemory,
+                _ => Ext4ErrorKind::Io,
+            };
+            Ext4Error::from_kind(kind, op)
+        }
+    }
+}
+
+/// 解析路径的父目录，返回（父目录inode编号，最后一级名称）
+fn resolve_parent<'a>(fs: &mut Fs, path: &'a str) -> Ext4Result<(u32, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, "invalid file name"));
+    }
+    Ok((resolve(fs, dir)?, name))
+}
+
+/// 类型的单字符表示（与ls -l一致）
+fn type_char(ty: InodeType) -> char {
+    match ty {
+        InodeType::Directory => 'd',
+        InodeType::RegularFile => '-',
+        InodeType::Symlink => 'l',
+        InodeType::CharacterDevice => 'c',
+        InodeType::BlockDevice => 'b',
+        InodeType::Fifo => 'p',
+        InodeType::Socket => 's',
+        InodeType::Unknown => '?',
+    }
+}
+
+fn cmd_ls(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let ino = resolve(fs, path)?;
+    let mut reader = fs.read_dir(ino, 0)?;
+    let mut entries = Vec::new();
+    while let Some(entry) = reader.current() {
+        entries.push((entry.ino(), String::from_utf8_lossy(entry.name()).into_owned()));
+        reader.step()?;
+    }
+    drop(reader);
+
+    let mut attr = FileAttr::default();
+    for (ino, name) in entries {
+        fs.get_attr(ino, &mut attr)?;
+        println!(
+            "{}{:o} {:>4} {:>5} {:>5} {:>10} {:>8} {}",
+            type_char(attr.node_type),
+            attr.mode & 0o7777,
+            attr.nlink,
+            attr.uid,
+            attr.gid,
+            attr.size,
+            ino,
+            name
+        );
+    }
+    Ok(())
+}
+
+fn cmd_cat(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let ino = resolve(fs, path)?;
+    let mut buf = vec![0u8; COPY_BUF_SIZE];
+    let mut offset = 0;
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        let n = fs.read_at(ino, &mut buf, offset)?;
+        if n == 0 {
+            break;
+        }
+        stdout
+            .write_all(&buf[..n])
+            .map_err(host_error("write stdout failed"))?;
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+fn cmd_stat(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let ino = resolve(fs, path)?;
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr)?;
+    println!("  File: {path}");
+    println!("  Type: {:?}", attr.node_type);
+    println!(" Inode: {}  Links: {}", attr.ino, attr.nlink);
+    println!("  Mode: {:o}  Uid: {}  Gid: {}", attr.mode & 0o7777, attr.uid, attr.gid);
+    println!("  Size: {}  Blocks: {}  IO Block: {}", attr.size, attr.blocks, attr.block_size);
+    println!("Access: {}", attr.atime.as_secs());
+    println!("Modify: {}", attr.mtime.as_secs());
+    println!("Change: {}", attr.ctime.as_secs());
+    Ok(())
+}
+
+fn cmd_cp_in(fs: &mut Fs, host: &str, path: &str) -> Ext4Result<()> {
+    let data = std::fs::read(host).map_err(host_error("read host file failed"))?;
+    let (parent, name) = resolve_parent(fs, path)?;
+    let ino = match fs.lookup(parent, name) {
+        Ok(mut result) => {
+            let ino = result.entry().ino();
+            drop(result);
+            fs.set_len(ino, 0)?;
+            ino
+        }
+        Err(err) if err.kind() == Ext4ErrorKind::NotFound => fs.create(parent, name, InodeType::RegularFile, 0o644)?,
+        Err(err) => return Err(err),
+    };
+    let mut offset = 0;
+    for chunk in data.chunks(COPY_BUF_SIZE) {
+        offset += fs.write_at_sparse(ino, chunk, offset as u64)?;
+    }
+    fs.flush()
+}
+
+fn cmd_cp_out(fs: &mut Fs, path: &str, host: &str) -> Ext4Result<()> {
+    let ino = resolve(fs, path)?;
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr)?;
+    let mut data = vec![0u8; attr.size as usize];
+    let mut offset = 0;
+    while offset < data.len() {
+        let n = fs.read_at(ino, &mut data[offset..], offset as u64)?;
+        if n == 0 {
+            break;
+        }
+        offset += n;
+    }
+    data.truncate(offset);
+    std::fs::write(host, data).map_err(host_error("write host file failed"))
+}
+
+fn cmd_mkdir(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let (parent, name) = resolve_parent(fs, path)?;
+    fs.create(parent, name, InodeType::Directory, 0o755)?;
+    fs.flush()
+}
+
+fn cmd_rm(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let (parent, name) = resolve_parent(fs, path)?;
+    fs.unlink(parent, name)?;
+    fs.flush()
+}
+
+fn cmd_df(fs: &mut Fs) -> Ext4Result<()> {
+    let stat = fs.stat()?;
+    let kib = |blocks: u64| blocks * stat.block_size as u64 / 1024;
+    println!("{:>12} {:>12} {:>12} {:>10} {:>10}", "1K-blocks", "Used", "Available", "Inodes", "IFree");
+    println!(
+        "{:>12} {:>12} {:>12} {:>10} {:>10}",
+        kib(stat.blocks_count),
+        kib(stat.blocks_count - stat.free_blocks_count),
+        kib(stat.free_blocks_count),
+        stat.inodes_count,
+        stat.free_inodes_count
+    );
+    Ok(())
+}
+
+fn cmd_du(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let usage = fs.disk_usage(path)?;
+    println!(
+        "{}K\t{path}\t({} files, {} dirs, apparent size {})",
+        usage.bytes() / 1024,
+        usage.files,
+        usage.dirs,
+        usage.apparent_size
+    );
+    Ok(())
+}
+
+fn cmd_defrag(fs: &mut Fs, path: &str) -> Ext4Result<()> {
+    let stats = fs.defragment(path)?;
+    println!(
+        "{path}: {} -> {} extents, {} blocks moved",
+        stats.extents_before, stats.extents_after, stats.blocks_moved
+    );
+    fs.flush()
+}
+
+fn cmd_freefrag(fs: &mut Fs) -> Ext4Result<()> {
+    let report = fs.free_space_report()?;
+    let kib = |blocks: u64| blocks * report.block_size as u64 / 1024;
+    println!("Total blocks: {}", report.total_blocks);
+    println!("Free blocks: {}", report.free_blocks);
+    println!("Min. free extent: {} KB", kib(report.min_extent as u64));
+    println!("Max. free extent: {} KB", kib(report.max_extent as u64));
+    if let Some(avg) = report.avg_extent() {
+        println!("Avg. free extent: {} KB", kib(avg));
+    }
+    println!("Num. free extent: {}", report.free_extents);
+    println!("{:>20} {:>12} {:>12}", "Extent Size (blocks)", "Free extents", "Free Blocks");
+    for (i, (&extents, &blocks)) in report
+        .histogram
+        .extents
+        .iter()
+        .zip(&report.histogram.blocks)
+        .enumerate()
+    {
+        if extents > 0 {
+            let range = format!("{}...{}-", 1u64 << i, 1u64 << (i + 1));
+            println!("{range:>20} {extents:>12} {blocks:>12}");
+        }
+    }
+    Ok(())
+}
+
+/// 输出各块组的元数据位置（格式与dumpe2fs相近），并检查块组描述符
+fn cmd_layout(fs: &mut Fs) -> Ext4Result<()> {
+    let range = |r: &std::ops::Range<u64>| format!("{}-{}", r.start, r.end - 1);
+    for group in 0..fs.group_count() {
+        let layout = fs.group_layout(group)?;
+        println!("Group {}: (Blocks {})", group, range(&layout.blocks));
+        if let Some(sb) = layout.super_block {
+            let kind = if group == 0 { "Primary" } else { "Backup" };
+            print!("  {kind} superblock at {sb}");
+            if !layout.gdt.is_empty() {
+                print!(", Group descriptors at {}", range(&layout.gdt));
+            }
+            println!();
+        } else if !layout.gdt.is_empty() {
+            println!("  Group descriptors at {}", range(&layout.gdt));
+        }
+        if !layout.reserved_gdt.is_empty() {
+            println!("  Reserved GDT blocks at {}", range(&layout.reserved_gdt));
+        }
+        println!("  Block bitmap at {}", layout.block_bitmap);
+        println!("  Inode bitmap at {}", layout.inode_bitmap);
+        println!("  Inode table at {}", range(&layout.inode_table));
+        if layout != fs.default_group_layout(group)? {
+            println!("  (differs from the default placement)");
+        }
+    }
+    fs.check_layout()
+}
+
+/// 解析"xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"形式的UUID
+fn parse_uuid(text: &str) -> Ext4Result<[u8; 16]> {
+    let invalid = || Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, "invalid uuid");
+    let hex: Vec<u8> = text.bytes().filter(|&b| b != b'-').collect();
+    if hex.len() != 32 {
+        return Err(invalid());
+    }
+    let mut uuid = [0u8; 16];
+    for (byte, pair) in uuid.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(uuid)
+}
+
+fn cmd_tune(fs: &mut Fs, what: &str, value: &str) -> Ext4Result<()> {
+    match (what, value) {
+        ("label", name) => fs.set_volume_name(name)?,
+        ("reserved", percent) => {
+            let percent = percent
+                .parse()
+                .map_err(|_| Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, "invalid percentage"))?;
+            fs.set_reserved_percent(percent)?
+        }
+        ("uuid", "random") => fs.set_random_uuid(&mut StdRandom)?,
+        ("uuid", uuid) => fs.set_uuid(parse_uuid(uuid)?)?,
+        ("csum", "on") => fs.enable_metadata_csum()?,
+        ("csum", "off") => fs.disable_metadata_csum()?,
+        _ => return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, "unknown tune setting")),
+    }
+    fs.flush()
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: lwext4-tool <image> <command> [args]");
+    eprintln!("commands:");
+    eprintln!("  ls [path]                 list a directory");
+    eprintln!("  cat <path>                print file contents");
+    eprintln!("  stat <path>               show file metadata");
+    eprintln!("  cp-in <host-file> <path>  copy a host file into the image");
+    eprintln!("  cp-out <path> <host-file> copy a file out of the image");
+    eprintln!("  import <host-path> <path> copy a host tree into the image");
+    eprintln!("  export <path> <host-path> copy a tree out of the image");
+    eprintln!("  mkdir <path>              create a directory");
+    eprintln!("  rm <path>                 remove a file or an empty directory");
+    eprintln!("  df                        show filesystem usage");
+    eprintln!("  du [path]                 show space used by a tree");
+    eprintln!("  defrag <path>             defragment a regular file");
+    eprintln!("  freefrag                  show free space fragmentation");
+    eprintln!("  layout                    show where each group keeps its metadata");
+    eprintln!("  tune label <name>         set the volume label");
+    eprintln!("  tune reserved <percent>   set the reserved block percentage");
+    eprintln!("  tune csum on|off          enable or disable metadata checksums");
+    eprintln!("  tune uuid random|<uuid>   set the filesystem uuid");
+    eprintln!("  mkfs [label]              create a new filesystem on the whole image");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        return usage();
+    }
+    let image = &args[0];
+    let cmd = args[1].as_str();
+    let rest: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+
+    let device = match FileBlockDevice::open(image) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("lwext4-tool: cannot open {image}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // 格式化不需要先挂载
+    if cmd == "mkfs" {
+        let label = match rest.as_slice() {
+            [] => "",
+            [label] => label,
+            _ => return usage(),
+        };
+        let opts = MkfsOptions { label: label.into(), ..Default::default() };
+        return match Fs::mkfs(device, &opts, FsConfig::default(), &mut StdRandom) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("lwext4-tool: mkfs: {err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut fs = match Fs::new(device, FsConfig::default()) {
+        Ok(fs) => fs,
+        Err(err) => {
+            eprintln!("lwext4-tool: cannot mount {image}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match (cmd, rest.as_slice()) {
+        ("ls", []) => cmd_ls(&mut fs, "/"),
+        ("ls", [path]) => cmd_ls(&mut fs, path),
+        ("cat", [path]) => cmd_cat(&mut fs, path),
+        ("stat", [path]) => cmd_stat(&mut fs, path),
+        ("cp-in", [host, path]) => cmd_cp_in(&mut fs, host, path),
+        ("cp-out", [path, host]) => cmd_cp_out(&mut fs, path, host),
+        ("import", [host, path]) => fs.import_tree(host, path),
+        ("export", [path, host]) => fs.export_tree(path, host),
+        ("mkdir", [path]) => cmd_mkdir(&mut fs, path),
+        ("rm", [path]) => cmd_rm(&mut fs, path),
+        ("df", []) => cmd_df(&mut fs),
+        ("du", []) => cmd_du(&mut fs, "/"),
+        ("du", [path]) => cmd_du(&mut fs, path),
+        ("defrag", [path]) => cmd_defrag(&mut fs, path),
+        ("freefrag", []) => cmd_freefrag(&mut fs),
+        ("layout", []) => cmd_layout(&mut fs),
+        ("tune", [what, value]) => cmd_tune(&mut fs, what, value),
+        _ => return usage(),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("lwext4-tool: {cmd}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! 以任意字节覆盖模板镜像的第一个块组描述符块，然后挂载并遍历
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use lwext4_arce::fuzz::Template;
+
+static TEMPLATE: OnceLock<Template> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    TEMPLATE
+        .get_or_init(|| Template::new().expect("failed to build template image"))
+        .group_desc(data);
+});
@@ -0,0 +1,8 @@
+//! 以任意字节作为整个镜像挂载并遍历（可用 lwext4_arce::fuzz::Template::image 的内容作为种子）
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    lwext4_arce::fuzz::mount_and_walk(data);
+});
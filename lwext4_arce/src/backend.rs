@@ -0,0 +1,202 @@
+//! 后端选择模块，描述可用的ext4实现（C FFI / 纯 Rust）并在挂载时进行选择。
+//!
+//! Ext4Filesystem、InodeRef等对外接口绑定到构建时选定的后端：启用use-rust时为纯 Rust 后端，
+//! 只启用use-ffi时为C后端。同时启用两者时C绑定也被编译进构建（lwext4_sys），但尚未实现经
+//! Ext4Backend使用C后端，挂载时还不能在两个后端之间选择：请求的后端不是Backend::current()时
+//! 返回ENOTSUP。
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use crate::ffi::{EINVAL, EISDIR, ENOENT, ENOTSUP};
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, FsConfig, InodeType,
+    SystemHal,
+};
+
+/// ext4实现后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// 原始 C 实现（lwext4，通过bindgen绑定）
+    Ffi,
+    /// 纯 Rust 实现（lwext4_core）
+    Rust,
+}
+
+impl Backend {
+    /// 当前构建中Ext4Filesystem所绑定的后端（启用use-rust时总是纯 Rust 后端）
+    pub const fn current() -> Backend {
+        if cfg!(feature = "use-rust") {
+            Backend::Rust
+        } else {
+            Backend::Ffi
+        }
+    }
+
+    /// 检查该后端是否被编译进当前构建（只有Backend::current()可以挂载）
+    pub const fn is_available(self) -> bool {
+        match self {
+            Backend::Ffi => cfg!(feature = "use-ffi"),
+            Backend::Rust => cfg!(feature = "use-rust"),
+        }
+    }
+
+    /// 后端名称（用于日志和命令行参数）
+    pub const fn name(self) -> &'static str {
+        match self {
+            Backend::Ffi => "ffi",
+            Backend::Rust => "rust",
+        }
+    }
+
+    /// 根据名称解析后端（如内核启动参数中的"ffi"/"rust"）
+    pub fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "ffi" | "c" => Some(Backend::Ffi),
+            "rust" => Some(Backend::Rust),
+            _ => None,
+        }
+    }
+
+    /// 解析Ext4Filesystem挂载时请求的后端（None表示使用当前构建的后端）
+    pub(crate) fn select(requested: Option<Backend>) -> Ext4Result<Backend> {
+        let backend = requested.unwrap_or(Self::current());
+        if !backend.is_available() {
+            return Err(Ext4Error::new(ENOTSUP as _, "backend not compiled in"));
+        }
+        if backend != Self::current() {
+            return Err(Ext4Error::new(ENOTSUP as _, "mounting a non-default backend is not supported"));
+        }
+        Ok(backend)
+    }
+}
+
+/// 按路径查询到的元数据（两个后端都能提供的部分）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendStat {
+    pub ino: u32,            // inode编号
+    pub node_type: InodeType, // 节点类型
+    pub mode: u32,           // 权限位（不含类型）
+    pub nlink: u32,          // 硬链接数量
+    pub size: u64,           // 文件大小（字节）
+}
+
+/// 与后端无关的按路径操作接口
+///
+/// 路径相对于文件系统根目录（开头的'/'可省略）。接口只包含两个后端都支持的操作，
+/// 用于在同一镜像上比较两个后端的行为。
+pub trait Ext4Backend {
+    /// 实际使用的后端
+    fn backend(&self) -> Backend;
+
+    /// 查询路径的元数据
+    fn metadata(&mut self, path: &str) -> Ext4Result<BackendStat>;
+
+    /// 列出目录中的条目名（不含"."和".."，按目录顺序）
+    fn list_dir(&mut self, path: &str) -> Ext4Result<Vec<String>>;
+
+    /// 从offset处读取文件内容，返回读取的字节数
+    fn read_file(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Ext4Result<usize>;
+
+    /// 在offset处写入文件内容（文件不存在时以0o644创建），返回写入的字节数
+    fn write_file(&mut self, path: &str, offset: u64, buf: &[u8]) -> Ext4Result<usize>;
+
+    /// 创建目录（权限0o755）
+    fn create_dir(&mut self, path: &str) -> Ext4Result<()>;
+
+    /// 删除文件
+    fn remove_file(&mut self, path: &str) -> Ext4Result<()>;
+
+    /// 把缓存中的修改写回设备
+    fn flush(&mut self) -> Ext4Result<()>;
+}
+
+/// 按FsConfig::backend挂载文件系统，返回与后端无关的接口
+///
+/// 请求当前构建的后端（或None）时返回Ext4Filesystem，请求其他后端时返回ENOTSUP。
+pub fn open_backend<Hal, Dev>(dev: Dev, config: FsConfig) -> Ext4Result<Box<dyn Ext4Backend>>
+where
+    Hal: SystemHal + 'static,
+    Dev: BlockDevice + 'static,
+{
+    Ok(Box::new(Ext4Filesystem::<Hal, Dev>::new(dev, config)?))
+}
+
+/// 拆分路径为（父目录路径, 最后一个分量）
+fn split_parent(path: &str) -> Ext4Result<(&str, &str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() {
+        return Err(Ext4Error::new(EINVAL as _, "path has no final component"));
+    }
+    Ok((parent, name))
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Backend for Ext4Filesystem<Hal, Dev> {
+    fn backend(&self) -> Backend {
+        Ext4Filesystem::backend(self)
+    }
+
+    fn metadata(&mut self, path: &str) -> Ext4Result<BackendStat> {
+        let ino = self.resolve_path(path)?;
+        let attr = &self.stat_many(&[ino])?[0];
+        Ok(BackendStat {
+            ino,
+            node_type: attr.node_type,
+            mode: attr.mode & 0o7777,
+            nlink: attr.nlink as u32,
+            size: attr.size,
+        })
+    }
+
+    fn list_dir(&mut self, path: &str) -> Ext4Result<Vec<String>> {
+        let dir = self.resolve_path(path)?;
+        let mut reader = self.read_dir(dir, 0)?;
+        let mut names = Vec::new();
+        while let Some(entry) = reader.current() {
+            let name = entry.name();
+            if name != b"." && name != b".." {
+                names.push(String::from_utf8_lossy(name).into_owned());
+            }
+            reader.step()?;
+        }
+        Ok(names)
+    }
+
+    fn read_file(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let ino = self.resolve_path(path)?;
+        self.read_at(ino, buf, offset)
+    }
+
+    fn write_file(&mut self, path: &str, offset: u64, buf: &[u8]) -> Ext4Result<usize> {
+        let ino = match self.resolve_path(path) {
+            Ok(ino) => ino,
+            Err(err) if err.errno() == ENOENT as _ => {
+                let (parent, name) = split_parent(path)?;
+                let parent = self.resolve_path(parent)?;
+                self.create(parent, name, InodeType::RegularFile, 0o644)?
+            }
+            Err(err) => return Err(err),
+        };
+        self.write_at(ino, buf, offset)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Ext4Result<()> {
+        let (parent, name) = split_parent(path)?;
+        let parent = self.resolve_path(parent)?;
+        self.create(parent, name, InodeType::Directory, 0o755)?;
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &str) -> Ext4Result<()> {
+        if self.metadata(path)?.node_type == InodeType::Directory {
+            return Err(Ext4Error::new(EISDIR as _, "remove_file on a directory"));
+        }
+        let (parent, name) = split_parent(path)?;
+        let parent = self.resolve_path(parent)?;
+        self.unlink(parent, name)
+    }
+
+    fn flush(&mut self) -> Ext4Result<()> {
+        Ext4Filesystem::flush(self)
+    }
+}
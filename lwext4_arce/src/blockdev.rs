@@ -5,32 +5,94 @@ use core::{
     mem, ptr, slice,
 };
 
-use crate::{error::Context, ffi::*, Ext4Result};
-use alloc::boxed::Box;
-// use lwext4_core::*;
-/// 设备的物理块大小（固定为512字节，与ext4规范一致）
-pub const EXT4_DEV_BSIZE: usize = 512;
-
-/// 块设备接口，定义了块设备的基本操作
-/// 这些是暴露给使用本crate的项目的接口， 如在arceos中， 需要有一个实现了blockdevice trait的bd结构体， 在其中调用arceos块设备驱动的方法与磁盘交互，进行磁盘块的读写
-/// ext4filesystem对象需要持有一个fs和一个bd（block device） ，当需要从磁盘读取数据时就会调用bd的blockdevice trait中的方法读写磁盘
-/// 可以知道，本crate的使用方只需为...TODO
-pub trait BlockDevice {
-    /// 向设备写入块（从block_id开始，写入buf中的数据）
-    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize>;
-
-    /// 从设备读取块（从block_id开始，读取到buf中）
-    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize>;
-
-    /// 获取设备的总块数
-    fn num_blocks(&self) -> Ext4Result<u64>;
+use crate::{error::Context, ffi::*, ErrorBehavior, Ext4Error, Ext4Result};
+use alloc::{
+    alloc::{Layout, alloc_zeroed, dealloc},
+    boxed::Box,
+};
+/// 设备物理块大小的默认值（512字节；4Kn等设备通过BlockDevice::block_size声明实际大小）
+pub use lwext4_core::EXT4_DEV_BSIZE;
+
+/// 块设备接口，定义了块设备的基本操作（与lwext4_core是同一个trait）
+///
+/// 使用本crate的项目（如arceos）为块设备驱动实现该trait，Ext4Filesystem持有设备并在需要时
+/// 通过它读写磁盘。同一个实现也可直接传给lwext4_core中以块设备为参数的接口。
+pub use lwext4_core::BlockDevice;
+
+/// 旧的适配器：BlockDevice合并为一个trait后不再需要，设备可以直接传给lwext4_core的接口
+#[deprecated(note = "BlockDevice is shared with lwext4_core; pass the device directly")]
+pub struct CoreDevice<Dev: BlockDevice>(pub Dev);
+
+#[allow(deprecated)]
+impl<Dev: BlockDevice> CoreDevice<Dev> {
+    /// 取回底层设备
+    pub fn into_inner(self) -> Dev {
+        self.0
+    }
+}
+
+#[allow(deprecated)]
+impl<Dev: BlockDevice> BlockDevice for CoreDevice<Dev> {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        self.0.write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        self.0.read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        self.0.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.0.block_size()
+    }
+
+    fn alignment(&self) -> usize {
+        self.0.alignment()
+    }
+}
+
+/// 按指定对齐分配的零初始化缓冲区
+struct AlignedBuf {
+    ptr: ptr::NonNull<u8>,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    fn new(len: usize, align: usize) -> Ext4Result<Self> {
+        let layout = Layout::from_size_align(len, align)
+            .map_err(|_| Ext4Error::new(EINVAL as _, "invalid buffer layout"))?;
+        let ptr = ptr::NonNull::new(unsafe { alloc_zeroed(layout) })
+            .ok_or_else(|| Ext4Error::new(ENOMEM as _, "failed to allocate block buffer"))?;
+        Ok(Self { ptr, layout })
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/// C接口回调中使用的设备状态（ext4_blockdev_iface::p_user指向它）
+struct DevState<Dev> {
+    dev: Dev,            // 底层块设备实例
+    failed: Option<u64>, // 最近一次读写失败的文件系统块号（由Ext4Filesystem附加到返回的错误上）
+    error_ro: bool,      // 写入出错后已按errors=remount-ro（或设备返回EROFS）转为只读
+    write_panic: Option<Ext4Error>, // errors=panic时的写入错误，回调返回后由Ext4Filesystem触发panic
 }
 
 /// 资源守卫：管理块设备相关资源的生命周期（确保安全释放）
 #[allow(dead_code)]
 struct ResourceGuard<Dev> {
-    dev: Box<Dev>,                             // 底层块设备实例
-    block_buf: Box<[u8; EXT4_DEV_BSIZE]>,      // 块缓冲区
+    dev: Box<DevState<Dev>>,                   // 底层块设备实例
+    block_buf: AlignedBuf,                     // 块缓冲区（一个物理块）
     block_cache_buf: Box<ext4_bcache>,         // 块缓存
     block_dev_iface: Box<ext4_blockdev_iface>, // 块设备接口（C兼容）
 }
@@ -44,10 +106,18 @@ pub struct Ext4BlockDevice<Dev: BlockDevice> {
 impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
     /// 创建新的Ext4BlockDevice实例
     pub fn new(dev: Dev) -> Ext4Result<Self> {
-        let mut dev = Box::new(dev); // 包装底层设备
+        let mut dev = Box::new(DevState { dev, failed: None, error_ro: false, write_panic: None }); // 包装底层设备
+        let ph_bsize = dev.dev.block_size();
+        if !ph_bsize.is_power_of_two() {
+            return Err(Ext4Error::new(EINVAL as _, "device block size is not a power of two"));
+        }
+        let align = dev.dev.alignment();
+        if !align.is_power_of_two() || align > u32::MAX as usize {
+            return Err(Ext4Error::new(EINVAL as _, "device alignment is not a power of two"));
+        }
 
-        // 初始化块缓冲区（用于单个块的读写）
-        let mut block_buf = Box::new([0u8; EXT4_DEV_BSIZE]);
+        // 初始化块缓冲区（用于不对齐部分的读写，按设备要求对齐）
+        let mut block_buf = AlignedBuf::new(ph_bsize, align.max(8))?;
         // 初始化块设备接口（C函数指针）
         let mut block_dev_iface = Box::new(ext4_blockdev_iface {
             open: Some(Self::dev_open),                    // 打开设备
@@ -56,13 +126,15 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
             close: Some(Self::dev_close),                  // 关闭设备
             lock: None,                                    // 未实现锁定
             unlock: None,                                  // 未实现解锁
-            ph_bsize: EXT4_DEV_BSIZE as u32,               // 物理块大小
+            ph_bsize: ph_bsize as u32,                     // 物理块大小
             ph_bcnt: 0,                                    // 总块数（后续初始化）
             ph_bbuf: block_buf.as_mut_ptr(),               // 块缓冲区指针
             ph_refctr: 0,                                  // 引用计数
             bread_ctr: 0,                                  // 读计数
             bwrite_ctr: 0,                                 // 写计数
             p_user: dev.as_mut() as *mut _ as *mut c_void, // 底层设备指针
+            #[cfg(feature = "use-rust")]
+            ph_align: align as u32,                        // 缓冲区对齐要求
         });
 
         // 初始化块缓存
@@ -78,7 +150,7 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
             cache_write_back: 0,             // 缓存写回模式
             fs: ptr::null_mut(),             // 关联的文件系统（后续设置）
             journal: ptr::null_mut(),        // 日志（未使用）
-            ph_bsize: EXT4_DEV_BSIZE as u32, // 物理块大小
+            ph_bsize: ph_bsize as u32,       // 物理块大小
             ph_bcnt: 0,                      // 物理块数（后续设置）
         });
 
@@ -104,27 +176,84 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
         })
     }
 
+    /// 重新查询设备总块数，变化时返回(原块数, 新块数)（不修改分区信息，见set_capacity）
+    pub(crate) fn probe_capacity(&self) -> Ext4Result<Option<(u64, u64)>> {
+        let bdif = unsafe { &*self.inner.bdif };
+        let blocks = self._guard.dev.dev.num_blocks()?;
+        let old = bdif.ph_bcnt;
+        Ok((blocks != old).then_some((old, blocks)))
+    }
+
+    /// 按新的设备总块数更新分区信息
+    ///
+    /// 只更新块设备本身，块缓存由调用方处理。设备字节数超出u64时返回EINVAL，不做任何改变。
+    pub(crate) fn set_capacity(&mut self, blocks: u64) -> Ext4Result {
+        let bdev = self.inner.as_mut();
+        let bdif = unsafe { &mut *bdev.bdif };
+        let part_size = blocks
+            .checked_mul(bdif.ph_bsize as u64)
+            .ok_or_else(|| Ext4Error::new(EINVAL as _, "device size overflows u64"))?;
+        bdif.ph_bcnt = blocks;
+        bdev.part_size = part_size;
+        if bdev.lg_bsize != 0 {
+            bdev.lg_bcnt = bdev.part_size / bdev.lg_bsize as u64;
+        }
+        Ok(())
+    }
+
+    /// 取出并清除最近一次读写失败的文件系统块号
+    pub(crate) fn take_failed_block(&mut self) -> Option<u64> {
+        self._guard.dev.failed.take()
+    }
+
+    /// errors=panic的文件系统写入出错时panic
+    ///
+    /// C接口回调中不能panic（unwind不能穿过extern "C"函数），回调只记录错误并返回EIO，
+    /// 由Ext4Filesystem在操作返回后调用这里。
+    pub(crate) fn panic_on_write_error(&mut self) {
+        if let Some(err) = self._guard.dev.write_panic.take() {
+            panic!("ext4 write error: {err:?}");
+        }
+    }
+
+    /// 是否因写入出错而转为只读（区别于挂载时即为只读）
+    pub(crate) fn is_error_read_only(&self) -> bool {
+        self._guard.dev.error_ro
+    }
+
     /// 从C接口中解析设备相关字段（辅助函数）
     unsafe fn dev_read_fields<'a>(
         bdev: *mut ext4_blockdev,
     ) -> (
         &'a mut ext4_blockdev,
         &'a mut ext4_blockdev_iface,
-        &'a mut Dev,
+        &'a mut DevState<Dev>,
     ) {
         let bdev = unsafe { &mut *bdev };
         let bdif = unsafe { &mut *bdev.bdif };
-        let dev = unsafe { &mut *(bdif.p_user as *mut Dev) };
-        (bdev, bdif, dev)
+        let state = unsafe { &mut *(bdif.p_user as *mut DevState<Dev>) };
+        (bdev, bdif, state)
+    }
+
+    /// 物理块号对应的文件系统块号（挂载前逻辑块大小未知时为物理块号）
+    ///
+    /// 从blk_id开始的len字节超出u64字节偏移时返回None。
+    fn fs_block(bdev: &ext4_blockdev, blk_id: u64, len: usize) -> Option<u64> {
+        let offset = blk_id.checked_mul(bdev.ph_bsize as u64)?;
+        offset.checked_add(len as u64)?;
+        Some(match bdev.lg_bsize {
+            0 => blk_id,
+            lg_bsize => offset.saturating_sub(bdev.part_offset) / lg_bsize as u64,
+        })
     }
 
     /// C接口：打开设备（初始化块数）
     unsafe extern "C" fn dev_open(bdev: *mut ext4_blockdev) -> c_int {
         debug!("open ext4 block device");
-        let (bdev, bdif, dev) = unsafe { Self::dev_read_fields(bdev) };
+        let (bdev, bdif, state) = unsafe { Self::dev_read_fields(bdev) };
 
         // 获取设备总块数
-        bdif.ph_bcnt = match dev.num_blocks() {
+        bdif.ph_bcnt = match state.dev.num_blocks() {
             Ok(cur) => cur,
             Err(err) => {
                 error!("num_blocks failed: {err:?}");
@@ -132,12 +261,21 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
             }
         };
 
-        // 设置分区信息
+        // 设置分区信息（设备字节数超出u64时拒绝打开）
+        let Some(part_size) = bdif.ph_bcnt.checked_mul(bdif.ph_bsize as u64) else {
+            error!("device size overflows u64: {} blocks", bdif.ph_bcnt);
+            return EINVAL as _;
+        };
         bdev.part_offset = 0;
-        bdev.part_size = bdif.ph_bcnt * bdif.ph_bsize as u64;
+        bdev.part_size = part_size;
         EOK as _ // 成功
     }
 
+    /// blk_cnt个物理块的总字节数，溢出时返回None
+    fn buf_len(ph_bsize: u32, blk_cnt: u32) -> Option<usize> {
+        usize::try_from(ph_bsize).ok()?.checked_mul(usize::try_from(blk_cnt).ok()?)
+    }
+
     /// C接口：读块（从设备读取指定块）
     unsafe extern "C" fn dev_bread(
         bdev: *mut ext4_blockdev,
@@ -150,14 +288,21 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
             return EOK as _;
         }
 
-        let (_bdev, bdif, dev) = unsafe { Self::dev_read_fields(bdev) };
-        // 计算读取的总字节数
-        let buf_len = (bdif.ph_bsize * blk_cnt) as usize;
+        let (bdev, bdif, state) = unsafe { Self::dev_read_fields(bdev) };
+        // 计算读取的总字节数（32位目标上可能超出地址空间）
+        let Some(buf_len) = Self::buf_len(bdif.ph_bsize, blk_cnt) else {
+            return EINVAL as _;
+        };
+        // 请求的字节范围超出u64时拒绝
+        let Some(fs_block) = Self::fs_block(bdev, blk_id, buf_len) else {
+            return EINVAL as _;
+        };
         // 转换为Rust切片
         let buffer = unsafe { slice::from_raw_parts_mut(buf as *mut u8, buf_len) };
         // 调用底层设备的读方法
-        if let Err(err) = dev.read_blocks(blk_id, buffer) {
+        if let Err(err) = state.dev.read_blocks(blk_id, buffer) {
             error!("read_blocks failed: {err:?}");
+            state.failed = Some(fs_block);
             return EIO as _;
         }
 
@@ -176,20 +321,56 @@ impl<Dev: BlockDevice> Ext4BlockDevice<Dev> {
             return EOK as _;
         }
 
-        let (_bdev, bdif, dev) = unsafe { Self::dev_read_fields(bdev) };
-        // 计算写入的总字节数
-        let buf_len = (bdif.ph_bsize * blk_cnt) as usize;
+        let (bdev, bdif, state) = unsafe { Self::dev_read_fields(bdev) };
+        // 写入出错转为只读后，进行中的操作剩余的写入和缓存中的脏块在这里拒绝
+        if state.error_ro {
+            warn!("write to block {blk_id} after switching to read-only");
+            return EROFS as _;
+        }
+        // 只读挂载时上层已拒绝全部修改，出现写入说明有遗漏的路径
+        let read_only = !bdev.fs.is_null() && unsafe { (*bdev.fs).read_only };
+        debug_assert!(!read_only, "write to block {blk_id} on a read-only mount");
+        if read_only {
+            return EROFS as _;
+        }
+        // 计算写入的总字节数（32位目标上可能超出地址空间）
+        let Some(buf_len) = Self::buf_len(bdif.ph_bsize, blk_cnt) else {
+            return EINVAL as _;
+        };
+        // 请求的字节范围超出u64时拒绝
+        let Some(fs_block) = Self::fs_block(bdev, blk_id, buf_len) else {
+            return EINVAL as _;
+        };
         // 转换为Rust切片
         let buffer = unsafe { slice::from_raw_parts(buf as *const u8, buf_len) };
         // 调用底层设备的写方法
-        if let Err(err) = dev.write_blocks(blk_id, buffer) {
+        if let Err(err) = state.dev.write_blocks(blk_id, buffer) {
             error!("write_blocks failed: {err:?}");
-            return EIO as _;
+            state.failed = Some(fs_block);
+            let errno = if err.errno() == EROFS as _ { EROFS } else { EIO };
+            if !bdev.fs.is_null() {
+                unsafe { Self::handle_write_error(state, &mut *bdev.fs, err) };
+            }
+            return errno as _;
         }
 
         EOK as _
     }
 
+    /// 写入失败时按errors=处理方式处理；设备本身变为只读（返回EROFS）时总是切换为只读
+    ///
+    /// errors=panic时只记录错误，见panic_on_write_error。
+    fn handle_write_error(state: &mut DevState<Dev>, fs: &mut ext4_fs, err: Ext4Error) {
+        let behavior = ErrorBehavior::from_raw(u16::from_le(fs.sb.errors));
+        if err.errno() == EROFS as _ || behavior == ErrorBehavior::RemountRo {
+            error!("write error, switching filesystem to read-only");
+            fs.read_only = true;
+            state.error_ro = true;
+        } else if behavior == ErrorBehavior::Panic {
+            state.write_panic = Some(err);
+        }
+    }
+
     /// C接口：关闭设备
     unsafe extern "C" fn dev_close(_bdev: *mut ext4_blockdev) -> c_int {
         debug!("close ext4 block device");
@@ -0,0 +1,248 @@
+//! 压缩镜像块设备：直接挂载按块分段压缩的镜像（如OTA升级包），只解压实际读取的部分。
+//!
+//! 镜像被切分为固定大小的段，每段独立压缩，文件头之后是段索引表，因此可以随机访问。
+//! 压缩算法由使用方通过Decompressor提供（如zstd），本模块不依赖任何压缩库。
+//! 设备是只读的：挂载时需设置FsConfig::read_only，或在其上叠加OverlayDevice以接收写入。
+//!
+//! 镜像格式（所有整数为小端序）：
+//! - 文件头（32字节）：魔数"LWX4CIMG"、版本(u32)、段大小(u32)、镜像原始长度(u64)、段数(u32)、保留(u32)
+//! - 段索引表：每段16字节，依次为数据偏移(u64)、数据长度(u32)、标志(u32)
+//! - 段数据：标志含CHUNK_FLAG_STORED时为未压缩的原始数据
+
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    BlockDevice, EXT4_DEV_BSIZE, Ext4Error, Ext4Result,
+    ffi::{EINVAL, EIO, EROFS},
+};
+
+/// 文件头魔数
+const CIMG_MAGIC: &[u8; 8] = b"LWX4CIMG";
+/// 当前格式版本
+const CIMG_VERSION: u32 = 1;
+/// 文件头长度
+const CIMG_HEADER_SIZE: usize = 32;
+/// 段索引项长度
+const CIMG_ENTRY_SIZE: usize = 16;
+/// 段标志：数据未压缩（压缩后反而变大的段原样存放）
+const CHUNK_FLAG_STORED: u32 = 1;
+
+/// 可随机读取的镜像来源
+pub trait ImageSource {
+    /// 从offset处读满buf（越过末尾视为错误）
+    fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> Ext4Result<()>;
+}
+
+/// 内存中的镜像（如已整体载入内存的升级包）
+impl<T: AsRef<[u8]> + ?Sized> ImageSource for &T {
+    fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> Ext4Result<()> {
+        let data = (*self).as_ref();
+        let src = usize::try_from(offset)
+            .ok()
+            .and_then(|start| data.get(start..start.checked_add(buf.len())?))
+            .ok_or_else(|| Ext4Error::new(EIO as _, "read past end of image"))?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ImageSource for File {
+    fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> Ext4Result<()> {
+        self.seek(SeekFrom::Start(offset))
+            .and_then(|_| self.read_exact(buf))
+            .map_err(|_| Ext4Error::new(EIO as _, "image read failed"))
+    }
+}
+
+/// 段解压算法
+pub trait Decompressor {
+    /// 将一段压缩数据解压到dst（dst的长度即为该段的原始长度，解压结果长度不符时应返回错误）
+    fn decompress(&mut self, src: &[u8], dst: &mut [u8]) -> Ext4Result<()>;
+}
+
+impl<F: FnMut(&[u8], &mut [u8]) -> Ext4Result<()>> Decompressor for F {
+    fn decompress(&mut self, src: &[u8], dst: &mut [u8]) -> Ext4Result<()> {
+        self(src, dst)
+    }
+}
+
+/// 段索引项
+#[derive(Debug, Clone, Copy)]
+struct ChunkEntry {
+    offset: u64,
+    len: u32,
+    flags: u32,
+}
+
+/// 压缩镜像块设备（只读）
+pub struct CompressedDevice<Src: ImageSource, Dec: Decompressor> {
+    source: Src,
+    decompressor: Dec,
+    chunk_size: usize,
+    image_size: u64,
+    chunks: Vec<ChunkEntry>,
+    cached: Option<usize>, // 当前缓存的段号
+    cache: Vec<u8>,        // 最近解压的一段
+    compressed: Vec<u8>,   // 读取压缩数据的缓冲区
+}
+
+fn bad_image(msg: &'static str) -> Ext4Error {
+    Ext4Error::new(EINVAL as _, msg)
+}
+
+impl<Src: ImageSource, Dec: Decompressor> CompressedDevice<Src, Dec> {
+    /// 读取文件头和段索引表（格式不符时返回EINVAL）
+    pub fn new(mut source: Src, decompressor: Dec) -> Ext4Result<Self> {
+        let mut header = [0u8; CIMG_HEADER_SIZE];
+        source.read_exact_at(0, &mut header)?;
+        let u32_at = |raw: &[u8], off: usize| u32::from_le_bytes(raw[off..off + 4].try_into().unwrap());
+        if &header[..8] != CIMG_MAGIC {
+            return Err(bad_image("bad compressed image magic"));
+        }
+        if u32_at(&header, 8) != CIMG_VERSION {
+            return Err(bad_image("unsupported compressed image version"));
+        }
+        let chunk_size = u32_at(&header, 12) as usize;
+        let image_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let count = u32_at(&header, 24) as usize;
+        if chunk_size == 0
+            || !chunk_size.is_multiple_of(EXT4_DEV_BSIZE)
+            || image_size.div_ceil(chunk_size as u64) != count as u64
+        {
+            return Err(bad_image("bad compressed image geometry"));
+        }
+
+        let mut table = vec![0u8; count * CIMG_ENTRY_SIZE];
+        source.read_exact_at(CIMG_HEADER_SIZE as u64, &mut table)?;
+        let chunks = table
+            .chunks_exact(CIMG_ENTRY_SIZE)
+            .map(|raw| ChunkEntry {
+                offset: u64::from_le_bytes(raw[..8].try_into().unwrap()),
+                len: u32_at(raw, 8),
+                flags: u32_at(raw, 12),
+            })
+            .collect();
+        Ok(Self {
+            source,
+            decompressor,
+            chunk_size,
+            image_size,
+            chunks,
+            cached: None,
+            cache: vec![0; chunk_size],
+            compressed: Vec::new(),
+        })
+    }
+
+    /// 镜像的原始长度
+    pub fn image_size(&self) -> u64 {
+        self.image_size
+    }
+
+    /// 段大小
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// 取回镜像来源和解压器
+    pub fn into_parts(self) -> (Src, Dec) {
+        (self.source, self.decompressor)
+    }
+
+    /// 确保指定段已解压到缓存中，返回该段的有效长度
+    fn load_chunk(&mut self, index: usize) -> Ext4Result<usize> {
+        let start = index as u64 * self.chunk_size as u64;
+        let len = (self.image_size - start).min(self.chunk_size as u64) as usize;
+        if self.cached == Some(index) {
+            return Ok(len);
+        }
+        self.cached = None;
+        let entry = self.chunks[index];
+        let dst = &mut self.cache[..len];
+        if entry.flags & CHUNK_FLAG_STORED != 0 {
+            if entry.len as usize != len {
+                return Err(Ext4Error::new(EIO as _, "bad stored chunk length"));
+            }
+            self.source.read_exact_at(entry.offset, dst)?;
+        } else {
+            self.compressed.resize(entry.len as usize, 0);
+            self.source.read_exact_at(entry.offset, &mut self.compressed)?;
+            self.decompressor.decompress(&self.compressed, dst)?;
+        }
+        self.cached = Some(index);
+        Ok(len)
+    }
+}
+
+impl<Src: ImageSource, Dec: Decompressor> BlockDevice for CompressedDevice<Src, Dec> {
+    fn write_blocks(&mut self, _block_id: u64, _buf: &[u8]) -> Ext4Result<usize> {
+        Err(Ext4Error::new(EROFS as _, "compressed image is read-only"))
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let mut pos = block_id * EXT4_DEV_BSIZE as u64;
+        if pos + buf.len() as u64 > self.image_size {
+            return Err(Ext4Error::new(EIO as _, "read past end of image"));
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let index = (pos / self.chunk_size as u64) as usize;
+            let chunk_len = self.load_chunk(index)?;
+            let in_chunk = (pos % self.chunk_size as u64) as usize;
+            let len = (chunk_len - in_chunk).min(buf.len() - done);
+            buf[done..done + len].copy_from_slice(&self.cache[in_chunk..in_chunk + len]);
+            done += len;
+            pos += len as u64;
+        }
+        Ok(buf.len())
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        Ok(self.image_size / EXT4_DEV_BSIZE as u64)
+    }
+}
+
+/// 将原始镜像编码为压缩镜像格式
+///
+/// compress返回一段数据的压缩结果；结果不比原始数据短时该段原样存放。
+pub fn encode_compressed_image(
+    image: &[u8],
+    chunk_size: u32,
+    mut compress: impl FnMut(&[u8]) -> Vec<u8>,
+) -> Ext4Result<Vec<u8>> {
+    if chunk_size == 0 || !(chunk_size as usize).is_multiple_of(EXT4_DEV_BSIZE) {
+        return Err(bad_image("chunk size is not a multiple of the block size"));
+    }
+    let count = image.len().div_ceil(chunk_size as usize);
+    let mut out = Vec::with_capacity(CIMG_HEADER_SIZE + count * CIMG_ENTRY_SIZE);
+    out.extend_from_slice(CIMG_MAGIC);
+    out.extend_from_slice(&CIMG_VERSION.to_le_bytes());
+    out.extend_from_slice(&chunk_size.to_le_bytes());
+    out.extend_from_slice(&(image.len() as u64).to_le_bytes());
+    out.extend_from_slice(&(count as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.resize(CIMG_HEADER_SIZE + count * CIMG_ENTRY_SIZE, 0);
+
+    for (i, chunk) in image.chunks(chunk_size as usize).enumerate() {
+        let packed = compress(chunk);
+        let (data, flags) = if packed.len() < chunk.len() {
+            (&packed[..], 0)
+        } else {
+            (chunk, CHUNK_FLAG_STORED)
+        };
+        let entry = CIMG_HEADER_SIZE + i * CIMG_ENTRY_SIZE;
+        let offset = out.len() as u64;
+        out[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+        out[entry + 8..entry + 12].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        out[entry + 12..entry + 16].copy_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
@@ -0,0 +1,351 @@
+//! 文件碎片整理模块（类似e4defrag的move extent），将文件数据复制到新分配的连续块中，
+//! 再整体交换两个inode的块映射；另提供按块组统计空闲块段的报告（类似e2freefrag）。
+//!
+//! 块映射的交换由lwext4_core的ext4_extent_swap_trees完成。
+
+use core::slice;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, InodeRef, InodeType, SystemHal,
+    DataWriteGuard, WritebackGuard,
+    error::{Context, ErrorContext},
+    ffi::*,
+    util::get_block_size,
+};
+
+/// 碎片整理结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefragStats {
+    /// 整理前的碎片数（物理上连续的块段数）
+    pub extents_before: u32,
+    /// 整理后的碎片数
+    pub extents_after: u32,
+    /// 移动的数据块数（未移动时为0）
+    pub blocks_moved: u64,
+}
+
+/// 空闲块段直方图的桶数
+pub const FREE_EXTENT_BUCKETS: usize = 32;
+
+/// 空闲块段大小直方图，第i个桶统计长度在[2^i, 2^(i+1))块之间的空闲段
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FreeExtentHistogram {
+    /// 各桶的空闲段数
+    pub extents: [u32; FREE_EXTENT_BUCKETS],
+    /// 各桶的空闲块数
+    pub blocks: [u64; FREE_EXTENT_BUCKETS],
+}
+
+impl FreeExtentHistogram {
+    /// 记入一个长度为len块的空闲段
+    fn add(&mut self, len: u32) {
+        let bucket = len.ilog2() as usize;
+        self.extents[bucket] += 1;
+        self.blocks[bucket] += len as u64;
+    }
+
+    /// 累加另一个直方图
+    fn merge(&mut self, other: &Self) {
+        for i in 0..FREE_EXTENT_BUCKETS {
+            self.extents[i] += other.extents[i];
+            self.blocks[i] += other.blocks[i];
+        }
+    }
+}
+
+/// 单个块组的空闲空间统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupFreeSpace {
+    /// 块组号
+    pub group: u32,
+    /// 空闲块数
+    pub free_blocks: u64,
+    /// 空闲段数
+    pub free_extents: u32,
+    /// 最短空闲段的块数（没有空闲块时为0）
+    pub min_extent: u32,
+    /// 最长空闲段的块数
+    pub max_extent: u32,
+    /// 空闲段大小直方图
+    pub histogram: FreeExtentHistogram,
+}
+
+/// 空闲空间碎片报告
+///
+/// 空闲段按块组统计，跨越块组边界的空闲段计为两段。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FreeSpaceReport {
+    /// 块大小（字节）
+    pub block_size: u32,
+    /// 总块数
+    pub total_blocks: u64,
+    /// 空闲块数
+    pub free_blocks: u64,
+    /// 空闲段数
+    pub free_extents: u64,
+    /// 最短空闲段的块数（没有空闲块时为0）
+    pub min_extent: u32,
+    /// 最长空闲段的块数
+    pub max_extent: u32,
+    /// 全部块组合计的空闲段大小直方图
+    pub histogram: FreeExtentHistogram,
+    /// 各块组的统计
+    pub groups: Vec<GroupFreeSpace>,
+}
+
+impl FreeSpaceReport {
+    /// 空闲段的平均块数（没有空闲块时为None）
+    pub fn avg_extent(&self) -> Option<u64> {
+        self.free_blocks.checked_div(self.free_extents)
+    }
+}
+
+impl GroupFreeSpace {
+    /// 记入一个长度为len块的空闲段
+    fn add(&mut self, len: u32) {
+        if self.free_extents == 0 || len < self.min_extent {
+            self.min_extent = len;
+        }
+        self.free_blocks += len as u64;
+        self.free_extents += 1;
+        self.max_extent = self.max_extent.max(len);
+        self.histogram.add(len);
+    }
+}
+
+/// 统计块位图中前nbits位里的空闲段
+fn scan_bitmap(bitmap: &[u8], nbits: u32, group: &mut GroupFreeSpace) {
+    let mut run = 0u32;
+    let mut bit = 0u32;
+    while bit < nbits {
+        let byte = bitmap[(bit / 8) as usize];
+        // 整字节全空闲或全占用时一次跳过
+        if bit.is_multiple_of(8) && bit + 8 <= nbits && (byte == 0 || byte == 0xff) {
+            if byte == 0 {
+                run += 8;
+            } else if run > 0 {
+                group.add(run);
+                run = 0;
+            }
+            bit += 8;
+            continue;
+        }
+        if byte & (1 << (bit % 8)) == 0 {
+            run += 1;
+        } else if run > 0 {
+            group.add(run);
+            run = 0;
+        }
+        bit += 1;
+    }
+    if run > 0 {
+        group.add(run);
+    }
+}
+
+/// 文件中物理上连续的块段（逻辑块，物理块，块数）
+type Run = (u32, u64, u32);
+
+/// 列出文件的全部数据块段，逻辑和物理上都相邻的extent合并为一段
+fn file_runs<Hal: SystemHal>(inode: &mut InodeRef<Hal>) -> Ext4Result<Vec<Run>> {
+    let block_size = get_block_size(inode.superblock()) as u64;
+    let blocks = inode.size().div_ceil(block_size).min(u32::MAX as u64) as u32;
+    let mut runs: Vec<Run> = Vec::new();
+    let mut iblock = 0;
+    while iblock < blocks {
+        let (mut pblk, mut count) = (0u64, 0u32);
+        unsafe {
+            ext4_extent_get_blocks(
+                inode.inner.as_mut(),
+                iblock,
+                blocks - iblock,
+                &mut pblk,
+                false,
+                &mut count,
+            )
+        }
+        .with_context(|| {
+            ErrorContext::new("ext4_extent_get_blocks")
+                .ino(inode.ino())
+                .block(iblock as _)
+        })?;
+        if pblk == 0 {
+            // 空洞
+            iblock += 1;
+            continue;
+        }
+        match runs.last_mut() {
+            Some((lblk, start, len)) if *lblk + *len == iblock && *start + *len as u64 == pblk => {
+                *len += count
+            }
+            _ => runs.push((iblock, pblk, count)),
+        }
+        iblock += count;
+    }
+    Ok(runs)
+}
+
+/// 释放用于整理的临时inode及其数据块
+fn release_donor<Hal: SystemHal>(mut donor: InodeRef<Hal>) -> Ext4Result<()> {
+    donor.truncate(0)?;
+    unsafe {
+        ext4_inode_set_del_time(donor.inner.inode, u32::MAX);
+        donor.mark_dirty();
+        ext4_fs_free_inode(donor.inner.as_mut()).context("ext4_fs_free_inode")
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 整理普通文件的碎片
+    ///
+    /// 先将数据逐块复制到一个未链接的临时inode中（分配器沿上一块顺序分配，得到尽量连续的
+    /// extent），再交换两个inode的extent树，最后释放持有旧数据块的临时inode。空洞保持为空洞，
+    /// 文件大小、时间戳等属性不变。复制结果的碎片数没有减少时放弃交换，文件保持原样。
+    /// 整理过程中需要与文件数据量相当的空闲空间。fs-verity文件返回NotPermitted。
+    /// 每复制一个块之前检查一次中断（见on_interrupt），中断时释放临时inode，文件保持原样。
+    pub fn defragment(&mut self, path: &str) -> Ext4Result<DefragStats> {
+        self.ensure_writable()?;
+        let ino = self.resolve_path(path)?;
+        let mut inode = self.inode_ref(ino)?;
+        if inode.inode_type() != InodeType::RegularFile {
+            return Err(Ext4Error::new(EINVAL as _, "not a regular file"));
+        }
+        if !unsafe { ext4_inode_has_flag(inode.inner.inode, EXT4_INODE_FLAG_EXTENTS) } {
+            return Err(Ext4Error::new(ENOTSUP as _, "file does not use extents"));
+        }
+        // 只复制i_size以内的数据，会丢失文件末尾之后的verity元数据
+        inode.check_writable("defragment")?;
+
+        let runs = file_runs(&mut inode)?;
+        let mut stats = DefragStats {
+            extents_before: runs.len() as _,
+            extents_after: runs.len() as _,
+            blocks_moved: 0,
+        };
+        if runs.len() <= 1 {
+            return Ok(stats);
+        }
+
+        let _guard = WritebackGuard::new(unsafe { (*inode.inner.fs).bdev });
+        // 新extent在数据复制完成之后才回写
+        let _data = DataWriteGuard::new(unsafe { (*inode.inner.fs).bdev });
+        let mut donor = self.alloc_inode(InodeType::RegularFile)?;
+        // 临时inode与文件等长，释放时截断即可回收其全部数据块
+        unsafe { ext4_inode_set_size(donor.inner.inode, inode.size()) };
+        let block_size = get_block_size(inode.superblock()) as u64;
+        let mut buf = vec![0u8; block_size as usize];
+        let copied = runs.iter().try_for_each(|&(lblk, pblk, len)| {
+            for i in 0..len {
+                self.check_interrupt("defragment")?;
+                let mut fblock = 0u64;
+                unsafe {
+                    let bdev = (*inode.inner.fs).bdev;
+                    ext4_block_readbytes(
+                        bdev,
+                        (pblk + i as u64) * block_size,
+                        buf.as_mut_ptr() as _,
+                        buf.len() as _,
+                    )
+                    .context("ext4_block_readbytes")?;
+                    ext4_fs_init_inode_dblk_idx(donor.inner.as_mut(), lblk + i, &mut fblock)
+                        .with_context(|| {
+                            ErrorContext::new("ext4_fs_init_inode_dblk_idx")
+                                .ino(donor.ino())
+                                .block((lblk + i) as _)
+                        })?;
+                    ext4_block_writebytes(
+                        bdev,
+                        fblock * block_size,
+                        buf.as_ptr() as _,
+                        buf.len() as _,
+                    )
+                    .context("ext4_block_writebytes")?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = copied {
+            release_donor(donor)?;
+            return Err(err);
+        }
+
+        let new_runs = file_runs(&mut donor)?;
+        if new_runs.len() >= runs.len() {
+            // 空闲空间同样碎片化，交换没有收益
+            release_donor(donor)?;
+            return Ok(stats);
+        }
+
+        unsafe {
+            ext4_extent_swap_trees(inode.inner.as_mut(), donor.inner.as_mut())
+                .with_context(|| ErrorContext::new("ext4_extent_swap_trees").ino(ino))?;
+        }
+        release_donor(donor)?;
+
+        stats.extents_after = new_runs.len() as _;
+        stats.blocks_moved = runs.iter().map(|&(_, _, len)| len as u64).sum();
+        Ok(stats)
+    }
+
+    /// 扫描全部块组的块位图，统计空闲块段的分布（类似e2freefrag）
+    ///
+    /// 位图尚未初始化（BLOCK_UNINIT）的块组不读取位图，其空闲块按一个空闲段计。
+    /// 只读操作，不会初始化这类块组的位图。
+    pub fn free_space_report(&mut self) -> Ext4Result<FreeSpaceReport> {
+        let fs: *mut ext4_fs = self.inner.as_mut();
+        let sb = unsafe { &(*fs).sb };
+        let block_size = get_block_size(sb);
+        let mut report = FreeSpaceReport {
+            block_size,
+            total_blocks: ext4_sb_get_blocks_cnt(sb),
+            min_extent: u32::MAX,
+            ..Default::default()
+        };
+
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut group = GroupFreeSpace {
+                group: bgid,
+                ..Default::default()
+            };
+            let mut bg_ref = ext4_block_group_ref::new();
+            unsafe {
+                ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref)
+                    .context("ext4_fs_get_block_group_ref")?;
+                let bg = &*bg_ref.block_group;
+                if ext4_bg_has_flag(bg, EXT4_BLOCK_GROUP_BLOCK_UNINIT) {
+                    let free = ext4_bg_get_free_blocks_count(bg, sb);
+                    if free > 0 {
+                        group.add(free);
+                    }
+                } else {
+                    let mut b = ext4_block::new();
+                    let r = ext4_block_get((*fs).bdev, &mut b, ext4_bg_get_block_bitmap(bg, sb));
+                    if r != EOK {
+                        ext4_fs_put_block_group_ref(&mut bg_ref);
+                        return Err(Ext4Error::new(r, "ext4_block_get"));
+                    }
+                    let bitmap = slice::from_raw_parts(b.data, block_size as usize);
+                    let nbits = ext4_blocks_in_group_cnt(sb, bgid);
+                    scan_bitmap(bitmap, nbits, &mut group);
+                    ext4_block_set((*fs).bdev, &mut b);
+                }
+                ext4_fs_put_block_group_ref(&mut bg_ref).context("ext4_fs_put_block_group_ref")?;
+            }
+
+            report.free_blocks += group.free_blocks;
+            report.free_extents += group.free_extents as u64;
+            report.max_extent = report.max_extent.max(group.max_extent);
+            if group.free_extents > 0 {
+                report.min_extent = report.min_extent.min(group.min_extent);
+            }
+            report.histogram.merge(&group.histogram);
+            report.groups.push(group);
+        }
+        if report.free_extents == 0 {
+            report.min_extent = 0;
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,247 @@
+//! 镜像比较模块：比较两个已挂载的文件系统（或同一镜像的两个快照）的目录树，
+//! 报告增删的路径、类型和元数据的差异，以及文件内容、符号链接目标和扩展属性的哈希差异，
+//! 用于验证生成的根文件系统镜像是否可重现。
+//!
+//! 哈希使用CRC32C，只用于报告；是否有差异按实际内容判断（两侧CRC相同的不同内容同样被报告）。
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    BlockDevice, Ext4Filesystem, Ext4Result, FileAttr, InodeType, SystemHal, WalkDir,
+    ffi::{EXT4_INODE_ROOT_INDEX, crc::ext4_crc32c},
+};
+
+/// 读取文件内容时每次读取的字节数
+const DIFF_CHUNK: usize = 64 << 10;
+
+/// 比较选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    pub times: bool,  // 比较修改时间（atime和ctime总是忽略）
+    pub owner: bool,  // 比较属主和属组
+    pub xattrs: bool, // 比较扩展属性
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            times: true,
+            owner: true,
+            xattrs: true,
+        }
+    }
+}
+
+/// 元数据字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MetadataField {
+    Mode,  // 权限位
+    Uid,   // 属主
+    Gid,   // 属组
+    Size,  // 文件大小
+    Nlink, // 硬链接数
+    Rdev,  // 设备号
+    Mtime, // 修改时间（纳秒）
+}
+
+/// 一项差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// 只存在于新镜像
+    Added(InodeType),
+    /// 只存在于旧镜像
+    Removed(InodeType),
+    /// 类型不同（不再比较其他内容）
+    TypeChanged { old: InodeType, new: InodeType },
+    /// 元数据不同
+    Metadata { field: MetadataField, old: u64, new: u64 },
+    /// 文件内容或符号链接目标不同（两侧内容的CRC32C）
+    Content { old: u32, new: u32 },
+    /// 扩展属性不同（两侧按名称排序后的名称和值的CRC32C）
+    Xattrs { old: u32, new: u32 },
+}
+
+/// 某个路径上的差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDiff {
+    pub path: String, // 以'/'开头的路径
+    pub change: Change,
+}
+
+/// 比较两个文件系统的目录树，按路径顺序返回全部差异
+///
+/// 符号链接不跟随，只比较目标。被增删的目录下的每个路径都单独报告。
+pub fn diff_images<H1, D1, H2, D2>(
+    old: &mut Ext4Filesystem<H1, D1>,
+    new: &mut Ext4Filesystem<H2, D2>,
+    opts: &DiffOptions,
+) -> Ext4Result<Vec<PathDiff>>
+where
+    H1: SystemHal,
+    D1: BlockDevice,
+    H2: SystemHal,
+    D2: BlockDevice,
+{
+    let old_tree = collect_tree(old)?;
+    let new_tree = collect_tree(new)?;
+    let mut diffs = Vec::new();
+    let mut push = |path: &str, change| {
+        diffs.push(PathDiff {
+            path: path.into(),
+            change,
+        })
+    };
+
+    let paths: BTreeSet<&String> = old_tree.keys().chain(new_tree.keys()).collect();
+    for path in paths {
+        let (old_ino, new_ino) = match (old_tree.get(path), new_tree.get(path)) {
+            (Some(&(_, ty)), None) => {
+                push(path, Change::Removed(ty));
+                continue;
+            }
+            (None, Some(&(_, ty))) => {
+                push(path, Change::Added(ty));
+                continue;
+            }
+            (Some(&(o, _)), Some(&(n, _))) => (o, n),
+            (None, None) => unreachable!(),
+        };
+
+        let mut oa = FileAttr::default();
+        let mut na = FileAttr::default();
+        old.get_attr(old_ino, &mut oa)?;
+        new.get_attr(new_ino, &mut na)?;
+        if oa.node_type != na.node_type {
+            push(path, Change::TypeChanged { old: oa.node_type, new: na.node_type });
+            continue;
+        }
+
+        let mtime = |attr: &FileAttr| attr.mtime.as_nanos() as u64;
+        let fields = [
+            (MetadataField::Mode, (oa.mode & 0o7777) as u64, (na.mode & 0o7777) as u64, true),
+            (MetadataField::Uid, oa.uid as u64, na.uid as u64, opts.owner),
+            (MetadataField::Gid, oa.gid as u64, na.gid as u64, opts.owner),
+            // 目录大小取决于目录项的排列，不作比较
+            (MetadataField::Size, oa.size, na.size, oa.node_type != InodeType::Directory),
+            (MetadataField::Nlink, oa.nlink, na.nlink, true),
+            (MetadataField::Rdev, oa.rdev, na.rdev, true),
+            (MetadataField::Mtime, mtime(&oa), mtime(&na), opts.times),
+        ];
+        for (field, o, n, enabled) in fields {
+            if enabled && o != n {
+                push(path, Change::Metadata { field, old: o, new: n });
+            }
+        }
+
+        let content = match oa.node_type {
+            InodeType::RegularFile => Some(compare_files(old, old_ino, new, new_ino)?),
+            InodeType::Symlink => {
+                let (o, n) = (old.read_link(old_ino)?, new.read_link(new_ino)?);
+                Some((o == n, crc(!0, &o), crc(!0, &n)))
+            }
+            _ => None,
+        };
+        if let Some((false, o, n)) = content {
+            push(path, Change::Content { old: o, new: n });
+        }
+
+        if opts.xattrs {
+            let o = xattr_bytes(old, old_ino)?;
+            let n = xattr_bytes(new, new_ino)?;
+            if o != n {
+                push(path, Change::Xattrs { old: crc(!0, &o), new: crc(!0, &n) });
+            }
+        }
+    }
+    Ok(diffs)
+}
+
+/// 全部路径到(inode编号, 类型)的映射（包括根目录"/"）
+fn collect_tree<Hal: SystemHal, Dev: BlockDevice>(
+    fs: &mut Ext4Filesystem<Hal, Dev>,
+) -> Ext4Result<BTreeMap<String, (u32, InodeType)>> {
+    let mut tree = BTreeMap::new();
+    tree.insert(String::from("/"), (EXT4_INODE_ROOT_INDEX, InodeType::Directory));
+    for entry in WalkDir::new(fs, "/")? {
+        let entry = entry?;
+        tree.insert(entry.path, (entry.ino, entry.inode_type));
+    }
+    Ok(tree)
+}
+
+/// 分块比较两个文件的内容，返回(是否相同, 旧文件的CRC32C, 新文件的CRC32C)
+fn compare_files<H1, D1, H2, D2>(
+    old: &mut Ext4Filesystem<H1, D1>,
+    old_ino: u32,
+    new: &mut Ext4Filesystem<H2, D2>,
+    new_ino: u32,
+) -> Ext4Result<(bool, u32, u32)>
+where
+    H1: SystemHal,
+    D1: BlockDevice,
+    H2: SystemHal,
+    D2: BlockDevice,
+{
+    let mut old_buf = vec![0u8; DIFF_CHUNK];
+    let mut new_buf = vec![0u8; DIFF_CHUNK];
+    let (mut old_crc, mut new_crc) = (!0, !0);
+    let mut same = true;
+    let mut offset = 0u64;
+    loop {
+        let o = read_full(old, old_ino, &mut old_buf, offset)?;
+        let n = read_full(new, new_ino, &mut new_buf, offset)?;
+        if o == 0 && n == 0 {
+            return Ok((same, old_crc, new_crc));
+        }
+        same &= old_buf[..o] == new_buf[..n];
+        old_crc = crc(old_crc, &old_buf[..o]);
+        new_crc = crc(new_crc, &new_buf[..n]);
+        offset += o.max(n) as u64;
+    }
+}
+
+/// 读满buf，只在文件末尾返回较短的长度
+fn read_full<Hal: SystemHal, Dev: BlockDevice>(
+    fs: &mut Ext4Filesystem<Hal, Dev>,
+    ino: u32,
+    buf: &mut [u8],
+    offset: u64,
+) -> Ext4Result<usize> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = fs.read_at(ino, &mut buf[done..], offset + done as u64)?;
+        if n == 0 {
+            break;
+        }
+        done += n;
+    }
+    Ok(done)
+}
+
+/// 按名称排序的扩展属性序列化结果（名称、0、值长度、值）
+fn xattr_bytes<Hal: SystemHal, Dev: BlockDevice>(
+    fs: &mut Ext4Filesystem<Hal, Dev>,
+    ino: u32,
+) -> Ext4Result<Vec<u8>> {
+    let mut names = fs.list_xattr(ino)?;
+    names.sort_unstable();
+    let mut bytes = Vec::new();
+    for name in names {
+        let value = fs.get_xattr(ino, &name)?.unwrap_or_default();
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&value);
+    }
+    Ok(bytes)
+}
+
+/// 累加CRC32C（初始值为!0）
+fn crc(crc: u32, data: &[u8]) -> u32 {
+    ext4_crc32c(crc, data)
+}
@@ -0,0 +1,72 @@
+//! 文件内容摘要模块：按块流式读取文件并送入调用方提供的摘要算法，不缓冲整个文件，
+//! 适合在内存很小的设备上生成完整性清单。
+//!
+//! 摘要接口与digest crate的`Digest::update`形式相同，sha2等实现只需一行适配。
+
+use alloc::vec;
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, InodeType, SystemHal,
+    error::ErrorContext,
+    ffi::{EINVAL, EISDIR},
+    util::get_block_size,
+};
+
+/// 流式摘要算法
+pub trait ContentDigest {
+    /// 追加一段数据
+    fn update(&mut self, data: &[u8]);
+}
+
+impl<D: ContentDigest + ?Sized> ContentDigest for &mut D {
+    fn update(&mut self, data: &[u8]) {
+        (**self).update(data)
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 把普通文件的内容按顺序送入hasher，返回文件长度
+    ///
+    /// 每次从设备读取一个块（没有预读），除该缓冲区外不占用与文件大小相关的内存；空洞按0送入。
+    /// 需要减少设备读取次数时用hash_file_with_buffer传入更大的缓冲区。
+    /// 路径不跟随符号链接，目录返回EISDIR，其他非普通文件返回EINVAL。
+    pub fn hash_file(&mut self, path: &str, hasher: &mut impl ContentDigest) -> Ext4Result<u64> {
+        let mut buf = vec![0u8; get_block_size(&self.inner.sb) as usize];
+        self.hash_file_with_buffer(path, hasher, &mut buf)
+    }
+
+    /// 与hash_file相同，但使用调用方提供的缓冲区（不分配堆内存）
+    ///
+    /// 每次读取填满整个缓冲区：缓冲区为块大小的整数倍时，其中物理上连续的块在一次设备读取中完成，
+    /// 超出缓冲区的部分不会提前读取。
+    pub fn hash_file_with_buffer(
+        &mut self,
+        path: &str,
+        hasher: &mut impl ContentDigest,
+        buf: &mut [u8],
+    ) -> Ext4Result<u64> {
+        if buf.is_empty() {
+            return Err(Ext4Error::new(EINVAL as _, "empty buffer"));
+        }
+        let ino = self.resolve_path(path)?;
+        let errno = match self.inode_ref(ino)?.inode_type() {
+            InodeType::RegularFile => None,
+            InodeType::Directory => Some(EISDIR),
+            _ => Some(EINVAL),
+        };
+        if let Some(errno) = errno {
+            return Err(Ext4Error::new(errno as _, "not a regular file")
+                .with_context(ErrorContext::new("hash_file").ino(ino)));
+        }
+
+        let mut offset = 0u64;
+        loop {
+            let n = self.read_at(ino, buf, offset)?;
+            if n == 0 {
+                return Ok(offset);
+            }
+            hasher.update(&buf[..n]);
+            offset += n as u64;
+        }
+    }
+}
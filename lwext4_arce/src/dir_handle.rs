@@ -0,0 +1,74 @@
+//! 目录句柄模块：打开目录时解析一次路径，之后的查找、创建、删除和读取都直接
+//! 作用于该目录的inode，适合已经解析过父目录的VFS层。
+
+use crate::{
+    BlockDevice, DirLookupResult, DirPage, DirReader, Ext4Error, Ext4Filesystem, Ext4Result,
+    FileName, InodeType, SystemHal, error::ErrorContext, ffi::ENOTDIR,
+};
+
+/// 已打开的目录
+///
+/// 通过 [`Ext4Filesystem::open_dir`] 或 [`Ext4Filesystem::dir_handle`] 创建，
+/// 持有文件系统的可变借用，各操作等价于以目录inode编号调用文件系统的对应方法。
+pub struct DirHandle<'a, Hal: SystemHal, Dev: BlockDevice> {
+    fs: &'a mut Ext4Filesystem<Hal, Dev>, // 所属文件系统
+    ino: u32,                             // 目录的inode编号
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 按路径打开目录（路径只解析一次），路径不是目录时返回NotADirectory
+    pub fn open_dir(&mut self, path: &str) -> Ext4Result<DirHandle<'_, Hal, Dev>> {
+        let ino = self.resolve_path(path)?;
+        self.dir_handle(ino)
+    }
+
+    /// 由已知的目录inode编号打开目录，inode不是目录时返回NotADirectory
+    pub fn dir_handle(&mut self, ino: u32) -> Ext4Result<DirHandle<'_, Hal, Dev>> {
+        if self.inode_ref(ino)?.inode_type() != InodeType::Directory {
+            return Err(Ext4Error::new(ENOTDIR as _, None)
+                .with_context(ErrorContext::new("open_dir").ino(ino)));
+        }
+        Ok(DirHandle { fs: self, ino })
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> DirHandle<'_, Hal, Dev> {
+    /// 目录的inode编号
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// 在目录中查找指定名称的条目
+    pub fn lookup<'n>(
+        &mut self,
+        name: impl Into<FileName<'n>>,
+    ) -> Ext4Result<DirLookupResult<Hal>> {
+        self.fs.lookup(self.ino, name)
+    }
+
+    /// 在目录中创建新文件/目录，返回新inode编号
+    pub fn create<'n>(
+        &mut self,
+        name: impl Into<FileName<'n>>,
+        ty: InodeType,
+        mode: u32,
+    ) -> Ext4Result<u32> {
+        self.fs.create(self.ino, name, ty, mode)
+    }
+
+    /// 删除目录中的条目
+    pub fn unlink<'n>(&mut self, name: impl Into<FileName<'n>>) -> Ext4Result {
+        self.fs.unlink(self.ino, name)
+    }
+
+    /// 从偏移量开始读取目录条目
+    pub fn read_dir(&mut self, offset: u64) -> Ext4Result<DirReader<Hal>> {
+        self.fs.read_dir(self.ino, offset)
+    }
+
+    /// 分页读取目录：从cookie（首次为0）开始最多读取max_entries个条目
+    pub fn read_dir_from(&mut self, cookie: u64, max_entries: usize) -> Ext4Result<DirPage> {
+        self.fs
+            .read_dir_page(self.ino, cookie, max_entries, &mut |_| true)
+    }
+}
@@ -1,79 +1,73 @@
 //! 错误处理模块，定义了ext4操作的错误类型和辅助方法。
 
-use core::{
-    error::Error,
-    fmt::{Debug, Display},
-};
+use crate::ffi::EOK; // 状态码（与C接口兼容）
 
-use crate::ffi::EOK; // 成功状态码
+/// 错误类型与lwext4_core共用（两种后端相同），BlockDevice实现和调用方直接使用
+pub use lwext4_core::{ErrorContext, Ext4Error, Ext4ErrorKind, Ext4Result};
 
-/// ext4操作的结果类型（成功或错误）
-pub type Ext4Result<T = ()> = Result<T, Ext4Error>;
-
-/// ext4错误类型，包含错误码和上下文信息
-pub struct Ext4Error {
-    pub code: i32, // 错误码（与C接口兼容）
-    pub context: Option<&'static str>, // 错误上下文（可选）
-}
-
-impl Ext4Error {
-    /// 创建新的Ext4Error
-    pub fn new(code: i32, context: impl Into<Option<&'static str>>) -> Self {
-        Ext4Error {
-            code,
-            context: context.into(),
-        }
-    }
-}
-
-/// 从错误码转换为Ext4Error
-impl From<i32> for Ext4Error {
-    fn from(code: i32) -> Self {
-        Ext4Error::new(code, None)
-    }
+/// POSIX错误码（Linux取值），不依赖libc
+///
+/// 供BlockDevice实现构造错误（如`Ext4Error::new(errno::EIO, "read failed")`），
+/// 以及调用方比较[`Ext4Error::errno`]的返回值。与lwext4的ext4_errno.h取值相同，两种后端通用。
+pub mod errno {
+    pub const EPERM: i32 = 1;
+    pub const ENOENT: i32 = 2;
+    pub const EIO: i32 = 5;
+    pub const ENXIO: i32 = 6;
+    pub const ENOMEM: i32 = 12;
+    pub const EBUSY: i32 = 16;
+    pub const EEXIST: i32 = 17;
+    pub const EXDEV: i32 = 18;
+    pub const ENODEV: i32 = 19;
+    pub const ENOTDIR: i32 = 20;
+    pub const EISDIR: i32 = 21;
+    pub const EINVAL: i32 = 22;
+    pub const EFBIG: i32 = 27;
+    pub const ENOSPC: i32 = 28;
+    pub const EROFS: i32 = 30;
+    pub const EMLINK: i32 = 31;
+    pub const ENAMETOOLONG: i32 = 36;
+    pub const ENOTEMPTY: i32 = 39;
+    pub const ENODATA: i32 = 61;
+    pub const ENOTSUP: i32 = 95;
+    pub const ETIMEDOUT: i32 = 110; // 长时间操作超过截止时间（见 Ext4Filesystem::set_deadline）
+    pub const ESTALE: i32 = 116;
+    pub const EUCLEAN: i32 = 117;
+    pub const ECANCELED: i32 = 125; // 长时间操作被中断回调取消（见 Ext4Filesystem::on_interrupt）
+    pub const ENOKEY: i32 = 126;
 }
 
-/// 实现Display trait，用于格式化错误信息
-impl Display for Ext4Error {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        if let Some(context) = self.context {
-            write!(f, "ext4 error {}: {context}", self.code)
-        } else {
-            write!(f, "ext4 error {}", self.code)
-        }
-    }
-}
-
-/// 实现Debug trait，复用Display的实现
-impl Debug for Ext4Error {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        Display::fmt(self, f)
-    }
-}
-
-/// 实现Error trait，使Ext4Error符合标准错误类型
-impl Error for Ext4Error {}
-
 /// 为结果类型添加上下文的 trait
 pub(crate) trait Context<T> {
-    /// 为错误添加上下文信息
+    /// 为错误追加一帧只含操作名的上下文
     fn context(self, context: &'static str) -> Result<T, Ext4Error>;
+
+    /// 为错误追加一帧完整的上下文（仅在出错时构造）
+    fn with_context(self, f: impl FnOnce() -> ErrorContext) -> Result<T, Ext4Error>;
 }
 
 /// 为i32（C函数返回码）实现Context trait
 impl Context<()> for i32 {
     fn context(self, context: &'static str) -> Result<(), Ext4Error> {
+        self.with_context(|| ErrorContext::new(context))
+    }
+
+    fn with_context(self, f: impl FnOnce() -> ErrorContext) -> Result<(), Ext4Error> {
         if self != EOK as _ {
-            Err(Ext4Error::new(self, Some(context)))
+            Err(Ext4Error::new(self, None).with_context(f()))
         } else {
             Ok(())
         }
     }
 }
 
-/// 为Ext4Result实现Context trait（嵌套错误时添加上下文）
+/// 为Ext4Result实现Context trait（嵌套错误时追加外层上下文）
 impl<T> Context<T> for Ext4Result<T> {
     fn context(self, context: &'static str) -> Result<T, Ext4Error> {
-        self.map_err(|e| Ext4Error::new(e.code, Some(context)))
+        self.with_context(|| ErrorContext::new(context))
     }
-}
\ No newline at end of file
+
+    fn with_context(self, f: impl FnOnce() -> ErrorContext) -> Result<T, Ext4Error> {
+        self.map_err(|e| e.with_context(f()))
+    }
+}
@@ -1,21 +1,39 @@
 //! 文件系统核心逻辑模块，实现ext4文件系统的初始化、inode管理及文件操作。
 
-use core::{marker::PhantomData, mem, time::Duration};
+use core::{marker::PhantomData, mem, ptr, str, time::Duration};
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec, vec::Vec};
 
 use crate::{
-    DirLookupResult, DirReader, Ext4Error, Ext4Result, FileAttr, InodeRef, InodeType,
+    Backend, DirEntry, DirLookupResult, DirPage, DirPlusEntry, DirPlusPage, DirReader, Ext4Error,
+    Ext4ErrorKind, Ext4Result, FileAttr, FileName, InodeRef, InodeType, Metrics, OpStats, OwnedDirEntry,
     blockdev::{BlockDevice, Ext4BlockDevice},
-    error::Context,
+    error::{Context, ErrorContext},
     ffi::*,
-    util::get_block_size,
+    util::{get_block_size, get_inode_size},
 };
 
 /// 系统硬件抽象层（HAL）接口，提供时间相关功能
 pub trait SystemHal {
     /// 获取当前时间（可选，用于更新文件的访问/修改时间）
     fn now() -> Option<Duration>;
+
+    /// 单调时钟（可选，用于统计各操作的耗时，见Ext4Filesystem::metrics）
+    fn monotonic() -> Option<Duration> {
+        None
+    }
+
+    /// 睡眠指定时长（可选，用于RetryDevice的重试退避；默认不等待立即重试）
+    fn sleep(_duration: Duration) {}
+
+    /// 让出CPU（可选，日志回放、缓存回写和remove_dir_all等长时间操作定期调用，
+    /// 间隔见FsConfig::yield_interval；协作式调度的宿主在这里切换到其他任务，默认不让出）
+    fn yield_now() {}
+
+    /// 内存紧张时返回块缓存应缩减到的块数（可选，每次文件操作开始前查询，见Ext4Filesystem::shrink_cache）
+    fn memory_pressure() -> Option<u32> {
+        None
+    }
 }
 
 /// 默认的硬件抽象层实现（不提供时间）
@@ -30,16 +48,158 @@ impl SystemHal for DummyHal {
 #[derive(Debug, Clone)]
 pub struct FsConfig {
     pub bcache_size: u32, // 块缓存大小
+    pub backend: Option<Backend>, // 期望的后端（None表示使用当前构建的后端）
+    pub ordered_data: bool, // 有序数据模式：数据写完之前不回写引用它的元数据（仅纯 Rust 后端）
+    pub force_features: bool, // 忽略不支持的特性强制挂载（可能损坏数据，仅纯 Rust 后端）
+    pub read_only: bool, // 严格只读挂载：不修改superblock，修改操作返回ReadOnly，不向设备写入任何块
+    pub path_max: usize, // 按路径操作时路径的最大字节数，超过时返回NameTooLong
+    pub extent_cache_size: usize, // extent 状态缓存的最大映射条目数，0表示不缓存（仅纯 Rust 后端）
+    pub max_dirty_blocks: u32, // 脏块达到该数量时在写入路径上回写其中一半，限制掉电时丢失的数据量，0表示不限制（仅纯 Rust 后端）
+    pub sb_commit_interval: Option<Duration>, // superblock计数的定期写回间隔（需要SystemHal::monotonic），None表示只在flush和卸载时写回（仅纯 Rust 后端）
+    pub yield_interval: u32, // 长时间操作每处理该数量的块或条目调用一次SystemHal::yield_now，0表示不让出（仅纯 Rust 后端）
+    pub reproducible: Option<Duration>, // 可重现模式：文件系统写入的时间戳固定为该值（如SOURCE_DATE_EPOCH），mkfs不使用随机数，None表示使用SystemHal::now
 }
 
+/// 默认的路径最大字节数（与Linux的PATH_MAX相同）
+pub const PATH_MAX: usize = 4096;
+
+/// 默认的让出间隔（块或条目数）
+pub const DEFAULT_YIELD_INTERVAL: u32 = 64;
+
 impl Default for FsConfig {
     fn default() -> Self {
         Self {
             bcache_size: CONFIG_BLOCK_DEV_CACHE_SIZE, // 使用默认缓存大小
+            backend: None,
+            ordered_data: false,
+            force_features: false,
+            read_only: false,
+            path_max: PATH_MAX,
+            #[cfg(feature = "use-rust")]
+            extent_cache_size: CONFIG_EXTENT_STATUS_CACHE_SIZE,
+            #[cfg(not(feature = "use-rust"))]
+            extent_cache_size: 0, // C 实现没有 extent 状态缓存
+            max_dirty_blocks: 0,
+            sb_commit_interval: None,
+            yield_interval: DEFAULT_YIELD_INTERVAL,
+            reproducible: None,
+        }
+    }
+}
+
+/// 出错时的处理方式（superblock的errors字段，对应errors=挂载选项）
+///
+/// 目前只作用于块设备写入失败。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorBehavior {
+    Continue,  // 返回错误，继续运行
+    RemountRo, // 切换为只读
+    Panic,     // 直接panic
+}
+
+impl ErrorBehavior {
+    /// 由superblock字段解析（未知值按Continue处理）
+    pub fn from_raw(raw: u16) -> Self {
+        match raw {
+            EXT4_ERRORS_RO => ErrorBehavior::RemountRo,
+            EXT4_ERRORS_PANIC => ErrorBehavior::Panic,
+            _ => ErrorBehavior::Continue,
+        }
+    }
+
+    /// superblock字段的取值
+    pub fn to_raw(self) -> u16 {
+        match self {
+            ErrorBehavior::Continue => EXT4_ERRORS_CONTINUE,
+            ErrorBehavior::RemountRo => EXT4_ERRORS_RO,
+            ErrorBehavior::Panic => EXT4_ERRORS_PANIC,
+        }
+    }
+}
+
+/// 介质变化事件（块数以设备的物理块为单位）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaChange {
+    pub old_blocks: u64,  // 变化前的设备块数
+    pub new_blocks: u64,  // 变化后的设备块数
+    pub read_only: bool,  // 设备小于文件系统，已切换为只读
+    pub replaced: bool,   // 介质上已不是挂载的文件系统（UUID或几何参数不同、无法读取superblock），需要重新挂载
+}
+
+/// 两个superblock是否属于同一个文件系统（UUID和决定布局的几何参数都相同）
+fn same_filesystem(a: &ext4_sblock, b: &ext4_sblock) -> bool {
+    a.magic == b.magic
+        && a.uuid == b.uuid
+        && a.blocks_count_lo == b.blocks_count_lo
+        && a.blocks_count_hi == b.blocks_count_hi
+        && a.inodes_count == b.inodes_count
+        && a.log_block_size == b.log_block_size
+        && a.blocks_per_group == b.blocks_per_group
+        && a.inodes_per_group == b.inodes_per_group
+        && a.inode_size == b.inode_size
+}
+
+/// 介质变化回调
+pub type MediaChangeHandler = Box<dyn FnMut(&MediaChange)>;
+
+/// 文件系统变更事件，由高层修改接口在操作成功后发出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeEvent<'a> {
+    /// 目录中新增了条目（创建文件/目录/特殊文件或硬链接）
+    Created { parent: u32, name: FileName<'a>, ino: u32 },
+    /// 目录中的条目被删除（ino的链接数为0时inode已被释放）
+    Removed { parent: u32, name: FileName<'a>, ino: u32 },
+    /// 条目被移动或改名（目标位置原有的条目被替换，不再单独发出Removed）
+    Renamed {
+        src_dir: u32,
+        src_name: FileName<'a>,
+        dst_dir: u32,
+        dst_name: FileName<'a>,
+        ino: u32,
+    },
+    /// 文件的[offset, offset + len)范围被写入
+    Written { ino: u32, offset: u64, len: u64 },
+    /// 文件大小被设置为len
+    Resized { ino: u32, len: u64 },
+}
+
+/// 变更事件回调
+pub type ChangeHandler = Box<dyn FnMut(&ChangeEvent<'_>)>;
+
+/// 中断检查回调：长时间操作在两步之间调用，返回true时中止操作
+pub type InterruptHandler = Box<dyn FnMut() -> bool>;
+
+/// 长时间操作的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressPhase {
+    MkfsGroups,  // 格式化：写入块组元数据（单位为块组）
+    MkfsJournal, // 格式化：创建日志（单位为日志块）
+    CheckDirs,   // 一致性检查：检查目录（单位为目录）
+    Import,      // 导入宿主目录树或tar归档（单位为条目，总数未知）
+}
+
+/// 进度报告
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: ProgressPhase, // 当前阶段
+    pub done: u64,            // 已处理的数量
+    pub total: Option<u64>,   // 总数（未知时为None）
+}
+
+impl Progress {
+    /// 完成百分比（0~100，总数未知时返回None）
+    pub fn percent(&self) -> Option<u8> {
+        let total = self.total?;
+        if total == 0 {
+            return Some(100);
         }
+        Some((self.done.min(total) * 100 / total) as u8)
     }
 }
 
+/// 进度回调
+pub type ProgressHandler = Box<dyn FnMut(&Progress)>;
+
 /// 文件系统状态信息
 #[derive(Debug, Clone)]
 pub struct StatFs {
@@ -50,25 +210,101 @@ pub struct StatFs {
     pub block_size: u32,         // 块大小
 }
 
+/// 文件句柄：inode编号和版本号，inode被释放或重新分配后失效
+///
+/// 用于NFS/9p等导出层，服务端重启后仍可通过 [`Ext4Filesystem::open_by_handle`] 找回文件。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileHandle {
+    pub ino: u32,        // inode编号
+    pub generation: u32, // inode版本号
+}
+
+impl FileHandle {
+    /// 编码后的字节数
+    pub const SIZE: usize = 8;
+
+    /// 编码为定长字节串（小端的inode编号和版本号）
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        buf[..4].copy_from_slice(&self.ino.to_le_bytes());
+        buf[4..].copy_from_slice(&self.generation.to_le_bytes());
+        buf
+    }
+
+    /// 从to_bytes的结果解码，长度不符时返回None
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        let buf: &[u8; Self::SIZE] = buf.try_into().ok()?;
+        Some(Self {
+            ino: u32::from_le_bytes(buf[..4].try_into().unwrap()),
+            generation: u32::from_le_bytes(buf[4..].try_into().unwrap()),
+        })
+    }
+}
+
 /// ext4文件系统实例结构体
 /// 泛型参数：Hal（硬件抽象层）、Dev（块设备）
 pub struct Ext4Filesystem<Hal: SystemHal, Dev: BlockDevice> {
-    inner: Box<ext4_fs>, // 底层C结构体
-    bdev: Ext4BlockDevice<Dev>, // 块设备包装器
-    _phantom: PhantomData<Hal>, // 泛型标记
+    pub(crate) inner: Box<ext4_fs>, // 底层C结构体
+    bdev: Ext4BlockDevice<Dev>,     // 块设备包装器
+    backend: Backend,               // 挂载时选定的后端
+    metrics: Metrics,               // 各操作的调用次数与耗时
+    #[cfg(feature = "use-rust")]
+    pub(crate) key_provider: Option<Box<dyn crate::KeyProvider>>, // fscrypt主密钥来源
+    media_change: Option<MediaChangeHandler>, // 介质变化回调
+    change: Option<ChangeHandler>,  // 变更事件回调
+    interrupt: Option<InterruptHandler>, // 长时间操作的中断检查回调
+    progress: Option<ProgressHandler>, // 长时间操作的进度回调
+    deadline: Option<Duration>,     // 长时间操作的截止时间（单调时钟）
+    frozen: bool,                   // 是否已冻结（拒绝修改操作）
+    stale: bool,                    // 介质已被更换，需要重新挂载（拒绝所有inode访问）
+    path_max: usize,                // 路径的最大字节数
+    sb_commit_interval: Option<Duration>, // superblock计数的定期写回间隔
+    sb_committed_at: Option<Duration>,    // 上次写回superblock的时间（单调时钟）
+    fixed_time: Option<Duration>,   // 可重现模式下固定的时间戳
+    _phantom: PhantomData<Hal>,     // 泛型标记
 }
 
 impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
     /// 创建新的ext4文件系统实例
     pub fn new(dev: Dev, config: FsConfig) -> Ext4Result<Self> {
+        // 选择后端（未编译进当前构建时返回ENOTSUP）
+        let backend = Backend::select(config.backend)?;
+        debug!("mount ext4 with {} backend", backend.name());
         // 初始化块设备
-        let mut bdev = Ext4BlockDevice::new(dev)?;
+        let bdev = Ext4BlockDevice::new(dev)?;
+        Self::mount(bdev, backend, config)
+    }
+
+    /// 在已打开的块设备上挂载文件系统
+    pub(crate) fn mount(
+        mut bdev: Ext4BlockDevice<Dev>,
+        backend: Backend,
+        config: FsConfig,
+    ) -> Ext4Result<Self> {
         // 初始化文件系统结构体
-        let mut fs = Box::new(unsafe { mem::zeroed() });
+        let mut fs: Box<ext4_fs> = Box::new(unsafe { mem::zeroed() });
+        // 写入量取决于缓存大小和回写时机，可重现模式下不更新s_kbytes_written
+        #[cfg(feature = "use-rust")]
+        {
+            fs.no_kbytes_written = config.reproducible.is_some();
+            // 挂载时的日志回放就需要让出CPU
+            if config.yield_interval != 0 {
+                fs.yield_hook = Some(Hal::yield_now);
+                fs.yield_interval = config.yield_interval;
+            }
+        }
+        if config.force_features {
+            #[cfg(not(feature = "use-rust"))]
+            return Err(Ext4Error::new(ENOTSUP as _, "force_features requires the rust backend"));
+            #[cfg(feature = "use-rust")]
+            {
+                fs.force_features = true;
+            }
+        }
         unsafe {
             let bd = bdev.inner.as_mut();
-            // 初始化ext4文件系统
-            ext4_fs_init(&mut *fs, bd, false).context("ext4_fs_init")?;
+            // 初始化ext4文件系统（包含不支持的特性时失败；只读挂载时不更新挂载计数和状态）
+            ext4_fs_init(&mut *fs, bd, config.read_only).context("ext4_fs_init")?;
 
             // 配置块大小和缓存
             let bs = get_block_size(&fs.sb);
@@ -78,6 +314,12 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
             if bs != (*bd.bc).itemsize {
                 return Err(Ext4Error::new(ENOTSUP as _, "block size mismatch"));
             }
+            if config.ordered_data {
+                #[cfg(not(feature = "use-rust"))]
+                return Err(Ext4Error::new(ENOTSUP as _, "ordered data mode requires the rust backend"));
+                #[cfg(feature = "use-rust")]
+                ext4_block_set_ordered(bd, true);
+            }
 
             // 关联块设备和文件系统
             bd.fs = &mut *fs;
@@ -85,21 +327,161 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
             let mut result = Self {
                 inner: fs,
                 bdev,
+                backend,
+                metrics: Metrics::default(),
+                #[cfg(feature = "use-rust")]
+                key_provider: None,
+                media_change: None,
+                change: None,
+                interrupt: None,
+                progress: None,
+                deadline: None,
+                frozen: false,
+                stale: false,
+                path_max: config.path_max,
+                sb_commit_interval: config.sb_commit_interval,
+                sb_committed_at: Hal::monotonic(),
+                fixed_time: config.reproducible,
                 _phantom: PhantomData,
             };
             let bd = result.bdev.inner.as_mut();
             ext4_block_bind_bcache(bd, bd.bc).context("ext4_block_bind_bcache")?;
+            #[cfg(feature = "use-rust")]
+            {
+                let mount_config = Ext4MountConfig {
+                    bcache_size: config.bcache_size,
+                    es_cache_size: config.extent_cache_size,
+                    max_dirty_blocks: config.max_dirty_blocks,
+                };
+                ext4_fs_set_config(&mut *result.inner, &mount_config).context("ext4_fs_set_config")?;
+            }
+            #[cfg(not(feature = "use-rust"))]
+            if config.max_dirty_blocks != 0 {
+                return Err(Ext4Error::new(ENOTSUP as _, "max_dirty_blocks requires the rust backend"));
+            }
+            #[cfg(not(feature = "use-rust"))]
+            if config.sb_commit_interval.is_some() {
+                return Err(Ext4Error::new(ENOTSUP as _, "sb_commit_interval requires the rust backend"));
+            }
+            // 未干净卸载时回放日志（需要缓存已绑定）
+            #[cfg(feature = "use-rust")]
+            ext4_journal_recover(&mut *result.inner).context("ext4_journal_recover")?;
+            // 挂载次数和状态已在ext4_fs_init中判断，这里补充按检查间隔的判断（需要系统时间）
+            #[cfg(feature = "use-rust")]
+            if let Some(now) = result.now() {
+                let reasons = ext4_fs_fsck_reasons(&result.inner.sb, Some(now.as_secs()));
+                result.inner.fsck_reasons |= reasons & EXT4_FSCK_CHECKINTERVAL;
+            }
+            // 块组描述符中的元数据位置不合理时不允许写入，避免覆盖元数据
+            #[cfg(feature = "use-rust")]
+            if !result.is_read_only() {
+                if let Err(err) = result.check_layout() {
+                    warn!("bad block group layout, mounting read-only: {err:?}");
+                    result.inner.read_only = true;
+                }
+            }
             Ok(result)
         }
     }
 
+    /// 获取挂载时选定的后端
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// 是否为只读（只读挂载，或写入出错后按errors=remount-ro转为只读）
+    pub fn is_read_only(&self) -> bool {
+        self.inner.read_only
+    }
+
+    /// 出错时的处理方式
+    pub fn error_behavior(&self) -> ErrorBehavior {
+        ErrorBehavior::from_raw(u16::from_le(self.inner.sb.errors))
+    }
+
+    /// 块组描述符是否写回失败过
+    ///
+    /// 此后内存中的描述符可能与磁盘上的不一致，修改操作返回Io，直到重新挂载。
+    pub fn is_poisoned(&self) -> bool {
+        #[cfg(feature = "use-rust")]
+        return self.inner.bg_poisoned;
+        #[cfg(not(feature = "use-rust"))]
+        false
+    }
+
+    /// 是否已冻结
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// 冻结文件系统：写回全部脏数据，此后修改操作返回Busy，直到thaw
+    ///
+    /// 冻结期间磁盘上的内容保持一致且不再变化，备份任务可以直接复制设备或通过本实例读取整棵树。
+    /// 已冻结时返回Busy，写回失败时不进入冻结状态。
+    pub fn freeze(&mut self) -> Ext4Result<()> {
+        if self.frozen {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Busy, "already frozen"));
+        }
+        self.flush()?;
+        self.frozen = true;
+        Ok(())
+    }
+
+    /// 解除冻结，恢复修改操作
+    pub fn thaw(&mut self) {
+        self.frozen = false;
+    }
+
+    /// 介质被更换后（见revalidate）拒绝访问，需要重新挂载
+    fn ensure_fresh(&self) -> Ext4Result {
+        if self.stale {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Stale, "media replaced, remount required"));
+        }
+        Ok(())
+    }
+
+    /// 只读挂载、冻结期间或块组描述符写回失败后拒绝修改操作
+    pub(crate) fn ensure_writable(&self) -> Ext4Result {
+        self.ensure_fresh()?;
+        if self.bdev.is_error_read_only() {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::ReadOnly, "remounted read-only after a write error"));
+        }
+        if self.is_read_only() {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::ReadOnly, "read-only mount"));
+        }
+        if self.frozen {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Busy, "filesystem frozen"));
+        }
+        if self.is_poisoned() {
+            return Err(Ext4Error::from_kind(
+                Ext4ErrorKind::Io,
+                "block group descriptor write failed",
+            ));
+        }
+        Ok(())
+    }
+
+    /// 保留inode（根目录、日志、resize inode等）不能通过目录操作删除或新增链接，返回EPERM
+    fn check_not_reserved(&self, op: &'static str, ino: u32) -> Ext4Result {
+        #[cfg(feature = "use-rust")]
+        let reserved = ext4_sb_is_reserved_ino(&self.inner.sb, ino);
+        #[cfg(not(feature = "use-rust"))]
+        let reserved = ino < EXT4_GOOD_OLD_FIRST_INO;
+        if reserved {
+            return Err(Ext4Error::new(EPERM as _, "reserved inode")
+                .with_context(ErrorContext::new(op).ino(ino)));
+        }
+        Ok(())
+    }
+
     /// 获取指定inode编号的InodeRef
-    fn inode_ref(&mut self, ino: u32) -> Ext4Result<InodeRef<Hal>> {
+    pub(crate) fn inode_ref(&mut self, ino: u32) -> Ext4Result<InodeRef<Hal>> {
+        self.ensure_fresh()?;
         unsafe {
-            let mut result = InodeRef::new(mem::zeroed());
+            let mut result = InodeRef::empty(self.fixed_time);
             // 调用C函数获取inode引用
             ext4_fs_get_inode_ref(self.inner.as_mut(), ino, result.inner.as_mut())
-                .context("ext4_fs_get_inode_ref")?;
+                .with_context(|| ErrorContext::new("ext4_fs_get_inode_ref").ino(ino))?;
             Ok(result)
         }
     }
@@ -110,13 +492,62 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
     }
 
     /// 对指定inode执行操作（通过闭包）
+    ///
+    /// 只读挂载或冻结期间闭包修改了inode会被撤销，并返回ReadOnly或Busy。
     pub fn with_inode_ref<R>(
         &mut self,
         ino: u32,
         f: impl FnOnce(&mut InodeRef<Hal>) -> Ext4Result<R>,
     ) -> Ext4Result<R> {
         let mut inode = self.inode_ref(ino)?;
-        f(&mut inode)
+        if !self.is_read_only() && !self.frozen {
+            return f(&mut inode);
+        }
+        // inode指向块缓存中的数据，修改需要就地还原
+        let saved = unsafe { ptr::read(inode.inner.inode) };
+        let ret = f(&mut inode);
+        if inode.inner.dirty {
+            unsafe { ptr::write(inode.inner.inode, saved) };
+            inode.inner.dirty = false;
+            self.ensure_writable()?;
+        }
+        ret
+    }
+
+    /// 同时对两个inode执行操作（如父目录和其中的子项），两个编号可以相同
+    ///
+    /// 只是with_inode_ref的两个inode版本：两个引用只在闭包内有效，闭包返回时写回并归还，
+    /// 不提供可以在闭包外保存或共享（Rc/Arc）的inode句柄。
+    /// 只读挂载或冻结期间任一inode被修改时，两个inode都被还原，并返回ReadOnly或Busy。
+    pub fn with_inode_refs<R>(
+        &mut self,
+        first: u32,
+        second: u32,
+        f: impl FnOnce(&mut InodeRef<Hal>, &mut InodeRef<Hal>) -> Ext4Result<R>,
+    ) -> Ext4Result<R> {
+        let mut a = self.inode_ref(first)?;
+        let mut b = self.inode_ref(second)?;
+        if !self.is_read_only() && !self.frozen {
+            return f(&mut a, &mut b);
+        }
+        // 编号相同时两次保存的内容一致，按相反顺序还原即可
+        let saved = unsafe { (ptr::read(a.inner.inode), ptr::read(b.inner.inode)) };
+        let ret = f(&mut a, &mut b);
+        if a.inner.dirty || b.inner.dirty {
+            unsafe {
+                ptr::write(b.inner.inode, saved.1);
+                ptr::write(a.inner.inode, saved.0);
+            }
+            a.inner.dirty = false;
+            b.inner.dirty = false;
+            self.ensure_writable()?;
+        }
+        ret
+    }
+
+    /// 写入文件系统的当前时间（可重现模式下为固定值，否则由SystemHal::now提供）
+    pub(crate) fn now(&self) -> Option<Duration> {
+        self.fixed_time.or_else(Hal::now)
     }
 
     /// 分配新的inode（指定类型）
@@ -133,12 +564,16 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
                 InodeType::Socket => EXT4_DE_SOCK,
                 InodeType::Unknown => EXT4_DE_UNKNOWN,
             };
-            let mut result = InodeRef::new(mem::zeroed());
+            let mut result = InodeRef::empty(self.fixed_time);
             // 调用C函数分配inode
             ext4_fs_alloc_inode(self.inner.as_mut(), result.inner.as_mut(), ty as _)
                 .context("ext4_fs_alloc_inode")?;
             // 初始化inode的块结构
             ext4_fs_inode_blocks_init(self.inner.as_mut(), result.inner.as_mut());
+            // 创建时间只在分配时设置
+            if let Some(now) = self.now() {
+                result.set_btime(&now);
+            }
             Ok(result)
         }
     }
@@ -149,29 +584,267 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
         Ok(())
     }
 
+    /// 获取inode的文件句柄
+    pub fn file_handle(&mut self, ino: u32) -> Ext4Result<FileHandle> {
+        let inode = self.inode_ref(ino)?;
+        Ok(FileHandle {
+            ino,
+            generation: inode.generation(),
+        })
+    }
+
+    /// 由文件句柄找回inode编号
+    ///
+    /// inode编号无效、inode已被释放（链接数为0）或版本号不符（已被重新分配给其他文件）时返回Stale。
+    pub fn open_by_handle(&mut self, handle: &FileHandle) -> Ext4Result<u32> {
+        let stale = || {
+            Ext4Error::from_kind(Ext4ErrorKind::Stale, None)
+                .with_context(ErrorContext::new("open_by_handle").ino(handle.ino))
+        };
+        let inode = match self.inode_ref(handle.ino) {
+            Ok(inode) => inode,
+            Err(err) if err.kind() == Ext4ErrorKind::InvalidInput => return Err(stale()),
+            Err(err) => return Err(err),
+        };
+        if inode.nlink() == 0 || inode.generation() != handle.generation {
+            return Err(stale());
+        }
+        Ok(handle.ino)
+    }
+
+    /// 批量读取多个inode的属性，返回的属性与inos一一对应（允许重复的inode编号）
+    ///
+    /// 按inode编号顺序读取，同一inode表块内的inode连续处理，并在处理完该块之前保持对它的引用，
+    /// 每个inode表块只从设备读取一次。逐个调用get_attr时，块缓存较小或被其他元数据挤出后
+    /// 每个inode都可能重新读取所在的块，在慢速介质上列目录时差别明显。
+    pub fn stat_many(&mut self, inos: &[u32]) -> Ext4Result<Vec<FileAttr>> {
+        let sb = &self.inner.sb;
+        let inodes_per_block = (get_block_size(sb) / get_inode_size(sb)).max(1);
+        let mut order: Vec<usize> = (0..inos.len()).collect();
+        order.sort_unstable_by_key(|&i| inos[i]);
+
+        let mut attrs = vec![FileAttr::default(); inos.len()];
+        // 当前inode表块号及该块中一个inode的引用（持有引用使块留在缓存中）
+        let mut held: Option<(u32, InodeRef<Hal>)> = None;
+        for i in order {
+            let ino = inos[i];
+            let inode = self.inode_ref(ino)?;
+            inode.get_attr(&mut attrs[i]);
+
+            // 每组的inode数是每块inode数的整数倍，按全局编号分块即可
+            let block = (ino - 1) / inodes_per_block;
+            if held.as_ref().is_none_or(|(b, _)| *b != block) {
+                held = Some((block, inode));
+            }
+        }
+        Ok(attrs)
+    }
+
+    /// 执行操作并记录到对应的统计项（Hal提供单调时钟时同时记录耗时）
+    ///
+    /// 操作因设备读写失败而出错时，错误中补充失败的文件系统块号。
+    fn timed<R>(
+        &mut self,
+        stats: fn(&mut Metrics) -> &mut OpStats,
+        f: impl FnOnce(&mut Self) -> Ext4Result<R>,
+    ) -> Ext4Result<R> {
+        if let Some(target) = Hal::memory_pressure() {
+            if let Err(err) = self.shrink_cache(target) {
+                warn!("shrink_cache under memory pressure failed: {err:?}");
+            }
+        }
+        let start = Hal::monotonic();
+        self.bdev.take_failed_block();
+        let ret = f(self);
+        let failed = self.bdev.take_failed_block();
+        self.bdev.panic_on_write_error();
+        let ret = ret.map_err(|err| match failed {
+            Some(block) => err.or_block(block),
+            None => err,
+        });
+        let elapsed = start
+            .zip(Hal::monotonic())
+            .map(|(start, end)| end.saturating_sub(start));
+        stats(&mut self.metrics).record(elapsed);
+        #[cfg(feature = "use-rust")]
+        self.commit_sb_periodic();
+        ret
+    }
+
+    /// 距上次写回超过sb_commit_interval时写回superblock中修改过的计数
+    #[cfg(feature = "use-rust")]
+    fn commit_sb_periodic(&mut self) {
+        let (Some(interval), Some(now)) = (self.sb_commit_interval, Hal::monotonic()) else {
+            return;
+        };
+        let last = *self.sb_committed_at.get_or_insert(now);
+        if !self.inner.sb_dirty || now.saturating_sub(last) < interval {
+            return;
+        }
+        match unsafe { ext4_fs_commit_sb(self.inner.as_mut()) } {
+            0 => self.sb_committed_at = Some(now),
+            r => warn!("periodic superblock commit failed: {}", Ext4Error::new(r, None)),
+        }
+    }
+
+    /// 执行修改操作，失败时撤销操作期间的全部元数据修改（释放已分配的inode和块）
+    ///
+    /// 嵌套调用并入最外层；C 后端没有撤销日志，直接执行。只读挂载时直接返回ReadOnly。
+    pub(crate) fn undoable<R>(&mut self, f: impl FnOnce(&mut Self) -> Ext4Result<R>) -> Ext4Result<R> {
+        self.ensure_writable()?;
+        #[cfg(feature = "use-rust")]
+        let mut undo = Ext4Undo::new();
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_undo_begin(self.inner.as_mut(), &mut undo)
+        };
+        let ret = f(self);
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            let fs = self.inner.as_mut();
+            if ret.is_ok() {
+                ext4_undo_commit(fs, &mut undo);
+            } else {
+                let r = ext4_undo_rollback(fs, &mut undo);
+                if r != 0 {
+                    log::error!("ext4_undo_rollback failed: {}", Ext4Error::new(r, None));
+                }
+            }
+        }
+        ret
+    }
+
     /// 从指定inode读取数据（偏移量pos处）
     pub fn read_at(&mut self, ino: u32, buf: &mut [u8], offset: u64) -> Ext4Result<usize> {
-        self.inode_ref(ino)?.read_at(buf, offset)
+        self.timed(
+            |m| &mut m.read,
+            |fs| {
+                fs.inode_ref(ino)?
+                    .read_at(buf, offset)
+                    .with_context(|| ErrorContext::new("read_at").ino(ino))
+            },
+        )
     }
 
     /// 向指定inode写入数据（偏移量pos处）
     pub fn write_at(&mut self, ino: u32, buf: &[u8], offset: u64) -> Ext4Result<usize> {
-        self.inode_ref(ino)?.write_at(buf, offset)
+        let len = self.timed(
+            |m| &mut m.write,
+            |fs| {
+                fs.undoable(|fs| {
+                    let _data = DataWriteGuard::new(fs.bdev.inner.as_mut());
+                    fs.inode_ref(ino)?
+                        .write_at(buf, offset)
+                        .with_context(|| ErrorContext::new("write_at").ino(ino))
+                })
+            },
+        )?;
+        self.notify_written(ino, offset, len);
+        Ok(len)
+    }
+
+    /// 稀疏写入：全零且对应块尚未分配的部分不分配块，保留为空洞（适合写入虚拟磁盘等稀疏数据）
+    pub fn write_at_sparse(&mut self, ino: u32, buf: &[u8], offset: u64) -> Ext4Result<usize> {
+        let len = self.timed(
+            |m| &mut m.write,
+            |fs| {
+                fs.undoable(|fs| {
+                    let _data = DataWriteGuard::new(fs.bdev.inner.as_mut());
+                    fs.inode_ref(ino)?
+                        .write_at_sparse(buf, offset)
+                        .with_context(|| ErrorContext::new("write_at_sparse").ino(ino))
+                })
+            },
+        )?;
+        self.notify_written(ino, offset, len);
+        Ok(len)
     }
 
     /// 设置指定inode的文件大小
     pub fn set_len(&mut self, ino: u32, len: u64) -> Ext4Result<()> {
-        self.inode_ref(ino)?.set_len(len)
+        self.timed(
+            |m| &mut m.set_len,
+            |fs| {
+                fs.undoable(|fs| {
+                    fs.inode_ref(ino)?
+                        .set_len(len)
+                        .with_context(|| ErrorContext::new("set_len").ino(ino))
+                })
+            },
+        )?;
+        self.notify(ChangeEvent::Resized { ino, len });
+        Ok(())
+    }
+
+    /// 按POSIX truncate语义把指定inode的大小设为size
+    ///
+    /// 缩小时与set_len(ino, size)相同，同时回收size之后预分配的块；扩展时不分配数据块，
+    /// 新增部分为空洞，读出为0。
+    pub fn truncate(&mut self, ino: u32, size: u64) -> Ext4Result<()> {
+        self.timed(
+            |m| &mut m.set_len,
+            |fs| {
+                fs.undoable(|fs| {
+                    fs.inode_ref(ino)?
+                        .set_len_sparse(size)
+                        .with_context(|| ErrorContext::new("truncate").ino(ino))
+                })
+            },
+        )?;
+        self.notify(ChangeEvent::Resized { ino, len: size });
+        Ok(())
+    }
+
+    /// 设置文件追加写入时的预分配提示：每次在文件末尾分配新块时，额外预分配blocks个
+    /// 物理上紧接其后的块（作为未写入extent，不计入文件大小），减少持续增长的文件（如日志）的碎片
+    ///
+    /// blocks为0时清除提示。提示只保存在内存中，卸载或inode被释放后失效；
+    /// 已预分配但未使用的块由release_prealloc或截断到当前大小的set_len回收。
+    #[cfg(feature = "use-rust")]
+    pub fn set_prealloc(&mut self, ino: u32, blocks: u32) {
+        if blocks == 0 {
+            self.inner.prealloc.remove(&ino);
+        } else {
+            self.inner.prealloc.insert(ino, blocks);
+        }
+    }
+
+    /// 文件的预分配提示（未设置时为0）
+    #[cfg(feature = "use-rust")]
+    pub fn prealloc(&self, ino: u32) -> u32 {
+        self.inner.prealloc.get(&ino).copied().unwrap_or(0)
+    }
+
+    /// 关闭文件时调用：清除预分配提示，并回收文件末尾之后尚未使用的预分配块
+    #[cfg(feature = "use-rust")]
+    pub fn release_prealloc(&mut self, ino: u32) -> Ext4Result<()> {
+        self.inner.prealloc.remove(&ino);
+        let size = self.inode_ref(ino)?.size();
+        self.set_len(ino, size)
     }
 
     /// 设置符号链接的目标路径
     pub fn set_symlink(&mut self, ino: u32, buf: &[u8]) -> Ext4Result<()> {
-        self.inode_ref(ino)?.set_symlink(buf)
+        self.undoable(|fs| fs.inode_ref(ino)?.set_symlink(buf))?;
+        self.notify_written(ino, 0, buf.len());
+        Ok(())
+    }
+
+    /// 读取符号链接的目标路径
+    pub fn read_link(&mut self, ino: u32) -> Ext4Result<Vec<u8>> {
+        self.inode_ref(ino)?
+            .read_link()
+            .with_context(|| ErrorContext::new("read_link").ino(ino))
     }
 
     /// 在目录inode中查找指定名称的条目
-    pub fn lookup(&mut self, parent: u32, name: &str) -> Ext4Result<DirLookupResult<Hal>> {
-        self.inode_ref(parent)?.lookup(name)
+    pub fn lookup<'n>(
+        &mut self,
+        parent: u32,
+        name: impl Into<FileName<'n>>,
+    ) -> Ext4Result<DirLookupResult<Hal>> {
+        let name = name.into();
+        self.timed(|m| &mut m.lookup, |fs| fs.inode_ref(parent)?.lookup(name))
     }
 
     /// 读取目录inode中的条目（从偏移量开始）
@@ -179,49 +852,315 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
         self.inode_ref(parent)?.read_dir(offset)
     }
 
+    /// 拒绝超过FsConfig::path_max的路径（返回NameTooLong）
+    fn check_path(&self, op: &'static str, path: &str) -> Ext4Result {
+        if path.len() > self.path_max {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::NameTooLong, None)
+                .with_context(ErrorContext::new(op)));
+        }
+        Ok(())
+    }
+
+    /// 按路径查找inode编号（从根目录开始，不跟随符号链接）
+    pub fn resolve_path(&mut self, path: &str) -> Ext4Result<u32> {
+        self.check_path("resolve_path", path)?;
+        let mut ino: u32 = EXT4_INODE_ROOT_INDEX as _;
+        for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            let dir = self.inode_ref(ino)?;
+            if dir.inode_type() != InodeType::Directory {
+                return Err(Ext4Error::new(ENOTDIR as _, None).with_context(
+                    ErrorContext::new("resolve_path")
+                        .ino(ino)
+                        .path_segment(name),
+                ));
+            }
+            ino = dir.lookup(name)?.entry().ino();
+        }
+        Ok(ino)
+    }
+
+    /// 分页读取目录：从cookie（首次为0）开始最多读取max_entries个条目
+    ///
+    /// 返回的DirPage::cookie用于读取下一页，适合缓冲区大小固定的内核逐页枚举大目录。
+    pub fn read_dir_from(
+        &mut self,
+        path: &str,
+        cookie: u64,
+        max_entries: usize,
+    ) -> Ext4Result<DirPage> {
+        self.read_dir_filtered(path, cookie, max_entries, |_| true)
+    }
+
+    /// 分页读取目录，只返回filter接受的条目（被过滤的条目不计入max_entries）
+    pub fn read_dir_filtered(
+        &mut self,
+        path: &str,
+        cookie: u64,
+        max_entries: usize,
+        mut filter: impl FnMut(&DirEntry<'_>) -> bool,
+    ) -> Ext4Result<DirPage> {
+        let ino = self.resolve_path(path)?;
+        self.read_dir_page(ino, cookie, max_entries, &mut filter)
+    }
+
+    /// 分页读取目录inode中的条目（read_dir_filtered的按inode编号版本）
+    pub(crate) fn read_dir_page(
+        &mut self,
+        ino: u32,
+        cookie: u64,
+        max_entries: usize,
+        filter: &mut dyn FnMut(&DirEntry<'_>) -> bool,
+    ) -> Ext4Result<DirPage> {
+        let dir = self.inode_ref(ino)?;
+        if dir.inode_type() != InodeType::Directory {
+            return Err(Ext4Error::new(ENOTDIR as _, None)
+                .with_context(ErrorContext::new("read_dir_from").ino(ino)));
+        }
+
+        let mut reader = dir.read_dir(cookie)?;
+        let mut page = DirPage::default();
+        while let Some(entry) = reader.current() {
+            if page.entries.len() >= max_entries {
+                page.cookie = Some(reader.offset());
+                break;
+            }
+            if filter(&entry) {
+                page.entries
+                    .push(OwnedDirEntry::new(&entry, reader.offset()));
+            }
+            reader.step()?;
+        }
+        Ok(page)
+    }
+
+    /// 分页读取目录并一并返回各条目指向的inode属性（readdirplus）
+    ///
+    /// 读出一页条目后用stat_many批量读取属性，避免对每个条目单独调用get_attr。
+    pub fn read_dir_plus(
+        &mut self,
+        path: &str,
+        cookie: u64,
+        max_entries: usize,
+    ) -> Ext4Result<DirPlusPage> {
+        let page = self.read_dir_from(path, cookie, max_entries)?;
+        let inos: Vec<u32> = page.entries.iter().map(|entry| entry.ino).collect();
+        let attrs = self.stat_many(&inos)?;
+
+        Ok(DirPlusPage {
+            entries: page
+                .entries
+                .into_iter()
+                .zip(attrs)
+                .map(|(entry, attr)| DirPlusEntry { entry, attr })
+                .collect(),
+            cookie: page.cookie,
+        })
+    }
+
     /// 创建新文件/目录（在parent目录下，指定名称、类型和权限）
-    pub fn create(&mut self, parent: u32, name: &str, ty: InodeType, mode: u32) -> Ext4Result<u32> {
+    pub fn create<'n>(
+        &mut self,
+        parent: u32,
+        name: impl Into<FileName<'n>>,
+        ty: InodeType,
+        mode: u32,
+    ) -> Ext4Result<u32> {
+        let name = name.into();
+        let ino = self.timed(
+            |m| &mut m.create,
+            |fs| fs.undoable(|fs| fs.create_inner(parent, name, ty, mode)),
+        )?;
+        self.notify(ChangeEvent::Created { parent, name, ino });
+        Ok(ino)
+    }
+
+    fn create_inner(
+        &mut self,
+        parent: u32,
+        name: FileName<'_>,
+        ty: InodeType,
+        mode: u32,
+    ) -> Ext4Result<u32> {
+        let mut parent = self.inode_ref(parent)?;
+        Ok(self.create_in(&mut parent, name, ty, mode)?.ino())
+    }
+
+    /// 在已持有引用的目录中创建条目，返回新inode的引用
+    fn create_in(
+        &mut self,
+        parent: &mut InodeRef<Hal>,
+        name: FileName<'_>,
+        ty: InodeType,
+        mode: u32,
+    ) -> Ext4Result<InodeRef<Hal>> {
+        // 新目录的".."增加父目录的链接数
+        if ty == InodeType::Directory {
+            parent.check_link_max("create")?;
+        }
         // 分配新inode
         let mut child = self.alloc_inode(ty)?;
-        // 获取父目录inode
-        let mut parent = self.inode_ref(parent)?;
+        // 数据块优先分配在父目录所在的块组（只是分配提示，失败时忽略）
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            let _ = ext4_balloc_inherit_goal(child.inner.as_mut(), parent.inner.as_mut());
+        }
         // 在父目录中添加条目
         parent.add_entry(name, &mut child)?;
 
         // 如果是目录，添加"."和".."条目
         if ty == InodeType::Directory {
             child.add_entry(".", &mut self.clone_ref(&child))?; // "."指向自身
-            child.add_entry("..", &mut parent)?; // ".."指向父目录
-            assert_eq!(child.nlink(), 2); // 目录初始链接数为2
+            child.add_entry("..", parent)?; // ".."指向父目录
+            child.set_nlink(2); // 目录初始链接数为2（父目录中的名称和"."）
         }
 
         // 设置文件权限
         child.set_mode((child.mode() & !0o777) | (mode & 0o777));
 
-        Ok(child.ino())
+        Ok(child)
+    }
+
+    /// 在同一目录下批量创建文件/目录，返回与names一一对应的inode编号
+    ///
+    /// 整个批次只获取一次父目录的引用，并保持最近创建的inode的引用，使连续分配的inode
+    /// 所在的inode表块留在缓存中；位图和块组描述符本就常驻缓存，superblock计数在同步时才写回。
+    /// 任一条目失败时整批撤销（C 后端没有撤销日志，失败前创建的条目保留）。
+    /// 与create相同，不检查名称是否已经存在。
+    pub fn create_many<'n, N: Into<FileName<'n>>>(
+        &mut self,
+        parent: u32,
+        names: impl IntoIterator<Item = N>,
+        ty: InodeType,
+        mode: u32,
+    ) -> Ext4Result<Vec<u32>> {
+        let names: Vec<FileName<'n>> = names.into_iter().map(Into::into).collect();
+        let inos = self.timed(
+            |m| &mut m.create,
+            |fs| {
+                fs.undoable(|fs| {
+                    let mut parent = fs.inode_ref(parent)?;
+                    let mut inos = Vec::with_capacity(names.len());
+                    let mut _last = None;
+                    for &name in &names {
+                        let child = fs.create_in(&mut parent, name, ty, mode)?;
+                        inos.push(child.ino());
+                        _last = Some(child);
+                    }
+                    Ok(inos)
+                })
+            },
+        )?;
+        for (&name, &ino) in names.iter().zip(&inos) {
+            self.notify(ChangeEvent::Created { parent, name, ino });
+        }
+        Ok(inos)
+    }
+
+    /// 按路径创建特殊文件（字符设备、块设备、命名管道或套接字），返回新inode编号
+    ///
+    /// rdev为设备号，编码与libc的dev_t相同（可由makedev得到），只对字符设备和块设备有效。
+    /// ext4最多表示12位主设备号和20位次设备号，超出时返回InvalidInput。
+    pub fn mknod(&mut self, path: &str, ty: InodeType, mode: u32, rdev: u64) -> Ext4Result<u32> {
+        self.check_path("mknod", path)?;
+        let path = path.trim_end_matches('/');
+        let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(Ext4Error::new(EINVAL as _, "invalid mknod path"));
+        }
+        let parent = self.resolve_path(parent_path)?;
+        match self.lookup(parent, name) {
+            Ok(_) => {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::AlreadyExists, None)
+                    .with_context(ErrorContext::new("mknod").ino(parent).path_segment(name)));
+            }
+            Err(err) if err.kind() == Ext4ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        self.mknod_at(parent, name, ty, mode, rdev)
+    }
+
+    /// 在指定目录中创建特殊文件
+    pub(crate) fn mknod_at<'n>(
+        &mut self,
+        parent: u32,
+        name: impl Into<FileName<'n>>,
+        ty: InodeType,
+        mode: u32,
+        rdev: u64,
+    ) -> Ext4Result<u32> {
+        let name = name.into();
+        let special = matches!(
+            ty,
+            InodeType::Fifo | InodeType::CharacterDevice | InodeType::BlockDevice | InodeType::Socket
+        );
+        if !special {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, None)
+                .with_context(ErrorContext::new("mknod").ino(parent).path_segment(name)));
+        }
+        let ino = self.timed(
+            |m| &mut m.create,
+            |fs| {
+                fs.undoable(|fs| {
+                    let ino = fs.create_inner(parent, name, ty, mode)?;
+                    let mut inode = fs.inode_ref(ino)?;
+                    if inode.is_device() {
+                        inode.set_rdev("mknod", rdev)?;
+                    }
+                    Ok(ino)
+                })
+            },
+        )?;
+        self.notify(ChangeEvent::Created { parent, name, ino });
+        Ok(ino)
     }
 
     /// 重命名文件/目录
-    pub fn rename(
+    pub fn rename<'s, 'd>(
         &mut self,
         src_dir: u32,
-        src_name: &str,
+        src_name: impl Into<FileName<'s>>,
         dst_dir: u32,
-        dst_name: &str,
+        dst_name: impl Into<FileName<'d>>,
     ) -> Ext4Result {
+        let (src_name, dst_name) = (src_name.into(), dst_name.into());
+        let ino = self.timed(
+            |m| &mut m.rename,
+            |fs| fs.undoable(|fs| fs.rename_inner(src_dir, src_name, dst_dir, dst_name)),
+        )?;
+        self.notify(ChangeEvent::Renamed {
+            src_dir,
+            src_name,
+            dst_dir,
+            dst_name,
+            ino,
+        });
+        Ok(())
+    }
+
+    /// 执行重命名，返回被移动的inode编号
+    fn rename_inner(
+        &mut self,
+        src_dir: u32,
+        src_name: FileName<'_>,
+        dst_dir: u32,
+        dst_name: FileName<'_>,
+    ) -> Ext4Result<u32> {
         let mut src_dir_ref = self.inode_ref(src_dir)?;
         let mut dst_dir_ref = self.inode_ref(dst_dir)?;
 
         // 先删除目标路径的现有文件（如果存在）
-        match self.unlink(dst_dir, dst_name) {
+        match self.unlink_inner(dst_dir, dst_name) {
             Ok(_) => {}
-            Err(err) if err.code == ENOENT as i32 => {} // 目标不存在，忽略
+            Err(err) if err.kind() == Ext4ErrorKind::NotFound => {} // 目标不存在，忽略
             Err(err) => return Err(err),
         }
 
         // 获取源文件的inode
-        let src = self.lookup(src_dir, src_name)?.entry().ino();
+        let src = self.clone_ref(&src_dir_ref).lookup(src_name)?.entry().ino();
         let mut src_ref = self.inode_ref(src)?;
+        if src_ref.is_dir() && src_dir != dst_dir {
+            dst_dir_ref.check_link_max("rename")?;
+        }
 
         // 如果是目录，更新".."指向
         if src_ref.is_dir() {
@@ -231,30 +1170,62 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
             dst_dir_ref.inc_nlink(); // 目标目录的链接数加1
         }
 
-        // 从源目录移除条目，添加到目标目录
+        // 从源目录移除条目，添加到目标目录（被移动inode的链接数不变）
+        let nlink = src_ref.nlink();
         src_dir_ref.remove_entry(src_name, &mut src_ref)?;
         dst_dir_ref.add_entry(dst_name, &mut src_ref)?;
+        src_ref.set_nlink(nlink);
 
-        Ok(())
+        Ok(src)
     }
 
     /// 创建硬链接
-    pub fn link(&mut self, dir: u32, name: &str, child: u32) -> Ext4Result {
+    pub fn link<'n>(&mut self, dir: u32, name: impl Into<FileName<'n>>, child: u32) -> Ext4Result {
+        let name = name.into();
+        self.timed(|m| &mut m.link, |fs| {
+            fs.undoable(|fs| fs.link_inner(dir, name, child))
+        })?;
+        self.notify(ChangeEvent::Created {
+            parent: dir,
+            name,
+            ino: child,
+        });
+        Ok(())
+    }
+
+    fn link_inner(&mut self, dir: u32, name: FileName<'_>, child: u32) -> Ext4Result {
+        self.check_not_reserved("link", child)?;
         let mut child_ref = self.inode_ref(child)?;
         // 不允许对目录创建硬链接
         if child_ref.is_dir() {
             return Err(Ext4Error::new(EISDIR as _, "cannot link to directory"));
         }
+        child_ref.check_link_max("link")?;
         // 在目录中添加链接条目
         self.inode_ref(dir)?.add_entry(name, &mut child_ref)?;
         Ok(())
     }
 
     /// 删除文件/目录
-    pub fn unlink(&mut self, dir: u32, name: &str) -> Ext4Result {
+    pub fn unlink<'n>(&mut self, dir: u32, name: impl Into<FileName<'n>>) -> Ext4Result {
+        let name = name.into();
+        let ino = self.timed(|m| &mut m.unlink, |fs| {
+            fs.undoable(|fs| fs.unlink_inner(dir, name))
+        })?;
+        self.notify(ChangeEvent::Removed {
+            parent: dir,
+            name,
+            ino,
+        });
+        Ok(())
+    }
+
+    /// 执行删除，返回被删除条目的inode编号
+    fn unlink_inner(&mut self, dir: u32, name: FileName<'_>) -> Ext4Result<u32> {
         let mut dir_ref = self.inode_ref(dir)?;
         // 获取要删除的子inode
         let child = self.clone_ref(&dir_ref).lookup(name)?.entry().ino();
+        self.check_not_reserved("unlink", child)?;
         let mut child_ref = self.inode_ref(child)?;
 
         // 如果是目录且非空，返回错误
@@ -271,10 +1242,10 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
         // 从目录中移除条目
         dir_ref.remove_entry(name, &mut child_ref)?;
 
-        // 更新目录链接数
+        // 更新目录链接数（删除的目录不再有任何链接）
         if child_ref.is_dir() {
             dir_ref.dec_nlink();
-            child_ref.dec_nlink();
+            child_ref.set_nlink(0);
         }
 
         // 如果链接数为0，释放inode
@@ -286,6 +1257,175 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
                 ext4_fs_free_inode(child_ref.inner.as_mut()); // 释放inode
             }
         }
+        Ok(child)
+    }
+
+    /// 删除目录中已存在的同名非目录条目（不存在时什么也不做，是目录时返回EISDIR）
+    #[cfg(any(all(feature = "std", unix), feature = "tar"))]
+    pub(crate) fn remove_non_dir<'n>(
+        &mut self,
+        parent: u32,
+        name: impl Into<FileName<'n>>,
+    ) -> Ext4Result {
+        let name = name.into();
+        let ino = match self.inode_ref(parent)?.lookup(name) {
+            Ok(mut result) => result.entry().ino(),
+            Err(err) if err.kind() == Ext4ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        if self.inode_ref(ino)?.is_dir() {
+            return Err(Ext4Error::new(EISDIR as _, None).with_context(
+                ErrorContext::new("remove_non_dir")
+                    .ino(ino)
+                    .path_segment(name),
+            ));
+        }
+        self.unlink(parent, name)
+    }
+
+    /// 按路径递归创建目录（类似mkdir -p），返回最后一级目录的inode编号
+    ///
+    /// 已存在的目录直接沿用，路径中某一级已存在但不是目录时返回ENOTDIR。
+    /// 整条路径作为一次修改：中途失败（空间不足、I/O错误、ENOTDIR等）时撤销本次已创建的
+    /// 全部目录（C 后端没有撤销日志，失败前创建的目录保留）。
+    /// 全部修改在同一个写回批次内完成，结束时统一写回设备。
+    pub fn create_dir_all(&mut self, path: &str, mode: u32) -> Ext4Result<u32> {
+        self.check_path("create_dir_all", path)?;
+        let mut created = Vec::new();
+        let ino = self.timed(
+            |m| &mut m.create,
+            |fs| {
+                fs.undoable(|fs| {
+                    let _guard = WritebackGuard::new(fs.bdev.inner.as_mut());
+                    let mut ino: u32 = EXT4_INODE_ROOT_INDEX as _;
+                    for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+                        let dir = fs.inode_ref(ino)?;
+                        if !dir.is_dir() {
+                            return Err(Ext4Error::new(ENOTDIR as _, None).with_context(
+                                ErrorContext::new("create_dir_all")
+                                    .ino(ino)
+                                    .path_segment(name),
+                            ));
+                        }
+                        ino = match dir.lookup(name) {
+                            Ok(mut result) => result.entry().ino(),
+                            Err(err) if err.kind() == Ext4ErrorKind::NotFound => {
+                                let child =
+                                    fs.create_inner(ino, name.into(), InodeType::Directory, mode)?;
+                                created.push((ino, name, child));
+                                child
+                            }
+                            Err(err) => return Err(err),
+                        };
+                    }
+
+                    if !fs.inode_ref(ino)?.is_dir() {
+                        return Err(Ext4Error::new(ENOTDIR as _, None)
+                            .with_context(ErrorContext::new("create_dir_all").ino(ino)));
+                    }
+                    Ok(ino)
+                })
+            },
+        )?;
+        for (parent, name, ino) in created {
+            self.notify(ChangeEvent::Created {
+                parent,
+                name: name.into(),
+                ino,
+            });
+        }
+        Ok(ino)
+    }
+
+    /// 压缩目录：把目录项紧凑地重写到尽量少的块中并释放其余的块，返回释放的块数
+    ///
+    /// 删除目录项不会回收目录块，大量删除之后可以调用本方法。重写后需要多个块的目录
+    /// 按哈希重建htree索引，只需一个块的目录改为线性目录；节省不了块时目录保持原样。
+    /// 目录项的readdir偏移随之改变，不应在遍历该目录的过程中调用。
+    #[cfg(feature = "use-rust")]
+    pub fn compact_dir(&mut self, ino: u32) -> Ext4Result<u32> {
+        self.undoable(|fs| {
+            let mut dir = fs.inode_ref(ino)?;
+            if !dir.is_dir() {
+                return Err(Ext4Error::new(ENOTDIR as _, None)
+                    .with_context(ErrorContext::new("compact_dir").ino(ino)));
+            }
+            dir.check_writable("compact_dir")?;
+            let _guard = WritebackGuard::new(fs.bdev.inner.as_mut());
+            let mut released = 0;
+            unsafe { ext4_dir_compact(dir.inner.as_mut(), &mut released) }
+                .with_context(|| ErrorContext::new("ext4_dir_compact").ino(ino))?;
+            Ok(released)
+        })
+    }
+
+    /// 按路径递归删除目录及其全部内容（类似rm -r）
+    ///
+    /// 用显式栈代替递归，目录深度不受调用栈限制。条目逐个删除，中途出错（包括被
+    /// on_interrupt中断）时已删除的条目不会恢复，但文件系统保持一致，再次调用可以继续删除剩余部分。
+    /// 与create_dir_all不同，整棵树不作为一次可撤销的修改：撤销日志在内存中保存操作期间
+    /// 访问过的每个块的原始内容，删除大目录树时会随树的大小无限增长；而每个条目的删除各自
+    /// 可撤销，部分删除的结果与用户中断rm -r相同，不会留下泄漏的inode或块。
+    /// 全部修改在同一个写回批次内完成，结束时统一写回设备。
+    pub fn remove_dir_all(&mut self, path: &str) -> Ext4Result {
+        self.check_path("remove_dir_all", path)?;
+        let path = path.trim_end_matches('/');
+        let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(Ext4Error::new(EINVAL as _, "cannot remove root or dot entry"));
+        }
+
+        let parent = self.resolve_path(parent_path)?;
+        let dir = self.inode_ref(parent)?;
+        if !dir.is_dir() {
+            return Err(Ext4Error::new(ENOTDIR as _, None)
+                .with_context(ErrorContext::new("remove_dir_all").ino(parent)));
+        }
+        let target = dir.lookup(name)?.entry().ino();
+        if !self.inode_ref(target)?.is_dir() {
+            return Err(Ext4Error::new(ENOTDIR as _, None).with_context(
+                ErrorContext::new("remove_dir_all")
+                    .ino(target)
+                    .path_segment(name),
+            ));
+        }
+
+        let _guard = WritebackGuard::new(self.bdev.inner.as_mut());
+        // 栈中每项为（父目录、名称、目录inode、子项是否已处理）
+        let mut stack = vec![(parent, Vec::from(name.as_bytes()), target, false)];
+        while let Some((parent, name, dir, emptied)) = stack.pop() {
+            self.check_interrupt("remove_dir_all")?;
+            if emptied {
+                // 子项已全部删除，目录已为空
+                self.unlink(parent, &name)?;
+                continue;
+            }
+
+            let mut children = Vec::new();
+            let mut reader = self.read_dir(dir, 0)?;
+            while let Some(entry) = reader.current() {
+                if !entry.file_name().is_dot() {
+                    children.push((entry.name().to_vec(), entry.ino(), entry.inode_type()));
+                }
+                reader.step()?;
+            }
+            drop(reader);
+
+            stack.push((parent, name, dir, true));
+            for (child, ino, ty) in children {
+                // 未启用filetype特性时条目中没有类型，从inode读取
+                let ty = match ty {
+                    InodeType::Unknown => self.inode_ref(ino)?.inode_type(),
+                    ty => ty,
+                };
+                if ty == InodeType::Directory {
+                    stack.push((dir, child, ino, false));
+                } else {
+                    self.check_interrupt("remove_dir_all")?;
+                    self.unlink(dir, &child)?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -306,12 +1446,261 @@ impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
     }
 
     /// 刷新缓存到磁盘
+    ///
+    /// 先写回块组描述符，写回失败时文件系统进入is_poisoned状态；最后写回superblock中修改过的计数。
     pub fn flush(&mut self) -> Ext4Result<()> {
+        self.timed(
+            |m| &mut m.flush,
+            |fs| unsafe {
+                #[cfg(feature = "use-rust")]
+                ext4_fs_commit_block_groups(fs.inner.as_mut())
+                    .context("ext4_fs_commit_block_groups")?;
+                ext4_block_cache_flush(fs.bdev.inner.as_mut()).context("ext4_cache_flush")?;
+                // 位图和描述符写回之后再写回superblock中的计数
+                #[cfg(feature = "use-rust")]
+                ext4_fs_commit_sb(fs.inner.as_mut()).context("ext4_fs_commit_sb")?;
+                Ok(())
+            },
+        )
+    }
+
+    /// 回写最多max_blocks个脏块，返回剩余的脏块数
+    ///
+    /// no_std环境下无法自行创建回写线程，由宿主的周期任务调用，每次只做有限的工作，
+    /// 不阻塞前台操作。脏块全部写回后一并写回superblock中的计数（不计入max_blocks）。
+    pub fn flush_some(&mut self, max_blocks: u32) -> Ext4Result<u32> {
+        #[cfg(not(feature = "use-rust"))]
+        {
+            let _ = max_blocks;
+            Err(Ext4Error::new(ENOTSUP as _, "flush_some requires the rust backend"))
+        }
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            let mut remaining = 0;
+            let r = ext4_block_cache_flush_some(self.bdev.inner.as_mut(), max_blocks, &mut remaining);
+            self.bdev.panic_on_write_error();
+            r.context("ext4_block_cache_flush_some")?;
+            if remaining == 0 {
+                ext4_fs_commit_sb(self.inner.as_mut()).context("ext4_fs_commit_sb")?;
+            }
+            Ok(remaining)
+        }
+    }
+
+    /// 缩减块缓存，直到缓存中的块数不超过target_blocks，返回缩减后缓存中的块数
+    ///
+    /// 供宿主在内存紧张时调用（也可以通过SystemHal::memory_pressure自动触发）：按LRU顺序
+    /// 回收干净的块，脏块先写回。正在使用和常驻缓存的块不会被回收，返回值可能高于target_blocks。
+    pub fn shrink_cache(&mut self, target_blocks: u32) -> Ext4Result<u32> {
+        #[cfg(not(feature = "use-rust"))]
+        {
+            let _ = target_blocks;
+            Err(Ext4Error::new(ENOTSUP as _, "shrink_cache requires the rust backend"))
+        }
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            let bd = self.bdev.inner.as_mut();
+            ext4_block_cache_shrink(bd, target_blocks).context("ext4_block_cache_shrink")?;
+            Ok((*bd.bc).ref_blocks)
+        }
+    }
+
+    /// 重新检查设备容量（可移动介质被更换或调整大小后调用）
+    ///
+    /// 容量变化时丢弃整个块缓存（其中的数据可能来自旧介质）并重新读取superblock：仍是挂载的
+    /// 文件系统时以介质上的superblock替换内存中的副本（块组描述符等元数据此后从介质重新读取），
+    /// 否则标记为已更换，之后访问inode返回ESTALE，需要重新挂载。设备小于文件系统时切换为只读。
+    /// 检测到变化时通知介质变化回调；容量未变时返回None。
+    ///
+    /// 缓存中还有未写回的修改时返回Busy，不做任何改变：这些修改属于哪块介质只有调用者知道，
+    /// 同一介质调整大小时先flush再重试，介质已被更换时应卸载（修改随之丢失）。
+    pub fn revalidate(&mut self) -> Ext4Result<Option<MediaChange>> {
+        let Some((old_blocks, new_blocks)) = self.bdev.probe_capacity()? else {
+            return Ok(None);
+        };
+        if self.has_unwritten_changes() {
+            return Err(Ext4Error::from_kind(
+                Ext4ErrorKind::Busy,
+                "media changed with unwritten modifications in the cache",
+            ));
+        }
+        self.bdev.set_capacity(new_blocks)?;
+        let bdev = self.bdev.inner.as_mut();
+        unsafe { ext4_bcache_cleanup(bdev.bc) };
+        #[cfg(feature = "use-rust")]
+        ext4_es_clear(&mut self.inner.es_cache);
+
+        let mut sb: ext4_sblock = unsafe { mem::zeroed() };
+        let read = unsafe { ext4_sb_read(bdev, &mut sb) }.context("ext4_sb_read");
+        let replaced = read.is_err() || !same_filesystem(&self.inner.sb, &sb);
+        if replaced {
+            warn!("media replaced, remount required");
+            self.stale = true;
+        } else {
+            self.inner.sb = sb;
+        }
+        let stat = self.stat()?;
+        let fs_bytes = stat.blocks_count * stat.block_size as u64;
+        let ph_bsize = unsafe { (*self.bdev.inner.bdif).ph_bsize } as u64;
+        let read_only = replaced || new_blocks * ph_bsize < fs_bytes;
+        if read_only {
+            warn!("device no longer holds the whole filesystem ({new_blocks} blocks), switching to read-only");
+            self.inner.read_only = true;
+        }
+        let change = MediaChange {
+            old_blocks,
+            new_blocks,
+            read_only,
+            replaced,
+        };
+        if let Some(handler) = self.media_change.as_mut() {
+            handler(&change);
+        }
+        Ok(Some(change))
+    }
+
+    /// 块缓存或内存中的superblock中是否有尚未写回设备的修改
+    fn has_unwritten_changes(&self) -> bool {
+        let bc = self.bdev.inner.bc;
+        #[cfg(feature = "use-rust")]
+        {
+            self.inner.sb_dirty || unsafe { ext4_bcache_dirty_count(bc) } != 0
+        }
+        #[cfg(not(feature = "use-rust"))]
         unsafe {
-            ext4_block_cache_flush(self.bdev.inner.as_mut()).context("ext4_cache_flush")?;
+            !(*bc).dirty_list.slh_first.is_null()
+        }
+    }
+
+    /// 设置介质变化回调（None表示移除），由revalidate在检测到容量变化时调用
+    pub fn on_media_change(&mut self, handler: Option<MediaChangeHandler>) {
+        self.media_change = handler;
+    }
+
+    /// 设置变更事件回调（None表示移除）
+    ///
+    /// 创建、删除、重命名、链接、写入和设置大小等高层接口在操作成功后调用，
+    /// 回调期间不能访问文件系统。事务中的操作在执行时即发出事件，事务中止时不会撤回。
+    pub fn on_change(&mut self, handler: Option<ChangeHandler>) {
+        self.change = handler;
+    }
+
+    /// 设置中断检查回调（None表示移除）
+    ///
+    /// remove_dir_all、defragment和check_dir_types等长时间操作在每删除一个条目、
+    /// 复制一个块或检查一个目录之前调用，返回true时操作中止并返回ECANCELED。
+    /// 回调中可以让出CPU；回调期间不能访问文件系统。已完成的步骤不会撤销，
+    /// 文件系统保持一致，再次调用可以继续。
+    pub fn on_interrupt(&mut self, handler: Option<InterruptHandler>) {
+        self.interrupt = handler;
+    }
+
+    /// 设置进度回调（None表示移除）
+    ///
+    /// check_dir_types每检查一个目录、import_tree和tar导入每处理一个条目后调用，
+    /// 回调期间不能访问文件系统。格式化的进度见mkfs_with_progress。
+    pub fn on_progress(&mut self, handler: Option<ProgressHandler>) {
+        self.progress = handler;
+    }
+
+    /// 报告进度
+    pub(crate) fn report_progress(&mut self, phase: ProgressPhase, done: u64, total: Option<u64>) {
+        if let Some(handler) = self.progress.as_mut() {
+            handler(&Progress { phase, done, total });
+        }
+    }
+
+    /// 设置长时间操作的截止时间为从现在起timeout之后（None表示取消）
+    ///
+    /// 截止时间一直有效直到被取消，过期后长时间操作在同样的检查点返回ETIMEDOUT。
+    /// 需要Hal提供单调时钟，否则返回ENOTSUP。
+    pub fn set_deadline(&mut self, timeout: Option<Duration>) -> Ext4Result<()> {
+        self.deadline = match timeout {
+            None => None,
+            Some(timeout) => match Hal::monotonic() {
+                Some(now) => Some(now.saturating_add(timeout)),
+                None => return Err(Ext4Error::from_kind(Ext4ErrorKind::Unsupported, "no monotonic clock")),
+            },
+        };
+        Ok(())
+    }
+
+    /// 长时间操作的检查点：按让出间隔让出CPU，被中断时返回ECANCELED，超过截止时间时返回ETIMEDOUT
+    pub(crate) fn check_interrupt(&mut self, op: &'static str) -> Ext4Result<()> {
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_fs_yield_point(self.inner.as_mut())
+        };
+        if self.interrupt.as_mut().is_some_and(|handler| handler()) {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Canceled, "interrupted")
+                .with_context(ErrorContext::new(op)));
+        }
+        if let (Some(deadline), Some(now)) = (self.deadline, Hal::monotonic()) {
+            if now >= deadline {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::TimedOut, "deadline exceeded")
+                    .with_context(ErrorContext::new(op)));
+            }
         }
         Ok(())
     }
+
+    /// 发出变更事件
+    fn notify(&mut self, event: ChangeEvent<'_>) {
+        if let Some(handler) = self.change.as_mut() {
+            handler(&event);
+        }
+    }
+
+    /// 发出写入事件（写入长度为0时不发出）
+    fn notify_written(&mut self, ino: u32, offset: u64, len: usize) {
+        if len > 0 {
+            self.notify(ChangeEvent::Written {
+                ino,
+                offset,
+                len: len as u64,
+            });
+        }
+    }
+
+    /// 获取运行统计（设备读写次数、缓存命中率、块分配/释放、各操作耗时等）
+    pub fn metrics(&self) -> Metrics {
+        let mut metrics = self.metrics.clone();
+        let bdif = unsafe { &*self.bdev.inner.bdif };
+        metrics.block_reads = bdif.bread_ctr as _;
+        metrics.block_writes = bdif.bwrite_ctr as _;
+        // C 实现没有维护这些计数
+        #[cfg(feature = "use-rust")]
+        {
+            let m = &self.inner.metrics;
+            metrics.cache_hits = m.cache_hits;
+            metrics.cache_misses = m.cache_misses;
+            metrics.blocks_allocated = m.blocks_allocated;
+            metrics.blocks_freed = m.blocks_freed;
+            metrics.journal_commits = m.journal_commits;
+            metrics.dirty_flushes = m.dirty_flushes;
+            metrics.extent_cache_hits = m.es_hits;
+            metrics.extent_cache_misses = m.es_misses;
+            metrics.alloc_goal_hits = m.alloc_goal_hits;
+            metrics.alloc_goal_group_hits = m.alloc_goal_group_hits;
+            metrics.alloc_goal_misses = m.alloc_goal_misses;
+            metrics.pinned_blocks = unsafe { (*self.bdev.inner.bc).pinned_blocks };
+            metrics.cached_blocks = unsafe { (*self.bdev.inner.bc).ref_blocks };
+            metrics.dirty_blocks = unsafe { ext4_bcache_dirty_count(self.bdev.inner.bc) };
+        }
+        metrics
+    }
+
+    /// 清零运行统计
+    pub fn reset_metrics(&mut self) {
+        self.metrics = Metrics::default();
+        let bdif = unsafe { &mut *self.bdev.inner.bdif };
+        bdif.bread_ctr = 0;
+        bdif.bwrite_ctr = 0;
+        #[cfg(feature = "use-rust")]
+        {
+            self.inner.metrics = Default::default();
+        }
+    }
 }
 
 /// 当文件系统实例被销毁时，释放资源
@@ -350,4 +1739,34 @@ impl Drop for WritebackGuard {
     fn drop(&mut self) {
         unsafe { ext4_block_cache_write_back(self.bdev, 0) };
     }
-}
\ No newline at end of file
+}
+
+/// 数据写入守卫：有序数据模式下，作用域内修改的元数据在离开作用域之前不会写回设备
+pub(crate) struct DataWriteGuard {
+    #[cfg_attr(not(feature = "use-rust"), allow(dead_code))]
+    bdev: *mut ext4_blockdev, // 块设备指针
+}
+
+impl DataWriteGuard {
+    /// 开始写入文件数据（C 实现没有有序数据模式，不做任何事）
+    pub fn new(bdev: *mut ext4_blockdev) -> Self {
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_block_data_begin(bdev)
+        };
+        Self { bdev }
+    }
+}
+
+/// 当数据写入守卫被销毁时，允许回写推迟的元数据
+impl Drop for DataWriteGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "use-rust")]
+        {
+            let r = unsafe { ext4_block_data_end(self.bdev) };
+            if r != 0 {
+                log::error!("ext4_block_data_end failed: {}", Ext4Error::new(r, None));
+            }
+        }
+    }
+}
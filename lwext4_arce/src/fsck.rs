@@ -0,0 +1,84 @@
+//! 在线一致性检查模块：检查目录项的类型字节与目标inode的文件类型是否一致，并可就地修复。
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    BlockDevice, Ext4Filesystem, Ext4Result, InodeType, ProgressPhase, SystemHal,
+    error::Context,
+    ffi::*,
+    inode::{RawDirEntry, dirent_type},
+};
+
+/// 类型字节与目标inode不一致的目录项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirTypeMismatch {
+    pub dir: u32,            // 所在目录的inode号
+    pub name: String,        // 目录项名称（非UTF-8字节按有损方式转换）
+    pub ino: u32,            // 目录项指向的inode号
+    pub found: InodeType,    // 目录项中记录的类型
+    pub expected: InodeType, // 由inode模式得到的类型（未启用filetype特性时为Unknown）
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 检查所有目录项的类型字节，返回不一致的目录项
+    ///
+    /// repair为真时同时把类型字节改为正确的值（要求可写挂载）；
+    /// htree根块中的"."和".."只报告不修复。每检查一个目录之前检查一次中断（见on_interrupt）
+    /// 并报告进度（ProgressPhase::CheckDirs，总数取自块组描述符中的目录数）。
+    pub fn check_dir_types(&mut self, repair: bool) -> Ext4Result<Vec<DirTypeMismatch>> {
+        if repair {
+            self.ensure_writable()?;
+        }
+        let mut mismatches = Vec::new();
+        let mut interrupted = None;
+        let fs: *mut ext4_fs = self.inner.as_mut();
+        let sb: *const ext4_sblock = unsafe { &(*fs).sb };
+        let total = used_dirs(fs)?;
+        let mut checked = 0;
+        let mut visit = |_| match self.check_interrupt("check_dir_types") {
+            Ok(()) => {
+                self.report_progress(ProgressPhase::CheckDirs, checked, Some(total));
+                checked += 1;
+                EOK
+            }
+            Err(err) => {
+                let errno = err.errno();
+                interrupted = Some(err);
+                errno
+            }
+        };
+        let ret = unsafe {
+            ext4_fs_check_dir_types(fs, repair, &mut visit, &mut |dir, de, expected| {
+                let entry = &*(de as *const RawDirEntry);
+                mismatches.push(DirTypeMismatch {
+                    dir,
+                    name: String::from_utf8_lossy(entry.name(&*sb)).into(),
+                    ino: entry.ino(),
+                    found: entry.inode_type(&*sb),
+                    expected: dirent_type(expected),
+                });
+            })
+        };
+        if let Some(err) = interrupted {
+            return Err(err);
+        }
+        ret.context("ext4_fs_check_dir_types")?;
+        self.report_progress(ProgressPhase::CheckDirs, checked, Some(total.max(checked)));
+        Ok(mismatches)
+    }
+}
+
+/// 块组描述符中记录的目录总数（作为检查进度的总数）
+fn used_dirs(fs: *mut ext4_fs) -> Ext4Result<u64> {
+    let mut total = 0;
+    unsafe {
+        let sb = &(*fs).sb;
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut bg_ref = ext4_block_group_ref::new();
+            ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref).context("ext4_fs_get_block_group_ref")?;
+            total += ext4_bg_get_used_dirs_count(&*bg_ref.block_group, sb) as u64;
+            ext4_fs_put_block_group_ref(&mut bg_ref).context("ext4_fs_put_block_group_ref")?;
+        }
+    }
+    Ok(total)
+}
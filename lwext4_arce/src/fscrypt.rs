@@ -0,0 +1,104 @@
+//! fscrypt加密支持模块：识别加密inode、解析加密策略，并预留提供主密钥的接口。
+//!
+//! 目前不实现解密：读写加密inode的内容或目录项时返回Ext4ErrorKind::Encrypted，
+//! 即使KeyProvider能提供密钥也是如此。
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, SystemHal, ffi::*};
+
+/// 加密上下文在扩展属性中的名称（位于EXT4_XATTR_INDEX_ENCRYPTION索引下）
+const FSCRYPT_CONTEXT_NAME: &str = "c";
+/// 加密上下文版本及长度
+const FSCRYPT_CONTEXT_V1: u8 = 1;
+const FSCRYPT_CONTEXT_V1_SIZE: usize = 28;
+const FSCRYPT_CONTEXT_V2: u8 = 2;
+const FSCRYPT_CONTEXT_V2_SIZE: usize = 40;
+
+/// 主密钥标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterKeySpec {
+    /// v1策略：8字节的密钥描述符
+    Descriptor([u8; 8]),
+    /// v2策略：16字节的密钥标识符
+    Identifier([u8; 16]),
+}
+
+/// inode的加密策略（由扩展属性中的加密上下文解析而来）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncryptionPolicy {
+    pub version: u8,        // 上下文版本（1或2）
+    pub contents_mode: u8,  // 文件内容加密算法（FSCRYPT_MODE_*）
+    pub filenames_mode: u8, // 文件名加密算法（FSCRYPT_MODE_*）
+    pub flags: u8,          // 策略标志（FSCRYPT_POLICY_FLAG_*）
+    pub key: MasterKeySpec, // 主密钥标识
+    pub nonce: [u8; 16],    // 每个inode独有的随机数（用于派生文件密钥）
+}
+
+impl EncryptionPolicy {
+    /// 解析加密上下文（版本或长度不符时返回None）
+    pub fn parse(ctx: &[u8]) -> Option<Self> {
+        let (key, nonce) = match (ctx.first()?, ctx.len()) {
+            (&FSCRYPT_CONTEXT_V1, FSCRYPT_CONTEXT_V1_SIZE) => (
+                MasterKeySpec::Descriptor(ctx[4..12].try_into().ok()?),
+                &ctx[12..28],
+            ),
+            (&FSCRYPT_CONTEXT_V2, FSCRYPT_CONTEXT_V2_SIZE) => (
+                MasterKeySpec::Identifier(ctx[8..24].try_into().ok()?),
+                &ctx[24..40],
+            ),
+            _ => return None,
+        };
+        Some(EncryptionPolicy {
+            version: ctx[0],
+            contents_mode: ctx[1],
+            filenames_mode: ctx[2],
+            flags: ctx[3],
+            key,
+            nonce: nonce.try_into().ok()?,
+        })
+    }
+}
+
+/// 主密钥来源（为将来的解密支持预留）
+pub trait KeyProvider {
+    /// 返回指定主密钥的原始内容（没有该密钥时返回None）
+    fn master_key(&self, key: &MasterKeySpec) -> Option<Vec<u8>>;
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// inode是否为fscrypt加密的inode
+    pub fn is_encrypted(&mut self, ino: u32) -> Ext4Result<bool> {
+        Ok(self.inode_ref(ino)?.is_encrypted())
+    }
+
+    /// 读取inode的加密策略（未加密时返回None）
+    ///
+    /// 设置了加密标志但加密上下文缺失或无法解析时返回EIO。
+    pub fn encryption_policy(&mut self, ino: u32) -> Ext4Result<Option<EncryptionPolicy>> {
+        if !self.is_encrypted(ino)? {
+            return Ok(None);
+        }
+        let ctx = self.xattr_raw(ino, EXT4_XATTR_INDEX_ENCRYPTION, FSCRYPT_CONTEXT_NAME)?;
+        ctx.as_deref()
+            .and_then(EncryptionPolicy::parse)
+            .map(Some)
+            .ok_or_else(|| Ext4Error::new(EIO as _, "bad fscrypt context"))
+    }
+
+    /// 设置主密钥来源（None表示移除）
+    pub fn set_key_provider(&mut self, provider: Option<Box<dyn KeyProvider>>) {
+        self.key_provider = provider;
+    }
+
+    /// 向KeyProvider查询inode的主密钥（未加密、未设置来源或没有该密钥时返回None）
+    pub fn master_key(&mut self, ino: u32) -> Ext4Result<Option<Vec<u8>>> {
+        let Some(policy) = self.encryption_policy(ino)? else {
+            return Ok(None);
+        };
+        Ok(self
+            .key_provider
+            .as_ref()
+            .and_then(|provider| provider.master_key(&policy.key)))
+    }
+}
@@ -0,0 +1,184 @@
+//! 模糊测试入口（仅fuzz特性启用时）：把任意字节写入镜像中的特定磁盘结构后挂载并遍历，
+//! 检查解析不合法的磁盘数据时不会panic、越界访问或陷入死循环。
+//!
+//! 各入口供仓库中fuzz目录下的cargo-fuzz目标调用，也可以在普通测试中用固定输入回放崩溃用例。
+//! 被覆盖的结构包括superblock、块组描述符、extent树节点和目录块；
+//! 模板镜像关闭元数据校验和，使修改后的数据能到达解析代码而不是在校验和检查处被拒绝。
+
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::{
+    DummyHal, Ext4Filesystem, Ext4Result, FsConfig, InodeType, MkfsOptions, RamDisk, RandomSource,
+    WalkDir, error::Context, ffi::*,
+};
+
+/// 模板镜像大小
+const TEMPLATE_SIZE: usize = 2 << 20;
+
+/// 模板镜像的块大小（1KiB块使superblock、块组描述符分别位于不同的块）
+const TEMPLATE_BLOCK_SIZE: u32 = 1024;
+
+/// 遍历时最多访问的条目数（避免损坏的目录形成的环使单个输入运行过久）
+const MAX_ENTRIES: usize = 256;
+
+/// 每个文件最多读取的字节数
+const MAX_READ: u64 = 64 << 10;
+
+/// 固定种子的随机数来源，使模板镜像在每次构造时完全相同
+struct FixedRandom(u64);
+
+impl RandomSource for FixedRandom {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            *b = self.0 as u8;
+        }
+    }
+}
+
+type FuzzFs<'a> = Ext4Filesystem<DummyHal, &'a mut RamDisk>;
+
+/// 模板镜像：包含目录、普通文件、符号链接和深度为1的extent树，并记录各被测结构的位置
+#[derive(Debug, Clone)]
+pub struct Template {
+    image: Vec<u8>,     // 镜像内容
+    gdt: usize,         // 块组描述符表的字节偏移
+    dir_block: usize,   // 根目录第一个数据块的字节偏移
+    extent_leaf: usize, // 多extent文件的extent叶子节点的字节偏移
+}
+
+impl Template {
+    /// 格式化并填充模板镜像
+    pub fn new() -> Ext4Result<Self> {
+        let mut disk = RamDisk::new(TEMPLATE_SIZE);
+        let opts = MkfsOptions {
+            block_size: TEMPLATE_BLOCK_SIZE,
+            journal: false,
+            metadata_csum: false,
+            uuid: Some([0x5a; 16]),
+            ..Default::default()
+        };
+        let bs = TEMPLATE_BLOCK_SIZE as usize;
+        let (gdt, dir_block, extent_leaf) = {
+            let mut fs = FuzzFs::mkfs(&mut disk, &opts, FsConfig::default(), &mut FixedRandom(1))?;
+            let root = EXT4_INODE_ROOT_INDEX;
+            let dir = fs.create(root, "dir", InodeType::Directory, 0o755)?;
+            let file = fs.create(dir, "file", InodeType::RegularFile, 0o644)?;
+            fs.write_at(file, b"hello fuzz\n", 0)?;
+            let link = fs.create(root, "link", InodeType::Symlink, 0o777)?;
+            fs.set_symlink(link, b"dir/file")?;
+
+            // 每隔一块写入一块，extent数超过inode内根节点的容量，树深度变为1
+            let fragmented = fs.create(root, "fragmented", InodeType::RegularFile, 0o644)?;
+            for i in 0..8u64 {
+                fs.write_at_sparse(fragmented, &vec![i as u8; bs], i * 2 * bs as u64)?;
+            }
+
+            let gdt = fs.group_layout(0)?.gdt.start as usize * bs;
+            let mut root_ref = fs.inode_ref(root)?;
+            let mut dir_block = 0u64;
+            unsafe { ext4_fs_get_inode_dblk_idx(root_ref.inner.as_mut(), 0, &mut dir_block, true) }
+                .context("ext4_fs_get_inode_dblk_idx")?;
+            drop(root_ref);
+            let frag_ref = fs.inode_ref(fragmented)?;
+            let i_block = unsafe { (*frag_ref.inner.inode).blocks };
+            // 根节点的第一个索引项：ei_block, ei_leaf_lo, ei_leaf_hi
+            let leaf = u32::from_le(i_block[4]) as u64 | ((u32::from_le(i_block[5]) & 0xFFFF) as u64) << 32;
+            (gdt, dir_block as usize * bs, leaf as usize * bs)
+        };
+        Ok(Self {
+            image: disk.into_inner(),
+            gdt,
+            dir_block,
+            extent_leaf,
+        })
+    }
+
+    /// 模板镜像内容
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// 用data覆盖superblock后挂载并遍历
+    pub fn superblock(&self, data: &[u8]) {
+        self.patch_and_exercise(EXT4_SUPERBLOCK_OFFSET as usize, EXT4_SUPERBLOCK_SIZE, data);
+    }
+
+    /// 用data覆盖块组描述符表的第一个块后挂载并遍历
+    pub fn group_desc(&self, data: &[u8]) {
+        self.patch_and_exercise(self.gdt, TEMPLATE_BLOCK_SIZE as usize, data);
+    }
+
+    /// 用data覆盖extent叶子节点后挂载并遍历
+    pub fn extent_node(&self, data: &[u8]) {
+        self.patch_and_exercise(self.extent_leaf, TEMPLATE_BLOCK_SIZE as usize, data);
+    }
+
+    /// 用data覆盖根目录的第一个数据块后挂载并遍历
+    pub fn dir_block(&self, data: &[u8]) {
+        self.patch_and_exercise(self.dir_block, TEMPLATE_BLOCK_SIZE as usize, data);
+    }
+
+    /// 把data写到镜像的[offset, offset + max_len)处（超出部分忽略），然后挂载并遍历
+    fn patch_and_exercise(&self, offset: usize, max_len: usize, data: &[u8]) {
+        let mut image = self.image.clone();
+        let len = data.len().min(max_len);
+        image[offset..offset + len].copy_from_slice(&data[..len]);
+        exercise(&mut RamDisk::from_vec(image));
+    }
+}
+
+/// 以data作为整个镜像挂载并遍历（长度不是扇区整数倍时末尾补0）
+pub fn mount_and_walk(data: &[u8]) {
+    let mut image = data.to_vec();
+    image.resize(data.len().next_multiple_of(EXT4_DEV_BSIZE), 0);
+    exercise(&mut RamDisk::from_vec(image));
+}
+
+/// 挂载设备，遍历目录树并读取各文件，再做少量修改；所有错误都被忽略
+fn exercise(disk: &mut RamDisk) {
+    let Ok(mut fs) = FuzzFs::new(disk, FsConfig::default()) else {
+        return;
+    };
+    let _ = fs.stat();
+    let _ = fs.check_layout();
+
+    let mut entries = Vec::new();
+    if let Ok(walk) = WalkDir::new(&mut fs, "/") {
+        entries.extend(walk.take(MAX_ENTRIES).flatten());
+    }
+    let mut buf = vec![0u8; 4096];
+    for entry in &entries {
+        let _ = fs.list_xattr(entry.ino);
+        match entry.inode_type {
+            InodeType::RegularFile => {
+                let mut offset = 0;
+                while offset < MAX_READ {
+                    match fs.read_at(entry.ino, &mut buf, offset) {
+                        Ok(n) if n > 0 => offset += n as u64,
+                        _ => break,
+                    }
+                }
+            }
+            InodeType::Symlink => {
+                let _ = fs.read_link(entry.ino);
+            }
+            _ => {}
+        }
+    }
+
+    // 修改路径同样要经受损坏的位图和目录
+    let root = EXT4_INODE_ROOT_INDEX;
+    if let Ok(ino) = fs.create(root, "fuzz-new", InodeType::RegularFile, 0o644) {
+        let _ = fs.write_at(ino, &buf, 0);
+        let _ = fs.set_len(ino, 1);
+    }
+    let _ = fs.unlink(root, "fuzz-new");
+    if let Some(entry) = entries.iter().find(|e| e.depth == 1) {
+        let name = String::from(entry.path.trim_start_matches('/'));
+        let _ = fs.unlink(root, name.as_str());
+    }
+    let _ = fs.flush();
+}
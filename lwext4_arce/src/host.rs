@@ -0,0 +1,306 @@
+//! 宿主目录树与镜像之间的批量导入/导出（类似cp -a），保留权限、时间戳、符号链接和硬链接，
+//! 供构建系统直接用Rust填充根文件系统镜像。
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File, FileTimes, Metadata},
+    io::{self, Read, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+    vec,
+};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4ErrorKind, Ext4Filesystem, Ext4Result, FileAttr, FileName,
+    InodeType, ProgressPhase, SystemHal,
+    error::ErrorContext,
+    ffi::EIO,
+};
+
+/// 文件内容的复制缓冲区大小
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+/// 将宿主I/O错误转换为Ext4Error
+fn io_error<'a>(op: &'static str, path: &'a Path) -> impl FnOnce(io::Error) -> Ext4Error + 'a {
+    move |err| {
+        let name = path.file_name().map(OsStr::as_bytes).unwrap_or_default();
+        Ext4Error::new(err.raw_os_error().unwrap_or(EIO as _), None)
+            .with_context(ErrorContext::new(op).path_segment(name))
+    }
+}
+
+
+/// 拆分镜像路径的父目录和最后一级名称
+fn split_parent(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 将宿主路径（目录或单个文件）导入到镜像路径
+    ///
+    /// 目录递归导入，镜像中已存在的目录沿用，同名的非目录条目被替换。保留权限位、属主、
+    /// 时间戳、符号链接、硬链接和设备号（字符设备和块设备）。文件数据以稀疏方式写入，
+    /// 全零的块保留为空洞。每导入一个条目后报告进度（ProgressPhase::Import，见on_progress）。
+    pub fn import_tree(&mut self, host: impl AsRef<Path>, image_path: &str) -> Ext4Result<()> {
+        let host = host.as_ref();
+        let meta = fs::symlink_metadata(host).map_err(io_error("import_tree", host))?;
+        let mut hardlinks = HashMap::new();
+
+        if !meta.is_dir() {
+            let (parent, name) = split_parent(image_path);
+            let parent = self.resolve_path(parent)?;
+            self.import_node(parent, name.into(), host, &meta, &mut hardlinks)?;
+            self.report_progress(ProgressPhase::Import, 1, None);
+            return self.flush();
+        }
+
+        let root = self.create_dir_all(image_path, meta.mode())?;
+        let mut stack = vec![(host.to_path_buf(), root)];
+        let mut dirs = vec![(root, meta)];
+        let mut imported = 0;
+        while let Some((dir, ino)) = stack.pop() {
+            for entry in fs::read_dir(&dir).map_err(io_error("read_dir", &dir))? {
+                let entry = entry.map_err(io_error("read_dir", &dir))?;
+                let path = entry.path();
+                let name = entry.file_name();
+                let name = FileName::new(name.as_bytes());
+                let meta =
+                    fs::symlink_metadata(&path).map_err(io_error("symlink_metadata", &path))?;
+                if meta.is_dir() {
+                    let child = self.import_dir(ino, name)?;
+                    stack.push((path, child));
+                    dirs.push((child, meta));
+                } else {
+                    self.import_node(ino, name, &path, &meta, &mut hardlinks)?;
+                }
+                imported += 1;
+                self.report_progress(ProgressPhase::Import, imported, None);
+            }
+        }
+
+        // 目录的属性最后设置，避免填充子项时修改其时间戳
+        for (ino, meta) in dirs.iter().rev() {
+            self.set_host_attr(*ino, meta)?;
+        }
+        self.flush()
+    }
+
+    /// 在镜像目录中获取或创建子目录（同名的非目录条目被替换）
+    fn import_dir(&mut self, parent: u32, name: FileName<'_>) -> Ext4Result<u32> {
+        match self.lookup(parent, name) {
+            Ok(mut result) => {
+                let ino = result.entry().ino();
+                drop(result);
+                let mut attr = FileAttr::default();
+                self.get_attr(ino, &mut attr)?;
+                if attr.node_type == InodeType::Directory {
+                    return Ok(ino);
+                }
+                self.unlink(parent, name)?;
+            }
+            Err(err) if err.kind() == Ext4ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        self.create(parent, name, InodeType::Directory, 0o755)
+    }
+
+    /// 导入单个非目录节点
+    fn import_node(
+        &mut self,
+        parent: u32,
+        name: FileName<'_>,
+        path: &Path,
+        meta: &Metadata,
+        hardlinks: &mut HashMap<(u64, u64), u32>,
+    ) -> Ext4Result<()> {
+        let ty = meta.file_type();
+        let node_type = if ty.is_file() {
+            InodeType::RegularFile
+        } else if ty.is_symlink() {
+            InodeType::Symlink
+        } else if ty.is_fifo() {
+            InodeType::Fifo
+        } else if ty.is_socket() {
+            InodeType::Socket
+        } else if ty.is_char_device() {
+            InodeType::CharacterDevice
+        } else if ty.is_block_device() {
+            InodeType::BlockDevice
+        } else {
+            warn!("import_tree: skipping unknown file type {}", path.display());
+            return Ok(());
+        };
+
+        // 替换已存在的同名条目
+        self.remove_non_dir(parent, name)?;
+
+        // 宿主上的硬链接在镜像中也建立为硬链接
+        let key = (meta.dev(), meta.ino());
+        if meta.nlink() > 1 {
+            if let Some(&ino) = hardlinks.get(&key) {
+                return self.link(parent, name, ino);
+            }
+        }
+
+        let ino = match node_type {
+            InodeType::RegularFile | InodeType::Symlink => {
+                self.create(parent, name, node_type, meta.mode())?
+            }
+            _ => self.mknod_at(parent, name, node_type, meta.mode(), meta.rdev())?,
+        };
+        match node_type {
+            InodeType::RegularFile => {
+                let mut file = File::open(path).map_err(io_error("open", path))?;
+                let mut buf = vec![0; COPY_BUF_SIZE];
+                let mut offset = 0;
+                loop {
+                    let n = file.read(&mut buf).map_err(io_error("read", path))?;
+                    if n == 0 {
+                        break;
+                    }
+                    self.write_at_sparse(ino, &buf[..n], offset)?;
+                    offset += n as u64;
+                }
+            }
+            InodeType::Symlink => {
+                let target = fs::read_link(path).map_err(io_error("read_link", path))?;
+                self.set_symlink(ino, target.as_os_str().as_bytes())?;
+            }
+            _ => {}
+        }
+        self.set_host_attr(ino, meta)?;
+
+        if meta.nlink() > 1 {
+            hardlinks.insert(key, ino);
+        }
+        Ok(())
+    }
+
+    /// 将宿主文件的权限位、属主和时间戳写入镜像inode
+    fn set_host_attr(&mut self, ino: u32, meta: &Metadata) -> Ext4Result<()> {
+        self.with_inode_ref(ino, |inode| {
+            inode.set_mode((inode.mode() & !0o7777) | (meta.mode() & 0o7777));
+            inode.set_owner(meta.uid(), meta.gid());
+            inode.set_atime(&Duration::new(meta.atime() as _, meta.atime_nsec() as _));
+            inode.set_mtime(&Duration::new(meta.mtime() as _, meta.mtime_nsec() as _));
+            inode.set_ctime(&Duration::new(meta.ctime() as _, meta.ctime_nsec() as _));
+            Ok(())
+        })
+    }
+
+    /// 将镜像路径（目录或单个文件）导出到宿主路径
+    ///
+    /// 目录递归导出，宿主上已存在的同名文件被覆盖。保留权限位、访问/修改时间、符号链接和
+    /// 硬链接；属主需要特权，不做设置；设备、管道和套接字节点被跳过。
+    pub fn export_tree(&mut self, image_path: &str, host: impl AsRef<Path>) -> Ext4Result<()> {
+        let host = host.as_ref();
+        let root = self.resolve_path(image_path)?;
+        let mut attr = FileAttr::default();
+        self.get_attr(root, &mut attr)?;
+        let mut hardlinks = HashMap::new();
+
+        if attr.node_type != InodeType::Directory {
+            return self.export_node(&attr, host, &mut hardlinks);
+        }
+
+        fs::create_dir_all(host).map_err(io_error("create_dir_all", host))?;
+        let mut stack = vec![(root, host.to_path_buf())];
+        let mut dirs = vec![(host.to_path_buf(), attr)];
+        while let Some((ino, dir)) = stack.pop() {
+            // 先读出整个目录，再逐个导出
+            let page = self.read_dir_page(ino, 0, usize::MAX, &mut |e| {
+                e.name() != b"." && e.name() != b".."
+            })?;
+            for entry in page.entries {
+                let path = dir.join(OsStr::from_bytes(&entry.name));
+                let mut attr = FileAttr::default();
+                self.get_attr(entry.ino, &mut attr)?;
+                if attr.node_type == InodeType::Directory {
+                    match fs::create_dir(&path) {
+                        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => {
+                            return Err(io_error("create_dir", &path)(err));
+                        }
+                        _ => {}
+                    }
+                    stack.push((entry.ino, path.clone()));
+                    dirs.push((path, attr));
+                } else {
+                    self.export_node(&attr, &path, &mut hardlinks)?;
+                }
+            }
+        }
+
+        // 目录的属性最后设置，避免只读目录无法写入子项
+        for (path, attr) in dirs.iter().rev() {
+            set_host_attr(path, attr)?;
+        }
+        Ok(())
+    }
+
+    /// 导出单个非目录节点
+    fn export_node(
+        &mut self,
+        attr: &FileAttr,
+        path: &Path,
+        hardlinks: &mut HashMap<u32, PathBuf>,
+    ) -> Ext4Result<()> {
+        if !matches!(attr.node_type, InodeType::RegularFile | InodeType::Symlink) {
+            warn!("export_tree: skipping special file {}", path.display());
+            return Ok(());
+        }
+        match fs::symlink_metadata(path) {
+            Ok(_) => fs::remove_file(path).map_err(io_error("remove_file", path))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(io_error("symlink_metadata", path)(err)),
+        }
+
+        if attr.nlink > 1 {
+            if let Some(first) = hardlinks.get(&attr.ino) {
+                return fs::hard_link(first, path).map_err(io_error("hard_link", path));
+            }
+        }
+
+        let mut buf = vec![0; COPY_BUF_SIZE];
+        if attr.node_type == InodeType::Symlink {
+            let target = self.read_link(attr.ino)?;
+            std::os::unix::fs::symlink(OsStr::from_bytes(&target), path)
+                .map_err(io_error("symlink", path))?;
+        } else {
+            let mut file = File::create(path).map_err(io_error("create", path))?;
+            let mut offset = 0;
+            loop {
+                let n = self.read_at(attr.ino, &mut buf, offset)?;
+                if n == 0 {
+                    break;
+                }
+                file.write_all(&buf[..n]).map_err(io_error("write", path))?;
+                offset += n as u64;
+            }
+            set_host_attr(path, attr)?;
+        }
+
+        if attr.nlink > 1 {
+            hardlinks.insert(attr.ino, path.to_path_buf());
+        }
+        Ok(())
+    }
+}
+
+/// 将镜像inode的权限位和访问/修改时间写入宿主文件或目录
+fn set_host_attr(path: &Path, attr: &FileAttr) -> Ext4Result<()> {
+    let file = File::open(path).map_err(io_error("open", path))?;
+    file.set_times(
+        FileTimes::new()
+            .set_accessed(UNIX_EPOCH + attr.atime)
+            .set_modified(UNIX_EPOCH + attr.mtime),
+    )
+    .map_err(io_error("set_times", path))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(attr.mode & 0o7777))
+        .map_err(io_error("set_permissions", path))
+}
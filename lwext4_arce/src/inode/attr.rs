@@ -1,8 +1,11 @@
 //! 该模块实现inode属性（元数据）的读写操作，如权限、大小、时间戳等。
 
-use core::time::Duration;
+use core::{mem::offset_of, time::Duration};
 
-use crate::{SystemHal, ffi::*, util::get_block_size};
+use crate::{
+    Ext4Error, Ext4ErrorKind, Ext4Result, SystemHal, error::ErrorContext, ffi::*,
+    util::get_block_size,
+};
 
 use super::{InodeRef, InodeType};
 
@@ -29,6 +32,8 @@ pub struct FileAttr {
     pub block_size: u64,
     /// 分配的512B块数量
     pub blocks: u64,
+    /// 设备号（字符设备和块设备，与libc的dev_t编码相同，其他类型为0）
+    pub rdev: u64,
 
     /// 最后访问时间
     pub atime: Duration,
@@ -36,26 +41,72 @@ pub struct FileAttr {
     pub mtime: Duration,
     /// 最后状态修改时间
     pub ctime: Duration,
+    /// 创建时间（inode的扩展部分没有crtime字段时为None）
+    pub btime: Option<Duration>,
 }
 
+// 各时间扩展字段在inode中的结束偏移，超出扩展部分（128 + extra_isize）的字段不存在
+const CTIME_EXTRA_END: usize = offset_of!(ext4_inode, ctime_extra) + 4;
+const MTIME_EXTRA_END: usize = offset_of!(ext4_inode, mtime_extra) + 4;
+const ATIME_EXTRA_END: usize = offset_of!(ext4_inode, atime_extra) + 4;
+const CRTIME_END: usize = offset_of!(ext4_inode, crtime) + 4;
+const CRTIME_EXTRA_END: usize = offset_of!(ext4_inode, crtime_extra) + 4;
+
 /// 将Duration转换为ext4存储的时间格式（秒+纳秒/扩展秒）
+///
+/// 与Linux相同，秒的低32位按有符号数解释，扩展字段的低2位（epoch）是在此基础上以2^32为单位的偏移，
+/// 因此2038~2106年之间的时间epoch为1。
 fn encode_time(dur: &Duration) -> (u32, u32) {
-    let sec = dur.as_secs();
+    let sec = dur.as_secs() as i64;
     let nsec = dur.subsec_nanos();
     let time = u32::to_le(sec as u32); // 秒部分（小端存储）
-    // 纳秒左移2位 + 秒的高位（超过32位的部分）
-    let extra = u32::to_le((nsec << 2) | (sec >> 32) as u32);
+    let epoch = ((sec - sec as i32 as i64) >> 32) as u32 & 3;
+    // 纳秒左移2位 + epoch
+    let extra = u32::to_le((nsec << 2) | epoch);
     (time, extra)
 }
 
-/// 将ext4存储的时间格式转换为Duration
+/// 将ext4存储的时间格式转换为Duration（1970年以前的时间截断为0）
 fn decode_time(time: u32, extra: u32) -> Duration {
-    let sec = u32::from_le(time); // 秒部分（从小端读取）
+    let time = u32::from_le(time) as i32; // 秒部分（从小端读取，有符号）
     let extra = u32::from_le(extra);
-    let epoch = extra & 3; // 秒的高位（超过32位的部分）
+    let epoch = extra & 3; // 以2^32秒为单位的偏移
     let nsec = extra >> 2; // 纳秒部分
 
-    Duration::new(sec as u64 + ((epoch as u64) << 32), nsec)
+    let sec = time as i64 + ((epoch as i64) << 32);
+    if sec < 0 {
+        return Duration::ZERO;
+    }
+    Duration::new(sec as u64, nsec)
+}
+
+/// ext4能表示的最大主设备号（12位）
+const DEV_MAJOR_MAX: u64 = 0xfff;
+/// ext4能表示的最大次设备号（20位）
+const DEV_MINOR_MAX: u64 = 0xfffff;
+
+/// 将libc编码的dev_t转换为ext4的设备号编码，主次设备号超出ext4的表示范围时返回None
+fn encode_dev(rdev: u64) -> Option<u32> {
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & !0xff);
+    if major > DEV_MAJOR_MAX || minor > DEV_MINOR_MAX {
+        return None;
+    }
+    Some(((minor & 0xff) | (major << 8) | ((minor & !0xff) << 12)) as u32)
+}
+
+/// 将ext4的设备号编码转换为libc编码的dev_t
+fn decode_dev(dev: u32) -> u64 {
+    makedev((dev & 0xfff00) >> 8, (dev & 0xff) | ((dev >> 12) & 0xfff00))
+}
+
+/// 由主次设备号得到设备号（与libc的makedev相同的dev_t编码）
+pub fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xfff) << 8)
+        | ((major & !0xfff) << 32)
+        | (minor & 0xff)
+        | ((minor & !0xff) << 12)
 }
 
 impl<Hal: SystemHal> InodeRef<Hal> {
@@ -85,81 +136,167 @@ impl<Hal: SystemHal> InodeRef<Hal> {
         }
     }
 
-    /// 设置权限模式（仅保留低9位，即0o777范围）
+    /// 设置权限模式（只修改低12位的权限位，文件类型位保持不变，避免与目录项的类型不一致）
     pub fn set_mode(&mut self, mode: u32) {
         unsafe {
+            let mode = (self.mode() & !0o7777) | (mode & 0o7777);
             ext4_inode_set_mode(self.superblock_mut(), self.inner.inode, mode);
             self.mark_dirty(); // 标记为脏
         }
     }
 
+    /// 是否为设备文件（字符设备或块设备）
+    pub fn is_device(&self) -> bool {
+        matches!(
+            self.inode_type(),
+            InodeType::CharacterDevice | InodeType::BlockDevice
+        )
+    }
+
+    /// 获取设备号（与libc的dev_t编码相同，非设备文件返回0）
+    pub fn rdev(&self) -> u64 {
+        if !self.is_device() {
+            return 0;
+        }
+        decode_dev(unsafe { ext4_inode_get_dev(self.inner.inode) })
+    }
+
+    /// 设置设备文件的设备号（主设备号超过12位或次设备号超过20位时返回InvalidInput）
+    pub(crate) fn set_rdev(&mut self, op: &'static str, rdev: u64) -> Ext4Result {
+        let Some(dev) = encode_dev(rdev).filter(|_| self.is_device()) else {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, None)
+                .with_context(ErrorContext::new(op).ino(self.ino())));
+        };
+        unsafe { ext4_inode_set_dev(self.inner.inode, dev) };
+        self.mark_dirty();
+        Ok(())
+    }
+
     /// 获取硬链接计数
     pub fn nlink(&self) -> u16 {
         u16::from_le(self.raw_inode().links_count) // 从小端读取
     }
 
-    /// 获取所有者用户ID
-    pub fn uid(&self) -> u16 {
-        u16::from_le(self.raw_inode().uid)
+    /// 获取inode版本号（inode编号每次被重新分配时递增）
+    pub fn generation(&self) -> u32 {
+        u32::from_le(self.raw_inode().generation)
     }
 
-    /// 获取所有者组ID
-    pub fn gid(&self) -> u16 {
-        u16::from_le(self.raw_inode().gid)
+    /// 获取所有者用户ID（32位，高16位存放在osd2中）
+    pub fn uid(&self) -> u32 {
+        unsafe { ext4_inode_get_uid(self.inner.inode) }
     }
 
-    /// 设置所有者用户ID和组ID
-    pub fn set_owner(&mut self, uid: u16, gid: u16) {
-        let inode = self.raw_inode_mut();
-        inode.uid = u16::to_le(uid); // 转换为小端存储
-        inode.gid = u16::to_le(gid);
+    /// 获取所有者组ID（32位，高16位存放在osd2中）
+    pub fn gid(&self) -> u32 {
+        unsafe { ext4_inode_get_gid(self.inner.inode) }
+    }
+
+    /// 设置所有者用户ID和组ID（同时写入低16位和高16位）
+    ///
+    /// 启用了配额用量统计时，inode的用量随之转移到新的属主。
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_quota_set_owner(self.inner.as_mut(), uid, gid);
+        }
+        #[cfg(not(feature = "use-rust"))]
+        unsafe {
+            ext4_inode_set_uid(self.inner.inode, uid);
+            ext4_inode_set_gid(self.inner.inode, gid);
+        }
         self.mark_dirty();
     }
 
-    /// 设置最后访问时间
+    /// 字段是否位于inode的扩展部分之内（对应EXT4_FITS_IN_INODE），end为字段的结束偏移
+    ///
+    /// 128字节的inode以及extra_isize较小的inode没有纳秒和创建时间字段，
+    /// 这些位置属于相邻的inode或扩展属性区，不能读写。
+    fn fits_in_inode(&self, end: usize) -> bool {
+        let extra = unsafe {
+            ext4_inode_get_extra_isize(self.superblock() as *const _ as _, self.inner.inode)
+        };
+        end <= EXT4_GOOD_OLD_INODE_SIZE as usize + extra as usize
+    }
+
+    /// 设置最后访问时间（inode没有扩展字段时只保存秒）
     pub fn set_atime(&mut self, dur: &Duration) {
         let (time, extra) = encode_time(dur);
+        let has_extra = self.fits_in_inode(ATIME_EXTRA_END);
         let inode = self.raw_inode_mut();
         inode.access_time = time;
-        inode.atime_extra = extra;
+        if has_extra {
+            inode.atime_extra = extra;
+        }
         self.mark_dirty();
     }
 
-    /// 设置最后修改时间
+    /// 设置最后修改时间（inode没有扩展字段时只保存秒）
     pub fn set_mtime(&mut self, dur: &Duration) {
         let (time, extra) = encode_time(dur);
+        let has_extra = self.fits_in_inode(MTIME_EXTRA_END);
         let inode = self.raw_inode_mut();
         inode.modification_time = time;
-        inode.mtime_extra = extra;
+        if has_extra {
+            inode.mtime_extra = extra;
+        }
         self.mark_dirty();
     }
 
-    /// 设置最后状态修改时间
+    /// 设置最后状态修改时间（inode没有扩展字段时只保存秒）
     pub fn set_ctime(&mut self, dur: &Duration) {
         let (time, extra) = encode_time(dur);
+        let has_extra = self.fits_in_inode(CTIME_EXTRA_END);
         let inode = self.raw_inode_mut();
         inode.change_inode_time = time;
-        inode.ctime_extra = extra;
+        if has_extra {
+            inode.ctime_extra = extra;
+        }
+        self.mark_dirty();
+    }
+
+    /// 创建时间（inode没有crtime字段时返回None）
+    pub fn btime(&self) -> Option<Duration> {
+        if !self.fits_in_inode(CRTIME_END) {
+            return None;
+        }
+        let inode = self.raw_inode();
+        let extra = if self.fits_in_inode(CRTIME_EXTRA_END) { inode.crtime_extra } else { 0 };
+        Some(decode_time(inode.crtime, extra))
+    }
+
+    /// 设置创建时间（inode没有crtime字段时忽略）
+    pub fn set_btime(&mut self, dur: &Duration) {
+        if !self.fits_in_inode(CRTIME_END) {
+            return;
+        }
+        let (time, extra) = encode_time(dur);
+        let has_extra = self.fits_in_inode(CRTIME_EXTRA_END);
+        let inode = self.raw_inode_mut();
+        inode.crtime = time;
+        if has_extra {
+            inode.crtime_extra = extra;
+        }
         self.mark_dirty();
     }
 
-    /// 根据系统时间更新最后访问时间
+    /// 根据文件系统的当前时间更新最后访问时间（可重现模式下为挂载时指定的固定时间）
     pub fn update_atime(&mut self) {
-        if let Some(dur) = Hal::now() {
+        if let Some(dur) = self.now() {
             self.set_atime(&dur);
         }
     }
 
-    /// 根据系统时间更新最后修改时间
+    /// 根据文件系统的当前时间更新最后修改时间（可重现模式下为挂载时指定的固定时间）
     pub fn update_mtime(&mut self) {
-        if let Some(dur) = Hal::now() {
+        if let Some(dur) = self.now() {
             self.set_mtime(&dur);
         }
     }
 
-    /// 根据系统时间更新最后状态修改时间
+    /// 根据文件系统的当前时间更新最后状态修改时间（可重现模式下为挂载时指定的固定时间）
     pub fn update_ctime(&mut self) {
-        if let Some(dur) = Hal::now() {
+        if let Some(dur) = self.now() {
             self.set_ctime(&dur);
         }
     }
@@ -171,19 +308,22 @@ impl<Hal: SystemHal> InodeRef<Hal> {
         attr.nlink = self.nlink() as _;
         attr.mode = self.mode();
         attr.node_type = self.inode_type();
-        attr.uid = self.uid() as _;
-        attr.gid = self.gid() as _;
+        attr.uid = self.uid();
+        attr.gid = self.gid();
         attr.size = self.size();
         attr.block_size = get_block_size(self.superblock()) as _;
         attr.blocks = unsafe {
             // 调用C函数获取块计数
             ext4_inode_get_blocks_count(self.superblock() as *const _ as _, self.inner.inode)
         };
+        attr.rdev = self.rdev();
 
-        // 解析时间戳
+        // 解析时间戳（纳秒和秒的高位来自扩展字段，不存在时按0处理）
         let inode = self.raw_inode();
-        attr.atime = decode_time(inode.access_time, inode.atime_extra);
-        attr.mtime = decode_time(inode.modification_time, inode.mtime_extra);
-        attr.ctime = decode_time(inode.change_inode_time, inode.ctime_extra);
+        let extra = |end, value| if self.fits_in_inode(end) { value } else { 0 };
+        attr.atime = decode_time(inode.access_time, extra(ATIME_EXTRA_END, inode.atime_extra));
+        attr.mtime = decode_time(inode.modification_time, extra(MTIME_EXTRA_END, inode.mtime_extra));
+        attr.ctime = decode_time(inode.change_inode_time, extra(CTIME_EXTRA_END, inode.ctime_extra));
+        attr.btime = self.btime();
     }
 }
\ No newline at end of file
@@ -1,19 +1,35 @@
 //! 该模块实现目录inode的操作，包括目录条目查找、读取、添加和删除。
 
-use core::{mem, slice};
+use core::mem;
 
-use crate::{Ext4Result, SystemHal, error::Context, ffi::*, util::revision_tuple};
+use alloc::vec::Vec;
 
-use super::{InodeRef, InodeType};
+use crate::{
+    Ext4Error, Ext4ErrorKind, Ext4Result, FileName, SystemHal,
+    error::{Context, ErrorContext},
+    ffi::*,
+    util::revision_tuple,
+};
+
+use super::{FileAttr, InodeRef, InodeType};
+
+/// 目录项名称的最大字节数
+const NAME_MAX: usize = 255;
 
 impl<Hal: SystemHal> InodeRef<Hal> {
     /// 读取目录条目（从offset开始），返回目录读取器
-    pub fn read_dir(mut self, offset: u64) -> Ext4Result<DirReader<Hal>> {
+    pub fn read_dir(self, offset: u64) -> Ext4Result<DirReader<Hal>> {
+        self.check_plain("read_dir")?;
+        self.read_dir_raw(offset)
+    }
+
+    /// 读取目录条目，不检查加密标志（加密目录中的名称为密文，"."和".."除外）
+    fn read_dir_raw(mut self, offset: u64) -> Ext4Result<DirReader<Hal>> {
         unsafe {
             let mut iter = mem::zeroed(); // 初始化目录迭代器
             // 调用C函数初始化迭代器
             ext4_dir_iterator_init(&mut iter, self.inner.as_mut(), offset)
-                .context("ext4_dir_iterator_init")?;
+                .with_context(|| ErrorContext::new("ext4_dir_iterator_init").ino(self.ino()))?;
 
             Ok(DirReader {
                 parent: self,
@@ -23,17 +39,24 @@ impl<Hal: SystemHal> InodeRef<Hal> {
     }
 
     /// 在目录中查找指定名称的条目
-    pub fn lookup(mut self, name: &str) -> Ext4Result<DirLookupResult<Hal>> {
+    pub fn lookup<'n>(mut self, name: impl Into<FileName<'n>>) -> Ext4Result<DirLookupResult<Hal>> {
+        let name = name.into();
+        self.check_plain("lookup")?;
+        self.check_name("lookup", name)?;
         unsafe {
             let mut result = mem::zeroed(); // 初始化查找结果
             // 调用C函数查找目录条目
             ext4_dir_find_entry(
                 &mut result,
                 self.inner.as_mut(),
-                name.as_ptr() as *const _,
+                name.as_bytes().as_ptr() as *const _,
                 name.len() as _,
             )
-            .context("ext4_dir_find_entry")?;
+            .with_context(|| {
+                ErrorContext::new("ext4_dir_find_entry")
+                    .ino(self.ino())
+                    .path_segment(name)
+            })?;
 
             Ok(DirLookupResult {
                 parent: self,
@@ -43,11 +66,13 @@ impl<Hal: SystemHal> InodeRef<Hal> {
     }
 
     /// 检查目录是否有子目录/文件（非"."和".."）
+    ///
+    /// 不要求能够解密名称，因此可以删除空的加密目录。
     pub fn has_children(self) -> Ext4Result<bool> {
         if self.inode_type() != InodeType::Directory {
             return Ok(false);
         }
-        let mut reader = self.read_dir(0)?;
+        let mut reader = self.read_dir_raw(0)?;
         // 遍历目录条目
         while let Some(curr) = reader.current() {
             let name = curr.name();
@@ -61,31 +86,61 @@ impl<Hal: SystemHal> InodeRef<Hal> {
     }
 
     /// 向目录添加条目（关联名称和inode）
-    pub(crate) fn add_entry(&mut self, name: &str, entry: &mut InodeRef<Hal>) -> Ext4Result {
+    pub(crate) fn add_entry<'n>(
+        &mut self,
+        name: impl Into<FileName<'n>>,
+        entry: &mut InodeRef<Hal>,
+    ) -> Ext4Result {
+        let name = name.into();
+        self.check_plain("add_entry")?;
+        self.check_name("add_entry", name)?;
         unsafe {
             // 调用C函数添加目录条目
             ext4_dir_add_entry(
                 self.inner.as_mut(),
-                name.as_ptr() as *const _,
+                name.as_bytes().as_ptr() as *const _,
                 name.len() as _,
                 entry.inner.as_mut(),
             )
-            .context("ext4_dir_add_entry")?;
+            .with_context(|| {
+                ErrorContext::new("ext4_dir_add_entry")
+                    .ino(self.ino())
+                    .path_segment(name)
+            })?;
         }
         entry.inc_nlink(); // 增加inode的链接计数
         Ok(())
     }
 
+    /// 拒绝超过NAME_MAX的名称（返回NameTooLong，目录项的名称长度只有8位）
+    fn check_name(&self, op: &'static str, name: FileName<'_>) -> Ext4Result {
+        if name.len() > NAME_MAX {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::NameTooLong, None)
+                .with_context(ErrorContext::new(op).ino(self.ino()).path_segment(name)));
+        }
+        Ok(())
+    }
+
     /// 从目录删除条目
-    pub(crate) fn remove_entry(&mut self, name: &str, entry: &mut InodeRef<Hal>) -> Ext4Result {
+    pub(crate) fn remove_entry<'n>(
+        &mut self,
+        name: impl Into<FileName<'n>>,
+        entry: &mut InodeRef<Hal>,
+    ) -> Ext4Result {
+        let name = name.into();
+        self.check_plain("remove_entry")?;
         unsafe {
             // 调用C函数删除目录条目
             ext4_dir_remove_entry(
                 self.inner.as_mut(),
-                name.as_ptr() as *const _,
+                name.as_bytes().as_ptr() as *const _,
                 name.len() as _,
             )
-            .context("ext4_dir_remove_entry")?;
+            .with_context(|| {
+                ErrorContext::new("ext4_dir_remove_entry")
+                    .ino(self.ino())
+                    .path_segment(name)
+            })?;
         }
         entry.dec_nlink(); // 减少inode的链接计数
         Ok(())
@@ -100,7 +155,7 @@ pub struct DirLookupResult<Hal: SystemHal> {
 
 impl<Hal: SystemHal> DirLookupResult<Hal> {
     /// 获取找到的目录条目
-    pub fn entry(&mut self) -> DirEntry {
+    pub fn entry(&mut self) -> DirEntry<'_> {
         DirEntry {
             inner: unsafe { &mut *(self.inner.dentry as *mut _) }, //  unsafe：转换原始指针
             sb: self.parent.superblock(),
@@ -159,20 +214,25 @@ impl RawDirEntry {
             InodeType::Unknown
         } else {
             // 转换C类型值为InodeType
-            match self.inner.in_.inode_type() as u32 {  // 方法调用
-                EXT4_DE_DIR => InodeType::Directory,
-                EXT4_DE_REG_FILE => InodeType::RegularFile,
-                EXT4_DE_SYMLINK => InodeType::Symlink,
-                EXT4_DE_CHRDEV => InodeType::CharacterDevice,
-                EXT4_DE_BLKDEV => InodeType::BlockDevice,
-                EXT4_DE_FIFO => InodeType::Fifo,
-                EXT4_DE_SOCK => InodeType::Socket,
-                _ => InodeType::Unknown,
-            }
+            dirent_type(self.inner.in_.inode_type())  // 方法调用
         }
     }
 }
 
+/// 目录项类型字节（EXT4_DE_*）对应的InodeType
+pub(crate) fn dirent_type(ty: u8) -> InodeType {
+    match ty as u32 {
+        EXT4_DE_DIR => InodeType::Directory,
+        EXT4_DE_REG_FILE => InodeType::RegularFile,
+        EXT4_DE_SYMLINK => InodeType::Symlink,
+        EXT4_DE_CHRDEV => InodeType::CharacterDevice,
+        EXT4_DE_BLKDEV => InodeType::BlockDevice,
+        EXT4_DE_FIFO => InodeType::Fifo,
+        EXT4_DE_SOCK => InodeType::Socket,
+        _ => InodeType::Unknown,
+    }
+}
+
 /// 目录条目（包含超级块引用，用于解析名称和类型）
 pub struct DirEntry<'a> {
     inner: &'a mut RawDirEntry,
@@ -190,6 +250,11 @@ impl DirEntry<'_> {
         self.inner.name(self.sb)
     }
 
+    /// 获取名称（FileName形式，便于显示和传回按名称操作的接口）
+    pub fn file_name(&self) -> FileName<'_> {
+        FileName::new(self.name())
+    }
+
     /// 获取inode类型
     pub fn inode_type(&self) -> InodeType {
         self.inner.inode_type(self.sb)
@@ -216,6 +281,52 @@ impl DirEntry<'_> {
     }
 }
 
+/// 脱离目录块的目录条目副本（用于分页读取）
+#[derive(Debug, Clone)]
+pub struct OwnedDirEntry {
+    pub ino: u32,              // inode编号
+    pub name: Vec<u8>,         // 名称
+    pub inode_type: InodeType, // inode类型
+    pub offset: u64,           // 条目的偏移量（传给read_dir可从该条目开始读取）
+}
+
+impl OwnedDirEntry {
+    pub(crate) fn new(entry: &DirEntry<'_>, offset: u64) -> Self {
+        Self {
+            ino: entry.ino(),
+            name: entry.name().to_vec(),
+            inode_type: entry.inode_type(),
+            offset,
+        }
+    }
+
+    /// 获取名称（FileName形式）
+    pub fn file_name(&self) -> FileName<'_> {
+        FileName::new(&self.name)
+    }
+}
+
+/// 一页目录条目
+#[derive(Debug, Clone, Default)]
+pub struct DirPage {
+    pub entries: Vec<OwnedDirEntry>, // 本页的条目
+    pub cookie: Option<u64>,         // 读取下一页的偏移量（None表示目录已读完）
+}
+
+/// 带属性的目录条目（readdirplus）
+#[derive(Debug, Clone)]
+pub struct DirPlusEntry {
+    pub entry: OwnedDirEntry, // 目录条目
+    pub attr: FileAttr,       // 条目指向的inode的属性
+}
+
+/// 一页带属性的目录条目
+#[derive(Debug, Clone, Default)]
+pub struct DirPlusPage {
+    pub entries: Vec<DirPlusEntry>, // 本页的条目（按目录顺序）
+    pub cookie: Option<u64>,        // 读取下一页的偏移量（None表示目录已读完）
+}
+
 /// 目录读取器，用于迭代目录条目
 pub struct DirReader<Hal: SystemHal> {
     parent: InodeRef<Hal>, // 父目录inode
@@ -224,7 +335,7 @@ pub struct DirReader<Hal: SystemHal> {
 
 impl<Hal: SystemHal> DirReader<Hal> {
     /// 获取当前条目（如果存在）
-    pub fn current(&self) -> Option<DirEntry> {
+    pub fn current(&self) -> Option<DirEntry<'_>> {
         if self.inner.curr.is_null() {
             return None;
         }
@@ -239,13 +350,17 @@ impl<Hal: SystemHal> DirReader<Hal> {
         if !self.inner.curr.is_null() {
             unsafe {
                 // 调用C函数移动迭代器
-                ext4_dir_iterator_next(&mut self.inner).context("ext4_dir_iterator_next")?;
+                ext4_dir_iterator_next(&mut self.inner).with_context(|| {
+                    ErrorContext::new("ext4_dir_iterator_next").ino(self.parent.ino())
+                })?;
             }
         }
         Ok(())
     }
 
-    /// 获取当前偏移量
+    /// 获取当前偏移量（传给read_dir可从当前条目继续读取）
+    ///
+    /// 纯 Rust 后端下htree目录的偏移量基于文件名哈希，目录增删条目后仍然有效。
     pub fn offset(&self) -> u64 {
         self.inner.curr_off
     }
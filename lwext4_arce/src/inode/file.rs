@@ -5,10 +5,15 @@ use core::{
     slice,
 };
 
+use alloc::{vec, vec::Vec};
+
 use super::InodeRef;
 
 use crate::{
-    Ext4Result, InodeType, SystemHal, WritebackGuard, error::Context, ffi::*, util::get_block_size,
+    DataWriteGuard, Ext4Error, Ext4ErrorKind, Ext4Result, InodeType, SystemHal, WritebackGuard,
+    error::{Context, ErrorContext},
+    ffi::*,
+    util::get_block_size,
 };
 
 /// 从缓冲区中提取前cnt个字节，并更新缓冲区剩余部分
@@ -33,7 +38,11 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let mut fblock = 0u64;
             // 调用C函数获取物理块号
             ext4_fs_get_inode_dblk_idx(self.inner.as_mut(), block, &mut fblock, true)
-                .context("ext4_fs_get_inode_dblk_idx")?;
+                .with_context(|| {
+                    ErrorContext::new("ext4_fs_get_inode_dblk_idx")
+                        .ino(self.ino())
+                        .block(block as _)
+                })?;
             Ok(fblock)
         }
     }
@@ -44,7 +53,11 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let mut fblock = 0u64;
             // 调用C函数初始化物理块
             ext4_fs_init_inode_dblk_idx(self.inner.as_mut(), block, &mut fblock)
-                .context("ext4_fs_init_inode_dblk_idx")?;
+                .with_context(|| {
+                    ErrorContext::new("ext4_fs_init_inode_dblk_idx")
+                        .ino(self.ino())
+                        .block(block as _)
+                })?;
             Ok(fblock)
         }
     }
@@ -56,37 +69,78 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let mut block = 0u32;
             // 调用C函数追加块
             ext4_fs_append_inode_dblk(self.inner.as_mut(), &mut fblock, &mut block)
-                .context("ext4_fs_append_inode_dblk")?;
+                .with_context(|| ErrorContext::new("ext4_fs_append_inode_dblk").ino(self.ino()))?;
             Ok((fblock, block))
         }
     }
 
-    /// 从设备读取指定偏移量的字节
-    fn read_bytes(&mut self, offset: u64, buf: &mut [u8]) -> Ext4Result<()> {
+    /// 设置文件大小（纯Rust后端下超过2 GiB时同时启用large_file特性）
+    fn set_size(&mut self, size: u64) -> Ext4Result<()> {
+        #[cfg(feature = "use-rust")]
+        unsafe { ext4_fs_set_inode_size(self.inner.as_mut(), size) }
+            .with_context(|| ErrorContext::new("ext4_fs_set_inode_size").ino(self.ino()))?;
+        #[cfg(not(feature = "use-rust"))]
+        unsafe {
+            ext4_inode_set_size(self.inner.inode, size);
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// 物理块fblock中offset处在设备上的字节偏移
+    ///
+    /// 块号来自磁盘上的块映射，恶意镜像中的块号可能使乘法溢出，此时返回Corrupted。
+    fn block_offset(&self, op: &'static str, fblock: u64, offset: u64) -> Ext4Result<u64> {
+        let block_size = get_block_size(self.superblock()) as u64;
+        fblock
+            .checked_mul(block_size)
+            .and_then(|pos| pos.checked_add(offset))
+            .ok_or_else(|| {
+                Ext4Error::from_kind(Ext4ErrorKind::Corrupted, "block number out of range")
+                    .with_context(ErrorContext::new(op).ino(self.ino()).block(fblock))
+            })
+    }
+
+    /// 从设备读取物理块fblock中offset处的字节
+    fn read_bytes(&mut self, fblock: u64, offset: u64, buf: &mut [u8]) -> Ext4Result<()> {
+        let pos = self.block_offset("ext4_block_readbytes", fblock, offset)?;
         unsafe {
             let bdev = (*self.inner.fs).bdev;
             // 调用C函数读取字节
-            ext4_block_readbytes(bdev, offset, buf.as_mut_ptr() as _, buf.len() as _)
-                .context("ext4_block_readbytes")
+            ext4_block_readbytes(bdev, pos, buf.as_mut_ptr() as _, buf.len() as _).with_context(
+                || {
+                    ErrorContext::new("ext4_block_readbytes")
+                        .ino(self.ino())
+                        .block(fblock)
+                },
+            )
         }
     }
 
-    /// 向设备写入指定偏移量的字节
-    fn write_bytes(&mut self, offset: u64, buf: &[u8]) -> Ext4Result<()> {
+    /// 向设备写入物理块fblock中offset处的字节
+    fn write_bytes(&mut self, fblock: u64, offset: u64, buf: &[u8]) -> Ext4Result<()> {
+        let pos = self.block_offset("ext4_block_writebytes", fblock, offset)?;
         unsafe {
             let bdev = (*self.inner.fs).bdev;
             // 调用C函数写入字节
-            ext4_block_writebytes(bdev, offset, buf.as_ptr() as _, buf.len() as _)
-                .context("ext4_block_writebytes")
+            ext4_block_writebytes(bdev, pos, buf.as_ptr() as _, buf.len() as _).with_context(
+                || {
+                    ErrorContext::new("ext4_block_writebytes")
+                        .ino(self.ino())
+                        .block(fblock)
+                },
+            )
         }
     }
 
     /// 从inode读取数据（从偏移量pos开始，写入buf）
     pub fn read_at(&mut self, mut buf: &mut [u8], pos: u64) -> Ext4Result<usize> {
+        self.check_plain("read_at")?;
         unsafe {
             let file_size = self.size(); // 文件总大小
             let block_size = get_block_size(self.superblock()); // 块大小
             let bdev = (*self.inner.fs).bdev;
+            let ino = self.ino();
 
             // 如果偏移量超出文件大小或缓冲区为空，返回0
             if pos >= file_size || buf.is_empty() {
@@ -98,18 +152,20 @@ impl<Hal: SystemHal> InodeRef<Hal> {
 
             let inode = self.raw_inode();
 
-            // 处理符号链接的内联数据（短路径直接存储在inode中）
-            if self.inode_type() == InodeType::Symlink && file_size < size_of::<[u32; 15]>() as u64
-            {
-                // 从inode的blocks字段读取内联数据
+            // 快速符号链接：目标路径直接存储在inode中，blocks字段不是块映射
+            if self.is_fast_symlink() {
                 let content = (inode as *const _ as *const u8).add(offset_of!(ext4_inode, blocks));
-                let buf = take_mut(&mut buf, (file_size - pos) as usize);
                 buf.copy_from_slice(slice::from_raw_parts(content.add(pos as usize), buf.len()));
+                return Ok(to_be_read);
             }
 
-            // 计算起始块和结束块（逻辑块号）
+            // 计算起始块和结束块（逻辑块号）；损坏的i_size可能超出32位逻辑块号的范围
+            let end = (pos + buf.len() as u64).min(file_size);
+            let Ok(block_end) = u32::try_from(end / block_size as u64) else {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::Corrupted, "file size out of range")
+                    .with_context(ErrorContext::new("read_at").ino(ino)));
+            };
             let mut block_start = (pos / block_size as u64) as u32;
-            let block_end = ((pos + buf.len() as u64).min(file_size) / block_size as u64) as u32;
 
             // 处理块内的偏移量（非块对齐的起始部分）
             let offset = pos % block_size as u64;
@@ -118,7 +174,7 @@ impl<Hal: SystemHal> InodeRef<Hal> {
                 let fblock = self.get_inode_fblock(block_start)?;
                 if fblock != 0 {
                     // 读取物理块中从偏移量开始的数据
-                    self.read_bytes(fblock * block_size as u64 + offset, buf_segment)?;
+                    self.read_bytes(fblock, offset, buf_segment)?;
                 } else {
                     // 块未分配，填充0
                     buf_segment.fill(0);
@@ -141,7 +197,11 @@ impl<Hal: SystemHal> InodeRef<Hal> {
                 let buf_segment = take_mut(buf, count as usize * block_size as usize);
                 // 调用C函数批量读取块
                 ext4_blocks_get_direct(bdev, buf_segment.as_mut_ptr() as _, start, count)
-                    .context("ext4_blocks_get_direct")
+                    .with_context(|| {
+                        ErrorContext::new("ext4_blocks_get_direct")
+                            .ino(ino)
+                            .block(start)
+                    })
             };
 
             // 处理中间的完整块
@@ -171,7 +231,7 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             if !buf.is_empty() {
                 let fblock = self.get_inode_fblock(block_end)?;
                 if fblock != 0 {
-                    self.read_bytes(fblock * block_size as u64, buf)?;
+                    self.read_bytes(fblock, 0, buf)?;
                 } else {
                     buf.fill(0);
                 }
@@ -183,7 +243,12 @@ impl<Hal: SystemHal> InodeRef<Hal> {
 
     /// 向inode写入数据（从偏移量pos开始，读取buf）
     pub fn write_at(&mut self, mut buf: &[u8], pos: u64) -> Ext4Result<usize> {
+        self.check_plain("write_at")?;
+        self.check_writable("write_at")?;
+        self.check_size("write_at", pos.saturating_add(buf.len() as u64))?;
         unsafe {
+            // 有序数据模式下，新分配块的映射在数据写入之后才回写
+            let _data = DataWriteGuard::new((*self.inner.fs).bdev);
             let mut file_size = self.size();
             // 如果写入偏移量超出文件大小，扩展文件
             if pos > file_size {
@@ -194,6 +259,7 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let block_size = get_block_size(self.superblock());
             let block_count = file_size.div_ceil(block_size as u64) as u32; // 当前块数
             let bdev = (*self.inner.fs).bdev;
+            let ino = self.ino();
 
             if buf.is_empty() {
                 return Ok(0);
@@ -211,6 +277,16 @@ impl<Hal: SystemHal> InodeRef<Hal> {
                 }
             };
 
+            // 获取只写入部分内容的块：空洞中新分配的块先整块清零，避免其余部分残留旧数据
+            let get_partial_fblock = |this: &mut Self, block: u32| -> Ext4Result<u64> {
+                if block < block_count && this.get_inode_fblock(block)? == 0 {
+                    let fblock = this.init_inode_fblock(block)?;
+                    this.write_bytes(fblock, 0, &vec![0; block_size as usize])?;
+                    return Ok(fblock);
+                }
+                get_fblock(this, block)
+            };
+
             // 计算起始块和结束块（逻辑块号）
             let mut block_start = (pos / block_size as u64) as u32;
             let block_end = ((pos + buf.len() as u64) / block_size as u64) as u32;
@@ -219,9 +295,9 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let offset = pos % block_size as u64;
             if offset > 0 {
                 let buf_segment = take(&mut buf, block_size as usize - offset as usize);
-                let fblock = get_fblock(self, block_start)?;
+                let fblock = get_partial_fblock(self, block_start)?;
                 // 写入物理块中从偏移量开始的位置
-                self.write_bytes(fblock * block_size as u64 + offset, buf_segment)?;
+                self.write_bytes(fblock, offset, buf_segment)?;
                 block_start += 1;
             }
 
@@ -237,7 +313,11 @@ impl<Hal: SystemHal> InodeRef<Hal> {
                 let buf_segment = take(buf, count as usize * block_size as usize);
                 // 调用C函数批量写入块
                 ext4_blocks_set_direct(bdev, buf_segment.as_ptr() as _, start, count)
-                    .context("ext4_blocks_set_direct")
+                    .with_context(|| {
+                        ErrorContext::new("ext4_blocks_set_direct")
+                            .ino(ino)
+                            .block(start)
+                    })
             };
 
             // 处理中间的完整块
@@ -257,28 +337,110 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             // 处理块内的剩余部分（非块对齐的结束部分）
             assert!(buf.len() < block_size as usize);
             if !buf.is_empty() {
-                let fblock = get_fblock(self, block_end)?;
-                self.write_bytes(fblock * block_size as u64, buf)?;
+                let fblock = get_partial_fblock(self, block_end)?;
+                self.write_bytes(fblock, 0, buf)?;
             }
 
             // 如果写入超出原文件大小，更新文件大小
             let end = pos + to_be_written as u64;
             if end > file_size {
-                ext4_inode_set_size(self.inner.inode, end);
-                self.mark_dirty();
+                self.set_size(end)?;
             }
 
             Ok(to_be_written)
         }
     }
 
+    /// 稀疏写入：与write_at相同，但全零且对应块尚未分配的部分不写入，保留为空洞
+    ///
+    /// 按块边界切分数据，只有全零的部分落在空洞（或文件末尾之后）时才跳过，
+    /// 已分配的块仍按原样写入零，读取结果与write_at完全一致。
+    pub fn write_at_sparse(&mut self, buf: &[u8], pos: u64) -> Ext4Result<usize> {
+        self.check_plain("write_at_sparse")?;
+        self.check_writable("write_at_sparse")?;
+        let block_size = get_block_size(self.superblock()) as u64;
+        let end = pos.saturating_add(buf.len() as u64);
+        self.check_size("write_at_sparse", end)?;
+
+        // 连续的需要写入的部分合并为一次write_at
+        let mut pending: Option<(u64, usize)> = None; // (文件偏移量, 缓冲区起始下标)
+        let mut cur = pos;
+        while cur < end {
+            let len = (block_size - cur % block_size).min(end - cur);
+            let start = (cur - pos) as usize;
+            let piece = &buf[start..start + len as usize];
+            let hole = piece.iter().all(|&b| b == 0)
+                && self.get_inode_fblock((cur / block_size) as u32)? == 0;
+            if hole {
+                if let Some((off, idx)) = pending.take() {
+                    self.write_dense(&buf[idx..start], off)?;
+                }
+            } else if pending.is_none() {
+                pending = Some((cur, start));
+            }
+            cur += len;
+        }
+        if let Some((off, idx)) = pending {
+            self.write_dense(&buf[idx..], off)?;
+        }
+
+        if end > self.size() {
+            self.set_len_sparse(end)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// 写入一段数据，写入位置在文件末尾之后时先以空洞扩展文件
+    fn write_dense(&mut self, buf: &[u8], pos: u64) -> Ext4Result<()> {
+        if pos > self.size() {
+            self.set_len_sparse(pos)?;
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            written += self.write_at(&buf[written..], pos + written as u64)?;
+        }
+        Ok(())
+    }
+
+    /// 设置文件大小，扩展的部分为空洞而不分配数据块（缩小时同set_len）
+    pub fn set_len_sparse(&mut self, len: u64) -> Ext4Result<()> {
+        self.check_plain("set_len_sparse")?;
+        self.check_writable("set_len_sparse")?;
+        self.check_size("set_len_sparse", len)?;
+        let cur_len = self.size();
+        if len <= cur_len {
+            return self.set_len(len);
+        }
+
+        // 原最后一块中文件末尾之后的部分可能残留旧数据，需要清零
+        let block_size = get_block_size(self.superblock()) as u64;
+        let tail = cur_len % block_size;
+        if tail != 0 {
+            let fblock = self.get_inode_fblock((cur_len / block_size) as u32)?;
+            if fblock != 0 {
+                let zeros = vec![0; (block_size - tail) as usize];
+                self.write_bytes(fblock, tail, &zeros)?;
+            }
+        }
+
+        self.set_size(len)
+    }
+
     /// 截断文件到指定大小
+    ///
+    /// 释放数据不需要解密：加密inode可以截断为0，删除目录前截断目录块也不受限制。
+    /// fs-verity文件同样只能截断为0（连同文件末尾之后的元数据一起释放）。
     pub fn truncate(&mut self, size: u64) -> Ext4Result<()> {
+        if size != 0 && !self.is_dir() {
+            self.check_plain("truncate")?;
+            self.check_writable("truncate")?;
+        }
         unsafe {
             let bdev = (*self.inner.fs).bdev;
             let _guard = WritebackGuard::new(bdev); // 启用写回模式
             // 调用C函数截断inode
-            ext4_fs_truncate_inode(self.inner.as_mut(), size).context("ext4_fs_truncate_inode")
+            ext4_fs_truncate_inode(self.inner.as_mut(), size)
+                .with_context(|| ErrorContext::new("ext4_fs_truncate_inode").ino(self.ino()))
         }
     }
 
@@ -287,43 +449,73 @@ impl<Hal: SystemHal> InodeRef<Hal> {
         let block_size = get_block_size(self.superblock());
         // 路径过长（超过块大小）
         if target.len() > block_size as usize {
-            return 36.context("symlink too long"); // 36对应ENAMETOOLONG
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::NameTooLong, None)
+                .with_context(ErrorContext::new("set_symlink").ino(self.ino())));
         }
 
         unsafe {
             // 短路径：直接存储在inode的blocks字段中（内联数据）
-            if target.len() < size_of::<u32>() * EXT4_INODE_BLOCKS as usize {
+            if target.len() < size_of::<u32>() * EXT4_INODE_BLOCKS {
                 let ptr = (self.inner.inode as *mut u8).add(offset_of!(ext4_inode, blocks));
-                slice::from_raw_parts_mut(ptr, target.len()).copy_from_slice(target);
+                // 清除create时初始化的extent头，目标之后必须全为0
+                let blocks = slice::from_raw_parts_mut(ptr, size_of::<u32>() * EXT4_INODE_BLOCKS);
+                blocks.fill(0);
+                blocks[..target.len()].copy_from_slice(target);
                 ext4_inode_clear_flag(self.inner.inode, EXT4_INODE_FLAG_EXTENTS); // 清除扩展标志
             } else {
                 // 长路径：存储在数据块中
+                let _data = DataWriteGuard::new((*self.inner.fs).bdev);
                 ext4_fs_inode_blocks_init(self.inner.fs, self.inner.as_mut());
                 let mut fblock: u64 = 0;
                 let mut sblock: u32 = 0;
                 // 分配数据块
                 ext4_fs_append_inode_dblk(self.inner.as_mut(), &mut fblock, &mut sblock)
-                    .context("ext4_fs_append_inode_dblk")?;
+                    .with_context(|| {
+                        ErrorContext::new("ext4_fs_append_inode_dblk").ino(self.ino())
+                    })?;
 
                 // 写入目标路径到数据块
-                let off = fblock * block_size as u64;
-                self.write_bytes(off, target)?;
+                self.write_bytes(fblock, 0, target)?;
             }
             // 设置符号链接的大小
             ext4_inode_set_size(self.inner.inode, target.len() as u64);
         }
+        self.mark_dirty();
 
         Ok(())
     }
 
+    /// 读取符号链接的目标路径（快速符号链接和存放在数据块中的目标均可，返回内容长度即目标长度）
+    pub fn read_link(&mut self) -> Ext4Result<Vec<u8>> {
+        if self.inode_type() != InodeType::Symlink {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, None)
+                .with_context(ErrorContext::new("read_link").ino(self.ino())));
+        }
+        // 目标路径不会超过一个块，超出说明inode已损坏
+        let len = self.size();
+        if len > get_block_size(self.superblock()) as u64 {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Io, None)
+                .with_context(ErrorContext::new("read_link").ino(self.ino())));
+        }
+        let mut target = vec![0; len as usize];
+        let n = self.read_at(&mut target, 0)?;
+        target.truncate(n);
+        Ok(target)
+    }
+
     /// 设置文件长度（扩展或截断）
     pub fn set_len(&mut self, len: u64) -> Ext4Result<()> {
+        self.check_plain("set_len")?;
+        self.check_writable("set_len")?;
+        self.check_size("set_len", len)?;
         static EMPTY: [u8; 4096] = [0; 4096]; // 空数据块（用于填充）
 
         let cur_len = self.size();
-        if len < cur_len {
+        // 大小不变时同样截断，回收文件末尾之后预分配的块
+        if len <= cur_len {
             self.truncate(len)?;
         } else if len > cur_len {
+            let _data = DataWriteGuard::new(unsafe { (*self.inner.fs).bdev });
             // TODO: correct implementation
             let block_size = get_block_size(self.superblock());
             let old_blocks = cur_len.div_ceil(block_size as u64) as u32;
@@ -331,7 +523,7 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             for block in old_blocks..new_blocks {
                 let (fblock, new_block) = self.append_inode_fblock()?;
                 assert_eq!(block, new_block);
-                self.write_bytes(fblock * block_size as u64, &EMPTY[..block_size as usize])?;
+                self.write_bytes(fblock, 0, &EMPTY[..block_size as usize])?;
             }
 
             // Clear the last block extended part
@@ -340,15 +532,9 @@ impl<Hal: SystemHal> InodeRef<Hal> {
             let fblock = self.init_inode_fblock(old_last_block)?;
             assert!(fblock != 0, "fblock should not be zero");
             let length = block_size as usize - old_block_start;
-            self.write_bytes(
-                fblock * block_size as u64 + old_block_start as u64,
-                &EMPTY[..length],
-            )?;
+            self.write_bytes(fblock, old_block_start as u64, &EMPTY[..length])?;
 
-            unsafe {
-                ext4_inode_set_size(self.inner.inode, len);
-            }
-            self.mark_dirty();
+            self.set_size(len)?;
         }
         Ok(())
     }
@@ -7,17 +7,26 @@ mod dir;
 // 文件inode操作子模块
 mod file;
 
-// 引入内存分配相关类型
+// 引入内存分配相关类型（C后端下封装ext4_inode_ref）
+#[cfg(not(feature = "use-rust"))]
 use alloc::boxed::Box;
 // 对外暴露文件属性和目录相关类型
-pub use attr::FileAttr;
-pub use dir::{DirEntry, DirLookupResult, DirReader};
+pub use attr::{FileAttr, makedev};
+pub use dir::{
+    DirEntry, DirLookupResult, DirPage, DirPlusEntry, DirPlusPage, DirReader, OwnedDirEntry,
+};
+#[cfg(feature = "use-rust")]
+pub(crate) use dir::{RawDirEntry, dirent_type};
 
 // 引入标记类型（用于泛型约束）
-use core::marker::PhantomData;
+use core::{marker::PhantomData, time::Duration};
 
 // 引入系统硬件抽象层和FFI绑定
-use crate::{SystemHal, ffi::*};
+use crate::{Ext4Error, Ext4ErrorKind, Ext4Result, SystemHal, error::ErrorContext, ffi::*};
+
+// fscrypt加密标志和fs-verity标志（lwext4 的 ext4_types.h 中没有定义，Linux 取值）
+const INODE_FLAG_ENCRYPT: u32 = 0x800;
+const INODE_FLAG_VERITY: u32 = 0x100000;
 
 /// inode类型枚举，对应不同的文件系统对象类型
 #[repr(u8)]
@@ -50,23 +59,48 @@ impl From<u8> for InodeType {
     }
 }
 
+// 底层的inode引用：纯 Rust 后端直接使用lwext4_core::InodeRef，C后端为堆上的ext4_inode_ref
+#[cfg(feature = "use-rust")]
+type RawInodeRef = lwext4_core::InodeRef;
+#[cfg(not(feature = "use-rust"))]
+type RawInodeRef = Box<ext4_inode_ref>;
+
 /// inode引用结构体，封装了底层C结构体ext4_inode_ref
 /// 泛型参数Hal表示系统硬件抽象层
-#[repr(transparent)]
+///
+/// 这是本crate唯一的inode引用类型，文件、目录、扩展属性等模块都通过它访问inode。
+/// 纯 Rust 后端下它是lwext4_core::InodeRef加上类型化的接口，与lwext4_core内部的extent、
+/// 目录等模块使用同一种引用。它只持有inode表块在块缓存中的引用和指向文件系统的指针，
+/// 不借用Ext4Filesystem，因此可以同时持有多个（如父目录和子项）；释放时（Drop）写回修改并归还缓存块。
+/// 外部通过Ext4Filesystem::with_inode_ref在闭包中使用，不能比文件系统存活得更久。
 pub struct InodeRef<Hal: SystemHal> {
-    pub(crate) inner: Box<ext4_inode_ref>, // 内部封装的C结构体
+    pub(crate) inner: RawInodeRef, // 底层的inode引用
+    fixed_time: Option<Duration>, // 文件系统可重现模式下固定的时间戳（update_*time使用）
     _phantom: PhantomData<Hal>, // 泛型标记，确保Hal的生命周期
 }
 
 impl<Hal: SystemHal> InodeRef<Hal> {
-    /// 创建新的InodeRef实例
-    pub(crate) fn new(inner: ext4_inode_ref) -> Self {
+    /// 创建尚未关联inode的InodeRef，由ext4_fs_get_inode_ref或ext4_fs_alloc_inode填充
+    ///
+    /// fixed_time为文件系统的固定时间戳（见Ext4Filesystem::now），使update_*time与文件系统使用同一时钟。
+    pub(crate) fn empty(fixed_time: Option<Duration>) -> Self {
+        // C后端的ext4_inode_ref没有构造函数，以全0初始化
+        #[cfg(not(feature = "use-rust"))]
+        let inner = Box::new(unsafe { core::mem::zeroed() });
+        #[cfg(feature = "use-rust")]
+        let inner = RawInodeRef::new();
         Self {
-            inner: Box::new(inner),
+            inner,
+            fixed_time,
             _phantom: PhantomData,
         }
     }
 
+    /// 文件系统的当前时间（可重现模式下为固定值，否则由SystemHal::now提供）
+    fn now(&self) -> Option<Duration> {
+        self.fixed_time.or_else(Hal::now)
+    }
+
     /// 获取inode编号
     pub fn ino(&self) -> u32 {
         self.inner.index
@@ -96,18 +130,89 @@ impl<Hal: SystemHal> InodeRef<Hal> {
         self.mark_dirty(); // 标记为脏
     }
 
-    /// 减少硬链接计数
+    /// 减少硬链接计数（纯Rust后端下按dir_nlink处理、链接数为1的目录保持为1）
     pub(crate) fn dec_nlink(&mut self) {
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_fs_inode_links_count_dec(self.inner.as_mut());
+        }
+        #[cfg(not(feature = "use-rust"))]
         self.set_nlink(self.nlink() - 1);
         self.mark_dirty();
     }
 
+    /// 拒绝超过EXT4_LINK_MAX的链接（返回EMLINK，按dir_nlink处理的目录不受限制，C后端下不检查）
+    #[cfg_attr(not(feature = "use-rust"), allow(unused_variables))]
+    pub(crate) fn check_link_max(&self, op: &'static str) -> Ext4Result {
+        #[cfg(feature = "use-rust")]
+        if unsafe { ext4_inode_links_max(self.superblock(), self.inner.inode) } {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::TooManyLinks, None)
+                .with_context(ErrorContext::new(op).ino(self.ino())));
+        }
+        Ok(())
+    }
+
     /// 设置硬链接计数
     pub(crate) fn set_nlink(&mut self, nlink: u16) {
         self.raw_inode_mut().links_count = u16::to_le(nlink); // 转换为小端存储
         self.mark_dirty();
     }
 
+    /// 是否为fscrypt加密的inode（文件内容、目录项名称和符号链接目标均为密文）
+    pub fn is_encrypted(&self) -> bool {
+        u32::from_le(self.raw_inode().flags) & INODE_FLAG_ENCRYPT != 0
+    }
+
+    /// 拒绝访问加密inode的内容（目前不支持解密，直接读取只会得到密文）
+    pub(crate) fn check_plain(&self, op: &'static str) -> Ext4Result {
+        if self.is_encrypted() {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Encrypted, None)
+                .with_context(ErrorContext::new(op).ino(self.ino())));
+        }
+        Ok(())
+    }
+
+    /// 是否为快速符号链接（目标路径直接存放在inode的blocks字段中，没有数据块）
+    pub fn is_fast_symlink(&self) -> bool {
+        #[cfg(feature = "use-rust")]
+        unsafe {
+            ext4_inode_is_fast_symlink(self.superblock(), self.inner.inode)
+        }
+        #[cfg(not(feature = "use-rust"))]
+        unsafe {
+            // C后端没有扩展属性块的修正，按没有数据块判断
+            self.inode_type() == InodeType::Symlink
+                && self.size() < size_of::<[u32; 15]>() as u64
+                && ext4_inode_get_blocks_count(self.superblock() as *const _ as _, self.inner.inode)
+                    == 0
+        }
+    }
+
+    /// 是否为启用了fs-verity的文件（内容只读，Merkle树和描述符存放在文件末尾之后）
+    pub fn is_verity(&self) -> bool {
+        u32::from_le(self.raw_inode().flags) & INODE_FLAG_VERITY != 0
+    }
+
+    /// 拒绝修改fs-verity文件的内容（与Linux相同返回EPERM）
+    pub(crate) fn check_writable(&self, op: &'static str) -> Ext4Result {
+        if self.is_verity() {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::NotPermitted, None)
+                .with_context(ErrorContext::new(op).ino(self.ino())));
+        }
+        Ok(())
+    }
+
+    /// 拒绝超过inode能表示的最大文件大小的写入或扩展（返回EFBIG，C后端下不检查）
+    #[cfg_attr(not(feature = "use-rust"), allow(unused_variables))]
+    pub(crate) fn check_size(&self, op: &'static str, end: u64) -> Ext4Result {
+        #[cfg(feature = "use-rust")]
+        if end > unsafe { ext4_inode_max_size(self.superblock(), self.inner.inode) } {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::FileTooLarge, None)
+                .with_context(ErrorContext::new(op).ino(self.ino())));
+        }
+        Ok(())
+    }
+
     /// 获取原始inode结构体的不可变引用
     pub(crate) fn raw_inode(&self) -> &ext4_inode {
         unsafe { &*self.inner.inode } //  unsafe：直接访问原始指针
@@ -122,7 +227,11 @@ impl<Hal: SystemHal> InodeRef<Hal> {
 /// 当InodeRef被销毁时，释放底层资源
 impl<Hal: SystemHal> Drop for InodeRef<Hal> {
     fn drop(&mut self) {
+        // 纯 Rust 后端由lwext4_core::InodeRef::put归还（之后它的Drop不再处理）
+        #[cfg(feature = "use-rust")]
+        let ret = core::mem::take(&mut self.inner).put();
         // 调用C函数释放inode引用
+        #[cfg(not(feature = "use-rust"))]
         let ret = unsafe { ext4_fs_put_inode_ref(self.inner.as_mut()) };
         if ret != 0 {
             panic!("ext4_fs_put_inode_ref failed: {}", ret);
@@ -0,0 +1,69 @@
+//! 文件系统布局模块：查询各块组的元数据位置（类似dumpe2fs的块组部分），并检查块组描述符。
+
+use core::ops::Range;
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, SystemHal, error::Context, ffi::*,
+};
+
+/// 块组布局（均为文件系统绝对块号，空区间表示没有该区域）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupLayout {
+    pub group: u32,               // 块组号
+    pub blocks: Range<u64>,       // 块组包含的块
+    pub super_block: Option<u64>, // superblock（或其备份）所在块
+    pub gdt: Range<u64>,          // GDT（或其备份）
+    pub reserved_gdt: Range<u64>, // 在线扩容预留的GDT
+    pub block_bitmap: u64,        // 块位图
+    pub inode_bitmap: u64,        // inode位图
+    pub inode_table: Range<u64>,  // inode表
+}
+
+impl From<&Ext4GroupLayout> for GroupLayout {
+    fn from(l: &Ext4GroupLayout) -> Self {
+        let range = |start: u64, len: u32| start..start + len as u64;
+        GroupLayout {
+            group: l.group,
+            blocks: range(l.first_block, l.blocks),
+            super_block: l.super_block,
+            gdt: range(l.gdt_start, l.gdt_blocks),
+            reserved_gdt: range(l.reserved_gdt_start, l.reserved_gdt_blocks),
+            block_bitmap: l.block_bitmap,
+            inode_bitmap: l.inode_bitmap,
+            inode_table: range(l.inode_table, l.inode_table_blocks),
+        }
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 块组数
+    pub fn group_count(&self) -> u32 {
+        ext4_block_group_cnt(&self.inner.sb)
+    }
+
+    /// 块组的实际布局（位图和inode表位置取自块组描述符）
+    pub fn group_layout(&mut self, group: u32) -> Ext4Result<GroupLayout> {
+        if group >= self.group_count() {
+            return Err(Ext4Error::new(EINVAL as _, "block group out of range"));
+        }
+        let mut layout = Ext4GroupLayout::default();
+        unsafe { ext4_layout_get(self.inner.as_mut(), group, &mut layout) }
+            .context("ext4_layout_get")?;
+        Ok(GroupLayout::from(&layout))
+    }
+
+    /// 按格式化时的默认规则计算的块组布局（不读取块组描述符，flex_bg时与mke2fs的放置方式相同）
+    pub fn default_group_layout(&self, group: u32) -> Ext4Result<GroupLayout> {
+        if group >= self.group_count() {
+            return Err(Ext4Error::new(EINVAL as _, "block group out of range"));
+        }
+        Ok(GroupLayout::from(&ext4_layout_default(&self.inner.sb, group)))
+    }
+
+    /// 检查块组描述符中的位图和inode表位置（越界或与superblock/GDT重叠时返回EIO）
+    ///
+    /// 可写挂载时自动执行，检查失败的文件系统转为只读。
+    pub fn check_layout(&mut self) -> Ext4Result<()> {
+        unsafe { ext4_layout_check(self.inner.as_mut()) }.context("ext4_layout_check")
+    }
+}
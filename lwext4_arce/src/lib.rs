@@ -11,6 +11,10 @@
 // 引入内存分配库
 extern crate alloc;
 
+// 宿主环境支持（仅std特性启用时）
+#[cfg(feature = "std")]
+extern crate std;
+
 // 引入日志宏
 #[macro_use]
 extern crate log;
@@ -19,9 +23,9 @@ extern crate log;
 #[cfg(feature = "use-ffi")]
 mod ulibc;
 
-// 对外暴露的FFI（Foreign Function Interface）绑定
+// lwext4的C绑定（由bindgen生成）
 #[cfg(feature = "use-ffi")]
-pub mod ffi {
+mod lwext4_sys {
     // 允许非大写全局变量（C风格）
     #![allow(non_upper_case_globals)]
     // 允许非驼峰式类型名（C风格）
@@ -33,7 +37,14 @@ pub mod ffi {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+// 对外暴露的FFI（Foreign Function Interface）绑定：只启用use-ffi时为C绑定
+#[cfg(not(feature = "use-rust"))]
+pub mod ffi {
+    pub use crate::lwext4_sys::*;
+}
+
 // 使用纯 Rust 实现时，从 lwext4_core 导入所有接口
+// （同时启用use-ffi时，对外接口也绑定到纯 Rust 后端，C 绑定在lwext4_sys中）
 #[cfg(feature = "use-rust")]
 pub mod ffi {
     // 重新导出 lwext4_core 的所有内容
@@ -42,6 +53,8 @@ pub mod ffi {
     pub use lwext4_core::*;
 }
 
+// 后端选择模块
+mod backend;
 // 块设备抽象模块
 mod blockdev;
 // 错误处理模块
@@ -50,14 +63,157 @@ mod error;
 mod fs;
 // inode（索引节点）相关模块
 mod inode;
+// 目录项名称模块
+mod name;
+// 运行统计模块
+mod metrics;
+// 写时复制块设备模块
+mod overlay;
+// 压缩镜像块设备模块
+mod compressed;
+// I/O重试块设备模块
+mod retry;
 // 工具函数模块
 mod util;
+// 目录树遍历模块
+mod walk;
+// 目录句柄模块
+mod dir_handle;
+// 按打开选项打开文件模块
+mod open;
+// 内存块设备模块
+mod ramdisk;
+// 文件内容摘要模块
+mod digest;
+// 记录写入的块设备模块（崩溃一致性测试）
+mod recording;
+// 以下模块直接使用lwext4_core的接口和数据结构（extent树、日志、布局计算、扩展属性等），
+// 只在纯Rust后端（use-rust特性）下编译，C后端下不可用
+// 文件碎片整理模块
+#[cfg(feature = "use-rust")]
+mod defrag;
+// 多操作事务模块
+#[cfg(feature = "use-rust")]
+mod transaction;
+// 文件系统参数调整模块
+#[cfg(feature = "use-rust")]
+mod tune;
+// 格式化模块
+#[cfg(feature = "use-rust")]
+mod mkfs;
+// 扩展属性模块
+#[cfg(feature = "use-rust")]
+mod xattr;
+// fscrypt加密识别模块
+#[cfg(feature = "use-rust")]
+mod fscrypt;
+// fs-verity元数据读取模块
+#[cfg(feature = "use-rust")]
+mod verity;
+// 文件系统布局查询模块
+#[cfg(feature = "use-rust")]
+mod layout;
+// 在线一致性检查模块
+#[cfg(feature = "use-rust")]
+mod fsck;
+// 已删除文件恢复模块
+#[cfg(feature = "use-rust")]
+mod undelete;
+// 镜像比较模块
+#[cfg(feature = "use-rust")]
+mod diff;
+// 配额用量统计模块
+#[cfg(feature = "use-rust")]
+mod quota;
+// 宿主环境下基于文件的块设备（仅std特性启用时）
+#[cfg(feature = "std")]
+mod std_device;
+// 宿主目录树的导入/导出（仅std特性启用时，需要Unix宿主）
+#[cfg(all(feature = "std", unix))]
+mod host;
+// tar归档流式导入模块（仅tar特性启用时）
+#[cfg(feature = "tar")]
+mod tar;
+// 模糊测试入口（仅fuzz特性启用时）
+#[cfg(all(feature = "fuzz", feature = "use-rust"))]
+pub mod fuzz;
 
+// 对外暴露后端类型
+pub use backend::{Backend, BackendStat, Ext4Backend, open_backend};
 // 对外暴露块设备相关类型
 pub use blockdev::{BlockDevice, EXT4_DEV_BSIZE};
+#[allow(deprecated)]
+pub use blockdev::CoreDevice;
 // 对外暴露错误处理类型
-pub use error::{Ext4Error, Ext4Result};
+pub use error::{ErrorContext, Ext4Error, Ext4ErrorKind, Ext4Result, errno};
 // 对外暴露文件系统相关类型和方法
 pub use fs::*;
 // 对外暴露inode相关类型
-pub use inode::*;
\ No newline at end of file
+pub use inode::*;
+// 对外暴露目录项名称类型
+pub use name::FileName;
+// 对外暴露运行统计类型
+pub use metrics::{Metrics, OpStats};
+// 对外暴露写时复制块设备
+pub use overlay::{DeltaStore, MemoryDelta, OverlayDevice};
+#[cfg(feature = "std")]
+pub use overlay::FileDelta;
+// 对外暴露压缩镜像块设备
+pub use compressed::{CompressedDevice, Decompressor, ImageSource, encode_compressed_image};
+// 对外暴露I/O重试块设备
+pub use retry::{RetryDevice, RetryPolicy, RetryStats};
+// 对外暴露目录句柄类型
+pub use dir_handle::DirHandle;
+// 对外暴露打开选项和已打开的文件
+pub use open::{OpenFile, OpenOptions};
+// 对外暴露内存块设备
+pub use ramdisk::RamDisk;
+// 对外暴露流式摘要接口
+pub use digest::ContentDigest;
+// 对外暴露记录写入的块设备
+pub use recording::{RecordingDevice, WriteRecord};
+// 对外暴露目录树遍历类型
+pub use walk::{DiskUsage, Glob, SymlinkPolicy, WalkDir, WalkEntry, glob_match};
+// 对外暴露碎片整理和空闲空间报告类型
+#[cfg(feature = "use-rust")]
+pub use defrag::{
+    DefragStats, FREE_EXTENT_BUCKETS, FreeExtentHistogram, FreeSpaceReport, GroupFreeSpace,
+};
+// 对外暴露事务类型
+#[cfg(feature = "use-rust")]
+pub use transaction::Transaction;
+// 对外暴露格式化相关类型
+#[cfg(feature = "use-rust")]
+pub use mkfs::{MkfsOptions, RandomSource, uuid_v4};
+#[cfg(all(feature = "std", feature = "use-rust"))]
+pub use mkfs::StdRandom;
+// 对外暴露fscrypt相关类型
+#[cfg(feature = "use-rust")]
+pub use fscrypt::{EncryptionPolicy, KeyProvider, MasterKeySpec};
+// 对外暴露fs-verity相关类型
+#[cfg(feature = "use-rust")]
+pub use verity::{FS_VERITY_HASH_ALG_SHA256, FS_VERITY_HASH_ALG_SHA512, VerityDescriptor};
+// 对外暴露fsck建议类型
+#[cfg(feature = "use-rust")]
+pub use tune::FsckAdvice;
+// 对外暴露块组布局类型
+#[cfg(feature = "use-rust")]
+pub use layout::GroupLayout;
+// 对外暴露目录项类型检查结果
+#[cfg(feature = "use-rust")]
+pub use fsck::DirTypeMismatch;
+// 对外暴露已删除文件恢复类型
+#[cfg(feature = "use-rust")]
+pub use undelete::{DeletedInode, RecoveredFile};
+// 对外暴露镜像比较接口
+#[cfg(feature = "use-rust")]
+pub use diff::{Change, DiffOptions, MetadataField, PathDiff, diff_images};
+// 对外暴露配额用量类型
+#[cfg(feature = "use-rust")]
+pub use quota::{QuotaReport, QuotaUsage};
+// 对外暴露宿主环境块设备
+#[cfg(feature = "std")]
+pub use std_device::FileBlockDevice;
+// 对外暴露tar导入类型
+#[cfg(feature = "tar")]
+pub use tar::{TarImporter, TarStats};
\ No newline at end of file
@@ -0,0 +1,79 @@
+//! 运行统计模块，汇总块缓存命中率、块分配/释放、脏块回写等计数以及各操作的耗时，
+//! 用于嵌入式平台上的性能调优。
+
+use core::time::Duration;
+
+/// 单类操作的调用次数与耗时
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpStats {
+    pub count: u64,      // 调用次数
+    pub timed: u64,      // 有耗时数据的调用次数（SystemHal::monotonic返回None时不计时）
+    pub total: Duration, // 总耗时
+    pub max: Duration,   // 单次最大耗时
+}
+
+impl OpStats {
+    /// 平均耗时（没有计时数据时返回None）
+    pub fn mean(&self) -> Option<Duration> {
+        (self.timed != 0)
+            .then(|| Duration::from_nanos((self.total.as_nanos() / self.timed as u128) as u64))
+    }
+
+    /// 记录一次调用
+    pub(crate) fn record(&mut self, elapsed: Option<Duration>) {
+        self.count += 1;
+        if let Some(elapsed) = elapsed {
+            self.timed += 1;
+            self.total += elapsed;
+            self.max = self.max.max(elapsed);
+        }
+    }
+}
+
+/// 文件系统运行统计
+///
+/// 缓存、块分配和回写计数由纯 Rust 后端维护，C FFI 后端下恒为0；
+/// 设备读写次数和各操作耗时两种后端都可用。
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub block_reads: u64,         // 设备读请求次数
+    pub block_writes: u64,        // 设备写请求次数
+    pub cache_hits: u64,          // 块缓存命中次数
+    pub cache_misses: u64,        // 块缓存未命中次数
+    pub blocks_allocated: u64,    // 分配的块数
+    pub blocks_freed: u64,        // 释放的块数
+    pub journal_commits: u64,     // 日志事务提交次数
+    pub dirty_flushes: u64,       // 写回设备的脏缓冲区数
+    pub pinned_blocks: u32,       // 当前常驻缓存的元数据块数（块组描述符、位图）
+    pub cached_blocks: u32,       // 当前块缓存中的块数（见Ext4Filesystem::shrink_cache）
+    pub dirty_blocks: u32,        // 当前等待回写的脏块数（见FsConfig::max_dirty_blocks）
+    pub extent_cache_hits: u64,   // extent 映射命中状态缓存的次数
+    pub extent_cache_misses: u64, // extent 映射需要遍历 extent 树的次数
+    pub alloc_goal_hits: u64,     // 块分配正好落在目标块上的次数
+    pub alloc_goal_group_hits: u64, // 块分配落在目标块组内其他位置的次数
+    pub alloc_goal_misses: u64,   // 目标块组已满、分配到其他块组的次数
+
+    pub read: OpStats,    // read_at
+    pub write: OpStats,   // write_at
+    pub set_len: OpStats, // set_len
+    pub lookup: OpStats,  // lookup
+    pub create: OpStats,  // create
+    pub link: OpStats,    // link
+    pub unlink: OpStats,  // unlink
+    pub rename: OpStats,  // rename
+    pub flush: OpStats,   // flush
+}
+
+impl Metrics {
+    /// 块缓存命中率（0.0 ~ 1.0，没有访问记录时返回None）
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let total = self.cache_hits + self.cache_misses;
+        (total != 0).then(|| self.cache_hits as f64 / total as f64)
+    }
+
+    /// 块分配落在目标块上的比例（0.0 ~ 1.0，没有分配记录时返回None）
+    pub fn alloc_goal_hit_rate(&self) -> Option<f64> {
+        let total = self.alloc_goal_hits + self.alloc_goal_group_hits + self.alloc_goal_misses;
+        (total != 0).then(|| self.alloc_goal_hits as f64 / total as f64)
+    }
+}
@@ -0,0 +1,214 @@
+//! 格式化模块：在块设备上创建新的ext4文件系统，以及生成随机UUID。
+//!
+//! no_std环境下没有统一的随机数来源，UUID和htree哈希种子由使用者通过RandomSource提供；
+//! 可重现模式（FsConfig::reproducible）下改为由格式化参数确定性地导出，并固定所有时间戳；
+//! 块和inode的分配顺序只取决于操作序列，因此相同的输入得到逐字节相同的镜像。
+
+use core::{mem, time::Duration};
+
+use alloc::{boxed::Box, string::String};
+
+use crate::{
+    Backend, BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, FsConfig, Progress, ProgressPhase,
+    SystemHal, blockdev::Ext4BlockDevice, error::Context, ffi::{crc::ext4_crc32c, *},
+};
+
+/// 随机数来源（用于生成UUID和htree哈希种子）
+pub trait RandomSource {
+    /// 用随机字节填满buf
+    fn fill_bytes(&mut self, buf: &mut [u8]);
+}
+
+/// 基于标准库哈希随机种子的随机数来源（仅std特性启用时）
+///
+/// 每次调用都重新构造RandomState，其密钥由操作系统随机数初始化，足以生成不重复的UUID，
+/// 但不适合用于密码学用途。
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdRandom;
+
+#[cfg(feature = "std")]
+impl RandomSource for StdRandom {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        use std::hash::{BuildHasher, Hasher};
+
+        for (i, chunk) in buf.chunks_mut(8).enumerate() {
+            let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            hasher.write_usize(i);
+            let bytes = hasher.finish().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// 由种子确定性生成的伪随机数（splitmix64），用于可重现模式
+struct SeededRandom(u64);
+
+impl SeededRandom {
+    /// 由格式化参数和固定时间导出种子
+    fn new(opts: &MkfsOptions, time: Duration) -> Self {
+        let mut crc = ext4_crc32c(!0, &time.as_secs().to_le_bytes());
+        crc = ext4_crc32c(crc, &time.subsec_nanos().to_le_bytes());
+        crc = ext4_crc32c(crc, opts.label.as_bytes());
+        let hi = ext4_crc32c(crc, &opts.uuid.unwrap_or_default());
+        Self((hi as u64) << 32 | crc as u64)
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+            chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// 生成随机UUID（RFC 4122版本4）
+pub fn uuid_v4(rng: &mut (impl RandomSource + ?Sized)) -> [u8; 16] {
+    let mut uuid = [0u8; 16];
+    rng.fill_bytes(&mut uuid);
+    uuid[6] = (uuid[6] & 0x0F) | 0x40; // 版本号4
+    uuid[8] = (uuid[8] & 0x3F) | 0x80; // RFC 4122变体
+    uuid
+}
+
+/// 格式化参数
+#[derive(Debug, Clone)]
+pub struct MkfsOptions {
+    pub block_size: u32,        // 块大小（0表示默认的4096）
+    pub inode_size: u16,        // inode大小（0表示默认的256）
+    pub inodes: u32,            // 总inode数（0表示每16KiB一个）
+    pub journal: bool,          // 是否创建日志（文件系统太小时不创建）
+    pub journal_blocks: u32,    // 日志块数（0表示按文件系统大小选择）
+    pub metadata_csum: bool,    // 是否启用元数据校验和
+    pub label: String,          // 卷标（最多16字节）
+    pub uuid: Option<[u8; 16]>, // 文件系统UUID（None表示随机生成）
+    pub reserved_percent: u32,  // 保留块比例（0~50）
+}
+
+impl Default for MkfsOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 0,
+            inode_size: 0,
+            inodes: 0,
+            journal: true,
+            journal_blocks: 0,
+            metadata_csum: true,
+            label: String::new(),
+            uuid: None,
+            reserved_percent: 5,
+        }
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 在块设备上创建新的文件系统并挂载
+    ///
+    /// 未指定UUID时用rng生成版本4的UUID；htree哈希种子总是由rng生成。
+    /// Hal提供时间时，用它设置创建时间和根目录、lost+found的时间戳。
+    ///
+    /// config.reproducible为Some时不使用rng：UUID（未指定时）和哈希种子由格式化参数和固定时间导出，
+    /// 所有时间戳都取固定时间，相同的参数在全0的设备上总是生成逐字节相同的镜像。
+    pub fn mkfs(
+        dev: Dev,
+        opts: &MkfsOptions,
+        config: FsConfig,
+        rng: &mut impl RandomSource,
+    ) -> Ext4Result<Self> {
+        Self::mkfs_with_progress(dev, opts, config, rng, &mut |_| {})
+    }
+
+    /// 与mkfs相同，格式化期间通过progress报告进度
+    ///
+    /// 依次报告ProgressPhase::MkfsGroups（每写完一个块组）和MkfsJournal（按日志块，
+    /// 不创建日志时没有这一阶段），两个阶段都给出总数。
+    pub fn mkfs_with_progress(
+        dev: Dev,
+        opts: &MkfsOptions,
+        config: FsConfig,
+        rng: &mut impl RandomSource,
+        progress: &mut dyn FnMut(&Progress),
+    ) -> Ext4Result<Self> {
+        let backend = Backend::select(config.backend)?;
+        if config.read_only {
+            return Err(Ext4Error::new(EROFS as _, "mkfs on a read-only mount"));
+        }
+        if opts.label.len() > EXT4_LABEL_MAX {
+            return Err(Ext4Error::new(EINVAL as _, "volume label too long"));
+        }
+        if opts.reserved_percent > EXT4_RESERVED_PERCENT_MAX {
+            return Err(Ext4Error::new(EINVAL as _, "reserved percent out of range"));
+        }
+
+        let mut seeded = config.reproducible.map(|time| SeededRandom::new(opts, time));
+        let rng: &mut dyn RandomSource = match seeded.as_mut() {
+            Some(seeded) => seeded,
+            None => rng,
+        };
+        let mut info = Ext4MkfsInfo {
+            block_size: opts.block_size,
+            inode_size: opts.inode_size,
+            inodes: opts.inodes,
+            journal: opts.journal,
+            journal_blocks: opts.journal_blocks,
+            uuid: opts.uuid.unwrap_or_else(|| uuid_v4(rng)),
+            ..Default::default()
+        };
+        if !opts.metadata_csum {
+            info.feat_compat = EXT4_MKFS_DEFAULT_FCOM;
+            info.feat_incompat = EXT4_MKFS_DEFAULT_FINCOM;
+            info.feat_ro_compat = EXT4_MKFS_DEFAULT_FRO_COM & !EXT4_FRO_COM_METADATA_CSUM;
+        }
+        rng.fill_bytes(&mut info.hash_seed);
+        info.label[..opts.label.len()].copy_from_slice(opts.label.as_bytes());
+
+        let mut bdev = Ext4BlockDevice::new(dev)?;
+        let mut fs: Box<ext4_fs> = Box::new(unsafe { mem::zeroed() });
+        fs.no_kbytes_written = config.reproducible.is_some();
+        let mut report = |phase, done, total| {
+            let phase = match phase {
+                EXT4_MKFS_PROGRESS_GROUPS => ProgressPhase::MkfsGroups,
+                _ => ProgressPhase::MkfsJournal,
+            };
+            progress(&Progress { phase, done, total: Some(total) });
+        };
+        unsafe { ext4_mkfs_progress(&mut *fs, bdev.inner.as_mut(), &mut info, &mut report) }
+            .context("ext4_mkfs")?;
+        debug!(
+            "mkfs: {} bytes, block size {}, {} inodes, journal {} blocks",
+            info.len, info.block_size, info.inodes, info.journal_blocks
+        );
+
+        let mut result = Self::mount(bdev, backend, config)?;
+        if let Some(now) = result.now() {
+            let secs = now.as_secs();
+            let sb = &mut result.inner.sb;
+            sb.mkfs_time = (secs as u32).to_le();
+            sb.mkfs_time_hi = (secs >> 32) as u8;
+            let lpf = u32::from_le(sb.lpf_ino);
+            for ino in [EXT4_INODE_ROOT_INDEX, lpf] {
+                result.with_inode_ref(ino, |inode| {
+                    inode.set_atime(&now);
+                    inode.set_mtime(&now);
+                    inode.set_ctime(&now);
+                    inode.set_btime(&now);
+                    Ok(())
+                })?;
+            }
+        }
+        // 同时写回superblock
+        result.set_reserved_percent(opts.reserved_percent)?;
+        Ok(result)
+    }
+
+    /// 设置随机生成的UUID（版本4）
+    pub fn set_random_uuid(&mut self, rng: &mut impl RandomSource) -> Ext4Result<()> {
+        self.set_uuid(uuid_v4(rng))
+    }
+}
@@ -0,0 +1,127 @@
+//! 目录项名称模块，磁盘上的名称是任意字节序列，不要求是合法的UTF-8。
+
+use core::fmt::{self, Debug, Display, Write};
+
+use alloc::{string::String, vec::Vec};
+
+/// 目录项名称（原始字节）
+///
+/// 按名称操作的接口都接受FileName，&str、String和字节切片均可直接传入，
+/// 非UTF-8名称（常见于各类设备生成的镜像）也能查找、创建、重命名和删除。
+/// Display按有损方式输出，无效的字节显示为U+FFFD。
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileName<'a>(&'a [u8]);
+
+impl<'a> FileName<'a> {
+    /// 由原始字节创建名称
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    /// 名称的原始字节
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// 名称的字节数
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// 名称是否为空
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 名称是合法的UTF-8时返回字符串
+    pub fn to_str(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.0).ok()
+    }
+
+    /// 转换为字符串（无效的字节替换为U+FFFD）
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(self.0).into_owned()
+    }
+
+    /// 是否为"."或".."
+    pub fn is_dot(&self) -> bool {
+        self.0 == b"." || self.0 == b".."
+    }
+}
+
+/// 用于在错误上下文中记录名称（ErrorContext::path_segment）
+impl AsRef<[u8]> for FileName<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> From<&'a str> for FileName<'a> {
+    fn from(name: &'a str) -> Self {
+        Self(name.as_bytes())
+    }
+}
+
+impl<'a> From<&'a String> for FileName<'a> {
+    fn from(name: &'a String) -> Self {
+        Self(name.as_bytes())
+    }
+}
+
+impl<'a> From<&'a [u8]> for FileName<'a> {
+    fn from(name: &'a [u8]) -> Self {
+        Self(name)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for FileName<'a> {
+    fn from(name: &'a [u8; N]) -> Self {
+        Self(name)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for FileName<'a> {
+    fn from(name: &'a Vec<u8>) -> Self {
+        Self(name)
+    }
+}
+
+impl PartialEq<str> for FileName<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<[u8]> for FileName<'_> {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.0 == other
+    }
+}
+
+impl Display for FileName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_char(char::REPLACEMENT_CHARACTER)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 与&str的Debug格式相同，无效的字节按\xNN转义
+impl Debug for FileName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                write!(f, "{}", c.escape_debug())?;
+            }
+            for b in chunk.invalid() {
+                write!(f, "\\x{b:02x}")?;
+            }
+        }
+        f.write_char('"')
+    }
+}
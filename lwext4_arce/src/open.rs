@@ -0,0 +1,270 @@
+//! 按打开选项打开文件：与std::fs::OpenOptions和fopen的模式字符串对应，
+//! 把O_CREAT、O_EXCL、O_TRUNC、O_APPEND等标志映射为创建、截断和追加行为，
+//! 宿主运行时可以直接把POSIX open()转到这里。
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4ErrorKind, Ext4Filesystem, Ext4Result, InodeType, SystemHal,
+    error::ErrorContext,
+};
+
+// Linux的open()标志取值
+const O_ACCMODE: u32 = 0o3;
+const O_WRONLY: u32 = 0o1;
+const O_RDWR: u32 = 0o2;
+const O_CREAT: u32 = 0o100;
+const O_EXCL: u32 = 0o200;
+const O_TRUNC: u32 = 0o1000;
+const O_APPEND: u32 = 0o2000;
+
+/// 打开选项（对应std::fs::OpenOptions）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,     // 每次写入前定位到文件末尾（隐含可写）
+    truncate: bool,   // 打开已有文件时截断为0
+    create: bool,     // 不存在时创建
+    create_new: bool, // 总是创建，已存在时返回AlreadyExists
+    mode: u32,        // 新建文件的权限位
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    /// 所有选项都关闭，新建文件的权限为0o644
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            mode: 0o644,
+        }
+    }
+
+    /// 由fopen的模式字符串创建（"r"、"w"、"a"、"r+"、"w+"、"a+"，可带"b"），无法识别时返回None
+    pub fn from_mode_str(mode: &str) -> Option<Self> {
+        let flags = match mode {
+            "r" | "rb" => 0,
+            "w" | "wb" => O_WRONLY | O_CREAT | O_TRUNC,
+            "a" | "ab" => O_WRONLY | O_CREAT | O_APPEND,
+            "r+" | "rb+" | "r+b" => O_RDWR,
+            "w+" | "wb+" | "w+b" => O_RDWR | O_CREAT | O_TRUNC,
+            "a+" | "ab+" | "a+b" => O_RDWR | O_CREAT | O_APPEND,
+            _ => return None,
+        };
+        Some(Self::from_flags(flags))
+    }
+
+    /// 由POSIX open()的标志创建（Linux取值），不认识的标志被忽略
+    ///
+    /// O_EXCL只与O_CREAT同时使用时有效。与std不同，O_TRUNC可以和O_APPEND同时使用。
+    pub fn from_flags(flags: u32) -> Self {
+        let access = flags & O_ACCMODE;
+        let creat = flags & O_CREAT != 0;
+        Self {
+            read: access != O_WRONLY,
+            write: access == O_WRONLY || access == O_RDWR,
+            append: flags & O_APPEND != 0,
+            truncate: flags & O_TRUNC != 0,
+            create: creat,
+            create_new: creat && flags & O_EXCL != 0,
+            ..Self::new()
+        }
+    }
+
+    /// 可读
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// 可写
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// 追加写入（隐含可写）
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// 打开已有文件时截断为0（需要可写）
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// 文件不存在时创建（需要可写）
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// 总是创建新文件，已存在时返回AlreadyExists（需要可写，忽略create和truncate）
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// 新建文件的权限位
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// 按选项打开路径，等价于fs.open(path, self)
+    pub fn open<'a, Hal: SystemHal, Dev: BlockDevice>(
+        &self,
+        fs: &'a mut Ext4Filesystem<Hal, Dev>,
+        path: &str,
+    ) -> Ext4Result<OpenFile<'a, Hal, Dev>> {
+        fs.open(path, self)
+    }
+
+    fn writable(&self) -> bool {
+        self.write || self.append
+    }
+}
+
+/// 已打开的文件
+///
+/// 通过 [`Ext4Filesystem::open`] 或 [`OpenOptions::open`] 创建，持有文件系统的可变借用，
+/// 在当前位置顺序读写。
+pub struct OpenFile<'a, Hal: SystemHal, Dev: BlockDevice> {
+    fs: &'a mut Ext4Filesystem<Hal, Dev>, // 所属文件系统
+    ino: u32,                             // 文件的inode编号
+    pos: u64,                             // 当前读写位置
+    opts: OpenOptions,                    // 打开时的选项
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 按打开选项打开普通文件（路径不跟随符号链接，与resolve_path相同）
+    ///
+    /// 选项组合无效（既不可读也不可写，或截断、创建时不可写）返回InvalidInput；
+    /// 路径是目录时返回IsADirectory，是符号链接或特殊文件时返回InvalidInput。
+    /// 需要写入时在打开前检查文件系统是否可写。
+    pub fn open(&mut self, path: &str, opts: &OpenOptions) -> Ext4Result<OpenFile<'_, Hal, Dev>> {
+        let invalid = |msg| {
+            Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, msg)
+                .with_context(ErrorContext::new("open")))
+        };
+        let writable = opts.writable();
+        if !opts.read && !writable {
+            return invalid("neither readable nor writable");
+        }
+        if (opts.truncate || opts.create || opts.create_new) && !writable {
+            return invalid("truncate or create without write access");
+        }
+        if writable {
+            self.ensure_writable()?;
+        }
+
+        let (ino, created) = match self.resolve_path(path) {
+            Ok(_) if opts.create_new => {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::AlreadyExists, None)
+                    .with_context(ErrorContext::new("open").path_segment(path)));
+            }
+            Ok(ino) => (ino, false),
+            Err(err) if err.kind() == Ext4ErrorKind::NotFound && (opts.create || opts.create_new) => {
+                let path = path.trim_end_matches('/');
+                let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+                let parent = self.resolve_path(parent_path)?;
+                (self.create(parent, name, InodeType::RegularFile, opts.mode)?, true)
+            }
+            Err(err) => return Err(err),
+        };
+
+        match self.inode_ref(ino)?.inode_type() {
+            InodeType::RegularFile => {}
+            InodeType::Directory => {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::IsADirectory, None)
+                    .with_context(ErrorContext::new("open").ino(ino)));
+            }
+            _ => {
+                return Err(Ext4Error::from_kind(Ext4ErrorKind::InvalidInput, "not a regular file")
+                    .with_context(ErrorContext::new("open").ino(ino)));
+            }
+        }
+        if opts.truncate && !opts.create_new && !created {
+            self.set_len(ino, 0)?;
+        }
+        Ok(OpenFile {
+            fs: self,
+            ino,
+            pos: 0,
+            opts: *opts,
+        })
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> OpenFile<'_, Hal, Dev> {
+    /// 文件的inode编号
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// 当前读写位置
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// 设置读写位置（可以超出文件末尾，之后的写入留下空洞）
+    pub fn seek(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// 文件大小
+    pub fn len(&mut self) -> Ext4Result<u64> {
+        Ok(self.fs.inode_ref(self.ino)?.size())
+    }
+
+    /// 文件是否为空
+    pub fn is_empty(&mut self) -> Ext4Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// 从当前位置读取，返回读取的字节数（文件末尾返回0）
+    pub fn read(&mut self, buf: &mut [u8]) -> Ext4Result<usize> {
+        if !self.opts.read {
+            return Err(self.denied("file not opened for reading"));
+        }
+        let n = self.fs.read_at(self.ino, buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    /// 在当前位置写入（追加模式下先定位到文件末尾），返回写入的字节数
+    pub fn write(&mut self, buf: &[u8]) -> Ext4Result<usize> {
+        if !self.opts.writable() {
+            return Err(self.denied("file not opened for writing"));
+        }
+        if self.opts.append {
+            self.pos = self.len()?;
+        }
+        let n = self.fs.write_at(self.ino, buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    /// 修改文件大小（不移动读写位置）
+    pub fn set_len(&mut self, len: u64) -> Ext4Result<()> {
+        if !self.opts.writable() {
+            return Err(self.denied("file not opened for writing"));
+        }
+        self.fs.set_len(self.ino, len)
+    }
+
+    fn denied(&self, msg: &'static str) -> Ext4Error {
+        Ext4Error::from_kind(Ext4ErrorKind::NotPermitted, msg)
+            .with_context(ErrorContext::new("open_file").ino(self.ino))
+    }
+}
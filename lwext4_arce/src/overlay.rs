@@ -0,0 +1,282 @@
+//! 写时复制块设备：从只读的基础镜像读取，写入重定向到差异存储。
+//!
+//! 可用于在测试中安全地修改标准镜像，或实现重启即丢弃修改的临时根文件系统。
+//! 差异以EXT4_DEV_BSIZE大小的块为单位保存（基础设备的物理块更大时，一个物理块对应多个差异块）；
+//! 基础设备永远不会被写入。
+
+use alloc::{boxed::Box, collections::BTreeMap};
+
+#[cfg(feature = "std")]
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+#[cfg(feature = "std")]
+use crate::ffi::EIO;
+use crate::{BlockDevice, EXT4_DEV_BSIZE, Ext4Error, Ext4Result, ffi::EINVAL};
+
+/// 差异存储：保存被覆盖的块（块号以EXT4_DEV_BSIZE为单位）
+pub trait DeltaStore {
+    /// 读取块的覆盖内容（该块没有被覆盖时返回false，buf内容不确定）
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8; EXT4_DEV_BSIZE]) -> Ext4Result<bool>;
+
+    /// 保存块的新内容
+    fn write_block(&mut self, block_id: u64, buf: &[u8; EXT4_DEV_BSIZE]) -> Ext4Result<()>;
+}
+
+/// 允许以可变引用作为差异存储（文件系统卸载后仍可检查差异）
+impl<T: DeltaStore + ?Sized> DeltaStore for &mut T {
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8; EXT4_DEV_BSIZE]) -> Ext4Result<bool> {
+        (**self).read_block(block_id, buf)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8; EXT4_DEV_BSIZE]) -> Ext4Result<()> {
+        (**self).write_block(block_id, buf)
+    }
+}
+
+/// 内存中的差异存储
+#[derive(Debug, Default, Clone)]
+pub struct MemoryDelta {
+    blocks: BTreeMap<u64, Box<[u8; EXT4_DEV_BSIZE]>>,
+}
+
+impl MemoryDelta {
+    /// 创建空的差异存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 被覆盖的块数
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// 是否没有任何覆盖
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// 按块号顺序遍历被覆盖的块
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[u8; EXT4_DEV_BSIZE])> {
+        self.blocks.iter().map(|(&id, data)| (id, &**data))
+    }
+
+    /// 丢弃全部修改（设备恢复为基础镜像的内容）
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+impl DeltaStore for MemoryDelta {
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8; EXT4_DEV_BSIZE]) -> Ext4Result<bool> {
+        match self.blocks.get(&block_id) {
+            Some(data) => {
+                buf.copy_from_slice(&data[..]);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8; EXT4_DEV_BSIZE]) -> Ext4Result<()> {
+        self.blocks.insert(block_id, Box::new(*buf));
+        Ok(())
+    }
+}
+
+/// 写时复制块设备（基础设备的物理块不能小于EXT4_DEV_BSIZE）
+pub struct OverlayDevice<Base: BlockDevice, Delta: DeltaStore> {
+    base: Base,
+    delta: Delta,
+}
+
+impl<Base: BlockDevice, Delta: DeltaStore> OverlayDevice<Base, Delta> {
+    /// 在基础设备之上叠加差异存储
+    pub fn new(base: Base, delta: Delta) -> Self {
+        Self { base, delta }
+    }
+
+    /// 基础设备
+    pub fn base(&self) -> &Base {
+        &self.base
+    }
+
+    /// 差异存储
+    pub fn delta(&self) -> &Delta {
+        &self.delta
+    }
+
+    /// 差异存储（可变）
+    pub fn delta_mut(&mut self) -> &mut Delta {
+        &mut self.delta
+    }
+
+    /// 拆分为基础设备和差异存储
+    pub fn into_parts(self) -> (Base, Delta) {
+        (self.base, self.delta)
+    }
+}
+
+/// 检查缓冲区长度是块大小的整数倍
+fn check_len(len: usize, block_size: usize) -> Ext4Result<()> {
+    if !len.is_multiple_of(block_size) {
+        return Err(Ext4Error::new(EINVAL as _, "buffer is not a multiple of the block size"));
+    }
+    Ok(())
+}
+
+impl<Base: BlockDevice, Delta: DeltaStore> OverlayDevice<Base, Delta> {
+    /// 每个物理块对应的差异块数
+    fn delta_per_block(&self) -> usize {
+        (self.base.block_size() / EXT4_DEV_BSIZE).max(1)
+    }
+
+    /// 将物理块中被覆盖的部分读入buf，返回被覆盖的差异块数
+    fn read_delta(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let per = self.delta_per_block();
+        let mut covered = 0;
+        for (i, chunk) in buf.chunks_exact_mut(EXT4_DEV_BSIZE).enumerate() {
+            let id = block_id * per as u64 + i as u64;
+            if self.delta.read_block(id, chunk.try_into().unwrap())? {
+                covered += 1;
+            }
+        }
+        Ok(covered)
+    }
+}
+
+impl<Base: BlockDevice, Delta: DeltaStore> BlockDevice for OverlayDevice<Base, Delta> {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        check_len(buf.len(), self.block_size())?;
+        let first = block_id * self.delta_per_block() as u64;
+        for (i, chunk) in buf.chunks_exact(EXT4_DEV_BSIZE).enumerate() {
+            self.delta.write_block(first + i as u64, chunk.try_into().unwrap())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let bs = self.block_size();
+        check_len(buf.len(), bs)?;
+        let per = self.delta_per_block();
+        // 连续的未完全覆盖的物理块合并为一次基础设备读取
+        let count = buf.len() / bs;
+        let mut run_start = 0;
+        for i in 0..=count {
+            let covered = i < count && {
+                let chunk = &mut buf[i * bs..(i + 1) * bs];
+                self.read_delta(block_id + i as u64, chunk)? == per
+            };
+            if covered || i == count {
+                if run_start < i {
+                    let run = &mut buf[run_start * bs..i * bs];
+                    self.base.read_blocks(block_id + run_start as u64, run)?;
+                    // 部分覆盖的物理块重新叠加差异
+                    if per > 1 {
+                        for j in run_start..i {
+                            self.read_delta(block_id + j as u64, &mut buf[j * bs..(j + 1) * bs])?;
+                        }
+                    }
+                }
+                run_start = i + 1;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        self.base.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.base.block_size()
+    }
+
+    fn alignment(&self) -> usize {
+        self.base.alignment()
+    }
+}
+
+/// 以文件保存的差异存储（仅std特性启用时）
+///
+/// 文件由(块号, 块内容)记录依次组成，打开时扫描一遍建立索引，因此差异可以跨进程保留。
+#[cfg(feature = "std")]
+pub struct FileDelta {
+    file: File,
+    index: BTreeMap<u64, u64>, // 块号 -> 记录在文件中的偏移
+}
+
+#[cfg(feature = "std")]
+impl FileDelta {
+    /// 每条记录的长度（8字节块号 + 块内容）
+    const RECORD_SIZE: u64 = 8 + EXT4_DEV_BSIZE as u64;
+
+    /// 创建新的差异文件（已存在时清空）
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            file,
+            index: BTreeMap::new(),
+        })
+    }
+
+    /// 打开已有的差异文件
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut file = File::options().read(true).write(true).open(path)?;
+        let records = file.metadata()?.len() / Self::RECORD_SIZE;
+        let mut index = BTreeMap::new();
+        let mut id = [0u8; 8];
+        for i in 0..records {
+            let offset = i * Self::RECORD_SIZE;
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut id)?;
+            index.insert(u64::from_le_bytes(id), offset);
+        }
+        Ok(Self { file, index })
+    }
+
+    /// 被覆盖的块数
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// 是否没有任何覆盖
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl DeltaStore for FileDelta {
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8; EXT4_DEV_BSIZE]) -> Ext4Result<bool> {
+        let Some(&offset) = self.index.get(&block_id) else {
+            return Ok(false);
+        };
+        self.file
+            .seek(SeekFrom::Start(offset + 8))
+            .and_then(|_| self.file.read_exact(buf))
+            .map_err(|_| Ext4Error::new(EIO as _, "delta read failed"))?;
+        Ok(true)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8; EXT4_DEV_BSIZE]) -> Ext4Result<()> {
+        let io_err = |_| Ext4Error::new(EIO as _, "delta write failed");
+        let offset = match self.index.get(&block_id) {
+            Some(&offset) => offset,
+            None => self.file.seek(SeekFrom::End(0)).map_err(io_err)?,
+        };
+        self.file.seek(SeekFrom::Start(offset)).map_err(io_err)?;
+        self.file.write_all(&block_id.to_le_bytes()).map_err(io_err)?;
+        self.file.write_all(buf).map_err(io_err)?;
+        self.index.insert(block_id, offset);
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! 配额用量统计模块：在挂载期间按uid/gid统计占用的空间和inode数，供管理程序上报。
+//!
+//! 只在内存中统计，不读写配额inode（quota特性），也不限制用量。启用时扫描一次inode表，
+//! 之后随块分配/释放、inode分配/释放和修改属主增量更新；重新挂载后需要再次启用。
+
+use alloc::collections::BTreeMap;
+
+use crate::{BlockDevice, Ext4Filesystem, Ext4Result, SystemHal, error::Context, ffi::*};
+
+/// 一个用户或组的用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub space: u64,  // 占用空间（字节，含索引块和扩展属性块）
+    pub inodes: u64, // inode数
+}
+
+/// 全部用户和组的用量（没有占用的id不出现）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuotaReport {
+    pub users: BTreeMap<u32, QuotaUsage>,
+    pub groups: BTreeMap<u32, QuotaUsage>,
+}
+
+impl From<&ext4_quota_entry> for QuotaUsage {
+    fn from(entry: &ext4_quota_entry) -> Self {
+        Self {
+            space: entry.space,
+            inodes: entry.inodes,
+        }
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 启用用量统计（扫描全部已使用的inode得到当前用量；已启用时重新扫描）
+    ///
+    /// 根目录以外的保留inode（日志等）不计入。
+    pub fn enable_quota_tracking(&mut self) -> Ext4Result<()> {
+        unsafe { ext4_quota_enable(self.inner.as_mut()) }.context("ext4_quota_enable")
+    }
+
+    /// 停止用量统计
+    pub fn disable_quota_tracking(&mut self) {
+        unsafe { ext4_quota_disable(self.inner.as_mut()) }
+    }
+
+    /// 当前的全部用量（未启用统计时返回None）
+    pub fn quota_report(&self) -> Option<QuotaReport> {
+        let usage = self.inner.quota.as_ref()?;
+        let convert = |map: &BTreeMap<u32, ext4_quota_entry>| {
+            map.iter().map(|(&id, entry)| (id, entry.into())).collect()
+        };
+        Some(QuotaReport {
+            users: convert(&usage.users),
+            groups: convert(&usage.groups),
+        })
+    }
+
+    /// 用户的用量（未启用统计时返回None）
+    pub fn user_usage(&self, uid: u32) -> Option<QuotaUsage> {
+        let usage = self.inner.quota.as_ref()?;
+        Some(usage.users.get(&uid).map(Into::into).unwrap_or_default())
+    }
+
+    /// 组的用量（未启用统计时返回None）
+    pub fn group_usage(&self, gid: u32) -> Option<QuotaUsage> {
+        let usage = self.inner.quota.as_ref()?;
+        Some(usage.groups.get(&gid).map(Into::into).unwrap_or_default())
+    }
+}
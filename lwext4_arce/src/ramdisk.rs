@@ -0,0 +1,69 @@
+//! 内存块设备：以内存中的字节数组作为磁盘，不依赖宿主文件系统。
+//!
+//! 适用于无持久存储的嵌入式环境的临时文件系统，以及测试和模糊测试中快速构造、修改镜像。
+
+use alloc::{vec, vec::Vec};
+
+use crate::{BlockDevice, EXT4_DEV_BSIZE, Ext4Error, Ext4Result, ffi::ENXIO};
+
+/// 以内存为后端的块设备（块大小为EXT4_DEV_BSIZE，不足一块的尾部不可访问）
+#[derive(Debug, Default, Clone)]
+pub struct RamDisk {
+    data: Vec<u8>,
+}
+
+impl RamDisk {
+    /// 创建指定字节数、内容全为0的设备
+    pub fn new(len: usize) -> Self {
+        Self { data: vec![0; len] }
+    }
+
+    /// 以已有的镜像内容创建设备
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// 设备的全部内容
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// 设备的全部内容（可修改，用于在卸载后直接改写镜像）
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// 取出设备内容
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// 块范围对应的字节范围，超出设备末尾时返回ENXIO
+    fn range(&self, block_id: u64, len: usize) -> Ext4Result<core::ops::Range<usize>> {
+        let start = usize::try_from(block_id)
+            .ok()
+            .and_then(|id| id.checked_mul(EXT4_DEV_BSIZE));
+        match start.and_then(|start| Some(start..start.checked_add(len)?)) {
+            Some(range) if range.end <= self.data.len() => Ok(range),
+            _ => Err(Ext4Error::new(ENXIO as _, "ram disk access out of range")),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        let range = self.range(block_id, buf.len())?;
+        self.data[range].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let range = self.range(block_id, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(buf.len())
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        Ok((self.data.len() / EXT4_DEV_BSIZE) as u64)
+    }
+}
@@ -0,0 +1,89 @@
+//! 记录块设备：转发并按顺序记录所有写入，可以把任意前缀重放到另一个设备上，
+//! 模拟在任意两次写入之间断电后磁盘上的状态，用于崩溃一致性测试。
+//!
+//! 记录以BlockDevice的一次write_blocks调用为单位；假定设备按调用顺序持久化写入（没有写缓存重排）。
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{BlockDevice, Ext4Result};
+
+/// 一次写入
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteRecord {
+    pub block_id: u64,  // 起始块号（以设备物理块为单位）
+    pub data: Box<[u8]>, // 写入的数据
+}
+
+/// 记录所有写入的块设备
+pub struct RecordingDevice<Dev: BlockDevice> {
+    dev: Dev,
+    writes: Vec<WriteRecord>,
+}
+
+impl<Dev: BlockDevice> RecordingDevice<Dev> {
+    /// 包装块设备，从此刻开始记录
+    pub fn new(dev: Dev) -> Self {
+        Self {
+            dev,
+            writes: Vec::new(),
+        }
+    }
+
+    /// 底层设备
+    pub fn inner(&self) -> &Dev {
+        &self.dev
+    }
+
+    /// 取回底层设备
+    pub fn into_inner(self) -> Dev {
+        self.dev
+    }
+
+    /// 按顺序记录的写入
+    pub fn writes(&self) -> &[WriteRecord] {
+        &self.writes
+    }
+
+    /// 丢弃已有记录（之后的写入从头开始记录，重放的起点相应变为当前的设备内容）
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+
+    /// 把前count次写入依次重放到target上
+    ///
+    /// target应当与开始记录时的底层设备内容相同，重放后即为第count次写入完成、
+    /// 之后的写入都未发生时的磁盘状态。count超过记录数时重放全部写入。
+    pub fn replay(&self, count: usize, target: &mut impl BlockDevice) -> Ext4Result<()> {
+        for record in self.writes.iter().take(count) {
+            target.write_blocks(record.block_id, &record.data)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Dev: BlockDevice> BlockDevice for RecordingDevice<Dev> {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        let written = self.dev.write_blocks(block_id, buf)?;
+        self.writes.push(WriteRecord {
+            block_id,
+            data: buf[..written].into(),
+        });
+        Ok(written)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        self.dev.read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        self.dev.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn alignment(&self) -> usize {
+        self.dev.alignment()
+    }
+}
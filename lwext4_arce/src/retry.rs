@@ -0,0 +1,161 @@
+//! 重试块设备：对暂时性I/O错误（EIO，常见于SD卡）按策略重试，并在写入反复失败后转为只读。
+//!
+//! 转为只读后写入返回EROFS，文件系统随之切换为只读（与errors=remount-ro相同，
+//! 但不受superblock中errors字段的影响）。读取失败不会导致转为只读。
+
+use core::{marker::PhantomData, time::Duration};
+
+use crate::{BlockDevice, DummyHal, Ext4Error, Ext4Result, SystemHal, ffi::*};
+
+/// 重试策略
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub read_retries: u32,        // 读取失败后的重试次数
+    pub write_retries: u32,       // 写入失败后的重试次数
+    pub backoff: Duration,        // 第一次重试前的等待时间，之后每次加倍
+    pub max_backoff: Duration,    // 等待时间上限
+    pub write_failure_limit: u32, // 连续多少次写入（重试用尽后）失败时转为只读，0表示从不
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            read_retries: 3,
+            write_retries: 3,
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+            write_failure_limit: 3,
+        }
+    }
+}
+
+/// 重试统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetryStats {
+    pub read_retries: u64,  // 读取重试次数
+    pub write_retries: u64, // 写入重试次数
+    pub failed_reads: u64,  // 重试用尽后仍失败的读取
+    pub failed_writes: u64, // 重试用尽后仍失败的写入
+}
+
+/// 带重试的块设备
+pub struct RetryDevice<Dev: BlockDevice, Hal: SystemHal = DummyHal> {
+    dev: Dev,
+    policy: RetryPolicy,
+    stats: RetryStats,
+    write_failures: u32, // 连续失败的写入数
+    read_only: bool,     // 是否已转为只读
+    _phantom: PhantomData<Hal>,
+}
+
+impl<Dev: BlockDevice, Hal: SystemHal> RetryDevice<Dev, Hal> {
+    /// 按策略包装块设备
+    pub fn new(dev: Dev, policy: RetryPolicy) -> Self {
+        Self {
+            dev,
+            policy,
+            stats: RetryStats::default(),
+            write_failures: 0,
+            read_only: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// 底层设备
+    pub fn inner(&self) -> &Dev {
+        &self.dev
+    }
+
+    /// 底层设备（可变）
+    pub fn inner_mut(&mut self) -> &mut Dev {
+        &mut self.dev
+    }
+
+    /// 取回底层设备
+    pub fn into_inner(self) -> Dev {
+        self.dev
+    }
+
+    /// 重试统计
+    pub fn stats(&self) -> &RetryStats {
+        &self.stats
+    }
+
+    /// 是否因写入反复失败而转为只读
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 解除只读状态（如更换介质后）；文件系统的只读状态需要重新挂载才能解除
+    pub fn reset(&mut self) {
+        self.read_only = false;
+        self.write_failures = 0;
+    }
+
+    /// 执行op，遇到EIO时按策略重试；返回结果和重试次数
+    fn with_retries<T>(
+        &mut self,
+        retries: u32,
+        mut op: impl FnMut(&mut Dev) -> Ext4Result<T>,
+    ) -> (Ext4Result<T>, u32) {
+        let mut backoff = self.policy.backoff;
+        let mut attempt = 0;
+        loop {
+            match op(&mut self.dev) {
+                Err(err) if err.errno() == EIO as _ && attempt < retries => {
+                    debug!("transient I/O error, retry {}: {err:?}", attempt + 1);
+                    Hal::sleep(backoff);
+                    backoff = (backoff * 2).min(self.policy.max_backoff);
+                    attempt += 1;
+                }
+                result => return (result, attempt),
+            }
+        }
+    }
+}
+
+impl<Dev: BlockDevice, Hal: SystemHal> BlockDevice for RetryDevice<Dev, Hal> {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        if self.read_only {
+            return Err(Ext4Error::new(EROFS as _, "device switched to read-only"));
+        }
+        let (result, retries) =
+            self.with_retries(self.policy.write_retries, |dev| dev.write_blocks(block_id, buf));
+        self.stats.write_retries += retries as u64;
+        if result.is_ok() {
+            self.write_failures = 0;
+            return result;
+        }
+        self.stats.failed_writes += 1;
+        self.write_failures += 1;
+        let limit = self.policy.write_failure_limit;
+        if limit != 0 && self.write_failures >= limit {
+            error!("{} consecutive write failures, switching device to read-only", self.write_failures);
+            self.read_only = true;
+            return Err(Ext4Error::new(EROFS as _, "too many write failures"));
+        }
+        result
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        let (result, retries) =
+            self.with_retries(self.policy.read_retries, |dev| dev.read_blocks(block_id, buf));
+        self.stats.read_retries += retries as u64;
+        if result.is_err() {
+            self.stats.failed_reads += 1;
+        }
+        result
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        self.dev.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn alignment(&self) -> usize {
+        self.dev.alignment()
+    }
+}
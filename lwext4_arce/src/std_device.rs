@@ -0,0 +1,74 @@
+//! 宿主环境（std）下基于镜像文件的块设备实现，供命令行工具和测试使用。
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{BlockDevice, EXT4_DEV_BSIZE, Ext4Error, Ext4Result, ffi::EIO};
+
+/// 将宿主I/O错误转换为Ext4Error（保留errno，没有errno时为EIO，如读到文件末尾之后）
+fn io_error(op: &'static str) -> impl FnOnce(io::Error) -> Ext4Error {
+    move |err| Ext4Error::new(err.raw_os_error().unwrap_or(EIO as _), op)
+}
+
+/// 以普通文件作为后端的块设备（块大小默认为EXT4_DEV_BSIZE）
+pub struct FileBlockDevice {
+    file: File,
+    block_size: usize,
+}
+
+impl FileBlockDevice {
+    /// 以读写方式打开镜像文件
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::open_with_block_size(path, EXT4_DEV_BSIZE)
+    }
+
+    /// 以指定的物理块大小打开镜像文件（如4096，模拟4Kn设备）
+    pub fn open_with_block_size(path: impl AsRef<Path>, block_size: usize) -> std::io::Result<Self> {
+        Ok(Self {
+            file: File::options().read(true).write(true).open(path)?,
+            block_size,
+        })
+    }
+
+    /// 定位到指定块的起始位置
+    fn seek_block(&mut self, block_id: u64) -> Ext4Result<()> {
+        self.file
+            .seek(SeekFrom::Start(block_id * self.block_size as u64))
+            .map_err(io_error("seek failed"))?;
+        Ok(())
+    }
+}
+
+impl BlockDevice for FileBlockDevice {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
+        self.seek_block(block_id)?;
+        self.file
+            .write_all(buf)
+            .map_err(io_error("write failed"))?;
+        Ok(buf.len())
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
+        self.seek_block(block_id)?;
+        self.file
+            .read_exact(buf)
+            .map_err(io_error("read failed"))?;
+        Ok(buf.len())
+    }
+
+    fn num_blocks(&self) -> Ext4Result<u64> {
+        let size = self
+            .file
+            .metadata()
+            .map_err(io_error("metadata failed"))?
+            .len();
+        Ok(size / self.block_size as u64)
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+}
@@ -0,0 +1,589 @@
+//! tar归档流式导入模块（tar特性），将ustar/GNU/PAX格式的归档直接写入已挂载的镜像。
+//!
+//! 归档数据分块送入TarImporter，不需要整个归档在内存中。支持目录、普通文件、符号链接、
+//! 硬链接、命名管道、字符设备和块设备，以及GNU长文件名和PAX扩展头部中的路径、大小、属主和时间戳。
+//! 文件数据以稀疏方式写入，全零的块保留为空洞。
+//! PAX头部中的SCHILY.xattr.*记录（如security.capability）写入条目的扩展属性；
+//! 命名空间不支持或inode内放不下的属性被忽略（C后端下全部忽略），数量记入TarStats::xattrs_skipped。
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, InodeType, ProgressPhase, SystemHal,
+    ffi::EINVAL, makedev,
+};
+
+/// tar块大小
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// 导入统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TarStats {
+    pub entries: u64,        // 导入的条目数
+    pub bytes: u64,          // 写入的文件数据字节数
+    pub skipped: u64,        // 跳过的条目数（不支持的条目类型）
+    pub xattrs: u64,         // 写入的扩展属性数
+    pub xattrs_skipped: u64, // 忽略的扩展属性数
+}
+
+/// 扩展头部（PAX / GNU长文件名）对下一个条目的覆盖值
+#[derive(Debug, Default)]
+struct TarOverrides {
+    path: Option<String>,
+    link: Option<String>,
+    size: Option<u64>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    atime: Option<Duration>,
+    mtime: Option<Duration>,
+    ctime: Option<Duration>,
+    xattrs: Vec<(String, Vec<u8>)>, // SCHILY.xattr.*记录（带命名空间前缀的名称和值）
+}
+
+/// 条目的属性
+#[derive(Debug, Clone, Copy)]
+struct TarAttr {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    atime: Duration,
+    mtime: Duration,
+    ctime: Duration,
+}
+
+/// 解析状态
+#[derive(Debug)]
+enum TarState {
+    /// 读取512字节头部
+    Header,
+    /// 写入普通文件数据
+    File {
+        ino: u32,
+        offset: u64,
+        remaining: u64,
+        attr: TarAttr,
+    },
+    /// 收集扩展头部数据
+    Meta { kind: u8, remaining: u64 },
+    /// 跳过数据或块尾填充
+    Skip { remaining: u64 },
+    /// 已读到结束标记
+    End,
+}
+
+/// tar归档流式导入器
+///
+/// 依次调用feed送入归档数据，最后调用finish完成导入。
+#[derive(Debug)]
+pub struct TarImporter {
+    dest: String,                // 导入的目标目录
+    state: TarState,             // 解析状态
+    block: [u8; TAR_BLOCK_SIZE], // 正在读取的头部
+    block_len: usize,            // 头部已读取的字节数
+    zero_blocks: u32,            // 连续的全零块数（两个表示归档结束）
+    meta: Vec<u8>,               // 正在收集的扩展头部数据
+    overrides: TarOverrides,     // 下一个条目的覆盖值
+    dirs: Vec<(u32, TarAttr)>,   // 待设置属性的目录
+    stats: TarStats,             // 导入统计
+}
+
+impl TarImporter {
+    /// 创建导入器，归档中的路径相对dest（不存在时自动创建）
+    pub fn new(dest: &str) -> Self {
+        Self {
+            dest: dest.trim_end_matches('/').to_string(),
+            state: TarState::Header,
+            block: [0; TAR_BLOCK_SIZE],
+            block_len: 0,
+            zero_blocks: 0,
+            meta: Vec::new(),
+            overrides: TarOverrides::default(),
+            dirs: Vec::new(),
+            stats: TarStats::default(),
+        }
+    }
+
+    /// 送入一段归档数据
+    ///
+    /// 每开始导入一个条目后报告进度（ProgressPhase::Import，见Ext4Filesystem::on_progress）。
+    pub fn feed<Hal: SystemHal, Dev: BlockDevice>(
+        &mut self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+        mut data: &[u8],
+    ) -> Ext4Result<()> {
+        while !data.is_empty() {
+            match &mut self.state {
+                TarState::Header => {
+                    let n = (TAR_BLOCK_SIZE - self.block_len).min(data.len());
+                    self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+                    self.block_len += n;
+                    data = &data[n..];
+                    if self.block_len == TAR_BLOCK_SIZE {
+                        self.block_len = 0;
+                        let entries = self.stats.entries;
+                        self.header(fs)?;
+                        if self.stats.entries != entries {
+                            fs.report_progress(ProgressPhase::Import, self.stats.entries, None);
+                        }
+                    }
+                }
+                TarState::File {
+                    ino,
+                    offset,
+                    remaining,
+                    attr,
+                } => {
+                    let n = (*remaining).min(data.len() as u64) as usize;
+                    fs.write_at_sparse(*ino, &data[..n], *offset)?;
+                    *offset += n as u64;
+                    *remaining -= n as u64;
+                    self.stats.bytes += n as u64;
+                    data = &data[n..];
+                    if *remaining == 0 {
+                        let (ino, size, attr) = (*ino, *offset, *attr);
+                        set_attr(fs, ino, &attr)?;
+                        self.skip_padding(size);
+                    }
+                }
+                TarState::Meta { kind, remaining } => {
+                    let n = (*remaining).min(data.len() as u64) as usize;
+                    self.meta.extend_from_slice(&data[..n]);
+                    *remaining -= n as u64;
+                    data = &data[n..];
+                    if *remaining == 0 {
+                        let kind = *kind;
+                        self.apply_meta(kind)?;
+                        self.skip_padding(self.meta.len() as u64);
+                        self.meta.clear();
+                    }
+                }
+                TarState::Skip { remaining } => {
+                    let n = (*remaining).min(data.len() as u64);
+                    *remaining -= n;
+                    data = &data[n as usize..];
+                    if *remaining == 0 {
+                        self.state = TarState::Header;
+                    }
+                }
+                // 结束标记之后的数据（通常是补齐记录大小的零块）被忽略
+                TarState::End => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// 完成导入：设置目录属性并写回，返回导入统计
+    ///
+    /// 归档在条目中间截断时返回EINVAL；只缺少结束标记的归档视为完整。
+    pub fn finish<Hal: SystemHal, Dev: BlockDevice>(
+        self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+    ) -> Ext4Result<TarStats> {
+        match self.state {
+            TarState::Header if self.block_len == 0 => {}
+            TarState::End => {}
+            _ => return Err(Ext4Error::new(EINVAL as _, "truncated tar archive")),
+        }
+        // 目录的属性最后设置，避免创建子项时修改其时间戳
+        for (ino, attr) in self.dirs.iter().rev() {
+            set_attr(fs, *ino, attr)?;
+        }
+        fs.flush()?;
+        Ok(self.stats)
+    }
+
+    /// 跳过数据之后补齐到512字节的填充
+    fn skip_padding(&mut self, size: u64) {
+        let pad = (TAR_BLOCK_SIZE as u64 - size % TAR_BLOCK_SIZE as u64) % TAR_BLOCK_SIZE as u64;
+        self.state = if pad == 0 {
+            TarState::Header
+        } else {
+            TarState::Skip { remaining: pad }
+        };
+    }
+
+    /// 处理一个完整的头部块
+    fn header<Hal: SystemHal, Dev: BlockDevice>(
+        &mut self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+    ) -> Ext4Result<()> {
+        let hdr = &self.block;
+        if hdr.iter().all(|&b| b == 0) {
+            self.zero_blocks += 1;
+            if self.zero_blocks == 2 {
+                self.state = TarState::End;
+            }
+            return Ok(());
+        }
+        self.zero_blocks = 0;
+
+        // 校验和按校验和字段全为空格计算
+        let expected = parse_number(&hdr[148..156])?;
+        let sum: u64 = hdr
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+            .sum();
+        if sum != expected {
+            return Err(Ext4Error::new(EINVAL as _, "bad tar header checksum"));
+        }
+
+        let kind = hdr[156];
+        let size = parse_number(&hdr[124..136])?;
+        if matches!(kind, b'x' | b'g' | b'L' | b'K') {
+            self.state = TarState::Meta {
+                kind,
+                remaining: size,
+            };
+            if size == 0 {
+                self.apply_meta(kind)?;
+                self.state = TarState::Header;
+            }
+            return Ok(());
+        }
+
+        let overrides = core::mem::take(&mut self.overrides);
+        let size = overrides.size.unwrap_or(size);
+        let path = match overrides.path {
+            Some(path) => path,
+            None => {
+                let name = field_str(&hdr[0..100]);
+                let prefix = if &hdr[257..262] == b"ustar" {
+                    field_str(&hdr[345..500])
+                } else {
+                    ""
+                };
+                if prefix.is_empty() {
+                    name.to_string()
+                } else {
+                    alloc::format!("{prefix}/{name}")
+                }
+            }
+        };
+        let link = overrides
+            .link
+            .unwrap_or_else(|| field_str(&hdr[157..257]).to_string());
+        let mtime = match overrides.mtime {
+            Some(mtime) => mtime,
+            None => Duration::from_secs(parse_number(&hdr[136..148])?),
+        };
+        // 设备号只对字符设备和块设备有意义
+        let rdev = if matches!(kind, b'3' | b'4') {
+            makedev(
+                parse_number(&hdr[329..337])? as u32,
+                parse_number(&hdr[337..345])? as u32,
+            )
+        } else {
+            0
+        };
+        let attr = TarAttr {
+            mode: parse_number(&hdr[100..108])? as u32 & 0o7777,
+            uid: overrides
+                .uid
+                .map_or_else(|| parse_number(&hdr[108..116]).map(|v| v as u32), Ok)?,
+            gid: overrides
+                .gid
+                .map_or_else(|| parse_number(&hdr[116..124]).map(|v| v as u32), Ok)?,
+            rdev,
+            atime: overrides.atime.unwrap_or(mtime),
+            mtime,
+            ctime: overrides.ctime.unwrap_or(mtime),
+        };
+
+        let path = self.image_path(&path)?;
+        self.entry(fs, kind, &path, &link, size, attr, &overrides.xattrs)
+    }
+
+    /// 创建一个条目并进入相应的数据状态
+    #[allow(clippy::too_many_arguments)]
+    fn entry<Hal: SystemHal, Dev: BlockDevice>(
+        &mut self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+        kind: u8,
+        path: &str,
+        link: &str,
+        size: u64,
+        attr: TarAttr,
+        xattrs: &[(String, Vec<u8>)],
+    ) -> Ext4Result<()> {
+        let ty = match kind {
+            b'0' | b'\0' | b'7' => InodeType::RegularFile,
+            b'5' => InodeType::Directory,
+            b'2' => InodeType::Symlink,
+            b'3' => InodeType::CharacterDevice,
+            b'4' => InodeType::BlockDevice,
+            b'6' => InodeType::Fifo,
+            b'1' => {
+                let (parent, name) = self.parent_of(fs, path)?;
+                fs.remove_non_dir(parent, name)?;
+                let target = fs.resolve_path(&self.image_path(link)?)?;
+                fs.link(parent, name, target)?;
+                self.set_xattrs(fs, target, xattrs)?;
+                self.stats.entries += 1;
+                self.skip_padding(0);
+                return Ok(());
+            }
+            _ => {
+                warn!(
+                    "tar: skipping unsupported entry {path} (type {})",
+                    kind as char
+                );
+                self.stats.skipped += 1;
+                self.stats.xattrs_skipped += xattrs.len() as u64;
+                self.state = TarState::Skip {
+                    remaining: size.div_ceil(TAR_BLOCK_SIZE as u64) * TAR_BLOCK_SIZE as u64,
+                };
+                if size == 0 {
+                    self.state = TarState::Header;
+                }
+                return Ok(());
+            }
+        };
+        self.stats.entries += 1;
+
+        if ty == InodeType::Directory {
+            let ino = fs.create_dir_all(path, attr.mode)?;
+            self.set_xattrs(fs, ino, xattrs)?;
+            self.dirs.push((ino, attr));
+            self.skip_padding(0);
+            return Ok(());
+        }
+
+        let (parent, name) = self.parent_of(fs, path)?;
+        fs.remove_non_dir(parent, name)?;
+        let ino = match ty {
+            InodeType::CharacterDevice | InodeType::BlockDevice => {
+                fs.mknod_at(parent, name, ty, attr.mode, attr.rdev)?
+            }
+            _ => fs.create(parent, name, ty, attr.mode)?,
+        };
+        if ty == InodeType::Symlink {
+            fs.set_symlink(ino, link.as_bytes())?;
+        }
+        self.set_xattrs(fs, ino, xattrs)?;
+        if ty == InodeType::RegularFile && size != 0 {
+            self.state = TarState::File {
+                ino,
+                offset: 0,
+                remaining: size,
+                attr,
+            };
+            return Ok(());
+        }
+        set_attr(fs, ino, &attr)?;
+        self.skip_padding(0);
+        Ok(())
+    }
+
+    /// 写入条目的扩展属性
+    ///
+    /// 命名空间不支持或inode内放不下的属性记入xattrs_skipped后继续导入，其他错误中止导入。
+    #[cfg(feature = "use-rust")]
+    fn set_xattrs<Hal: SystemHal, Dev: BlockDevice>(
+        &mut self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+        ino: u32,
+        xattrs: &[(String, Vec<u8>)],
+    ) -> Ext4Result<()> {
+        use crate::Ext4ErrorKind;
+
+        for (name, value) in xattrs {
+            match fs.set_xattr(ino, name, value) {
+                Ok(()) => self.stats.xattrs += 1,
+                Err(err) if matches!(err.kind(), Ext4ErrorKind::Unsupported | Ext4ErrorKind::NoSpace) => {
+                    warn!("tar: skipping xattr {name} of inode {ino}: {err}");
+                    self.stats.xattrs_skipped += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// C后端不支持写入扩展属性，全部记入xattrs_skipped
+    #[cfg(not(feature = "use-rust"))]
+    fn set_xattrs<Hal: SystemHal, Dev: BlockDevice>(
+        &mut self,
+        _fs: &mut Ext4Filesystem<Hal, Dev>,
+        _ino: u32,
+        xattrs: &[(String, Vec<u8>)],
+    ) -> Ext4Result<()> {
+        self.stats.xattrs_skipped += xattrs.len() as u64;
+        Ok(())
+    }
+
+    /// 解析条目的父目录（不存在时创建）和名称
+    fn parent_of<'p, Hal: SystemHal, Dev: BlockDevice>(
+        &self,
+        fs: &mut Ext4Filesystem<Hal, Dev>,
+        path: &'p str,
+    ) -> Ext4Result<(u32, &'p str)> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        Ok((fs.create_dir_all(parent, 0o755)?, name))
+    }
+
+    /// 归档中的路径转换为镜像中的路径（去掉开头的'/'和"./"，拒绝".."）
+    fn image_path(&self, path: &str) -> Ext4Result<String> {
+        let mut result = self.dest.clone();
+        for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if name == ".." {
+                return Err(Ext4Error::new(
+                    EINVAL as _,
+                    "tar entry path escapes destination",
+                ));
+            }
+            result.push('/');
+            result.push_str(name);
+        }
+        if result.is_empty() {
+            result.push('/');
+        }
+        Ok(result)
+    }
+
+    /// 应用收集到的扩展头部
+    fn apply_meta(&mut self, kind: u8) -> Ext4Result<()> {
+        let meta = core::mem::take(&mut self.meta);
+        match kind {
+            b'L' => self.overrides.path = Some(field_str(&meta).to_string()),
+            b'K' => self.overrides.link = Some(field_str(&meta).to_string()),
+            b'x' => self.apply_pax(&meta)?,
+            // 全局PAX头部很少使用，其中的记录被忽略
+            _ => {}
+        }
+        self.meta = meta;
+        Ok(())
+    }
+
+    /// 解析PAX记录（"长度 键=值\n"）
+    fn apply_pax(&mut self, mut data: &[u8]) -> Ext4Result<()> {
+        let bad = || Ext4Error::new(EINVAL as _, "bad pax record");
+        while !data.is_empty() {
+            let space = data.iter().position(|&b| b == b' ').ok_or_else(bad)?;
+            let len: usize = core::str::from_utf8(&data[..space])
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(bad)?;
+            if len <= space + 1 || len > data.len() || data[len - 1] != b'\n' {
+                return Err(bad());
+            }
+            let record = &data[space + 1..len - 1];
+            data = &data[len..];
+
+            let eq = record.iter().position(|&b| b == b'=').ok_or_else(bad)?;
+            let (key, value) = (&record[..eq], &record[eq + 1..]);
+            let text = || core::str::from_utf8(value).map_err(|_| bad());
+            match key {
+                b"path" => self.overrides.path = Some(text()?.to_string()),
+                b"linkpath" => self.overrides.link = Some(text()?.to_string()),
+                b"size" => self.overrides.size = Some(text()?.parse().map_err(|_| bad())?),
+                b"uid" => self.overrides.uid = Some(text()?.parse().map_err(|_| bad())?),
+                b"gid" => self.overrides.gid = Some(text()?.parse().map_err(|_| bad())?),
+                b"atime" => self.overrides.atime = Some(parse_pax_time(text()?).ok_or_else(bad)?),
+                b"mtime" => self.overrides.mtime = Some(parse_pax_time(text()?).ok_or_else(bad)?),
+                b"ctime" => self.overrides.ctime = Some(parse_pax_time(text()?).ok_or_else(bad)?),
+                _ if key.starts_with(b"SCHILY.xattr.") => {
+                    // 值是原始字节，可能不是UTF-8
+                    let name = core::str::from_utf8(&key[b"SCHILY.xattr.".len()..]).map_err(|_| bad())?;
+                    self.overrides.xattrs.push((name.to_string(), value.to_vec()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 设置条目的权限位、属主和时间戳
+fn set_attr<Hal: SystemHal, Dev: BlockDevice>(
+    fs: &mut Ext4Filesystem<Hal, Dev>,
+    ino: u32,
+    attr: &TarAttr,
+) -> Ext4Result<()> {
+    fs.with_inode_ref(ino, |inode| {
+        inode.set_mode((inode.mode() & !0o7777) | attr.mode);
+        inode.set_owner(attr.uid, attr.gid);
+        inode.set_atime(&attr.atime);
+        inode.set_mtime(&attr.mtime);
+        inode.set_ctime(&attr.ctime);
+        Ok(())
+    })
+}
+
+/// 头部中以NUL结尾的字符串字段
+fn field_str(field: &[u8]) -> &str {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).unwrap_or_default()
+}
+
+/// 解析头部中的数值字段（八进制，或最高位置1的GNU base-256编码）
+fn parse_number(field: &[u8]) -> Ext4Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let mut value = (field[0] & 0x7f) as u64;
+        for &b in &field[1..] {
+            value = value
+                .checked_mul(256)
+                .ok_or_else(|| Ext4Error::new(EINVAL as _, "tar number overflow"))?
+                | b as u64;
+        }
+        return Ok(value);
+    }
+
+    let mut value = 0u64;
+    for &b in field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| b != 0 && b != b' ')
+    {
+        if !(b'0'..=b'7').contains(&b) {
+            return Err(Ext4Error::new(EINVAL as _, "bad tar number field"));
+        }
+        value = value
+            .checked_mul(8)
+            .ok_or_else(|| Ext4Error::new(EINVAL as _, "tar number overflow"))?
+            + (b - b'0') as u64;
+    }
+    Ok(value)
+}
+
+/// 解析PAX时间戳（"秒[.小数]"，不支持1970年之前的时间）
+fn parse_pax_time(s: &str) -> Option<Duration> {
+    let (secs, frac) = s.split_once('.').unwrap_or((s, ""));
+    let secs = secs.parse().ok()?;
+    let mut nanos = 0u32;
+    for (i, c) in frac.chars().take(9).enumerate() {
+        nanos += c.to_digit(10)? * 10u32.pow(8 - i as u32);
+    }
+    Some(Duration::new(secs, nanos))
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 从reader流式导入tar归档到镜像目录dest
+    #[cfg(feature = "std")]
+    pub fn import_tar(
+        &mut self,
+        mut reader: impl std::io::Read,
+        dest: &str,
+    ) -> Ext4Result<TarStats> {
+        let mut importer = TarImporter::new(dest);
+        let mut buf = alloc::vec![0; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf).map_err(|err| {
+                Ext4Error::new(
+                    err.raw_os_error().unwrap_or(crate::ffi::EIO as _),
+                    "read tar archive",
+                )
+            })?;
+            if n == 0 {
+                break;
+            }
+            importer.feed(self, &buf[..n])?;
+        }
+        importer.finish(self)
+    }
+}
@@ -0,0 +1,84 @@
+//! 事务模块：把多个高层操作（创建、重命名、写入等）组合为一个原子单元，
+//! 提交时整体写入日志，中止或提交前断电时全部修改都不生效。
+
+use core::ops::{Deref, DerefMut};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, SystemHal, error::Context, ffi::*,
+};
+
+/// 进行中的事务
+///
+/// 通过 [`Ext4Filesystem::begin_transaction`] 创建，可以像文件系统本身一样调用各种操作。
+/// 事务期间修改的元数据留在块缓存中，[`commit`](Self::commit) 时先写入日志再写回原位置；
+/// 未提交就被销毁时自动中止。文件数据不经过日志，中止时已写入的数据不会恢复。
+pub struct Transaction<'a, Hal: SystemHal, Dev: BlockDevice> {
+    fs: &'a mut Ext4Filesystem<Hal, Dev>, // 所属文件系统
+    inner: Ext4Transaction,               // 底层事务状态
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 开始事务
+    ///
+    /// 文件系统需要有内部日志（否则返回ENOTSUP），且不能是只读挂载。
+    pub fn begin_transaction(&mut self) -> Ext4Result<Transaction<'_, Hal, Dev>> {
+        let mut inner = Ext4Transaction::new();
+        let r = unsafe { ext4_trans_start(self.inner.as_mut(), &mut inner) };
+        if r != 0 {
+            return Err(Ext4Error::new(r, None)).context("ext4_trans_start");
+        }
+        Ok(Transaction { fs: self, inner })
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Transaction<'_, Hal, Dev> {
+    /// 提交事务：返回后全部修改都已持久化
+    ///
+    /// 写入日志失败时事务被中止并返回错误。
+    pub fn commit(mut self) -> Ext4Result<()> {
+        let r = unsafe { ext4_trans_stop(self.fs.inner.as_mut(), &mut self.inner) };
+        if r != 0 {
+            return Err(Ext4Error::new(r, None)).context("ext4_trans_stop");
+        }
+        Ok(())
+    }
+
+    /// 中止事务：丢弃事务期间的全部元数据修改
+    pub fn abort(mut self) -> Ext4Result<()> {
+        self.abort_inner()
+    }
+
+    fn abort_inner(&mut self) -> Ext4Result<()> {
+        if !self.inner.is_active() {
+            return Ok(());
+        }
+        let r = unsafe { ext4_trans_abort(self.fs.inner.as_mut(), &mut self.inner) };
+        if r != 0 {
+            return Err(Ext4Error::new(r, None)).context("ext4_trans_abort");
+        }
+        Ok(())
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Deref for Transaction<'_, Hal, Dev> {
+    type Target = Ext4Filesystem<Hal, Dev>;
+
+    fn deref(&self) -> &Self::Target {
+        self.fs
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> DerefMut for Transaction<'_, Hal, Dev> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.fs
+    }
+}
+
+/// 未提交的事务被销毁时自动中止
+impl<Hal: SystemHal, Dev: BlockDevice> Drop for Transaction<'_, Hal, Dev> {
+    fn drop(&mut self) {
+        if let Err(err) = self.abort_inner() {
+            log::error!("transaction abort failed: {}", err);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! 文件系统参数调整模块（类似tune2fs）：卷标、UUID、保留块比例、默认挂载选项、
+//! 出错处理方式、定期检查策略，以及metadata_csum的启用/关闭。修改立即写回superblock。
+
+use core::time::Duration;
+
+use crate::{
+    BlockDevice, ErrorBehavior, Ext4Error, Ext4Filesystem, Ext4Result, SystemHal, error::Context,
+    ffi::*,
+};
+
+/// 挂载时建议运行fsck的原因
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsckAdvice {
+    pub not_clean: bool,       // 上次未正常卸载，或记录了错误
+    pub max_mount_count: bool, // 挂载次数达到上限
+    pub check_interval: bool,  // 距上次检查超过检查间隔（需要系统时间）
+}
+
+impl FsckAdvice {
+    /// 是否建议运行fsck
+    pub fn recommended(&self) -> bool {
+        self.not_clean || self.max_mount_count || self.check_interval
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 获取卷标（不含末尾的0）
+    pub fn volume_name(&self) -> &[u8] {
+        let name = &self.inner.sb.volume_name;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        &name[..len]
+    }
+
+    /// 设置卷标（最多16字节，否则返回EINVAL）
+    pub fn set_volume_name(&mut self, name: &str) -> Ext4Result<()> {
+        unsafe { ext4_tune_set_label(self.inner.as_mut(), name.as_bytes()) }
+            .context("ext4_tune_set_label")
+    }
+
+    /// 获取文件系统UUID
+    pub fn uuid(&self) -> [u8; 16] {
+        self.inner.sb.uuid
+    }
+
+    /// 生命周期内写入设备的KiB数（superblock中的s_kbytes_written加上本次挂载尚未计入的写入量）
+    ///
+    /// 写入量在flush等同步操作和卸载时计入superblock；可重现模式下不累计。
+    pub fn kbytes_written(&self) -> u64 {
+        u64::from_le(self.inner.sb.kbytes_written) + self.inner.bytes_written / 1024
+    }
+
+    /// 设置文件系统UUID
+    ///
+    /// 启用metadata_csum时自动启用csum_seed特性，已有的校验和保持有效。
+    pub fn set_uuid(&mut self, uuid: [u8; 16]) -> Ext4Result<()> {
+        unsafe { ext4_tune_set_uuid(self.inner.as_mut(), &uuid) }.context("ext4_tune_set_uuid")
+    }
+
+    /// 按总块数的百分比设置保留块数（0~50，否则返回EINVAL）
+    pub fn set_reserved_percent(&mut self, percent: u32) -> Ext4Result<()> {
+        unsafe { ext4_tune_set_reserved_percent(self.inner.as_mut(), percent) }
+            .context("ext4_tune_set_reserved_percent")
+    }
+
+    /// 设置默认挂载选项（EXT4_DEFM_*的组合）
+    pub fn set_default_mount_opts(&mut self, opts: u32) -> Ext4Result<()> {
+        unsafe { ext4_tune_set_default_mount_opts(self.inner.as_mut(), opts) }
+            .context("ext4_tune_set_default_mount_opts")
+    }
+
+    /// 设置出错时的处理方式（tune2fs -e）
+    pub fn set_error_behavior(&mut self, behavior: ErrorBehavior) -> Ext4Result<()> {
+        unsafe { ext4_tune_set_errors(self.inner.as_mut(), behavior.to_raw()) }
+            .context("ext4_tune_set_errors")
+    }
+
+    /// 挂载时建议运行fsck的原因（与Linux挂载时的警告相同）
+    pub fn fsck_advice(&self) -> FsckAdvice {
+        let reasons = self.inner.fsck_reasons;
+        FsckAdvice {
+            not_clean: reasons & EXT4_FSCK_NOT_CLEAN != 0,
+            max_mount_count: reasons & EXT4_FSCK_MAX_MNT_COUNT != 0,
+            check_interval: reasons & EXT4_FSCK_CHECKINTERVAL != 0,
+        }
+    }
+
+    /// 是否建议运行fsck
+    pub fn fsck_recommended(&self) -> bool {
+        self.fsck_advice().recommended()
+    }
+
+    /// 挂载次数（可写挂载时包括本次挂载）
+    pub fn mount_count(&self) -> u16 {
+        u16::from_le(self.inner.sb.mnt_count)
+    }
+
+    /// 设置挂载次数上限和检查间隔（tune2fs -c和-i）
+    ///
+    /// max_mount_count不大于0时不按挂载次数建议检查；check_interval为0时不按时间建议检查，
+    /// 超过u32秒时返回EINVAL。
+    pub fn set_check_policy(&mut self, max_mount_count: i16, check_interval: Duration) -> Ext4Result<()> {
+        let Ok(interval) = u32::try_from(check_interval.as_secs()) else {
+            return Err(Ext4Error::new(EINVAL as _, "check interval too long"));
+        };
+        unsafe { ext4_tune_set_check_policy(self.inner.as_mut(), max_mount_count, interval) }
+            .context("ext4_tune_set_check_policy")
+    }
+
+    /// 检查通过后调用：挂载次数清零、清除错误标志和fsck建议，有系统时间时更新最后检查时间
+    pub fn mark_checked(&mut self) -> Ext4Result<()> {
+        let now = self.now().map(|now| now.as_secs());
+        unsafe { ext4_tune_mark_checked(self.inner.as_mut(), now) }.context("ext4_tune_mark_checked")
+    }
+
+    /// 启用metadata_csum并为全部元数据计算校验和
+    ///
+    /// 要求文件系统处于干净状态（否则返回EBUSY）；有目录块放不下校验和尾部时返回ENOSPC，
+    /// 此时文件系统保持不变。htree目录会转换为线性目录。
+    pub fn enable_metadata_csum(&mut self) -> Ext4Result<()> {
+        unsafe { ext4_tune_enable_metadata_csum(self.inner.as_mut()) }
+            .context("ext4_tune_enable_metadata_csum")
+    }
+
+    /// 关闭metadata_csum
+    pub fn disable_metadata_csum(&mut self) -> Ext4Result<()> {
+        unsafe { ext4_tune_disable_metadata_csum(self.inner.as_mut()) }
+            .context("ext4_tune_disable_metadata_csum")
+    }
+}
@@ -0,0 +1,245 @@
+//! 已删除文件恢复模块（类似debugfs的lsdel和dump_unused）：扫描inode表中已释放、
+//! 删除时间非0但块映射仍然完整的inode，并尽量取回其内容。
+//!
+//! 本实现和Linux内核删除文件时都会清空块映射，这类inode主要来自删除过程中断电、
+//! 旧版本的ext2驱动以及只释放inode和位图的工具（如debugfs kill_file）。
+//! 恢复只读取数据块，不修改文件系统；块已被其他文件重新分配时无法取回，以0填充。
+
+use core::{slice, time::Duration};
+
+use alloc::{
+    collections::{BTreeMap, btree_map::Entry},
+    vec::Vec,
+};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, FileAttr, InodeRef, InodeType, SystemHal,
+    error::{Context, ErrorContext},
+    ffi::{bitmap::{ext4_bmap_is_bit_clr, ext4_bmap_is_bit_set}, *},
+    util::get_block_size,
+};
+
+/// 已删除但块映射仍然完整的inode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedInode {
+    pub ino: u32,              // inode编号
+    pub inode_type: InodeType, // 文件类型
+    pub size: u64,             // 删除时的文件大小
+    pub dtime: u32,            // 删除时间（秒）
+    pub mtime: Duration,       // 最后修改时间
+    pub blocks: u64,           // 块映射中的数据块数
+    pub free_blocks: u64,      // 其中仍未被重新分配的块数（可以取回）
+}
+
+/// 恢复结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveredFile {
+    pub data: Vec<u8>,         // 文件内容（不超过删除时的文件大小，末尾的空洞不计入）
+    pub blocks_recovered: u64, // 取回的数据块数
+    pub blocks_lost: u64,      // 已被重新分配或超出范围、以0填充的数据块数
+}
+
+/// 按块组缓存的块位图（块组未初始化时为None，视为全部空闲）
+#[derive(Default)]
+struct BlockBitmaps(BTreeMap<u32, Option<Vec<u8>>>);
+
+impl BlockBitmaps {
+    /// 块是否空闲（超出文件系统范围的块视为不可用）
+    fn is_free(&mut self, fs: *mut ext4_fs, block: u64) -> Ext4Result<bool> {
+        let sb = unsafe { &(*fs).sb };
+        if block < u32::from_le(sb.first_data_block) as u64 || block >= ext4_sb_get_blocks_cnt(sb) {
+            return Ok(false);
+        }
+        let bgid = ext4_balloc_get_bgid_of_block(sb, block);
+        let bitmap = match self.0.entry(bgid) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(unsafe { read_bitmap(fs, bgid, false)? }),
+        };
+        Ok(match bitmap {
+            Some(bitmap) => ext4_bmap_is_bit_clr(bitmap, ext4_fs_addr_to_idx_bg(sb, block)),
+            None => true,
+        })
+    }
+}
+
+/// 读取块组的块位图或inode位图（对应的UNINIT标志置位时返回None）
+unsafe fn read_bitmap(fs: *mut ext4_fs, bgid: u32, inode: bool) -> Ext4Result<Option<Vec<u8>>> {
+    unsafe {
+        let sb = &(*fs).sb;
+        let mut bg_ref = ext4_block_group_ref::new();
+        ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref).context("ext4_fs_get_block_group_ref")?;
+        let bg = &*bg_ref.block_group;
+        let (flag, block) = if inode {
+            (EXT4_BLOCK_GROUP_INODE_UNINIT, ext4_bg_get_inode_bitmap(bg, sb))
+        } else {
+            (EXT4_BLOCK_GROUP_BLOCK_UNINIT, ext4_bg_get_block_bitmap(bg, sb))
+        };
+        let uninit = ext4_bg_has_flag(bg, flag);
+        ext4_fs_put_block_group_ref(&mut bg_ref).context("ext4_fs_put_block_group_ref")?;
+        if uninit {
+            return Ok(None);
+        }
+        let mut b = ext4_block::new();
+        let r = ext4_block_get((*fs).bdev, &mut b, block);
+        if r != EOK {
+            return Err(Ext4Error::new(r, "ext4_block_get"));
+        }
+        let bitmap = slice::from_raw_parts(b.data, get_block_size(sb) as usize).to_vec();
+        ext4_block_set((*fs).bdev, &mut b);
+        Ok(Some(bitmap))
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 扫描所有块组的inode表，列出已删除但块映射仍然完整的inode
+    ///
+    /// 只检查inode位图中空闲、删除时间非0、文件大小和块数非0且至少映射了一个数据块的inode，
+    /// 块组尾部从未使用过的inode（itable_unused）和未初始化的块组不扫描。
+    pub fn scan_deleted(&mut self) -> Ext4Result<Vec<DeletedInode>> {
+        let fs: *mut ext4_fs = self.inner.as_mut();
+        let sb = unsafe { &(*fs).sb };
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+        let mut bitmaps = BlockBitmaps::default();
+        let mut found = Vec::new();
+
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let Some(inode_bitmap) = (unsafe { read_bitmap(fs, bgid, true)? }) else {
+                continue;
+            };
+            let used = unsafe {
+                let mut bg_ref = ext4_block_group_ref::new();
+                ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref)
+                    .context("ext4_fs_get_block_group_ref")?;
+                let unused = ext4_bg_get_itable_unused(&*bg_ref.block_group, sb);
+                ext4_fs_put_block_group_ref(&mut bg_ref).context("ext4_fs_put_block_group_ref")?;
+                ext4_inodes_in_group_cnt(sb, bgid).saturating_sub(unused)
+            };
+            for idx in 0..used {
+                let ino = bgid * inodes_per_group + idx + 1;
+                if ext4_bmap_is_bit_set(&inode_bitmap, idx) || ext4_sb_is_reserved_ino(sb, ino) {
+                    continue;
+                }
+                // 校验和错误等无法读取的inode跳过
+                let Ok(mut inode) = self.inode_ref(ino) else {
+                    continue;
+                };
+                let dtime = unsafe { ext4_inode_get_del_time(inode.inner.inode) };
+                if dtime == 0 || inode.size() == 0 || inode.inode_type() == InodeType::Unknown {
+                    continue;
+                }
+                let mut deleted = DeletedInode {
+                    ino,
+                    inode_type: inode.inode_type(),
+                    size: inode.size(),
+                    dtime,
+                    mtime: Duration::ZERO,
+                    blocks: 0,
+                    free_blocks: 0,
+                };
+                let ret = for_each_mapped(&mut inode, |_, pblk| {
+                    deleted.blocks += 1;
+                    if bitmaps.is_free(fs, pblk)? {
+                        deleted.free_blocks += 1;
+                    }
+                    Ok(())
+                });
+                drop_cached_extents(&mut inode);
+                // 块映射已损坏的inode不在恢复范围内
+                if ret.is_err() || deleted.blocks == 0 {
+                    continue;
+                }
+                let mut attr = FileAttr::default();
+                inode.get_attr(&mut attr);
+                deleted.mtime = attr.mtime;
+                found.push(deleted);
+            }
+        }
+        Ok(found)
+    }
+
+    /// 按已删除inode的块映射取回文件内容（尽力而为）
+    ///
+    /// inode仍在使用或删除时间为0时返回EINVAL。已被重新分配的块不读取，对应内容以0填充，
+    /// 空洞同样为0。文件大小可能已损坏，结果只延伸到最后一个映射的数据块为止。
+    pub fn recover_deleted(&mut self, ino: u32) -> Ext4Result<RecoveredFile> {
+        let fs: *mut ext4_fs = self.inner.as_mut();
+        let sb = unsafe { &(*fs).sb };
+        if ino == 0 || ino > u32::from_le(sb.inodes_count) {
+            return Err(Ext4Error::new(EINVAL as _, "inode number out of range"));
+        }
+        let bgid = ext4_ialloc_get_bgid_of_inode(sb, ino);
+        if let Some(bitmap) = unsafe { read_bitmap(fs, bgid, true)? } {
+            if ext4_bmap_is_bit_set(&bitmap, ext4_ialloc_inode_to_bgidx(sb, ino)) {
+                return Err(Ext4Error::new(EINVAL as _, "inode is in use")
+                    .with_context(ErrorContext::new("recover_deleted").ino(ino)));
+            }
+        }
+        let mut inode = self.inode_ref(ino)?;
+        if unsafe { ext4_inode_get_del_time(inode.inner.inode) } == 0 {
+            return Err(Ext4Error::new(EINVAL as _, "inode was not deleted")
+                .with_context(ErrorContext::new("recover_deleted").ino(ino)));
+        }
+
+        let block_size = get_block_size(sb) as u64;
+        let size = inode.size();
+        let mut recovered = RecoveredFile::default();
+        let mut bitmaps = BlockBitmaps::default();
+        let ret = for_each_mapped(&mut inode, |iblock, pblk| {
+            let start = (iblock * block_size) as usize;
+            let len = block_size.min(size - iblock * block_size) as usize;
+            recovered.data.resize(start + len, 0);
+            if !bitmaps.is_free(fs, pblk)? {
+                recovered.blocks_lost += 1;
+                return Ok(());
+            }
+            let buf = &mut recovered.data[start..start + len];
+            unsafe {
+                ext4_block_readbytes((*fs).bdev, pblk * block_size, buf.as_mut_ptr() as _, len as _)
+            }
+            .context("ext4_block_readbytes")?;
+            recovered.blocks_recovered += 1;
+            Ok(())
+        });
+        drop_cached_extents(&mut inode);
+        ret?;
+        Ok(recovered)
+    }
+}
+
+/// 对文件大小范围内每个已映射的数据块调用f(逻辑块号, 物理块号)，只读取块映射
+///
+/// 找到的块数达到inode记录的块数（含索引块，不小于数据块数）后停止，
+/// 损坏的文件大小不会导致遍历整个32位逻辑块空间。
+fn for_each_mapped<Hal: SystemHal>(
+    inode: &mut InodeRef<Hal>,
+    mut f: impl FnMut(u64, u64) -> Ext4Result<()>,
+) -> Ext4Result<()> {
+    let sb = inode.superblock();
+    let block_size = get_block_size(sb) as u64;
+    let blocks = inode.size().div_ceil(block_size);
+    let mut remaining = unsafe { ext4_inode_get_blocks_count(sb, inode.inner.inode) }
+        / (block_size / EXT4_INODE_BLOCK_SIZE as u64);
+    // 逻辑块号为32位
+    for iblock in 0..blocks.min(u32::MAX as u64) {
+        if remaining == 0 {
+            break;
+        }
+        let mut pblk = 0u64;
+        unsafe { ext4_fs_get_inode_dblk_idx(inode.inner.as_mut(), iblock as u32, &mut pblk, false) }
+            .with_context(|| {
+                ErrorContext::new("ext4_fs_get_inode_dblk_idx")
+                    .ino(inode.ino())
+                    .block(iblock)
+            })?;
+        if pblk != 0 {
+            remaining -= 1;
+            f(iblock, pblk)?;
+        }
+    }
+    Ok(())
+}
+
+/// 丢弃读取块映射时为已删除inode缓存的extent
+fn drop_cached_extents<Hal: SystemHal>(inode: &mut InodeRef<Hal>) {
+    unsafe { ext4_es_drop_inode(&mut (*inode.inner.fs).es_cache, inode.ino()) };
+}
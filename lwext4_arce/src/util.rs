@@ -11,4 +11,12 @@ pub fn get_block_size(sb: &ext4_sblock) -> u32 {
 /// 获取文件系统的版本号（主版本 + 次版本）
 pub fn revision_tuple(sb: &ext4_sblock) -> (u32, u16) {
     (u32::from_le(sb.rev_level), u16::from_le(sb.minor_rev_level))
-}
\ No newline at end of file
+}
+/// 获取inode结构的大小（版本0固定为128字节）
+pub fn get_inode_size(sb: &ext4_sblock) -> u32 {
+    if u32::from_le(sb.rev_level) == 0 {
+        128
+    } else {
+        u16::from_le(sb.inode_size) as u32
+    }
+}
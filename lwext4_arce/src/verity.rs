@@ -0,0 +1,138 @@
+//! fs-verity支持模块：读取verity文件的描述符和Merkle树，供校验只读系统镜像的加载器使用。
+//!
+//! 本模块不计算哈希，只提供校验所需的数据；verity文件的内容拒绝修改。
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, SystemHal,
+    error::{Context, ErrorContext},
+    ffi::*,
+};
+
+/// 哈希算法编号（FS_VERITY_HASH_ALG_*）
+pub const FS_VERITY_HASH_ALG_SHA256: u8 = 1;
+pub const FS_VERITY_HASH_ALG_SHA512: u8 = 2;
+
+/// 描述符固定部分的长度（签名紧随其后）
+const FS_VERITY_DESCRIPTOR_SIZE: usize = 256;
+/// root_hash和salt字段的长度
+const FS_VERITY_MAX_DIGEST_SIZE: usize = 64;
+const FS_VERITY_MAX_SALT_SIZE: usize = 32;
+
+/// fs-verity描述符（struct fsverity_descriptor）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerityDescriptor {
+    pub version: u8,         // 描述符版本（目前为1）
+    pub hash_algorithm: u8,  // 哈希算法（FS_VERITY_HASH_ALG_*）
+    pub log_blocksize: u8,   // Merkle树块大小的log2
+    pub data_size: u64,      // 文件数据长度
+    pub root_hash: [u8; 64], // Merkle树根哈希（按摘要长度使用前若干字节）
+    pub salt: Vec<u8>,       // 盐值
+    pub signature: Vec<u8>,  // 内置签名（PKCS#7，可能为空）
+}
+
+impl VerityDescriptor {
+    /// 解析描述符（版本或长度字段不合理时返回None）
+    pub fn parse(raw: &[u8]) -> Option<Self> {
+        if raw.len() < FS_VERITY_DESCRIPTOR_SIZE || raw[0] != 1 {
+            return None;
+        }
+        let salt_size = raw[3] as usize;
+        let sig_size = u32::from_le_bytes(raw[4..8].try_into().ok()?) as usize;
+        if salt_size > FS_VERITY_MAX_SALT_SIZE || raw.len() < FS_VERITY_DESCRIPTOR_SIZE + sig_size {
+            return None;
+        }
+        Some(VerityDescriptor {
+            version: raw[0],
+            hash_algorithm: raw[1],
+            log_blocksize: raw[2],
+            data_size: u64::from_le_bytes(raw[8..16].try_into().ok()?),
+            root_hash: raw[16..80].try_into().ok()?,
+            salt: raw[80..80 + salt_size].to_vec(),
+            signature: raw[FS_VERITY_DESCRIPTOR_SIZE..FS_VERITY_DESCRIPTOR_SIZE + sig_size].to_vec(),
+        })
+    }
+
+    /// 摘要长度（未知的哈希算法返回None）
+    pub fn digest_size(&self) -> Option<usize> {
+        match self.hash_algorithm {
+            FS_VERITY_HASH_ALG_SHA256 => Some(32),
+            FS_VERITY_HASH_ALG_SHA512 => Some(FS_VERITY_MAX_DIGEST_SIZE),
+            _ => None,
+        }
+    }
+
+    /// Merkle树块大小
+    pub fn block_size(&self) -> u64 {
+        1 << self.log_blocksize
+    }
+
+    /// 计算文件摘要时输入哈希的描述符字节（sig_size置0且不含签名）
+    pub fn digest_input(&self) -> [u8; FS_VERITY_DESCRIPTOR_SIZE] {
+        let mut raw = [0u8; FS_VERITY_DESCRIPTOR_SIZE];
+        raw[0] = self.version;
+        raw[1] = self.hash_algorithm;
+        raw[2] = self.log_blocksize;
+        raw[3] = self.salt.len() as u8;
+        raw[8..16].copy_from_slice(&self.data_size.to_le_bytes());
+        raw[16..80].copy_from_slice(&self.root_hash);
+        raw[80..80 + self.salt.len()].copy_from_slice(&self.salt);
+        raw
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 文件系统是否启用了verity特性
+    pub fn verity_enabled(&self) -> bool {
+        u32::from_le(self.inner.sb.feature_ro_compat) & EXT4_FRO_COM_VERITY != 0
+    }
+
+    /// 定位inode的verity描述符，返回(偏移, 长度)；不是verity文件时返回None
+    fn verity_location(&mut self, ino: u32) -> Ext4Result<Option<(u64, u32)>> {
+        let mut inode = self.inode_ref(ino)?;
+        let (mut pos, mut size) = (0, 0);
+        let r = unsafe { ext4_verity_descriptor_location(inode.inner.as_mut(), &mut pos, &mut size) };
+        if r == ENODATA {
+            return Ok(None);
+        }
+        r.with_context(|| ErrorContext::new("ext4_verity_descriptor_location").ino(ino))?;
+        Ok(Some((pos, size)))
+    }
+
+    /// 读取文件的fs-verity描述符（不是verity文件时返回None）
+    ///
+    /// 描述符无法解析时返回EIO。
+    pub fn verity_descriptor(&mut self, path: &str) -> Ext4Result<Option<VerityDescriptor>> {
+        let ino = self.resolve_path(path)?;
+        let Some((pos, size)) = self.verity_location(ino)? else {
+            return Ok(None);
+        };
+        let mut raw = vec![0u8; size as usize];
+        let mut inode = self.inode_ref(ino)?;
+        unsafe { ext4_verity_read(inode.inner.as_mut(), pos, &mut raw) }
+            .with_context(|| ErrorContext::new("ext4_verity_read").ino(ino))?;
+        VerityDescriptor::parse(&raw)
+            .map(Some)
+            .ok_or_else(|| Ext4Error::new(EIO as _, "bad verity descriptor"))
+    }
+
+    /// 读取verity文件的Merkle树（offset从树的起始位置算起），返回读取的字节数
+    ///
+    /// 树的层次按自顶向下存放，与Linux的FS_IOC_READ_VERITY_METADATA相同。
+    pub fn read_merkle_tree(&mut self, ino: u32, buf: &mut [u8], offset: u64) -> Ext4Result<usize> {
+        let Some((desc_pos, _)) = self.verity_location(ino)? else {
+            return Err(Ext4Error::new(ENODATA as _, "not a verity file"));
+        };
+        let mut inode = self.inode_ref(ino)?;
+        let start = ext4_verity_metadata_pos(inode.size());
+        let tree_size = desc_pos - start;
+        if offset >= tree_size {
+            return Ok(0);
+        }
+        let len = buf.len().min((tree_size - offset) as usize);
+        unsafe { ext4_verity_read(inode.inner.as_mut(), start + offset, &mut buf[..len]) }
+            .with_context(|| ErrorContext::new("ext4_verity_read").ino(ino))?;
+        Ok(len)
+    }
+}
@@ -0,0 +1,496 @@
+//! 目录树遍历模块，提供深度优先的WalkDir迭代器、简单的glob路径匹配和du式的空间统计。
+//!
+//! 遍历时每层目录只缓存一页条目，内存占用与目录深度成正比，与目录大小无关。
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeSet, VecDeque},
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4Filesystem, Ext4Result, FileAttr, InodeType, OwnedDirEntry,
+    SystemHal,
+    error::ErrorContext,
+    ffi::{EINVAL, ENOTDIR},
+};
+
+/// 每次从目录读取的条目数
+const WALK_PAGE_SIZE: usize = 64;
+
+/// 跟随符号链接时读取的目标路径最大长度
+const SYMLINK_MAX: u64 = 4096;
+
+/// 遍历时对符号链接的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// 不返回符号链接
+    Skip,
+    /// 返回符号链接本身，不进入其指向的目录
+    #[default]
+    Report,
+    /// 跟随符号链接，指向目录时进入该目录（会形成环的链接不进入）
+    Follow,
+}
+
+/// 遍历得到的条目
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: String,          // 完整路径（以'/'开头）
+    pub ino: u32,              // 条目的inode编号（跟随符号链接时为目标的inode编号）
+    pub inode_type: InodeType, // 条目类型（跟随符号链接时为目标的类型）
+    pub depth: usize,          // 相对起点的深度（起点的直接子项为1）
+}
+
+/// 目录树的空间占用统计（类似du）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub blocks: u64,        // 实际分配的512B块数（含extent树等元数据块）
+    pub apparent_size: u64, // 文件大小之和
+    pub files: u64,         // 非目录的inode数
+    pub dirs: u64,          // 目录数（包括起点）
+}
+
+impl DiskUsage {
+    /// 实际占用的字节数
+    pub fn bytes(&self) -> u64 {
+        self.blocks * 512
+    }
+
+    /// 累加一个inode的属性
+    fn add(&mut self, attr: &FileAttr) {
+        self.blocks += attr.blocks;
+        self.apparent_size += attr.size;
+        if attr.node_type == InodeType::Directory {
+            self.dirs += 1;
+        } else {
+            self.files += 1;
+        }
+    }
+}
+
+/// 遍历栈中的一层目录
+struct WalkFrame {
+    ino: u32,                         // 目录inode编号
+    path: String,                     // 目录路径
+    depth: usize,                     // 目录深度（起点为0）
+    entries: VecDeque<OwnedDirEntry>, // 已读出、尚未处理的条目
+    cookie: Option<u64>,              // 下一页的偏移量（None表示已读完）
+}
+
+impl WalkFrame {
+    fn new(ino: u32, path: String, depth: usize) -> Self {
+        Self {
+            ino,
+            path,
+            depth,
+            entries: VecDeque::new(),
+            cookie: Some(0),
+        }
+    }
+}
+
+/// 条目过滤函数，返回false时跳过该条目且不进入该目录
+type WalkFilter<'a> = Box<dyn FnMut(&WalkEntry) -> bool + 'a>;
+
+/// 深度优先的目录树遍历迭代器（不返回起点本身以及"."和".."）
+pub struct WalkDir<'a, Hal: SystemHal, Dev: BlockDevice> {
+    fs: &'a mut Ext4Filesystem<Hal, Dev>,
+    stack: Vec<WalkFrame>,
+    max_depth: usize,
+    symlinks: SymlinkPolicy,
+    filter: Option<WalkFilter<'a>>,
+}
+
+impl<'a, Hal: SystemHal, Dev: BlockDevice> WalkDir<'a, Hal, Dev> {
+    /// 创建从path开始的遍历（path必须是目录）
+    pub fn new(fs: &'a mut Ext4Filesystem<Hal, Dev>, path: &str) -> Ext4Result<Self> {
+        let ino = fs.resolve_path(path)?;
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr)?;
+        if attr.node_type != InodeType::Directory {
+            return Err(Ext4Error::new(ENOTDIR as _, None)
+                .with_context(ErrorContext::new("walk_dir").ino(ino)));
+        }
+
+        let mut root = String::new();
+        for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            root.push('/');
+            root.push_str(name);
+        }
+        if root.is_empty() {
+            root.push('/');
+        }
+
+        Ok(Self {
+            fs,
+            stack: vec![WalkFrame::new(ino, root, 0)],
+            max_depth: usize::MAX,
+            symlinks: SymlinkPolicy::default(),
+            filter: None,
+        })
+    }
+
+    /// 设置最大深度（1表示只返回起点的直接子项）
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// 设置符号链接的处理方式
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// 设置条目过滤函数，返回false的条目不返回，是目录时也不进入
+    pub fn filter_entry(mut self, filter: impl FnMut(&WalkEntry) -> bool + 'a) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// 获取条目的类型（未启用filetype特性时从inode读取）
+    fn entry_type(&mut self, entry: &OwnedDirEntry) -> Ext4Result<InodeType> {
+        if entry.inode_type != InodeType::Unknown {
+            return Ok(entry.inode_type);
+        }
+        let mut attr = FileAttr::default();
+        self.fs.get_attr(entry.ino, &mut attr)?;
+        Ok(attr.node_type)
+    }
+
+    /// 解析符号链接的目标，返回目标的inode编号和类型（目标不存在时返回None）
+    fn follow(&mut self, ino: u32, dir_path: &str) -> Ext4Result<Option<(u32, InodeType)>> {
+        let mut attr = FileAttr::default();
+        self.fs.get_attr(ino, &mut attr)?;
+        if attr.size > SYMLINK_MAX {
+            return Ok(None);
+        }
+        let buf = self.fs.read_link(ino)?;
+        let Ok(target) = core::str::from_utf8(&buf) else {
+            return Ok(None);
+        };
+
+        let target = if target.starts_with('/') {
+            target.to_string()
+        } else {
+            alloc::format!("{dir_path}/{target}")
+        };
+        let Ok(target) = self.fs.resolve_path(&target) else {
+            return Ok(None);
+        };
+        self.fs.get_attr(target, &mut attr)?;
+        Ok(Some((target, attr.node_type)))
+    }
+
+    /// 取出栈顶目录的下一个条目（需要时读取下一页），栈顶目录读完时返回None
+    fn next_entry(&mut self) -> Ext4Result<Option<OwnedDirEntry>> {
+        let frame = self.stack.last_mut().unwrap();
+        loop {
+            if let Some(entry) = frame.entries.pop_front() {
+                if entry.name != b"." && entry.name != b".." {
+                    return Ok(Some(entry));
+                }
+                continue;
+            }
+            let Some(cookie) = frame.cookie else {
+                return Ok(None);
+            };
+            let page = self
+                .fs
+                .read_dir_page(frame.ino, cookie, WALK_PAGE_SIZE, &mut |_| true)?;
+            frame.entries.extend(page.entries);
+            frame.cookie = page.cookie;
+        }
+    }
+
+    /// 处理一个条目：返回Ok(None)表示跳过
+    fn visit(&mut self, entry: OwnedDirEntry) -> Ext4Result<Option<WalkEntry>> {
+        let frame = self.stack.last().unwrap();
+        let mut path = frame.path.clone();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        path.push_str(&String::from_utf8_lossy(&entry.name));
+        let depth = frame.depth + 1;
+        let dir_path = frame.path.clone();
+
+        let mut walk_entry = WalkEntry {
+            path,
+            ino: entry.ino,
+            inode_type: self.entry_type(&entry)?,
+            depth,
+        };
+        if walk_entry.inode_type == InodeType::Symlink {
+            match self.symlinks {
+                SymlinkPolicy::Skip => return Ok(None),
+                SymlinkPolicy::Report => {}
+                SymlinkPolicy::Follow => {
+                    if let Some((ino, ty)) = self.follow(entry.ino, &dir_path)? {
+                        walk_entry.ino = ino;
+                        walk_entry.inode_type = ty;
+                    }
+                }
+            }
+        }
+
+        if let Some(filter) = &mut self.filter {
+            if !filter(&walk_entry) {
+                return Ok(None);
+            }
+        }
+
+        // 跟随符号链接时，目标是正在遍历的某一层目录则不进入，避免成环
+        let looped = self.stack.iter().any(|f| f.ino == walk_entry.ino);
+        if walk_entry.inode_type == InodeType::Directory && depth < self.max_depth && !looped {
+            self.stack.push(WalkFrame::new(
+                walk_entry.ino,
+                walk_entry.path.clone(),
+                depth,
+            ));
+        }
+        Ok(Some(walk_entry))
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Iterator for WalkDir<'_, Hal, Dev> {
+    type Item = Ext4Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.stack.is_empty() {
+            let entry = match self.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => {
+                    self.stack.pop();
+                    continue;
+                }
+                Err(err) => {
+                    // 读取失败的目录不再继续
+                    self.stack.pop();
+                    return Some(Err(err));
+                }
+            };
+            match self.visit(entry) {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+/// 按glob模式查找路径的迭代器
+pub struct Glob<'a, Hal: SystemHal, Dev: BlockDevice> {
+    walk: WalkDir<'a, Hal, Dev>,
+    pattern: String,
+}
+
+impl<'a, Hal: SystemHal, Dev: BlockDevice> Glob<'a, Hal, Dev> {
+    /// 创建glob查找：从模式中不含通配符的最长前缀目录开始遍历，
+    /// 只进入可能匹配模式的目录
+    pub fn new(fs: &'a mut Ext4Filesystem<Hal, Dev>, pattern: &str) -> Ext4Result<Self> {
+        let segs: Vec<&str> = split_path(pattern).collect();
+        if segs.is_empty() {
+            return Err(Ext4Error::new(EINVAL as _, "empty glob pattern"));
+        }
+        let literal = segs
+            .iter()
+            .take_while(|s| !has_wildcard(s))
+            .count()
+            .min(segs.len() - 1);
+
+        let mut root = String::from("/");
+        root.push_str(&segs[..literal].join("/"));
+        let mut pattern = String::from("/");
+        pattern.push_str(&segs.join("/"));
+
+        let rest = &segs[literal..];
+        let max_depth = if rest.contains(&"**") {
+            usize::MAX
+        } else {
+            rest.len()
+        };
+
+        let prefix = pattern.clone();
+        let walk = WalkDir::new(fs, &root)?
+            .max_depth(max_depth)
+            .filter_entry(move |e| {
+                e.inode_type != InodeType::Directory || glob_match_prefix(&prefix, &e.path)
+            });
+        Ok(Self { walk, pattern })
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Iterator for Glob<'_, Hal, Dev> {
+    type Item = Ext4Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.walk.next()? {
+                Ok(entry) if !glob_match(&self.pattern, &entry.path) => continue,
+                item => return Some(item),
+            }
+        }
+    }
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 从path开始深度优先遍历目录树
+    pub fn walk_dir(&mut self, path: &str) -> Ext4Result<WalkDir<'_, Hal, Dev>> {
+        WalkDir::new(self, path)
+    }
+
+    /// 按glob模式（如"/etc/**/*.conf"）查找路径
+    pub fn glob(&mut self, pattern: &str) -> Ext4Result<Glob<'_, Hal, Dev>> {
+        Glob::new(self, pattern)
+    }
+
+    /// 统计path下整个目录树的空间占用（path为文件时只统计该文件）
+    ///
+    /// 按inode实际分配的块数累计，稀疏文件的空洞不计入；有多个硬链接的inode只计一次；
+    /// 不跟随符号链接。
+    pub fn disk_usage(&mut self, path: &str) -> Ext4Result<DiskUsage> {
+        let ino = self.resolve_path(path)?;
+        let mut attr = FileAttr::default();
+        self.get_attr(ino, &mut attr)?;
+        let mut usage = DiskUsage::default();
+        usage.add(&attr);
+        if attr.node_type != InodeType::Directory {
+            return Ok(usage);
+        }
+
+        let mut seen = BTreeSet::new();
+        let mut walk = WalkDir::new(self, path)?;
+        while let Some(entry) = walk.next() {
+            let entry = entry?;
+            walk.fs.get_attr(entry.ino, &mut attr)?;
+            if attr.node_type != InodeType::Directory && attr.nlink > 1 && !seen.insert(entry.ino)
+            {
+                continue;
+            }
+            usage.add(&attr);
+        }
+        Ok(usage)
+    }
+}
+
+/// 按'/'切分路径，忽略空分量和"."
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty() && *s != ".")
+}
+
+/// 路径分量是否包含通配符
+fn has_wildcard(seg: &str) -> bool {
+    seg.contains(['*', '?', '['])
+}
+
+/// 判断路径是否匹配glob模式
+///
+/// 按'/'分量逐个匹配：*匹配分量内任意字符，?匹配单个字符，[abc]、[a-z]、[!a-z]匹配
+/// 字符集合，单独的**分量匹配任意层（包括零层）目录。以'.'开头的名称不做特殊处理。
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pat: Vec<&str> = split_path(pattern).collect();
+    let path: Vec<&str> = split_path(path).collect();
+    match_segments(&pat, &path, false)
+}
+
+/// 判断路径是否可能是某个匹配模式的路径的前缀目录
+fn glob_match_prefix(pattern: &str, path: &str) -> bool {
+    let pat: Vec<&str> = split_path(pattern).collect();
+    let path: Vec<&str> = split_path(path).collect();
+    match_segments(&pat, &path, true)
+}
+
+/// 逐分量匹配，prefix为true时路径分量用完即视为匹配
+fn match_segments(pat: &[&str], path: &[&str], prefix: bool) -> bool {
+    match pat.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..], prefix)),
+        Some((seg, rest)) => match path.split_first() {
+            None => prefix,
+            Some((name, tail)) => {
+                match_segment(seg.as_bytes(), name.as_bytes()) && match_segments(rest, tail, prefix)
+            }
+        },
+    }
+}
+
+/// 匹配单个路径分量（遇到*时回溯）
+fn match_segment(pat: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star = None; // 最近一个*的位置及其当前匹配到的名称位置
+    while n < name.len() {
+        if p < pat.len() {
+            match pat[p] {
+                b'*' => {
+                    star = Some((p, n));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+                b'[' => match match_class(&pat[p..], name[n]) {
+                    Some((true, len)) => {
+                        p += len;
+                        n += 1;
+                        continue;
+                    }
+                    Some((false, _)) => {}
+                    // 没有闭合的'['按普通字符处理
+                    None if name[n] == b'[' => {
+                        p += 1;
+                        n += 1;
+                        continue;
+                    }
+                    None => {}
+                },
+                c if c == name[n] => {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        // 不匹配：让上一个*多吞一个字符
+        match star {
+            Some((sp, sn)) => {
+                p = sp + 1;
+                n = sn + 1;
+                star = Some((sp, sn + 1));
+            }
+            None => return false,
+        }
+    }
+    pat[p..].iter().all(|&c| c == b'*')
+}
+
+/// 匹配字符集合（pat以'['开头），返回(是否匹配, 集合长度)，集合未闭合时返回None
+fn match_class(pat: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pat.get(i), Some(b'!' | b'^'));
+    if negate {
+        i += 1;
+    }
+    let start = i;
+    let mut matched = false;
+    while i < pat.len() {
+        if pat[i] == b']' && i > start {
+            return Some((matched != negate, i + 1));
+        }
+        if i + 2 < pat.len() && pat[i + 1] == b'-' && pat[i + 2] != b']' {
+            matched |= (pat[i]..=pat[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= pat[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
@@ -0,0 +1,124 @@
+//! 扩展属性模块：按带命名空间前缀的完整名称（user.、trusted.等）读取、列出、设置和删除扩展属性。
+
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::{
+    BlockDevice, Ext4Error, Ext4ErrorKind, Ext4Filesystem, Ext4Result, SystemHal,
+    error::{Context, ErrorContext},
+    ffi::*,
+};
+
+/// 名称索引与名称前缀的对应关系（ACL属性的名称为空，前缀即完整名称）
+const XATTR_PREFIXES: [(u8, &str); 6] = [
+    (EXT4_XATTR_INDEX_USER, "user."),
+    (EXT4_XATTR_INDEX_TRUSTED, "trusted."),
+    (EXT4_XATTR_INDEX_SECURITY, "security."),
+    (EXT4_XATTR_INDEX_POSIX_ACL_ACCESS, "system.posix_acl_access"),
+    (EXT4_XATTR_INDEX_POSIX_ACL_DEFAULT, "system.posix_acl_default"),
+    (EXT4_XATTR_INDEX_SYSTEM, "system."),
+];
+
+/// 将完整名称拆分为名称索引和不含前缀的名称
+fn split_xattr_name(name: &str) -> Option<(u8, &str)> {
+    XATTR_PREFIXES.iter().find_map(|&(index, prefix)| {
+        let rest = name.strip_prefix(prefix)?;
+        // ACL属性只匹配完整名称
+        let is_acl = !prefix.ends_with('.');
+        (!is_acl || rest.is_empty()).then_some((index, rest))
+    })
+}
+
+impl<Hal: SystemHal, Dev: BlockDevice> Ext4Filesystem<Hal, Dev> {
+    /// 按名称索引读取扩展属性值（不存在时返回None）
+    pub(crate) fn xattr_raw(&mut self, ino: u32, index: u8, name: &str) -> Ext4Result<Option<Vec<u8>>> {
+        let mut inode = self.inode_ref(ino)?;
+        let context = || {
+            ErrorContext::new("ext4_xattr_get")
+                .ino(ino)
+                .path_segment(name)
+        };
+        let mut len = 0;
+        let r = unsafe { ext4_xattr_get(inode.inner.as_mut(), index, name.as_bytes(), &mut [], &mut len) };
+        if r == ENODATA {
+            return Ok(None);
+        }
+        r.with_context(context)?;
+
+        let mut value = vec![0u8; len];
+        if len != 0 {
+            unsafe { ext4_xattr_get(inode.inner.as_mut(), index, name.as_bytes(), &mut value, &mut len) }
+                .with_context(context)?;
+        }
+        Ok(Some(value))
+    }
+
+    /// 读取扩展属性值（名称需带命名空间前缀，不存在时返回None）
+    ///
+    /// 不认识的前缀返回Unsupported；值存放在独立inode中（ea_inode特性）时同样返回Unsupported。
+    pub fn get_xattr(&mut self, ino: u32, name: &str) -> Ext4Result<Option<Vec<u8>>> {
+        let Some((index, name)) = split_xattr_name(name) else {
+            return Err(Ext4Error::from_kind(Ext4ErrorKind::Unsupported, "unknown xattr namespace"));
+        };
+        self.xattr_raw(ino, index, name)
+    }
+
+    /// 按完整名称得到可修改的名称索引和名称
+    ///
+    /// system.前缀下的属性（如内联数据system.data）由文件系统内部维护，不允许直接修改。
+    fn writable_xattr_name<'n>(op: &'static str, ino: u32, name: &'n str) -> Ext4Result<(u8, &'n str)> {
+        match split_xattr_name(name) {
+            Some((index, name)) if index != EXT4_XATTR_INDEX_SYSTEM => Ok((index, name)),
+            _ => Err(Ext4Error::from_kind(Ext4ErrorKind::Unsupported, "unsupported xattr namespace")
+                .with_context(ErrorContext::new(op).ino(ino).path_segment(name))),
+        }
+    }
+
+    /// 设置扩展属性值（名称需带命名空间前缀，不存在时创建，存在时替换）
+    ///
+    /// 属性只写入inode内的扩展属性区：放不下时返回NoSpace，
+    /// 修改存放在扩展属性块中的已有属性返回Unsupported。
+    pub fn set_xattr(&mut self, ino: u32, name: &str, value: &[u8]) -> Ext4Result<()> {
+        let (index, short) = Self::writable_xattr_name("set_xattr", ino, name)?;
+        self.undoable(|fs| {
+            let mut inode = fs.inode_ref(ino)?;
+            unsafe { ext4_xattr_set(inode.inner.as_mut(), index, short.as_bytes(), value) }
+                .with_context(|| ErrorContext::new("ext4_xattr_set").ino(ino).path_segment(name))
+        })
+    }
+
+    /// 删除扩展属性（名称需带命名空间前缀）
+    ///
+    /// 属性不存在时返回NotFound；存放在扩展属性块中的属性不能删除，返回Unsupported。
+    pub fn remove_xattr(&mut self, ino: u32, name: &str) -> Ext4Result<()> {
+        let (index, short) = Self::writable_xattr_name("remove_xattr", ino, name)?;
+        self.undoable(|fs| {
+            let mut inode = fs.inode_ref(ino)?;
+            let context = || ErrorContext::new("ext4_xattr_remove").ino(ino).path_segment(name);
+            match unsafe { ext4_xattr_remove(inode.inner.as_mut(), index, short.as_bytes()) } {
+                ENODATA => Err(Ext4Error::from_kind(Ext4ErrorKind::NotFound, None).with_context(context())),
+                r => r.with_context(context),
+            }
+        })
+    }
+
+    /// 列出inode的扩展属性名称（带命名空间前缀）
+    ///
+    /// 内部使用的属性（如加密上下文）不在结果中。
+    pub fn list_xattr(&mut self, ino: u32) -> Ext4Result<Vec<String>> {
+        let mut inode = self.inode_ref(ino)?;
+        let mut raw = Vec::new();
+        unsafe { ext4_xattr_list(inode.inner.as_mut(), &mut raw) }
+            .with_context(|| ErrorContext::new("ext4_xattr_list").ino(ino))?;
+
+        let mut names = Vec::with_capacity(raw.len());
+        for (index, name) in raw {
+            let Some(&(_, prefix)) = XATTR_PREFIXES.iter().find(|(i, _)| *i == index) else {
+                continue;
+            };
+            let mut full = String::from(prefix);
+            full.push_str(&String::from_utf8_lossy(&name));
+            names.push(full);
+        }
+        Ok(names)
+    }
+}
@@ -0,0 +1,250 @@
+//! 与e2fsprogs的差分测试工具
+//!
+//! 用mke2fs生成镜像，通过Rust接口执行一组脚本化的操作并同时维护预期的目录树，
+//! 卸载后用`e2fsck -fn`检查一致性，再用debugfs逐项读出目录、文件内容、符号链接和链接数与预期比较。
+//! 宿主没有e2fsprogs时mke2fs返回false，调用方应跳过测试。
+
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+use lwext4_arce::{BlockDevice, Ext4Filesystem, InodeType, SystemHal};
+
+/// 脚本中的一步操作（路径均为以'/'开头的绝对路径）
+#[derive(Debug, Clone)]
+pub enum Op {
+    Mkdir(&'static str),
+    Write(&'static str, u64, Vec<u8>), // 不存在时创建，在偏移处写入
+    Truncate(&'static str, u64),
+    Symlink(&'static str, &'static str), // 链接路径、目标
+    Link(&'static str, &'static str),    // 已有文件、新路径
+    Rename(&'static str, &'static str),  // 目标已存在时被替换
+    Remove(&'static str),                // 文件、符号链接或空目录
+}
+
+/// 预期的inode内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+    Symlink(Vec<u8>),
+}
+
+/// 预期的目录树：路径到节点编号的映射（硬链接的多个路径指向同一节点）
+#[derive(Debug, Default)]
+pub struct Model {
+    paths: BTreeMap<String, usize>,
+    nodes: Vec<Node>,
+}
+
+impl Model {
+    fn add(&mut self, path: &str, node: Node) {
+        self.nodes.push(node);
+        self.paths.insert(path.to_string(), self.nodes.len() - 1);
+    }
+
+    fn node_mut(&mut self, path: &str) -> &mut Node {
+        let id = self.paths[path];
+        &mut self.nodes[id]
+    }
+
+    fn links(&self, id: usize) -> usize {
+        let names = self.paths.values().filter(|&&i| i == id).count();
+        match self.nodes[id] {
+            // 目录的链接数 = 父目录中的条目 + "." + 各子目录的".."
+            Node::Dir => {
+                let path = self.paths.iter().find(|(_, &i)| i == id).unwrap().0;
+                1 + names + self.children(path).filter(|(_, &i)| self.nodes[i] == Node::Dir).count()
+            }
+            _ => names,
+        }
+    }
+
+    /// 目录的直接子项（名称、节点编号）
+    fn children<'a>(&'a self, dir: &'a str) -> impl Iterator<Item = (&'a str, &'a usize)> + 'a {
+        self.paths.iter().filter_map(move |(path, id)| {
+            let (parent, name) = split(path);
+            (parent == dir).then_some((name, id))
+        })
+    }
+}
+
+/// 拆分为父目录和名称
+fn split(path: &str) -> (&str, &str) {
+    let pos = path.rfind('/').unwrap();
+    (if pos == 0 { "/" } else { &path[..pos] }, &path[pos + 1..])
+}
+
+/// 用mke2fs生成指定大小的ext4镜像（宿主没有mke2fs时返回false）
+pub fn mke2fs(path: &str, size_kib: u64, args: &[&str]) -> bool {
+    let Ok(output) = Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext4"])
+        .args(args)
+        .args([path, &format!("{size_kib}k")])
+        .output()
+    else {
+        return false;
+    };
+    assert!(output.status.success(), "mke2fs failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    true
+}
+
+/// 执行脚本，同时更新预期的目录树
+pub fn run_script<Hal: SystemHal, Dev: BlockDevice>(
+    fs: &mut Ext4Filesystem<Hal, Dev>,
+    model: &mut Model,
+    ops: &[Op],
+) {
+    for op in ops {
+        let parent_of = |fs: &mut Ext4Filesystem<Hal, Dev>, path: &str| {
+            let (parent, name) = split(path);
+            (fs.resolve_path(parent).unwrap(), name.to_string())
+        };
+        match op {
+            Op::Mkdir(path) => {
+                let (parent, name) = parent_of(fs, path);
+                fs.create(parent, name.as_str(), InodeType::Directory, 0o755).unwrap();
+                model.add(path, Node::Dir);
+            }
+            Op::Write(path, offset, data) => {
+                let ino = match fs.resolve_path(path) {
+                    Ok(ino) => ino,
+                    Err(_) => {
+                        let (parent, name) = parent_of(fs, path);
+                        model.add(path, Node::File(Vec::new()));
+                        fs.create(parent, name.as_str(), InodeType::RegularFile, 0o644).unwrap()
+                    }
+                };
+                assert_eq!(fs.write_at(ino, data, *offset).unwrap(), data.len(), "{op:?}");
+                let Node::File(content) = model.node_mut(path) else { panic!("{op:?}") };
+                let end = *offset as usize + data.len();
+                if content.len() < end {
+                    content.resize(end, 0);
+                }
+                content[*offset as usize..end].copy_from_slice(data);
+            }
+            Op::Truncate(path, len) => {
+                let ino = fs.resolve_path(path).unwrap();
+                fs.set_len(ino, *len).unwrap();
+                let Node::File(content) = model.node_mut(path) else { panic!("{op:?}") };
+                content.resize(*len as usize, 0);
+            }
+            Op::Symlink(path, target) => {
+                let (parent, name) = parent_of(fs, path);
+                let ino = fs.create(parent, name.as_str(), InodeType::Symlink, 0o777).unwrap();
+                fs.set_symlink(ino, target.as_bytes()).unwrap();
+                model.add(path, Node::Symlink(target.as_bytes().to_vec()));
+            }
+            Op::Link(existing, path) => {
+                let ino = fs.resolve_path(existing).unwrap();
+                let (parent, name) = parent_of(fs, path);
+                fs.link(parent, name.as_str(), ino).unwrap();
+                let id = model.paths[*existing];
+                model.paths.insert(path.to_string(), id);
+            }
+            Op::Rename(from, to) => {
+                let (src, src_name) = parent_of(fs, from);
+                let (dst, dst_name) = parent_of(fs, to);
+                fs.rename(src, src_name.as_str(), dst, dst_name.as_str()).unwrap();
+                // 目录的子项随之移动
+                let moved: Vec<_> = model
+                    .paths
+                    .keys()
+                    .filter(|p| *p == from || p.starts_with(&format!("{from}/")))
+                    .cloned()
+                    .collect();
+                model.paths.retain(|p, _| p != to && !p.starts_with(&format!("{to}/")));
+                for old in moved {
+                    let id = model.paths.remove(&old).unwrap();
+                    model.paths.insert(format!("{to}{}", &old[from.len()..]), id);
+                }
+            }
+            Op::Remove(path) => {
+                let (parent, name) = parent_of(fs, path);
+                fs.unlink(parent, name.as_str()).unwrap();
+                model.paths.remove(*path);
+            }
+        }
+    }
+}
+
+/// 执行debugfs只读命令，返回标准输出
+///
+/// 标准错误（版本信息等）被捕获，只在失败时输出。
+pub fn debugfs(image: &str, request: &str) -> Vec<u8> {
+    let output = Command::new("debugfs")
+        .args(["-R", request, image])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .expect("failed to run debugfs");
+    assert!(
+        output.status.success(),
+        "debugfs {request} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+/// 用e2fsck -fn检查镜像，再用debugfs比较实际内容与预期的目录树
+pub fn verify_with_e2fsprogs(image: &str, model: &Model) {
+    let output = Command::new("e2fsck").args(["-fn", image]).output().unwrap();
+    assert!(
+        output.status.success(),
+        "e2fsck failed:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let dirs = ["/"].into_iter().chain(
+        model
+            .paths
+            .iter()
+            .filter(|(_, &id)| model.nodes[id] == Node::Dir)
+            .map(|(p, _)| p.as_str()),
+    );
+    for dir in dirs {
+        // ls -p：/ino/mode/uid/gid/name/size/
+        // 删除的目录项是所在块的第一项时只把inode清零（Linux相同），ls -p仍会列出
+        let listing = String::from_utf8(debugfs(image, &format!("ls -p \"{dir}\""))).unwrap();
+        let mut actual: Vec<_> = listing
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('/').skip(1);
+                let ino = fields.next()?;
+                let name = fields.nth(3)?;
+                (ino != "0").then_some(name)
+            })
+            .filter(|name| !matches!(*name, "." | ".." | "lost+found"))
+            .collect();
+        let mut expected: Vec<_> = model.children(dir).map(|(name, _)| name).collect();
+        actual.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(actual, expected, "entries of {dir}");
+    }
+
+    for (path, &id) in &model.paths {
+        let stat = String::from_utf8(debugfs(image, &format!("stat \"{path}\""))).unwrap();
+        let links = stat
+            .split("Links: ")
+            .nth(1)
+            .and_then(|s| s.split_whitespace().next())
+            .unwrap_or_else(|| panic!("no stat for {path}:\n{stat}"));
+        assert_eq!(links.parse::<usize>().unwrap(), model.links(id), "links of {path}");
+        match &model.nodes[id] {
+            Node::Dir => {}
+            Node::File(content) => {
+                assert!(debugfs(image, &format!("cat \"{path}\"")) == *content, "content of {path}");
+            }
+            Node::Symlink(target) => {
+                let actual = match stat.split("Fast link dest: \"").nth(1) {
+                    Some(rest) => rest.rsplit_once('"').unwrap().0.as_bytes().to_vec(),
+                    None => debugfs(image, &format!("cat \"{path}\"")),
+                };
+                assert_eq!(
+                    String::from_utf8_lossy(&actual),
+                    String::from_utf8_lossy(target),
+                    "target of {path}"
+                );
+            }
+        }
+    }
+}
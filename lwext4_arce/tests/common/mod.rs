@@ -1,38 +1,210 @@
 use std::fs::File;
 use std::io::{Read, Write, Seek, SeekFrom};
-use lwext4_arce::{BlockDevice, Ext4Result, Ext4Error};
+use lwext4_arce::{BlockDevice, RamDisk, RecordingDevice};
 
-pub struct FileBlockDevice {
-    file: File,
+pub use lwext4_arce::FileBlockDevice;
+
+pub mod e2fs;
+
+/// 将测试镜像复制到临时文件，返回其路径（测试之间互不影响）
+pub fn copy_test_image(name: &str) -> String {
+    let dst = std::env::temp_dir().join(format!("lwext4-{}-{}.ext4", name, std::process::id()));
+    std::fs::copy(test_image(), &dst).expect("Failed to copy test image");
+    dst.to_str().unwrap().to_string()
+}
+
+/// 测试镜像的路径
+///
+/// 每个测试进程首次使用时在CARGO_TARGET_TMPDIR中生成，不修改仓库中的文件。
+pub fn test_image() -> &'static str {
+    static IMAGE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    IMAGE.get_or_init(|| {
+        let path = concat!(env!("CARGO_TARGET_TMPDIR"), "/test.ext4");
+        // 先生成到临时文件再改名，同时运行的测试进程不会读到生成了一半的镜像
+        let tmp = format!("{path}.{}", std::process::id());
+        generate_test_image(&tmp);
+        std::fs::rename(&tmp, path).expect("Failed to install test image");
+        path.to_string()
+    })
+}
+
+/// 生成测试镜像，内容为/test.txt（"Hello!\n"）和htree索引目录/htree（空文件entry-000 ~ entry-299）
+///
+/// 纯Rust后端用mkfs格式化（10MiB、4KiB块、2560个inode、1024块的日志）；C后端没有mkfs，
+/// 以仓库中的test-images/test.ext4为基础。
+fn generate_test_image(path: &str) {
+    use lwext4_arce::InodeType;
+
+    const ROOT_INO: u32 = 2;
+    let mut fs = base_image(path);
+    let dir = fs.create(ROOT_INO, "htree", InodeType::Directory, 0o755).unwrap();
+    for i in 0..300 {
+        fs.create(dir, format!("entry-{i:03}").as_str(), InodeType::RegularFile, 0o666).unwrap();
+    }
+    drop(fs);
+
+    // 目录超过一个块时文件系统自行建立htree索引，先由e2fsck -fn确认写出的镜像没有错误，
+    // 再由e2fsck -D按e2fsprogs的方式重建目录索引（宿主没有e2fsck时跳过检查，/htree保持文件系统建立的索引）
+    if let Ok(status) = e2fsck(&["-fn", path]) {
+        assert_eq!(status.code(), Some(0), "e2fsck -fn found errors in the generated image: {status}");
+        let status = e2fsck(&["-fyD", path]).unwrap();
+        // 退出码1表示已修改（优化了目录）
+        assert!(status.code().is_some_and(|c| c <= 1), "e2fsck -D failed: {status}");
+    }
+}
+
+/// 运行e2fsck，丢弃输出
+fn e2fsck(args: &[&str]) -> std::io::Result<std::process::ExitStatus> {
+    std::process::Command::new("e2fsck")
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+}
+
+/// 格式化测试镜像并写入/test.txt
+#[cfg(feature = "use-rust")]
+fn base_image(path: &str) -> lwext4_arce::Ext4Filesystem<lwext4_arce::DummyHal, FileBlockDevice> {
+    use lwext4_arce::{DummyHal, Ext4Filesystem, FsConfig, InodeType, MkfsOptions, RandomSource};
+
+    /// 固定种子，使每次生成的UUID和哈希种子相同
+    struct Seeded(u64);
+
+    impl RandomSource for Seeded {
+        fn fill_bytes(&mut self, buf: &mut [u8]) {
+            for b in buf {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                *b = self.0 as u8;
+            }
+        }
+    }
+
+    const ROOT_INO: u32 = 2;
+    File::create(path).unwrap().set_len(10 << 20).unwrap();
+    let opts = MkfsOptions {
+        block_size: 4096,
+        inodes: 2560,
+        journal_blocks: 1024,
+        ..Default::default()
+    };
+    let dev = FileBlockDevice::open(path).unwrap();
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(dev, &opts, FsConfig::default(), &mut Seeded(0x7E57))
+        .expect("Failed to format test image");
+    let file = fs.create(ROOT_INO, "test.txt", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, b"Hello!\n", 0).unwrap();
+    fs
+}
+
+/// 复制仓库中的测试镜像（已包含/test.txt）并挂载
+#[cfg(not(feature = "use-rust"))]
+fn base_image(path: &str) -> lwext4_arce::Ext4Filesystem<lwext4_arce::DummyHal, FileBlockDevice> {
+    use lwext4_arce::{DummyHal, Ext4Filesystem, FsConfig};
+
+    std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/../test-images/test.ext4"), path)
+        .expect("Failed to copy test image");
+    let dev = FileBlockDevice::open(path).unwrap();
+    Ext4Filesystem::<DummyHal, _>::new(dev, FsConfig::default()).expect("Failed to mount test image")
 }
 
-impl FileBlockDevice {
-    pub fn open(path: &str) -> std::io::Result<Self> {
-        Ok(Self {
-            file: File::options().read(true).write(true).open(path)?
-        })
+/// 复制测试镜像并打开
+pub fn open_test_image(name: &str) -> FileBlockDevice {
+    FileBlockDevice::open(copy_test_image(name)).expect("Failed to open test image")
+}
+
+/// CRC32C（Castagnoli），用于重新计算手工修改后的校验和
+pub fn crc32c(mut crc: u32, buf: &[u8]) -> u32 {
+    for &b in buf {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82F6_3B78 } else { crc >> 1 };
+        }
     }
+    crc
+}
+
+/// 修改镜像中的超级块并重新计算其校验和
+pub fn patch_superblock(path: &str, patch: impl FnOnce(&mut [u8])) {
+    let mut file = File::options().read(true).write(true).open(path).unwrap();
+    let mut sb = vec![0u8; 1024];
+    file.seek(SeekFrom::Start(1024)).unwrap();
+    file.read_exact(&mut sb).unwrap();
+    patch(&mut sb);
+    let csum = crc32c(!0, &sb[..0x3FC]);
+    sb[0x3FC..].copy_from_slice(&csum.to_le_bytes());
+    file.seek(SeekFrom::Start(1024)).unwrap();
+    file.write_all(&sb).unwrap();
 }
 
-impl BlockDevice for FileBlockDevice {
-    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Ext4Result<usize> {
-        self.file.seek(SeekFrom::Start(block_id * 512))
-            .map_err(|_| Ext4Error::new(libc::EIO, "seek failed"))?;
-        self.file.read(buf)
-            .map_err(|_| Ext4Error::new(libc::EIO, "read failed"))
+/// 修改镜像中的inode并重新计算其校验和（要求文件系统已卸载）
+pub fn patch_inode(path: &str, ino: u32, patch: impl FnOnce(&mut [u8])) {
+    let mut file = File::options().read(true).write(true).open(path).unwrap();
+    let mut sb = vec![0u8; 1024];
+    file.seek(SeekFrom::Start(1024)).unwrap();
+    file.read_exact(&mut sb).unwrap();
+    let le16 = |buf: &[u8], off: usize| u16::from_le_bytes(buf[off..off + 2].try_into().unwrap());
+    let le32 = |buf: &[u8], off: usize| u32::from_le_bytes(buf[off..off + 4].try_into().unwrap());
+    let block_size = 1024u64 << le32(&sb, 0x18);
+    let inodes_per_group = le32(&sb, 0x28);
+    let inode_size = le16(&sb, 0x58) as usize;
+    let is_64bit = le32(&sb, 0x60) & 0x80 != 0;
+    let desc_size = if is_64bit { le16(&sb, 0xFE) as u64 } else { 32 };
+
+    // 由块组描述符得到inode表位置
+    let group = ((ino - 1) / inodes_per_group) as u64;
+    let index = ((ino - 1) % inodes_per_group) as u64;
+    let mut desc = vec![0u8; desc_size as usize];
+    let gdt = (le32(&sb, 0x14) as u64 + 1) * block_size;
+    file.seek(SeekFrom::Start(gdt + group * desc_size)).unwrap();
+    file.read_exact(&mut desc).unwrap();
+    let mut table = le32(&desc, 0x08) as u64;
+    if is_64bit {
+        table |= (le32(&desc, 0x28) as u64) << 32;
     }
+    let offset = table * block_size + index * inode_size as u64;
+
+    let mut raw = vec![0u8; inode_size];
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.read_exact(&mut raw).unwrap();
+    patch(&mut raw);
 
-    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Ext4Result<usize> {
-        self.file.seek(SeekFrom::Start(block_id * 512))
-            .map_err(|_| Ext4Error::new(libc::EIO, "seek failed"))?;
-        self.file.write(buf)
-            .map_err(|_| Ext4Error::new(libc::EIO, "write failed"))
+    // metadata_csum：crc32c(种子, inode号, generation, inode（校验和字段视为0）)
+    if le32(&sb, 0x64) & 0x400 != 0 {
+        let seed = if le32(&sb, 0x60) & 0x2000 != 0 {
+            le32(&sb, 0x270)
+        } else {
+            crc32c(!0, &sb[0x68..0x78])
+        };
+        raw[0x7C..0x7E].fill(0);
+        if inode_size > 128 {
+            raw[0x82..0x84].fill(0);
+        }
+        let mut csum = crc32c(seed, &ino.to_le_bytes());
+        csum = crc32c(csum, &raw[0x64..0x68]);
+        csum = crc32c(csum, &raw);
+        raw[0x7C..0x7E].copy_from_slice(&(csum as u16).to_le_bytes());
+        if inode_size > 128 {
+            raw[0x82..0x84].copy_from_slice(&((csum >> 16) as u16).to_le_bytes());
+        }
     }
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(&raw).unwrap();
+}
 
-    fn num_blocks(&self) -> Ext4Result<u64> {
-        let size = self.file.metadata()
-            .map_err(|_| Ext4Error::new(libc::EIO, "metadata failed"))?
-            .len();
-        Ok(size / 512)
+/// 对每个断电时刻（记录的每个写入前缀，包括不写入和全部写入）把磁盘状态写到path，然后调用check
+///
+/// base为开始记录时的镜像内容；check的参数为已完成的写入数。
+pub fn for_each_crash_state<Dev: BlockDevice>(
+    base: &[u8],
+    recording: &RecordingDevice<Dev>,
+    path: &str,
+    mut check: impl FnMut(usize),
+) {
+    for count in 0..=recording.writes().len() {
+        let mut disk = RamDisk::from_vec(base.to_vec());
+        recording.replay(count, &mut disk).unwrap();
+        std::fs::write(path, disk.as_slice()).unwrap();
+        check(count);
     }
 }
@@ -1,27 +1,5599 @@
 mod common;
 
-use common::FileBlockDevice;
-use lwext4_arce::{DummyHal, Ext4Filesystem, FsConfig};
+use common::{
+    copy_test_image, crc32c, open_test_image, patch_inode, patch_superblock, FileBlockDevice,
+};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use lwext4_arce::{
+    DummyHal, ErrorContext, errno, Ext4Error, Ext4ErrorKind, Ext4Filesystem, FileAttr, FileHandle,
+    FsConfig, InodeType, SymlinkPolicy, SystemHal, glob_match,
+};
+
+/// 根目录的inode编号
+const ROOT_INO: u32 = 2;
+
+type Fs = Ext4Filesystem<DummyHal, FileBlockDevice>;
+
+fn mount(name: &str) -> Fs {
+    Fs::new(open_test_image(name), FsConfig::default()).expect("Failed to initialize filesystem")
+}
+
+/// 列出目录中的名称（不含"."和".."）
+fn list_dir(fs: &mut Fs, dir: u32) -> Vec<String> {
+    let mut reader = fs.read_dir(dir, 0).unwrap();
+    let mut names = Vec::new();
+    while let Some(entry) = reader.current() {
+        let name = String::from_utf8(entry.name().to_vec()).unwrap();
+        if name != "." && name != ".." {
+            names.push(name);
+        }
+        reader.step().unwrap();
+    }
+    names
+}
 
 #[test]
 fn test_open_filesystem() {
     // 测试能否成功打开文件系统
-    let test_image = "/home/c20h30o2/files/lwext4-rust/lwext4-rust/test-images/test.ext4";
-    let device = FileBlockDevice::open(test_image).expect("Failed to open test image");
+    let _fs = mount("open");
+}
+
+#[test]
+fn test_read_existing_file() {
+    let mut fs = mount("read");
+    let names = list_dir(&mut fs, ROOT_INO);
+    assert!(names.contains(&"lost+found".to_string()));
+    assert!(names.contains(&"test.txt".to_string()));
+
+    let ino = fs.lookup(ROOT_INO, "test.txt").unwrap().entry().ino();
+    let mut buf = [0u8; 64];
+    let n = fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(n, 7);
+}
+
+#[test]
+fn test_create_write_read() {
+    let mut fs = mount("write");
+    let ino = fs.create(ROOT_INO, "hello.bin", InodeType::RegularFile, 0o644).unwrap();
+
+    // 跨越多个块的数据
+    let data: Vec<u8> = (0..20000u32).map(|i| (i % 251) as u8).collect();
+    assert_eq!(fs.write_at(ino, &data, 0).unwrap(), data.len());
+
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(buf, data);
+
+    fs.set_len(ino, 100).unwrap();
+    let mut buf = vec![0u8; 200];
+    assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), 100);
+    assert_eq!(&buf[..100], &data[..100]);
+}
+
+#[test]
+fn test_mkdir_and_unlink() {
+    let mut fs = mount("mkdir");
+    let dir = fs.create(ROOT_INO, "sub", InodeType::Directory, 0o755).unwrap();
+    for i in 0..200 {
+        fs.create(dir, &format!("file-{i:03}"), InodeType::RegularFile, 0o644).unwrap();
+    }
+    assert_eq!(list_dir(&mut fs, dir).len(), 200);
+
+    for i in 0..200 {
+        fs.unlink(dir, &format!("file-{i:03}")).unwrap();
+    }
+    assert!(list_dir(&mut fs, dir).is_empty());
+    fs.unlink(ROOT_INO, "sub").unwrap();
+    assert!(fs.lookup(ROOT_INO, "sub").is_err());
+}
+
+#[test]
+fn test_rename_directory() {
+    let mut fs = mount("rename");
+    let a = fs.create(ROOT_INO, "a", InodeType::Directory, 0o755).unwrap();
+    let b = fs.create(ROOT_INO, "b", InodeType::Directory, 0o755).unwrap();
+    fs.rename(ROOT_INO, "a", b, "moved").unwrap();
+
+    assert_eq!(fs.lookup(b, "moved").unwrap().entry().ino(), a);
+    assert_eq!(fs.lookup(a, "..").unwrap().entry().ino(), b);
+    assert!(fs.lookup(ROOT_INO, "a").is_err());
+}
+
+#[test]
+fn test_error_kinds() {
+    let mut fs = mount("errors");
+    let err = fs.lookup(ROOT_INO, "missing").err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotFound);
+    assert_eq!(err.errno(), 2);
+
+    let dir = fs.create(ROOT_INO, "full", InodeType::Directory, 0o755).unwrap();
+    fs.create(dir, "child", InodeType::RegularFile, 0o644).unwrap();
+    let err = fs.unlink(ROOT_INO, "full").err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::DirectoryNotEmpty);
+
+    let long_name = "x".repeat(300);
+    let err = fs.create(ROOT_INO, &long_name, InodeType::RegularFile, 0o644).err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::NameTooLong);
+
+    assert_eq!(Ext4ErrorKind::from_errno(Ext4ErrorKind::NoSpace.to_errno()), Ext4ErrorKind::NoSpace);
+    assert_eq!(Ext4ErrorKind::from_errno(12345), Ext4ErrorKind::Other);
+    // 与lwext4_core是同一个类型，errno模块中的错误码都有对应的类别
+    let kind: lwext4_core::Ext4ErrorKind = Ext4ErrorKind::from_errno(errno::EBUSY);
+    assert_eq!(kind, Ext4ErrorKind::Busy);
+    for code in [errno::EMLINK, errno::ETIMEDOUT, errno::ESTALE, errno::EUCLEAN, errno::ECANCELED, errno::ENOKEY] {
+        let kind = Ext4ErrorKind::from_errno(code);
+        assert_ne!(kind, Ext4ErrorKind::Other, "{code}");
+        assert_eq!(kind.to_errno(), code);
+    }
+}
+
+#[test]
+fn test_error_context() {
+    let mut fs = mount("error-context");
+    let err = fs.lookup(ROOT_INO, "missing").err().unwrap();
+    assert_eq!(err.contexts().collect::<Vec<_>>(), ["ext4_dir_find_entry"]);
+    assert_eq!(err.ino(), Some(ROOT_INO));
+    assert_eq!(err.path_segment(), Some("missing"));
+    assert_eq!(
+        err.to_string(),
+        "ext4 error 2 (NotFound): ext4_dir_find_entry (ino 2, name \"missing\")"
+    );
+
+    // 内层帧的位置信息优先，超出深度的外层帧只留下截断标记
+    let err = Ext4Error::new(5, "inner")
+        .with_context(ErrorContext::new("extent").ino(12).block(345))
+        .with_context(ErrorContext::new("read_at").ino(99).path_segment("é".repeat(20)))
+        .with_context(ErrorContext::new("outer"));
+    assert_eq!(err.contexts().collect::<Vec<_>>(), ["inner", "extent", "read_at"]);
+    assert_eq!((err.ino(), err.block()), (Some(12), Some(345)));
+    assert_eq!(err.path_segment(), Some("é".repeat(11).as_str()));
+    assert!(err.to_string().ends_with("read_at <- ... (ino 12, block 345, name \"ééééééééééé\"...)"));
+    // 兼容字段：最外层的操作名
+    #[allow(deprecated)]
+    let outer = err.context;
+    assert_eq!(outer, Some("outer"));
+}
+
+#[test]
+fn test_extent_cache() {
+    let mut fs = mount("extent-cache");
+    let ino = fs
+        .create(ROOT_INO, "seq.bin", InodeType::RegularFile, 0o644)
+        .unwrap();
+    let data: Vec<u8> = (0..8 * 4096u32).map(|i| (i % 253) as u8).collect();
+    fs.write_at(ino, &data, 0).unwrap();
+
+    // 写入时已记录映射，顺序和随机读取都不需要遍历 extent 树
+    fs.reset_metrics();
+    let mut buf = vec![0u8; data.len()];
+    fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(buf, data);
+    let mut one = [0u8; 4096];
+    for blk in [5u64, 1, 7, 3] {
+        fs.read_at(ino, &mut one, blk * 4096).unwrap();
+        assert_eq!(&one[..], &data[blk as usize * 4096..][..4096]);
+    }
+    let m = fs.metrics();
+    assert!(m.extent_cache_hits >= 12);
+    assert_eq!(m.extent_cache_misses, 0);
+
+    // 截断后缓存失效，重新扩展的部分是空洞
+    fs.set_len(ino, 4096).unwrap();
+    fs.set_len(ino, data.len() as u64).unwrap();
+    fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(&buf[..4096], &data[..4096]);
+    assert!(buf[4096..].iter().all(|&b| b == 0));
+
+    fs.write_at(ino, &[0xab; 4096], 5 * 4096).unwrap();
+    fs.read_at(ino, &mut one, 5 * 4096).unwrap();
+    assert_eq!(one, [0xab; 4096]);
+}
+
+#[test]
+fn test_htree_readdir_cookies() {
+    let mut fs = mount("htree-readdir");
+    // 测试镜像中的 /htree 是 htree 索引目录，包含 entry-000 ~ entry-299
+    let dir = fs.lookup(ROOT_INO, "htree").unwrap().entry().ino();
+    let mut all = list_dir(&mut fs, dir);
+    all.sort();
+    let expected: Vec<String> = (0..300).map(|i| format!("entry-{i:03}")).collect();
+    assert_eq!(all, expected);
+
+    // 读取前100个目录项，记下继续读取的偏移
+    let mut seen = Vec::new();
+    let mut reader = fs.read_dir(dir, 0).unwrap();
+    let mut last = 0;
+    for _ in 0..100 {
+        let entry = reader.current().unwrap();
+        assert!(reader.offset() >= last);
+        last = reader.offset();
+        seen.push(String::from_utf8(entry.name().to_vec()).unwrap());
+        reader.step().unwrap();
+    }
+    let cookie = reader.offset();
+    drop(reader);
+
+    // 中途删除已读和未读的目录项、添加新目录项
+    let seen_names: Vec<_> = seen.iter().filter(|n| n.starts_with("entry-")).cloned().collect();
+    let unseen: Vec<_> = expected.iter().filter(|n| !seen.contains(n)).cloned().collect();
+    for name in seen_names.iter().take(20).chain(unseen.iter().take(20)) {
+        fs.unlink(dir, name).unwrap();
+    }
+    for i in 0..50 {
+        fs.create(dir, &format!("new-{i:02}"), InodeType::RegularFile, 0o644)
+            .unwrap();
+    }
+
+    // 从偏移继续读取：未删除的原有目录项恰好各出现一次
+    let mut reader = fs.read_dir(dir, cookie).unwrap();
+    while let Some(entry) = reader.current() {
+        assert!(reader.offset() >= last);
+        last = reader.offset();
+        seen.push(String::from_utf8(entry.name().to_vec()).unwrap());
+        reader.step().unwrap();
+    }
+    let mut old: Vec<_> = seen.iter().filter(|n| n.starts_with("entry-")).cloned().collect();
+    let len = old.len();
+    old.sort();
+    old.dedup();
+    assert_eq!(old.len(), len);
+    let survivors: Vec<_> = expected
+        .iter()
+        .filter(|n| !unseen[..20].contains(n))
+        .cloned()
+        .collect();
+    assert_eq!(old, survivors);
+    let mut new: Vec<_> = seen.iter().filter(|n| n.starts_with("new-")).collect();
+    let len = new.len();
+    new.sort();
+    new.dedup();
+    assert_eq!(new.len(), len);
+}
+
+#[test]
+fn test_htree_readdir_corrupt_leaf() {
+    use common::e2fs::debugfs;
+
+    // 破坏 /htree 第一个叶子块中第一个目录项的长度
+    let path = copy_test_image("htree-corrupt-leaf");
+    let blocks: Vec<u64> = String::from_utf8(debugfs(&path, "blocks /htree"))
+        .unwrap()
+        .split_whitespace()
+        .map(|b| b.parse().unwrap())
+        .collect();
+    let mut image = std::fs::read(&path).unwrap();
+    let rec_len = blocks[1] as usize * 4096 + 4;
+    image[rec_len..rec_len + 2].copy_from_slice(&3u16.to_le_bytes());
+    std::fs::write(&path, image).unwrap();
+
+    // 读取目录时报告EIO，而不是跳过损坏的叶子
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let dir = fs.lookup(ROOT_INO, "htree").unwrap().entry().ino();
+    let mut count = 0;
+    let err = fs.read_dir(dir, 0).and_then(|mut reader| {
+        while reader.current().is_some() {
+            count += 1;
+            reader.step()?;
+        }
+        Ok(())
+    });
+    assert_eq!(err.unwrap_err().errno(), errno::EIO);
+    assert!(count < 302, "{count}");
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_dir_pages() {
+    let mut fs = mount("read-dir-pages");
+
+    // 每页最多7个条目，逐页读完 /htree
+    let mut names = Vec::new();
+    let mut cookie = 0;
+    let mut pages = 0;
+    loop {
+        let page = fs.read_dir_from("/htree", cookie, 7).unwrap();
+        assert!(page.entries.len() <= 7);
+        names.extend(
+            page.entries
+                .iter()
+                .map(|e| String::from_utf8(e.name.clone()).unwrap()),
+        );
+        pages += 1;
+        match page.cookie {
+            Some(next) => cookie = next,
+            None => break,
+        }
+    }
+    assert_eq!(pages, 302usize.div_ceil(7));
+    names.sort();
+    let mut expected: Vec<String> = (0..300).map(|i| format!("entry-{i:03}")).collect();
+    expected.extend([".".into(), "..".into()]);
+    expected.sort();
+    assert_eq!(names, expected);
+
+    // 过滤掉的条目不计入页大小
+    let page = fs
+        .read_dir_filtered("htree/", 0, 5, |e| e.inode_type() == InodeType::Directory)
+        .unwrap();
+    let dirs: Vec<_> = page.entries.iter().map(|e| e.name.as_slice()).collect();
+    assert_eq!(dirs, [b".".as_slice(), b"..".as_slice()]);
+    assert_eq!(page.cookie, None);
+
+    let page = fs.read_dir_from("/", 0, 100).unwrap();
+    let entry = page.entries.iter().find(|e| e.name == b"test.txt").unwrap();
+    assert_eq!(entry.inode_type, InodeType::RegularFile);
+    assert_eq!(fs.resolve_path("/htree/../test.txt").unwrap(), entry.ino);
+
+    let err = fs.read_dir_from("/test.txt", 0, 10).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+    let err = fs.read_dir_from("/test.txt/x", 0, 10).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+    let err = fs.read_dir_from("/missing", 0, 10).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotFound);
+}
+
+#[test]
+fn test_read_dir_plus() {
+    let mut fs = mount("read-dir-plus");
+
+    let mut count = 0;
+    let mut cookie = 0;
+    loop {
+        let page = fs.read_dir_plus("/htree", cookie, 50).unwrap();
+        for e in &page.entries {
+            assert_eq!(e.attr.ino, e.entry.ino);
+            assert_eq!(e.attr.node_type, e.entry.inode_type);
+        }
+        count += page.entries.len();
+        match page.cookie {
+            Some(next) => cookie = next,
+            None => break,
+        }
+    }
+    assert_eq!(count, 302);
+
+    // /htree 的条目按哈希顺序返回，指向的300个inode分布在20个inode表块中，
+    // 每个inode表块只读取一次
+    fs.read_dir_from("/htree", 0, 400).unwrap();
+    fs.reset_metrics();
+    let page = fs.read_dir_plus("/htree", 0, 400).unwrap();
+    assert_eq!(page.entries.len(), 302);
+    let reads = fs.metrics().block_reads;
+    assert!(reads <= 20, "block_reads = {reads}");
+
+    // 属性与单独调用get_attr的结果一致
+    let page = fs.read_dir_plus("/", 0, 100).unwrap();
+    let file = page
+        .entries
+        .iter()
+        .find(|e| e.entry.name == b"test.txt")
+        .unwrap();
+    let mut attr = FileAttr::default();
+    fs.get_attr(file.entry.ino, &mut attr).unwrap();
+    assert_eq!(file.attr.size, attr.size);
+    assert_eq!(file.attr.mode, attr.mode);
+    assert_eq!(file.attr.mtime, attr.mtime);
+    assert_eq!(file.attr.node_type, InodeType::RegularFile);
+    assert!(page.cookie.is_none());
+
+    let err = fs.read_dir_plus("/test.txt", 0, 10).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+}
+
+#[test]
+fn test_create_many() {
+    let path = copy_test_image("create-many");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let dir = fs.create(ROOT_INO, "bulk", InodeType::Directory, 0o755).unwrap();
+    let names: Vec<String> = (0..200).map(|i| format!("f{i}")).collect();
+    let inos = fs
+        .create_many(dir, names.iter().map(String::as_str), InodeType::RegularFile, 0o640)
+        .unwrap();
+    assert_eq!(inos.len(), names.len());
+    for (name, &ino) in names.iter().zip(&inos) {
+        assert_eq!(fs.lookup(dir, name.as_str()).unwrap().entry().ino(), ino);
+    }
+    let mut attr = FileAttr::default();
+    fs.get_attr(inos[0], &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o640);
+
+    let subdirs = fs.create_many(dir, ["d0", "d1"], InodeType::Directory, 0o755).unwrap();
+    fs.get_attr(dir, &mut attr).unwrap();
+    assert_eq!(attr.nlink, 4);
+    assert_eq!(fs.lookup(subdirs[1], "..").unwrap().entry().ino(), dir);
+
+    // 中途失败时整批撤销
+    let before = fs.stat().unwrap();
+    let long_name = "x".repeat(300);
+    let err = fs
+        .create_many(dir, ["ok", long_name.as_str()], InodeType::RegularFile, 0o644)
+        .unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NameTooLong);
+    assert!(fs.lookup(dir, "ok").is_err());
+    assert_eq!(fs.stat().unwrap().free_inodes_count, before.free_inodes_count);
+
+    assert!(fs.create_many(dir, Vec::<&str>::new(), InodeType::RegularFile, 0o644).unwrap().is_empty());
+    fs.flush().unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+}
+
+#[test]
+fn test_open_options() {
+    use lwext4_arce::OpenOptions;
+
+    let path = copy_test_image("open-options");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let kind = |r: Result<(), Ext4Error>| r.unwrap_err().kind();
+
+    // 不存在且未指定create
+    assert_eq!(kind(fs.open("/new.txt", &OpenOptions::from_mode_str("r").unwrap()).map(drop)), Ext4ErrorKind::NotFound);
+    // "w"：创建并截断
+    let mut file = OpenOptions::from_mode_str("w").unwrap().mode(0o600).open(&mut fs, "/new.txt").unwrap();
+    assert_eq!(file.write(b"hello world").unwrap(), 11);
+    assert_eq!(file.position(), 11);
+    assert_eq!(kind(file.read(&mut [0; 4]).map(drop)), Ext4ErrorKind::NotPermitted);
+    let ino = file.ino();
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o600);
+
+    // "r+"：读写已有文件，不截断
+    let mut file = fs.open("/new.txt", &OpenOptions::from_mode_str("r+").unwrap()).unwrap();
+    let mut buf = [0u8; 5];
+    assert_eq!(file.read(&mut buf).unwrap(), 5);
+    assert_eq!(&buf, b"hello");
+    file.seek(6);
+    file.write(b"WORLD").unwrap();
+    assert_eq!(file.len().unwrap(), 11);
+
+    // O_WRONLY|O_APPEND：每次写入都在末尾
+    let mut file = fs.open("/new.txt", &OpenOptions::from_flags(0o1 | 0o2000)).unwrap();
+    file.seek(0);
+    file.write(b"!").unwrap();
+    assert_eq!(file.position(), 12);
+    let mut content = vec![0u8; 12];
+    fs.read_at(ino, &mut content, 0).unwrap();
+    assert_eq!(content, b"hello WORLD!");
+
+    // O_CREAT|O_EXCL：已存在时失败；O_TRUNC截断已有文件
+    let excl = OpenOptions::from_flags(0o1 | 0o100 | 0o200);
+    assert_eq!(kind(fs.open("/new.txt", &excl).map(drop)), Ext4ErrorKind::AlreadyExists);
+    let file = OpenOptions::new().write(true).truncate(true).open(&mut fs, "/new.txt").unwrap();
+    assert_eq!(file.ino(), ino);
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, 0);
+    let created = fs.open("/excl.txt", &excl).unwrap().ino();
+    assert_eq!(fs.resolve_path("/excl.txt").unwrap(), created);
+
+    // 无效组合和非普通文件
+    assert_eq!(kind(fs.open("/new.txt", &OpenOptions::new()).map(drop)), Ext4ErrorKind::InvalidInput);
+    let read_create = *OpenOptions::new().read(true).create(true);
+    assert_eq!(kind(fs.open("/other", &read_create).map(drop)), Ext4ErrorKind::InvalidInput);
+    assert_eq!(kind(fs.open("/", &OpenOptions::from_mode_str("a").unwrap()).map(drop)), Ext4ErrorKind::IsADirectory);
+    assert!(OpenOptions::from_mode_str("rw").is_none());
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_dir_handle() {
+    let mut fs = mount("dir-handle");
+    fs.create_dir_all("/a/b", 0o755).unwrap();
+    let b = fs.resolve_path("/a/b").unwrap();
+
+    let mut dir = fs.open_dir("/a/b").unwrap();
+    assert_eq!(dir.ino(), b);
+    let file = dir.create("file", InodeType::RegularFile, 0o644).unwrap();
+    dir.create("sub", InodeType::Directory, 0o755).unwrap();
+    assert_eq!(dir.lookup("file").unwrap().entry().ino(), file);
+    let page = dir.read_dir_from(0, 16).unwrap();
+    assert_eq!(page.entries.len(), 4);
+    assert!(page.cookie.is_none());
+    dir.unlink("file").unwrap();
+    assert_eq!(dir.lookup("file").err().unwrap().kind(), Ext4ErrorKind::NotFound);
+    let sub = fs.dir_handle(b).unwrap().lookup("sub").unwrap().entry().ino();
+    assert_eq!(fs.resolve_path("/a/b/sub").unwrap(), sub);
+
+    let file = fs.create(b, "plain", InodeType::RegularFile, 0o644).unwrap();
+    assert_eq!(fs.open_dir("/a/b/plain").err().unwrap().kind(), Ext4ErrorKind::NotADirectory);
+    assert_eq!(fs.dir_handle(file).err().unwrap().kind(), Ext4ErrorKind::NotADirectory);
+    assert_eq!(fs.open_dir("/missing").err().unwrap().kind(), Ext4ErrorKind::NotFound);
+}
+
+#[test]
+fn test_file_handle() {
+    let path = copy_test_image("file-handle");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let old = fs.create(ROOT_INO, "old", InodeType::RegularFile, 0o644).unwrap();
+    let handle = fs.file_handle(old).unwrap();
+    assert_eq!(handle.ino, old);
+    assert_eq!(FileHandle::from_bytes(&handle.to_bytes()), Some(handle));
+    assert_eq!(FileHandle::from_bytes(&[0; 4]), None);
+    assert_eq!(fs.open_by_handle(&handle).unwrap(), old);
+
+    // 释放后同一inode编号被重新分配，版本号递增
+    fs.unlink(ROOT_INO, "old").unwrap();
+    let err = fs.open_by_handle(&handle).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Stale);
+    assert_eq!(err.errno(), 116);
+    let new = fs.create(ROOT_INO, "new", InodeType::RegularFile, 0o644).unwrap();
+    assert_eq!(new, old);
+    let new_handle = fs.file_handle(new).unwrap();
+    assert_eq!(new_handle.generation, handle.generation.wrapping_add(1));
+    assert_eq!(fs.open_by_handle(&handle).unwrap_err().kind(), Ext4ErrorKind::Stale);
+    assert_eq!(fs.open_by_handle(&new_handle).unwrap(), new);
+
+    let bogus = FileHandle { ino: u32::MAX, generation: 0 };
+    assert_eq!(fs.open_by_handle(&bogus).unwrap_err().kind(), Ext4ErrorKind::Stale);
+
+    // 版本号持久化
+    drop(fs);
+    assert_fsck_clean(&path);
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert_eq!(fs.open_by_handle(&new_handle).unwrap(), new);
+}
+
+#[test]
+fn test_owner_32bit() {
+    let path = copy_test_image("owner-32bit");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let file = fs.create(ROOT_INO, "owned", InodeType::RegularFile, 0o644).unwrap();
+    // 低16位与另一个ID相同，只写低16位时无法区分
+    let (uid, gid) = (0x1_0005, 0xdead_beef);
+    fs.with_inode_ref(file, |inode| {
+        inode.set_owner(uid, gid);
+        assert_eq!((inode.uid(), inode.gid()), (uid, gid));
+        Ok(())
+    })
+    .unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut attr = FileAttr::default();
+    fs.get_attr(file, &mut attr).unwrap();
+    assert_eq!((attr.uid, attr.gid), (uid, gid));
+    fs.with_inode_ref(file, |inode| {
+        inode.set_owner(5, 0);
+        Ok(())
+    })
+    .unwrap();
+    fs.get_attr(file, &mut attr).unwrap();
+    assert_eq!((attr.uid, attr.gid), (5, 0));
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 固定返回同一时刻的墙上时钟
+struct WallClockHal;
+impl SystemHal for WallClockHal {
+    fn now() -> Option<Duration> {
+        Some(Duration::new(1_700_000_000, 123_456_789))
+    }
+}
+
+#[test]
+fn test_timestamps_nsec() {
+    use common::e2fs::mke2fs;
+
+    for inode_size in ["256", "128"] {
+        let path = std::env::temp_dir().join(format!("lwext4-nsec-{inode_size}-{}.ext4", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::File::create(path).unwrap().set_len(8 << 20).unwrap();
+        if !mke2fs(path, 8 << 10, &["-I", inode_size]) {
+            return;
+        }
+        let large = inode_size == "256";
+        // 超过2038年的时间需要扩展字段中的秒高位
+        let mtime = Duration::new(0x1_2345_6789, 987_654_321);
+        {
+            let mut fs = Ext4Filesystem::<WallClockHal, _>::new(FileBlockDevice::open(path).unwrap(), FsConfig::default())
+                .unwrap();
+            let a = fs.create(ROOT_INO, "a", InodeType::RegularFile, 0o644).unwrap();
+            let b = fs.create(ROOT_INO, "b", InodeType::RegularFile, 0o644).unwrap();
+            fs.with_inode_ref(a, |inode| {
+                inode.set_mtime(&mtime);
+                inode.set_atime(&Duration::new(5, 6));
+                Ok(())
+            })
+            .unwrap();
+            let mut attr = FileAttr::default();
+            fs.get_attr(b, &mut attr).unwrap();
+            assert_eq!(attr.mode & 0o777, 0o644);
+        }
+        assert_fsck_clean(path);
+
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let a = fs.resolve_path("/a").unwrap();
+        let mut attr = FileAttr::default();
+        fs.get_attr(a, &mut attr).unwrap();
+        if large {
+            assert_eq!(attr.btime, Some(WallClockHal::now().unwrap()));
+            assert_eq!(attr.mtime, mtime);
+            assert_eq!(attr.atime, Duration::new(5, 6));
+        } else {
+            // 128字节的inode只有秒（32位）
+            assert_eq!(attr.btime, None);
+            assert_eq!(attr.mtime, Duration::from_secs(mtime.as_secs() as u32 as u64));
+            assert_eq!(attr.atime, Duration::from_secs(5));
+        }
+        drop(fs);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_timestamps_epoch_matches_kernel() {
+    use common::e2fs::{debugfs, mke2fs};
+    use std::process::Command;
+
+    let path = std::env::temp_dir().join(format!("lwext4-epoch-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(8 << 20).unwrap();
+    if !mke2fs(path, 8 << 10, &["-I", "256"]) {
+        return;
+    }
+    // 2040年和2100年（秒的低32位按有符号数解释时为负，epoch为1），以及debugfs显示的原始字段
+    let cases = [
+        ("y2040", 2_208_988_800u64, "0x83aa7e80:00000001"),
+        ("y2100", 4_102_444_800u64, "0xf4865700:00000001"),
+    ];
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        for (name, secs, _) in cases {
+            let ino = fs.create(ROOT_INO, name, InodeType::RegularFile, 0o644).unwrap();
+            fs.with_inode_ref(ino, |inode| {
+                inode.set_mtime(&Duration::from_secs(secs));
+                Ok(())
+            })
+            .unwrap();
+        }
+        fs.create(ROOT_INO, "pre1970", InodeType::RegularFile, 0o644).unwrap();
+        fs.create(ROOT_INO, "kernel", InodeType::RegularFile, 0o644).unwrap();
+    }
+    assert_fsck_clean(path);
+
+    // 本实现写入的时间与内核（debugfs）的解释一致
+    for (name, _, raw) in cases {
+        let stat = String::from_utf8(debugfs(path, &format!("stat /{name}"))).unwrap();
+        assert!(stat.contains(&format!("mtime: {raw}")), "{name}:\n{stat}");
+    }
+
+    // 由debugfs按内核方式写入的时间能被正确读出，1970年以前的时间截断为0
+    for request in ["sif /kernel mtime @4102444800", "sif /pre1970 mtime @-86400"] {
+        let status = Command::new("debugfs").args(["-w", "-R", request, path]).output().unwrap().status;
+        assert!(status.success(), "debugfs {request}");
+    }
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let mut attr = FileAttr::default();
+    for (name, secs, _) in cases {
+        let ino = fs.resolve_path(&format!("/{name}")).unwrap();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!(attr.mtime, Duration::from_secs(secs), "{name}");
+    }
+    let ino = fs.resolve_path("/kernel").unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.mtime, Duration::from_secs(4_102_444_800));
+    let ino = fs.resolve_path("/pre1970").unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.mtime, Duration::ZERO);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_dir_entry_types() {
+    let path = copy_test_image("dir-types");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let file = fs.create(ROOT_INO, "dtype-file", InodeType::RegularFile, 0o644).unwrap();
+    let dir = fs.create(ROOT_INO, "dtype-dir", InodeType::Directory, 0o755).unwrap();
+    fs.create(dir, "fifo", InodeType::Fifo, 0o644).unwrap();
+    // set_mode只修改权限位，不会让目录项的类型与inode不一致
+    fs.with_inode_ref(file, |inode| {
+        inode.set_mode(0o600);
+        assert_eq!(inode.inode_type(), InodeType::RegularFile);
+        Ok(())
+    })
+    .unwrap();
+    assert!(fs.check_dir_types(false).unwrap().is_empty());
+    drop(fs);
+
+    // 把目录项中的类型改为符号链接（name_len、类型字节紧接在名称之前）
+    let mut image = std::fs::read(&path).unwrap();
+    let name = b"dtype-file";
+    let mut patched = 0;
+    for pos in 2..image.len() - name.len() {
+        if &image[pos..pos + name.len()] == name && image[pos - 2] == name.len() as u8 {
+            image[pos - 1] = 7;
+            patched += 1;
+        }
+    }
+    assert!(patched > 0);
+    std::fs::write(&path, &image).unwrap();
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let expected = vec![lwext4_arce::DirTypeMismatch {
+        dir: ROOT_INO,
+        name: "dtype-file".into(),
+        ino: file,
+        found: InodeType::Symlink,
+        expected: InodeType::RegularFile,
+    }];
+    assert_eq!(fs.check_dir_types(false).unwrap(), expected);
+    assert_eq!(fs.check_dir_types(true).unwrap(), expected);
+    assert!(fs.check_dir_types(false).unwrap().is_empty());
+    drop(fs);
+    assert_fsck_clean(&path);
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_reserved_inodes() {
+    use common::e2fs::mke2fs;
+
+    let path = std::env::temp_dir().join(format!("lwext4-reserved-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    if !mke2fs(path, 4 << 10, &["-b", "1024"]) {
+        return;
+    }
+    // first_ino落在块组位图中间：12~19号inode在位图中空闲，但仍属于保留范围
+    patch_superblock(path, |sb| sb[0x54..0x58].copy_from_slice(&20u32.to_le_bytes()));
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+    assert_eq!(file, 20);
+    let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    assert_eq!(dir, 21);
+
+    // 保留inode不能新增链接或被删除
+    assert_eq!(fs.link(ROOT_INO, "journal", 8).unwrap_err().errno(), errno::EPERM);
+    assert_eq!(fs.unlink(ROOT_INO, "..").unwrap_err().errno(), errno::EPERM);
+    assert_eq!(fs.unlink(dir, "..").unwrap_err().errno(), errno::EPERM);
+    assert_eq!(fs.unlink(ROOT_INO, "lost+found").unwrap_err().errno(), errno::EPERM);
+    // 重命名时被替换的目标也不能是保留inode
+    assert_eq!(fs.rename(ROOT_INO, "file", ROOT_INO, "lost+found").unwrap_err().errno(), errno::EPERM);
+    assert!(fs.resolve_path("/file").is_ok());
+    fs.unlink(ROOT_INO, "file").unwrap();
+    assert_eq!(fs.create(ROOT_INO, "again", InodeType::RegularFile, 0o644).unwrap(), 20);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_recover_deleted() {
+    use common::e2fs::mke2fs;
+    use std::process::Command;
+
+    let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+    // extent和间接块映射各一次
+    for (i, features) in ["extent", "^extent,^64bit"].into_iter().enumerate() {
+        let path = std::env::temp_dir().join(format!("lwext4-undelete-{i}-{}.ext4", std::process::id()));
+        let path = path.to_str().unwrap();
+        if !mke2fs(path, 4 << 10, &["-b", "1024", "-O", features]) {
+            return;
+        }
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let lost = fs.create(ROOT_INO, "lost.bin", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(lost, &data, 0).unwrap();
+        let kept = fs.create(ROOT_INO, "kept.bin", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(kept, b"still here", 0).unwrap();
+        let filler = fs.create(ROOT_INO, "filler", InodeType::RegularFile, 0o644).unwrap();
+        // 本实现删除文件时清空块映射，删除后没有可恢复的inode
+        let gone = fs.create(ROOT_INO, "gone.bin", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(gone, &data, 0).unwrap();
+        fs.unlink(ROOT_INO, "gone.bin").unwrap();
+        drop(fs);
+
+        // kill_file只释放inode和数据块（设置删除时间），保留块映射
+        for request in ["kill_file /lost.bin", "unlink /lost.bin"] {
+            let status = Command::new("debugfs").args(["-w", "-R", request, path]).output().unwrap().status;
+            assert!(status.success(), "debugfs {request}");
+        }
+
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let deleted = fs.scan_deleted().unwrap();
+        assert_eq!(deleted.len(), 1, "{features}: {deleted:?}");
+        let entry = &deleted[0];
+        assert_eq!((entry.ino, entry.inode_type, entry.size), (lost, InodeType::RegularFile, 5000));
+        assert_ne!(entry.dtime, 0);
+        assert_eq!((entry.blocks, entry.free_blocks), (5, 5));
+
+        let recovered = fs.recover_deleted(lost).unwrap();
+        assert_eq!((recovered.blocks_recovered, recovered.blocks_lost), (5, 0));
+        assert!(recovered.data == data, "{features}");
+        assert_eq!(fs.recover_deleted(kept).unwrap_err().errno(), errno::EINVAL);
+
+        // 数据块被重新分配后以0填充
+        let mut offset = 0;
+        while fs.scan_deleted().unwrap().first().is_some_and(|e| e.free_blocks == 5) {
+            fs.write_at(filler, &[0xAA; 1024], offset).unwrap();
+            offset += 1024;
+        }
+        let partial = fs.recover_deleted(lost).unwrap();
+        assert_eq!(partial.blocks_recovered + partial.blocks_lost, 5);
+        assert!(partial.blocks_lost > 0);
+        drop(fs);
+        std::fs::remove_file(path).unwrap();
+    }
+}
 
-    let _fs = Ext4Filesystem::<DummyHal, _>::new(device, FsConfig::default())
-        .expect("Failed to initialize filesystem");
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_diff_images() {
+    use lwext4_arce::{Change, DiffOptions, MetadataField, MkfsOptions, PathDiff, RamDisk, diff_images};
+
+    let opts = MkfsOptions { block_size: 1024, journal: false, ..Default::default() };
+    let build = |seed| {
+        let disk = RamDisk::new(8 << 20);
+        let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(disk, &opts, FsConfig::default(), &mut XorShift(seed)).unwrap();
+        let etc = fs.create_dir_all("/etc", 0o755).unwrap();
+        let conf = fs.create(etc, "app.conf", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(conf, &vec![b'x'; 200_000], 0).unwrap();
+        let link = fs.create(ROOT_INO, "link", InodeType::Symlink, 0o777).unwrap();
+        fs.set_symlink(link, b"etc/app.conf").unwrap();
+        fs.create(ROOT_INO, "old", InodeType::RegularFile, 0o644).unwrap();
+        fs.create(ROOT_INO, "kind", InodeType::RegularFile, 0o644).unwrap();
+        fs
+    };
+    // 不同的UUID和随机数不影响比较结果
+    let mut a = build(1);
+    let mut b = build(2);
+    assert_eq!(diff_images(&mut a, &mut b, &DiffOptions::default()).unwrap(), vec![]);
+
+    let conf = b.resolve_path("/etc/app.conf").unwrap();
+    b.write_at(conf, b"y", 150_000).unwrap();
+    b.with_inode_ref(conf, |inode| {
+        inode.set_mode(0o600);
+        inode.set_mtime(&Duration::from_secs(1000));
+        Ok(())
+    })
+    .unwrap();
+    let link = b.resolve_path("/link").unwrap();
+    b.set_symlink(link, b"etc/other").unwrap();
+    b.unlink(ROOT_INO, "old").unwrap();
+    b.create(ROOT_INO, "new", InodeType::RegularFile, 0o644).unwrap();
+    b.unlink(ROOT_INO, "kind").unwrap();
+    b.create(ROOT_INO, "kind", InodeType::Directory, 0o755).unwrap();
+
+    let diffs = diff_images(&mut a, &mut b, &DiffOptions::default()).unwrap();
+    let changes: Vec<(&str, &Change)> = diffs.iter().map(|d| (d.path.as_str(), &d.change)).collect();
+    let crc_of = |path: &str| {
+        diffs
+            .iter()
+            .find_map(|d| match &d.change {
+                Change::Content { old, new } if d.path == path => Some((*old, *new)),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let (link_crc, file_crc) = (crc_of("/link"), crc_of("/etc/app.conf"));
+    assert_ne!(link_crc.0, link_crc.1);
+    assert_ne!(file_crc.0, file_crc.1);
+    assert_eq!(
+        changes,
+        [
+            (
+                "/",
+                &Change::Metadata { field: MetadataField::Nlink, old: 4, new: 5 }
+            ),
+            (
+                "/etc/app.conf",
+                &Change::Metadata { field: MetadataField::Mode, old: 0o644, new: 0o600 }
+            ),
+            (
+                "/etc/app.conf",
+                &Change::Metadata { field: MetadataField::Mtime, old: 0, new: 1_000_000_000_000 }
+            ),
+            ("/etc/app.conf", &Change::Content { old: file_crc.0, new: file_crc.1 }),
+            (
+                "/kind",
+                &Change::TypeChanged { old: InodeType::RegularFile, new: InodeType::Directory }
+            ),
+            ("/link", &Change::Metadata { field: MetadataField::Size, old: 12, new: 9 }),
+            ("/link", &Change::Content { old: link_crc.0, new: link_crc.1 }),
+            ("/new", &Change::Added(InodeType::RegularFile)),
+            ("/old", &Change::Removed(InodeType::RegularFile)),
+        ],
+        "{diffs:#?}"
+    );
+
+    // 忽略修改时间
+    let opts = DiffOptions { times: false, ..Default::default() };
+    let diffs: Vec<PathDiff> = diff_images(&mut a, &mut b, &opts).unwrap();
+    assert!(diffs.iter().all(|d| !matches!(d.change, Change::Metadata { field: MetadataField::Mtime, .. })));
+    assert_eq!(diffs.len(), 8);
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_quota_usage() {
+    use lwext4_arce::{MkfsOptions, QuotaUsage, RamDisk};
+
+    let opts = MkfsOptions { block_size: 1024, journal: false, ..Default::default() };
+    let disk = RamDisk::new(8 << 20);
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(disk, &opts, FsConfig::default(), &mut XorShift(3)).unwrap();
+    assert_eq!(fs.user_usage(0), None);
+    fs.enable_quota_tracking().unwrap();
+    let root = fs.user_usage(0).unwrap();
+    // 根目录和lost+found
+    assert!(root.inodes >= 2 && root.space > 0, "{root:?}");
+    assert_eq!(fs.user_usage(1000), Some(QuotaUsage::default()));
+
+    let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+    fs.with_inode_ref(file, |inode| {
+        inode.set_owner(1000, 100);
+        Ok(())
+    })
+    .unwrap();
+    fs.write_at(file, &[1u8; 10 * 1024], 0).unwrap();
+    assert_eq!(fs.user_usage(1000), Some(QuotaUsage { space: 10 * 1024, inodes: 1 }));
+    assert_eq!(fs.group_usage(100), fs.user_usage(1000));
+    assert_eq!(fs.user_usage(0), Some(root));
+
+    // 修改属主时用量随之转移
+    fs.with_inode_ref(file, |inode| {
+        inode.set_owner(2000, 100);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(fs.user_usage(1000), Some(QuotaUsage::default()));
+    assert_eq!(fs.user_usage(2000), Some(QuotaUsage { space: 10 * 1024, inodes: 1 }));
+    fs.set_len(file, 3 * 1024).unwrap();
+    assert_eq!(fs.user_usage(2000), Some(QuotaUsage { space: 3 * 1024, inodes: 1 }));
+
+    let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    fs.with_inode_ref(dir, |inode| {
+        inode.set_owner(1000, 200);
+        Ok(())
+    })
+    .unwrap();
+    for i in 0..200 {
+        fs.create(dir, format!("entry-{i:03}").as_str(), InodeType::RegularFile, 0o644).unwrap();
+    }
+    // 稀疏写入产生多个extent和索引块
+    let sparse = fs.create(dir, "sparse", InodeType::RegularFile, 0o644).unwrap();
+    fs.with_inode_ref(sparse, |inode| {
+        inode.set_owner(1000, 200);
+        Ok(())
+    })
+    .unwrap();
+    for i in 0..40 {
+        fs.write_at_sparse(sparse, b"x", i * 2 * 1024).unwrap();
+    }
+    let usage = fs.user_usage(1000).unwrap();
+    assert_eq!(usage.inodes, 2);
+    assert!(usage.space > 40 * 1024, "{usage:?}");
+
+    // 增量统计与重新扫描的结果一致
+    let report = fs.quota_report().unwrap();
+    fs.enable_quota_tracking().unwrap();
+    assert_eq!(fs.quota_report().unwrap(), report);
+
+    fs.unlink(ROOT_INO, "file").unwrap();
+    fs.remove_dir_all("/dir").unwrap();
+    let report = fs.quota_report().unwrap();
+    assert_eq!(report.users.keys().collect::<Vec<_>>(), [&0]);
+    assert_eq!(report.users[&0], root);
+    assert_eq!(report.groups[&0], root);
+
+    fs.disable_quota_tracking();
+    assert_eq!(fs.quota_report(), None);
+}
+
+#[test]
+fn test_hash_file() {
+    use lwext4_arce::ContentDigest;
+
+    /// 记录全部数据和单次update的最大长度
+    #[derive(Default)]
+    struct Collect {
+        data: Vec<u8>,
+        max_chunk: usize,
+    }
+    impl ContentDigest for Collect {
+        fn update(&mut self, data: &[u8]) {
+            self.max_chunk = self.max_chunk.max(data.len());
+            self.data.extend_from_slice(data);
+        }
+    }
+
+    let path = copy_test_image("hash-file");
+    let bs = 4096usize;
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let ino = fs.create(ROOT_INO, "data", InodeType::RegularFile, 0o644).unwrap();
+    let mut expected: Vec<u8> = (0..3 * bs + 100).map(|i| (i % 251) as u8).collect();
+    fs.write_at(ino, &expected, 0).unwrap();
+    fs.write_at_sparse(ino, b"tail", 8 * bs as u64).unwrap();
+    expected.resize(8 * bs, 0);
+    expected.extend_from_slice(b"tail");
+
+    let mut hasher = Collect::default();
+    assert_eq!(fs.hash_file("/data", &mut hasher).unwrap(), expected.len() as u64);
+    assert!(hasher.data == expected);
+    assert!(hasher.max_chunk <= bs, "{}", hasher.max_chunk);
+
+    // 调用方提供的缓冲区
+    let mut hasher = Collect::default();
+    let mut buf = [0u8; 1000];
+    fs.hash_file_with_buffer("/data", &mut hasher, &mut buf).unwrap();
+    assert!(hasher.data == expected);
+    assert_eq!(hasher.max_chunk, buf.len());
+
+    fs.create(ROOT_INO, "empty", InodeType::RegularFile, 0o644).unwrap();
+    assert_eq!(fs.hash_file("/empty", &mut Collect::default()).unwrap(), 0);
+    assert_eq!(fs.hash_file("/", &mut Collect::default()).unwrap_err().errno(), errno::EISDIR);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_stat_many() {
+    let config = FsConfig {
+        bcache_size: 8,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(open_test_image("stat-many"), config).unwrap();
+    // 哈希顺序的条目，相邻条目的inode多半在不同的inode表块中
+    let page = fs.read_dir_from("/htree", 0, 400).unwrap();
+    let mut inos: Vec<u32> = page
+        .entries
+        .iter()
+        .filter(|e| !e.file_name().is_dot())
+        .map(|e| e.ino)
+        .collect();
+    assert_eq!(inos.len(), 300);
+    inos.push(inos[0]);
+
+    fs.reset_metrics();
+    let attrs = fs.stat_many(&inos).unwrap();
+    let batched = fs.metrics().block_reads;
+    assert!(batched <= 21, "block_reads = {batched}");
+    for (attr, &ino) in attrs.iter().zip(&inos) {
+        assert_eq!(attr.ino, ino);
+        assert_eq!(attr.node_type, InodeType::RegularFile);
+    }
+
+    // 逐个读取时缓存装不下，同一个inode表块被反复读取
+    fs.reset_metrics();
+    let mut attr = FileAttr::default();
+    for &ino in &inos {
+        fs.get_attr(ino, &mut attr).unwrap();
+    }
+    let single = fs.metrics().block_reads;
+    assert!(single > batched * 4, "{single} vs {batched}");
+
+    assert!(fs.stat_many(&[]).unwrap().is_empty());
+    let err = fs.stat_many(&[ROOT_INO, 0]).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_create_remove_dir_all() {
+    let mut fs = mount("dir-all");
+    let before = fs.stat().unwrap();
+
+    let c = fs.create_dir_all("/a/b/c", 0o755).unwrap();
+    assert_eq!(fs.resolve_path("a/b/c").unwrap(), c);
+    // 已存在的目录直接沿用
+    assert_eq!(fs.create_dir_all("/a/b/c/", 0o700).unwrap(), c);
+    let d = fs.create_dir_all("/a/b/d", 0o755).unwrap();
+    assert_ne!(c, d);
+
+    let file = fs
+        .create(c, "f.txt", InodeType::RegularFile, 0o644)
+        .unwrap();
+    fs.write_at(file, &[0x5a; 10000], 0).unwrap();
+    let b = fs.resolve_path("/a/b").unwrap();
+    fs.create(b, "g.txt", InodeType::RegularFile, 0o644)
+        .unwrap();
+    fs.create(d, "link", InodeType::Symlink, 0o777).unwrap();
+
+    let err = fs.create_dir_all("/a/b/c/f.txt/x", 0o755).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+    let err = fs.create_dir_all("/a/b/g.txt", 0o755).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+    let err = fs.remove_dir_all("/a/b/g.txt").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+    let err = fs.remove_dir_all("/").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+
+    fs.remove_dir_all("/a/").unwrap();
+    assert_eq!(
+        fs.resolve_path("/a").unwrap_err().kind(),
+        Ext4ErrorKind::NotFound
+    );
+    assert!(!list_dir(&mut fs, ROOT_INO).contains(&"a".to_string()));
+
+    let after = fs.stat().unwrap();
+    assert_eq!(after.free_inodes_count, before.free_inodes_count);
+    assert_eq!(after.free_blocks_count, before.free_blocks_count);
+
+    // 删除较深的目录树
+    let mut path = String::new();
+    for i in 0..64 {
+        path.push_str(&format!("/d{i}"));
+    }
+    fs.create_dir_all(&path, 0o755).unwrap();
+    fs.remove_dir_all("/d0").unwrap();
+    assert_eq!(
+        fs.stat().unwrap().free_inodes_count,
+        before.free_inodes_count
+    );
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_create_dir_all_rolls_back() {
+    use std::sync::{Arc, Mutex};
+
+    let mut fs = mount("dir-all-rollback");
+    let root = list_dir(&mut fs, ROOT_INO);
+    let before = fs.stat().unwrap();
+    let events = Arc::new(Mutex::new(0));
+    let sink = events.clone();
+    fs.on_change(Some(Box::new(move |_| *sink.lock().unwrap() += 1)));
+
+    // 前两级新建之后才在第三级失败，已创建的目录全部撤销
+    let long = "x".repeat(300);
+    let err = fs.create_dir_all(&format!("/new1/new2/{long}/new3"), 0o755).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NameTooLong);
+    assert_eq!(fs.resolve_path("/new1").unwrap_err().kind(), Ext4ErrorKind::NotFound);
+    assert_eq!(list_dir(&mut fs, ROOT_INO), root);
+    let after = fs.stat().unwrap();
+    assert_eq!(after.free_inodes_count, before.free_inodes_count);
+    assert_eq!(after.free_blocks_count, before.free_blocks_count);
+    assert_eq!(*events.lock().unwrap(), 0);
+
+    // 已存在的前缀不受影响
+    let a = fs.create_dir_all("/a", 0o755).unwrap();
+    *events.lock().unwrap() = 0;
+    assert!(fs.create_dir_all(&format!("/a/b/{long}"), 0o755).is_err());
+    assert_eq!(fs.resolve_path("/a").unwrap(), a);
+    assert!(list_dir(&mut fs, a).is_empty());
+    assert_eq!(*events.lock().unwrap(), 0);
+
+    fs.create_dir_all("/a/b/c", 0o755).unwrap();
+    assert_eq!(*events.lock().unwrap(), 2);
+}
+
+#[test]
+fn test_walk_dir_and_glob() {
+    let mut fs = mount("walk-glob");
+
+    let etc = fs.create_dir_all("/etc", 0o755).unwrap();
+    let sub = fs.create_dir_all("/etc/sub", 0o755).unwrap();
+    let deep = fs.create_dir_all("/etc/sub/deep", 0o755).unwrap();
+    for (dir, name) in [(etc, "a.conf"), (etc, "x.txt"), (sub, "b.conf"), (deep, "c.conf")] {
+        fs.create(dir, name, InodeType::RegularFile, 0o644).unwrap();
+    }
+    // 指向上级目录的符号链接，跟随时会形成环
+    let link = fs.create(sub, "up", InodeType::Symlink, 0o777).unwrap();
+    fs.set_symlink(link, b"..").unwrap();
+
+    let walk = |fs: &mut Fs, depth, policy| {
+        let mut paths: Vec<String> = fs
+            .walk_dir("/etc")
+            .unwrap()
+            .max_depth(depth)
+            .symlinks(policy)
+            .map(|e| e.unwrap().path)
+            .collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(
+        walk(&mut fs, usize::MAX, SymlinkPolicy::Report),
+        [
+            "/etc/a.conf",
+            "/etc/sub",
+            "/etc/sub/b.conf",
+            "/etc/sub/deep",
+            "/etc/sub/deep/c.conf",
+            "/etc/sub/up",
+            "/etc/x.txt",
+        ]
+    );
+    assert_eq!(
+        walk(&mut fs, 1, SymlinkPolicy::Report),
+        ["/etc/a.conf", "/etc/sub", "/etc/x.txt"]
+    );
+    assert!(!walk(&mut fs, usize::MAX, SymlinkPolicy::Skip).contains(&"/etc/sub/up".into()));
+    // 跟随符号链接：/etc/sub/up指向正在遍历的/etc，不再进入
+    let entry = fs
+        .walk_dir("/etc/sub")
+        .unwrap()
+        .symlinks(SymlinkPolicy::Follow)
+        .map(|e| e.unwrap())
+        .find(|e| e.path == "/etc/sub/up")
+        .unwrap();
+    assert_eq!(entry.ino, etc);
+    assert_eq!(entry.inode_type, InodeType::Directory);
+    assert_eq!(
+        walk(&mut fs, usize::MAX, SymlinkPolicy::Follow).len(),
+        walk(&mut fs, usize::MAX, SymlinkPolicy::Report).len()
+    );
+
+    let glob = |fs: &mut Fs, pattern| {
+        let mut paths: Vec<String> = fs.glob(pattern).unwrap().map(|e| e.unwrap().path).collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(
+        glob(&mut fs, "/etc/**/*.conf"),
+        ["/etc/a.conf", "/etc/sub/b.conf", "/etc/sub/deep/c.conf"]
+    );
+    assert_eq!(glob(&mut fs, "/etc/*.conf"), ["/etc/a.conf"]);
+    assert_eq!(glob(&mut fs, "/etc/s?b/[a-c].*"), ["/etc/sub/b.conf"]);
+    assert_eq!(glob(&mut fs, "/etc/sub"), ["/etc/sub"]);
+    assert_eq!(glob(&mut fs, "/*/sub/deep"), ["/etc/sub/deep"]);
+    assert_eq!(glob(&mut fs, "/htree/entry-29[!0-8]"), ["/htree/entry-299"]);
+    assert!(glob(&mut fs, "/etc/*.none").is_empty());
+
+    assert!(glob_match("/a/**/b", "/a/b"));
+    assert!(glob_match("**/*.txt", "/x/y/z.txt"));
+    assert!(!glob_match("/a/*", "/a/b/c"));
+    assert!(glob_match("/a/[]x]", "/a/]"));
+
+    let err = fs.walk_dir("/test.txt").err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotADirectory);
+}
+
+#[test]
+fn test_import_export_tree() {
+    use std::fs::{self, FileTimes};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+    use std::time::UNIX_EPOCH;
+
+    let tmp = std::env::temp_dir().join(format!("lwext4-tree-{}", std::process::id()));
+    let src = tmp.join("src");
+    let dst = tmp.join("dst");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(src.join("sub/deep")).unwrap();
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(src.join("big.bin"), &data).unwrap();
+    fs::write(src.join("sub/conf"), b"key=value\n").unwrap();
+    fs::set_permissions(src.join("sub/conf"), fs::Permissions::from_mode(0o640)).unwrap();
+    fs::hard_link(src.join("sub/conf"), src.join("sub/deep/conf.link")).unwrap();
+    symlink("../big.bin", src.join("sub/big")).unwrap();
+    let mtime = UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_700);
+    fs::File::open(src.join("big.bin"))
+        .unwrap()
+        .set_times(FileTimes::new().set_modified(mtime))
+        .unwrap();
+
+    let mut fs = mount("import-export");
+    fs.import_tree(&src, "/rootfs").unwrap();
+
+    let big = fs.resolve_path("/rootfs/big.bin").unwrap();
+    let mut attr = FileAttr::default();
+    fs.get_attr(big, &mut attr).unwrap();
+    assert_eq!(attr.size, data.len() as u64);
+    assert_eq!(UNIX_EPOCH + attr.mtime, mtime);
+    let mut buf = vec![0; data.len()];
+    assert_eq!(fs.read_at(big, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(buf, data);
+
+    let conf = fs.resolve_path("/rootfs/sub/conf").unwrap();
+    fs.get_attr(conf, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o7777, 0o640);
+    assert_eq!(attr.nlink, 2);
+    assert_eq!(fs.resolve_path("/rootfs/sub/deep/conf.link").unwrap(), conf);
+
+    let link = fs.resolve_path("/rootfs/sub/big").unwrap();
+    fs.get_attr(link, &mut attr).unwrap();
+    assert_eq!(attr.node_type, InodeType::Symlink);
+    let n = fs.read_at(link, &mut buf, 0).unwrap();
+    assert_eq!(&buf[..n], b"../big.bin");
+
+    // 再次导入覆盖已有条目
+    fs::write(src.join("sub/conf"), b"key=other\n").unwrap();
+    fs.import_tree(&src, "/rootfs").unwrap();
+    let conf = fs.resolve_path("/rootfs/sub/conf").unwrap();
+    let n = fs.read_at(conf, &mut buf, 0).unwrap();
+    assert_eq!(&buf[..n], b"key=other\n");
+
+    fs.export_tree("/rootfs", &dst).unwrap();
+    assert_eq!(fs::read(dst.join("big.bin")).unwrap(), data);
+    let meta = fs::metadata(dst.join("big.bin")).unwrap();
+    assert_eq!(meta.modified().unwrap(), mtime);
+    let meta = fs::metadata(dst.join("sub/conf")).unwrap();
+    assert_eq!(meta.mode() & 0o7777, 0o640);
+    assert_eq!(meta.nlink(), 2);
+    assert_eq!(fs::read(dst.join("sub/deep/conf.link")).unwrap(), b"key=other\n");
+    assert_eq!(
+        fs::read_link(dst.join("sub/big")).unwrap().to_str(),
+        Some("../big.bin")
+    );
 
-    println!("✅ Successfully opened filesystem!");
+    // 导出单个文件
+    fs.export_tree("/test.txt", tmp.join("test.txt")).unwrap();
+    assert!(tmp.join("test.txt").is_file());
+
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn test_import_tar() {
+    use lwext4_arce::TarImporter;
+    use std::fs;
+    use std::process::Command;
+
+    let tmp = std::env::temp_dir().join(format!("lwext4-tar-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&tmp);
+    let long = "d".repeat(60);
+    let deep = tmp.join("src").join(&long).join(&long);
+    fs::create_dir_all(&deep).unwrap();
+    let data: Vec<u8> = (0..70_000u32).map(|i| (i % 253) as u8).collect();
+    fs::write(deep.join("data.bin"), &data).unwrap();
+    fs::write(tmp.join("src/hello"), b"hello\n").unwrap();
+    fs::hard_link(tmp.join("src/hello"), tmp.join("src/hello.link")).unwrap();
+    std::os::unix::fs::symlink("hello", tmp.join("src/hello.sym")).unwrap();
+
+    let mut fs = mount("import-tar");
+    for format in ["pax", "gnu"] {
+        let archive = tmp.join(format!("{format}.tar"));
+        let status = Command::new("tar")
+            .arg(format!("--format={format}"))
+            .args(["--mtime=@1500000000", "-cf"])
+            .arg(&archive)
+            .arg("-C")
+            .arg(tmp.join("src"))
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let archive = fs::read(&archive).unwrap();
+        let dest = format!("/{format}");
+
+        // 以很小的块送入，覆盖跨块的头部和数据
+        let mut importer = TarImporter::new(&dest);
+        for chunk in archive.chunks(7) {
+            importer.feed(&mut fs, chunk).unwrap();
+        }
+        let stats = importer.finish(&mut fs).unwrap();
+        assert_eq!(stats.bytes, data.len() as u64 + 6);
+
+        let ino = fs
+            .resolve_path(&format!("{dest}/{long}/{long}/data.bin"))
+            .unwrap();
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!(attr.mtime, Duration::from_secs(1_500_000_000));
+        let mut buf = vec![0; data.len()];
+        assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), data.len());
+        assert_eq!(buf, data);
+
+        let hello = fs.resolve_path(&format!("{dest}/hello")).unwrap();
+        assert_eq!(fs.resolve_path(&format!("{dest}/hello.link")).unwrap(), hello);
+        fs.get_attr(hello, &mut attr).unwrap();
+        assert_eq!(attr.nlink, 2);
+        let sym = fs.resolve_path(&format!("{dest}/hello.sym")).unwrap();
+        fs.get_attr(sym, &mut attr).unwrap();
+        assert_eq!(attr.node_type, InodeType::Symlink);
+        let n = fs.read_at(sym, &mut buf, 0).unwrap();
+        assert_eq!(&buf[..n], b"hello");
+    }
+
+    // 在条目中间截断的归档
+    let archive = fs::read(tmp.join("pax.tar")).unwrap();
+    let mut importer = TarImporter::new("/truncated");
+    importer.feed(&mut fs, &archive[..archive.len() / 2]).unwrap();
+    let err = importer.finish(&mut fs).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+
+    // 字符设备条目带有主次设备号
+    let mut header = [0u8; 512];
+    header[..4].copy_from_slice(b"tty1");
+    header[100..107].copy_from_slice(b"0000620");
+    header[124..135].copy_from_slice(b"00000000000");
+    header[136..147].copy_from_slice(b"00000000000");
+    header[156] = b'3';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[329..336].copy_from_slice(b"0000004");
+    header[337..344].copy_from_slice(b"0000401");
+    let sum: u32 = header.iter().map(|&b| b as u32).sum::<u32>() + 8 * b' ' as u32;
+    header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    let mut importer = TarImporter::new("/devs");
+    importer.feed(&mut fs, &header).unwrap();
+    importer.feed(&mut fs, &[0; 1024]).unwrap();
+    assert_eq!(importer.finish(&mut fs).unwrap().skipped, 0);
+    let tty = fs.resolve_path("/devs/tty1").unwrap();
+    let mut attr = FileAttr::default();
+    fs.get_attr(tty, &mut attr).unwrap();
+    assert_eq!(attr.node_type, InodeType::CharacterDevice);
+    assert_eq!(attr.rdev, lwext4_arce::makedev(4, 0o401));
+    assert_eq!(attr.mode & 0o7777, 0o620);
+
+    // 路径不能跳出目标目录
+    let mut header = [0u8; 512];
+    header[..9].copy_from_slice(b"../escape");
+    header[100..107].copy_from_slice(b"0000644");
+    header[124..135].copy_from_slice(b"00000000000");
+    header[136..147].copy_from_slice(b"00000000000");
+    header[156] = b'0';
+    let sum: u32 = header.iter().map(|&b| b as u32).sum::<u32>() + 8 * b' ' as u32;
+    header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    let mut importer = TarImporter::new("/escape");
+    let err = importer.feed(&mut fs, &header).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("escapes destination"), "{err}");
+
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+/// 构造ustar头部（权限0644，时间戳为0）
+#[cfg(feature = "tar")]
+fn tar_header(name: &str, kind: u8, size: usize) -> [u8; 512] {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+    header[136..147].copy_from_slice(b"00000000000");
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    let sum: u32 = header.iter().map(|&b| b as u32).sum::<u32>() + 8 * b' ' as u32;
+    header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    header
+}
+
+/// 构造PAX记录（"长度 键=值\n"，长度包含自身的位数）
+#[cfg(feature = "tar")]
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let body = key.len() + value.len() + 3;
+    let mut len = body + 1;
+    while len != body + len.to_string().len() {
+        len = body + len.to_string().len();
+    }
+    let mut record = format!("{len} {key}=").into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+#[cfg(all(feature = "tar", feature = "use-rust"))]
+#[test]
+fn test_import_tar_xattrs() {
+    use lwext4_arce::TarImporter;
+
+    // VFS_CAP_REVISION_2 + 有效位，permitted = CAP_NET_RAW
+    let mut capability = Vec::new();
+    for word in [0x0200_0001u32, 1 << 13, 0, 0, 0] {
+        capability.extend_from_slice(&word.to_le_bytes());
+    }
+    let mut pax = pax_record("SCHILY.xattr.security.capability", &capability);
+    pax.extend(pax_record("SCHILY.xattr.user.origin", b"tar"));
+    pax.extend(pax_record("SCHILY.xattr.bogus.name", b"x"));
+
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&tar_header("PaxHeaders/ping", b'x', pax.len()));
+    archive.extend_from_slice(&pax);
+    archive.resize(archive.len().next_multiple_of(512), 0);
+    archive.extend_from_slice(&tar_header("ping", b'0', 4));
+    archive.extend_from_slice(b"ELF\n");
+    archive.resize(archive.len().next_multiple_of(512) + 1024, 0);
+
+    let path = copy_test_image("tar-xattrs");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut importer = TarImporter::new("/bin");
+    importer.feed(&mut fs, &archive).unwrap();
+    let stats = importer.finish(&mut fs).unwrap();
+    assert_eq!((stats.entries, stats.xattrs, stats.xattrs_skipped), (1, 2, 1));
+
+    let ping = fs.resolve_path("/bin/ping").unwrap();
+    assert_eq!(fs.get_xattr(ping, "security.capability").unwrap().unwrap(), capability);
+    assert_eq!(fs.get_xattr(ping, "user.origin").unwrap().unwrap(), b"tar");
+    let mut buf = [0u8; 8];
+    assert_eq!(fs.read_at(ping, &mut buf, 0).unwrap(), 4);
+    drop(fs);
+
+    assert_fsck_clean(&path);
+    let out = common::e2fs::debugfs(&path, "ea_get /bin/ping user.origin");
+    assert!(String::from_utf8_lossy(&out).contains("tar"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sparse_write() {
+    let mut fs = mount("sparse-write");
+    let bs = 4096usize;
+    let root = ROOT_INO;
+
+    // 数据块 + 10个全零块 + 跨块的非零数据
+    let mut data = vec![0u8; 13 * bs];
+    data[..bs].fill(0xaa);
+    data[11 * bs + 100..12 * bs + 100].fill(0x55);
+    let ino = fs
+        .create(root, "sparse.img", InodeType::RegularFile, 0o644)
+        .unwrap();
+    assert_eq!(fs.write_at_sparse(ino, &data, 0).unwrap(), data.len());
+
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, data.len() as u64);
+    // 只分配了第0、11、12块
+    assert_eq!(attr.blocks, 3 * bs as u64 / 512);
+    let mut buf = vec![0xffu8; data.len()];
+    assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(buf, data);
+
+    // 向空洞中写入部分块：块内其余部分读出为零
+    fs.write_at_sparse(ino, b"hole", 5 * bs as u64 + 10).unwrap();
+    data[5 * bs + 10..5 * bs + 14].copy_from_slice(b"hole");
+    fs.write_at(ino, b"dense", 7 * bs as u64 + 4000).unwrap();
+    data[7 * bs + 4000..7 * bs + 4005].copy_from_slice(b"dense");
+    // 已分配的块写入零时仍然覆盖
+    fs.write_at_sparse(ino, &vec![0; bs], 0).unwrap();
+    data[..bs].fill(0);
+    fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(buf, data);
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.blocks, 5 * bs as u64 / 512);
+
+    // 在文件末尾之后写入：中间部分为空洞
+    fs.write_at_sparse(ino, b"tail", 100 * bs as u64).unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, 100 * bs as u64 + 4);
+    // 第五个extent放不进inode，extent树增加一个叶子块
+    assert_eq!(attr.blocks, 7 * bs as u64 / 512);
+    let mut tail = vec![0xffu8; 2 * bs];
+    fs.read_at(ino, &mut tail, 98 * bs as u64).unwrap();
+    assert!(tail[..2 * bs].iter().all(|&b| b == 0));
+    let mut tail = [0u8; 4];
+    fs.read_at(ino, &mut tail, 100 * bs as u64).unwrap();
+    assert_eq!(&tail, b"tail");
+
+    // 全零写入只扩展文件大小
+    fs.write_at_sparse(ino, &vec![0; 3 * bs], 200 * bs as u64).unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, 203 * bs as u64);
+    assert_eq!(attr.blocks, 7 * bs as u64 / 512);
+    let mut rest = vec![0xffu8; bs];
+    fs.read_at(ino, &mut rest, 100 * bs as u64).unwrap();
+    assert_eq!(&rest[..4], b"tail");
+    assert!(rest[4..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_read_holes() {
+    let path = copy_test_image("read-holes");
+    let bs = 4096usize;
+    let ino = {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let ino = fs.create(ROOT_INO, "holes", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(ino, &vec![0xaa; bs], 0).unwrap();
+        fs.write_at_sparse(ino, b"end", 4096 * bs as u64).unwrap();
+        fs.flush().unwrap();
+        ino
+    };
+
+    // 空洞读出为零，且不读取设备（先读一个字节使inode和extent树进入缓存）
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut buf = vec![0xffu8; 1024 * bs];
+    fs.read_at(ino, &mut buf[..1], 0).unwrap();
+    fs.reset_metrics();
+    assert_eq!(fs.read_at(ino, &mut buf, 1000 * bs as u64 + 7).unwrap(), buf.len());
+    assert!(buf.iter().all(|&b| b == 0));
+    assert_eq!(fs.metrics().block_reads, 0);
+    let mut tail = [0xffu8; 2 * 4096 + 3];
+    assert_eq!(fs.read_at(ino, &mut tail, 4094 * bs as u64).unwrap(), tail.len());
+    assert!(tail[..2 * bs].iter().all(|&b| b == 0));
+    assert_eq!(&tail[2 * bs..], b"end");
+    drop(fs);
+
+    // 未写入（unwritten）的extent同样视为空洞，即使物理块中有数据
+    patch_inode(&path, ino, |raw| {
+        let len = u16::from_le_bytes([raw[0x34], raw[0x35]]);
+        raw[0x34..0x36].copy_from_slice(&(len | 0x8000).to_le_bytes());
+    });
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut head = vec![0xffu8; bs + 10];
+    assert_eq!(fs.read_at(ino, &mut head, 0).unwrap(), head.len());
+    assert!(head.iter().all(|&b| b == 0));
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_large_file() {
+    use std::process::Command;
+
+    let path = std::env::temp_dir().join(format!("lwext4-large-file-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(32 << 20).unwrap();
+    let made = Command::new("mkfs.ext4")
+        .args(["-q", "-F", "-O", "^large_file,^huge_file", path])
+        .status()
+        .is_ok_and(|status| status.success());
+    if !made {
+        std::fs::remove_file(path).unwrap();
+        return;
+    }
+    let ro_compat = |path: &str| {
+        let image = std::fs::read(path).unwrap();
+        u32::from_le_bytes(image[1024 + 0x64..1024 + 0x68].try_into().unwrap())
+    };
+    assert_eq!(ro_compat(path) & 0x2, 0);
+
+    // 超过4 GiB的文件：大小的高32位和large_file特性
+    let big = 5u64 << 30;
+    let ino = {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let ino = fs.create(ROOT_INO, "big", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(ino, b"head", 0).unwrap();
+        assert_eq!(fs.write_at_sparse(ino, b"tail", big).unwrap(), 4);
+        ino
+    };
+    assert_ne!(ro_compat(path) & 0x2, 0);
+    assert_fsck_clean(path);
+
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, big + 4);
+    let mut buf = [0u8; 4];
+    fs.read_at(ino, &mut buf, big).unwrap();
+    assert_eq!(&buf, b"tail");
+    fs.write_at(ino, b"more", big + 4).unwrap();
+    fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(&buf, b"head");
+
+    // 没有huge_file时i_blocks为32位，4K块的文件最大约2 TiB
+    let err = fs.write_at_sparse(ino, &[0], 3 << 40).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::FileTooLarge);
+    assert_eq!(fs.write_at(ino, b"x", 2 << 40).unwrap_err().kind(), Ext4ErrorKind::FileTooLarge);
+    fs.write_at_sparse(ino, &[0], (1 << 40) - 1).unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, 1 << 40);
+    fs.set_len(ino, 4096).unwrap();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, 4096);
+    drop(fs);
+    assert_fsck_clean(path);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_dir_nlink() {
+    const LINK_MAX: u16 = 65000;
+    let path = copy_test_image("dir-nlink");
+    let set_links = |ino: u32, links: u16| {
+        patch_inode(&path, ino, |raw| raw[0x1A..0x1C].copy_from_slice(&links.to_le_bytes()))
+    };
+    let nlink = |fs: &mut Fs, ino: u32| {
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        attr.nlink
+    };
+    let (big, full, file) = {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let big = fs.lookup(ROOT_INO, "htree").unwrap().entry().ino();
+        let full = fs.create(ROOT_INO, "full", InodeType::Directory, 0o755).unwrap();
+        let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+        (big, full, file)
+    };
+    // 模拟已有约65000个子目录
+    set_links(big, LINK_MAX - 1);
+    set_links(full, LINK_MAX);
+    set_links(file, LINK_MAX);
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    // 目录超过上限后链接数记为1，之后不再计数
+    let a = fs.create(big, "a", InodeType::Directory, 0o755).unwrap();
+    assert_eq!(nlink(&mut fs, big), LINK_MAX as u64);
+    fs.create(big, "b", InodeType::Directory, 0o755).unwrap();
+    assert_eq!(nlink(&mut fs, big), 1);
+    fs.create(big, "c", InodeType::Directory, 0o755).unwrap();
+    fs.unlink(big, "b").unwrap();
+    fs.rename(big, "c", ROOT_INO, "c").unwrap();
+    assert_eq!(nlink(&mut fs, big), 1);
+    assert_eq!(nlink(&mut fs, a), 2);
+    let c = fs.lookup(ROOT_INO, "c").unwrap().entry().ino();
+    assert_eq!(nlink(&mut fs, c), 2);
+    fs.rename(ROOT_INO, "c", big, "c").unwrap();
+    assert_eq!(nlink(&mut fs, c), 2);
+    assert_eq!(nlink(&mut fs, big), 1);
+
+    // 链接数为1的目录也可以删除
+    fs.unlink(big, "a").unwrap();
+    fs.unlink(big, "c").unwrap();
+    assert_eq!(list_dir(&mut fs, big).len(), 300);
+    assert_eq!(nlink(&mut fs, big), 1);
+
+    fs.create(big, "e", InodeType::Directory, 0o755).unwrap();
+    drop(fs);
+
+    // 没有dir_nlink特性时目录受65000的上限限制，普通文件总是受限制
+    patch_superblock(&path, |sb| sb[0x64] &= !0x20);
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let err = fs.create(full, "d", InodeType::Directory, 0o755).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::TooManyLinks);
+    let err = fs.rename(big, "e", full, "d").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::TooManyLinks);
+    fs.create(full, "f", InodeType::RegularFile, 0o644).unwrap();
+    let err = fs.link(ROOT_INO, "file2", file).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::TooManyLinks);
+    assert!(fs.lookup(ROOT_INO, "file2").is_err());
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_large_dir() {
+    use std::process::Command;
+
+    // mke2fs创建的large_dir镜像，e2fsck -D为目录建立htree索引
+    let tmp = std::env::temp_dir().join(format!("lwext4-large-dir-{}", std::process::id()));
+    let src = tmp.join("src/big");
+    std::fs::create_dir_all(&src).unwrap();
+    let long = "n".repeat(200);
+    for i in 0..600 {
+        std::fs::File::create(src.join(format!("{long}{i:04}"))).unwrap();
+    }
+    let path = tmp.join("large-dir.ext4");
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(32 << 20).unwrap();
+    let made = Command::new("mkfs.ext4")
+        .args(["-q", "-F", "-b", "1024", "-O", "large_dir", "-d", tmp.join("src").to_str().unwrap(), path])
+        .status()
+        .is_ok_and(|status| status.success())
+        && Command::new("e2fsck").args(["-fyD", path]).output().is_ok_and(|out| out.status.code().unwrap_or(8) < 4);
+    if !made {
+        std::fs::remove_dir_all(&tmp).unwrap();
+        return;
+    }
+
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        assert!(!fs.is_read_only());
+        let big = fs.lookup(ROOT_INO, "big").unwrap().entry().ino();
+        assert_eq!(list_dir(&mut fs, big).len(), 600);
+        fs.lookup(big, &format!("{long}0599")).unwrap();
+        for i in 600..700 {
+            fs.create(big, &format!("{long}{i:04}"), InodeType::RegularFile, 0o644).unwrap();
+        }
+        for i in (0..700).step_by(14) {
+            fs.unlink(big, &format!("{long}{i:04}")).unwrap();
+        }
+    }
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let big = fs.lookup(ROOT_INO, "big").unwrap().entry().ino();
+    assert_eq!(list_dir(&mut fs, big).len(), 650);
+    fs.lookup(big, &format!("{long}0699")).unwrap();
+    assert!(fs.lookup(big, &format!("{long}0014")).is_err());
+    drop(fs);
+    assert_fsck_clean(path);
+    std::fs::remove_dir_all(&tmp).unwrap();
 }
 
-// 更多测试可以在这里添加
-// #[test]
-// fn test_read_superblock() { ... }
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_htree_growth() {
+    use common::e2fs::{debugfs, mke2fs};
+
+    let path = std::env::temp_dir().join(format!("lwext4-htree-growth-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(16 << 20).unwrap();
+    if !mke2fs(path, 16 << 10, &["-b", "1024", "-O", "metadata_csum,^large_dir"]) {
+        return;
+    }
+    let indexed = |path: &str, dir: &str| {
+        let stat = String::from_utf8(debugfs(path, &format!("stat {dir}"))).unwrap();
+        let flags = stat.split("Flags: 0x").nth(1).unwrap().split_whitespace().next().unwrap();
+        u32::from_str_radix(flags, 16).unwrap() & 0x1000 != 0
+    };
+
+    // 长文件名使叶子块很快分裂，根的索引项用完后增加一层并分裂中间节点
+    let name = |i: usize| format!("{}-{i:05}", "n".repeat(100));
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let small = fs.create(ROOT_INO, "small", InodeType::Directory, 0o755).unwrap();
+        for i in 0..5 {
+            fs.create(small, &name(i), InodeType::RegularFile, 0o644).unwrap();
+        }
+        let big = fs.create(ROOT_INO, "big", InodeType::Directory, 0o755).unwrap();
+        for i in 0..1200 {
+            fs.create(big, &name(i), InodeType::RegularFile, 0o644).unwrap();
+        }
+        for i in (0..1200).step_by(7) {
+            fs.unlink(big, &name(i)).unwrap();
+        }
+        assert_eq!(fs.lookup(big, "..").unwrap().entry().ino(), ROOT_INO);
+    }
+    assert_fsck_clean(path);
+    assert!(indexed(path, "/big"));
+    assert!(!indexed(path, "/small"));
+
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let small = fs.lookup(ROOT_INO, "small").unwrap().entry().ino();
+    let mut attr = FileAttr::default();
+    fs.get_attr(small, &mut attr).unwrap();
+    assert_eq!(attr.size, 1024);
+    let big = fs.lookup(ROOT_INO, "big").unwrap().entry().ino();
+    let mut names = list_dir(&mut fs, big);
+    names.sort_unstable();
+    let expected: Vec<_> = (0..1200).filter(|i| i % 7 != 0).map(name).collect();
+    assert_eq!(names, expected);
+    for i in [1, 599, 1199] {
+        fs.lookup(big, &name(i)).unwrap();
+    }
+    assert!(fs.lookup(big, &name(7)).is_err());
+    let sub = fs.create(big, "sub", InodeType::Directory, 0o755).unwrap();
+    fs.rename(big, &name(1), sub, "moved").unwrap();
+    fs.lookup(sub, "moved").unwrap();
+    drop(fs);
+    assert_fsck_clean(path);
+    std::fs::remove_file(path).unwrap();
+}
 
 #[test]
-fn test_new_ext4filesystem() {
-    let test_image = "/home/c20h30o2/files/lwext4-rust/lwext4-rust/test-images/test.ext4";
-    let device = FileBlockDevice::open(test_image).expect("Failed to open test image");
-    
+#[cfg(feature = "use-rust")]
+fn test_large_dir_three_levels() {
+    use common::e2fs::{debugfs, mke2fs};
+
+    let path = std::env::temp_dir().join(format!("lwext4-large-dir-3-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(48 << 20).unwrap();
+    if !mke2fs(path, 48 << 10, &["-b", "1024", "-N", "256", "-O", "metadata_csum,large_dir"]) {
+        return;
+    }
+    let levels = |path: &str| {
+        let htree = String::from_utf8(debugfs(path, "htree /big")).unwrap();
+        htree.split("Indirect levels: ").nth(1).unwrap().split_whitespace().next().unwrap().parse::<u32>().unwrap()
+    };
+
+    // 最长的文件名使每个叶子块只能放3项，用硬链接填满根和中间节点后索引增加到三层
+    const COUNT: usize = 24000;
+    let name = |i: usize| format!("{}-{i:05}", "n".repeat(248));
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+        let big = fs.create(ROOT_INO, "big", InodeType::Directory, 0o755).unwrap();
+        for i in 0..COUNT {
+            fs.link(big, &name(i), file).unwrap();
+        }
+    }
+    assert_fsck_clean(path);
+    assert_eq!(levels(path), 2);
+
+    // 三层索引的查找、插入（分裂各层节点）和删除
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let file = fs.lookup(ROOT_INO, "file").unwrap().entry().ino();
+        let big = fs.lookup(ROOT_INO, "big").unwrap().entry().ino();
+        for i in (0..COUNT).step_by(997) {
+            assert_eq!(fs.lookup(big, &name(i)).unwrap().entry().ino(), file);
+        }
+        for i in COUNT..COUNT + 2000 {
+            fs.link(big, &name(i), file).unwrap();
+        }
+        for i in (0..COUNT + 2000).step_by(5) {
+            fs.unlink(big, &name(i)).unwrap();
+        }
+        assert!(fs.lookup(big, &name(COUNT)).is_err());
+        fs.lookup(big, &name(COUNT + 1999)).unwrap();
+        assert_eq!(list_dir(&mut fs, big).len(), (COUNT + 2000) / 5 * 4);
+    }
+    assert_fsck_clean(path);
+    assert_eq!(levels(path), 2);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_compact_dir() {
+    use common::e2fs::{debugfs, mke2fs};
+
+    let path = std::env::temp_dir().join(format!("lwext4-compact-dir-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(16 << 20).unwrap();
+    if !mke2fs(path, 16 << 10, &["-b", "1024", "-O", "metadata_csum"]) {
+        return;
+    }
+    let name = |i: usize| format!("{}-{i:05}", "n".repeat(100));
+    let dir_size = |fs: &mut Fs, ino| {
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        attr.size
+    };
+
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    for i in 0..1200 {
+        fs.create(dir, &name(i), InodeType::RegularFile, 0o644).unwrap();
+    }
+    // 分裂后的叶子块约半满，填满后需要两层索引
+    assert!(fs.compact_dir(dir).unwrap() > 0);
+    for i in (0..1200).step_by(97) {
+        fs.lookup(dir, name(i).as_str()).unwrap();
+    }
+    let free_before = fs.stat().unwrap().free_blocks_count;
+    let blocks_before = dir_size(&mut fs, dir) / 1024;
+    // 删除后目录块不会被回收
+    for i in (0..1200).filter(|i| i % 25 != 0) {
+        fs.unlink(dir, &name(i)).unwrap();
+    }
+    assert_eq!(dir_size(&mut fs, dir) / 1024, blocks_before);
+
+    // 剩余48项重建为htree
+    let released = fs.compact_dir(dir).unwrap() as u64;
+    assert!(released > 0);
+    assert_eq!(dir_size(&mut fs, dir) / 1024, blocks_before - released);
+    assert!(fs.stat().unwrap().free_blocks_count >= free_before + released);
+    assert_eq!(fs.compact_dir(dir).unwrap(), 0);
+    let expected: Vec<_> = (0..1200).step_by(25).map(name).collect();
+    let mut names = list_dir(&mut fs, dir);
+    names.sort_unstable();
+    assert_eq!(names, expected);
+    for n in &expected {
+        fs.lookup(dir, n.as_str()).unwrap();
+    }
+    // 重建后的索引可以继续插入
+    for i in 1200..1300 {
+        fs.create(dir, &name(i), InodeType::RegularFile, 0o644).unwrap();
+    }
+    let file = fs.lookup(dir, name(0).as_str()).unwrap().entry().ino();
+    assert_eq!(fs.compact_dir(file).unwrap_err().errno(), errno::ENOTDIR);
+    drop(fs);
+    assert_fsck_clean(path);
+    let stat = String::from_utf8(debugfs(path, "stat /dir")).unwrap();
+    assert!(stat.contains("Flags: 0x81000"), "{stat}");
+
+    // 只剩几项时改为单个块的线性目录
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    for i in (0..1300).filter(|i| *i >= 1200 || i % 25 == 0).skip(3) {
+        fs.unlink(dir, &name(i)).unwrap();
+    }
+    fs.compact_dir(dir).unwrap();
+    assert_eq!(dir_size(&mut fs, dir), 1024);
+    assert_eq!(list_dir(&mut fs, dir).len(), 3);
+    assert_eq!(fs.lookup(dir, "..").unwrap().entry().ino(), ROOT_INO);
+    drop(fs);
+    assert_fsck_clean(path);
+    let stat = String::from_utf8(debugfs(path, "stat /dir")).unwrap();
+    assert!(stat.contains("Flags: 0x80000"), "{stat}");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_symlink_targets() {
+    use std::process::Command;
+
+    let path = copy_test_image("symlink-targets");
+    let long = "d/".repeat(50) + "target";
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let fast = fs.create(ROOT_INO, "fast", InodeType::Symlink, 0o777).unwrap();
+        fs.set_symlink(fast, b"short/target").unwrap();
+        let slow = fs.create(ROOT_INO, "slow", InodeType::Symlink, 0o777).unwrap();
+        fs.set_symlink(slow, long.as_bytes()).unwrap();
+        let edge = fs.create(ROOT_INO, "edge", InodeType::Symlink, 0o777).unwrap();
+        fs.set_symlink(edge, &[b'e'; 60]).unwrap();
+
+        let too_long = fs.create(ROOT_INO, "too-long", InodeType::Symlink, 0o777).unwrap();
+        let err = fs.set_symlink(too_long, &[b'x'; 4097]).unwrap_err();
+        assert_eq!(err.kind(), Ext4ErrorKind::NameTooLong);
+        fs.unlink(ROOT_INO, "too-long").unwrap();
+        let file = fs.create(ROOT_INO, "plain", InodeType::RegularFile, 0o644).unwrap();
+        assert_eq!(fs.read_link(file).unwrap_err().kind(), Ext4ErrorKind::InvalidInput);
+    }
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let fast = fs.lookup(ROOT_INO, "fast").unwrap().entry().ino();
+    let slow = fs.lookup(ROOT_INO, "slow").unwrap().entry().ino();
+    let edge = fs.lookup(ROOT_INO, "edge").unwrap().entry().ino();
+    assert_eq!(fs.read_link(fast).unwrap(), b"short/target");
+    assert_eq!(fs.read_link(slow).unwrap(), long.as_bytes());
+    assert_eq!(fs.read_link(edge).unwrap(), [b'e'; 60]);
+    let mut attr = FileAttr::default();
+    fs.get_attr(fast, &mut attr).unwrap();
+    assert_eq!((attr.size, attr.blocks), (12, 0));
+    fs.get_attr(edge, &mut attr).unwrap();
+    assert_eq!((attr.size, attr.blocks), (60, 8));
+    // 从中间读取快速符号链接
+    let mut buf = [0u8; 16];
+    assert_eq!(fs.read_at(fast, &mut buf, 6).unwrap(), 6);
+    assert_eq!(&buf[..6], b"target");
+    drop(fs);
+    assert_fsck_clean(&path);
+
+    // 长度小于60、但存放在数据块中的符号链接
+    patch_inode(&path, slow, |raw| raw[0x04..0x08].copy_from_slice(&10u32.to_le_bytes()));
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert_eq!(fs.read_link(slow).unwrap(), &long.as_bytes()[..10]);
+    drop(fs);
+
+    // 不启用extent时数据块使用间接块映射
+    let tmp = std::env::temp_dir().join(format!("lwext4-symlink-noext-{}", std::process::id()));
+    let src = tmp.join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::os::unix::fs::symlink(&long, src.join("slow")).unwrap();
+    std::os::unix::fs::symlink("fast", src.join("fast")).unwrap();
+    let image = tmp.join("noext.ext4");
+    let image = image.to_str().unwrap();
+    std::fs::File::create(image).unwrap().set_len(8 << 20).unwrap();
+    let made = Command::new("mkfs.ext4")
+        .args(["-q", "-F", "-O", "^extent,^64bit", "-d", src.to_str().unwrap(), image])
+        .status()
+        .is_ok_and(|status| status.success());
+    if made {
+        {
+            let mut fs = Fs::new(FileBlockDevice::open(image).unwrap(), FsConfig::default()).unwrap();
+            let slow = fs.lookup(ROOT_INO, "slow").unwrap().entry().ino();
+            assert_eq!(fs.read_link(slow).unwrap(), long.as_bytes());
+            let fast = fs.lookup(ROOT_INO, "fast").unwrap().entry().ino();
+            assert_eq!(fs.read_link(fast).unwrap(), b"fast");
+            let new = fs.create(ROOT_INO, "new", InodeType::Symlink, 0o777).unwrap();
+            fs.set_symlink(new, long.as_bytes()).unwrap();
+        }
+        let mut fs = Fs::new(FileBlockDevice::open(image).unwrap(), FsConfig::default()).unwrap();
+        let new = fs.lookup(ROOT_INO, "new").unwrap().entry().ino();
+        assert_eq!(fs.read_link(new).unwrap(), long.as_bytes());
+        drop(fs);
+        assert_fsck_clean(image);
+    }
+    std::fs::remove_dir_all(&tmp).unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_mknod() {
+    use lwext4_arce::makedev;
+    use std::process::Command;
+
+    // glibc的makedev(300, 70000)
+    assert_eq!(makedev(300, 70000), 0x1111_2c70);
+    let path = copy_test_image("mknod");
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        fs.create_dir_all("/dev", 0o755).unwrap();
+        fs.mknod("/dev/null", InodeType::CharacterDevice, 0o666, makedev(1, 3)).unwrap();
+        fs.mknod("/dev/sda", InodeType::BlockDevice, 0o660, makedev(8, 0)).unwrap();
+        // 主次设备号超过8位时使用新格式
+        fs.mknod("/dev/big", InodeType::CharacterDevice, 0o600, makedev(300, 70000)).unwrap();
+        fs.mknod("/dev/fifo", InodeType::Fifo, 0o644, 0).unwrap();
+        fs.mknod("/dev/sock", InodeType::Socket, 0o644, 0).unwrap();
+
+        let err = fs.mknod("/dev/huge", InodeType::CharacterDevice, 0o600, makedev(0x1000, 0));
+        assert_eq!(err.unwrap_err().kind(), Ext4ErrorKind::InvalidInput);
+        let err = fs.mknod("/dev/file", InodeType::RegularFile, 0o644, 0);
+        assert_eq!(err.unwrap_err().kind(), Ext4ErrorKind::InvalidInput);
+        let err = fs.mknod("/dev/null", InodeType::CharacterDevice, 0o666, makedev(1, 3));
+        assert_eq!(err.unwrap_err().kind(), Ext4ErrorKind::AlreadyExists);
+        let dev = fs.resolve_path("/dev").unwrap();
+        assert_eq!(list_dir(&mut fs, dev).len(), 5);
+    }
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut attr = FileAttr::default();
+    for (name, ty, rdev, mode) in [
+        ("null", InodeType::CharacterDevice, makedev(1, 3), 0o666),
+        ("sda", InodeType::BlockDevice, makedev(8, 0), 0o660),
+        ("big", InodeType::CharacterDevice, makedev(300, 70000), 0o600),
+        ("fifo", InodeType::Fifo, 0, 0o644),
+        ("sock", InodeType::Socket, 0, 0o644),
+    ] {
+        let ino = fs.resolve_path(&format!("/dev/{name}")).unwrap();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!((attr.node_type, attr.rdev, attr.mode & 0o7777), (ty, rdev, mode), "{name}");
+        assert_eq!((attr.size, attr.blocks), (0, 0));
+    }
+    drop(fs);
+    assert_fsck_clean(&path);
+
+    // 与debugfs的设备号编码互通
+    let Ok(out) = Command::new("debugfs").args(["-R", "stat /dev/big", &path]).output() else {
+        std::fs::remove_file(&path).unwrap();
+        return;
+    };
+    let out = String::from_utf8_lossy(&out.stdout);
+    assert!(out.contains("Device major/minor number: 300:70000"), "{out}");
+    let made = Command::new("debugfs")
+        .args(["-w", "-R", "mknod loop b 7 300", &path])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if made {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let ino = fs.resolve_path("/loop").unwrap();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!((attr.node_type, attr.rdev), (InodeType::BlockDevice, makedev(7, 300)));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_name_too_long() {
+    let path = copy_test_image("name-too-long");
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let max = "n".repeat(255);
+        let long = "n".repeat(256);
+        let ino = fs
+            .create(ROOT_INO, &max, InodeType::RegularFile, 0o644)
+            .unwrap();
+        assert_eq!(fs.lookup(ROOT_INO, &max).unwrap().entry().ino(), ino);
+
+        assert_eq!(
+            fs.create(ROOT_INO, &long, InodeType::RegularFile, 0o644)
+                .err()
+                .unwrap()
+                .kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        assert_eq!(
+            fs.lookup(ROOT_INO, &long).err().unwrap().kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        assert_eq!(
+            fs.resolve_path(&format!("/{long}")).err().unwrap().kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        assert_eq!(
+            fs.link(ROOT_INO, &long, ino).err().unwrap().kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        assert_eq!(
+            fs.rename(ROOT_INO, &max, ROOT_INO, &long)
+                .err()
+                .unwrap()
+                .kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        assert_eq!(
+            fs.create_dir_all(&format!("/a/{long}/b"), 0o755)
+                .err()
+                .unwrap()
+                .kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        // 失败的操作不留下条目
+        assert_eq!(fs.lookup(ROOT_INO, &max).unwrap().entry().ino(), ino);
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!(attr.nlink, 1);
+
+        // 默认路径上限为PATH_MAX
+        let deep = "/d".repeat(lwext4_arce::PATH_MAX / 2 + 1);
+        assert_eq!(
+            fs.resolve_path(&deep).err().unwrap().kind(),
+            Ext4ErrorKind::NameTooLong
+        );
+        fs.unlink(ROOT_INO, &max).unwrap();
+    }
+
+    let config = FsConfig {
+        path_max: 15,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    fs.create_dir_all("/0123456789/abc", 0o755).unwrap();
+    fs.resolve_path("/0123456789/abc").unwrap();
+    assert_eq!(
+        fs.resolve_path("/0123456789/abcd").err().unwrap().kind(),
+        Ext4ErrorKind::NameTooLong
+    );
+    assert_eq!(
+        fs.create_dir_all("/0123456789/abcd", 0o755)
+            .err()
+            .unwrap()
+            .kind(),
+        Ext4ErrorKind::NameTooLong
+    );
+    assert_eq!(
+        fs.mknod("/0123456789/fifo", InodeType::Fifo, 0o644, 0)
+            .err()
+            .unwrap()
+            .kind(),
+        Ext4ErrorKind::NameTooLong
+    );
+    assert_eq!(
+        fs.remove_dir_all("/0123456789/abcd").err().unwrap().kind(),
+        Ext4ErrorKind::NameTooLong
+    );
+    let dir = fs.resolve_path("/0123456789").unwrap();
+    assert_eq!(list_dir(&mut fs, dir), ["abc"]);
+    fs.remove_dir_all("/0123456789").unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_raw_names() {
+    use lwext4_arce::FileName;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let raw = FileName::new(b"caf\xe9");
+    assert_eq!(raw.to_str(), None);
+    assert_eq!(raw.to_string(), "caf\u{fffd}");
+    assert_eq!(format!("{raw:?}"), "\"caf\\xe9\"");
+    assert_eq!(FileName::from("café").to_str(), Some("café"));
+
+    let path = copy_test_image("raw-names");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let dir = fs.create(ROOT_INO, "raw", InodeType::Directory, 0o755).unwrap();
+    let ino = fs.create(dir, raw, InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(ino, b"latin-1", 0).unwrap();
+    assert_eq!(fs.lookup(dir, b"caf\xe9").unwrap().entry().ino(), ino);
+    // 与UTF-8的"café"是不同的名称
+    let err = fs.lookup(dir, "café").err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotFound);
+    let err = fs.lookup(dir, b"\xff\xfemissing").err().unwrap();
+    assert_eq!(err.path_segment(), Some("\u{fffd}\u{fffd}missing"));
+
+    fs.link(dir, b"link\x80", ino).unwrap();
+    fs.rename(dir, raw, dir, b"moved\xe9").unwrap();
+    let mut names = Vec::new();
+    let mut reader = fs.read_dir(dir, 0).unwrap();
+    while let Some(entry) = reader.current() {
+        if !entry.file_name().is_dot() {
+            names.push((entry.name().to_vec(), entry.ino(), entry.file_name().to_string()));
+        }
+        reader.step().unwrap();
+    }
+    drop(reader);
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            (b"link\x80".to_vec(), ino, "link\u{fffd}".to_string()),
+            (b"moved\xe9".to_vec(), ino, "moved\u{fffd}".to_string()),
+        ]
+    );
+    let page = fs.read_dir_from("/raw", 0, 16).unwrap();
+    assert!(page.entries.iter().any(|e| e.file_name() == FileName::new(b"moved\xe9")));
+    fs.unlink(dir, b"link\x80").unwrap();
+
+    // 宿主上的非UTF-8文件名原样导入和导出
+    let tmp = std::env::temp_dir().join(format!("lwext4-raw-names-{}", std::process::id()));
+    let src = tmp.join("src");
+    std::fs::create_dir_all(src.join(OsStr::from_bytes(b"dir\xfe"))).unwrap();
+    std::fs::write(src.join(OsStr::from_bytes(b"dir\xfe/file\xff")), b"host").unwrap();
+    fs.import_tree(&src, "/imported").unwrap();
+    let imported = fs.resolve_path("/imported").unwrap();
+    let sub = fs.lookup(imported, b"dir\xfe").unwrap().entry().ino();
+    let file = fs.lookup(sub, b"file\xff").unwrap().entry().ino();
+    let mut buf = [0u8; 4];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), 4);
+    assert_eq!(&buf, b"host");
+    fs.export_tree("/imported", tmp.join("dst")).unwrap();
+    assert_eq!(
+        std::fs::read(tmp.join("dst").join(OsStr::from_bytes(b"dir\xfe/file\xff"))).unwrap(),
+        b"host"
+    );
+
+    // 递归删除含非UTF-8名称的目录
+    fs.remove_dir_all("/imported").unwrap();
+    fs.remove_dir_all("/raw").unwrap();
+    assert_eq!(fs.lookup(ROOT_INO, "raw").err().unwrap().kind(), Ext4ErrorKind::NotFound);
+    drop(fs);
+    assert_fsck_clean(&path);
+    std::fs::remove_dir_all(&tmp).unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_defragment() {
+    let mut fs = mount("defragment");
+    let bs = 4096usize;
+
+    // 两个文件交替追加，每块都与另一个文件的块相邻
+    let a = fs
+        .create(ROOT_INO, "frag", InodeType::RegularFile, 0o644)
+        .unwrap();
+    let b = fs
+        .create(ROOT_INO, "other", InodeType::RegularFile, 0o644)
+        .unwrap();
+    let mut data = Vec::new();
+    for i in 0..40u8 {
+        let block = vec![i; bs];
+        fs.write_at(a, &block, data.len() as u64).unwrap();
+        fs.write_at(b, &block, data.len() as u64).unwrap();
+        data.extend_from_slice(&block);
+    }
+    // 末尾一个空洞和半块数据
+    fs.write_at_sparse(a, &[0xee; 100], 45 * bs as u64).unwrap();
+    data.resize(45 * bs, 0);
+    data.extend_from_slice(&[0xee; 100]);
+    let mut before = FileAttr::default();
+    fs.get_attr(a, &mut before).unwrap();
+
+    let stats = fs.defragment("/frag").unwrap();
+    assert!(stats.extents_before >= 20, "{stats:?}");
+    // 分配器沿上一块顺序分配，只在绕开尾部数据块处断开
+    assert!(stats.extents_after <= 3, "{stats:?}");
+    assert_eq!(stats.blocks_moved, 41);
+
+    let mut buf = vec![0xffu8; data.len()];
+    assert_eq!(fs.read_at(a, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(buf, data);
+    let mut after = FileAttr::default();
+    fs.get_attr(a, &mut after).unwrap();
+    assert_eq!(after.size, before.size);
+    assert_eq!(after.mtime, before.mtime);
+    // 旧extent树的索引块随临时inode一起释放
+    assert_eq!(after.blocks, 41 * bs as u64 / 512);
+
+    // 已经连续的文件不再移动
+    let stats = fs.defragment("/frag").unwrap();
+    assert_eq!(stats.blocks_moved, 0);
+    assert_eq!(stats.extents_before, stats.extents_after);
+    assert_eq!(fs.defragment("/htree").unwrap_err().errno(), errno::EINVAL);
+    fs.flush().unwrap();
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_interrupt_long_operations() {
+    use lwext4_arce::{MkfsOptions, RamDisk};
+    use std::{cell::Cell, rc::Rc};
+
+    fn build_tree<Hal: SystemHal>(fs: &mut Ext4Filesystem<Hal, RamDisk>) {
+        for d in 0..3 {
+            let dir = fs.create_dir_all(&format!("/tree/d{d}"), 0o755).unwrap();
+            for f in 0..20 {
+                fs.create(dir, format!("f{f}").as_str(), InodeType::RegularFile, 0o644).unwrap();
+            }
+        }
+    }
+    /// 第limit次调用起返回true，calls记录调用次数
+    fn cancel_after(limit: u32) -> (lwext4_arce::InterruptHandler, Rc<Cell<u32>>) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let handler = Box::new(move || {
+            counter.set(counter.get() + 1);
+            counter.get() >= limit
+        });
+        (handler, calls)
+    }
+
+    let opts = MkfsOptions { block_size: 1024, journal: false, ..Default::default() };
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(RamDisk::new(8 << 20), &opts, FsConfig::default(), &mut XorShift(5)).unwrap();
+    build_tree(&mut fs);
+
+    let (handler, calls) = cancel_after(10);
+    fs.on_interrupt(Some(handler));
+    let err = fs.remove_dir_all("/tree").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Canceled);
+    assert_eq!(calls.get(), 10);
+    let remaining: usize = (0..3)
+        .map(|d| match fs.resolve_path(&format!("/tree/d{d}")) {
+            Ok(dir) => list_dir_generic(&mut fs, dir).len(),
+            Err(_) => 0,
+        })
+        .sum();
+    assert!(remaining > 0 && remaining < 60, "{remaining}");
+
+    let (handler, _) = cancel_after(1);
+    fs.on_interrupt(Some(handler));
+    assert_eq!(fs.check_dir_types(false).unwrap_err().kind(), Ext4ErrorKind::Canceled);
+
+    // 中断后文件系统保持一致，再次调用可以继续
+    fs.on_interrupt(None);
+    fs.remove_dir_all("/tree").unwrap();
+    assert_eq!(fs.resolve_path("/tree").unwrap_err().kind(), Ext4ErrorKind::NotFound);
+    assert_eq!(fs.check_dir_types(false).unwrap(), vec![]);
+
+    // 碎片整理在复制途中被中断时文件保持原样
+    let a = fs.create(ROOT_INO, "frag", InodeType::RegularFile, 0o644).unwrap();
+    let b = fs.create(ROOT_INO, "other", InodeType::RegularFile, 0o644).unwrap();
+    let mut data = Vec::new();
+    for i in 0..20u8 {
+        let block = vec![i; 1024];
+        fs.write_at(a, &block, data.len() as u64).unwrap();
+        fs.write_at(b, &block, data.len() as u64).unwrap();
+        data.extend_from_slice(&block);
+    }
+    let free = fs.stat().unwrap().free_blocks_count;
+    let (handler, _) = cancel_after(5);
+    fs.on_interrupt(Some(handler));
+    assert_eq!(fs.defragment("/frag").unwrap_err().kind(), Ext4ErrorKind::Canceled);
+    assert_eq!(fs.stat().unwrap().free_blocks_count, free);
+    let mut buf = vec![0u8; data.len()];
+    fs.read_at(a, &mut buf, 0).unwrap();
+    assert!(buf == data);
+    fs.on_interrupt(None);
+    assert!(fs.defragment("/frag").unwrap().blocks_moved > 0);
+
+    // 截止时间需要单调时钟
+    assert_eq!(
+        fs.set_deadline(Some(Duration::from_secs(1))).unwrap_err().kind(),
+        Ext4ErrorKind::Unsupported
+    );
+    let mut fs = Ext4Filesystem::<TickHal, _>::mkfs(RamDisk::new(8 << 20), &opts, FsConfig::default(), &mut XorShift(5)).unwrap();
+    build_tree(&mut fs);
+    fs.set_deadline(Some(Duration::from_millis(5))).unwrap();
+    assert_eq!(fs.remove_dir_all("/tree").unwrap_err().kind(), Ext4ErrorKind::TimedOut);
+    fs.set_deadline(None).unwrap();
+    fs.remove_dir_all("/tree").unwrap();
+}
+
+#[cfg(feature = "use-rust")]
+#[test]
+fn test_progress_reporting() {
+    use lwext4_arce::{MkfsOptions, Progress, ProgressPhase, RamDisk};
+    use std::{cell::RefCell, rc::Rc};
+
+    // 8MiB、1KiB块：1个块组，1024块的日志
+    let opts = MkfsOptions { block_size: 1024, ..Default::default() };
+    let mut events = Vec::new();
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs_with_progress(
+        RamDisk::new(8 << 20),
+        &opts,
+        FsConfig::default(),
+        &mut XorShift(9),
+        &mut |p: &Progress| events.push(*p),
+    )
+    .unwrap();
+    let groups: Vec<_> = events.iter().filter(|p| p.phase == ProgressPhase::MkfsGroups).collect();
+    let journal: Vec<_> = events.iter().filter(|p| p.phase == ProgressPhase::MkfsJournal).collect();
+    assert_eq!(groups.last().unwrap().done, groups.last().unwrap().total.unwrap());
+    assert_eq!(journal.last().unwrap().total, Some(1024));
+    assert_eq!(journal.last().unwrap().percent(), Some(100));
+    // 各阶段依次进行，完成数单调不减
+    let last_group = events.iter().rposition(|p| p.phase == ProgressPhase::MkfsGroups).unwrap();
+    assert!(events[..last_group].iter().all(|p| p.phase == ProgressPhase::MkfsGroups));
+    assert!(journal.windows(2).all(|w| w[0].done <= w[1].done));
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let sink = events.clone();
+    fs.on_progress(Some(Box::new(move |p: &Progress| sink.borrow_mut().push(*p))));
+    for d in 0..5 {
+        fs.create_dir_all(&format!("/dirs/d{d}"), 0o755).unwrap();
+    }
+    fs.check_dir_types(false).unwrap();
+    // 根目录、lost+found、/dirs和5个子目录
+    let last = *events.borrow().last().unwrap();
+    assert_eq!(last, Progress { phase: ProgressPhase::CheckDirs, done: 8, total: Some(8) });
+    assert_eq!(events.borrow().len(), 9);
+
+    // 导入宿主目录树（import_tree需要std和Unix宿主）
+    #[cfg(all(feature = "std", unix))]
+    {
+        let tmp = std::env::temp_dir().join(format!("lwext4-progress-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("sub")).unwrap();
+        for name in ["a", "b", "sub/c"] {
+            std::fs::write(tmp.join(name), name).unwrap();
+        }
+        events.borrow_mut().clear();
+        fs.import_tree(&tmp, "/imported").unwrap();
+        let done: Vec<_> = events.borrow().iter().map(|p| (p.phase, p.done, p.total)).collect();
+        assert_eq!(done, (1..=4).map(|i| (ProgressPhase::Import, i, None)).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    events.borrow_mut().clear();
+    fs.on_progress(None);
+    fs.check_dir_types(false).unwrap();
+    assert!(events.borrow().is_empty());
+}
+
+#[test]
+fn test_prealloc() {
+    let path = copy_test_image("prealloc");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let bs = 4096usize;
+    let blocks_of = |fs: &mut Fs, ino: u32| {
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        attr.blocks / (bs as u64 / 512)
+    };
+
+    // 与另一个文件交替追加半块，设置了预分配提示的文件仍然保持连续
+    let log = fs.create(ROOT_INO, "log", InodeType::RegularFile, 0o644).unwrap();
+    let other = fs.create(ROOT_INO, "other", InodeType::RegularFile, 0o644).unwrap();
+    fs.set_prealloc(log, 16);
+    assert_eq!(fs.prealloc(log), 16);
+    let mut data = Vec::new();
+    for i in 0..40u8 {
+        let half = vec![i; bs / 2];
+        fs.write_at(log, &half, data.len() as u64).unwrap();
+        fs.write_at(other, &vec![i; bs], i as u64 * bs as u64).unwrap();
+        data.extend_from_slice(&half);
+    }
+    // 20块数据之后还有未使用的预分配块，文件大小不变，读取不到预分配的部分
+    let allocated = blocks_of(&mut fs, log);
+    assert!(allocated > 20, "{allocated}");
+    let mut buf = vec![0xffu8; data.len() + bs];
+    assert_eq!(fs.read_at(log, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(&buf[..data.len()], &data[..]);
+
+    // 预分配的块是文件末尾之后的未写入extent，卸载后e2fsck不报错
+    drop(fs);
+    assert_fsck_clean(&path);
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert_eq!(fs.prealloc(log), 0);
+
+    // 关闭时回收未使用的块
+    fs.set_prealloc(log, 16);
+    let free = fs.stat().unwrap().free_blocks_count;
+    fs.release_prealloc(log).unwrap();
+    assert_eq!(fs.prealloc(log), 0);
+    assert_eq!(blocks_of(&mut fs, log), 20);
+    assert_eq!(fs.stat().unwrap().free_blocks_count, free + allocated - 20);
+    // 每次新分配连同预分配的16块物理连续，20块最多分成两段（不预分配时交替追加的每块各成一段）
+    let stats = fs.defragment("/log").unwrap();
+    assert!(stats.extents_before <= 2, "{stats:?}");
+
+    // 截断到当前大小同样回收，之后的追加从预分配的块继续
+    fs.set_prealloc(log, 8);
+    fs.write_at(log, &[0xaa; 10], data.len() as u64).unwrap();
+    data.extend_from_slice(&[0xaa; 10]);
+    assert_eq!(blocks_of(&mut fs, log), 29);
+    fs.write_at(log, &vec![0xbb; bs], data.len() as u64).unwrap();
+    data.extend_from_slice(&vec![0xbb; bs]);
+    assert_eq!(blocks_of(&mut fs, log), 29);
+    fs.set_len(log, data.len() as u64).unwrap();
+    assert_eq!(blocks_of(&mut fs, log), 22);
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(fs.read_at(log, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(buf, data);
+    fs.flush().unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+}
+
+#[test]
+fn test_free_space_report() {
+    let mut fs = mount("free-space");
+    let bs = 4096usize;
+
+    // 与e2freefrag的结果一致：新格式化的测试镜像中全部空闲块连成一段
+    let report = fs.free_space_report().unwrap();
+    let stat = fs.stat().unwrap();
+    let free = stat.free_blocks_count;
+    assert_eq!(report.block_size, 4096);
+    assert_eq!(report.total_blocks, stat.blocks_count);
+    assert_eq!(report.free_blocks, free);
+    assert_eq!((report.free_extents, report.min_extent, report.max_extent), (1, free as u32, free as u32));
+    assert_eq!(report.avg_extent(), Some(free));
+    assert_eq!(report.histogram.extents[free.ilog2() as usize], 1);
+    assert_eq!(report.histogram.blocks[free.ilog2() as usize], free);
+    assert_eq!(report.groups.len(), 1);
+    assert_eq!(report.groups[0].histogram, report.histogram);
+
+    // 两个文件交替分配后删除其中一个，留下一串单块空洞
+    let a = fs
+        .create(ROOT_INO, "keep", InodeType::RegularFile, 0o644)
+        .unwrap();
+    let b = fs
+        .create(ROOT_INO, "drop", InodeType::RegularFile, 0o644)
+        .unwrap();
+    for i in 0..10 {
+        fs.write_at(a, &vec![1; bs], (i * bs) as u64).unwrap();
+        fs.write_at(b, &vec![2; bs], (i * bs) as u64).unwrap();
+    }
+    fs.unlink(ROOT_INO, "drop").unwrap();
+
+    let report = fs.free_space_report().unwrap();
+    assert_eq!(report.free_blocks, fs.stat().unwrap().free_blocks_count);
+    assert_eq!(report.min_extent, 1);
+    assert!(report.histogram.extents[0] >= 8, "{:?}", report.histogram);
+    assert_eq!(
+        report.histogram.blocks.iter().sum::<u64>(),
+        report.free_blocks
+    );
+}
+
+#[test]
+fn test_disk_usage() {
+    let mut fs = mount("disk-usage");
+    let bs = 4096u64;
+
+    let top = fs.create_dir_all("/du/sub", 0o755).unwrap();
+    let du = fs.resolve_path("/du").unwrap();
+    // 稀疏文件：大小100块，只分配首尾两块
+    let sparse = fs
+        .create(du, "sparse", InodeType::RegularFile, 0o644)
+        .unwrap();
+    fs.write_at_sparse(sparse, &[1; 10], 0).unwrap();
+    fs.write_at_sparse(sparse, &[1; 10], 99 * bs).unwrap();
+    // 同一个inode的两个硬链接只计一次
+    let data = fs
+        .create(top, "data", InodeType::RegularFile, 0o644)
+        .unwrap();
+    fs.write_at(data, &vec![2; 3 * bs as usize], 0).unwrap();
+    fs.link(du, "data-link", data).unwrap();
+    let link = fs.create(top, "link", InodeType::Symlink, 0o777).unwrap();
+    fs.set_symlink(link, b"../sparse").unwrap();
+
+    let usage = fs.disk_usage("/du").unwrap();
+    // 两个目录各一块，稀疏文件两块，硬链接文件三块，短符号链接不占块
+    assert_eq!(usage.blocks, 7 * bs / 512);
+    assert_eq!(usage.bytes(), 7 * bs);
+    assert_eq!(usage.apparent_size, 2 * bs + 99 * bs + 10 + 3 * bs + 9);
+    assert_eq!((usage.files, usage.dirs), (3, 2));
+
+    let usage = fs.disk_usage("/du/sparse").unwrap();
+    assert_eq!((usage.blocks, usage.files, usage.dirs), (2 * bs / 512, 1, 0));
+    assert!(fs.disk_usage("/du/missing").is_err());
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_mount_config() {
+    let path = copy_test_image("mount-config");
+    let run = |config: FsConfig| {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+        fs.reset_metrics();
+        let ino = fs.create(ROOT_INO, "config", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(ino, &[0x3c; 8 * 4096], 0).unwrap();
+        let mut buf = [0u8; 4096];
+        for i in 0..8 {
+            fs.read_at(ino, &mut buf, i * 4096).unwrap();
+        }
+        for i in 0..10 {
+            fs.create(ROOT_INO, &format!("f{i}"), InodeType::RegularFile, 0o644).unwrap();
+        }
+        let metrics = fs.metrics();
+        for i in 0..10 {
+            fs.unlink(ROOT_INO, &format!("f{i}")).unwrap();
+        }
+        fs.unlink(ROOT_INO, "config").unwrap();
+        metrics
+    };
+    let config = FsConfig {
+        bcache_size: 64,
+        ..FsConfig::default()
+    };
+
+    let metrics = run(config.clone());
+    assert!(metrics.extent_cache_hits > 0);
+    assert_eq!(metrics.dirty_flushes, 0);
+
+    // 不缓存extent映射；脏块达到上限时在操作过程中回写
+    let metrics = run(FsConfig {
+        extent_cache_size: 0,
+        max_dirty_blocks: 4,
+        ..config
+    });
+    assert_eq!(metrics.extent_cache_hits, 0);
+    assert!(metrics.dirty_flushes > 0);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_dirty_throttle() {
+    let path = copy_test_image("dirty-throttle");
+    let write = |max_dirty_blocks: u32| {
+        let config = FsConfig {
+            bcache_size: 256,
+            max_dirty_blocks,
+            ..FsConfig::default()
+        };
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+        fs.reset_metrics();
+        // 每个目录的数据块都经过块缓存
+        for i in 0..32 {
+            fs.create(ROOT_INO, &format!("d{i}"), InodeType::Directory, 0o755).unwrap();
+        }
+        let metrics = fs.metrics();
+        for i in 0..32 {
+            fs.unlink(ROOT_INO, &format!("d{i}")).unwrap();
+        }
+        metrics
+    };
+
+    // 不限制时全部留在缓存中等待回写
+    let metrics = write(0);
+    assert!(metrics.dirty_blocks >= 32, "{} dirty", metrics.dirty_blocks);
+    assert_eq!(metrics.dirty_flushes, 0);
+
+    // 达到上限时在写入过程中分批回写，不会一次清空
+    let metrics = write(16);
+    assert!(metrics.dirty_blocks > 0 && metrics.dirty_blocks <= 16, "{} dirty", metrics.dirty_blocks);
+    assert!(metrics.dirty_flushes >= 16);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 统计写入指定字节偏移的次数的块设备
+struct WriteCounter {
+    inner: FileBlockDevice,
+    offset: u64,
+    writes: std::rc::Rc<std::cell::Cell<u32>>,
+}
+
+impl lwext4_arce::BlockDevice for WriteCounter {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Result<usize, Ext4Error> {
+        let start = block_id * self.inner.block_size() as u64;
+        if (start..start + buf.len() as u64).contains(&self.offset) {
+            self.writes.set(self.writes.get() + 1);
+        }
+        self.inner.write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Result<usize, Ext4Error> {
+        self.inner.read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> Result<u64, Ext4Error> {
+        self.inner.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_batched_descriptor_writes() {
+    let path = copy_test_image("bg-batch");
+    let writes = std::rc::Rc::new(std::cell::Cell::new(0));
+    // 4K块的文件系统，块组描述符表在块1
+    let dev = WriteCounter {
+        inner: FileBlockDevice::open(&path).unwrap(),
+        offset: 4096,
+        writes: writes.clone(),
+    };
+    let config = FsConfig {
+        bcache_size: 256,
+        max_dirty_blocks: 4,
+        ..FsConfig::default()
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, WriteCounter>::new(dev, config).unwrap();
+    fs.reset_metrics();
+    for i in 0..32 {
+        fs.create(ROOT_INO, &format!("d{i}"), InodeType::Directory, 0o755).unwrap();
+    }
+    // 脏块限额触发的回写不包括块组描述符，同步时只写一次
+    assert!(fs.metrics().dirty_flushes > 0);
+    assert_eq!(writes.get(), 0);
+    fs.flush().unwrap();
+    assert_eq!(writes.get(), 1);
+    drop(fs);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 内存紧张时要求块缓存缩减到PRESSURE个块（0表示没有压力）
+static PRESSURE: AtomicU32 = AtomicU32::new(0);
+
+struct PressureHal;
+impl SystemHal for PressureHal {
+    fn now() -> Option<Duration> {
+        None
+    }
+
+    fn memory_pressure() -> Option<u32> {
+        let target = PRESSURE.load(Ordering::SeqCst);
+        (target != 0).then_some(target)
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_shrink_cache() {
+    let path = copy_test_image("shrink");
+    let config = FsConfig {
+        bcache_size: 256,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config.clone()).unwrap();
+    let page = fs.read_dir_from("/htree", 0, 400).unwrap();
+    let inos: Vec<u32> = page.entries.iter().map(|e| e.ino).collect();
+    fs.stat_many(&inos).unwrap();
+    let ino = fs.create(ROOT_INO, "pending", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(ino, b"dirty", 0).unwrap();
+    let before = fs.metrics().cached_blocks;
+    assert!(before > 16, "only {before} blocks cached");
+
+    // 常驻缓存的块不会被回收，其余的块回收到目标以下（脏块先写回）
+    let pinned = fs.metrics().pinned_blocks;
+    let cached = fs.shrink_cache(8).unwrap();
+    assert_eq!(cached, fs.metrics().cached_blocks);
+    assert!(cached <= 8.max(pinned), "{cached} blocks left, {pinned} pinned");
+    assert_eq!(fs.shrink_cache(before).unwrap(), cached);
+    let mut buf = [0u8; 5];
+    fs.read_at(ino, &mut buf, 0).unwrap();
+    assert_eq!(&buf, b"dirty");
+    drop(fs);
+    assert_fsck_clean(&path);
+
+    // SystemHal报告内存紧张时每次操作前自动缩减
+    let mut fs = Ext4Filesystem::<PressureHal, FileBlockDevice>::new(
+        FileBlockDevice::open(&path).unwrap(),
+        config,
+    )
+    .unwrap();
+    fs.stat_many(&inos).unwrap();
+    assert!(fs.metrics().cached_blocks > 16);
+    let pinned = fs.metrics().pinned_blocks;
+    PRESSURE.store(4, Ordering::SeqCst);
+    let ret = fs.lookup(ROOT_INO, "pending").map(|mut r| r.entry().ino());
+    PRESSURE.store(0, Ordering::SeqCst);
+    assert_eq!(ret.unwrap(), ino);
+    // lookup自身读取的块留在缓存中
+    assert!(fs.metrics().cached_blocks <= 4.max(pinned) + 4);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 每次读取前进1ms的单调时钟
+struct TickHal;
+impl SystemHal for TickHal {
+    fn now() -> Option<Duration> {
+        None
+    }
+
+    fn monotonic() -> Option<Duration> {
+        static TICKS: AtomicU64 = AtomicU64::new(0);
+        Some(Duration::from_millis(TICKS.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+/// 手动推进的单调时钟（秒）
+static CLOCK_SECS: AtomicU64 = AtomicU64::new(0);
+
+struct ClockHal;
+impl SystemHal for ClockHal {
+    fn now() -> Option<Duration> {
+        None
+    }
+
+    fn monotonic() -> Option<Duration> {
+        Some(Duration::from_secs(CLOCK_SECS.load(Ordering::SeqCst)))
+    }
+}
+
+/// 设备上superblock中的空闲inode数
+fn disk_free_inodes(path: &str) -> u32 {
+    let image = std::fs::read(path).unwrap();
+    u32::from_le_bytes(image[1024 + 0x10..1024 + 0x14].try_into().unwrap())
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_sb_commit_policy() {
+    let path = copy_test_image("sb-commit");
+    let initial = disk_free_inodes(&path);
+
+    // 默认只在flush时写回superblock计数
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    for i in 0..3 {
+        fs.create(ROOT_INO, &format!("f{i}"), InodeType::RegularFile, 0o644).unwrap();
+    }
+    assert_eq!(disk_free_inodes(&path), initial);
+    fs.flush().unwrap();
+    assert_eq!(disk_free_inodes(&path), initial - 3);
+    drop(fs);
+
+    // 定期写回：距上次写回超过间隔后的下一次操作写回
+    let config = FsConfig {
+        sb_commit_interval: Some(Duration::from_secs(5)),
+        ..FsConfig::default()
+    };
+    let mut fs = Ext4Filesystem::<ClockHal, FileBlockDevice>::new(
+        FileBlockDevice::open(&path).unwrap(),
+        config,
+    )
+    .unwrap();
+    fs.create(ROOT_INO, "g0", InodeType::RegularFile, 0o644).unwrap();
+    CLOCK_SECS.fetch_add(4, Ordering::SeqCst);
+    fs.lookup(ROOT_INO, "g0").unwrap();
+    assert_eq!(disk_free_inodes(&path), initial - 3);
+    CLOCK_SECS.fetch_add(1, Ordering::SeqCst);
+    fs.lookup(ROOT_INO, "g0").unwrap();
+    assert_eq!(disk_free_inodes(&path), initial - 4);
+    drop(fs);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 设备上superblock中的s_kbytes_written
+fn disk_kbytes_written(path: &str) -> u64 {
+    let image = std::fs::read(path).unwrap();
+    u64::from_le_bytes(image[1024 + 0x178..1024 + 0x180].try_into().unwrap())
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_kbytes_written() {
+    let path = copy_test_image("kbytes");
+    let initial = disk_kbytes_written(&path);
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let ino = fs.create(ROOT_INO, "data", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(ino, &vec![0x5a; 1 << 20], 0).unwrap();
+    fs.flush().unwrap();
+    let kbytes = fs.kbytes_written();
+    assert!(kbytes >= initial + 1024, "{kbytes} < {initial} + 1024");
+    assert_eq!(disk_kbytes_written(&path), kbytes);
+
+    // 没有新的写入时同步不再写回superblock
+    let writes = fs.metrics().block_writes;
+    fs.flush().unwrap();
+    assert_eq!(fs.metrics().block_writes, writes);
+    assert_eq!(fs.kbytes_written(), kbytes);
+    drop(fs);
+    let after_unmount = disk_kbytes_written(&path);
+    assert!(after_unmount >= kbytes);
+
+    let out = common::e2fs::debugfs(&path, "stats");
+    let out = String::from_utf8_lossy(&out);
+    assert!(out.contains("Lifetime writes:"), "{out}");
+    assert_fsck_clean(&path);
+
+    // 可重现模式下不累计
+    let config = FsConfig { reproducible: Some(Duration::ZERO), ..FsConfig::default() };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    fs.write_at(ino, &vec![0xa5; 1 << 20], 0).unwrap();
+    fs.flush().unwrap();
+    assert_eq!(fs.kbytes_written(), after_unmount);
+    drop(fs);
+    assert_eq!(disk_kbytes_written(&path), after_unmount);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_fsck_advice() {
+    use lwext4_arce::FsckAdvice;
+
+    let path = copy_test_image("fsck-advice");
+    let mount = |path: &str| Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+
+    let mut fs = mount(&path);
+    fs.set_check_policy(3, Duration::ZERO).unwrap();
+    fs.mark_checked().unwrap();
+    assert_eq!(fs.mount_count(), 0);
+    drop(fs);
+
+    // 挂载前的挂载次数达到上限时建议检查
+    for count in 0..3 {
+        let fs = mount(&path);
+        assert!(!fs.fsck_recommended(), "mount {count}");
+        assert_eq!(fs.mount_count(), count + 1);
+    }
+    let mut fs = mount(&path);
+    assert_eq!(fs.fsck_advice(), FsckAdvice { max_mount_count: true, ..Default::default() });
+    fs.mark_checked().unwrap();
+    assert!(!fs.fsck_recommended());
+    drop(fs);
+    assert!(!mount(&path).fsck_recommended());
+
+    // 挂载期间的镜像相当于未正常卸载
+    let crashed = copy_test_image("fsck-advice-crashed");
+    let fs = mount(&path);
+    std::fs::copy(&path, &crashed).unwrap();
+    drop(fs);
+    let mut fs = mount(&crashed);
+    assert_eq!(fs.fsck_advice(), FsckAdvice { not_clean: true, ..Default::default() });
+    fs.mark_checked().unwrap();
+    drop(fs);
+    assert!(!mount(&crashed).fsck_recommended());
+    std::fs::remove_file(&crashed).unwrap();
+
+    // 按检查间隔：最后检查时间取自固定时间，没有系统时间时不判断
+    let config = FsConfig { reproducible: Some(Duration::from_secs(1_000_000_000)), ..FsConfig::default() };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    fs.set_check_policy(-1, Duration::from_secs(86400)).unwrap();
+    fs.mark_checked().unwrap();
+    drop(fs);
+    let fs = Ext4Filesystem::<WallClockHal, _>::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default())
+        .unwrap();
+    assert_eq!(fs.fsck_advice(), FsckAdvice { check_interval: true, ..Default::default() });
+    drop(fs);
+    assert!(!mount(&path).fsck_recommended());
+    let err = mount(&path).set_check_policy(0, Duration::from_secs(1 << 40)).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+
+    let out = common::e2fs::debugfs(&path, "stats");
+    let out = String::from_utf8_lossy(&out);
+    assert!(out.contains("Maximum mount count:      -1"), "{out}");
+    assert!(out.contains("Check interval:           86400"), "{out}");
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_flush_some() {
+    let path = copy_test_image("flush-some");
+    let config = FsConfig {
+        bcache_size: 256,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    for i in 0..32 {
+        fs.create(ROOT_INO, &format!("d{i}"), InodeType::Directory, 0o755).unwrap();
+    }
+    let free_inodes = fs.stat().unwrap().free_inodes_count;
+    let dirty = fs.metrics().dirty_blocks;
+    assert!(dirty >= 32, "{dirty} dirty");
+    assert_ne!(disk_free_inodes(&path), free_inodes);
+
+    // 每次最多回写8个块
+    fs.reset_metrics();
+    let mut remaining = dirty;
+    let mut rounds = 0;
+    while remaining > 0 {
+        let writes = fs.metrics().block_writes;
+        let next = fs.flush_some(8).unwrap();
+        assert_eq!(next, remaining.saturating_sub(8));
+        assert!(fs.metrics().block_writes - writes <= 9);
+        remaining = next;
+        rounds += 1;
+    }
+    assert_eq!(rounds, dirty.div_ceil(8));
+    assert_eq!(fs.metrics().dirty_blocks, 0);
+    assert_eq!(disk_free_inodes(&path), free_inodes);
+    assert_eq!(fs.flush_some(0).unwrap(), 0);
+
+    // 写回之后的修改继续累积
+    fs.create(ROOT_INO, "late", InodeType::RegularFile, 0o644).unwrap();
+    assert!(fs.flush_some(0).unwrap() > 0);
+    drop(fs);
+    assert_fsck_clean(&path);
+}
+
+#[test]
+fn test_metrics() {
+    let mut fs = mount("metrics");
+    fs.reset_metrics();
+    let ino = fs
+        .create(ROOT_INO, "data", InodeType::RegularFile, 0o644)
+        .unwrap();
+    fs.write_at(ino, &[0x5a; 3 * 4096], 0).unwrap();
+    let mut buf = [0u8; 4096];
+    fs.read_at(ino, &mut buf, 4096).unwrap();
+    fs.flush().unwrap();
+
+    let m = fs.metrics();
+    assert!(m.blocks_allocated >= 3);
+    assert!(m.block_reads > 0 && m.block_writes > 0);
+    assert!(m.cache_hits > 0 && m.dirty_flushes > 0);
+    assert!(m.cache_hit_rate().is_some());
+    // 块组描述符和位图常驻缓存，但不超过缓存容量的一半
+    assert!(m.pinned_blocks > 0);
+    assert!(m.pinned_blocks <= FsConfig::default().bcache_size / 2);
+    assert_eq!(
+        (m.create.count, m.write.count, m.read.count, m.flush.count),
+        (1, 1, 1, 1)
+    );
+    // DummyHal没有单调时钟，不记录耗时
+    assert_eq!(m.write.timed, 0);
+    assert_eq!(m.write.mean(), None);
+
+    fs.unlink(ROOT_INO, "data").unwrap();
+    assert!(fs.metrics().blocks_freed >= 3);
+
+    fs.reset_metrics();
+    let m = fs.metrics();
+    assert_eq!((m.block_reads, m.cache_hits, m.unlink.count), (0, 0, 0));
+
+    let mut fs =
+        Ext4Filesystem::<TickHal, _>::new(open_test_image("metrics-timed"), FsConfig::default())
+            .unwrap();
+    fs.lookup(ROOT_INO, "test.txt").unwrap();
+    let m = fs.metrics();
+    assert_eq!((m.lookup.count, m.lookup.timed), (1, 1));
+    assert!(m.lookup.max >= Duration::from_millis(1));
+    assert_eq!(m.lookup.mean(), Some(m.lookup.max));
+}
+
+#[test]
+fn test_alloc_goal_stats() {
+    let mut fs = mount("alloc-goal");
+    let a = fs.create(ROOT_INO, "a", InodeType::RegularFile, 0o644).unwrap();
+    let b = fs.create(ROOT_INO, "b", InodeType::RegularFile, 0o644).unwrap();
+    let bs = fs.stat().unwrap().block_size as u64;
+    fs.write_at(a, &[1], 0).unwrap();
+    fs.reset_metrics();
+
+    // 顺序追加：每个新块都紧接在上一块之后
+    for i in 1..16 {
+        fs.write_at(a, &[1], i * bs).unwrap();
+    }
+    let m = fs.metrics();
+    assert_eq!(m.alloc_goal_hits, m.blocks_allocated);
+    assert_eq!((m.alloc_goal_group_hits, m.alloc_goal_misses), (0, 0));
+    assert_eq!(m.alloc_goal_hit_rate(), Some(1.0));
+
+    // 交替追加：目标块已被另一个文件占用时，在同一块组内向后查找
+    fs.reset_metrics();
+    for i in 0..8 {
+        fs.write_at(b, &[2], i * bs).unwrap();
+        fs.write_at(a, &[1], (16 + i) * bs).unwrap();
+    }
+    let m = fs.metrics();
+    assert!(m.alloc_goal_group_hits > 0);
+    assert_eq!(m.alloc_goal_misses, 0);
+    assert!(m.alloc_goal_hit_rate().unwrap() < 1.0);
+}
+
+/// 先创建大量文件使缓存中堆满脏元数据，再交替写入两个文件（每块一个extent，需要分配extent叶块）
+fn ordered_write_flushes(name: &str, ordered_data: bool) -> u64 {
+    let path = copy_test_image(name);
+    let config = FsConfig {
+        bcache_size: 16,
+        ordered_data,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config.clone()).unwrap();
+    let dir = fs.create(ROOT_INO, "many", InodeType::Directory, 0o755).unwrap();
+    for i in 0..300 {
+        fs.create(dir, &format!("f{i}"), InodeType::RegularFile, 0o644).unwrap();
+    }
+    let a = fs.create(ROOT_INO, "a", InodeType::RegularFile, 0o644).unwrap();
+    let b = fs.create(ROOT_INO, "b", InodeType::RegularFile, 0o644).unwrap();
+
+    let before = fs.metrics().dirty_flushes;
+    for i in 0..20u8 {
+        fs.write_at(a, &[i; 4096], i as u64 * 4096).unwrap();
+        fs.write_at(b, &[!i; 4096], i as u64 * 4096).unwrap();
+    }
+    let flushes = fs.metrics().dirty_flushes - before;
+    fs.flush().unwrap();
+    drop(fs);
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    let mut buf = vec![0u8; 4096];
+    for i in 0..20u8 {
+        fs.read_at(a, &mut buf, i as u64 * 4096).unwrap();
+        assert!(buf.iter().all(|&x| x == i));
+        fs.read_at(b, &mut buf, i as u64 * 4096).unwrap();
+        assert!(buf.iter().all(|&x| x == !i));
+    }
+    assert_eq!(list_dir(&mut fs, dir).len(), 300);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+    flushes
+}
+
+#[test]
+fn test_ordered_data() {
+    // 默认模式下缓存已满时写入数据会先回写脏元数据
+    assert!(ordered_write_flushes("unordered", false) > 0);
+    // 有序数据模式下写入数据期间不回写任何元数据（缓存暂时超出容量）
+    #[cfg(feature = "use-rust")]
+    assert_eq!(ordered_write_flushes("ordered", true), 0);
+    #[cfg(not(feature = "use-rust"))]
+    assert!(Fs::new(
+        open_test_image("ordered"),
+        FsConfig {
+            ordered_data: true,
+            ..FsConfig::default()
+        }
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_failed_op_rolls_back() {
+    let path = copy_test_image("rollback");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let before = fs.stat().unwrap();
+
+    // 名称过长：inode已分配，添加目录项时失败
+    let long = "x".repeat(300);
+    assert!(fs.create(ROOT_INO, &long, InodeType::RegularFile, 0o644).is_err());
+    let stat = fs.stat().unwrap();
+    assert_eq!(stat.free_inodes_count, before.free_inodes_count);
+
+    // 空间耗尽：目录项已添加，为新目录分配数据块时失败
+    let fill = fs.create(ROOT_INO, "fill", InodeType::RegularFile, 0o644).unwrap();
+    let chunk = vec![0xa5u8; 4096];
+    let mut offset = 0;
+    while fs.write_at(fill, &chunk, offset).is_ok() {
+        offset += chunk.len() as u64;
+    }
+    let full = fs.stat().unwrap();
+    let err = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NoSpace);
+    let stat = fs.stat().unwrap();
+    assert_eq!(stat.free_inodes_count, full.free_inodes_count);
+    assert_eq!(stat.free_blocks_count, full.free_blocks_count);
+    assert!(fs.lookup(ROOT_INO, "dir").is_err());
+    // 失败的写入不改变文件大小
+    let mut attr = FileAttr::default();
+    fs.get_attr(fill, &mut attr).unwrap();
+    assert_eq!(attr.size, offset);
+
+    fs.unlink(ROOT_INO, "fill").unwrap();
+    drop(fs);
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let stat = fs.stat().unwrap();
+    assert_eq!(stat.free_inodes_count, before.free_inodes_count);
+    assert_eq!(stat.free_blocks_count, before.free_blocks_count);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_superblock_validation() {
+    let path = copy_test_image("sb-validation");
+    let golden = std::fs::read(&path).unwrap();
+    fn le32(sb: &[u8], off: usize) -> u32 {
+        u32::from_le_bytes(sb[off..off + 4].try_into().unwrap())
+    }
+    fn set32(sb: &mut [u8], off: usize, v: u32) {
+        sb[off..off + 4].copy_from_slice(&v.to_le_bytes())
+    }
+    type Patch = fn(&mut [u8]);
+    let cases: [(&str, Patch); 8] = [
+        ("block size", |sb| set32(sb, 0x18, 7)),
+        ("blocks_per_group", |sb| set32(sb, 0x20, 0)),
+        ("inodes_per_group", |sb| set32(sb, 0x28, 0)),
+        ("group beyond bitmap", |sb| set32(sb, 0x20, 4096 * 8 + 8)),
+        ("inodes_count", |sb| set32(sb, 0x00, le32(sb, 0x00) + 1)),
+        ("inode size", |sb| sb[0x58..0x5A].copy_from_slice(&100u16.to_le_bytes())),
+        ("first_ino", |sb| set32(sb, 0x54, 3)),
+        ("blocks_count", |sb| set32(sb, 0x04, le32(sb, 0x04) * 2)),
+    ];
+    for (what, patch) in cases {
+        patch_superblock(&path, patch);
+        let err = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default())
+            .err()
+            .unwrap_or_else(|| panic!("{what}: mounted"));
+        assert_eq!(err.kind(), Ext4ErrorKind::Corrupted, "{what}: {err:?}");
+        std::fs::write(&path, &golden).unwrap();
+    }
+
+    // 版本级别过高是不支持而不是损坏
+    patch_superblock(&path, |sb| set32(sb, 0x4C, 2));
+    let err = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::Unsupported);
+    std::fs::write(&path, &golden).unwrap();
+    drop(Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_out_of_range_block_math() {
+    let path = copy_test_image("block_math");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let far = fs.create(ROOT_INO, "far", InodeType::RegularFile, 0o644).unwrap();
+    let huge = fs.create(ROOT_INO, "huge", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(far, &[0x5a; 4096], 0).unwrap();
+    fs.write_at(huge, &[0x5a; 4096], 0).unwrap();
+    drop(fs);
+
+    // extent指向远超设备末尾的物理块
+    patch_inode(&path, far, |raw| raw[0x3A..0x3C].copy_from_slice(&0xFFFFu16.to_le_bytes()));
+    // i_size超出32位逻辑块号可表示的范围
+    patch_inode(&path, huge, |raw| raw[0x6C..0x70].copy_from_slice(&u32::MAX.to_le_bytes()));
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let mut buf = [0u8; 64];
+    let err = fs.read_at(far, &mut buf, 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Io);
+    let err = fs.read_at(huge, &mut buf, (u32::MAX as u64) << 32).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Corrupted);
+    assert_eq!(fs.read_at(huge, &mut buf, 0).unwrap(), buf.len());
+    assert_eq!(buf, [0x5a; 64]);
+}
+
+#[test]
+fn test_unsupported_features() {
+    let path = copy_test_image("features");
+    let mount_with = |force_features: bool| {
+        let config = FsConfig {
+            force_features,
+            ..FsConfig::default()
+        };
+        Fs::new(FileBlockDevice::open(&path).unwrap(), config)
+    };
+
+    // 不支持的不兼容特性（inline_data）：拒绝挂载
+    patch_superblock(&path, |sb| sb[0x61] |= 0x80);
+    let err = mount_with(false).err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::Unsupported);
+    #[cfg(feature = "use-rust")]
+    {
+        drop(mount_with(true).unwrap());
+        patch_superblock(&path, |sb| sb[0x61] &= !0x80);
+
+        // 不支持写入的只读兼容特性（未知位）：拒绝可写挂载
+        patch_superblock(&path, |sb| sb[0x66] |= 0x80);
+        let err = mount_with(false).err().unwrap();
+        assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+        let mut fs = mount_with(true).unwrap();
+        assert!(fs.lookup(ROOT_INO, "test.txt").is_ok());
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_with_inode_refs() {
+    let path = copy_test_image("inode-refs");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    let file = fs.create(dir, "file", InodeType::RegularFile, 0o644).unwrap();
+
+    // 父目录和子项同时持有
+    let (parent_links, child_links) = fs
+        .with_inode_refs(dir, file, |parent, child| {
+            child.set_mode(0o600);
+            parent.set_mode(0o700);
+            Ok((parent.nlink(), child.nlink()))
+        })
+        .unwrap();
+    assert_eq!((parent_links, child_links), (2, 1));
+    let mut attr = FileAttr::default();
+    fs.get_attr(file, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o600);
+    fs.get_attr(dir, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o700);
+
+    // 同一inode的两个引用看到彼此的修改
+    let mode = fs
+        .with_inode_refs(file, file, |a, b| {
+            a.set_mode(0o640);
+            Ok(b.mode() & 0o777)
+        })
+        .unwrap();
+    assert_eq!(mode, 0o640);
+    drop(fs);
+
+    // 只读挂载时两个inode的修改都被撤销
+    let config = FsConfig { read_only: true, ..FsConfig::default() };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+    let err = fs
+        .with_inode_refs(dir, file, |parent, child| {
+            parent.set_mode(0o755);
+            child.set_mode(0o644);
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+    fs.get_attr(file, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o640);
+    fs.get_attr(dir, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o700);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_strict_read_only() {
+    let path = copy_test_image("readonly");
+    let before = std::fs::read(&path).unwrap();
+    let config = FsConfig {
+        read_only: true,
+        ..FsConfig::default()
+    };
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config.clone()).unwrap();
+    assert!(fs.is_read_only());
+
+    // 读取操作不受影响
+    let ino = fs.lookup(ROOT_INO, "test.txt").unwrap().entry().ino();
+    let mut buf = [0u8; 64];
+    assert!(fs.read_at(ino, &mut buf, 0).unwrap() > 0);
+    assert!(!list_dir(&mut fs, ROOT_INO).is_empty());
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr).unwrap();
+
+    // 修改操作在接口层被拒绝
+    let kind = |r: Result<(), Ext4Error>| r.unwrap_err().kind();
+    assert_eq!(kind(fs.write_at(ino, b"x", 0).map(drop)), Ext4ErrorKind::ReadOnly);
+    assert_eq!(kind(fs.set_len(ino, 0)), Ext4ErrorKind::ReadOnly);
+    assert_eq!(
+        kind(fs.create(ROOT_INO, "new", InodeType::RegularFile, 0o644).map(drop)),
+        Ext4ErrorKind::ReadOnly
+    );
+    assert_eq!(kind(fs.unlink(ROOT_INO, "test.txt")), Ext4ErrorKind::ReadOnly);
+    assert_eq!(kind(fs.rename(ROOT_INO, "test.txt", ROOT_INO, "moved")), Ext4ErrorKind::ReadOnly);
+    assert_eq!(kind(fs.create_dir_all("/a/b", 0o755).map(drop)), Ext4ErrorKind::ReadOnly);
+    assert_eq!(kind(fs.truncate(ino, 0)), Ext4ErrorKind::ReadOnly);
+    assert_eq!(kind(fs.set_symlink(ino, b"target")), Ext4ErrorKind::ReadOnly);
+    assert_eq!(
+        kind(fs.mknod("/fifo", InodeType::Fifo, 0o644, 0).map(drop)),
+        Ext4ErrorKind::ReadOnly
+    );
+    let err = fs
+        .with_inode_ref(ino, |inode| {
+            inode.set_mode(0o600);
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+    let mut after = FileAttr::default();
+    fs.get_attr(ino, &mut after).unwrap();
+    assert_eq!(after.mode, attr.mode);
+    #[cfg(feature = "use-rust")]
+    {
+        assert_eq!(kind(fs.defragment("/test.txt").map(drop)), Ext4ErrorKind::ReadOnly);
+        assert_eq!(kind(fs.set_volume_name("label")), Ext4ErrorKind::ReadOnly);
+        assert_eq!(kind(fs.mark_checked()), Ext4ErrorKind::ReadOnly);
+        assert_eq!(kind(fs.set_xattr(ino, "user.comment", b"x")), Ext4ErrorKind::ReadOnly);
+        assert_eq!(kind(fs.remove_xattr(ino, "user.comment")), Ext4ErrorKind::ReadOnly);
+    }
+    fs.flush().unwrap();
+    assert_eq!(fs.metrics().block_writes, 0);
+    drop(fs);
+
+    // 挂载计数、状态等superblock字段都没有改变
+    assert!(std::fs::read(&path).unwrap() == before, "image modified by read-only mount");
+
+    // 包含不支持写入的只读兼容特性时仍可只读挂载
+    #[cfg(feature = "use-rust")]
+    {
+        patch_superblock(&path, |sb| sb[0x66] |= 0x80);
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+        assert!(fs.lookup(ROOT_INO, "test.txt").is_ok());
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_overlay_device() {
+    use lwext4_arce::{DeltaStore, MemoryDelta, OverlayDevice};
+
+    type OverlayFs<D> = Ext4Filesystem<DummyHal, OverlayDevice<FileBlockDevice, D>>;
+    fn mount_overlay<D: DeltaStore>(path: &str, delta: D) -> OverlayFs<D> {
+        let dev = OverlayDevice::new(FileBlockDevice::open(path).unwrap(), delta);
+        OverlayFs::new(dev, FsConfig::default()).unwrap()
+    }
+
+    let path = copy_test_image("overlay");
+    let golden = std::fs::read(&path).unwrap();
+
+    // 修改全部落在内存差异中
+    let mut delta = MemoryDelta::new();
+    {
+        let mut fs = mount_overlay(&path, &mut delta);
+        let ino = fs.create(ROOT_INO, "scratch", InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(ino, &[7u8; 10000], 0).unwrap();
+        fs.unlink(ROOT_INO, "test.txt").unwrap();
+    }
+    assert!(std::fs::read(&path).unwrap() == golden, "base image modified");
+    assert!(!delta.is_empty());
+
+    {
+        let mut fs = mount_overlay(&path, &mut delta);
+        let ino = fs.lookup(ROOT_INO, "scratch").unwrap().entry().ino();
+        let mut buf = vec![0u8; 10000];
+        assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), buf.len());
+        assert!(buf.iter().all(|&b| b == 7));
+        assert!(fs.lookup(ROOT_INO, "test.txt").is_err());
+    }
+
+    // 丢弃差异后恢复为原始镜像
+    delta.clear();
+    {
+        let mut fs = mount_overlay(&path, &mut delta);
+        assert!(fs.lookup(ROOT_INO, "scratch").is_err());
+        assert!(fs.lookup(ROOT_INO, "test.txt").is_ok());
+    }
+
+    // 文件差异在重新打开后仍然有效
+    #[cfg(feature = "std")]
+    {
+        use lwext4_arce::FileDelta;
+
+        let delta_path = format!("{path}.delta");
+        {
+            let mut fs = mount_overlay(&path, FileDelta::create(&delta_path).unwrap());
+            fs.create(ROOT_INO, "persisted", InodeType::Directory, 0o755).unwrap();
+        }
+        let delta = FileDelta::open(&delta_path).unwrap();
+        assert!(!delta.is_empty());
+        {
+            let mut fs = mount_overlay(&path, delta);
+            assert!(fs.lookup(ROOT_INO, "persisted").is_ok());
+        }
+        assert!(std::fs::read(&path).unwrap() == golden, "base image modified");
+        std::fs::remove_file(&delta_path).unwrap();
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_change_events() {
+    use std::sync::{Arc, Mutex};
+
+    let mut fs = mount("change-events");
+    let events = Arc::new(Mutex::new(Vec::<String>::new()));
+    let sink = events.clone();
+    fs.on_change(Some(Box::new(move |event| {
+        sink.lock().unwrap().push(format!("{event:?}"))
+    })));
+    let take = || core::mem::take(&mut *events.lock().unwrap());
+
+    let dir = fs.create(ROOT_INO, "d", InodeType::Directory, 0o755).unwrap();
+    let file = fs.create(dir, "f", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, b"hello", 3).unwrap();
+    fs.write_at(file, b"", 0).unwrap();
+    fs.set_len(file, 4).unwrap();
+    fs.link(ROOT_INO, "hard", file).unwrap();
+    fs.rename(dir, "f", ROOT_INO, "g").unwrap();
+    fs.unlink(ROOT_INO, "hard").unwrap();
+    assert_eq!(
+        take(),
+        [
+            format!("Created {{ parent: {ROOT_INO}, name: \"d\", ino: {dir} }}"),
+            format!("Created {{ parent: {dir}, name: \"f\", ino: {file} }}"),
+            format!("Written {{ ino: {file}, offset: 3, len: 5 }}"),
+            format!("Resized {{ ino: {file}, len: 4 }}"),
+            format!("Created {{ parent: {ROOT_INO}, name: \"hard\", ino: {file} }}"),
+            format!(
+                "Renamed {{ src_dir: {dir}, src_name: \"f\", dst_dir: {ROOT_INO}, \
+                 dst_name: \"g\", ino: {file} }}"
+            ),
+            format!("Removed {{ parent: {ROOT_INO}, name: \"hard\", ino: {file} }}"),
+        ]
+    );
+
+    // 失败的操作不发出事件
+    assert!(fs.unlink(ROOT_INO, "missing").is_err());
+    assert!(fs.create_many(dir, ["a", &"x".repeat(300)], InodeType::RegularFile, 0o644).is_err());
+    assert!(take().is_empty());
+
+    let inos = fs.create_many(dir, ["a", "b"], InodeType::RegularFile, 0o644).unwrap();
+    assert_eq!(take().len(), inos.len());
+    fs.on_change(None);
+    fs.unlink(dir, "a").unwrap();
+    assert!(take().is_empty());
+}
+
+#[test]
+fn test_freeze() {
+    let path = copy_test_image("freeze");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let file = fs.create(ROOT_INO, "data", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, b"before freeze", 0).unwrap();
+    fs.create_dir_all("/a/b/c", 0o755).unwrap();
+
+    fs.freeze().unwrap();
+    assert!(fs.is_frozen());
+    assert_eq!(fs.freeze().unwrap_err().kind(), Ext4ErrorKind::Busy);
+
+    // 冻结期间设备上的内容是完整一致的
+    let backup = format!("{path}.bak");
+    std::fs::copy(&path, &backup).unwrap();
+    assert_fsck_clean(&backup);
+    std::fs::remove_file(&backup).unwrap();
+
+    // 读取照常进行，修改被拒绝
+    let mut buf = [0; 13];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), 13);
+    assert_eq!(&buf, b"before freeze");
+    assert!(fs.resolve_path("/a/b/c").is_ok());
+    let err = fs.write_at(file, b"x", 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Busy);
+    assert_eq!(err.errno(), 16);
+    let err = fs.create(ROOT_INO, "new", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Busy);
+    let err = fs.with_inode_ref(file, |inode| {
+        inode.set_mode(0o600);
+        Ok(())
+    });
+    assert_eq!(err.unwrap_err().kind(), Ext4ErrorKind::Busy);
+    let mut attr = FileAttr::default();
+    fs.get_attr(file, &mut attr).unwrap();
+    assert_eq!(attr.mode & 0o777, 0o644);
+    fs.flush().unwrap();
+
+    fs.thaw();
+    assert!(!fs.is_frozen());
+    fs.write_at(file, b"after", 0).unwrap();
+    fs.create(ROOT_INO, "new", InodeType::RegularFile, 0o644).unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+}
+
+#[test]
+fn test_revalidate_media_change() {
+    use lwext4_arce::MediaChange;
+    use std::sync::{Arc, Mutex};
+
+    let path = copy_test_image("revalidate");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let events = Arc::new(Mutex::new(Vec::<MediaChange>::new()));
+    let sink = events.clone();
+    fs.on_media_change(Some(Box::new(move |change| sink.lock().unwrap().push(*change))));
+    assert_eq!(fs.revalidate().unwrap(), None);
+
+    // 介质变大：仍可读写
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len + 1024 * 1024).unwrap();
+    let change = fs.revalidate().unwrap().unwrap();
+    assert_eq!(change.old_blocks * 512, len);
+    assert_eq!(change.new_blocks * 512, len + 1024 * 1024);
+    assert!(!change.read_only);
+    fs.create(ROOT_INO, "after-grow", InodeType::RegularFile, 0o644).unwrap();
+
+    // 缓存中有未写回的修改时拒绝，不做任何改变；写回后重试
+    file.set_len(len + 2 * 1024 * 1024).unwrap();
+    assert_eq!(fs.revalidate().unwrap_err().kind(), Ext4ErrorKind::Busy);
+    assert_eq!(events.lock().unwrap().len(), 1);
+    fs.flush().unwrap();
+    let change = fs.revalidate().unwrap().unwrap();
+    assert_eq!(change.old_blocks * 512, len + 1024 * 1024);
+    assert!(!change.replaced);
+    assert!(fs.lookup(ROOT_INO, "after-grow").is_ok());
+    assert_eq!(fs.revalidate().unwrap(), None);
+
+    // 同一文件系统：重新读取superblock
+    #[cfg(feature = "use-rust")]
+    {
+        patch_superblock(&path, |sb| sb[0x78..0x78 + 7].copy_from_slice(b"swapped"));
+        file.set_len(len + 3 * 1024 * 1024).unwrap();
+        assert!(!fs.revalidate().unwrap().unwrap().replaced);
+        assert_eq!(fs.volume_name(), b"swapped");
+    }
+
+    // 介质小于文件系统：切换为只读
+    file.set_len(len / 2).unwrap();
+    let change = fs.revalidate().unwrap().unwrap();
+    assert!(change.read_only);
+    assert!(fs.is_read_only());
+    let err = fs.create(ROOT_INO, "after-shrink", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+    assert_eq!(events.lock().unwrap().len(), if cfg!(feature = "use-rust") { 4 } else { 3 });
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_revalidate_media_replaced() {
+    let path = copy_test_image("revalidate-swap");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    fs.flush().unwrap();
+
+    // 换上UUID不同的介质：需要重新挂载，之后的访问返回ESTALE
+    patch_superblock(&path, |sb| sb[0x68] ^= 0xff);
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(file.metadata().unwrap().len() + 1024 * 1024).unwrap();
+    let image = std::fs::read(&path).unwrap();
+    let change = fs.revalidate().unwrap().unwrap();
+    assert!(change.replaced && change.read_only);
+    assert_eq!(fs.lookup(ROOT_INO, "test.txt").err().unwrap().kind(), Ext4ErrorKind::Stale);
+    let err = fs.create(ROOT_INO, "after-swap", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Stale);
+    drop(fs);
+
+    // 卸载时没有写入新介质
+    assert!(std::fs::read(&path).unwrap() == image);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 可注入I/O错误的块设备（计数为u64::MAX时一直失败）
+struct FlakyDevice {
+    inner: FileBlockDevice,
+    failing_reads: std::sync::Arc<AtomicU64>,
+    failing_writes: std::sync::Arc<AtomicU64>,
+}
+
+impl FlakyDevice {
+    fn fail(counter: &AtomicU64) -> bool {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| match n {
+                0 => None,
+                u64::MAX => Some(n),
+                _ => Some(n - 1),
+            })
+            .is_ok()
+    }
+}
+
+impl lwext4_arce::BlockDevice for FlakyDevice {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Result<usize, Ext4Error> {
+        if Self::fail(&self.failing_writes) {
+            return Err(Ext4Error::new(5, "injected write error"));
+        }
+        self.inner.write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Result<usize, Ext4Error> {
+        if Self::fail(&self.failing_reads) {
+            return Err(Ext4Error::new(5, "injected read error"));
+        }
+        self.inner.read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> Result<u64, Ext4Error> {
+        self.inner.num_blocks()
+    }
+}
+
+#[test]
+fn test_retry_device() {
+    use lwext4_arce::{RetryDevice, RetryPolicy};
+    use std::sync::Arc;
+
+    let reads = Arc::new(AtomicU64::new(2));
+    let writes = Arc::new(AtomicU64::new(0));
+    let dev = FlakyDevice {
+        inner: open_test_image("retry"),
+        failing_reads: reads.clone(),
+        failing_writes: writes.clone(),
+    };
+    let policy = RetryPolicy {
+        write_failure_limit: 2,
+        ..RetryPolicy::default()
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, RetryDevice<FlakyDevice>>::new(
+        RetryDevice::new(dev, policy),
+        FsConfig::default(),
+    )
+    .unwrap();
+    // 暂时性读错误被重试掩盖
+    assert!(fs.lookup(ROOT_INO, "test.txt").is_ok());
+
+    // 写入连续失败后设备和文件系统转为只读
+    fs.create(ROOT_INO, "doomed", InodeType::RegularFile, 0o644).unwrap();
+    writes.store(u64::MAX, Ordering::SeqCst);
+    assert!(fs.flush().is_err());
+    assert!(fs.flush().is_err());
+    assert!(fs.is_read_only());
+    let err = fs.create(ROOT_INO, "rejected", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+}
+
+#[test]
+fn test_io_error_block() {
+    use common::e2fs::debugfs;
+    use std::sync::Arc;
+
+    let path = copy_test_image("io-error-block");
+    let data_block: u64 = String::from_utf8(debugfs(&path, "blocks /test.txt"))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    let reads = Arc::new(AtomicU64::new(0));
+    let dev = FlakyDevice {
+        inner: FileBlockDevice::open(&path).unwrap(),
+        failing_reads: reads.clone(),
+        failing_writes: Arc::new(AtomicU64::new(0)),
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, FlakyDevice>::new(dev, FsConfig::default()).unwrap();
+    let ino = fs.lookup(ROOT_INO, "test.txt").unwrap().entry().ino();
+
+    // 设备读失败时错误中带有失败的文件系统块号
+    reads.store(u64::MAX, Ordering::SeqCst);
+    let err = fs.read_at(ino, &mut [0; 7], 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Io);
+    assert_eq!(err.block(), Some(data_block));
+    assert_eq!(err.ino(), Some(ino));
+
+    // 之后与设备无关的错误不带块号
+    reads.store(0, Ordering::SeqCst);
+    let err = fs.lookup(ROOT_INO, "missing").err().unwrap();
+    assert_eq!(err.block(), None);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_errors_remount_ro() {
+    use lwext4_arce::ErrorBehavior;
+    use std::sync::Arc;
+
+    let path = copy_test_image("errors-ro");
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        assert_eq!(fs.error_behavior(), ErrorBehavior::Continue);
+        fs.set_error_behavior(ErrorBehavior::RemountRo).unwrap();
+    }
+    let writes = Arc::new(AtomicU64::new(0));
+    let dev = FlakyDevice {
+        inner: FileBlockDevice::open(&path).unwrap(),
+        failing_reads: Arc::new(AtomicU64::new(0)),
+        failing_writes: writes.clone(),
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, FlakyDevice>::new(dev, FsConfig::default()).unwrap();
+    assert_eq!(fs.error_behavior(), ErrorBehavior::RemountRo);
+    fs.create(ROOT_INO, "pending", InodeType::RegularFile, 0o644).unwrap();
+    writes.store(1, Ordering::SeqCst);
+    assert!(fs.flush().is_err());
+    assert!(fs.is_read_only());
+    // 之后的修改被拒绝，缓存中剩余的脏块写回时返回EROFS（不同于只读挂载中出现的写入）
+    let err = fs.create(ROOT_INO, "rejected", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::ReadOnly);
+    assert_eq!(fs.flush().unwrap_err().errno(), errno::EROFS);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_errors_panic() {
+    use lwext4_arce::ErrorBehavior;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+
+    let path = copy_test_image("errors-panic");
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        fs.set_error_behavior(ErrorBehavior::Panic).unwrap();
+    }
+    let writes = Arc::new(AtomicU64::new(0));
+    let dev = FlakyDevice {
+        inner: FileBlockDevice::open(&path).unwrap(),
+        failing_reads: Arc::new(AtomicU64::new(0)),
+        failing_writes: writes.clone(),
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, FlakyDevice>::new(dev, FsConfig::default()).unwrap();
+    fs.create(ROOT_INO, "pending", InodeType::RegularFile, 0o644).unwrap();
+    // 写入在设备回调中失败，panic在回调返回后由操作本身触发（不穿过C接口）
+    writes.store(1, Ordering::SeqCst);
+    assert!(catch_unwind(AssertUnwindSafe(|| fs.flush())).is_err());
+    assert!(!fs.is_read_only());
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_block_group_poisoned() {
+    use std::sync::Arc;
+
+    let path = copy_test_image("bg-poison");
+    let writes = Arc::new(AtomicU64::new(0));
+    let dev = FlakyDevice {
+        inner: FileBlockDevice::open(&path).unwrap(),
+        failing_reads: Arc::new(AtomicU64::new(0)),
+        failing_writes: writes.clone(),
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, FlakyDevice>::new(dev, FsConfig::default()).unwrap();
+    fs.create(ROOT_INO, "pending", InodeType::RegularFile, 0o644).unwrap();
+    assert!(!fs.is_poisoned());
+
+    // 块组描述符写回失败：之后的修改被拒绝，读取不受影响
+    writes.store(1, Ordering::SeqCst);
+    assert_eq!(fs.flush().unwrap_err().kind(), Ext4ErrorKind::Io);
+    assert!(fs.is_poisoned());
+    assert!(!fs.is_read_only());
+    let err = fs.create(ROOT_INO, "rejected", InodeType::RegularFile, 0o644).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Io);
+    assert!(fs.lookup(ROOT_INO, "pending").is_ok());
+    assert!(fs.lookup(ROOT_INO, "rejected").is_err());
+
+    // 已有的修改仍可写回，重新挂载后恢复
+    fs.flush().unwrap();
+    assert!(fs.is_poisoned());
+    drop(fs);
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert!(!fs.is_poisoned());
+    assert!(fs.lookup(ROOT_INO, "pending").is_ok());
+    fs.create(ROOT_INO, "accepted", InodeType::RegularFile, 0o644).unwrap();
+    drop(fs);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 测试用的游程编码：(重复次数, 字节)对
+fn rle_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for run in data.chunk_by(|a, b| a == b) {
+        for part in run.chunks(255) {
+            out.extend_from_slice(&[part.len() as u8, part[0]]);
+        }
+    }
+    out
+}
+
+fn rle_decompress(src: &[u8], dst: &mut [u8]) -> Result<(), Ext4Error> {
+    let mut pos = 0;
+    for pair in src.chunks_exact(2) {
+        let run = dst
+            .get_mut(pos..pos + pair[0] as usize)
+            .ok_or_else(|| Ext4Error::new(5, "rle overflow"))?;
+        run.fill(pair[1]);
+        pos += run.len();
+    }
+    if pos != dst.len() {
+        return Err(Ext4Error::new(5, "rle underflow"));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_compressed_device() {
+    use lwext4_arce::{BlockDevice, CompressedDevice, MemoryDelta, OverlayDevice, encode_compressed_image};
+    use std::cell::Cell;
+
+    let path = copy_test_image("compressed");
+    let raw = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let image = encode_compressed_image(&raw, 64 * 1024, rle_compress).unwrap();
+    assert!(image.len() < raw.len() / 2, "image did not compress");
+
+    // 只解压实际读取的段
+    let decompressed = Cell::new(0);
+    let decompress = |src: &[u8], dst: &mut [u8]| {
+        decompressed.set(decompressed.get() + 1);
+        rle_decompress(src, dst)
+    };
+    let mut dev = CompressedDevice::new(&image, decompress).unwrap();
+    assert_eq!(dev.num_blocks().unwrap() * 512, raw.len() as u64);
+    let mut block = [0u8; 512];
+    dev.read_blocks(2, &mut block).unwrap();
+    assert_eq!(&block[..], &raw[1024..1536]);
+    assert!(dev.write_blocks(2, &block).is_err());
+
+    let config = FsConfig {
+        read_only: true,
+        ..FsConfig::default()
+    };
+    let mut fs = Ext4Filesystem::<DummyHal, _>::new(dev, config).unwrap();
+    let ino = fs.lookup(ROOT_INO, "test.txt").unwrap().entry().ino();
+    let mut buf = [0u8; 64];
+    assert!(fs.read_at(ino, &mut buf, 0).unwrap() > 0);
+    drop(fs);
+    let chunks = raw.len().div_ceil(64 * 1024);
+    assert!(decompressed.get() < chunks, "decompressed {} of {chunks} chunks", decompressed.get());
+
+    // 叠加写时复制层后可写
+    let dev = CompressedDevice::new(&image, rle_decompress).unwrap();
+    let mut fs = Ext4Filesystem::<DummyHal, _>::new(
+        OverlayDevice::new(dev, MemoryDelta::new()),
+        FsConfig::default(),
+    )
+    .unwrap();
+    fs.create(ROOT_INO, "ota", InodeType::RegularFile, 0o644).unwrap();
+    assert!(fs.lookup(ROOT_INO, "ota").is_ok());
+
+    // 格式错误的镜像
+    assert!(CompressedDevice::new(&raw[..4096], rle_decompress).is_err());
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_tune_superblock() {
+    use lwext4_arce::ffi::{EXT4_DEFM_ACL, EXT4_DEFM_XATTR_USER};
+
+    let path = copy_test_image("tune");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let uuid = *b"provisioned-uuid";
+    fs.set_volume_name("rootfs").unwrap();
+    fs.set_uuid(uuid).unwrap();
+    fs.set_reserved_percent(10).unwrap();
+    fs.set_default_mount_opts(EXT4_DEFM_ACL | EXT4_DEFM_XATTR_USER).unwrap();
+    let err = fs.set_volume_name("a-label-longer-than-16").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+    assert!(fs.set_reserved_percent(51).is_err());
+    assert!(fs.set_default_mount_opts(0x8000_0000).is_err());
+
+    // 关闭校验和后修改文件系统，再重新启用
+    fs.disable_metadata_csum().unwrap();
+    let dir = fs.create(ROOT_INO, "plain", InodeType::Directory, 0o755).unwrap();
+    let file = fs.create(dir, "data", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, b"written without checksums", 0).unwrap();
+    fs.enable_metadata_csum().unwrap();
+    let names = list_dir(&mut fs, ROOT_INO);
+    drop(fs);
+
+    let sb = std::fs::read(&path).unwrap()[1024..2048].to_vec();
+    let le32 = |off: usize| u32::from_le_bytes(sb[off..off + 4].try_into().unwrap());
+    assert_ne!(le32(0x64) & 0x400, 0, "metadata_csum");
+    assert_eq!(le32(0x3FC), crc32c(!0, &sb[..0x3FC]));
+    assert_eq!(le32(0x08) as u64, le32(0x04) as u64 / 10);
+    assert_eq!(le32(0x100), EXT4_DEFM_ACL | EXT4_DEFM_XATTR_USER);
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert_eq!(fs.volume_name(), b"rootfs");
+    assert_eq!(fs.uuid(), uuid);
+    assert_eq!(list_dir(&mut fs, ROOT_INO), names);
+    let file = fs.resolve_path("/plain/data").unwrap();
+    let mut buf = [0u8; 25];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), buf.len());
+    assert_eq!(&buf, b"written without checksums");
+    fs.create(dir, "more", InodeType::RegularFile, 0o644).unwrap();
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 固定种子的xorshift随机数（测试结果可复现）
+#[cfg(feature = "use-rust")]
+struct XorShift(u64);
+
+#[cfg(feature = "use-rust")]
+impl lwext4_arce::RandomSource for XorShift {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            *b = self.0 as u8;
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_mkfs() {
+    use lwext4_arce::MkfsOptions;
+
+    let path = std::env::temp_dir().join(format!("lwext4-mkfs-{}.ext4", std::process::id()));
+    std::fs::File::create(&path).unwrap().set_len(16 << 20).unwrap();
+    let opts = MkfsOptions {
+        block_size: 1024,
+        label: "scratch".into(),
+        ..Default::default()
+    };
+    let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+    let dev = FileBlockDevice::open(path.to_str().unwrap()).unwrap();
+    let mut fs = Fs::mkfs(dev, &opts, FsConfig::default(), &mut rng).unwrap();
+    let uuid = fs.uuid();
+    assert_eq!(uuid[6] >> 4, 4, "uuid version");
+    assert_eq!(uuid[8] >> 6, 0b10, "uuid variant");
+    assert_eq!(fs.volume_name(), b"scratch");
+    let stat = fs.stat().unwrap();
+    assert_eq!(stat.block_size, 1024);
+    assert_eq!(stat.blocks_count, 16 << 10);
+    assert_eq!(stat.inodes_count - stat.free_inodes_count, 11);
+    assert_eq!(list_dir(&mut fs, ROOT_INO), ["lost+found"]);
+
+    let dir = fs.create(ROOT_INO, "etc", InodeType::Directory, 0o755).unwrap();
+    let file = fs.create(dir, "hostname", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, b"scratch\n", 0).unwrap();
+    fs.set_random_uuid(&mut rng).unwrap();
+    let new_uuid = fs.uuid();
+    assert_ne!(new_uuid, uuid);
+    drop(fs);
+
+    let dev = FileBlockDevice::open(path.to_str().unwrap()).unwrap();
+    let mut fs = Fs::new(dev, FsConfig::default()).unwrap();
+    assert_eq!(fs.uuid(), new_uuid);
+    let file = fs.resolve_path("/etc/hostname").unwrap();
+    let mut buf = [0u8; 8];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), 8);
+    assert_eq!(&buf, b"scratch\n");
+    drop(fs);
+
+    // 不支持的参数不会写入设备
+    let dev = FileBlockDevice::open(path.to_str().unwrap()).unwrap();
+    let opts = MkfsOptions { block_size: 3000, ..Default::default() };
+    let err = Fs::mkfs(dev, &opts, FsConfig::default(), &mut rng).err().unwrap();
+    assert_eq!(err.kind(), Ext4ErrorKind::InvalidInput);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 每次读取前进1秒的系统时间（两次运行得到的时间戳不同）
+struct AdvancingHal;
+impl SystemHal for AdvancingHal {
+    fn now() -> Option<Duration> {
+        static SECS: AtomicU64 = AtomicU64::new(1_700_000_000);
+        Some(Duration::from_secs(SECS.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_reproducible_mkfs() {
+    use lwext4_arce::MkfsOptions;
+
+    let epoch = Duration::from_secs(1_600_000_000);
+    let build = |name: &str, reproducible: Option<Duration>, seed: u64| {
+        let path = std::env::temp_dir().join(format!("lwext4-repro-{name}-{}.ext4", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        std::fs::File::create(&path).unwrap().set_len(16 << 20).unwrap();
+        let opts = MkfsOptions { block_size: 1024, label: "rootfs".into(), ..Default::default() };
+        let config = FsConfig { reproducible, ..Default::default() };
+        let dev = FileBlockDevice::open(&path).unwrap();
+        let mut fs = Ext4Filesystem::<AdvancingHal, _>::mkfs(dev, &opts, config, &mut XorShift(seed)).unwrap();
+        let etc = fs.create_dir_all("/etc/init.d", 0o755).unwrap();
+        let file = fs.create(etc, "rcS", InodeType::RegularFile, 0o755).unwrap();
+        fs.write_at(file, &[0x5a; 5000], 0).unwrap();
+        // VFS层写入后更新时间戳
+        fs.with_inode_ref(file, |inode| {
+            inode.update_atime();
+            inode.update_mtime();
+            inode.update_ctime();
+            Ok(())
+        })
+        .unwrap();
+        let link = fs.create(ROOT_INO, "sh", InodeType::Symlink, 0o777).unwrap();
+        fs.set_symlink(link, b"/bin/busybox").unwrap();
+        let bin = fs.create(ROOT_INO, "bin", InodeType::Directory, 0o755).unwrap();
+        for i in 0..300 {
+            fs.create(bin, &format!("applet-with-a-long-name-{i:04}"), InodeType::RegularFile, 0o755).unwrap();
+        }
+        let uuid = fs.uuid();
+        let mut attr = FileAttr::default();
+        fs.get_attr(file, &mut attr).unwrap();
+        drop(fs);
+        (path, uuid, attr)
+    };
+
+    let (a, uuid, attr) = build("a", Some(epoch), 1);
+    let (b, _, _) = build("b", Some(epoch), 2);
+    assert_eq!(attr.btime, Some(epoch));
+    assert_eq!((attr.atime, attr.mtime, attr.ctime), (epoch, epoch, epoch));
+    assert_eq!(uuid[6] >> 4, 4, "uuid version");
+    assert_eq!(std::fs::read(&a).unwrap(), std::fs::read(&b).unwrap());
+    assert_fsck_clean(&a);
+
+    // 不同的固定时间或非可重现模式下镜像不同
+    let (c, _, _) = build("c", Some(epoch + Duration::from_secs(1)), 1);
+    let (d, _, attr) = build("d", None, 1);
+    let (e, _, _) = build("e", None, 1);
+    assert!(attr.mtime > epoch, "non-reproducible mounts use SystemHal::now");
+    assert_ne!(std::fs::read(&a).unwrap(), std::fs::read(&c).unwrap());
+    assert_ne!(std::fs::read(&d).unwrap(), std::fs::read(&e).unwrap());
+    for path in [a, b, c, d, e] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_alloc_goal_from_parent() {
+    use lwext4_arce::{MkfsOptions, RamDisk};
+
+    // 每个块组只有16个inode，新文件的inode很快落到根目录以外的块组
+    let opts = MkfsOptions { block_size: 1024, inodes: 64, journal: false, ..Default::default() };
+    let disk = RamDisk::new(32 << 20);
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(disk, &opts, FsConfig::default(), &mut XorShift(3)).unwrap();
+    let inodes_per_group = fs.stat().unwrap().inodes_count / fs.group_count();
+    let mut ino = ROOT_INO;
+    for i in 0.. {
+        ino = fs.create(ROOT_INO, format!("f{i}").as_str(), InodeType::RegularFile, 0o644).unwrap();
+        if (ino - 1) / inodes_per_group != 0 {
+            break;
+        }
+    }
+    let free_of = |fs: &mut Ext4Filesystem<DummyHal, RamDisk>| {
+        let report = fs.free_space_report().unwrap();
+        report.groups.iter().map(|g| g.free_blocks).collect::<Vec<_>>()
+    };
+
+    // 首个数据块分配在根目录所在的块组，而不是新inode所在的块组
+    let before = free_of(&mut fs);
+    fs.write_at(ino, &[1; 1024], 0).unwrap();
+    let after = free_of(&mut fs);
+    assert_eq!(after[0], before[0] - 1);
+    assert_eq!(after[1..], before[1..]);
+
+    // 子目录下的文件跟随子目录所在的块组
+    let dir = fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    let group = ((dir - 1) / inodes_per_group) as usize;
+    assert_ne!(group, 0);
+    let file = fs.create(dir, "file", InodeType::RegularFile, 0o644).unwrap();
+    let before = free_of(&mut fs);
+    fs.write_at(file, &[2; 1024], 0).unwrap();
+    let after = free_of(&mut fs);
+    for (g, (a, b)) in after.iter().zip(&before).enumerate() {
+        assert_eq!(*a, if g == group { b - 1 } else { *b }, "group {g}");
+    }
+}
+
+/// 要求缓冲区按4KiB对齐的内存盘（检查每次读写的缓冲区地址）
+#[cfg(feature = "use-rust")]
+struct AlignedDisk(lwext4_arce::RamDisk, usize);
+
+#[cfg(feature = "use-rust")]
+impl lwext4_arce::BlockDevice for AlignedDisk {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Result<usize, Ext4Error> {
+        assert_eq!(buf.as_ptr() as usize % self.1, 0, "unaligned write at {block_id}");
+        self.0.write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Result<usize, Ext4Error> {
+        assert_eq!(buf.as_ptr() as usize % self.1, 0, "unaligned read at {block_id}");
+        self.0.read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> Result<u64, Ext4Error> {
+        self.0.num_blocks()
+    }
+
+    fn alignment(&self) -> usize {
+        self.1
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_aligned_device_buffers() {
+    use lwext4_arce::{MkfsOptions, RamDisk, RandomSource};
+
+    // 1KiB的文件系统块小于设备要求的对齐，mkfs和日志的临时缓冲区都需要中转
+    let mut disk = AlignedDisk(RamDisk::new(8 << 20), 4096);
+    let opts = MkfsOptions { block_size: 1024, ..Default::default() };
+    let mut fs = Ext4Filesystem::<DummyHal, _>::mkfs(&mut disk, &opts, FsConfig::default(), &mut XorShift(4)).unwrap();
+    let mut data = vec![0u8; 40 << 10];
+    XorShift(11).fill_bytes(&mut data);
+    let ino = fs.create(ROOT_INO, "data", InodeType::RegularFile, 0o644).unwrap();
+    // 不对齐的切片和偏移
+    assert_eq!(fs.write_at(ino, &data[3..], 5).unwrap(), data.len() - 3);
+    fs.shrink_cache(0).unwrap();
+    let mut back = vec![0u8; data.len() + 1];
+    assert_eq!(fs.read_at(ino, &mut back[1..], 5).unwrap(), data.len() - 3);
+    assert_eq!(back[1..data.len() - 2], data[3..]);
+    drop(fs);
+
+    let mut fs = Ext4Filesystem::<DummyHal, _>::new(&mut disk, FsConfig::default()).unwrap();
+    let ino = fs.resolve_path("/data").unwrap();
+    let mut back = vec![0u8; data.len() - 3];
+    assert_eq!(fs.read_at(ino, &mut back, 5).unwrap(), back.len());
+    assert_eq!(back, data[3..]);
+    drop(fs);
+
+    // 对齐要求必须是2的幂
+    let err = Ext4Filesystem::<DummyHal, _>::new(AlignedDisk(RamDisk::new(1 << 20), 24), FsConfig::default())
+        .err()
+        .unwrap();
+    assert_eq!(err.errno(), 22);
+}
+
+#[test]
+fn test_file_device_errno() {
+    use lwext4_arce::BlockDevice;
+
+    // 宿主I/O错误保留操作系统的errno
+    if let Ok(mut dev) = FileBlockDevice::open("/dev/full") {
+        let err = dev.write_blocks(0, &[0u8; 512]).unwrap_err();
+        assert_eq!(err.errno(), errno::ENOSPC);
+    }
+    // 读到镜像末尾之后没有errno，按EIO处理
+    let path = copy_test_image("device-errno");
+    let mut dev = FileBlockDevice::open(&path).unwrap();
+    let end = dev.num_blocks().unwrap();
+    let err = dev.read_blocks(end, &mut [0u8; 512]).unwrap_err();
+    assert_eq!(err.errno(), errno::EIO);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_device_size_overflow() {
+    // 总字节数超出u64的设备在打开时被拒绝（而不是计算分区大小时溢出）
+    struct HugeDevice(FileBlockDevice);
+
+    impl lwext4_arce::BlockDevice for HugeDevice {
+        fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> Result<usize, Ext4Error> {
+            self.0.write_blocks(block_id, buf)
+        }
+
+        fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> Result<usize, Ext4Error> {
+            self.0.read_blocks(block_id, buf)
+        }
+
+        fn num_blocks(&self) -> Result<u64, Ext4Error> {
+            Ok(u64::MAX / 256)
+        }
+    }
+
+    let path = copy_test_image("device-overflow");
+    let dev = HugeDevice(FileBlockDevice::open(&path).unwrap());
+    let err = Ext4Filesystem::<DummyHal, HugeDevice>::new(dev, FsConfig::default()).err().unwrap();
+    assert_eq!(err.errno(), errno::EINVAL);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_core_device_shared_trait() {
+    use lwext4_core::{BlockDevice as _, read_superblock};
+
+    let path = copy_test_image("core-device");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let stat = fs.stat().unwrap();
+    drop(fs);
+
+    // 同一个设备实现直接用于lwext4_core的接口，物理块大于1024字节时同样可以读取superblock
+    for block_size in [512usize, 4096] {
+        let mut dev = FileBlockDevice::open_with_block_size(&path, block_size).unwrap();
+        assert_eq!(dev.block_size(), block_size);
+        let sb = read_superblock(&mut dev).unwrap();
+        assert_eq!(u32::from_le(sb.blocks_count_lo) as u64, stat.blocks_count);
+        // 可变引用同样是块设备
+        let sb = read_superblock(&mut &mut dev).unwrap();
+        assert_eq!(u32::from_le(sb.blocks_count_lo) as u64, stat.blocks_count);
+    }
+
+    // 旧的适配器只是转发，错误原样保留
+    #[allow(deprecated)]
+    {
+        let mut dev = lwext4_arce::CoreDevice(lwext4_arce::RamDisk::new(4096));
+        let expected = dev.0.read_blocks(8, &mut [0; 512]).unwrap_err();
+        let err = dev.read_blocks(8, &mut [0; 512]).unwrap_err();
+        assert_eq!(err.errno(), expected.errno());
+        let dev = dev.into_inner();
+        assert_eq!(dev.num_blocks().unwrap(), 8);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(all(feature = "fuzz", feature = "use-rust"))]
+fn test_fuzz_entry_points() {
+    use lwext4_arce::{RamDisk, RandomSource};
+    use lwext4_arce::fuzz::{Template, mount_and_walk};
+
+    let template = Template::new().unwrap();
+    let image = template.image().to_vec();
+    let mut fs = Ext4Filesystem::<DummyHal, _>::new(RamDisk::from_vec(image.clone()), FsConfig::default()).unwrap();
+    let file = fs.resolve_path("/fragmented").unwrap();
+    let mut buf = [0u8; 4];
+    assert_eq!(fs.read_at(file, &mut buf, 14 * 1024).unwrap(), 4);
+    assert_eq!(buf, [7; 4]);
+    drop(fs);
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    let mut noise = vec![0u8; 4096];
+    rng.fill_bytes(&mut noise);
+    let inputs: [&[u8]; 5] = [&[], &[0; 1024], &[0xFF; 1024], &noise, &image[1024..2048]];
+    for data in inputs {
+        template.superblock(data);
+        template.group_desc(data);
+        template.extent_node(data);
+        template.dir_block(data);
+    }
+
+    // 对整个镜像随机翻转若干字节
+    mount_and_walk(&image);
+    mount_and_walk(&image[..1000]);
+    for _ in 0..32 {
+        let mut corrupted = image.clone();
+        for _ in 0..64 {
+            let mut pos = [0u8; 4];
+            rng.fill_bytes(&mut pos);
+            let pos = u32::from_le_bytes(pos) as usize % (64 << 10);
+            corrupted[pos] ^= 1 << (pos % 8);
+        }
+        mount_and_walk(&corrupted);
+    }
+}
+
+#[test]
+fn test_differential_e2fsprogs() {
+    use common::e2fs::{Model, Op, mke2fs, run_script, verify_with_e2fsprogs};
+
+    let path = std::env::temp_dir().join(format!("lwext4-e2fs-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    if !mke2fs(path, 16 << 10, &["-b", "1024"]) {
+        return;
+    }
+    let big: Vec<u8> = (0..40_000u32).map(|i| (i * 7 + i / 1024) as u8).collect();
+    let long_target: &'static str = format!("/{}", "t".repeat(100)).leak();
+    let mut ops = vec![
+        Op::Mkdir("/etc"),
+        Op::Mkdir("/var"),
+        Op::Mkdir("/var/log"),
+        Op::Write("/etc/hostname", 0, b"lwext4\n".to_vec()),
+        Op::Write("/var/log/big", 0, big.clone()),
+        Op::Write("/var/log/big", 100_000, b"tail".to_vec()),
+        Op::Truncate("/var/log/big", 30_000),
+        Op::Write("/sparse", 1 << 20, b"end".to_vec()),
+        Op::Symlink("/etc/short", "hostname"),
+        Op::Symlink("/etc/long", long_target),
+        Op::Link("/etc/hostname", "/var/hostname"),
+        Op::Write("/var/old", 0, b"replaced".to_vec()),
+        Op::Rename("/etc/hostname", "/var/log/name"),
+        Op::Rename("/var/hostname", "/var/old"),
+        Op::Mkdir("/tmp"),
+        Op::Write("/tmp/scratch", 0, b"gone".to_vec()),
+        Op::Remove("/tmp/scratch"),
+        Op::Remove("/tmp"),
+        Op::Mkdir("/many"),
+    ];
+    // 目录增长到多个块
+    for i in 0..40 {
+        let path: &'static str = format!("/many/a-fairly-long-entry-name-{i:03}").leak();
+        ops.push(Op::Write(path, 0, path.as_bytes().to_vec()));
+    }
+    ops.push(Op::Remove("/many/a-fairly-long-entry-name-007"));
+    ops.push(Op::Rename("/var/log", "/many/log"));
+    ops.push(Op::Truncate("/sparse", 10));
+
+    let mut model = Model::default();
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    run_script(&mut fs, &mut model, &ops);
+    drop(fs);
+    verify_with_e2fsprogs(path, &model);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_alloc_goal_per_inode() {
+    use common::e2fs::{debugfs, mke2fs};
+
+    // 不使用extent时分配目标取自文件最后一块，写入稀疏文件的中间时需要inode自己记录的上次分配位置
+    let path = std::env::temp_dir().join(format!("lwext4-alloc-goal-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    if !mke2fs(path, 8 << 10, &["-b", "1024", "-O", "^extent,^64bit,^flex_bg"]) {
+        return;
+    }
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    let filler = fs.create(ROOT_INO, "filler", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(filler, &[1; 1024], 0).unwrap();
+    let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at_sparse(file, &[0], (8 << 10) - 1).unwrap();
+    fs.write_at(file, &[2; 1024], 0).unwrap();
+    // 删除后在文件的第一块之前留下空洞，此后的写入不应填进去
+    fs.unlink(ROOT_INO, "filler").unwrap();
+    let other = fs.create(ROOT_INO, "other", InodeType::RegularFile, 0o644).unwrap();
+    for i in 1..4u64 {
+        fs.write_at(file, &[2; 1024], i * 1024).unwrap();
+        fs.write_at(other, &[3; 1024], (i - 1) * 1024).unwrap();
+    }
+    drop(fs);
+    assert_fsck_clean(path);
+
+    let blocks = |name: &str| -> Vec<u64> {
+        String::from_utf8(debugfs(path, &format!("blocks /{name}")))
+            .unwrap()
+            .split_whitespace()
+            .map(|b| b.parse().unwrap())
+            .collect()
+    };
+    let file_blocks = blocks("file");
+    assert_eq!(file_blocks.len(), 4);
+    // 与other交替写入，每次都紧接在上次分配的块之后查找
+    assert_eq!(file_blocks[1], file_blocks[0] + 1);
+    assert!(file_blocks.windows(2).all(|w| w[1] > w[0]), "{file_blocks:?}");
+    let other_blocks = blocks("other");
+    assert!(other_blocks.iter().min() < file_blocks.iter().min(), "{other_blocks:?} {file_blocks:?}");
+    std::fs::remove_file(path).unwrap();
+}
+
+/// 用e2fsck只读检查镜像（宿主没有e2fsck时跳过）
+fn assert_fsck_clean(path: &str) {
+    use std::process::Command;
+    let Ok(output) = Command::new("e2fsck").args(["-fn", path]).output() else {
+        return;
+    };
+    assert!(
+        output.status.success(),
+        "e2fsck failed:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_4kn_device() {
+    use lwext4_arce::{MemoryDelta, MkfsOptions, OverlayDevice};
+
+    fn write_and_check<D: lwext4_arce::BlockDevice>(fs: &mut Ext4Filesystem<DummyHal, D>, name: &str) {
+        let ino = fs.create(ROOT_INO, name, InodeType::RegularFile, 0o644).unwrap();
+        let data: Vec<u8> = (0..20000u32).map(|i| (i % 251) as u8).collect();
+        fs.write_at(ino, &data, 1000).unwrap();
+        let mut buf = vec![0u8; data.len()];
+        fs.read_at(ino, &mut buf, 1000).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    // 4KiB块的文件系统位于4Kn设备上：逻辑块与物理块一一对应
+    let path = copy_test_image("4kn");
+    {
+        let dev = FileBlockDevice::open_with_block_size(&path, 4096).unwrap();
+        let mut fs = Ext4Filesystem::<DummyHal, _>::new(dev, FsConfig::default()).unwrap();
+        write_and_check(&mut fs, "on-4kn");
+    }
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+        let ino = fs.lookup(ROOT_INO, "on-4kn").unwrap().entry().ino();
+        let mut attr = FileAttr::default();
+        fs.get_attr(ino, &mut attr).unwrap();
+        assert_eq!(attr.size, 21000);
+    }
+    assert_fsck_clean(&path);
+
+    // 写时复制层叠加在4Kn设备上
+    {
+        let base = FileBlockDevice::open_with_block_size(&path, 4096).unwrap();
+        let dev = OverlayDevice::new(base, MemoryDelta::new());
+        let mut fs = Ext4Filesystem::<DummyHal, _>::new(dev, FsConfig::default()).unwrap();
+        write_and_check(&mut fs, "in-overlay");
+        assert!(fs.lookup(ROOT_INO, "on-4kn").is_ok());
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // 1KiB块的文件系统位于4Kn设备上：逻辑块小于物理块，按物理块先读后写
+    let path = std::env::temp_dir().join(format!("lwext4-1k-on-4kn-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(8 << 20).unwrap();
+    {
+        let dev = FileBlockDevice::open_with_block_size(path, 4096).unwrap();
+        let opts = MkfsOptions { block_size: 1024, ..Default::default() };
+        let mut fs = Fs::mkfs(dev, &opts, FsConfig::default(), &mut XorShift(1)).unwrap();
+        write_and_check(&mut fs, "small-blocks");
+        fs.create(ROOT_INO, "dir", InodeType::Directory, 0o755).unwrap();
+    }
+    for sector in [512, 2048, 4096] {
+        let dev = FileBlockDevice::open_with_block_size(path, sector).unwrap();
+        let mut fs = Ext4Filesystem::<DummyHal, _>::new(dev, FsConfig::default()).unwrap();
+        assert_eq!(fs.stat().unwrap().block_size, 1024);
+        let ino = fs.lookup(ROOT_INO, "small-blocks").unwrap().entry().ino();
+        let mut buf = [0u8; 4];
+        fs.read_at(ino, &mut buf, 1000 + 251).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3]);
+    }
+    assert_fsck_clean(path);
+
+    // 物理块大小必须是2的幂
+    let dev = FileBlockDevice::open_with_block_size(path, 3000).unwrap();
+    assert!(Ext4Filesystem::<DummyHal, _>::new(dev, FsConfig::default()).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+/// 小块文件系统上的综合操作：多块目录、跨块组的大文件、删除与截断
+fn small_block_workload<D: lwext4_arce::BlockDevice>(fs: &mut Ext4Filesystem<DummyHal, D>) {
+    let dir = fs.create(ROOT_INO, "many", InodeType::Directory, 0o755).unwrap();
+    for i in 0..300 {
+        let ino = fs.create(dir, &format!("file-{i:04}"), InodeType::RegularFile, 0o644).unwrap();
+        fs.write_at(ino, format!("content {i}").as_bytes(), 0).unwrap();
+    }
+    let big = fs.create(ROOT_INO, "big", InodeType::RegularFile, 0o644).unwrap();
+    let chunk: Vec<u8> = (0..65536u32).map(|i| (i * 7 % 253) as u8).collect();
+    for i in 0..160 {
+        fs.write_at(big, &chunk, i * chunk.len() as u64).unwrap();
+    }
+    for i in (0..300).step_by(2) {
+        fs.unlink(dir, &format!("file-{i:04}")).unwrap();
+    }
+    fs.set_len(big, 3 * chunk.len() as u64 + 100).unwrap();
+    // 经过日志提交的修改
+    let mut trans = fs.begin_transaction().unwrap();
+    trans.rename(ROOT_INO, "big", dir, "big-moved").unwrap();
+    trans.create(ROOT_INO, "journaled", InodeType::Directory, 0o755).unwrap();
+    trans.commit().unwrap();
+    fs.flush().unwrap();
+}
+
+/// 检查small_block_workload的结果
+fn check_small_block_workload<D: lwext4_arce::BlockDevice>(fs: &mut Ext4Filesystem<DummyHal, D>) {
+    let dir = fs.lookup(ROOT_INO, "many").unwrap().entry().ino();
+    let names = list_dir_generic(fs, dir);
+    assert_eq!(names.len(), 151);
+    assert!(fs.lookup(ROOT_INO, "journaled").is_ok());
+    for i in (1..300).step_by(2) {
+        let ino = fs.lookup(dir, &format!("file-{i:04}")).unwrap().entry().ino();
+        let mut buf = vec![0u8; 32];
+        let n = fs.read_at(ino, &mut buf, 0).unwrap();
+        assert_eq!(&buf[..n], format!("content {i}").as_bytes());
+    }
+    let big = fs.lookup(dir, "big-moved").unwrap().entry().ino();
+    let mut attr = FileAttr::default();
+    fs.get_attr(big, &mut attr).unwrap();
+    assert_eq!(attr.size, 3 * 65536 + 100);
+    let mut buf = [0u8; 100];
+    fs.read_at(big, &mut buf, 3 * 65536).unwrap();
+    assert!(buf.iter().enumerate().all(|(i, &b)| b == (i as u32 * 7 % 253) as u8));
+}
+
+fn list_dir_generic<D: lwext4_arce::BlockDevice>(fs: &mut Ext4Filesystem<DummyHal, D>, dir: u32) -> Vec<String> {
+    let mut reader = fs.read_dir(dir, 0).unwrap();
+    let mut names = Vec::new();
+    while let Some(entry) = reader.current() {
+        let name = String::from_utf8(entry.name().to_vec()).unwrap();
+        if name != "." && name != ".." {
+            names.push(name);
+        }
+        reader.step().unwrap();
+    }
+    names
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_small_block_sizes() {
+    use lwext4_arce::MkfsOptions;
+    use std::process::Command;
+
+    for block_size in [1024u32, 2048] {
+        // mke2fs创建的镜像（1KiB块时first_data_block为1）
+        let path = std::env::temp_dir().join(format!("lwext4-bs{block_size}-{}.ext4", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::File::create(path).unwrap().set_len(32 << 20).unwrap();
+        let made = Command::new("mkfs.ext4")
+            .args(["-q", "-F", "-b", &block_size.to_string(), path])
+            .status()
+            .is_ok_and(|status| status.success());
+        if made {
+            {
+                let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+                assert_eq!(fs.stat().unwrap().block_size, block_size);
+                small_block_workload(&mut fs);
+            }
+            let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+            check_small_block_workload(&mut fs);
+            drop(fs);
+            assert_fsck_clean(path);
+        }
+
+        // 自身格式化的镜像
+        let opts = MkfsOptions { block_size, ..Default::default() };
+        {
+            let dev = FileBlockDevice::open(path).unwrap();
+            let mut fs = Fs::mkfs(dev, &opts, FsConfig::default(), &mut XorShift(7)).unwrap();
+            small_block_workload(&mut fs);
+        }
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        check_small_block_workload(&mut fs);
+        drop(fs);
+        assert_fsck_clean(path);
+
+        // 日志块与文件系统块一样大：提交后、写回前断电，挂载时回放
+        let pristine = std::fs::read(path).unwrap();
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let mut trans = fs.begin_transaction().unwrap();
+        trans.create(ROOT_INO, "replayed", InodeType::Directory, 0o755).unwrap();
+        trans.commit().unwrap();
+        const JOURNAL_INO: u32 = 8;
+        let mut attr = FileAttr::default();
+        fs.get_attr(JOURNAL_INO, &mut attr).unwrap();
+        let mut log = vec![0u8; attr.size as usize];
+        assert_eq!(fs.read_at(JOURNAL_INO, &mut log, 0).unwrap(), log.len());
+        drop(fs);
+        std::fs::write(path, &pristine).unwrap();
+
+        let be32 = |buf: &[u8], off: usize| u32::from_be_bytes(buf[off..off + 4].try_into().unwrap());
+        let first = be32(&log, 0x14);
+        let tid = be32(&log, 0x18) - 1;
+        log[0x1C..0x20].copy_from_slice(&first.to_be_bytes());
+        log[0x18..0x1C].copy_from_slice(&tid.to_be_bytes());
+        log[0xFC..0x100].fill(0);
+        let csum = crc32c(!0, &log[..1024]);
+        log[0xFC..0x100].copy_from_slice(&csum.to_be_bytes());
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        fs.write_at(JOURNAL_INO, &log, 0).unwrap();
+        drop(fs);
+        patch_superblock(path, |sb| sb[0x60] |= 0x4);
+
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        assert!(fs.lookup(ROOT_INO, "replayed").is_ok());
+        check_small_block_workload(&mut fs);
+        drop(fs);
+        assert_fsck_clean(path);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_alloc_skips_metadata() {
+    use lwext4_arce::MkfsOptions;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let path = std::env::temp_dir().join(format!("lwext4-meta-guard-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(8 << 20).unwrap();
+    let opts = MkfsOptions { journal: false, metadata_csum: false, reserved_percent: 0, ..Default::default() };
+    drop(Fs::mkfs(FileBlockDevice::open(path).unwrap(), &opts, FsConfig::default(), &mut XorShift(3)).unwrap());
+
+    // 损坏块组0的块位图：inode表被标记为空闲，空闲计数也相应增加
+    let le16 = |buf: &[u8], off: usize| u16::from_le_bytes(buf[off..off + 2].try_into().unwrap());
+    let le32 = |buf: &[u8], off: usize| u32::from_le_bytes(buf[off..off + 4].try_into().unwrap());
+    let mut file = std::fs::File::options().read(true).write(true).open(path).unwrap();
+    let mut sb = vec![0u8; 1024];
+    file.seek(SeekFrom::Start(1024)).unwrap();
+    file.read_exact(&mut sb).unwrap();
+    let bs = 1024u64 << le32(&sb, 0x18);
+    let itable_blocks = (le32(&sb, 0x28) as u64 * le16(&sb, 0x58) as u64).div_ceil(bs) as u16;
+    let gdt = (le32(&sb, 0x14) as u64 + 1) * bs;
+    let mut desc = [0u8; 32];
+    file.seek(SeekFrom::Start(gdt)).unwrap();
+    file.read_exact(&mut desc).unwrap();
+    let (bitmap_blk, itable) = (le32(&desc, 0) as u64, le32(&desc, 8) as u64);
+    let mut bitmap = vec![0u8; bs as usize];
+    file.seek(SeekFrom::Start(bitmap_blk * bs)).unwrap();
+    file.read_exact(&mut bitmap).unwrap();
+    for blk in itable..itable + itable_blocks as u64 {
+        bitmap[blk as usize / 8] &= !(1 << (blk % 8));
+    }
+    file.seek(SeekFrom::Start(bitmap_blk * bs)).unwrap();
+    file.write_all(&bitmap).unwrap();
+    let free = le16(&desc, 0x0C) + itable_blocks;
+    desc[0x0C..0x0E].copy_from_slice(&free.to_le_bytes());
+    file.seek(SeekFrom::Start(gdt)).unwrap();
+    file.write_all(&desc).unwrap();
+    drop(file);
+    patch_superblock(path, |sb| {
+        let free = le32(sb, 0x0C) + itable_blocks as u32;
+        sb[0x0C..0x10].copy_from_slice(&free.to_le_bytes());
+    });
+
+    // 写满文件系统：位图中“空闲”的inode表块不会被分配出去（最后一次写入失败并撤销）
+    let chunk = vec![0xA5u8; bs as usize];
+    let mut pos = 0;
+    {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        let ino = fs.create(ROOT_INO, "fill", InodeType::RegularFile, 0o644).unwrap();
+        while fs.write_at(ino, &chunk, pos).is_ok() {
+            pos += bs;
+        }
+        assert!(pos > 0 && pos <= (free as u64 - itable_blocks as u64) * bs);
+        fs.flush().unwrap();
+    }
+    // inode表没有被覆盖
+    let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+    assert!(list_dir_generic(&mut fs, ROOT_INO).contains(&"fill".to_string()));
+    let ino = fs.lookup(ROOT_INO, "fill").unwrap().entry().ino();
+    let mut attr = FileAttr::default();
+    fs.get_attr(ino, &mut attr).unwrap();
+    assert_eq!(attr.size, pos);
+    let mut buf = vec![0u8; bs as usize];
+    fs.read_at(ino, &mut buf, pos - bs).unwrap();
+    assert_eq!(buf, chunk);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_gdt_csum() {
+    use std::process::Command;
+
+    // uninit_bg（gdt_csum）且没有metadata_csum：块组描述符用crc16校验
+    for features in ["^metadata_csum,uninit_bg", "^metadata_csum,uninit_bg,64bit"] {
+        let path = std::env::temp_dir().join(format!("lwext4-gdt-csum-{}.ext4", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::File::create(path).unwrap().set_len(32 << 20).unwrap();
+        let made = Command::new("mkfs.ext4")
+            .args(["-q", "-F", "-b", "1024", "-O", features, path])
+            .status()
+            .is_ok_and(|status| status.success());
+        if !made {
+            std::fs::remove_file(path).unwrap();
+            return;
+        }
+        {
+            let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+            assert!(!fs.is_read_only());
+            small_block_workload(&mut fs);
+        }
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        check_small_block_workload(&mut fs);
+        drop(fs);
+        assert_fsck_clean(path);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+/// 在inode内扩展属性区写入属性（索引, 名称, 值），值从区域末尾向前存放
+#[cfg(feature = "use-rust")]
+fn set_ibody_xattrs(raw: &mut [u8], attrs: &[(u8, &str, &[u8])]) {
+    let start = 128 + u16::from_le_bytes([raw[0x80], raw[0x81]]) as usize;
+    raw[start..].fill(0);
+    raw[start..start + 4].copy_from_slice(&0xEA02_0000u32.to_le_bytes());
+    let area = &mut raw[start + 4..];
+    let (mut off, mut value_end) = (0, area.len());
+    for &(index, name, value) in attrs {
+        value_end -= (value.len() + 3) & !3;
+        area[off] = name.len() as u8;
+        area[off + 1] = index;
+        area[off + 2..off + 4].copy_from_slice(&(value_end as u16).to_le_bytes());
+        area[off + 8..off + 12].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        area[off + 16..off + 16 + name.len()].copy_from_slice(name.as_bytes());
+        area[value_end..value_end + value.len()].copy_from_slice(value);
+        off += (16 + name.len() + 3) & !3;
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_xattr_write() {
+    let path = copy_test_image("xattr-write");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let file = fs.create(ROOT_INO, "file", InodeType::RegularFile, 0o644).unwrap();
+    fs.write_at(file, &[7u8; 10000], 0).unwrap();
+
+    fs.set_xattr(file, "user.comment", b"hello").unwrap();
+    fs.set_xattr(file, "trusted.empty", b"").unwrap();
+    fs.set_xattr(file, "user.comment", b"replaced").unwrap();
+    assert_eq!(fs.list_xattr(file).unwrap(), ["user.comment", "trusted.empty"]);
+    assert_eq!(fs.get_xattr(file, "user.comment").unwrap().unwrap(), b"replaced");
+    assert_eq!(fs.get_xattr(file, "trusted.empty").unwrap().unwrap(), b"");
+
+    fs.remove_xattr(file, "trusted.empty").unwrap();
+    assert_eq!(fs.list_xattr(file).unwrap(), ["user.comment"]);
+    let err = fs.remove_xattr(file, "trusted.empty").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotFound);
+    // 内联数据等内部属性不能直接修改
+    let err = fs.set_xattr(file, "system.data", b"x").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Unsupported);
+    // inode内的扩展属性区放不下
+    let err = fs.set_xattr(file, "user.big", &[0u8; 4096]).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NoSpace);
+    assert_eq!(fs.list_xattr(file).unwrap(), ["user.comment"]);
+
+    // 截断既能缩小也能以空洞扩展文件，扩展的部分读出为0
+    fs.truncate(file, 100).unwrap();
+    let attr = fs.stat_many(&[file]).unwrap()[0].clone();
+    assert_eq!(attr.size, 100);
+    fs.truncate(file, 50000).unwrap();
+    let extended = fs.stat_many(&[file]).unwrap()[0].clone();
+    assert_eq!(extended.size, 50000);
+    assert_eq!(extended.blocks, attr.blocks);
+    let mut buf = vec![0xffu8; 50000];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), 50000);
+    assert!(buf[..100].iter().all(|&b| b == 7));
+    assert!(buf[100..].iter().all(|&b| b == 0));
+
+    let link = fs.create(ROOT_INO, "link", InodeType::Symlink, 0o777).unwrap();
+    fs.set_symlink(link, b"file").unwrap();
+    assert_eq!(fs.read_link(link).unwrap(), b"file");
+    drop(fs);
+
+    assert_fsck_clean(&path);
+    let out = common::e2fs::debugfs(&path, "ea_get /file user.comment");
+    assert!(String::from_utf8_lossy(&out).contains("replaced"));
+}
+
+/// 按密钥标识返回固定密钥
+#[cfg(feature = "use-rust")]
+struct FixedKeys(lwext4_arce::MasterKeySpec);
+
+#[cfg(feature = "use-rust")]
+impl lwext4_arce::KeyProvider for FixedKeys {
+    fn master_key(&self, key: &lwext4_arce::MasterKeySpec) -> Option<Vec<u8>> {
+        (*key == self.0).then(|| vec![0x5A; 64])
+    }
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_encrypted_inodes() {
+    use lwext4_arce::{EncryptionPolicy, MasterKeySpec};
+
+    let path = copy_test_image("fscrypt");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let vault = fs.create(ROOT_INO, "vault", InodeType::Directory, 0o700).unwrap();
+    let secret = fs.create(vault, "secret", InodeType::RegularFile, 0o600).unwrap();
+    fs.write_at(secret, b"ciphertext", 0).unwrap();
+    let empty = fs.create(ROOT_INO, "empty", InodeType::Directory, 0o700).unwrap();
+    drop(fs);
+
+    // 模拟 mkfs -O encrypt 后由内核创建的加密目录：加密标志 + v1加密上下文
+    patch_superblock(&path, |sb| {
+        let incompat = u32::from_le_bytes(sb[0x60..0x64].try_into().unwrap()) | 0x10000;
+        sb[0x60..0x64].copy_from_slice(&incompat.to_le_bytes());
+    });
+    let descriptor = *b"\x01\x23\x45\x67\x89\xab\xcd\xef";
+    let context = |nonce: u8| {
+        let mut ctx = vec![1, 1, 4, 2];
+        ctx.extend_from_slice(&descriptor);
+        ctx.extend_from_slice(&[nonce; 16]);
+        ctx
+    };
+    for (ino, nonce) in [(vault, 1u8), (secret, 2), (empty, 3)] {
+        patch_inode(&path, ino, |raw| {
+            let flags = u32::from_le_bytes(raw[0x20..0x24].try_into().unwrap()) | 0x800;
+            raw[0x20..0x24].copy_from_slice(&flags.to_le_bytes());
+            set_ibody_xattrs(raw, &[(1, "comment", b"hello"), (9, "c", &context(nonce))]);
+        });
+    }
+
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert!(fs.is_encrypted(secret).unwrap());
+    assert!(!fs.is_encrypted(ROOT_INO).unwrap());
+    assert_eq!(fs.encryption_policy(ROOT_INO).unwrap(), None);
+    let policy = fs.encryption_policy(secret).unwrap().unwrap();
+    assert_eq!(
+        policy,
+        EncryptionPolicy {
+            version: 1,
+            contents_mode: 1,
+            filenames_mode: 4,
+            flags: 2,
+            key: MasterKeySpec::Descriptor(descriptor),
+            nonce: [2; 16],
+        }
+    );
+
+    // 加密上下文不出现在属性列表中
+    assert_eq!(fs.list_xattr(secret).unwrap(), ["user.comment"]);
+    assert_eq!(fs.get_xattr(secret, "user.comment").unwrap().unwrap(), b"hello");
+    assert_eq!(fs.get_xattr(secret, "user.missing").unwrap(), None);
+    assert_eq!(fs.get_xattr(ROOT_INO, "user.comment").unwrap(), None);
+    let err = fs.get_xattr(secret, "bogus.comment").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Unsupported);
+
+    // 内容和目录项不可访问，元数据仍然可读
+    let mut buf = [0u8; 16];
+    let err = fs.read_at(secret, &mut buf, 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Encrypted);
+    assert_eq!(err.errno(), 126);
+    assert_eq!(err.ino(), Some(secret));
+    let err = fs.write_at(secret, b"plain", 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::Encrypted);
+    assert_eq!(fs.lookup(vault, "secret").err().unwrap().kind(), Ext4ErrorKind::Encrypted);
+    assert_eq!(fs.read_dir(vault, 0).err().unwrap().kind(), Ext4ErrorKind::Encrypted);
+    let mut attr = FileAttr::default();
+    fs.get_attr(secret, &mut attr).unwrap();
+    assert_eq!(attr.size, 10);
+
+    // 密钥来源只是预留接口
+    assert_eq!(fs.master_key(secret).unwrap(), None);
+    fs.set_key_provider(Some(Box::new(FixedKeys(MasterKeySpec::Descriptor(descriptor)))));
+    assert_eq!(fs.master_key(secret).unwrap().unwrap().len(), 64);
+    assert_eq!(fs.master_key(ROOT_INO).unwrap(), None);
+
+    // 空的加密目录无需密钥即可删除
+    fs.unlink(ROOT_INO, "empty").unwrap();
+    assert!(fs.lookup(ROOT_INO, "empty").is_err());
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_verity_metadata() {
+    use lwext4_arce::{FS_VERITY_HASH_ALG_SHA256, VerityDescriptor};
+
+    let path = copy_test_image("verity");
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    let free_before = fs.stat().unwrap().free_blocks_count;
+    let file = fs.create(ROOT_INO, "system.img", InodeType::RegularFile, 0o444).unwrap();
+
+    // 按ext4的布局写入：数据、64KiB对齐处的Merkle树、下一个块中的描述符、块末尾的描述符长度
+    let data = [0xA5u8; 100];
+    let tree: Vec<u8> = (0..4096u32).map(|i| (i % 253) as u8).collect();
+    let mut desc = [0u8; 256];
+    desc[..4].copy_from_slice(&[1, FS_VERITY_HASH_ALG_SHA256, 12, 4]);
+    desc[8..16].copy_from_slice(&(data.len() as u64).to_le_bytes());
+    desc[16..48].copy_from_slice(&[0x11; 32]);
+    desc[80..84].copy_from_slice(b"salt");
+    fs.write_at(file, &data, 0).unwrap();
+    fs.write_at(file, &tree, 65536).unwrap();
+    fs.write_at(file, &desc, 69632).unwrap();
+    fs.write_at(file, &256u32.to_le_bytes(), 73728 - 4).unwrap();
+    drop(fs);
+    patch_superblock(&path, |sb| {
+        let ro_compat = u32::from_le_bytes(sb[0x64..0x68].try_into().unwrap()) | 0x8000;
+        sb[0x64..0x68].copy_from_slice(&ro_compat.to_le_bytes());
+    });
+    patch_inode(&path, file, |raw| {
+        raw[0x04..0x08].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        let flags = u32::from_le_bytes(raw[0x20..0x24].try_into().unwrap()) | 0x100000;
+        raw[0x20..0x24].copy_from_slice(&flags.to_le_bytes());
+    });
+
+    // 启用verity特性的镜像仍可读写挂载
+    let mut fs = Fs::new(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert!(fs.verity_enabled());
+    let descriptor = fs.verity_descriptor("/system.img").unwrap().unwrap();
+    assert_eq!(descriptor, VerityDescriptor::parse(&desc).unwrap());
+    assert_eq!(descriptor.data_size, 100);
+    assert_eq!(descriptor.digest_size(), Some(32));
+    assert_eq!(descriptor.block_size(), 4096);
+    assert_eq!(descriptor.salt, b"salt");
+    assert_eq!(descriptor.digest_input(), desc);
+    assert_eq!(fs.verity_descriptor("/test.txt").unwrap(), None);
+
+    // 普通读取止于i_size，元数据通过专门的接口读取
+    let mut buf = vec![0u8; 8192];
+    assert_eq!(fs.read_at(file, &mut buf, 0).unwrap(), data.len());
+    assert_eq!(&buf[..data.len()], &data);
+    assert_eq!(fs.read_at(file, &mut buf, 65536).unwrap(), 0);
+    assert_eq!(fs.read_merkle_tree(file, &mut buf, 0).unwrap(), tree.len());
+    assert_eq!(&buf[..tree.len()], &tree);
+    assert_eq!(fs.read_merkle_tree(file, &mut buf, 4096).unwrap(), 0);
+
+    // 内容不可修改
+    let err = fs.write_at(file, b"tamper", 0).unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotPermitted);
+    assert_eq!(fs.set_len(file, 0).unwrap_err().kind(), Ext4ErrorKind::NotPermitted);
+    let err = fs.defragment("/system.img").unwrap_err();
+    assert_eq!(err.kind(), Ext4ErrorKind::NotPermitted);
+
+    // 删除时元数据块一并释放
+    fs.unlink(ROOT_INO, "system.img").unwrap();
+    assert_eq!(fs.stat().unwrap().free_blocks_count, free_before);
+    drop(fs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_group_layout() {
+    use lwext4_arce::MkfsOptions;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::process::Command;
+
+    // 300M、1K块：flex组的inode表需要跳过块组1的superblock/GDT备份
+    let path = std::env::temp_dir().join(format!("lwext4-layout-{}.ext4", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::File::create(path).unwrap().set_len(300 << 20).unwrap();
+    let made = Command::new("mkfs.ext4")
+        .args(["-q", "-F", "-b", "1024", "-O", "^metadata_csum", path])
+        .status()
+        .is_ok_and(|status| status.success());
+    if made {
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        assert!(!fs.is_read_only());
+        fs.check_layout().unwrap();
+        for group in 0..fs.group_count() {
+            assert_eq!(fs.group_layout(group).unwrap(), fs.default_group_layout(group).unwrap());
+        }
+        let g0 = fs.group_layout(0).unwrap();
+        assert_eq!((g0.blocks.start, g0.super_block, g0.gdt.start), (1, Some(1), 2));
+        assert_eq!(fs.group_layout(1).unwrap().super_block, Some(8193));
+        assert_eq!(fs.group_layout(2).unwrap().super_block, None);
+        assert!(fs.group_layout(2).unwrap().gdt.is_empty());
+        assert_eq!(fs.group_layout(fs.group_count()).unwrap_err().errno(), errno::EINVAL);
+        drop(fs);
+
+        // 损坏块组1的描述符：inode位图指向块组0的GDT，挂载后转为只读
+        let le32 = |buf: &[u8], off: usize| u32::from_le_bytes(buf[off..off + 4].try_into().unwrap());
+        let mut file = std::fs::File::options().read(true).write(true).open(path).unwrap();
+        let mut sb = vec![0u8; 1024];
+        file.seek(SeekFrom::Start(1024)).unwrap();
+        file.read_exact(&mut sb).unwrap();
+        let desc_size = if le32(&sb, 0x60) & 0x80 != 0 { u16::from_le_bytes([sb[0xFE], sb[0xFF]]) as u64 } else { 32 };
+        file.seek(SeekFrom::Start(2 * 1024 + desc_size + 4)).unwrap();
+        file.write_all(&2u32.to_le_bytes()).unwrap();
+        drop(file);
+        let mut fs = Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap();
+        assert!(fs.is_read_only());
+        assert_eq!(fs.check_layout().unwrap_err().errno(), errno::EIO);
+        assert_eq!(fs.group_layout(1).unwrap().inode_bitmap, 2);
+    }
+
+    // 自带的mkfs使用相同的默认布局
+    std::fs::File::create(path).unwrap().set_len(64 << 20).unwrap();
+    let opts = MkfsOptions { block_size: 1024, ..Default::default() };
+    let mut fs = Fs::mkfs(FileBlockDevice::open(path).unwrap(), &opts, FsConfig::default(), &mut XorShift(5)).unwrap();
+    fs.check_layout().unwrap();
+    for group in 0..fs.group_count() {
+        assert_eq!(fs.group_layout(group).unwrap(), fs.default_group_layout(group).unwrap());
+    }
+    drop(fs);
+    assert_fsck_clean(path);
+    std::fs::remove_file(path).unwrap();
+}
+
+/// 手工构造日志内容，检查挂载时的日志回放和事务提交（FFI 后端不做恢复）
+#[cfg(feature = "use-rust")]
+mod journal_replay {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom};
+
+    const JOURNAL_INO: u32 = 8;
+    const BS: usize = 4096;
+    const JBD_MAGIC: u32 = 0xC03B3998;
+    const JBD_FEATURE_INCOMPAT_FAST_COMMIT: u32 = 0x20;
+    /// 测试镜像中的空闲块（位于最后一个空闲段内）
+    const FREE_BLOCK: u64 = 2500;
+
+    /// 镜像中test.txt数据块的物理块号
+    fn test_txt_block(path: &str) -> u64 {
+        let image = std::fs::read(path).unwrap();
+        image
+            .chunks(BS)
+            .position(|block| block.starts_with(b"Hello!\n") && block[7..].iter().all(|&b| b == 0))
+            .expect("test.txt block not found") as u64
+    }
+
+    fn be32(buf: &[u8], off: usize) -> u32 {
+        u32::from_be_bytes(buf[off..off + 4].try_into().unwrap())
+    }
+
+    fn put_be32(buf: &mut [u8], off: usize, v: u32) {
+        buf[off..off + 4].copy_from_slice(&v.to_be_bytes());
+    }
+
+    /// 日志超级块及写入位置
+    struct Journal {
+        sb: Vec<u8>,
+        seed: u32,
+        next: u64,
+    }
+
+    impl Journal {
+        fn open(fs: &mut Fs) -> Self {
+            let mut sb = vec![0u8; BS];
+            fs.read_at(JOURNAL_INO, &mut sb, 0).unwrap();
+            assert_eq!(be32(&sb, 0), JBD_MAGIC);
+            let seed = crc32c(!0, &sb[0x30..0x40]);
+            let next = be32(&sb, 0x14) as u64;
+            Self { sb, seed, next }
+        }
+
+        fn sequence(&self) -> u32 {
+            be32(&self.sb, 0x18)
+        }
+
+        fn block(kind: u32, seq: u32) -> Vec<u8> {
+            let mut buf = vec![0u8; BS];
+            put_be32(&mut buf, 0, JBD_MAGIC);
+            put_be32(&mut buf, 4, kind);
+            put_be32(&mut buf, 8, seq);
+            buf
+        }
+
+        fn append(&mut self, fs: &mut Fs, buf: &[u8]) {
+            fs.write_at(JOURNAL_INO, buf, self.next * BS as u64).unwrap();
+            self.next += 1;
+        }
+
+        /// 描述块 + 数据块（每个目标块一个 csum_v3 标签）
+        fn write_blocks(&mut self, fs: &mut Fs, seq: u32, blocks: &[(u64, &[u8])]) {
+            let mut desc = Self::block(1, seq);
+            let mut off = 12;
+            for (i, &(target, data)) in blocks.iter().enumerate() {
+                let mut flags = if i == 0 { 0 } else { 2 };
+                if i + 1 == blocks.len() {
+                    flags |= 8;
+                }
+                put_be32(&mut desc, off, target as u32);
+                put_be32(&mut desc, off + 4, flags);
+                put_be32(&mut desc, off + 8, (target >> 32) as u32);
+                let csum = crc32c(crc32c(self.seed, &seq.to_be_bytes()), data);
+                put_be32(&mut desc, off + 12, csum);
+                off += 16;
+                if i == 0 {
+                    let uuid = self.sb[0x30..0x40].to_vec();
+                    desc[off..off + 16].copy_from_slice(&uuid);
+                    off += 16;
+                }
+            }
+            self.seal_tail(&mut desc);
+            self.append(fs, &desc);
+            for &(_, data) in blocks {
+                self.append(fs, data);
+            }
+        }
+
+        fn write_revoke(&mut self, fs: &mut Fs, seq: u32, target: u64) {
+            let mut buf = Self::block(5, seq);
+            put_be32(&mut buf, 12, 16 + 8);
+            buf[16..24].copy_from_slice(&target.to_be_bytes());
+            self.seal_tail(&mut buf);
+            self.append(fs, &buf);
+        }
+
+        fn write_commit(&mut self, fs: &mut Fs, seq: u32) {
+            let mut buf = Self::block(2, seq);
+            let csum = crc32c(self.seed, &buf);
+            put_be32(&mut buf, 16, csum);
+            self.append(fs, &buf);
+        }
+
+        fn seal_tail(&self, buf: &mut [u8]) {
+            let csum = crc32c(self.seed, buf);
+            put_be32(buf, BS - 4, csum);
+        }
+
+        /// 日志从第一个块开始有内容，写回超级块
+        fn finish(&mut self, fs: &mut Fs, incompat: u32) {
+            let first = be32(&self.sb, 0x14);
+            put_be32(&mut self.sb, 0x1C, first);
+            let features = be32(&self.sb, 0x28) | incompat;
+            put_be32(&mut self.sb, 0x28, features);
+            put_be32(&mut self.sb, 0xFC, 0);
+            let csum = crc32c(!0, &self.sb[..1024]);
+            put_be32(&mut self.sb, 0xFC, csum);
+            fs.write_at(JOURNAL_INO, &self.sb, 0).unwrap();
+            fs.flush().unwrap();
+        }
+    }
+
+    /// 追加一个快速提交标签并累加校验和
+    fn fc_tag(area: &mut Vec<u8>, crc: &mut u32, tag: u16, value: &[u8]) {
+        let start = area.len();
+        area.extend_from_slice(&tag.to_le_bytes());
+        area.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        area.extend_from_slice(value);
+        *crc = crc32c(*crc, &area[start..]);
+    }
+
+    fn read_raw(path: &str, offset: u64, len: usize) -> Vec<u8> {
+        let mut file = std::fs::File::open(path).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        let mut buf = vec![0u8; len];
+        file.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    /// 设置超级块中的 RECOVER 标志（模拟未干净卸载）
+    fn set_needs_recovery(path: &str) {
+        patch_superblock(path, |sb| sb[0x60] |= 0x4);
+    }
+
+    fn open(path: &str) -> Fs {
+        Fs::new(FileBlockDevice::open(path).unwrap(), FsConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_journal_replay() {
+        let path = copy_test_image("journal-replay");
+        let test_txt = test_txt_block(&path);
+        let mut fs = open(&path);
+        let free_before = read_raw(&path, FREE_BLOCK * BS as u64, BS);
+
+        let mut journal = Journal::open(&mut fs);
+        let seq = journal.sequence();
+        let a = vec![b'a'; BS];
+        let b = vec![b'b'; BS];
+        let c = vec![b'c'; BS];
+        // 第一个事务写入test.txt和一个空闲块，第二个事务撤销后者并再次写入test.txt
+        journal.write_blocks(&mut fs, seq, &[(test_txt, &a), (FREE_BLOCK, &a)]);
+        journal.write_commit(&mut fs, seq);
+        journal.write_revoke(&mut fs, seq + 1, FREE_BLOCK);
+        journal.write_blocks(&mut fs, seq + 1, &[(test_txt, &b)]);
+        journal.write_commit(&mut fs, seq + 1);
+        // 没有提交块的事务不回放
+        journal.write_blocks(&mut fs, seq + 2, &[(test_txt, &c)]);
+        journal.finish(&mut fs, 0);
+        drop(fs);
+        set_needs_recovery(&path);
+
+        let mut fs = open(&path);
+        let ino = fs.lookup(ROOT_INO, "test.txt").unwrap().entry().ino();
+        let mut buf = [0u8; 16];
+        assert_eq!(fs.read_at(ino, &mut buf, 0).unwrap(), 7);
+        assert_eq!(&buf[..7], b"bbbbbbb");
+        drop(fs);
+        assert_eq!(read_raw(&path, test_txt * BS as u64, BS), b);
+        assert_eq!(read_raw(&path, FREE_BLOCK * BS as u64, BS), free_before);
+
+        // 日志已清空，RECOVER 标志已清除
+        assert_eq!(read_raw(&path, 1024 + 0x60, 1)[0] & 0x4, 0);
+        let mut fs = open(&path);
+        let journal = Journal::open(&mut fs);
+        assert_eq!(be32(&journal.sb, 0x1C), 0);
+        // 与内核相同，跳过未提交事务的序号
+        assert_eq!(journal.sequence(), seq + 3);
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    static YIELDS: AtomicU64 = AtomicU64::new(0);
+
+    struct YieldHal;
+    impl SystemHal for YieldHal {
+        fn now() -> Option<Duration> {
+            None
+        }
+
+        fn yield_now() {
+            YIELDS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_replay_yields() {
+        let path = copy_test_image("replay-yield");
+        let mut fs = open(&path);
+        let mut journal = Journal::open(&mut fs);
+        let seq = journal.sequence();
+        let data = vec![b'y'; BS];
+        let blocks: Vec<(u64, &[u8])> = (0..32).map(|i| (FREE_BLOCK + i, data.as_slice())).collect();
+        journal.write_blocks(&mut fs, seq, &blocks);
+        journal.write_commit(&mut fs, seq);
+        journal.finish(&mut fs, 0);
+        drop(fs);
+        set_needs_recovery(&path);
+
+        // 日志回放的三遍扫描中每个日志块都经过让出点
+        let config = FsConfig { yield_interval: 4, ..FsConfig::default() };
+        let mut fs = Ext4Filesystem::<YieldHal, _>::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+        let after_mount = YIELDS.load(Ordering::SeqCst);
+        assert!(after_mount >= 3 * 32 / 4, "{after_mount}");
+        assert_eq!(read_raw(&path, (FREE_BLOCK + 31) * BS as u64, BS), data);
+
+        // remove_dir_all每删除4个条目让出一次，结束时的批量回写同样让出
+        let tree = fs.create(ROOT_INO, "tree", InodeType::Directory, 0o755).unwrap();
+        for i in 0..64 {
+            fs.create(tree, &format!("d{i}"), InodeType::Directory, 0o755).unwrap();
+        }
+        let before = YIELDS.load(Ordering::SeqCst);
+        fs.remove_dir_all("/tree").unwrap();
+        assert!(YIELDS.load(Ordering::SeqCst) - before >= 2 * 64 / 4);
+        drop(fs);
+
+        // 间隔为0时不让出
+        let config = FsConfig { yield_interval: 0, ..FsConfig::default() };
+        let mut fs = Ext4Filesystem::<YieldHal, _>::new(FileBlockDevice::open(&path).unwrap(), config).unwrap();
+        let tree = fs.create(ROOT_INO, "tree", InodeType::Directory, 0o755).unwrap();
+        for i in 0..64 {
+            fs.create(tree, &format!("d{i}"), InodeType::Directory, 0o755).unwrap();
+        }
+        let before = YIELDS.load(Ordering::SeqCst);
+        fs.remove_dir_all("/tree").unwrap();
+        drop(fs);
+        assert_eq!(YIELDS.load(Ordering::SeqCst), before);
+        assert_fsck_clean(&path);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_fast_commit_replay() {
+        let path = copy_test_image("fast-commit");
+        let mut fs = open(&path);
+
+        // 借用一个已删除文件的数据块和inode，快速提交重新创建它
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 241) as u8 ^ 0x5a).collect();
+        let donor = fs
+            .create(ROOT_INO, "donor", InodeType::RegularFile, 0o644)
+            .unwrap();
+        fs.write_at(donor, &data, 0).unwrap();
+        fs.flush().unwrap();
+        let used = fs.stat().unwrap();
+        let image = std::fs::read(&path).unwrap();
+        let pblk = image
+            .chunks(BS)
+            .position(|block| block == &data[..BS])
+            .expect("donor block not found") as u64;
+        fs.unlink(ROOT_INO, "donor").unwrap();
+
+        let mut journal = Journal::open(&mut fs);
+        let tid = journal.sequence();
+        // 日志区为空（第一个块不是描述块），快速提交区从 maxlen - 256 + 1 开始
+        journal.append(&mut fs, &vec![0u8; BS]);
+        let fc_first = be32(&journal.sb, 0x10) as u64 - 256 + 1;
+
+        let mut area = Vec::new();
+        let mut crc = 0;
+        let mut head = vec![0u8; 4];
+        head.extend_from_slice(&tid.to_le_bytes());
+        fc_tag(&mut area, &mut crc, 9, &head);
+
+        let mut inode = vec![0u8; 4 + 256];
+        inode[..4].copy_from_slice(&donor.to_le_bytes());
+        let raw = &mut inode[4..];
+        raw[0..2].copy_from_slice(&0o100644u16.to_le_bytes());
+        raw[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        raw[26..28].copy_from_slice(&1u16.to_le_bytes());
+        raw[32..36].copy_from_slice(&0x80000u32.to_le_bytes());
+        raw[128..130].copy_from_slice(&32u16.to_le_bytes());
+        fc_tag(&mut area, &mut crc, 6, &inode);
+
+        let mut range = donor.to_le_bytes().to_vec();
+        range.extend_from_slice(&0u32.to_le_bytes());
+        range.extend_from_slice(&2u16.to_le_bytes());
+        range.extend_from_slice(&((pblk >> 32) as u16).to_le_bytes());
+        range.extend_from_slice(&(pblk as u32).to_le_bytes());
+        fc_tag(&mut area, &mut crc, 1, &range);
+
+        let mut creat = ROOT_INO.to_le_bytes().to_vec();
+        creat.extend_from_slice(&donor.to_le_bytes());
+        creat.extend_from_slice(b"fc.txt");
+        fc_tag(&mut area, &mut crc, 3, &creat);
+
+        area.extend_from_slice(&8u16.to_le_bytes());
+        area.extend_from_slice(&8u16.to_le_bytes());
+        area.extend_from_slice(&tid.to_le_bytes());
+        crc = crc32c(crc, &area[area.len() - 8..]);
+        area.extend_from_slice(&crc.to_le_bytes());
+        area.resize(BS, 0);
+        fs.write_at(JOURNAL_INO, &area, fc_first * BS as u64).unwrap();
+        journal.finish(&mut fs, JBD_FEATURE_INCOMPAT_FAST_COMMIT);
+        drop(fs);
+        set_needs_recovery(&path);
+
+        let mut fs = open(&path);
+        assert_eq!(fs.lookup(ROOT_INO, "fc.txt").unwrap().entry().ino(), donor);
+        let mut buf = vec![0u8; 8192];
+        assert_eq!(fs.read_at(donor, &mut buf, 0).unwrap(), data.len());
+        assert_eq!(&buf[..data.len()], &data[..]);
+        let mut attr = FileAttr::default();
+        fs.get_attr(donor, &mut attr).unwrap();
+        assert_eq!((attr.nlink, attr.blocks), (1, 2 * BS as u64 / 512));
+        // 位图和计数与删除前一致
+        let stat = fs.stat().unwrap();
+        assert_eq!(stat.free_blocks_count, used.free_blocks_count);
+        assert_eq!(stat.free_inodes_count, used.free_inodes_count);
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_crash_consistency() {
+        use common::for_each_crash_state;
+        use lwext4_arce::{RamDisk, RecordingDevice};
+
+        // 在内存中执行事务并记录全部写入（包括挂载、提交后写回原位置和卸载）
+        let path = copy_test_image("crash");
+        let base = std::fs::read(&path).unwrap();
+        let mut disk = RamDisk::from_vec(base.clone());
+        let mut recording = RecordingDevice::new(&mut disk);
+        let mut fs = Ext4Filesystem::<DummyHal, _>::new(&mut recording, FsConfig::default()).unwrap();
+        let mut txn = fs.begin_transaction().unwrap();
+        let dir = txn.create(ROOT_INO, "txn", InodeType::Directory, 0o755).unwrap();
+        for name in ["a", "b", "c"] {
+            txn.create(dir, name, InodeType::RegularFile, 0o644).unwrap();
+        }
+        txn.rename(dir, "c", ROOT_INO, "moved").unwrap();
+        txn.commit().unwrap();
+        drop(fs);
+        assert!(recording.writes().len() > 1);
+
+        // 任意时刻断电后都能挂载（恢复日志），e2fsck检查通过，且事务要么完整生效要么完全没有生效
+        let mut committed = 0;
+        for_each_crash_state(&base, &recording, &path, |count| {
+            let mut fs = open(&path);
+            if fs.lookup(ROOT_INO, "txn").is_ok() {
+                check_transaction_result(&mut fs, dir);
+                committed += 1;
+            } else {
+                assert!(fs.lookup(ROOT_INO, "moved").is_err(), "partial transaction after {count} writes");
+            }
+            drop(fs);
+            assert_fsck_clean(&path);
+        });
+        assert!(committed > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// 在事务中创建文件、目录并重命名
+    fn transaction_ops(fs: &mut Fs) -> u32 {
+        let mut txn = fs.begin_transaction().unwrap();
+        let dir = txn.create(ROOT_INO, "txn", InodeType::Directory, 0o755).unwrap();
+        for name in ["a", "b", "c"] {
+            txn.create(dir, name, InodeType::RegularFile, 0o644).unwrap();
+        }
+        txn.rename(dir, "c", ROOT_INO, "moved").unwrap();
+        txn.commit().unwrap();
+        dir
+    }
+
+    fn check_transaction_result(fs: &mut Fs, dir: u32) {
+        assert_eq!(fs.lookup(ROOT_INO, "txn").unwrap().entry().ino(), dir);
+        let mut names = list_dir(fs, dir);
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+        assert!(fs.lookup(ROOT_INO, "moved").is_ok());
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        let path = copy_test_image("txn-commit");
+        let mut fs = open(&path);
+        let seq = Journal::open(&mut fs).sequence();
+        let dir = transaction_ops(&mut fs);
+        assert_eq!(fs.metrics().journal_commits, 1);
+        // 空事务不占用日志
+        fs.begin_transaction().unwrap().commit().unwrap();
+        drop(fs);
+
+        // 提交后日志已清空，RECOVER 标志已清除
+        assert_eq!(read_raw(&path, 1024 + 0x60, 1)[0] & 0x4, 0);
+        let mut fs = open(&path);
+        let journal = Journal::open(&mut fs);
+        assert_eq!(be32(&journal.sb, 0x1C), 0);
+        assert_eq!(journal.sequence(), seq + 1);
+        check_transaction_result(&mut fs, dir);
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_transaction_abort() {
+        let path = copy_test_image("txn-abort");
+        let mut fs = open(&path);
+        let before = fs.stat().unwrap();
+        let names_before = list_dir(&mut fs, ROOT_INO);
+
+        let mut txn = fs.begin_transaction().unwrap();
+        let dir = txn.create(ROOT_INO, "gone", InodeType::Directory, 0o755).unwrap();
+        txn.create(dir, "a", InodeType::RegularFile, 0o644).unwrap();
+        txn.rename(ROOT_INO, "test.txt", dir, "b").unwrap();
+        txn.abort().unwrap();
+        // 未提交就销毁的事务同样被中止
+        let mut txn = fs.begin_transaction().unwrap();
+        txn.create(ROOT_INO, "dropped", InodeType::RegularFile, 0o644).unwrap();
+        drop(txn);
+
+        let check = |fs: &mut Fs| {
+            let stat = fs.stat().unwrap();
+            assert_eq!(stat.free_blocks_count, before.free_blocks_count);
+            assert_eq!(stat.free_inodes_count, before.free_inodes_count);
+            assert_eq!(list_dir(fs, ROOT_INO), names_before);
+        };
+        check(&mut fs);
+        // 中止后释放的inode可以重新分配
+        let ino = fs.create(ROOT_INO, "after", InodeType::RegularFile, 0o644).unwrap();
+        assert_eq!(ino, dir);
+        fs.unlink(ROOT_INO, "after").unwrap();
+        drop(fs);
+
+        let mut fs = open(&path);
+        check(&mut fs);
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_transaction_replay() {
+        // 模拟提交块写入之后、写回原位置之前断电：把提交后的日志内容放回事务前的镜像
+        let path = copy_test_image("txn-replay");
+        let pristine = std::fs::read(&path).unwrap();
+        let mut fs = open(&path);
+        let dir = transaction_ops(&mut fs);
+        let mut log = vec![0u8; 1024 * BS];
+        assert_eq!(fs.read_at(JOURNAL_INO, &mut log, 0).unwrap(), log.len());
+        drop(fs);
+        std::fs::write(&path, &pristine).unwrap();
+
+        let first = be32(&log, 0x14);
+        let tid = be32(&log, 0x18) - 1;
+        put_be32(&mut log, 0x1C, first);
+        put_be32(&mut log, 0x18, tid);
+        put_be32(&mut log, 0xFC, 0);
+        let csum = crc32c(!0, &log[..1024]);
+        put_be32(&mut log, 0xFC, csum);
+        let mut fs = open(&path);
+        fs.write_at(JOURNAL_INO, &log, 0).unwrap();
+        drop(fs);
+        set_needs_recovery(&path);
+
+        let mut fs = open(&path);
+        check_transaction_result(&mut fs, dir);
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+/// 通过Ext4Backend按路径读取整棵目录树：（路径, 类型, 权限位, 大小, 内容）
+fn backend_tree(fs: &mut dyn lwext4_arce::Ext4Backend) -> Vec<(String, InodeType, u32, u64, Vec<u8>)> {
+    let mut tree = Vec::new();
+    let mut stack = vec![String::new()];
+    while let Some(dir) = stack.pop() {
+        let mut names = fs.list_dir(&dir).unwrap();
+        names.sort();
+        for name in names {
+            let path = format!("{dir}/{name}");
+            let stat = fs.metadata(&path).unwrap();
+            let mut data = Vec::new();
+            match stat.node_type {
+                InodeType::Directory => stack.push(path.clone()),
+                InodeType::RegularFile => {
+                    data.resize(stat.size as usize, 0);
+                    assert_eq!(fs.read_file(&path, 0, &mut data).unwrap(), data.len());
+                }
+                _ => {}
+            }
+            tree.push((path, stat.node_type, stat.mode, stat.size, data));
+        }
+    }
+    tree
+}
+
+/// 修改后的目录树应与修改前的树加上新文件一致
+fn backend_modify(fs: &mut dyn lwext4_arce::Ext4Backend) {
+    fs.create_dir("/ab").unwrap();
+    assert_eq!(fs.write_file("/ab/data", 0, b"hello").unwrap(), 5);
+    assert_eq!(fs.write_file("/ab/data", 3, b"p!").unwrap(), 2);
+    assert_eq!(fs.write_file("/ab/tmp", 0, b"x").unwrap(), 1);
+    assert_eq!(fs.remove_file("/ab").unwrap_err().errno(), errno::EISDIR);
+    fs.remove_file("/ab/tmp").unwrap();
+    fs.flush().unwrap();
+}
+
+#[test]
+#[cfg(feature = "use-rust")]
+fn test_open_backend() {
+    use lwext4_arce::{Backend, open_backend};
+
+    let mut fs = mount("backend-direct");
+    let expected = backend_tree(&mut fs);
+    assert!(!expected.is_empty());
+    drop(fs);
+
+    // 默认后端与直接挂载的Ext4Filesystem看到同一棵树
+    let path = copy_test_image("backend-dyn");
+    let mut dynfs = open_backend::<DummyHal, _>(FileBlockDevice::open(&path).unwrap(), FsConfig::default()).unwrap();
+    assert_eq!(dynfs.backend(), Backend::current());
+    assert_eq!(backend_tree(dynfs.as_mut()), expected);
+    backend_modify(dynfs.as_mut());
+    let mut buf = [0u8; 8];
+    assert_eq!(dynfs.read_file("/ab/data", 0, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"help!");
+    assert_eq!(dynfs.list_dir("/ab").unwrap(), ["data"]);
+    drop(dynfs);
+    assert_fsck_clean(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    // 只能挂载当前构建绑定的后端（无论C后端是否被编译进构建）
+    let config = FsConfig {
+        backend: Some(Backend::Ffi),
+        ..FsConfig::default()
+    };
+    let err = open_backend::<DummyHal, _>(open_test_image("backend-missing"), config).err().unwrap();
+    assert_eq!(err.errno(), errno::ENOTSUP);
 }
@@ -0,0 +1,271 @@
+/* lwext4_core C 接口（lwext4 兼容），启用 c-api 特性构建。由 cbindgen 生成，请勿手动修改。 */
+
+#ifndef LWEXT4_CORE_H
+#define LWEXT4_CORE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+struct ext4_blockdev;
+
+#ifndef EOK
+#define EOK 0
+#endif
+
+#ifndef O_RDONLY
+#define O_RDONLY 00
+#define O_WRONLY 01
+#define O_RDWR 02
+#define O_CREAT 0100
+#define O_EXCL 0200
+#define O_TRUNC 01000
+#define O_APPEND 02000
+#endif
+
+#ifndef SEEK_SET
+#define SEEK_SET 0
+#define SEEK_CUR 1
+#define SEEK_END 2
+#endif
+
+#define CONFIG_EXT4_BLOCKDEVS_COUNT 2
+#define CONFIG_EXT4_MOUNTPOINTS_COUNT 2
+#define CONFIG_EXT4_MAX_BLOCKDEV_NAME 32
+#define CONFIG_EXT4_MAX_MP_NAME 32
+
+#define EXT4_MBR_PARTITIONS 4
+
+#define EXT4_DE_UNKNOWN 0
+#define EXT4_DE_REG_FILE 1
+#define EXT4_DE_DIR 2
+#define EXT4_DE_CHRDEV 3
+#define EXT4_DE_BLKDEV 4
+#define EXT4_DE_FIFO 5
+#define EXT4_DE_SOCK 6
+#define EXT4_DE_SYMLINK 7
+
+#define DEBUG_BALLOC (1ul << 0)
+#define DEBUG_BCACHE (1ul << 1)
+#define DEBUG_BITMAP (1ul << 2)
+#define DEBUG_BLOCK_GROUP (1ul << 3)
+#define DEBUG_BLOCKDEV (1ul << 4)
+#define DEBUG_DIR_IDX (1ul << 5)
+#define DEBUG_DIR (1ul << 6)
+#define DEBUG_EXTENT (1ul << 7)
+#define DEBUG_FS (1ul << 8)
+#define DEBUG_HASH (1ul << 9)
+#define DEBUG_IALLOC (1ul << 10)
+#define DEBUG_INODE (1ul << 11)
+#define DEBUG_SUPER (1ul << 12)
+#define DEBUG_XATTR (1ul << 13)
+#define DEBUG_MKFS (1ul << 14)
+#define DEBUG_EXT4 (1ul << 15)
+#define DEBUG_JBD (1ul << 16)
+#define DEBUG_MBR (1ul << 17)
+#define DEBUG_ALL (0xFFFFFFFF)
+
+
+typedef struct ext4_bcache_lists ext4_bcache_lists;
+
+typedef struct ext4_fs ext4_fs;
+
+typedef struct ext4_mountpoint ext4_mountpoint;
+
+typedef struct ext4_blockdev_iface {
+  int32_t (*open)(struct ext4_blockdev*);
+  int32_t (*bread)(struct ext4_blockdev*, void*, uint64_t, uint32_t);
+  int32_t (*bwrite)(struct ext4_blockdev*, const void*, uint64_t, uint32_t);
+  int32_t (*close)(struct ext4_blockdev*);
+  int32_t (*lock)(struct ext4_blockdev*);
+  int32_t (*unlock)(struct ext4_blockdev*);
+  uint32_t ph_bsize;
+  uint64_t ph_bcnt;
+  uint8_t *ph_bbuf;
+  uint32_t ph_refctr;
+  uint32_t bread_ctr;
+  uint32_t bwrite_ctr;
+  void *p_user;
+  uint32_t ph_align;
+} ext4_blockdev_iface;
+
+typedef struct ext4_bcache {
+  uint32_t cnt;
+  uint32_t itemsize;
+  uint32_t lru_ctr;
+  uint32_t ref_blocks;
+  uint32_t max_ref_blocks;
+  uint32_t pinned_blocks;
+  uint32_t pin_limit;
+  struct ext4_blockdev *bdev;
+  bool dont_shake;
+  bool ordered;
+  uint32_t data_writes;
+  bool in_trans;
+  uint32_t undo_depth;
+  uint32_t max_dirty;
+  uint32_t align;
+  struct ext4_bcache_lists *lists;
+} ext4_bcache;
+
+typedef struct ext4_blockdev {
+  struct ext4_blockdev_iface *bdif;
+  uint64_t part_offset;
+  uint64_t part_size;
+  struct ext4_bcache *bc;
+  uint32_t lg_bsize;
+  uint64_t lg_bcnt;
+  uint32_t cache_write_back;
+  struct ext4_fs *fs;
+  uint8_t *journal;
+  uint32_t ph_bsize;
+  uint64_t ph_bcnt;
+} ext4_blockdev;
+
+typedef struct ext4_mbr_bdevs {
+  struct ext4_blockdev partitions[EXT4_MBR_PARTITIONS];
+  uint8_t types[EXT4_MBR_PARTITIONS];
+} ext4_mbr_bdevs;
+
+typedef struct ext4_mount_config {
+  uint32_t bcache_size;
+  size_t es_cache_size;
+  uint32_t max_dirty_blocks;
+} ext4_mount_config;
+
+typedef struct ext4_mount_config Ext4MountConfig;
+
+typedef struct ext4_file {
+  struct ext4_mountpoint *mp;
+  uint32_t inode;
+  uint32_t flags;
+  uint64_t fsize;
+  uint64_t fpos;
+} ext4_file;
+
+typedef struct ext4_direntry {
+  uint32_t inode;
+  uint16_t entry_length;
+  uint8_t name_length;
+  uint8_t inode_type;
+  uint8_t name[255];
+} ext4_direntry;
+
+typedef struct ext4_dir {
+  struct ext4_file f;
+  struct ext4_direntry de;
+  uint64_t next_off;
+} ext4_dir;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+int32_t ext4_device_register(struct ext4_blockdev *bd, const char *dev_name);
+
+int32_t ext4_device_unregister(const char *dev_name);
+
+int32_t ext4_device_unregister_all(void);
+
+int32_t ext4_mbr_scan(struct ext4_blockdev *parent, struct ext4_mbr_bdevs *bdevs);
+
+int32_t ext4_mount(const char *dev_name, const char *mount_point, bool read_only);
+
+int32_t ext4_mount_with_config(const char *dev_name,
+                               const char *mount_point,
+                               bool read_only,
+                               const Ext4MountConfig *config);
+
+int32_t ext4_umount(const char *mount_point);
+
+int32_t ext4_recover(const char *mount_point);
+
+int32_t ext4_cache_flush(const char *path);
+
+int32_t ext4_fopen(struct ext4_file *file, const char *path, const char *flags);
+
+int32_t ext4_fopen2(struct ext4_file *file, const char *path, int32_t flags);
+
+int32_t ext4_fclose(struct ext4_file *file);
+
+int32_t ext4_ftruncate(struct ext4_file *file, uint64_t size);
+
+int32_t ext4_fread(struct ext4_file *file, void *buf, size_t size, size_t *rcnt);
+
+int32_t ext4_fwrite(struct ext4_file *file, const void *buf, size_t size, size_t *wcnt);
+
+int32_t ext4_fseek(struct ext4_file *file, int64_t offset, uint32_t origin);
+
+uint64_t ext4_ftell(struct ext4_file *file);
+
+uint64_t ext4_fsize(struct ext4_file *file);
+
+int32_t ext4_fremove(const char *path);
+
+int32_t ext4_frename(const char *path, const char *new_path);
+
+int32_t ext4_mode_set(const char *path, uint32_t mode);
+
+int32_t ext4_mode_get(const char *path, uint32_t *mode);
+
+int32_t ext4_owner_set(const char *path, uint32_t uid, uint32_t gid);
+
+int32_t ext4_owner_get(const char *path, uint32_t *uid, uint32_t *gid);
+
+int32_t ext4_atime_set(const char *path, uint32_t atime);
+
+int32_t ext4_mtime_set(const char *path, uint32_t mtime);
+
+int32_t ext4_ctime_set(const char *path, uint32_t ctime);
+
+int32_t ext4_atime_get(const char *path, uint32_t *atime);
+
+int32_t ext4_mtime_get(const char *path, uint32_t *mtime);
+
+int32_t ext4_ctime_get(const char *path, uint32_t *ctime);
+
+int32_t ext4_dir_mk(const char *path);
+
+int32_t ext4_dir_rm(const char *path);
+
+int32_t ext4_dir_open(struct ext4_dir *dir, const char *path);
+
+int32_t ext4_dir_close(struct ext4_dir *dir);
+
+const struct ext4_direntry *ext4_dir_entry_next(struct ext4_dir *dir);
+
+void ext4_dir_entry_rewind(struct ext4_dir *dir);
+
+void ext4_dmask_set(uint32_t m);
+
+void ext4_dmask_clr(uint32_t m);
+
+uint32_t ext4_dmask_get(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LWEXT4_CORE_H */
+
+#define EXT4_BLOCKDEV_STATIC_INSTANCE(__name, __bsize, __bcnt, __open, __bread, \
+                                      __bwrite, __close, __lock, __unlock)   \
+    static uint8_t __name##_ph_bbuf[(__bsize)];                             \
+    static struct ext4_blockdev_iface __name##_iface = {                    \
+        .open = __open,                                                     \
+        .bread = __bread,                                                   \
+        .bwrite = __bwrite,                                                 \
+        .close = __close,                                                   \
+        .lock = __lock,                                                     \
+        .unlock = __unlock,                                                 \
+        .ph_bsize = __bsize,                                                \
+        .ph_bcnt = __bcnt,                                                  \
+        .ph_bbuf = __name##_ph_bbuf,                                        \
+    };                                                                      \
+    static struct ext4_blockdev __name = {                                  \
+        .bdif = &__name##_iface,                                            \
+        .part_offset = 0,                                                   \
+        .part_size = (__bcnt) * (__bsize),                                  \
+    }
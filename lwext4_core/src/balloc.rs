@@ -0,0 +1,584 @@
+//! 块分配模块
+//!
+//! 对应C实现: ext4_balloc.c
+
+use alloc::vec::Vec;
+
+use crate::bcache::ext4_bcache_invalidate_lba;
+use crate::bitmap::*;
+use crate::block::{ext4_block_get, ext4_block_pin, ext4_block_set, ext4_block_set_dirty};
+use crate::block_group::*;
+use crate::layout::*;
+use crate::crc::{ext4_crc32c, ext4_sb_csum_seed};
+use crate::inode::{
+    ext4_fs_get_inode_dblk_idx, ext4_inode_blocks_count_fits, ext4_inode_get_blocks_count,
+    ext4_inode_get_size, ext4_inode_set_blocks_count,
+};
+use crate::quota::ext4_quota_charge_inode;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockGroup, Ext4BlockGroupRef, Ext4Filesystem, Ext4InodeRef, Ext4Superblock};
+
+/// 计算块位图校验和
+pub fn ext4_balloc_bitmap_csum(sb: &Ext4Superblock, bitmap: &[u8]) -> u32 {
+    let blocks_per_group = u32::from_le(sb.blocks_per_group);
+    let checksum = ext4_sb_csum_seed(sb);
+    ext4_crc32c(checksum, &bitmap[..(blocks_per_group / 8) as usize])
+}
+
+/// 更新块组描述符中的块位图校验和
+pub fn ext4_balloc_set_bitmap_csum(sb: &Ext4Superblock, bg: &mut Ext4BlockGroup, bitmap: &[u8]) {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return;
+    }
+    let csum = ext4_balloc_bitmap_csum(sb, bitmap);
+    bg.block_bitmap_csum_lo = (csum as u16).to_le();
+    if ext4_sb_get_desc_size(sb) == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE {
+        bg.block_bitmap_csum_hi = ((csum >> 16) as u16).to_le();
+    }
+}
+
+/// 校验块位图校验和
+pub fn ext4_balloc_verify_bitmap_csum(sb: &Ext4Superblock, bg: &Ext4BlockGroup, bitmap: &[u8]) -> bool {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return true;
+    }
+    let csum = ext4_balloc_bitmap_csum(sb, bitmap);
+    if u16::from_le(bg.block_bitmap_csum_lo) != csum as u16 {
+        return false;
+    }
+    if ext4_sb_get_desc_size(sb) == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE
+        && u16::from_le(bg.block_bitmap_csum_hi) != (csum >> 16) as u16
+    {
+        return false;
+    }
+    true
+}
+
+/// 加载块组的块位图（BLOCK_UNINIT 的块组先初始化位图）
+unsafe fn ext4_balloc_load_bitmap(bg_ref: *mut Ext4BlockGroupRef, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        let fs = (*bg_ref).fs;
+        let sb = &(*fs).sb;
+        if ext4_bg_has_flag(&*(*bg_ref).block_group, EXT4_BLOCK_GROUP_BLOCK_UNINIT) {
+            let r = ext4_fs_init_block_bitmap(bg_ref);
+            if r != EOK {
+                return r;
+            }
+            ext4_bg_clear_flag(&mut *(*bg_ref).block_group, EXT4_BLOCK_GROUP_BLOCK_UNINIT);
+            (*bg_ref).dirty = true;
+        }
+
+        let bg = &*(*bg_ref).block_group;
+        let r = ext4_block_get((*fs).bdev, b, ext4_bg_get_block_bitmap(bg, sb));
+        if r != EOK {
+            return r;
+        }
+        // 位图访问频繁，尽量常驻缓存
+        ext4_block_pin((*fs).bdev, b);
+        let bitmap = core::slice::from_raw_parts((*b).data, ext4_sb_get_block_size(sb) as usize);
+        if !ext4_balloc_verify_bitmap_csum(sb, bg, bitmap) {
+            ext4_dbg!(DEBUG_BALLOC, Warn, "block bitmap checksum failed: bg {}", (*bg_ref).index);
+        }
+    }
+    EOK
+}
+
+/// 块组中 inode 表之后的第一个块
+///
+/// flex_bg 时 inode 表集中在 flex 组的第一个块组中，该块落在 flex 组的元数据之间，
+/// 分配时向后查找，结果位于 flex 组的数据区。
+unsafe fn ext4_balloc_group_goal(fs: *mut Ext4Filesystem, bgid: u32, goal: *mut u64) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        let itab_first_block = ext4_bg_get_inode_table_first_block(&*bg_ref.block_group, sb);
+        let itab_bytes = ext4_inodes_in_group_cnt(sb, bgid) as u64 * get_inode_size(sb) as u64;
+        *goal = itab_first_block + itab_bytes.div_ceil(block_size as u64);
+        ext4_fs_put_block_group_ref(&mut bg_ref)
+    }
+}
+
+/// 没有相邻数据块可参照时 inode 的分配目标
+///
+/// 优先使用记录的目标（上次分配的数据块之后，或新文件继承自父目录，见 ext4_balloc_set_goal
+/// 和 ext4_balloc_inherit_goal），否则为 inode 所在块组中 inode 表之后的第一个块。
+pub unsafe fn ext4_balloc_bg_goal(inode_ref: *mut Ext4InodeRef, goal: *mut u64) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        if let Some(&g) = (*fs).alloc_goal.get(&(*inode_ref).index) {
+            *goal = g;
+            return EOK;
+        }
+        let inodes_per_group = u32::from_le((*fs).sb.inodes_per_group);
+        ext4_balloc_group_goal(fs, ((*inode_ref).index - 1) / inodes_per_group, goal)
+    }
+}
+
+/// 新建的 inode 以父目录所在块组（flex_bg 时为其 flex 组）作为首次分配的目标
+///
+/// inode 分配不考虑父目录，新文件的 inode 可能位于其他块组；继承父目录的目标使
+/// 同一目录下的文件数据在物理上聚集。目标只保存在内存中，inode 释放或重新挂载后失效。
+pub unsafe fn ext4_balloc_inherit_goal(inode_ref: *mut Ext4InodeRef, parent_ref: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let inodes_per_group = u32::from_le((*fs).sb.inodes_per_group);
+        let mut goal = 0u64;
+        let r = ext4_balloc_group_goal(fs, ((*parent_ref).index - 1) / inodes_per_group, &mut goal);
+        if r != EOK {
+            return r;
+        }
+        ext4_balloc_set_goal(fs, (*inode_ref).index, goal);
+        EOK
+    }
+}
+
+/// 记录 inode 下次分配的目标（数据写入路径在分配数据块后记为其下一块）
+///
+/// 每个 inode 各自保存，交替写入多个文件时互不影响；条目数超过
+/// CONFIG_ALLOC_GOAL_CACHE_SIZE 时丢弃编号最小的 inode 的目标。
+pub unsafe fn ext4_balloc_set_goal(fs: *mut Ext4Filesystem, ino: u32, goal: u64) {
+    unsafe {
+        let goals = &mut (*fs).alloc_goal;
+        if goals.len() >= CONFIG_ALLOC_GOAL_CACHE_SIZE && !goals.contains_key(&ino) {
+            goals.pop_first();
+        }
+        goals.insert(ino, goal);
+    }
+}
+
+/// 查找 inode 的块分配目标
+///
+/// 优先使用文件最后一个数据块之后的块，否则同 ext4_balloc_bg_goal。
+pub unsafe fn ext4_balloc_find_goal(inode_ref: *mut Ext4InodeRef, goal: *mut u64) -> i32 {
+    unsafe {
+        *goal = 0;
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode_size = ext4_inode_get_size(sb, (*inode_ref).inode);
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let iblock_cnt = inode_size.div_ceil(block_size);
+
+        if iblock_cnt > 0 {
+            let r = ext4_fs_get_inode_dblk_idx(inode_ref, (iblock_cnt - 1) as u32, goal, true);
+            if r != EOK {
+                return r;
+            }
+            if *goal != 0 {
+                *goal += 1;
+                return EOK;
+            }
+            // 稀疏文件的最后一块是空洞
+        }
+        ext4_balloc_bg_goal(inode_ref, goal)
+    }
+}
+
+/// 释放单个块
+pub unsafe fn ext4_balloc_free_block(inode_ref: *mut Ext4InodeRef, baddr: u64) -> i32 {
+    unsafe { ext4_balloc_free_blocks(inode_ref, baddr, 1) }
+}
+
+/// 释放一段连续的块（可跨越块组）
+pub unsafe fn ext4_balloc_free_blocks(inode_ref: *mut Ext4InodeRef, first: u64, count: u32) -> i32 {
+    ext4_dbg!(DEBUG_BALLOC, Debug, "ext4_balloc_free_blocks: first={}, count={}", first, count);
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let blocks_per_group = u32::from_le(sb.blocks_per_group);
+
+        if count == 0 {
+            return EOK;
+        }
+        if first < u32::from_le(sb.first_data_block) as u64
+            || first + count as u64 > ext4_sb_get_blocks_cnt(sb)
+        {
+            // 块号来自磁盘上的映射，越界说明元数据已损坏
+            return EIO;
+        }
+
+        let mut start_block = first;
+        let mut remaining = count;
+        while remaining > 0 {
+            let bgid = ext4_balloc_get_bgid_of_block(sb, start_block);
+            let idx_in_bg = ext4_fs_addr_to_idx_bg(sb, start_block);
+            let free_cnt = remaining.min(blocks_per_group - idx_in_bg);
+
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let mut b = Ext4Block::new();
+            let r = ext4_balloc_load_bitmap(&mut bg_ref, &mut b);
+            if r != EOK {
+                ext4_fs_put_block_group_ref(&mut bg_ref);
+                return r;
+            }
+
+            let bg = &mut *bg_ref.block_group;
+            let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+            // 只统计真正被释放的块，避免重复释放破坏计数
+            let mut freed = 0u32;
+            for bit in idx_in_bg..idx_in_bg + free_cnt {
+                if ext4_bmap_is_bit_set(bitmap, bit) {
+                    ext4_bmap_bit_clr(bitmap, bit);
+                    freed += 1;
+                }
+            }
+            if freed != free_cnt {
+                ext4_dbg!(DEBUG_BALLOC, Warn, "freeing {} already free blocks in bg {}", free_cnt - freed, bgid);
+            }
+            ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+            ext4_block_set_dirty(&mut b);
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                ext4_fs_put_block_group_ref(&mut bg_ref);
+                return r;
+            }
+
+            // 更新 superblock、块组和 inode 的计数
+            let sb_free = ext4_sb_get_free_blocks_cnt(sb);
+            ext4_sb_set_free_blocks_cnt(sb, sb_free + freed as u64);
+            (*fs).sb_dirty = true;
+            (*fs).metrics.blocks_freed += freed as u64;
+
+            let ino_blocks = ext4_inode_get_blocks_count(sb, (*inode_ref).inode);
+            let dec = freed as u64 * (block_size / EXT4_INODE_BLOCK_SIZE) as u64;
+            ext4_inode_set_blocks_count(sb, (*inode_ref).inode, ino_blocks.saturating_sub(dec));
+            (*inode_ref).dirty = true;
+            ext4_quota_charge_inode(inode_ref, -((ino_blocks.min(dec) * EXT4_INODE_BLOCK_SIZE as u64) as i64), 0);
+
+            let bg_free = ext4_bg_get_free_blocks_count(bg, sb);
+            ext4_bg_set_free_blocks_count(bg, sb, bg_free + freed);
+            bg_ref.dirty = true;
+
+            let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+
+            remaining -= free_cnt;
+            start_block += free_cnt as u64;
+        }
+
+        // 已释放的块在缓存中的内容不再有效
+        ext4_bcache_invalidate_lba((*(*fs).bdev).bc, first, count);
+    }
+    EOK
+}
+
+/// 直接设置一段连续块在位图中的状态（可跨越块组）
+///
+/// 只更新位图和 superblock、块组的空闲计数，不涉及任何 inode 的块计数，
+/// 供日志回放时按 extent 树重建位图使用。已处于目标状态的块不重复计数。
+pub unsafe fn ext4_balloc_mark_range(fs: *mut Ext4Filesystem, first: u64, count: u32, used: bool) -> i32 {
+    ext4_dbg!(DEBUG_BALLOC, Debug, "ext4_balloc_mark_range: first={}, count={}, used={}", first, count, used);
+    unsafe {
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let blocks_per_group = u32::from_le(sb.blocks_per_group);
+
+        if count == 0 {
+            return EOK;
+        }
+        if first < u32::from_le(sb.first_data_block) as u64
+            || first + count as u64 > ext4_sb_get_blocks_cnt(sb)
+        {
+            return EIO;
+        }
+
+        let mut start_block = first;
+        let mut remaining = count;
+        while remaining > 0 {
+            let bgid = ext4_balloc_get_bgid_of_block(sb, start_block);
+            let idx_in_bg = ext4_fs_addr_to_idx_bg(sb, start_block);
+            let cnt = remaining.min(blocks_per_group - idx_in_bg);
+
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let mut b = Ext4Block::new();
+            let r = ext4_balloc_load_bitmap(&mut bg_ref, &mut b);
+            if r != EOK {
+                ext4_fs_put_block_group_ref(&mut bg_ref);
+                return r;
+            }
+
+            let bg = &mut *bg_ref.block_group;
+            let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+            let mut changed = 0u32;
+            for bit in idx_in_bg..idx_in_bg + cnt {
+                if ext4_bmap_is_bit_set(bitmap, bit) != used {
+                    if used {
+                        ext4_bmap_bit_set(bitmap, bit);
+                    } else {
+                        ext4_bmap_bit_clr(bitmap, bit);
+                    }
+                    changed += 1;
+                }
+            }
+            if changed != 0 {
+                ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+                ext4_block_set_dirty(&mut b);
+
+                let sb_free = ext4_sb_get_free_blocks_cnt(sb);
+                let bg_free = ext4_bg_get_free_blocks_count(bg, sb);
+                if used {
+                    ext4_sb_set_free_blocks_cnt(sb, sb_free.saturating_sub(changed as u64));
+                    ext4_bg_set_free_blocks_count(bg, sb, bg_free.saturating_sub(changed));
+                } else {
+                    ext4_sb_set_free_blocks_cnt(sb, sb_free + changed as u64);
+                    ext4_bg_set_free_blocks_count(bg, sb, bg_free + changed);
+                }
+                bg_ref.dirty = true;
+                (*fs).sb_dirty = true;
+            }
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+
+            remaining -= cnt;
+            start_block += cnt as u64;
+        }
+    }
+    EOK
+}
+
+/// 计算块组内的元数据区间（块组内索引 [start, end)），只依据布局和块组描述符，不读取位图
+///
+/// 包括块组开头的 superblock/GDT/预留GDT 备份，以及（flex_bg 时同一 flex 组内各块组）
+/// 位于本组的块位图、inode 位图和 inode 表。
+unsafe fn ext4_balloc_meta_ranges(fs: *mut Ext4Filesystem, bgid: u32, ranges: &mut Vec<(u32, u32)>) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let own = ext4_layout_base(sb, bgid);
+        let group_end = own.first_block + own.blocks as u64;
+
+        ranges.clear();
+        ranges.push((0, ext4_layout_base_blocks(&own)));
+
+        let groups = if ext4_sb_feature_incom(sb, EXT4_FINCOM_FLEX_BG) {
+            let flex = 1u32 << sb.log_groups_per_flex.min(31);
+            let start = bgid & !(flex - 1);
+            start..start.saturating_add(flex).min(ext4_block_group_cnt(sb))
+        } else {
+            bgid..bgid + 1
+        };
+        for g in groups {
+            let mut layout = Ext4GroupLayout::default();
+            let r = ext4_layout_get(fs, g, &mut layout);
+            if r != EOK {
+                return r;
+            }
+            for (start, len) in &ext4_layout_meta_ranges(&layout)[1..] {
+                let s = (*start).max(own.first_block);
+                let e = (start + len).min(group_end);
+                if s < e {
+                    ranges.push(((s - own.first_block) as u32, (e - own.first_block) as u32));
+                }
+            }
+        }
+    }
+    EOK
+}
+
+/// 在块组位图中分配块：从 goal_idx 开始向后查找第一个空闲块，到组末尾后回绕到组开头
+///
+/// 位图中标记为空闲、但按布局属于元数据的块不会被分配：补上位图中的标记并跳过（位图损坏时的保护）。
+unsafe fn ext4_balloc_alloc_in_group(
+    inode_ref: *mut Ext4InodeRef,
+    bgid: u32,
+    goal_idx: Option<u32>,
+    fblock: *mut u64,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        if ext4_bg_get_free_blocks_count(&*bg_ref.block_group, sb) == 0 {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return ENOSPC;
+        }
+
+        let mut meta = Vec::new();
+        let r = ext4_balloc_meta_ranges(fs, bgid, &mut meta);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        let mut b = Ext4Block::new();
+        let r = ext4_balloc_load_bitmap(&mut bg_ref, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+        let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+        let blk_in_bg = ext4_blocks_in_group_cnt(sb, bgid);
+
+        let start = goal_idx.filter(|&idx| idx < blk_in_bg).unwrap_or(0);
+        let mut found = None;
+        let mut repaired = 0;
+        for (from, to) in [(start, blk_in_bg), (0, start)] {
+            let mut rel = from;
+            while found.is_none() && ext4_bmap_bit_find_clr(bitmap, rel, to, &mut rel) == EOK {
+                if !meta.iter().any(|&(s, e)| (s..e).contains(&rel)) {
+                    found = Some(rel);
+                } else {
+                    ext4_dbg!(
+                        DEBUG_BALLOC,
+                        Warn,
+                        "metadata block marked free in bitmap: bg {}, idx {}",
+                        bgid,
+                        rel
+                    );
+                    ext4_bmap_bit_set(bitmap, rel);
+                    repaired += 1;
+                    rel += 1;
+                }
+            }
+        }
+
+        // 修正被错误计为空闲的元数据块
+        let bg = &mut *bg_ref.block_group;
+        if repaired != 0 {
+            let bg_free = ext4_bg_get_free_blocks_count(bg, sb);
+            ext4_bg_set_free_blocks_count(bg, sb, bg_free.saturating_sub(repaired));
+            let sb_free = ext4_sb_get_free_blocks_cnt(sb);
+            ext4_sb_set_free_blocks_cnt(sb, sb_free.saturating_sub(repaired as u64));
+            (*fs).sb_dirty = true;
+            ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+            ext4_block_set_dirty(&mut b);
+            bg_ref.dirty = true;
+        }
+
+        let Some(idx) = found else {
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            return if r != EOK {
+                r
+            } else if r2 != EOK {
+                r2
+            } else {
+                ENOSPC
+            };
+        };
+
+        ext4_bmap_bit_set(bitmap, idx);
+        ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+        ext4_block_set_dirty(&mut b);
+        let r = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        // 更新 superblock、块组和 inode 的计数
+        let sb_free = ext4_sb_get_free_blocks_cnt(sb);
+        ext4_sb_set_free_blocks_cnt(sb, sb_free - 1);
+        (*fs).sb_dirty = true;
+
+        let ino_blocks = ext4_inode_get_blocks_count(sb, (*inode_ref).inode);
+        let inc = (block_size / EXT4_INODE_BLOCK_SIZE) as u64;
+        ext4_inode_set_blocks_count(sb, (*inode_ref).inode, ino_blocks + inc);
+        (*inode_ref).dirty = true;
+        ext4_quota_charge_inode(inode_ref, block_size as i64, 0);
+
+        let bg_free = ext4_bg_get_free_blocks_count(bg, sb);
+        ext4_bg_set_free_blocks_count(bg, sb, bg_free - 1);
+        bg_ref.dirty = true;
+
+        *fblock = ext4_fs_bg_idx_to_addr(sb, idx, bgid);
+        (*fs).metrics.blocks_allocated += 1;
+        ext4_fs_put_block_group_ref(&mut bg_ref)
+    }
+}
+
+/// 分配一个块
+///
+/// 先在 goal 所在块组中从 goal 开始查找（回绕到组开头），失败后依次尝试其余块组。
+/// goal 无效（如0）时使用 inode 所在块组的数据区开头。
+/// inode 的块数（i_blocks）无法再增加时返回 EFBIG。
+pub unsafe fn ext4_balloc_alloc_block(
+    inode_ref: *mut Ext4InodeRef,
+    goal: u64,
+    fblock: *mut u64,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let block_group_count = ext4_block_group_cnt(sb);
+        let units = (ext4_sb_get_block_size(sb) / EXT4_INODE_BLOCK_SIZE) as u64;
+        if !ext4_inode_blocks_count_fits(sb, ext4_inode_get_blocks_count(sb, (*inode_ref).inode) + units) {
+            return EFBIG;
+        }
+
+        let first_data_block = u32::from_le(sb.first_data_block) as u64;
+        let valid = |g: u64| g >= first_data_block && g < ext4_sb_get_blocks_cnt(&(*fs).sb);
+        let mut goal = goal;
+        if !valid(goal) {
+            let r = ext4_balloc_bg_goal(inode_ref, &mut goal);
+            if r != EOK {
+                return r;
+            }
+            if !valid(goal) {
+                goal = first_data_block;
+            }
+        }
+        let sb = &(*fs).sb;
+        let goal_bg = ext4_balloc_get_bgid_of_block(sb, goal);
+        let goal_idx = ext4_fs_addr_to_idx_bg(sb, goal);
+
+        // 目标块组，其后依次是其余块组
+        let mut r = ENOSPC;
+        for i in 0..block_group_count {
+            let bgid = (goal_bg + i) % block_group_count;
+            r = ext4_balloc_alloc_in_group(inode_ref, bgid, (i == 0).then_some(goal_idx), fblock);
+            if r == ENOSPC {
+                continue;
+            }
+            if r == EOK {
+                let m = &mut (*fs).metrics;
+                if *fblock == goal {
+                    m.alloc_goal_hits += 1;
+                } else if i == 0 {
+                    m.alloc_goal_group_hits += 1;
+                } else {
+                    m.alloc_goal_misses += 1;
+                }
+            }
+            ext4_dbg!(
+                DEBUG_BALLOC,
+                Debug,
+                "ext4_balloc_alloc_block: ino={}, goal={}, fblock={}, r={}",
+                (*inode_ref).index,
+                goal,
+                *fblock,
+                r
+            );
+            return r;
+        }
+        ext4_dbg!(DEBUG_BALLOC, Info, "ext4_balloc_alloc_block: no free blocks, ino={}", (*inode_ref).index);
+        r
+    }
+}
@@ -0,0 +1,570 @@
+//! 块缓存模块
+//!
+//! 对应C实现: ext4_bcache.c
+//! C中使用红黑树维护LBA索引和LRU、使用单链表维护脏块；
+//! 这里用BTreeMap实现相同的语义，缓冲区本身仍以裸指针形式在C风格接口间传递。
+//!
+//! 在此之上增加了常驻（pinned）缓冲区：块组描述符、位图等频繁访问的元数据可以
+//! 标记为常驻，引用计数归零后不进入LRU，避免大量数据读写时被回收后反复读取。
+//! 常驻块数不超过 pin_limit（默认为缓存容量的一半），超出时拒绝新的常驻请求。
+//!
+//! 有序数据模式（ordered）下，文件数据写入期间（data_writes 非零）释放的脏元数据
+//! 一律留在脏块列表中，回收时也只丢弃干净的缓冲区，保证数据先于引用它的元数据落盘。
+//! 日志事务进行中（in_trans）同样推迟全部脏块的回写，提交时由日志模块统一写出。
+//!
+//! 撤销日志（undo_depth 非零）记录每个缓冲区在操作期间第一次被访问时的内容，
+//! 以及之后是否被写回过设备，操作失败时由 undo 模块据此恢复。
+//!
+//! 缓冲区内存按 align 对齐（绑定块设备时取设备的 ph_align），释放的缓冲区内存
+//! 留在空闲池中供下次分配和块设备的对齐中转复用，池中最多保留 EXT4_BCACHE_POOL_MAX 个。
+
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::ptr;
+
+
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockCache, Ext4Buf};
+
+/// 块缓存的索引结构
+///
+/// 对应C中 ext4_bcache 的 lba_root、lru_root 和 dirty_list 字段
+#[allow(non_camel_case_types)]
+pub struct ext4_bcache_lists {
+    /// LBA -> 缓冲区（缓存中的所有缓冲区）
+    lba_root: BTreeMap<u64, *mut Ext4Buf>,
+    /// lru_id -> 缓冲区（只包含未被引用、未常驻、可回收的缓冲区）
+    lru_root: BTreeMap<u32, *mut Ext4Buf>,
+    /// LBA -> 缓冲区（等待回写的脏缓冲区，按LBA顺序回写）
+    dirty_list: BTreeMap<u64, *mut Ext4Buf>,
+    /// LBA -> 撤销记录（撤销日志开启期间访问过的块）
+    undo: BTreeMap<u64, ext4_bcache_undo>,
+    /// 空闲的缓冲区内存（itemsize字节，按align对齐）
+    pool: alloc::vec::Vec<*mut u8>,
+}
+
+/// 空闲池中最多保留的缓冲区数
+pub const EXT4_BCACHE_POOL_MAX: usize = 32;
+
+/// 撤销日志中一个块的记录
+#[allow(non_camel_case_types)]
+pub struct ext4_bcache_undo {
+    /// 第一次访问时的内容（None 表示当时缓冲区没有有效数据，例如新分配的块）
+    pub data: Option<Box<[u8]>>,
+    /// 记录之后是否写回过设备
+    pub written: bool,
+}
+
+/// 设置缓冲区标志
+pub unsafe fn ext4_bcache_set_flag(buf: *mut Ext4Buf, b: i32) {
+    unsafe { (*buf).flags |= 1 << b };
+}
+
+/// 清除缓冲区标志
+pub unsafe fn ext4_bcache_clear_flag(buf: *mut Ext4Buf, b: i32) {
+    unsafe { (*buf).flags &= !(1 << b) };
+}
+
+/// 测试缓冲区标志
+pub unsafe fn ext4_bcache_test_flag(buf: *const Ext4Buf, b: i32) -> bool {
+    unsafe { (*buf).flags & (1 << b) != 0 }
+}
+
+/// 标记缓冲区为脏（同时视为最新数据）
+pub unsafe fn ext4_bcache_set_dirty(buf: *mut Ext4Buf) {
+    unsafe {
+        ext4_bcache_set_flag(buf, BC_UPTODATE);
+        ext4_bcache_set_flag(buf, BC_DIRTY);
+    }
+}
+
+/// 清除缓冲区的脏标志
+pub unsafe fn ext4_bcache_clear_dirty(buf: *mut Ext4Buf) {
+    unsafe {
+        ext4_bcache_clear_flag(buf, BC_UPTODATE);
+        ext4_bcache_clear_flag(buf, BC_DIRTY);
+    }
+}
+
+unsafe fn lists<'a>(bc: *mut Ext4BlockCache) -> &'a mut ext4_bcache_lists {
+    unsafe { &mut *(*bc).lists }
+}
+
+unsafe fn buf_layout(bc: *mut Ext4BlockCache) -> Layout {
+    let align = unsafe { (*bc).align.max(8) } as usize;
+    Layout::from_size_align(unsafe { (*bc).itemsize } as usize, align)
+        .expect("invalid block cache item size")
+}
+
+/// 初始化动态块缓存
+pub unsafe fn ext4_bcache_init_dynamic(bc: *mut Ext4BlockCache, cnt: u32, itemsize: u32) -> i32 {
+    ext4_dbg!(
+        DEBUG_BCACHE,
+        Debug,
+        "ext4_bcache_init_dynamic: cnt={}, itemsize={}",
+        cnt, itemsize
+    );
+    if bc.is_null() || itemsize == 0 {
+        return EINVAL;
+    }
+    unsafe {
+        ptr::write(bc, Ext4BlockCache::new());
+        (*bc).cnt = cnt;
+        (*bc).itemsize = itemsize;
+        (*bc).pin_limit = cnt / 2;
+        (*bc).lists = Box::into_raw(Box::new(ext4_bcache_lists {
+            lba_root: BTreeMap::new(),
+            lru_root: BTreeMap::new(),
+            dirty_list: BTreeMap::new(),
+            undo: BTreeMap::new(),
+            pool: alloc::vec::Vec::new(),
+        }));
+    }
+    EOK
+}
+
+/// 销毁动态块缓存（缓冲区应已通过 ext4_bcache_cleanup 释放）
+pub unsafe fn ext4_bcache_fini_dynamic(bc: *mut Ext4BlockCache) -> i32 {
+    ext4_dbg!(DEBUG_BCACHE, Debug, "ext4_bcache_fini_dynamic");
+    unsafe {
+        if bc.is_null() || (*bc).lists.is_null() {
+            return EOK;
+        }
+        ext4_bcache_cleanup(bc);
+        ext4_bcache_pool_drain(bc);
+        drop(Box::from_raw((*bc).lists));
+        (*bc).lists = ptr::null_mut();
+    }
+    EOK
+}
+
+/// 释放缓存中的所有缓冲区（未回写的脏数据将被丢弃）
+pub unsafe fn ext4_bcache_cleanup(bc: *mut Ext4BlockCache) {
+    ext4_dbg!(DEBUG_BCACHE, Debug, "ext4_bcache_cleanup");
+    unsafe {
+        if bc.is_null() || (*bc).lists.is_null() {
+            return;
+        }
+        let all: alloc::vec::Vec<_> = lists(bc).lba_root.values().copied().collect();
+        for buf in all {
+            (*buf).refctr = 0;
+            ext4_bcache_clear_flag(buf, BC_PINNED);
+            ext4_bcache_drop_buf(bc, buf);
+        }
+        (*bc).pinned_blocks = 0;
+    }
+}
+
+/// 缓存中的缓冲区数量是否已达到上限
+pub unsafe fn ext4_bcache_is_full(bc: *mut Ext4BlockCache) -> bool {
+    unsafe { (*bc).cnt <= (*bc).ref_blocks }
+}
+
+/// 设置缓冲区的内存对齐（必须是2的幂，0表示默认）
+///
+/// 只能在缓存中没有缓冲区时修改，否则返回 EBUSY；空闲池随之清空。
+pub unsafe fn ext4_bcache_set_align(bc: *mut Ext4BlockCache, align: u32) -> i32 {
+    unsafe {
+        if align != 0 && !align.is_power_of_two() {
+            return EINVAL;
+        }
+        if (*bc).align.max(8) == align.max(8) {
+            return EOK;
+        }
+        if !(*bc).lists.is_null() {
+            if !lists(bc).lba_root.is_empty() {
+                return EBUSY;
+            }
+            ext4_bcache_pool_drain(bc);
+        }
+        (*bc).align = align;
+    }
+    EOK
+}
+
+/// 从空闲池取出一块缓冲区内存（池为空时新分配，内容未定义），分配失败时返回空指针
+pub unsafe fn ext4_bcache_pool_get(bc: *mut Ext4BlockCache) -> *mut u8 {
+    unsafe {
+        match lists(bc).pool.pop() {
+            Some(data) => data,
+            None => alloc_zeroed(buf_layout(bc)),
+        }
+    }
+}
+
+/// 把缓冲区内存放回空闲池（池已满时直接释放）
+pub unsafe fn ext4_bcache_pool_put(bc: *mut Ext4BlockCache, data: *mut u8) {
+    unsafe {
+        let pool = &mut lists(bc).pool;
+        if pool.len() < EXT4_BCACHE_POOL_MAX {
+            pool.push(data);
+        } else {
+            dealloc(data, buf_layout(bc));
+        }
+    }
+}
+
+/// 释放空闲池中的全部内存（用于宿主内存紧张时和销毁缓存时）
+pub unsafe fn ext4_bcache_pool_drain(bc: *mut Ext4BlockCache) {
+    unsafe {
+        let layout = buf_layout(bc);
+        for data in lists(bc).pool.drain(..) {
+            dealloc(data, layout);
+        }
+    }
+}
+
+/// 分配一个新的缓冲区（内存优先取自空闲池）
+unsafe fn ext4_buf_alloc(bc: *mut Ext4BlockCache, lba: u64) -> *mut Ext4Buf {
+    unsafe {
+        let data = ext4_bcache_pool_get(bc);
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+        ptr::write_bytes(data, 0, (*bc).itemsize as usize);
+        Box::into_raw(Box::new(Ext4Buf {
+            flags: 0,
+            lba,
+            data,
+            lru_prio: 0,
+            lru_id: 0,
+            refctr: 0,
+            bc,
+            on_dirty_list: false,
+        }))
+    }
+}
+
+/// 释放缓冲区（内存放回空闲池）
+unsafe fn ext4_buf_free(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        ext4_bcache_pool_put(bc, (*buf).data);
+        drop(Box::from_raw(buf));
+    }
+}
+
+/// 将缓冲区加入脏块列表
+pub unsafe fn ext4_bcache_insert_dirty_node(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if !(*buf).on_dirty_list {
+            lists(bc).dirty_list.insert((*buf).lba, buf);
+            (*buf).on_dirty_list = true;
+        }
+    }
+}
+
+/// 将缓冲区移出脏块列表
+pub unsafe fn ext4_bcache_remove_dirty_node(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if (*buf).on_dirty_list {
+            lists(bc).dirty_list.remove(&(*buf).lba);
+            (*buf).on_dirty_list = false;
+        }
+    }
+}
+
+/// 脏块列表中的缓冲区数
+pub unsafe fn ext4_bcache_dirty_count(bc: *mut Ext4BlockCache) -> u32 {
+    unsafe { lists(bc).dirty_list.len() as u32 }
+}
+
+/// 脏块列表中的第一个缓冲区
+pub unsafe fn ext4_bcache_first_dirty(bc: *mut Ext4BlockCache) -> *mut Ext4Buf {
+    unsafe {
+        lists(bc)
+            .dirty_list
+            .values()
+            .next()
+            .copied()
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+/// 最久未使用的可回收缓冲区
+pub unsafe fn ext4_buf_lowest_lru(bc: *mut Ext4BlockCache) -> *mut Ext4Buf {
+    unsafe {
+        lists(bc)
+            .lru_root
+            .values()
+            .next()
+            .copied()
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+/// 最久未使用的干净缓冲区（有序数据模式下写入数据期间只回收这些）
+pub unsafe fn ext4_buf_lowest_clean_lru(bc: *mut Ext4BlockCache) -> *mut Ext4Buf {
+    unsafe {
+        lists(bc)
+            .lru_root
+            .values()
+            .find(|&&buf| !ext4_bcache_test_flag(buf, BC_DIRTY))
+            .copied()
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+/// 是否推迟元数据回写（有序数据模式下正在写入文件数据，或事务进行中）
+pub unsafe fn ext4_bcache_holds_metadata(bc: *mut Ext4BlockCache) -> bool {
+    unsafe { ((*bc).ordered && (*bc).data_writes != 0) || (*bc).in_trans }
+}
+
+/// 脏块列表中的全部缓冲区（按LBA排序）
+pub unsafe fn ext4_bcache_dirty_bufs(bc: *mut Ext4BlockCache) -> alloc::vec::Vec<*mut Ext4Buf> {
+    unsafe { lists(bc).dirty_list.values().copied().collect() }
+}
+
+/// 使全部脏缓冲区失效（丢弃未回写的修改）
+pub unsafe fn ext4_bcache_invalidate_dirty(bc: *mut Ext4BlockCache) {
+    unsafe {
+        for buf in ext4_bcache_dirty_bufs(bc) {
+            ext4_bcache_invalidate_buf(bc, buf);
+        }
+    }
+}
+
+/// 记录缓冲区的当前内容（撤销日志未开启或已有记录时不做任何事）
+pub unsafe fn ext4_bcache_undo_record(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if (*bc).undo_depth == 0 || lists(bc).undo.contains_key(&(*buf).lba) {
+            return;
+        }
+        let data = ext4_bcache_test_flag(buf, BC_UPTODATE).then(|| {
+            core::slice::from_raw_parts((*buf).data, (*bc).itemsize as usize).into()
+        });
+        lists(bc).undo.insert((*buf).lba, ext4_bcache_undo { data, written: false });
+    }
+}
+
+/// 缓冲区已写回设备
+pub unsafe fn ext4_bcache_undo_written(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if (*bc).undo_depth == 0 {
+            return;
+        }
+        if let Some(undo) = lists(bc).undo.get_mut(&(*buf).lba) {
+            undo.written = true;
+        }
+    }
+}
+
+/// 取出全部撤销记录（同时清空撤销日志）
+pub unsafe fn ext4_bcache_undo_take(bc: *mut Ext4BlockCache) -> BTreeMap<u64, ext4_bcache_undo> {
+    unsafe { core::mem::take(&mut lists(bc).undo) }
+}
+
+/// 从缓存中丢弃缓冲区
+pub unsafe fn ext4_bcache_drop_buf(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if (*buf).refctr != 0 {
+            ext4_dbg!(DEBUG_BCACHE, Debug, "ext4_bcache_drop_buf: buffer {} is still referenced", (*buf).lba);
+            return;
+        }
+        if ext4_bcache_test_flag(buf, BC_PINNED) {
+            ext4_dbg!(DEBUG_BCACHE, Debug, "ext4_bcache_drop_buf: buffer {} is pinned", (*buf).lba);
+            return;
+        }
+        let lists = lists(bc);
+        lists.lba_root.remove(&(*buf).lba);
+        lists.lru_root.remove(&(*buf).lru_id);
+        // 强制丢弃脏缓冲区
+        ext4_bcache_remove_dirty_node(bc, buf);
+        ext4_buf_free(bc, buf);
+        (*bc).ref_blocks -= 1;
+    }
+}
+
+/// 使缓冲区失效（丢弃未回写的修改，下次访问时重新读取）
+pub unsafe fn ext4_bcache_invalidate_buf(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        ext4_bcache_remove_dirty_node(bc, buf);
+        ext4_bcache_clear_dirty(buf);
+        ext4_bcache_clear_flag(buf, BC_DEFER);
+    }
+}
+
+/// 使一段LBA范围内的缓冲区失效（用于块被释放后）
+pub unsafe fn ext4_bcache_invalidate_lba(bc: *mut Ext4BlockCache, from: u64, cnt: u32) {
+    unsafe {
+        if bc.is_null() || (*bc).lists.is_null() || cnt == 0 {
+            return;
+        }
+        let end = from + cnt as u64;
+        let bufs: alloc::vec::Vec<_> =
+            lists(bc).lba_root.range(from..end).map(|(_, &buf)| buf).collect();
+        for buf in bufs {
+            ext4_bcache_invalidate_buf(bc, buf);
+        }
+    }
+}
+
+/// 缓存中是否有指定LBA的缓冲区（不增加引用，也不更新LRU）
+pub unsafe fn ext4_bcache_contains(bc: *mut Ext4BlockCache, lba: u64) -> bool {
+    unsafe { lists(bc).lba_root.contains_key(&lba) }
+}
+
+/// 查找缓冲区并增加引用（不存在时返回空指针）
+pub unsafe fn ext4_bcache_find_get(
+    bc: *mut Ext4BlockCache,
+    b: *mut Ext4Block,
+    lba: u64,
+) -> *mut Ext4Buf {
+    unsafe {
+        let buf = match lists(bc).lba_root.get(&lba) {
+            Some(&buf) => buf,
+            None => return ptr::null_mut(),
+        };
+        if (*buf).refctr == 0 {
+            lists(bc).lru_root.remove(&(*buf).lru_id);
+            (*bc).lru_ctr += 1;
+            (*buf).lru_id = (*bc).lru_ctr;
+        }
+        (*buf).refctr += 1;
+        (*b).lb_id = lba;
+        (*b).buf = buf;
+        (*b).data = (*buf).data;
+        buf
+    }
+}
+
+/// 为块分配缓冲区（已在缓存中时直接引用）
+pub unsafe fn ext4_bcache_alloc(
+    bc: *mut Ext4BlockCache,
+    b: *mut Ext4Block,
+    is_new: *mut bool,
+) -> i32 {
+    unsafe {
+        let lba = (*b).lb_id;
+        if !ext4_bcache_find_get(bc, b, lba).is_null() {
+            *is_new = false;
+            return EOK;
+        }
+
+        let buf = ext4_buf_alloc(bc, lba);
+        if buf.is_null() {
+            return ENOMEM;
+        }
+
+        // 缓存中多了一个缓冲区
+        (*bc).ref_blocks += 1;
+        if (*bc).max_ref_blocks < (*bc).ref_blocks {
+            (*bc).max_ref_blocks = (*bc).ref_blocks;
+        }
+
+        (*buf).refctr = 1;
+        (*bc).lru_ctr += 1;
+        (*buf).lru_id = (*bc).lru_ctr;
+        lists(bc).lba_root.insert(lba, buf);
+
+        (*b).buf = buf;
+        (*b).data = (*buf).data;
+        *is_new = true;
+    }
+    EOK
+}
+
+/// 释放块的引用
+///
+/// 引用计数归零后缓冲区进入LRU；脏缓冲区根据回写模式加入脏块列表或立即写回
+/// （有序数据模式下写入数据期间总是加入脏块列表），脏块列表达到max_dirty时回写到一半。
+pub unsafe fn ext4_bcache_free(bc: *mut Ext4BlockCache, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        let buf = (*b).buf;
+        if buf.is_null() || (*buf).refctr == 0 {
+            return EINVAL;
+        }
+
+        (*buf).refctr -= 1;
+        if (*buf).refctr == 0 {
+            if !ext4_bcache_test_flag(buf, BC_PINNED) {
+                lists(bc).lru_root.insert((*buf).lru_id, buf);
+            }
+
+            // 该缓冲区可以被回写
+            if ext4_bcache_test_flag(buf, BC_DIRTY) && ext4_bcache_test_flag(buf, BC_UPTODATE) {
+                let bdev = (*bc).bdev;
+                let defer = ((*bdev).cache_write_back != 0 && !ext4_bcache_test_flag(buf, BC_FLUSH))
+                    || ext4_bcache_holds_metadata(bc)
+                    || ext4_bcache_test_flag(buf, BC_DEFER);
+                if defer && !ext4_bcache_test_flag(buf, BC_TMP) {
+                    ext4_bcache_insert_dirty_node(bc, buf);
+                    // 脏块过多时回写最多一半，避免写入期间集中回写整个缓存（推迟回写元数据期间除外）
+                    if (*bc).max_dirty != 0
+                        && ext4_bcache_dirty_count(bc) >= (*bc).max_dirty
+                        && !ext4_bcache_holds_metadata(bc)
+                    {
+                        let r = crate::block::ext4_block_cache_flush_to(bdev, (*bc).max_dirty / 2);
+                        if r != EOK {
+                            return r;
+                        }
+                    }
+                } else {
+                    let r = crate::block::ext4_block_flush_buf(bdev, buf);
+                    ext4_bcache_clear_flag(buf, BC_FLUSH);
+                    if r != EOK {
+                        return r;
+                    }
+                }
+            }
+
+            // 已失效或临时缓冲区不再保留
+            if !ext4_bcache_test_flag(buf, BC_UPTODATE) || ext4_bcache_test_flag(buf, BC_TMP) {
+                ext4_bcache_drop_buf(bc, buf);
+            }
+        }
+
+        (*b).lb_id = 0;
+        (*b).buf = ptr::null_mut();
+        (*b).data = ptr::null_mut();
+    }
+    EOK
+}
+
+/// 将缓冲区标记为常驻（已常驻时直接返回）
+///
+/// 常驻缓冲区不会被LRU回收；常驻块数达到 pin_limit 时返回 ENOSPC。
+pub unsafe fn ext4_bcache_pin_buf(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) -> i32 {
+    unsafe {
+        if ext4_bcache_test_flag(buf, BC_PINNED) {
+            return EOK;
+        }
+        if (*bc).pinned_blocks >= (*bc).pin_limit {
+            ext4_dbg!(DEBUG_BCACHE, Debug, "ext4_bcache_pin_buf: pin limit reached, lba={}", (*buf).lba);
+            return ENOSPC;
+        }
+        // 未被引用的缓冲区在LRU中，常驻后移出
+        if (*buf).refctr == 0 {
+            lists(bc).lru_root.remove(&(*buf).lru_id);
+        }
+        ext4_bcache_set_flag(buf, BC_PINNED);
+        (*bc).pinned_blocks += 1;
+        ext4_dbg!(DEBUG_BCACHE, Trace, "pin buffer: lba={}", (*buf).lba);
+    }
+    EOK
+}
+
+/// 取消缓冲区的常驻标记（未被引用时重新进入LRU）
+pub unsafe fn ext4_bcache_unpin_buf(bc: *mut Ext4BlockCache, buf: *mut Ext4Buf) {
+    unsafe {
+        if !ext4_bcache_test_flag(buf, BC_PINNED) {
+            return;
+        }
+        ext4_bcache_clear_flag(buf, BC_PINNED);
+        (*bc).pinned_blocks -= 1;
+        ext4_dbg!(DEBUG_BCACHE, Trace, "unpin buffer: lba={}", (*buf).lba);
+        if (*buf).refctr == 0 {
+            if ext4_bcache_test_flag(buf, BC_UPTODATE) {
+                lists(bc).lru_root.insert((*buf).lru_id, buf);
+            } else {
+                ext4_bcache_drop_buf(bc, buf);
+            }
+        }
+    }
+}
+
+/// 缓冲区是否常驻
+pub unsafe fn ext4_bcache_is_pinned(buf: *const Ext4Buf) -> bool {
+    unsafe { ext4_bcache_test_flag(buf, BC_PINNED) }
+}
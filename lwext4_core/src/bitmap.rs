@@ -0,0 +1,82 @@
+//! 位图操作模块
+//!
+//! 对应C实现: ext4_bitmap.c
+
+use crate::consts::*;
+
+/// 置位
+pub fn ext4_bmap_bit_set(bmap: &mut [u8], bit: u32) {
+    bmap[(bit >> 3) as usize] |= 1 << (bit & 7);
+}
+
+/// 清位
+pub fn ext4_bmap_bit_clr(bmap: &mut [u8], bit: u32) {
+    bmap[(bit >> 3) as usize] &= !(1 << (bit & 7));
+}
+
+/// 位是否已置位
+pub fn ext4_bmap_is_bit_set(bmap: &[u8], bit: u32) -> bool {
+    bmap[(bit >> 3) as usize] & (1 << (bit & 7)) != 0
+}
+
+/// 位是否已清除
+pub fn ext4_bmap_is_bit_clr(bmap: &[u8], bit: u32) -> bool {
+    !ext4_bmap_is_bit_set(bmap, bit)
+}
+
+/// 清除从 sbit 开始的 bcnt 个位
+pub fn ext4_bmap_bits_free(bmap: &mut [u8], sbit: u32, bcnt: u32) {
+    let mut bit = sbit;
+    let end = sbit + bcnt;
+
+    // 逐位处理到字节边界
+    while bit < end && bit & 7 != 0 {
+        ext4_bmap_bit_clr(bmap, bit);
+        bit += 1;
+    }
+    // 整字节清除
+    while bit + 8 <= end {
+        bmap[(bit >> 3) as usize] = 0;
+        bit += 8;
+    }
+    while bit < end {
+        ext4_bmap_bit_clr(bmap, bit);
+        bit += 1;
+    }
+}
+
+/// 在 [sbit, ebit) 范围内查找第一个清除的位
+///
+/// 找到时写入 bit_id 并返回 EOK，否则返回 ENOSPC。
+pub fn ext4_bmap_bit_find_clr(bmap: &[u8], sbit: u32, ebit: u32, bit_id: &mut u32) -> i32 {
+    let mut bit = sbit;
+
+    while bit < ebit {
+        // 整字节已满时跳过
+        if bit & 7 == 0 && bit + 8 <= ebit && bmap[(bit >> 3) as usize] == 0xFF {
+            bit += 8;
+            continue;
+        }
+        if ext4_bmap_is_bit_clr(bmap, bit) {
+            *bit_id = bit;
+            return EOK;
+        }
+        bit += 1;
+    }
+    ENOSPC
+}
+
+/// 将位图中 [start_bit, end_bit) 范围（超出块组实际大小的部分）全部置位
+pub fn ext4_fs_mark_bitmap_end(start_bit: u32, end_bit: u32, bmap: &mut [u8]) {
+    if start_bit >= end_bit {
+        return;
+    }
+    let mut i = start_bit;
+    while i < end_bit && i & 7 != 0 {
+        ext4_bmap_bit_set(bmap, i);
+        i += 1;
+    }
+    if i < end_bit {
+        bmap[(i >> 3) as usize..(end_bit >> 3) as usize].fill(0xFF);
+    }
+}
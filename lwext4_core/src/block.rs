@@ -1,14 +1,18 @@
 //! 块操作模块
 
+use core::ptr;
+
+use crate::bcache::*;
+use crate::fs::ext4_fs_yield_point;
 use crate::consts::*;
-use crate::{BlockDevice, Ext4BlockCache, Ext4BlockDevice, Ext4Error, Ext4Result};
-use log::debug;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockCache, Ext4BlockDevice, Ext4Buf, Ext4Metrics};
 
 /// 锁定块设备接口
 ///
 /// 如果块设备接口提供了 lock 回调，则调用它。
 /// 这用于在多线程环境中保护块设备访问。
-pub fn ext4_bdif_lock(bdev: *mut Ext4BlockDevice) {
+pub unsafe fn ext4_bdif_lock(bdev: *mut Ext4BlockDevice) {
     unsafe {
         // 检查 lock 函数指针是否存在
         if (*(*bdev).bdif).lock.is_none() {
@@ -26,7 +30,7 @@ pub fn ext4_bdif_lock(bdev: *mut Ext4BlockDevice) {
 /// 解锁块设备接口
 ///
 /// 如果块设备接口提供了 unlock 回调，则调用它。
-pub fn ext4_bdif_unlock(bdev: *mut Ext4BlockDevice) {
+pub unsafe fn ext4_bdif_unlock(bdev: *mut Ext4BlockDevice) {
     unsafe {
         // 检查 unlock 函数指针是否存在
         if (*(*bdev).bdif).unlock.is_none() {
@@ -41,119 +45,708 @@ pub fn ext4_bdif_unlock(bdev: *mut Ext4BlockDevice) {
     }
 }
 
-/// 初始化块设备（占位实现）
-pub fn ext4_block_init(bdev: *mut Ext4BlockDevice) -> i32 {
-    // TODO: 初始化块设备
-    debug!("ext4_block_init");
+/// 初始化块设备（打开底层设备，支持多次引用）
+pub unsafe fn ext4_block_init(bdev: *mut Ext4BlockDevice) -> i32 {
+    ext4_dbg!(DEBUG_BLOCKDEV, Debug, "ext4_block_init");
+    unsafe {
+        let bdif = (*bdev).bdif;
+        if (*bdif).ph_refctr != 0 {
+            (*bdif).ph_refctr += 1;
+            return EOK;
+        }
+
+        let r = match (*bdif).open {
+            Some(open) => open(bdev),
+            None => ENOTSUP,
+        };
+        if r != EOK {
+            return r;
+        }
+        (*bdif).ph_refctr = 1;
+    }
     EOK
 }
 
-/// 关闭块设备（占位实现）
-pub fn ext4_block_fini(bdev: *mut Ext4BlockDevice) -> i32 {
-    debug!("ext4_block_fini");
-    EOK
+/// 关闭块设备（最后一个引用释放时关闭底层设备）
+pub unsafe fn ext4_block_fini(bdev: *mut Ext4BlockDevice) -> i32 {
+    ext4_dbg!(DEBUG_BLOCKDEV, Debug, "ext4_block_fini");
+    unsafe {
+        let bdif = (*bdev).bdif;
+        if (*bdif).ph_refctr == 0 {
+            return EOK;
+        }
+        (*bdif).ph_refctr -= 1;
+        if (*bdif).ph_refctr != 0 {
+            return EOK;
+        }
+        match (*bdif).close {
+            Some(close) => close(bdev),
+            None => EOK,
+        }
+    }
+}
+
+/// 块号或块数换算为字节数（溢出时返回 None）
+///
+/// 块号可能来自损坏或恶意的镜像，换算前不能假定乘积不会溢出。
+pub fn ext4_blocks_to_bytes(blocks: u64, block_size: u64) -> Option<u64> {
+    blocks.checked_mul(block_size)
+}
+
+/// 分区内的字节范围 [offset, offset + len) 在设备上的起始位置（溢出或超出分区时返回 None）
+unsafe fn ext4_block_dev_pos(bdev: *mut Ext4BlockDevice, offset: u64, len: u64) -> Option<u64> {
+    unsafe {
+        if offset.checked_add(len)? > (*bdev).part_size {
+            return None;
+        }
+        offset.checked_add((*bdev).part_offset)
+    }
 }
 
-/// 读取字节（占位实现）
-pub fn ext4_block_readbytes(
+/// 按字节偏移读取（绕过块缓存，处理首尾不对齐的物理块）
+pub unsafe fn ext4_block_readbytes(
     bdev: *mut Ext4BlockDevice,
     offset: u64,
     buf: *mut u8,
     len: usize,
 ) -> i32 {
-    // TODO: 实现字节读取
-    // 1. 计算起始块号
-    // 2. 读取跨越的所有块
-    // 3. 复制所需字节到 buf
     unsafe {
-        if (*(*bdev).bdif).ph_refctr == 0 {
+        let bdif = (*bdev).bdif;
+        if (*bdif).ph_refctr == 0 {
             return EIO;
         }
-        if offset + len as u64 > (*bdev).part_size {
+        let Some(pos) = ext4_block_dev_pos(bdev, offset, len as u64) else {
+            return ENXIO;
+        };
+
+        let ph_bsize = (*bdif).ph_bsize as u64;
+        let mut block_idx = pos / ph_bsize;
+        let mut p = buf;
+        let mut len = len as u64;
+
+        // 第一个不对齐的物理块
+        let unalg = pos & (ph_bsize - 1);
+        if unalg != 0 {
+            let rlen = (ph_bsize - unalg).min(len);
+            let r = ext4_bdif_bread(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+            ptr::copy_nonoverlapping((*bdif).ph_bbuf.add(unalg as usize), p, rlen as usize);
+            p = p.add(rlen as usize);
+            len -= rlen;
+            block_idx += 1;
+        }
+
+        // 对齐部分
+        let blen = len / ph_bsize;
+        if blen > u32::MAX as u64 {
             return EINVAL;
         }
-        let block_idx = (offset + (*bdev).part_offset) / (*(*bdev).bdif).ph_bsize as u64;
-        let unalg=offset & ((*(*bdev).bdif).ph_bsize-1) as u64;
-        if unalg!=0 {
-            let rlen:u32 = if (*(*bdev).bdif).ph_bsize -unalg as u32>len as u32{len as u32} else {(*(*bdev).bdif).ph_bsize-unalg as u32} ;
+        if blen != 0 {
+            let r = ext4_bdif_bread(bdev, p as _, block_idx, blen as u32);
+            if r != EOK {
+                return r;
+            }
+            p = p.add((blen * ph_bsize) as usize);
+            len -= blen * ph_bsize;
+            block_idx += blen;
+        }
+
+        // 剩余部分
+        if len != 0 {
+            let r = ext4_bdif_bread(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+            ptr::copy_nonoverlapping((*bdif).ph_bbuf, p, len as usize);
         }
-        debug!("ext4_block_readbytes: offset={}, len={}", offset, len);
-        EOK
     }
+    ext4_dbg!(DEBUG_BLOCKDEV, Trace, "ext4_block_readbytes: offset={}, len={}", offset, len);
+    EOK
 }
 
-/// 写入字节（占位实现）
-pub fn ext4_block_writebytes(
+/// 按字节偏移写入（绕过块缓存，不对齐部分先读后写）
+pub unsafe fn ext4_block_writebytes(
     bdev: *mut Ext4BlockDevice,
     offset: u64,
     buf: *const u8,
     len: usize,
 ) -> i32 {
-    // TODO: 实现字节写入
-    debug!("ext4_block_writebytes: offset={}, len={}", offset, len);
+    unsafe {
+        let bdif = (*bdev).bdif;
+        if (*bdif).ph_refctr == 0 {
+            return EIO;
+        }
+        let Some(pos) = ext4_block_dev_pos(bdev, offset, len as u64) else {
+            return ENXIO;
+        };
+
+        let ph_bsize = (*bdif).ph_bsize as u64;
+        let mut block_idx = pos / ph_bsize;
+        let mut p = buf;
+        let mut len = len as u64;
+
+        // 第一个不对齐的物理块
+        let unalg = pos & (ph_bsize - 1);
+        if unalg != 0 {
+            let wlen = (ph_bsize - unalg).min(len);
+            let r = ext4_bdif_bread(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+            ptr::copy_nonoverlapping(p, (*bdif).ph_bbuf.add(unalg as usize), wlen as usize);
+            let r = ext4_bdif_bwrite(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+            p = p.add(wlen as usize);
+            len -= wlen;
+            block_idx += 1;
+        }
+
+        // 对齐部分
+        let blen = len / ph_bsize;
+        if blen > u32::MAX as u64 {
+            return EINVAL;
+        }
+        if blen != 0 {
+            let r = ext4_bdif_bwrite(bdev, p as _, block_idx, blen as u32);
+            if r != EOK {
+                return r;
+            }
+            p = p.add((blen * ph_bsize) as usize);
+            len -= blen * ph_bsize;
+            block_idx += blen;
+        }
+
+        // 剩余部分
+        if len != 0 {
+            let r = ext4_bdif_bread(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+            ptr::copy_nonoverlapping(p, (*bdif).ph_bbuf, len as usize);
+            let r = ext4_bdif_bwrite(bdev, (*bdif).ph_bbuf as _, block_idx, 1);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    ext4_dbg!(DEBUG_BLOCKDEV, Trace, "ext4_block_writebytes: offset={}, len={}", offset, len);
+    EOK
+}
+
+/// 将单个脏缓冲区写回设备
+pub unsafe fn ext4_block_flush_buf(bdev: *mut Ext4BlockDevice, buf: *mut Ext4Buf) -> i32 {
+    unsafe {
+        if ext4_bcache_test_flag(buf, BC_DIRTY) && ext4_bcache_test_flag(buf, BC_UPTODATE) {
+            ext4_dbg!(DEBUG_BCACHE, Trace, "flush buffer: lba={}", (*buf).lba);
+            let r = ext4_blocks_set_direct(bdev, (*buf).data as _, (*buf).lba, 1);
+            if r != EOK {
+                ext4_dbg!(DEBUG_BCACHE, Warn, "flush buffer failed: lba={}, r={}", (*buf).lba, r);
+                return r;
+            }
+            ext4_bcache_remove_dirty_node((*bdev).bc, buf);
+            ext4_bcache_clear_flag(buf, BC_DIRTY);
+            ext4_bcache_clear_flag(buf, BC_DEFER);
+            ext4_bcache_undo_written((*bdev).bc, buf);
+            if let Some(m) = ext4_bdev_metrics(bdev) {
+                m.dirty_flushes += 1;
+            }
+        }
+    }
+    EOK
+}
+
+/// 将指定LBA的缓冲区（如果在缓存中）写回设备
+pub unsafe fn ext4_block_flush_lba(bdev: *mut Ext4BlockDevice, lba: u64) -> i32 {
+    unsafe {
+        let mut b = Ext4Block::new();
+        let buf = ext4_bcache_find_get((*bdev).bc, &mut b, lba);
+        if buf.is_null() {
+            return EOK;
+        }
+        let r = ext4_block_flush_buf(bdev, buf);
+        let r2 = ext4_bcache_free((*bdev).bc, &mut b);
+        if r != EOK { r } else { r2 }
+    }
+}
+
+/// 按LBA顺序回写脏块，直到可回写的脏块数不超过target（事务进行中不回写）
+///
+/// 标记为BC_DEFER的缓冲区（块组描述符）留到ext4_block_cache_flush时回写。
+pub unsafe fn ext4_block_cache_flush_to(bdev: *mut Ext4BlockDevice, target: u32) -> i32 {
+    unsafe {
+        let bc = (*bdev).bc;
+        if bc.is_null() || (*bc).lists.is_null() || (*bc).in_trans {
+            return EOK;
+        }
+        let mut bufs = ext4_bcache_dirty_bufs(bc);
+        bufs.retain(|&buf| !ext4_bcache_test_flag(buf, BC_DEFER));
+        let excess = bufs.len().saturating_sub(target as usize);
+        for &buf in &bufs[..excess] {
+            let r = ext4_block_flush_buf(bdev, buf);
+            if r != EOK {
+                return r;
+            }
+            // 未处于最新状态的缓冲区不会被写回，直接移出脏块列表
+            ext4_bcache_remove_dirty_node(bc, buf);
+            if !(*bdev).fs.is_null() {
+                ext4_fs_yield_point((*bdev).fs);
+            }
+        }
+    }
+    EOK
+}
+
+/// 按LBA顺序回写最多max_blocks个脏块，remaining返回剩余的脏块数（事务进行中不回写）
+///
+/// 供宿主的周期任务分批调用实现后台回写。标记为BC_DEFER的缓冲区（块组描述符）排在其他脏块之后，
+/// 反复调用直到remaining为0即可写回全部脏块。
+pub unsafe fn ext4_block_cache_flush_some(
+    bdev: *mut Ext4BlockDevice,
+    max_blocks: u32,
+    remaining: *mut u32,
+) -> i32 {
+    unsafe {
+        *remaining = 0;
+        let bc = (*bdev).bc;
+        if bc.is_null() || (*bc).lists.is_null() {
+            return EOK;
+        }
+        if !(*bc).in_trans {
+            let mut bufs = ext4_bcache_dirty_bufs(bc);
+            // 稳定排序，两部分内部仍按LBA顺序
+            bufs.sort_by_key(|&buf| ext4_bcache_test_flag(buf, BC_DEFER));
+            for &buf in bufs.iter().take(max_blocks as usize) {
+                let r = ext4_block_flush_buf(bdev, buf);
+                if r != EOK {
+                    *remaining = ext4_bcache_dirty_count(bc);
+                    return r;
+                }
+                // 未处于最新状态的缓冲区不会被写回，直接移出脏块列表
+                ext4_bcache_remove_dirty_node(bc, buf);
+            }
+        }
+        *remaining = ext4_bcache_dirty_count(bc);
+    }
     EOK
 }
 
-/// 刷新块缓存（占位实现）
-pub fn ext4_block_cache_flush(bdev: *mut Ext4BlockDevice) -> i32 {
-    debug!("ext4_block_cache_flush");
+/// 回写缓存中的所有脏块
+pub unsafe fn ext4_block_cache_flush(bdev: *mut Ext4BlockDevice) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_BCACHE, "ext4_block_cache_flush");
+    unsafe {
+        let bc = (*bdev).bc;
+        if bc.is_null() || (*bc).lists.is_null() {
+            return EOK;
+        }
+        // 事务进行中：脏块在提交时写出
+        if (*bc).in_trans {
+            return EOK;
+        }
+        loop {
+            let buf = ext4_bcache_first_dirty(bc);
+            if buf.is_null() {
+                break;
+            }
+            let r = ext4_block_flush_buf(bdev, buf);
+            if r != EOK {
+                return r;
+            }
+            // 未处于最新状态的缓冲区不会被写回，直接移出脏块列表
+            ext4_bcache_remove_dirty_node(bc, buf);
+            if !(*bdev).fs.is_null() {
+                ext4_fs_yield_point((*bdev).fs);
+            }
+        }
+    }
     EOK
 }
 
-/// 绑定块缓存（占位实现）
-pub fn ext4_block_bind_bcache(bdev: *mut Ext4BlockDevice, bc: *mut Ext4BlockCache) -> i32 {
-    debug!("ext4_block_bind_bcache");
+/// 缓存已满时回收最久未使用的缓冲区（脏缓冲区先写回）
+///
+/// 有序数据模式下写入数据期间只回收干净的缓冲区，没有可回收的缓冲区时缓存暂时超出容量。
+unsafe fn ext4_block_cache_shake(bdev: *mut Ext4BlockDevice) -> i32 {
+    unsafe {
+        let bc = (*bdev).bc;
+        ext4_block_cache_reclaim(bdev, (*bc).cnt.saturating_sub(1))
+    }
+}
+
+/// 缩减块缓存，直到缓存中的块数不超过target（用于宿主内存紧张时）
+///
+/// 按LRU顺序回收，脏缓冲区先写回（有序数据模式下写入数据期间、事务进行中只回收干净的缓冲区）。
+/// 正在使用和常驻缓存的缓冲区不会被回收，因此缓存中的块数可能仍高于target。
+/// 回收的缓冲区内存连同空闲池一起归还给宿主。
+pub unsafe fn ext4_block_cache_shrink(bdev: *mut Ext4BlockDevice, target: u32) -> i32 {
+    unsafe {
+        let r = ext4_block_cache_reclaim(bdev, target);
+        ext4_bcache_pool_drain((*bdev).bc);
+        r
+    }
+}
+
+/// 按LRU顺序回收缓冲区，直到缓存中的块数不超过target（内存留在空闲池中）
+unsafe fn ext4_block_cache_reclaim(bdev: *mut Ext4BlockDevice, target: u32) -> i32 {
+    unsafe {
+        let bc = (*bdev).bc;
+        if (*bc).dont_shake {
+            return EOK;
+        }
+        (*bc).dont_shake = true;
+        let mut r = EOK;
+        // 缓冲区经由指针释放，ref_blocks在循环中变化
+        loop {
+            if (*bc).ref_blocks <= target {
+                break;
+            }
+            let buf = if ext4_bcache_holds_metadata(bc) {
+                ext4_buf_lowest_clean_lru(bc)
+            } else {
+                ext4_buf_lowest_lru(bc)
+            };
+            if buf.is_null() {
+                break;
+            }
+            if ext4_bcache_test_flag(buf, BC_DIRTY) {
+                r = ext4_block_flush_buf(bdev, buf);
+                if r != EOK {
+                    break;
+                }
+            }
+            ext4_bcache_drop_buf(bc, buf);
+        }
+        (*bc).dont_shake = false;
+        r
+    }
+}
+
+/// 绑定块缓存（缓存的缓冲区按设备的对齐要求分配，缓存中已有缓冲区且对齐不同时返回 EBUSY）
+pub unsafe fn ext4_block_bind_bcache(bdev: *mut Ext4BlockDevice, bc: *mut Ext4BlockCache) -> i32 {
+    ext4_dbg!(DEBUG_BLOCKDEV, Debug, "ext4_block_bind_bcache");
+    unsafe {
+        let r = ext4_bcache_set_align(bc, (*(*bdev).bdif).ph_align);
+        if r != EOK {
+            return r;
+        }
+        (*bdev).bc = bc;
+        (*bc).bdev = bdev;
+    }
     EOK
 }
 
-/// 设置逻辑块大小（占位实现）
-pub fn ext4_block_set_lb_size(bdev: *mut Ext4BlockDevice, lb_size: u32) {
+/// 设置逻辑块大小
+pub unsafe fn ext4_block_set_lb_size(bdev: *mut Ext4BlockDevice, lb_size: u32) {
     unsafe {
         (*bdev).lg_bsize = lb_size;
+        (*bdev).lg_bcnt = (*bdev).part_size / lb_size as u64;
+    }
+    ext4_dbg!(DEBUG_BLOCKDEV, Debug, "ext4_block_set_lb_size: {}", lb_size);
+}
+
+/// 启用/禁用块缓存写回模式（引用计数归零时回写脏块，BC_DEFER 的缓冲区留到同步缓存时回写）
+pub unsafe fn ext4_block_cache_write_back(bdev: *mut Ext4BlockDevice, on_off: u8) -> i32 {
+    ext4_dbg!(DEBUG_BLOCKDEV, Trace, "ext4_block_cache_write_back: on_off={}", on_off);
+    unsafe {
+        if on_off != 0 {
+            (*bdev).cache_write_back += 1;
+        } else if (*bdev).cache_write_back != 0 {
+            (*bdev).cache_write_back -= 1;
+        }
+
+        if (*bdev).cache_write_back != 0 {
+            return EOK;
+        }
+        // 块组描述符留到同步缓存时回写
+        ext4_block_cache_flush_to(bdev, 0)
+    }
+}
+
+/// 启用/禁用有序数据模式
+///
+/// 启用后，ext4_block_data_begin/ext4_block_data_end 之间释放的元数据不会写回设备，
+/// 直到最外层的数据写入结束。文件数据本身直接写入设备，因此断电后元数据最多指向
+/// 旧数据，而不会指向尚未写入的块。
+pub unsafe fn ext4_block_set_ordered(bdev: *mut Ext4BlockDevice, on: bool) {
+    ext4_dbg!(DEBUG_BLOCKDEV, Debug, "ext4_block_set_ordered: {}", on);
+    unsafe { (*(*bdev).bc).ordered = on };
+}
+
+/// 开始写入文件数据（可嵌套）
+pub unsafe fn ext4_block_data_begin(bdev: *mut Ext4BlockDevice) {
+    unsafe { (*(*bdev).bc).data_writes += 1 };
+}
+
+/// 文件数据写入结束
+///
+/// 最外层结束且未处于写回模式时，回写写入期间推迟的元数据。
+pub unsafe fn ext4_block_data_end(bdev: *mut Ext4BlockDevice) -> i32 {
+    unsafe {
+        let bc = (*bdev).bc;
+        if (*bc).data_writes == 0 {
+            return EOK;
+        }
+        (*bc).data_writes -= 1;
+        if (*bc).data_writes != 0 || !(*bc).ordered || (*bdev).cache_write_back != 0 {
+            return EOK;
+        }
+        ext4_block_cache_flush(bdev)
+    }
+}
+
+/// 为块分配缓冲区（不读取设备，也不记录撤销日志）
+unsafe fn ext4_block_alloc_buf(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block, lba: u64) -> i32 {
+    unsafe {
+        if (*(*bdev).bdif).ph_refctr == 0 {
+            return EIO;
+        }
+        if lba >= (*bdev).lg_bcnt {
+            return ENXIO;
+        }
+
+        (*b).lb_id = lba;
+
+        // 缓存已满时先回收
+        let r = ext4_block_cache_shake(bdev);
+        if r != EOK {
+            return r;
+        }
+
+        let mut is_new = false;
+        let r = ext4_bcache_alloc((*bdev).bc, b, &mut is_new);
+        if r != EOK {
+            return r;
+        }
+        if (*b).data.is_null() {
+            return ENOMEM;
+        }
+    }
+    EOK
+}
+
+/// 获取块（不从设备读取，用于即将整块覆盖写入的场景）
+pub unsafe fn ext4_block_get_noread(
+    bdev: *mut Ext4BlockDevice,
+    b: *mut Ext4Block,
+    lba: u64,
+) -> i32 {
+    unsafe {
+        let r = ext4_block_alloc_buf(bdev, b, lba);
+        if r != EOK {
+            return r;
+        }
+        ext4_bcache_undo_record((*bdev).bc, (*b).buf);
     }
-    debug!("ext4_block_set_lb_size: {}", lb_size);
+    EOK
 }
 
-/// 启用/禁用块缓存写回模式（占位实现）
-pub fn ext4_block_cache_write_back(bdev: *mut Ext4BlockDevice, enable: i32) -> i32 {
-    debug!("ext4_block_cache_write_back: enable={}", enable);
+/// 获取块（缓存中没有最新数据时从设备读取）
+pub unsafe fn ext4_block_get(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block, lba: u64) -> i32 {
+    unsafe {
+        let r = ext4_block_alloc_buf(bdev, b, lba);
+        if r != EOK {
+            return r;
+        }
+
+        if ext4_bcache_test_flag((*b).buf, BC_UPTODATE) {
+            if let Some(m) = ext4_bdev_metrics(bdev) {
+                m.cache_hits += 1;
+            }
+            ext4_bcache_undo_record((*bdev).bc, (*b).buf);
+            return EOK;
+        }
+        if let Some(m) = ext4_bdev_metrics(bdev) {
+            m.cache_misses += 1;
+        }
+
+        let r = ext4_blocks_get_direct(bdev, (*b).data as _, lba, 1);
+        if r != EOK {
+            ext4_bcache_free((*bdev).bc, b);
+            (*b).lb_id = 0;
+            return r;
+        }
+
+        ext4_bcache_set_flag((*b).buf, BC_UPTODATE);
+        ext4_bcache_undo_record((*bdev).bc, (*b).buf);
+    }
     EOK
 }
 
-/// 初始化动态块缓存（占位实现）
-pub fn ext4_bcache_init_dynamic(bc: *mut Ext4BlockCache, cnt: u32, itemsize: u32) -> i32 {
-    debug!(
-        "ext4_bcache_init_dynamic: cnt={}, itemsize={}",
-        cnt, itemsize
-    );
+/// 尝试获取块，缓存已满时不写回脏块
+///
+/// 块不在缓存中时只回收干净的缓冲区腾出空间；需要写回脏块（或全部缓冲区都在使用中）
+/// 才能腾出空间时返回EBUSY，调用方可以稍后重试或先刷新缓存。
+pub unsafe fn ext4_block_try_get(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block, lba: u64) -> i32 {
+    unsafe {
+        let bc = (*bdev).bc;
+        if !ext4_bcache_contains(bc, lba) {
+            while ext4_bcache_is_full(bc) {
+                let buf = ext4_buf_lowest_clean_lru(bc);
+                if buf.is_null() {
+                    return EBUSY;
+                }
+                ext4_bcache_drop_buf(bc, buf);
+            }
+        }
+        ext4_block_get(bdev, b, lba)
+    }
+}
+
+/// 重新从设备读取块，丢弃缓存中未回写的修改
+///
+/// 修改的是共享的缓冲区，同一块的其他引用也会看到重新读取的内容。读取失败时缓冲区
+/// 保持非最新状态，下次获取时重新读取。事务进行中、元数据回写被推迟或撤销日志正在记录时
+/// 返回EBUSY：缓冲区中的修改还要被提交或回滚使用。
+pub unsafe fn ext4_block_reload(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block) -> i32 {
     unsafe {
-        if !bc.is_null() {
-            (*bc).cnt = cnt;
-            (*bc).itemsize = itemsize;
+        let buf = (*b).buf;
+        if buf.is_null() {
+            return EINVAL;
+        }
+        let bc = (*bdev).bc;
+        if ext4_bcache_holds_metadata(bc) || (*bc).undo_depth != 0 {
+            return EBUSY;
+        }
+        ext4_bcache_invalidate_buf(bc, buf);
+        let r = ext4_blocks_get_direct(bdev, (*b).data as _, (*b).lb_id, 1);
+        if r != EOK {
+            return r;
         }
+        ext4_bcache_set_flag(buf, BC_UPTODATE);
     }
     EOK
 }
 
-/// 销毁动态块缓存（占位实现）
-pub fn ext4_bcache_fini_dynamic(bc: *mut Ext4BlockCache) -> i32 {
-    debug!("ext4_bcache_fini_dynamic");
+/// 释放块引用
+pub unsafe fn ext4_block_set(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        if (*(*bdev).bdif).ph_refctr == 0 {
+            return EIO;
+        }
+        if (*b).buf.is_null() {
+            return EOK;
+        }
+        ext4_bcache_free((*bdev).bc, b)
+    }
+}
+
+/// 标记块为脏
+pub unsafe fn ext4_block_set_dirty(b: *mut Ext4Block) {
+    unsafe { ext4_bcache_set_dirty((*b).buf) };
+}
+
+/// 将块标记为常驻缓存（释放引用后也不会被LRU回收）
+pub unsafe fn ext4_block_pin(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        if (*b).buf.is_null() {
+            return EINVAL;
+        }
+        ext4_bcache_pin_buf((*bdev).bc, (*b).buf)
+    }
+}
+
+/// 取消块的常驻标记
+pub unsafe fn ext4_block_unpin(bdev: *mut Ext4BlockDevice, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        if (*b).buf.is_null() {
+            return EINVAL;
+        }
+        ext4_bcache_unpin_buf((*bdev).bc, (*b).buf);
+    }
     EOK
 }
 
-/// 清理块缓存（占位实现）
-pub fn ext4_bcache_cleanup(bc: *mut Ext4BlockCache) {
-    debug!("ext4_bcache_cleanup");
+/// 块是否常驻缓存
+pub unsafe fn ext4_block_is_pinned(b: *const Ext4Block) -> bool {
+    unsafe { !(*b).buf.is_null() && ext4_bcache_is_pinned((*b).buf) }
+}
+
+/// 块设备所属文件系统的运行统计（尚未关联文件系统时为 None）
+unsafe fn ext4_bdev_metrics<'a>(bdev: *mut Ext4BlockDevice) -> Option<&'a mut Ext4Metrics> {
+    unsafe { (*bdev).fs.as_mut().map(|fs| &mut fs.metrics) }
+}
+
+/// 缓冲区地址是否满足设备的对齐要求
+unsafe fn ext4_bdif_is_aligned(bdev: *mut Ext4BlockDevice, buf: *const u8) -> bool {
+    let align = unsafe { (*(*bdev).bdif).ph_align } as usize;
+    align <= 1 || (buf as usize).is_multiple_of(align)
+}
+
+/// 不满足对齐要求的读写所用的中转缓冲区，返回(缓冲区, 容纳的物理块数, 是否取自空闲池)
+///
+/// 块缓存的缓冲区是物理块的整数倍且对齐足够时从空闲池取用，否则使用一个物理块的 ph_bbuf。
+unsafe fn ext4_bdif_bounce_buf(bdev: *mut Ext4BlockDevice) -> (*mut u8, u32, bool) {
+    unsafe {
+        let bdif = (*bdev).bdif;
+        let bc = (*bdev).bc;
+        let ph_bsize = (*bdif).ph_bsize;
+        if !bc.is_null()
+            && !(*bc).lists.is_null()
+            && (*bc).itemsize >= ph_bsize
+            && (*bc).itemsize.is_multiple_of(ph_bsize)
+            && (*bc).align.max(8) >= (*bdif).ph_align
+        {
+            let data = ext4_bcache_pool_get(bc);
+            if !data.is_null() {
+                return (data, (*bc).itemsize / ph_bsize, true);
+            }
+        }
+        ((*bdif).ph_bbuf, 1, false)
+    }
+}
+
+/// 经由中转缓冲区分段读写不对齐的缓冲区
+unsafe fn ext4_bdif_bounce(
+    bdev: *mut Ext4BlockDevice,
+    buf: *mut u8,
+    blk_id: u64,
+    blk_cnt: u32,
+    write: bool,
+) -> i32 {
+    unsafe {
+        let ph_bsize = (*(*bdev).bdif).ph_bsize as usize;
+        let (bounce, chunk, pooled) = ext4_bdif_bounce_buf(bdev);
+        let mut r = EOK;
+        let mut done = 0u32;
+        while done < blk_cnt {
+            let cnt = chunk.min(blk_cnt - done);
+            let p = buf.add(done as usize * ph_bsize);
+            let len = cnt as usize * ph_bsize;
+            let id = blk_id + done as u64;
+            if write {
+                ptr::copy_nonoverlapping(p, bounce, len);
+                r = ext4_bdif_bwrite(bdev, bounce as _, id, cnt);
+            } else {
+                r = ext4_bdif_bread(bdev, bounce as _, id, cnt);
+                if r == EOK {
+                    ptr::copy_nonoverlapping(bounce, p, len);
+                }
+            }
+            if r != EOK {
+                break;
+            }
+            done += cnt;
+        }
+        if pooled {
+            ext4_bcache_pool_put((*bdev).bc, bounce);
+        }
+        r
+    }
 }
 
-/// 底层块读取（带锁）
-fn ext4_bdif_bread(
+/// 底层块读取（带锁，缓冲区不满足设备的对齐要求时经由中转缓冲区读取）
+unsafe fn ext4_bdif_bread(
     bdev: *mut Ext4BlockDevice,
     buf: *mut core::ffi::c_void,
     blk_id: u64,
     blk_cnt: u32,
 ) -> i32 {
     unsafe {
+        if !ext4_bdif_is_aligned(bdev, buf as _) && buf as *mut u8 != (*(*bdev).bdif).ph_bbuf {
+            return ext4_bdif_bounce(bdev, buf as _, blk_id, blk_cnt, false);
+        }
         ext4_bdif_lock(bdev);
 
         let bread_fn = (*(*bdev).bdif).bread;
@@ -162,6 +755,10 @@ fn ext4_bdif_bread(
         } else {
             ENOTSUP
         };
+        ext4_dbg!(DEBUG_BLOCKDEV, Trace, "bread: blk_id={}, blk_cnt={}, r={}", blk_id, blk_cnt, r);
+        if r != EOK {
+            ext4_dbg!(DEBUG_BLOCKDEV, Warn, "bread failed: blk_id={}, blk_cnt={}, r={}", blk_id, blk_cnt, r);
+        }
 
         (*(*bdev).bdif).bread_ctr += 1;
         ext4_bdif_unlock(bdev);
@@ -169,14 +766,17 @@ fn ext4_bdif_bread(
     }
 }
 
-/// 底层块写入（带锁）
-fn ext4_bdif_bwrite(
+/// 底层块写入（带锁，缓冲区不满足设备的对齐要求时经由中转缓冲区写入）
+unsafe fn ext4_bdif_bwrite(
     bdev: *mut Ext4BlockDevice,
     buf: *const core::ffi::c_void,
     blk_id: u64,
     blk_cnt: u32,
 ) -> i32 {
     unsafe {
+        if !ext4_bdif_is_aligned(bdev, buf as _) && buf as *mut u8 != (*(*bdev).bdif).ph_bbuf {
+            return ext4_bdif_bounce(bdev, buf as _, blk_id, blk_cnt, true);
+        }
         ext4_bdif_lock(bdev);
 
         let bwrite_fn = (*(*bdev).bdif).bwrite;
@@ -185,17 +785,38 @@ fn ext4_bdif_bwrite(
         } else {
             ENOTSUP
         };
+        ext4_dbg!(DEBUG_BLOCKDEV, Trace, "bwrite: blk_id={}, blk_cnt={}, r={}", blk_id, blk_cnt, r);
+        if r != EOK {
+            ext4_dbg!(DEBUG_BLOCKDEV, Warn, "bwrite failed: blk_id={}, blk_cnt={}, r={}", blk_id, blk_cnt, r);
+        }
 
         (*(*bdev).bdif).bwrite_ctr += 1;
+        // 已挂载时累计写入量，同步时计入 s_kbytes_written
+        let fs = (*bdev).fs;
+        if r == EOK && !fs.is_null() && !(*fs).no_kbytes_written {
+            (*fs).bytes_written += blk_cnt as u64 * (*(*bdev).bdif).ph_bsize as u64;
+        }
         ext4_bdif_unlock(bdev);
         r
     }
 }
 
+/// 逻辑块能否整块映射到物理块（逻辑块是物理块的整数倍，且分区起点按物理块对齐）
+fn ext4_blocks_ph_aligned(lg_bsize: u64, ph_bsize: u64, part_offset: u64) -> bool {
+    lg_bsize >= ph_bsize && lg_bsize.is_multiple_of(ph_bsize) && part_offset.is_multiple_of(ph_bsize)
+}
+
+/// 逻辑块 [lba, lba + cnt) 的字节偏移和长度（溢出或长度超出地址空间时返回 None）
+fn ext4_blocks_byte_span(lba: u64, cnt: u32, lg_bsize: u64) -> Option<(u64, usize)> {
+    let offset = ext4_blocks_to_bytes(lba, lg_bsize)?;
+    let len = ext4_blocks_to_bytes(cnt as u64, lg_bsize)?;
+    Some((offset, usize::try_from(len).ok()?))
+}
+
 /// 从块设备直接读取块数据
 ///
 /// 将逻辑块地址转换为物理块地址并读取数据
-pub fn ext4_blocks_get_direct(
+pub unsafe fn ext4_blocks_get_direct(
     bdev: *mut Ext4BlockDevice,
     buf: *mut core::ffi::c_void,
     lba: u64,
@@ -208,18 +829,31 @@ pub fn ext4_blocks_get_direct(
         let ph_bsize = (*(*bdev).bdif).ph_bsize as u64;
         let part_offset = (*bdev).part_offset;
 
+        // 逻辑块小于物理块（如 1KiB 块的文件系统位于 4Kn 设备上）或分区未按物理块对齐时，
+        // 逻辑块不能整块映射到物理块，按字节读取
+        let Some((offset, len)) = ext4_blocks_byte_span(lba, cnt, lg_bsize) else {
+            return ENXIO;
+        };
+        if !ext4_blocks_ph_aligned(lg_bsize, ph_bsize, part_offset) {
+            return ext4_block_readbytes(bdev, offset, buf as *mut u8, len);
+        }
+
         // 计算物理块地址
-        let pba = (lba * lg_bsize + part_offset) / ph_bsize;
-        let pb_cnt = (lg_bsize / ph_bsize) as u32;
+        let Some(pos) = ext4_block_dev_pos(bdev, offset, len as u64) else {
+            return ENXIO;
+        };
+        let Some(pb_cnt) = ((lg_bsize / ph_bsize) as u32).checked_mul(cnt) else {
+            return EINVAL;
+        };
 
-        ext4_bdif_bread(bdev, buf, pba, pb_cnt * cnt)
+        ext4_bdif_bread(bdev, buf, pos / ph_bsize, pb_cnt)
     }
 }
 
 /// 向块设备直接写入块数据
 ///
 /// 将逻辑块地址转换为物理块地址并写入数据
-pub fn ext4_blocks_set_direct(
+pub unsafe fn ext4_blocks_set_direct(
     bdev: *mut Ext4BlockDevice,
     buf: *const core::ffi::c_void,
     lba: u64,
@@ -232,10 +866,22 @@ pub fn ext4_blocks_set_direct(
         let ph_bsize = (*(*bdev).bdif).ph_bsize as u64;
         let part_offset = (*bdev).part_offset;
 
+        // 逻辑块不能整块映射到物理块时按字节写入（先读后写所在的物理块）
+        let Some((offset, len)) = ext4_blocks_byte_span(lba, cnt, lg_bsize) else {
+            return ENXIO;
+        };
+        if !ext4_blocks_ph_aligned(lg_bsize, ph_bsize, part_offset) {
+            return ext4_block_writebytes(bdev, offset, buf as *const u8, len);
+        }
+
         // 计算物理块地址
-        let pba = (lba * lg_bsize + part_offset) / ph_bsize;
-        let pb_cnt = (lg_bsize / ph_bsize) as u32;
+        let Some(pos) = ext4_block_dev_pos(bdev, offset, len as u64) else {
+            return ENXIO;
+        };
+        let Some(pb_cnt) = ((lg_bsize / ph_bsize) as u32).checked_mul(cnt) else {
+            return EINVAL;
+        };
 
-        ext4_bdif_bwrite(bdev, buf, pba, pb_cnt * cnt)
+        ext4_bdif_bwrite(bdev, buf, pos / ph_bsize, pb_cnt)
     }
 }
@@ -0,0 +1,428 @@
+//! 块组描述符操作模块
+//!
+//! 对应C实现: ext4_block_group.h 以及 ext4_fs.c 中的块组引用部分
+
+
+use crate::balloc::ext4_balloc_set_bitmap_csum;
+use crate::bcache::ext4_bcache_set_flag;
+use crate::bitmap::{ext4_bmap_bit_set, ext4_fs_mark_bitmap_end};
+use crate::block::{
+    ext4_block_flush_buf, ext4_block_get, ext4_block_get_noread, ext4_block_pin, ext4_block_set, ext4_block_set_dirty,
+};
+use crate::crc::{ext4_crc16, ext4_crc32c, ext4_sb_csum_seed};
+use crate::ialloc::ext4_ialloc_set_bitmap_csum;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockGroup, Ext4BlockGroupRef, Ext4Filesystem, Ext4Superblock};
+
+/// 描述符是否包含64位扩展字段
+fn ext4_bg_is_64bit(sb: &Ext4Superblock) -> bool {
+    ext4_sb_get_desc_size(sb) >= EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE
+}
+
+fn lo_hi32(sb: &Ext4Superblock, lo: u32, hi: u32) -> u64 {
+    let mut v = u32::from_le(lo) as u64;
+    if ext4_bg_is_64bit(sb) {
+        v |= (u32::from_le(hi) as u64) << 32;
+    }
+    v
+}
+
+fn lo_hi16(sb: &Ext4Superblock, lo: u16, hi: u16) -> u32 {
+    let mut v = u16::from_le(lo) as u32;
+    if ext4_bg_is_64bit(sb) {
+        v |= (u16::from_le(hi) as u32) << 16;
+    }
+    v
+}
+
+/// 块位图所在块号
+pub fn ext4_bg_get_block_bitmap(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u64 {
+    lo_hi32(sb, bg.block_bitmap_lo, bg.block_bitmap_hi)
+}
+
+/// inode位图所在块号
+pub fn ext4_bg_get_inode_bitmap(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u64 {
+    lo_hi32(sb, bg.inode_bitmap_lo, bg.inode_bitmap_hi)
+}
+
+/// inode表起始块号
+pub fn ext4_bg_get_inode_table_first_block(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u64 {
+    lo_hi32(sb, bg.inode_table_first_block_lo, bg.inode_table_first_block_hi)
+}
+
+/// 空闲块数
+pub fn ext4_bg_get_free_blocks_count(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u32 {
+    lo_hi16(sb, bg.free_blocks_count_lo, bg.free_blocks_count_hi)
+}
+
+/// 设置空闲块数
+pub fn ext4_bg_set_free_blocks_count(bg: &mut Ext4BlockGroup, sb: &Ext4Superblock, cnt: u32) {
+    bg.free_blocks_count_lo = (cnt as u16).to_le();
+    if ext4_bg_is_64bit(sb) {
+        bg.free_blocks_count_hi = ((cnt >> 16) as u16).to_le();
+    }
+}
+
+/// 空闲inode数
+pub fn ext4_bg_get_free_inodes_count(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u32 {
+    lo_hi16(sb, bg.free_inodes_count_lo, bg.free_inodes_count_hi)
+}
+
+/// 设置空闲inode数
+pub fn ext4_bg_set_free_inodes_count(bg: &mut Ext4BlockGroup, sb: &Ext4Superblock, cnt: u32) {
+    bg.free_inodes_count_lo = (cnt as u16).to_le();
+    if ext4_bg_is_64bit(sb) {
+        bg.free_inodes_count_hi = ((cnt >> 16) as u16).to_le();
+    }
+}
+
+/// 目录数
+pub fn ext4_bg_get_used_dirs_count(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u32 {
+    lo_hi16(sb, bg.used_dirs_count_lo, bg.used_dirs_count_hi)
+}
+
+/// 设置目录数
+pub fn ext4_bg_set_used_dirs_count(bg: &mut Ext4BlockGroup, sb: &Ext4Superblock, cnt: u32) {
+    bg.used_dirs_count_lo = (cnt as u16).to_le();
+    if ext4_bg_is_64bit(sb) {
+        bg.used_dirs_count_hi = ((cnt >> 16) as u16).to_le();
+    }
+}
+
+/// 未使用的inode数（inode表尾部从未使用过的部分）
+pub fn ext4_bg_get_itable_unused(bg: &Ext4BlockGroup, sb: &Ext4Superblock) -> u32 {
+    lo_hi16(sb, bg.itable_unused_lo, bg.itable_unused_hi)
+}
+
+/// 设置未使用的inode数
+pub fn ext4_bg_set_itable_unused(bg: &mut Ext4BlockGroup, sb: &Ext4Superblock, cnt: u32) {
+    bg.itable_unused_lo = (cnt as u16).to_le();
+    if ext4_bg_is_64bit(sb) {
+        bg.itable_unused_hi = ((cnt >> 16) as u16).to_le();
+    }
+}
+
+/// 检查块组标志
+pub fn ext4_bg_has_flag(bg: &Ext4BlockGroup, flag: u16) -> bool {
+    u16::from_le(bg.flags) & flag != 0
+}
+
+/// 设置块组标志
+pub fn ext4_bg_set_flag(bg: &mut Ext4BlockGroup, flag: u16) {
+    bg.flags = (u16::from_le(bg.flags) | flag).to_le();
+}
+
+/// 清除块组标志
+pub fn ext4_bg_clear_flag(bg: &mut Ext4BlockGroup, flag: u16) {
+    bg.flags = (u16::from_le(bg.flags) & !flag).to_le();
+}
+
+// ===== 块地址与块组的换算 =====
+
+/// 块所在的块组
+pub fn ext4_balloc_get_bgid_of_block(sb: &Ext4Superblock, baddr: u64) -> u32 {
+    let mut baddr = baddr;
+    if u32::from_le(sb.first_data_block) != 0 && baddr != 0 {
+        baddr -= 1;
+    }
+    (baddr / u32::from_le(sb.blocks_per_group) as u64) as u32
+}
+
+/// 块组的第一个块
+pub fn ext4_balloc_get_block_of_bgid(sb: &Ext4Superblock, bgid: u32) -> u64 {
+    let mut baddr = 0u64;
+    if u32::from_le(sb.first_data_block) != 0 {
+        baddr += 1;
+    }
+    baddr + bgid as u64 * u32::from_le(sb.blocks_per_group) as u64
+}
+
+/// 块在所属块组内的索引
+pub fn ext4_fs_addr_to_idx_bg(sb: &Ext4Superblock, baddr: u64) -> u32 {
+    let mut baddr = baddr;
+    if u32::from_le(sb.first_data_block) != 0 {
+        baddr -= 1;
+    }
+    (baddr % u32::from_le(sb.blocks_per_group) as u64) as u32
+}
+
+/// 块组内索引对应的块地址
+pub fn ext4_fs_bg_idx_to_addr(sb: &Ext4Superblock, index: u32, bgid: u32) -> u64 {
+    let mut index = index as u64;
+    if u32::from_le(sb.first_data_block) != 0 {
+        index += 1;
+    }
+    u32::from_le(sb.blocks_per_group) as u64 * bgid as u64 + index
+}
+
+/// 块是否位于指定块组中
+pub fn ext4_block_in_group(sb: &Ext4Superblock, baddr: u64, bgid: u32) -> bool {
+    ext4_balloc_get_bgid_of_block(sb, baddr) == bgid
+}
+
+// ===== 块组描述符校验和 =====
+
+/// 计算块组描述符校验和
+///
+/// metadata_csum 使用 crc32c 的低16位，gdt_csum 使用 crc16（跳过校验和字段本身），
+/// 两者都未启用时为0。
+pub fn ext4_fs_bg_checksum(sb: &Ext4Superblock, bgid: u32, bg: &Ext4BlockGroup) -> u16 {
+    let metadata_csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM);
+    if !metadata_csum && !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_GDT_CSUM) {
+        return 0;
+    }
+
+    let desc_size = ext4_sb_get_desc_size(sb) as usize;
+    let mut desc = [0u8; EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE as usize];
+    // SAFETY: 描述符至少有 desc_size 字节（位于GDT块内）
+    unsafe {
+        core::ptr::copy_nonoverlapping(bg as *const _ as *const u8, desc.as_mut_ptr(), desc_size);
+    }
+    let off = core::mem::offset_of!(Ext4BlockGroup, checksum);
+
+    if !metadata_csum {
+        let mut crc = ext4_crc16(!0, &sb.uuid);
+        crc = ext4_crc16(crc, &bgid.to_le_bytes());
+        crc = ext4_crc16(crc, &desc[..off]);
+        // 64位描述符中校验和字段之后的部分（只有 64bit 特性启用时才计入）
+        if ext4_sb_feature_incom(sb, EXT4_FINCOM_64BIT) && desc_size > off + 2 {
+            crc = ext4_crc16(crc, &desc[off + 2..desc_size]);
+        }
+        return crc;
+    }
+
+    // 计算时校验和字段视为0
+    desc[off..off + 2].fill(0);
+
+    let mut checksum = ext4_sb_csum_seed(sb);
+    checksum = ext4_crc32c(checksum, &bgid.to_le_bytes());
+    checksum = ext4_crc32c(checksum, &desc[..desc_size]);
+    (checksum & 0xFFFF) as u16
+}
+
+/// 校验块组描述符校验和
+pub fn ext4_fs_verify_bg_csum(sb: &Ext4Superblock, bgid: u32, bg: &Ext4BlockGroup) -> bool {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM | EXT4_FRO_COM_GDT_CSUM) {
+        return true;
+    }
+    u16::from_le(bg.checksum) == ext4_fs_bg_checksum(sb, bgid, bg)
+}
+
+// ===== 块组引用 =====
+
+/// 块组描述符所在的GDT块
+fn ext4_fs_get_descriptor_block(sb: &Ext4Superblock, bgid: u32, dsc_per_block: u32) -> u64 {
+    let dsc_id = bgid / dsc_per_block;
+    let first_meta_bg = ext4_sb_first_meta_bg(sb);
+
+    if !ext4_sb_feature_incom(sb, EXT4_FINCOM_META_BG) || dsc_id < first_meta_bg {
+        return u32::from_le(sb.first_data_block) as u64 + dsc_id as u64 + 1;
+    }
+
+    // meta_bg：描述符块位于该meta块组第一个块组的开头
+    let first_bg = dsc_id * dsc_per_block;
+    let has_super = ext4_sb_is_super_in_bg(sb, first_bg) as u64;
+    has_super + ext4_balloc_get_block_of_bgid(sb, first_bg)
+}
+
+/// 获取块组引用
+pub unsafe fn ext4_fs_get_block_group_ref(
+    fs: *mut Ext4Filesystem,
+    bgid: u32,
+    bg_ref: *mut Ext4BlockGroupRef,
+) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        if bgid >= ext4_block_group_cnt(sb) {
+            return EINVAL;
+        }
+
+        let dsc_size = ext4_sb_get_desc_size(sb) as u32;
+        let dsc_per_block = ext4_sb_dsc_per_block(sb);
+        let block_id = ext4_fs_get_descriptor_block(sb, bgid, dsc_per_block);
+        let offset = (bgid % dsc_per_block) * dsc_size;
+
+        let r = ext4_block_get((*fs).bdev, &mut (*bg_ref).block, block_id);
+        if r != EOK {
+            return r;
+        }
+        // 块组描述符访问频繁，尽量常驻缓存（达到常驻上限时忽略）
+        ext4_block_pin((*fs).bdev, &mut (*bg_ref).block);
+
+        (*bg_ref).block_group = (*bg_ref).block.data.add(offset as usize) as *mut Ext4BlockGroup;
+        (*bg_ref).fs = fs;
+        (*bg_ref).index = bgid;
+        (*bg_ref).dirty = false;
+
+        if !ext4_fs_verify_bg_csum(sb, bgid, &*(*bg_ref).block_group) {
+            ext4_dbg!(DEBUG_BLOCK_GROUP, Warn, "block group descriptor checksum failed: bg {}", bgid);
+        }
+    }
+    EOK
+}
+
+/// 释放块组引用（修改过时更新校验和并标记为脏，推迟到同步缓存时回写）
+///
+/// 描述符写回失败过（bg_poisoned）时不再接受修改：返回EIO，修改不会被标记为脏，
+/// 由调用方的撤销日志还原。
+pub unsafe fn ext4_fs_put_block_group_ref(bg_ref: *mut Ext4BlockGroupRef) -> i32 {
+    unsafe { ext4_fs_release_block_group_ref(bg_ref, false) }
+}
+
+/// 提交块组引用：更新校验和并立即将描述符块写回设备，然后释放引用
+///
+/// 与ext4_fs_put_block_group_ref相同，只是不等待缓存回写。
+/// 事务进行中时描述符块随事务提交写出，这里不写回。
+pub unsafe fn ext4_fs_commit_block_group_ref(bg_ref: *mut Ext4BlockGroupRef) -> i32 {
+    unsafe { ext4_fs_release_block_group_ref(bg_ref, true) }
+}
+
+/// 释放块组引用，write_through时先写回描述符块
+///
+/// 描述符块写回失败（包括直写模式下释放引用时的写回）时将文件系统标记为bg_poisoned，
+/// 避免内存与磁盘上的描述符继续分叉。
+unsafe fn ext4_fs_release_block_group_ref(bg_ref: *mut Ext4BlockGroupRef, write_through: bool) -> i32 {
+    unsafe {
+        if (*bg_ref).block.buf.is_null() {
+            return EOK;
+        }
+        let fs = (*bg_ref).fs;
+        let bdev = (*fs).bdev;
+        let dirty = (*bg_ref).dirty;
+        (*bg_ref).dirty = false;
+        if dirty && (*fs).bg_poisoned {
+            ext4_dbg!(DEBUG_BLOCK_GROUP, Warn, "block group {} modified after a failed descriptor write", (*bg_ref).index);
+            ext4_block_set(bdev, &mut (*bg_ref).block);
+            return EIO;
+        }
+        if dirty {
+            let bg = &mut *(*bg_ref).block_group;
+            bg.checksum = ext4_fs_bg_checksum(&(*fs).sb, (*bg_ref).index, bg).to_le();
+            ext4_block_set_dirty(&mut (*bg_ref).block);
+            // 同一描述符块在批量创建等操作中反复修改，留到同步缓存时一次写回
+            if !write_through {
+                ext4_bcache_set_flag((*bg_ref).block.buf, BC_DEFER);
+            }
+        }
+        let r = if write_through && !(*(*bdev).bc).in_trans {
+            ext4_block_flush_buf(bdev, (*bg_ref).block.buf)
+        } else {
+            EOK
+        };
+        let r2 = ext4_block_set(bdev, &mut (*bg_ref).block);
+        let r = if r != EOK { r } else { r2 };
+        if r != EOK && (dirty || write_through) {
+            ext4_dbg!(DEBUG_BLOCK_GROUP, Error, "block group descriptor write failed: bg {}, r={}", (*bg_ref).index, r);
+            (*fs).bg_poisoned = true;
+        }
+        r
+    }
+}
+
+/// 将所有修改过的块组描述符块写回设备
+///
+/// 任一描述符块写回失败时返回错误并将文件系统标记为bg_poisoned（见ext4_fs_commit_block_group_ref）。
+pub unsafe fn ext4_fs_commit_block_groups(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        let dsc_per_block = ext4_sb_dsc_per_block(&(*fs).sb);
+        let bg_count = ext4_block_group_cnt(&(*fs).sb);
+        let mut bgid = 0;
+        while bgid < bg_count {
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_fs_commit_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            bgid += dsc_per_block;
+        }
+    }
+    EOK
+}
+
+/// 初始化 BLOCK_UNINIT 块组的块位图
+///
+/// 标记块组开头的 superblock/GDT 备份以及位于本组内的位图和inode表。
+pub unsafe fn ext4_fs_init_block_bitmap(bg_ref: *mut Ext4BlockGroupRef) -> i32 {
+    unsafe {
+        let fs = (*bg_ref).fs;
+        let sb = &(*fs).sb;
+        let bg = &mut *(*bg_ref).block_group;
+        let bgid = (*bg_ref).index;
+        let block_size = ext4_sb_get_block_size(sb);
+        let inode_size = get_inode_size(sb) as u32;
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+        let flex_bg = ext4_sb_feature_incom(sb, EXT4_FINCOM_FLEX_BG);
+
+        let bmp_blk = ext4_bg_get_block_bitmap(bg, sb);
+        let bmp_inode = ext4_bg_get_inode_bitmap(bg, sb);
+        let inode_table = ext4_bg_get_inode_table_first_block(bg, sb);
+        let first_bg = ext4_balloc_get_block_of_bgid(sb, bgid);
+        let inode_table_bcnt = (inodes_per_group * inode_size).div_ceil(block_size) as u64;
+        let group_blocks = ext4_blocks_in_group_cnt(sb, bgid);
+
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get_noread((*fs).bdev, &mut b, bmp_blk);
+        if r != EOK {
+            return r;
+        }
+        let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+        bitmap.fill(0);
+
+        for bit in 0..ext4_num_base_meta_clusters(sb, bgid) {
+            ext4_bmap_bit_set(bitmap, bit);
+        }
+
+        let mut mark = |blk: u64| {
+            if (!flex_bg || ext4_block_in_group(sb, blk, bgid)) && blk >= first_bg {
+                let bit = (blk - first_bg) as u32;
+                if bit < group_blocks {
+                    ext4_bmap_bit_set(bitmap, bit);
+                }
+            }
+        };
+        mark(bmp_blk);
+        mark(bmp_inode);
+        for blk in inode_table..inode_table + inode_table_bcnt {
+            mark(blk);
+        }
+
+        // 块组实际块数不足位图容量时，剩余位全部置位
+        ext4_fs_mark_bitmap_end(group_blocks, block_size * 8, bitmap);
+
+        ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+        ext4_block_set_dirty(&mut b);
+        (*bg_ref).dirty = true;
+        ext4_block_set((*fs).bdev, &mut b)
+    }
+}
+
+/// 初始化 INODE_UNINIT 块组的inode位图
+pub unsafe fn ext4_fs_init_inode_bitmap(bg_ref: *mut Ext4BlockGroupRef) -> i32 {
+    unsafe {
+        let fs = (*bg_ref).fs;
+        let sb = &(*fs).sb;
+        let bg = &mut *(*bg_ref).block_group;
+        let block_size = ext4_sb_get_block_size(sb);
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get_noread((*fs).bdev, &mut b, ext4_bg_get_inode_bitmap(bg, sb));
+        if r != EOK {
+            return r;
+        }
+        let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+        bitmap.fill(0);
+        ext4_fs_mark_bitmap_end(inodes_per_group, block_size * 8, bitmap);
+
+        ext4_ialloc_set_bitmap_csum(sb, bg, bitmap);
+        ext4_block_set_dirty(&mut b);
+        (*bg_ref).dirty = true;
+        ext4_block_set((*fs).bdev, &mut b)
+    }
+}
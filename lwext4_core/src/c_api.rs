@@ -0,0 +1,1798 @@
+//! lwext4 兼容的 C 接口（c-api 特性）
+//!
+//! 对应C实现: ext4.c / ext4.h、ext4_mbr.c
+//!
+//! 提供与 lwext4 相同符号和签名的挂载、文件、目录和元数据接口，
+//! 已有的 lwext4 C 代码无需修改即可链接到本实现。
+//! C 声明见 include/lwext4_core.h，由 cbindgen 根据本模块生成（`make header`）。
+//! 与未配置 os_locks 的 lwext4 一样，这些接口不做加锁，调用者需自行保证串行访问。
+
+#![allow(non_camel_case_types)]
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::{c_char, c_void, CStr};
+use core::mem;
+use core::ptr;
+
+
+use crate::balloc::ext4_balloc_inherit_goal;
+use crate::bcache::*;
+use crate::block::*;
+use crate::dir::*;
+use crate::fs::*;
+use crate::inode::*;
+use crate::journal::*;
+use crate::superblock::*;
+use crate::undo::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{
+    ext4_blockdev, Ext4BlockCache, Ext4BlockDevice, Ext4DirIterator, Ext4DirSearchResult, Ext4Filesystem, Ext4Inode,
+    Ext4InodeRef, Ext4MountConfig, Ext4Superblock,
+};
+
+/// 可注册的块设备数量
+pub const CONFIG_EXT4_BLOCKDEVS_COUNT: usize = 2;
+/// 可同时挂载的挂载点数量
+pub const CONFIG_EXT4_MOUNTPOINTS_COUNT: usize = 2;
+/// 块设备名称最大长度
+pub const CONFIG_EXT4_MAX_BLOCKDEV_NAME: usize = 32;
+/// 挂载点名称最大长度
+pub const CONFIG_EXT4_MAX_MP_NAME: usize = 32;
+
+/// 文件打开标志（对应 ext4_oflags.h）
+pub const O_RDONLY: u32 = 0o0;
+pub const O_WRONLY: u32 = 0o1;
+pub const O_RDWR: u32 = 0o2;
+pub const O_CREAT: u32 = 0o100;
+pub const O_EXCL: u32 = 0o200;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_APPEND: u32 = 0o2000;
+pub const O_ACCMODE: u32 = 0o3;
+
+/// ext4_fseek 的 origin 参数
+pub const SEEK_SET: u32 = 0;
+pub const SEEK_CUR: u32 = 1;
+pub const SEEK_END: u32 = 2;
+
+/// 目录读取结束标记
+pub const EXT4_DIR_ENTRY_OFFSET_TERM: u64 = u64::MAX;
+
+/// 挂载点
+///
+/// 对应C定义: struct ext4_mountpoint (ext4.c)，对 C 调用者不透明
+pub struct ext4_mountpoint {
+    mounted: bool,                   // 是否已挂载
+    name: String,                    // 挂载点名称（以'/'结尾）
+    fs: Ext4Filesystem,              // 文件系统
+    bc: Ext4BlockCache,              // 块设备未绑定缓存时使用的动态缓存
+    cache_dynamic: bool,             // 是否使用了 bc
+}
+
+/// 打开的文件
+///
+/// 对应C定义: ext4_file (ext4.h)
+#[repr(C)]
+pub struct ext4_file {
+    pub mp: *mut ext4_mountpoint,    // 所在挂载点
+    pub inode: u32,                  // inode 编号
+    pub flags: u32,                  // 打开标志（O_*）
+    pub fsize: u64,                  // 文件大小
+    pub fpos: u64,                   // 当前读写位置
+}
+
+/// 目录项（ext4_dir_entry_next 的返回值）
+///
+/// 对应C定义: ext4_direntry (ext4.h)
+#[repr(C)]
+pub struct ext4_direntry {
+    pub inode: u32,                  // inode 编号
+    pub entry_length: u16,           // 记录长度
+    pub name_length: u8,             // 名称长度
+    pub inode_type: u8,              // 类型（EXT4_DE_*）
+    pub name: [u8; 255],             // 名称（不以0结尾）
+}
+
+/// 打开的目录
+///
+/// 对应C定义: ext4_dir (ext4.h)
+#[repr(C)]
+pub struct ext4_dir {
+    pub f: ext4_file,                // 目录文件
+    pub de: ext4_direntry,           // 当前目录项
+    pub next_off: u64,               // 下一个目录项的偏移
+}
+
+/// Rust风格别名：打开的文件
+pub type Ext4File = ext4_file;
+
+/// Rust风格别名：打开的目录
+pub type Ext4Dir = ext4_dir;
+
+/// Rust风格别名：C 接口目录项
+pub type Ext4DirEntryInfo = ext4_direntry;
+
+// ===== 设备与挂载点注册表 =====
+
+struct Ext4Registry {
+    bdevs: [Option<(String, *mut Ext4BlockDevice)>; CONFIG_EXT4_BLOCKDEVS_COUNT],
+    mps: [Option<Box<ext4_mountpoint>>; CONFIG_EXT4_MOUNTPOINTS_COUNT],
+}
+
+struct Ext4GlobalRegistry(UnsafeCell<Ext4Registry>);
+
+// 与 lwext4 相同，注册表的并发访问由调用者保证
+unsafe impl Sync for Ext4GlobalRegistry {}
+
+static EXT4_REGISTRY: Ext4GlobalRegistry = Ext4GlobalRegistry(UnsafeCell::new(Ext4Registry {
+    bdevs: [const { None }; CONFIG_EXT4_BLOCKDEVS_COUNT],
+    mps: [const { None }; CONFIG_EXT4_MOUNTPOINTS_COUNT],
+}));
+
+unsafe fn ext4_registry() -> &'static mut Ext4Registry {
+    unsafe { &mut *EXT4_REGISTRY.0.get() }
+}
+
+/// C 字符串转换为字节切片（空指针返回 None）
+unsafe fn ext4_cstr<'a>(s: *const c_char) -> Option<&'a [u8]> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { Some(CStr::from_ptr(s).to_bytes()) }
+}
+
+/// 查找路径所在的挂载点（匹配最长的挂载点名称）
+unsafe fn ext4_get_mount(path: &[u8]) -> *mut ext4_mountpoint {
+    let mut found: *mut ext4_mountpoint = ptr::null_mut();
+    let mut found_len = 0;
+    unsafe {
+        for mp in ext4_registry().mps.iter_mut().flatten() {
+            if mp.mounted && path.starts_with(mp.name.as_bytes()) && mp.name.len() > found_len {
+                found_len = mp.name.len();
+                found = &mut **mp;
+            }
+        }
+    }
+    found
+}
+
+/// 注册块设备
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_device_register(bd: *mut ext4_blockdev, dev_name: *const c_char) -> i32 {
+    unsafe {
+        let Some(name) = ext4_cstr(dev_name) else {
+            return EINVAL;
+        };
+        if bd.is_null() || name.is_empty() {
+            return EINVAL;
+        }
+        if name.len() > CONFIG_EXT4_MAX_BLOCKDEV_NAME {
+            return EINVAL;
+        }
+
+        let reg = ext4_registry();
+        if reg.bdevs.iter().flatten().any(|(n, _)| n.as_bytes() == name) {
+            return EEXIST;
+        }
+        let Some(slot) = reg.bdevs.iter_mut().find(|b| b.is_none()) else {
+            return ENOSPC;
+        };
+        *slot = Some((String::from_utf8_lossy(name).into_owned(), bd));
+    }
+    EOK
+}
+
+/// 注销块设备
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_device_unregister(dev_name: *const c_char) -> i32 {
+    unsafe {
+        let Some(name) = ext4_cstr(dev_name) else {
+            return EINVAL;
+        };
+        for slot in ext4_registry().bdevs.iter_mut() {
+            if matches!(slot, Some((n, _)) if n.as_bytes() == name) {
+                *slot = None;
+                return EOK;
+            }
+        }
+    }
+    ENOENT
+}
+
+/// 注销所有块设备
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_device_unregister_all() -> i32 {
+    unsafe {
+        for slot in ext4_registry().bdevs.iter_mut() {
+            *slot = None;
+        }
+    }
+    EOK
+}
+
+// ===== MBR 分区扫描（对应 ext4_mbr.c） =====
+
+/// MBR 中的主分区数量
+const EXT4_MBR_PARTITIONS: usize = 4;
+/// MBR 签名（位于第一个扇区末尾）
+const EXT4_MBR_SIGNATURE: u16 = 0xAA55;
+/// 分区表在 MBR 中的偏移
+const EXT4_MBR_PART_TABLE_OFFSET: usize = 446;
+/// 分区表项大小
+const EXT4_MBR_PART_ENTRY_SIZE: usize = 16;
+
+/// MBR 扫描得到的分区块设备
+///
+/// 对应C定义: struct ext4_mbr_bdevs (ext4_mbr.h)，末尾追加了各分区的类型
+#[repr(C)]
+pub struct ext4_mbr_bdevs {
+    pub partitions: [ext4_blockdev; EXT4_MBR_PARTITIONS],
+    pub types: [u8; EXT4_MBR_PARTITIONS], // 分区表项中的分区类型（如 0x83 为 Linux 原生分区，空表项为 0）
+}
+
+/// 扫描父设备上的 MBR 分区表
+///
+/// 每个非空表项得到一个与父设备共用接口的分区块设备，分区类型记录在 types 中，
+/// 由调用者按类型选择要注册（ext4_device_register）和挂载的分区；空表项保持全零（bdif 为空）。
+/// 没有 MBR 签名时返回 ENOENT，超出父设备范围的分区被忽略。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mbr_scan(parent: *mut ext4_blockdev, bdevs: *mut ext4_mbr_bdevs) -> i32 {
+    ext4_dbg!(DEBUG_MBR, Info, "ext4_mbr_scan");
+    if parent.is_null() || bdevs.is_null() {
+        return EINVAL;
+    }
+    unsafe {
+        ptr::write_bytes(bdevs, 0, 1);
+        let r = ext4_block_init(parent);
+        if r != EOK {
+            return r;
+        }
+
+        let mut mbr = [0u8; 512];
+        let mut r = ext4_block_readbytes(parent, 0, mbr.as_mut_ptr(), mbr.len());
+        if r == EOK && u16::from_le_bytes([mbr[510], mbr[511]]) != EXT4_MBR_SIGNATURE {
+            ext4_dbg!(DEBUG_MBR, Warn, "ext4_mbr_scan: unknown signature");
+            r = ENOENT;
+        }
+        if r == EOK {
+            let bdif = (*parent).bdif;
+            let ph_bsize = (*bdif).ph_bsize as u64;
+            let bdevs = &mut *bdevs;
+            for (i, part) in bdevs.partitions.iter_mut().enumerate() {
+                let e = &mbr[EXT4_MBR_PART_TABLE_OFFSET + i * EXT4_MBR_PART_ENTRY_SIZE..];
+                let part_type = e[4];
+                let first_lba = u32::from_le_bytes([e[8], e[9], e[10], e[11]]) as u64;
+                let sectors = u32::from_le_bytes([e[12], e[13], e[14], e[15]]) as u64;
+                ext4_dbg!(
+                    DEBUG_MBR,
+                    Info,
+                    "mbr_part: {}: type 0x{:x}, first_lba {}, sectors {}",
+                    i,
+                    part_type,
+                    first_lba,
+                    sectors
+                );
+                if part_type == 0 || sectors == 0 {
+                    continue; // 空表项
+                }
+                if (first_lba + sectors) * ph_bsize > (*parent).part_size {
+                    ext4_dbg!(DEBUG_MBR, Warn, "mbr_part: {}: beyond the end of the device", i);
+                    continue;
+                }
+                part.bdif = bdif;
+                part.part_offset = (*parent).part_offset + first_lba * ph_bsize;
+                part.part_size = sectors * ph_bsize;
+                part.ph_bsize = ph_bsize as u32;
+                part.ph_bcnt = sectors;
+                bdevs.types[i] = part_type;
+            }
+        }
+        ext4_block_fini(parent);
+        r
+    }
+}
+
+/// 挂载文件系统
+///
+/// mount_point 必须以'/'结尾，例如 "/mp/"。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mount(dev_name: *const c_char, mount_point: *const c_char, read_only: bool) -> i32 {
+    unsafe { ext4_mount_with_config(dev_name, mount_point, read_only, ptr::null()) }
+}
+
+/// 按指定的挂载参数挂载文件系统（config 为空指针时使用默认参数）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mount_with_config(
+    dev_name: *const c_char,
+    mount_point: *const c_char,
+    read_only: bool,
+    config: *const Ext4MountConfig,
+) -> i32 {
+    unsafe {
+        let config = config.as_ref().copied().unwrap_or_default();
+        let (Some(dev_name), Some(mp_name)) = (ext4_cstr(dev_name), ext4_cstr(mount_point)) else {
+            return EINVAL;
+        };
+        if mp_name.is_empty() || mp_name.len() > CONFIG_EXT4_MAX_MP_NAME {
+            return EINVAL;
+        }
+        if mp_name[mp_name.len() - 1] != b'/' {
+            return ENOTSUP;
+        }
+
+        let reg = ext4_registry();
+        let Some(bd) = reg
+            .bdevs
+            .iter()
+            .flatten()
+            .find(|(n, _)| n.as_bytes() == dev_name)
+            .map(|(_, bd)| *bd)
+        else {
+            return ENODEV;
+        };
+
+        if reg.mps.iter().flatten().any(|mp| mp.name.as_bytes() == mp_name) {
+            return EEXIST;
+        }
+        let Some(slot) = reg.mps.iter_mut().find(|m| m.is_none()) else {
+            return ENOMEM;
+        };
+
+        let mut mp = Box::new(ext4_mountpoint {
+            mounted: false,
+            name: String::from_utf8_lossy(mp_name).into_owned(),
+            fs: Ext4Filesystem::new(),
+            bc: Ext4BlockCache::new(),
+            cache_dynamic: false,
+        });
+
+        let r = ext4_block_init(bd);
+        if r != EOK {
+            return r;
+        }
+
+        let r = ext4_fs_init(&mut mp.fs, bd, read_only);
+        if r != EOK {
+            (*bd).fs = ptr::null_mut();
+            ext4_block_fini(bd);
+            return r;
+        }
+
+        let bsize = ext4_sb_get_block_size(&mp.fs.sb);
+        ext4_block_set_lb_size(bd, bsize);
+
+        // 块设备没有绑定缓存时使用动态缓存
+        let mut r = EOK;
+        let bc = if (*bd).bc.is_null() {
+            r = ext4_bcache_init_dynamic(&mut mp.bc, config.bcache_size, bsize);
+            mp.cache_dynamic = r == EOK;
+            &mut mp.bc as *mut Ext4BlockCache
+        } else {
+            (*bd).bc
+        };
+        if r == EOK && bsize != (*bc).itemsize {
+            r = ENOTSUP;
+        }
+        if r == EOK {
+            r = ext4_block_bind_bcache(bd, bc);
+        }
+        if r == EOK {
+            r = ext4_fs_set_config(&mut mp.fs, &config);
+        }
+        if r != EOK {
+            ext4_fs_fini(&mut mp.fs);
+            if mp.cache_dynamic {
+                ext4_bcache_fini_dynamic(bc);
+            }
+            (*bd).fs = ptr::null_mut();
+            ext4_block_fini(bd);
+            return r;
+        }
+
+        // Box 中的地址在移动后保持不变，bdev.fs 指向的位置仍然有效
+        mp.mounted = true;
+        *slot = Some(mp);
+        ext4_dbg!(DEBUG_EXT4, Debug, "ext4_mount: {}", String::from_utf8_lossy(mp_name));
+    }
+    EOK
+}
+
+/// 卸载文件系统
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_umount(mount_point: *const c_char) -> i32 {
+    unsafe {
+        let Some(mp_name) = ext4_cstr(mount_point) else {
+            return EINVAL;
+        };
+        let Some(slot) = ext4_registry()
+            .mps
+            .iter_mut()
+            .find(|m| matches!(m, Some(mp) if mp.name.as_bytes() == mp_name))
+        else {
+            return ENODEV;
+        };
+        let mp = slot.as_mut().unwrap();
+
+        let r = ext4_fs_fini(&mut mp.fs);
+        if r != EOK {
+            return r;
+        }
+        mp.mounted = false;
+
+        let bd = mp.fs.bdev;
+        ext4_bcache_cleanup((*bd).bc);
+        if mp.cache_dynamic {
+            ext4_bcache_fini_dynamic((*bd).bc);
+            (*bd).bc = ptr::null_mut();
+        }
+        let r = ext4_block_fini(bd);
+        (*bd).fs = ptr::null_mut();
+        *slot = None;
+        r
+    }
+}
+
+/// 回放挂载点的日志（文件系统未干净卸载时）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_recover(mount_point: *const c_char) -> i32 {
+    unsafe {
+        let Some(mp_name) = ext4_cstr(mount_point) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(mp_name);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        ext4_journal_recover(&mut (*mp).fs)
+    }
+}
+
+/// 将挂载点的缓存写回设备
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_cache_flush(path: *const c_char) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        ext4_block_cache_flush((*mp).fs.bdev)
+    }
+}
+
+// ===== 路径解析 =====
+
+/// 解析字符串形式的打开模式（与 fopen 相同）
+fn ext4_parse_flags(flags: &[u8]) -> Option<u32> {
+    match flags {
+        b"r" | b"rb" => Some(O_RDONLY),
+        b"w" | b"wb" => Some(O_WRONLY | O_CREAT | O_TRUNC),
+        b"a" | b"ab" => Some(O_WRONLY | O_CREAT | O_APPEND),
+        b"r+" | b"rb+" | b"r+b" => Some(O_RDWR),
+        b"w+" | b"wb+" | b"w+b" => Some(O_RDWR | O_CREAT | O_TRUNC),
+        b"a+" | b"ab+" | b"a+b" => Some(O_RDWR | O_CREAT | O_APPEND),
+        _ => None,
+    }
+}
+
+/// 取路径的第一级名称，返回（长度，是否为最后一级）
+fn ext4_path_check(path: &[u8]) -> (usize, bool) {
+    match path.iter().position(|&c| c == b'/') {
+        Some(i) => (i, false),
+        None => (path.len(), true),
+    }
+}
+
+/// 路径中最后一级名称（去掉末尾的'/'）
+fn ext4_path_name(path: &[u8], name_off: usize) -> &[u8] {
+    let mut name = &path[name_off..];
+    while let [rest @ .., b'/'] = name {
+        name = rest;
+    }
+    name
+}
+
+/// 将子 inode 链接到父目录（新目录同时创建"."和".."）
+unsafe fn ext4_link(
+    fs: *mut Ext4Filesystem,
+    parent: *mut Ext4InodeRef,
+    child: *mut Ext4InodeRef,
+    name: &[u8],
+) -> i32 {
+    unsafe {
+        // 新目录增加父目录的链接数，其余增加自身的链接数
+        let is_dir = ext4_inode_is_type(&(*fs).sb, (*child).inode, EXT4_INODE_MODE_DIRECTORY);
+        if ext4_inode_links_max(&(*fs).sb, if is_dir { (*parent).inode } else { (*child).inode }) {
+            return EMLINK;
+        }
+        let r = ext4_dir_add_entry(parent, name.as_ptr(), name.len() as u32, child);
+        if r != EOK {
+            return r;
+        }
+
+        if is_dir {
+            let r = ext4_dir_add_entry(child, b".".as_ptr(), 1, child);
+            if r != EOK {
+                ext4_dir_remove_entry(parent, name.as_ptr(), name.len() as u32);
+                return r;
+            }
+            let r = ext4_dir_add_entry(child, b"..".as_ptr(), 2, parent);
+            if r != EOK {
+                ext4_dir_remove_entry(parent, name.as_ptr(), name.len() as u32);
+                ext4_dir_remove_entry(child, b".".as_ptr(), 1);
+                return r;
+            }
+            // 新建空目录有两个链接（父目录中的名称和"."）
+            ext4_inode_set_links_cnt((*child).inode, 2);
+            ext4_fs_inode_links_count_inc(parent);
+            (*child).dirty = true;
+            (*parent).dirty = true;
+            return EOK;
+        }
+
+        ext4_fs_inode_links_count_inc(child);
+    }
+    EOK
+}
+
+/// 截断 inode（截断期间开启缓存写回）
+unsafe fn ext4_trunc_inode(fs: *mut Ext4Filesystem, index: u32, new_size: u64) -> i32 {
+    unsafe {
+        let mut inode_ref = match InodeRef::get(fs, index) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+
+        ext4_block_cache_write_back((*fs).bdev, 1);
+        let r = ext4_fs_truncate_inode(&mut *inode_ref, new_size);
+        let r2 = inode_ref.put();
+        let r3 = ext4_block_cache_write_back((*fs).bdev, 0);
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+        r3
+    }
+}
+
+/// 从父目录中删除名称，链接数归零时释放 inode 及其数据块
+///
+/// 删除目录前需先清空目录内容。保留 inode 返回 EPERM。
+unsafe fn ext4_remove_name(
+    fs: *mut Ext4Filesystem,
+    parent: *mut Ext4InodeRef,
+    child_index: u32,
+    name: &[u8],
+) -> i32 {
+    unsafe {
+        if ext4_sb_is_reserved_ino(&(*fs).sb, child_index) {
+            return EPERM;
+        }
+        let mut child = match InodeRef::get(fs, child_index) {
+            Ok(child) => child,
+            Err(r) => return r,
+        };
+
+        let mut r = ext4_dir_remove_entry(parent, name.as_ptr(), name.len() as u32);
+        if r == EOK {
+            if ext4_inode_is_type(&(*fs).sb, child.inode, EXT4_INODE_MODE_DIRECTORY) {
+                // 父目录中的名称和"."同时失效，".."不再指向父目录
+                ext4_inode_set_links_cnt(child.inode, 0);
+                child.dirty = true;
+                ext4_fs_inode_links_count_dec(parent);
+            } else {
+                ext4_fs_inode_links_count_dec(&mut *child);
+            }
+
+            if ext4_inode_get_links_cnt(child.inode) == 0 {
+                r = ext4_fs_truncate_inode(&mut *child, 0);
+                if r == EOK {
+                    ext4_inode_set_del_time(child.inode, u32::MAX);
+                    r = ext4_fs_free_inode(&mut *child);
+                }
+            }
+        }
+
+        let r2 = child.put();
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 查找目录中除"."和".."以外的第一个目录项，目录为空时返回 ENOENT
+unsafe fn ext4_dir_first_child(
+    fs: *mut Ext4Filesystem,
+    dir_ref: *mut Ext4InodeRef,
+    name: &mut Vec<u8>,
+    index: &mut u32,
+) -> i32 {
+    unsafe {
+        let mut it = Ext4DirIterator::new();
+        let mut r = ext4_dir_iterator_init(&mut it, dir_ref, 0);
+        if r == EOK {
+            r = ENOENT;
+            while !it.curr.is_null() {
+                let de = it.curr;
+                let len = ext4_dir_en_get_name_len(&(*fs).sb, de) as usize;
+                let de_name = core::slice::from_raw_parts((*de).name_mut_ptr(), len);
+                if de_name != b"." && de_name != b".." {
+                    name.clear();
+                    name.extend_from_slice(de_name);
+                    *index = u32::from_le((*de).inode);
+                    r = EOK;
+                    break;
+                }
+                let r2 = ext4_dir_iterator_next(&mut it);
+                if r2 != EOK {
+                    r = r2;
+                    break;
+                }
+            }
+        }
+        ext4_dir_iterator_fini(&mut it);
+        r
+    }
+}
+
+/// 删除目录中的全部内容
+///
+/// 用显式栈代替递归遍历子目录，避免目录层次过深时耗尽栈空间。
+unsafe fn ext4_dir_rm_content(fs: *mut Ext4Filesystem, index: u32) -> i32 {
+    unsafe {
+        let mut stack = alloc::vec![index];
+        let mut name = Vec::new();
+        let mut sub_name = Vec::new();
+        while let Some(&top) = stack.last() {
+            let mut dir_ref = match InodeRef::get(fs, top) {
+                Ok(dir_ref) => dir_ref,
+                Err(r) => return r,
+            };
+
+            let mut child_index = 0u32;
+            let mut r = ext4_dir_first_child(fs, &mut *dir_ref, &mut name, &mut child_index);
+            if r == ENOENT {
+                // 目录已清空，由上一级删除
+                stack.pop();
+                r = EOK;
+            } else if r == EOK {
+                let mut non_empty_dir = false;
+                r = match InodeRef::get(fs, child_index) {
+                    Ok(mut child_ref) => {
+                        let mut sub_index = 0u32;
+                        non_empty_dir = ext4_inode_is_type(&(*fs).sb, child_ref.inode, EXT4_INODE_MODE_DIRECTORY)
+                            && ext4_dir_first_child(fs, &mut *child_ref, &mut sub_name, &mut sub_index) == EOK;
+                        child_ref.put()
+                    }
+                    Err(r) => r,
+                };
+                if r == EOK {
+                    if non_empty_dir {
+                        stack.push(child_index);
+                    } else {
+                        r = ext4_remove_name(fs, &mut *dir_ref, child_index, &name);
+                    }
+                }
+            }
+
+            let r2 = dir_ref.put();
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// 目录 index 是否为 ancestor 本身或位于其下（沿".."向上查找）
+unsafe fn ext4_dir_is_descendant(
+    fs: *mut Ext4Filesystem,
+    mut index: u32,
+    ancestor: u32,
+    out: &mut bool,
+) -> i32 {
+    unsafe {
+        *out = false;
+        loop {
+            if index == ancestor {
+                *out = true;
+                return EOK;
+            }
+            if index == EXT4_INODE_ROOT_INDEX {
+                return EOK;
+            }
+
+            let mut dir_ref = match InodeRef::get(fs, index) {
+                Ok(dir_ref) => dir_ref,
+                Err(r) => return r,
+            };
+            let mut result = Ext4DirSearchResult::new();
+            let mut r = ext4_dir_find_entry(&mut result, &mut *dir_ref, b"..".as_ptr(), 2);
+            let parent = if r == EOK { u32::from_le((*result.dentry).inode) } else { 0 };
+            let r2 = ext4_dir_destroy_result(&mut *dir_ref, &mut result);
+            let r3 = dir_ref.put();
+            for res in [r2, r3] {
+                if r == EOK {
+                    r = res;
+                }
+            }
+            if r != EOK {
+                return r;
+            }
+            // ".."指向自身只允许出现在根目录
+            if parent == index {
+                return EIO;
+            }
+            index = parent;
+        }
+    }
+}
+
+/// 将子 inode 从原目录的 name 移动到新目录的 new_name（子目录同时更新".."）
+unsafe fn ext4_move(
+    fs: *mut Ext4Filesystem,
+    parent_index: u32,
+    new_parent_index: u32,
+    child_index: u32,
+    name: &[u8],
+    new_name: &[u8],
+) -> i32 {
+    unsafe {
+        let same_dir = parent_index == new_parent_index;
+        // 出错返回时已获取的引用在 Drop 中归还
+        let mut parent = match InodeRef::get(fs, parent_index) {
+            Ok(parent) => parent,
+            Err(r) => return r,
+        };
+        let mut new_parent = if same_dir {
+            InodeRef::new()
+        } else {
+            match InodeRef::get(fs, new_parent_index) {
+                Ok(new_parent) => new_parent,
+                Err(r) => return r,
+            }
+        };
+        let mut child = match InodeRef::get(fs, child_index) {
+            Ok(child) => child,
+            Err(r) => return r,
+        };
+
+        let np: *mut Ext4InodeRef = if same_dir { &mut *parent } else { &mut *new_parent };
+        let mut r = if !same_dir
+            && ext4_inode_is_type(&(*fs).sb, child.inode, EXT4_INODE_MODE_DIRECTORY)
+            && ext4_inode_links_max(&(*fs).sb, (*np).inode)
+        {
+            EMLINK
+        } else {
+            ext4_dir_add_entry(np, new_name.as_ptr(), new_name.len() as u32, &mut *child)
+        };
+
+        if r == EOK && !same_dir && ext4_inode_is_type(&(*fs).sb, child.inode, EXT4_INODE_MODE_DIRECTORY) {
+            let mut result = Ext4DirSearchResult::new();
+            r = ext4_dir_find_entry(&mut result, &mut *child, b"..".as_ptr(), 2);
+            if r == EOK {
+                (*result.dentry).inode = new_parent_index.to_le();
+            }
+            let r2 = ext4_dir_destroy_result(&mut *child, &mut result);
+            if r == EOK {
+                r = r2;
+            }
+            if r == EOK {
+                ext4_fs_inode_links_count_inc(np);
+                ext4_fs_inode_links_count_dec(&mut *parent);
+            }
+        }
+
+        if r == EOK {
+            r = ext4_dir_remove_entry(&mut *parent, name.as_ptr(), name.len() as u32);
+        }
+
+        // 未加载的引用在 put 时直接返回
+        let results = [child.put(), new_parent.put(), parent.put()];
+        for res in results {
+            if r == EOK {
+                r = res;
+            }
+        }
+        r
+    }
+}
+
+/// 按路径打开文件或目录
+///
+/// 逐级查找路径中的名称，带 O_CREAT 时创建缺失的部分（中间部分创建为目录）。
+/// parent_inode 返回最后一级所在目录，name_off 返回最后一级名称在路径中的偏移。
+unsafe fn ext4_generic_open2(
+    f: *mut ext4_file,
+    path: &[u8],
+    flags: u32,
+    ftype: u32,
+    mut parent_inode: Option<&mut u32>,
+    mut name_off: Option<&mut usize>,
+) -> i32 {
+    unsafe {
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+
+        (*f).flags = flags;
+
+        // 去掉挂载点名称
+        let mp_name_len = (&*mp).name.len();
+        let mut path = &path[mp_name_len..];
+        // 末尾的'/'不影响查找结果
+        while let [rest @ .., b'/'] = path {
+            path = rest;
+        }
+        if let Some(off) = name_off.as_deref_mut() {
+            *off = mp_name_len;
+        }
+
+        // 出错返回时当前的引用在 Drop 中归还
+        let mut inode_ref = match InodeRef::get(fs, EXT4_INODE_ROOT_INDEX) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+
+        let mut is_goal;
+        let mut r = EOK;
+        loop {
+            let (len, goal) = ext4_path_check(path);
+            is_goal = goal;
+
+            if len == 0 {
+                // 打开挂载点根目录
+                if (ftype == EXT4_DE_DIR || ftype == EXT4_DE_UNKNOWN) && is_goal {
+                    break;
+                }
+                r = ENOENT;
+                break;
+            }
+            if len > EXT4_DIRECTORY_FILENAME_LEN {
+                r = ENAMETOOLONG;
+                break;
+            }
+
+            let mut result = Ext4DirSearchResult::new();
+            r = ext4_dir_find_entry(&mut result, &mut *inode_ref, path.as_ptr(), len as u32);
+            if r != EOK {
+                ext4_dir_destroy_result(&mut *inode_ref, &mut result);
+                if r != ENOENT || (*f).flags & O_CREAT == 0 {
+                    break;
+                }
+                if (*fs).read_only {
+                    r = EROFS;
+                    break;
+                }
+
+                // O_CREAT：创建缺失的名称
+                let mut child_ref = match InodeRef::alloc(fs, if is_goal { ftype } else { EXT4_DE_DIR }) {
+                    Ok(child_ref) => child_ref,
+                    Err(e) => {
+                        r = e;
+                        break;
+                    }
+                };
+                ext4_fs_inode_blocks_init(fs, &mut *child_ref);
+                // 只是分配提示，失败时忽略
+                let _ = ext4_balloc_inherit_goal(&mut *child_ref, &mut *inode_ref);
+
+                r = ext4_link(fs, &mut *inode_ref, &mut *child_ref, &path[..len]);
+                if r != EOK {
+                    // 释放新分配的 inode，不写回其内容
+                    ext4_fs_free_inode(&mut *child_ref);
+                    child_ref.dirty = false;
+                    break;
+                }
+                child_ref.put();
+                continue;
+            }
+
+            if let Some(p) = parent_inode.as_deref_mut() {
+                *p = inode_ref.index;
+            }
+            let next_inode = u32::from_le((*result.dentry).inode);
+            r = ext4_dir_destroy_result(&mut *inode_ref, &mut result);
+            if r != EOK {
+                break;
+            }
+
+            r = mem::take(&mut inode_ref).put();
+            if r != EOK {
+                break;
+            }
+            inode_ref = match InodeRef::get(fs, next_inode) {
+                Ok(inode_ref) => inode_ref,
+                Err(e) => {
+                    r = e;
+                    break;
+                }
+            };
+
+            let sb = &(*fs).sb;
+            let is_dir = ext4_inode_is_type(sb, inode_ref.inode, EXT4_INODE_MODE_DIRECTORY);
+            // 中间部分必须是目录
+            if !is_goal && !is_dir {
+                r = ENOENT;
+                break;
+            }
+            // 类型与期望不符
+            if is_goal
+                && ftype != EXT4_DE_UNKNOWN
+                && ext4_inode_type(sb, inode_ref.inode) != ext4_fs_correspond_inode_mode(ftype)
+            {
+                r = ENOENT;
+                break;
+            }
+
+            if is_goal {
+                break;
+            }
+            path = &path[len + 1..];
+            if let Some(off) = name_off.as_deref_mut() {
+                *off += len + 1;
+            }
+        }
+
+        if r != EOK {
+            return r;
+        }
+
+        if is_goal {
+            let sb = &(*fs).sb;
+            if (*f).flags & O_TRUNC != 0 && ext4_inode_is_type(sb, inode_ref.inode, EXT4_INODE_MODE_FILE) {
+                if (*fs).read_only {
+                    return EROFS;
+                }
+                let index = inode_ref.ino();
+                let r = mem::take(&mut inode_ref).put();
+                if r != EOK {
+                    return r;
+                }
+                let r = ext4_trunc_inode(fs, index, 0);
+                if r != EOK {
+                    return r;
+                }
+                inode_ref = match InodeRef::get(fs, index) {
+                    Ok(inode_ref) => inode_ref,
+                    Err(r) => return r,
+                };
+            }
+
+            (*f).mp = mp;
+            (*f).fsize = ext4_inode_get_size(&(*fs).sb, inode_ref.inode);
+            (*f).inode = inode_ref.index;
+            (*f).fpos = 0;
+            if (*f).flags & O_APPEND != 0 {
+                (*f).fpos = (*f).fsize;
+            }
+        }
+
+        inode_ref.put()
+    }
+}
+
+/// 在撤销点内执行修改操作，失败时恢复操作前的元数据（释放已分配的 inode 和块）
+unsafe fn ext4_undoable(fs: *mut Ext4Filesystem, op: impl FnOnce() -> i32) -> i32 {
+    unsafe {
+        let mut undo = Ext4Undo::new();
+        ext4_undo_begin(fs, &mut undo);
+        let r = op();
+        if r == EOK {
+            ext4_undo_commit(fs, &mut undo);
+        } else {
+            ext4_undo_rollback(fs, &mut undo);
+        }
+        r
+    }
+}
+
+/// 打开文件，需要创建或截断时在撤销点内进行
+unsafe fn ext4_generic_open(f: *mut ext4_file, path: &[u8], flags: u32, ftype: u32) -> i32 {
+    unsafe {
+        let mp = ext4_get_mount(path);
+        if mp.is_null() || flags & (O_CREAT | O_TRUNC) == 0 {
+            return ext4_generic_open2(f, path, flags, ftype, None, None);
+        }
+        ext4_undoable(&mut (*mp).fs, || ext4_generic_open2(f, path, flags, ftype, None, None))
+    }
+}
+
+// ===== 文件接口 =====
+
+/// 按字符串模式打开文件（"r"、"w"、"a"、"r+"、"w+"、"a+"，可带"b"）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fopen(file: *mut ext4_file, path: *const c_char, flags: *const c_char) -> i32 {
+    unsafe {
+        let (Some(path), Some(flags)) = (ext4_cstr(path), ext4_cstr(flags)) else {
+            return EINVAL;
+        };
+        if file.is_null() {
+            return EINVAL;
+        }
+        let Some(iflags) = ext4_parse_flags(flags) else {
+            return EINVAL;
+        };
+        ext4_generic_open(file, path, iflags, EXT4_DE_REG_FILE)
+    }
+}
+
+/// 按 O_* 标志打开文件
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fopen2(file: *mut ext4_file, path: *const c_char, flags: i32) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        if file.is_null() {
+            return EINVAL;
+        }
+        ext4_generic_open(file, path, flags as u32, EXT4_DE_REG_FILE)
+    }
+}
+
+/// 关闭文件
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fclose(file: *mut ext4_file) -> i32 {
+    if file.is_null() {
+        return EINVAL;
+    }
+    unsafe {
+        (*file).mp = ptr::null_mut();
+        (*file).flags = 0;
+        (*file).inode = 0;
+        (*file).fpos = 0;
+        (*file).fsize = 0;
+    }
+    EOK
+}
+
+/// 截断文件（只能缩小）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_ftruncate(file: *mut ext4_file, size: u64) -> i32 {
+    unsafe {
+        if file.is_null() || (*file).mp.is_null() {
+            return EINVAL;
+        }
+        if (*file).flags & O_ACCMODE == O_RDONLY {
+            return EPERM;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*(*file).mp).fs;
+        if (*fs).read_only {
+            return EROFS;
+        }
+
+        let inode_ref = match InodeRef::get(fs, (*file).inode) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+        // 同步文件大小
+        (*file).fsize = ext4_inode_get_size(&(*fs).sb, inode_ref.inode);
+        let r = inode_ref.put();
+        if r != EOK {
+            return r;
+        }
+        if (*file).fsize <= size {
+            return EOK;
+        }
+
+        let r = ext4_undoable(fs, || ext4_trunc_inode(fs, (*file).inode, size));
+        if r != EOK {
+            return r;
+        }
+        (*file).fsize = size;
+        if (*file).fpos > size {
+            (*file).fpos = size;
+        }
+    }
+    EOK
+}
+
+/// 从当前位置读取文件
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fread(file: *mut ext4_file, buf: *mut c_void, size: usize, rcnt: *mut usize) -> i32 {
+    unsafe {
+        if !rcnt.is_null() {
+            *rcnt = 0;
+        }
+        if file.is_null() || (*file).mp.is_null() || (buf.is_null() && size != 0) {
+            return EINVAL;
+        }
+        if (*file).flags & O_ACCMODE == O_WRONLY {
+            return EPERM;
+        }
+        if size == 0 {
+            return EOK;
+        }
+
+        let fs: *mut Ext4Filesystem = &mut (*(*file).mp).fs;
+        let sb = &(*fs).sb;
+        let bdev = (*fs).bdev;
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+
+        let mut inode_ref = match InodeRef::get(fs, (*file).inode) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+
+        // 同步文件大小
+        (*file).fsize = ext4_inode_get_size(sb, inode_ref.inode);
+        let size = (size as u64).min((*file).fsize.saturating_sub((*file).fpos)) as usize;
+        let buf = buf as *mut u8;
+
+        // 短符号链接的内容直接存放在 inode 中
+        if ext4_inode_is_fast_symlink(sb, inode_ref.inode) {
+            let content = (*inode_ref.inode).blocks.as_ptr() as *const u8;
+            ptr::copy_nonoverlapping(content.add((*file).fpos as usize), buf, size);
+            (*file).fpos += size as u64;
+            if !rcnt.is_null() {
+                *rcnt = size;
+            }
+            return inode_ref.put();
+        }
+
+        let mut done = 0usize;
+        let mut r = EOK;
+        while done < size {
+            let iblock = (*file).fpos / block_size;
+            let off = (*file).fpos % block_size;
+            let len = ((block_size - off) as usize).min(size - done);
+
+            let mut fblock = 0u64;
+            r = ext4_fs_get_inode_dblk_idx(&mut *inode_ref, iblock as u32, &mut fblock, true);
+            if r != EOK {
+                break;
+            }
+            if fblock == 0 {
+                // 空洞读出为0
+                ptr::write_bytes(buf.add(done), 0, len);
+            } else {
+                r = ext4_block_readbytes(bdev, fblock * block_size + off, buf.add(done), len);
+                if r != EOK {
+                    break;
+                }
+            }
+            done += len;
+            (*file).fpos += len as u64;
+        }
+
+        if !rcnt.is_null() {
+            *rcnt = done;
+        }
+        let r2 = inode_ref.put();
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 在当前位置写入文件（必要时分配数据块并扩展文件）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fwrite(file: *mut ext4_file, buf: *const c_void, size: usize, wcnt: *mut usize) -> i32 {
+    unsafe {
+        if !wcnt.is_null() {
+            *wcnt = 0;
+        }
+        if file.is_null() || (*file).mp.is_null() || (buf.is_null() && size != 0) {
+            return EINVAL;
+        }
+        if (*file).flags & O_ACCMODE == O_RDONLY {
+            return EPERM;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*(*file).mp).fs;
+        if (*fs).read_only {
+            return EROFS;
+        }
+        if size == 0 {
+            return EOK;
+        }
+
+        let sb = &(*fs).sb;
+        let bdev = (*fs).bdev;
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+
+        let mut inode_ref = match InodeRef::get(fs, (*file).inode) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+        (*file).fsize = ext4_inode_get_size(sb, inode_ref.inode);
+
+        ext4_block_cache_write_back(bdev, 1);
+
+        let buf = buf as *const u8;
+        let mut zero_buf = Vec::new();
+        let mut done = 0usize;
+        let mut r = EOK;
+        while done < size {
+            let iblock = (*file).fpos / block_size;
+            let off = (*file).fpos % block_size;
+            let len = ((block_size - off) as usize).min(size - done);
+            if iblock > u32::MAX as u64 {
+                r = EFBIG;
+                break;
+            }
+
+            let mut fblock = 0u64;
+            r = ext4_fs_get_inode_dblk_idx(&mut *inode_ref, iblock as u32, &mut fblock, false);
+            if r != EOK {
+                break;
+            }
+            let is_new = fblock == 0;
+            if is_new {
+                r = ext4_fs_init_inode_dblk_idx(&mut *inode_ref, iblock as u32, &mut fblock);
+                if r != EOK {
+                    break;
+                }
+            }
+
+            if is_new && len as u64 != block_size {
+                // 新分配的块只写入一部分时，其余部分填0
+                zero_buf.clear();
+                zero_buf.resize(block_size as usize, 0);
+                ptr::copy_nonoverlapping(buf.add(done), zero_buf.as_mut_ptr().add(off as usize), len);
+                r = ext4_blocks_set_direct(bdev, zero_buf.as_ptr() as _, fblock, 1);
+            } else {
+                r = ext4_block_writebytes(bdev, fblock * block_size + off, buf.add(done), len);
+            }
+            if r != EOK {
+                break;
+            }
+
+            done += len;
+            (*file).fpos += len as u64;
+            if (*file).fpos > (*file).fsize {
+                (*file).fsize = (*file).fpos;
+                r = ext4_fs_set_inode_size(&mut *inode_ref, (*file).fsize);
+                if r != EOK {
+                    break;
+                }
+            }
+        }
+
+        if !wcnt.is_null() {
+            *wcnt = done;
+        }
+        let r2 = inode_ref.put();
+        let r3 = ext4_block_cache_write_back(bdev, 0);
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+        r3
+    }
+}
+
+/// 移动读写位置（不能超出文件末尾）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fseek(file: *mut ext4_file, offset: i64, origin: u32) -> i32 {
+    if file.is_null() {
+        return EINVAL;
+    }
+    unsafe {
+        let f = &mut *file;
+        match origin {
+            SEEK_SET => {
+                if offset < 0 || offset as u64 > f.fsize {
+                    return EINVAL;
+                }
+                f.fpos = offset as u64;
+                EOK
+            }
+            SEEK_CUR => {
+                if (offset < 0 && offset.unsigned_abs() > f.fpos)
+                    || (offset > 0 && offset as u64 > f.fsize - f.fpos)
+                {
+                    return EINVAL;
+                }
+                f.fpos = f.fpos.wrapping_add_signed(offset);
+                EOK
+            }
+            SEEK_END => {
+                if offset > 0 || offset.unsigned_abs() > f.fsize {
+                    return EINVAL;
+                }
+                f.fpos = f.fsize.wrapping_add_signed(offset);
+                EOK
+            }
+            _ => EINVAL,
+        }
+    }
+}
+
+/// 当前读写位置
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_ftell(file: *mut ext4_file) -> u64 {
+    unsafe { (*file).fpos }
+}
+
+/// 文件大小
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fsize(file: *mut ext4_file) -> u64 {
+    unsafe { (*file).fsize }
+}
+
+/// 删除文件（链接数归零时释放数据块和 inode）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_fremove(path: *const c_char) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+        if (*fs).read_only {
+            return EROFS;
+        }
+
+        let mut f: ext4_file = core::mem::zeroed();
+        let mut parent_inode = 0u32;
+        let mut name_off = 0usize;
+        let r = ext4_generic_open2(
+            &mut f,
+            path,
+            O_RDWR,
+            EXT4_DE_UNKNOWN,
+            Some(&mut parent_inode),
+            Some(&mut name_off),
+        );
+        if r != EOK {
+            return r;
+        }
+        // 挂载点根目录
+        if f.inode == EXT4_INODE_ROOT_INDEX && parent_inode == 0 {
+            return EINVAL;
+        }
+
+        let child = match InodeRef::get(fs, f.inode) {
+            Ok(child) => child,
+            Err(r) => return r,
+        };
+        // 目录由 ext4_dir_rm 删除
+        let is_dir = ext4_inode_is_type(&(*fs).sb, child.inode, EXT4_INODE_MODE_DIRECTORY);
+        let r = child.put();
+        if is_dir {
+            return EISDIR;
+        }
+        if r != EOK {
+            return r;
+        }
+
+        let mut parent = match InodeRef::get(fs, parent_inode) {
+            Ok(parent) => parent,
+            Err(r) => return r,
+        };
+
+        ext4_block_cache_write_back((*fs).bdev, 1);
+        let r = ext4_undoable(fs, || ext4_remove_name(fs, &mut *parent, f.inode, ext4_path_name(path, name_off)));
+        let r2 = parent.put();
+        let r3 = ext4_block_cache_write_back((*fs).bdev, 0);
+        for res in [r, r2, r3] {
+            if res != EOK {
+                return res;
+            }
+        }
+    }
+    EOK
+}
+
+/// 重命名（移动）文件或目录
+///
+/// 新路径必须不存在且位于同一挂载点，其上级目录必须已存在；目录不能移动到自身之下。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_frename(path: *const c_char, new_path: *const c_char) -> i32 {
+    unsafe {
+        let (Some(path), Some(new_path)) = (ext4_cstr(path), ext4_cstr(new_path)) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        if ext4_get_mount(new_path) != mp {
+            return EXDEV;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+        if (*fs).read_only {
+            return EROFS;
+        }
+
+        let mut f: ext4_file = core::mem::zeroed();
+        let r = ext4_generic_open2(&mut f, new_path, O_RDONLY, EXT4_DE_UNKNOWN, None, None);
+        if r == EOK {
+            return EEXIST;
+        }
+        if r != ENOENT {
+            return r;
+        }
+
+        let mut parent_inode = 0u32;
+        let mut name_off = 0usize;
+        let r = ext4_generic_open2(
+            &mut f,
+            path,
+            O_RDONLY,
+            EXT4_DE_UNKNOWN,
+            Some(&mut parent_inode),
+            Some(&mut name_off),
+        );
+        if r != EOK {
+            return r;
+        }
+        // 挂载点根目录
+        if parent_inode == 0 {
+            return EINVAL;
+        }
+        let child_index = f.inode;
+        let name = ext4_path_name(path, name_off);
+
+        // 拆分新路径为上级目录和名称
+        let mp_name_len = (&*mp).name.len();
+        let new_rel = ext4_path_name(new_path, mp_name_len);
+        let (new_dir_len, new_name) = match new_rel.iter().rposition(|&c| c == b'/') {
+            Some(i) => (mp_name_len + i + 1, &new_rel[i + 1..]),
+            None => (mp_name_len, new_rel),
+        };
+        if new_name.is_empty() {
+            return EINVAL;
+        }
+        if new_name.len() > EXT4_DIRECTORY_FILENAME_LEN {
+            return ENAMETOOLONG;
+        }
+        let r = ext4_generic_open2(&mut f, &new_path[..new_dir_len], O_RDONLY, EXT4_DE_DIR, None, None);
+        if r != EOK {
+            return r;
+        }
+        let new_parent_inode = f.inode;
+
+        let mut in_child = false;
+        let r = ext4_dir_is_descendant(fs, new_parent_inode, child_index, &mut in_child);
+        if r != EOK {
+            return r;
+        }
+        if in_child {
+            return EINVAL;
+        }
+
+        ext4_block_cache_write_back((*fs).bdev, 1);
+        let r = ext4_undoable(fs, || ext4_move(fs, parent_inode, new_parent_inode, child_index, name, new_name));
+        let r2 = ext4_block_cache_write_back((*fs).bdev, 0);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+// ===== 元数据接口 =====
+
+/// 按路径取得 inode 并调用 op(superblock, inode)，modify 为真时写回 inode
+unsafe fn ext4_path_inode_op<F>(path: *const c_char, modify: bool, op: F) -> i32
+where
+    F: FnOnce(*mut Ext4Superblock, *mut Ext4Inode),
+{
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+        if modify && (*fs).read_only {
+            return EROFS;
+        }
+
+        let mut f: ext4_file = core::mem::zeroed();
+        let r = ext4_generic_open2(&mut f, path, O_RDONLY, EXT4_DE_UNKNOWN, None, None);
+        if r != EOK {
+            return r;
+        }
+
+        let mut inode_ref = match InodeRef::get(fs, f.inode) {
+            Ok(inode_ref) => inode_ref,
+            Err(r) => return r,
+        };
+        op(&mut (*fs).sb, inode_ref.inode);
+        if modify {
+            inode_ref.dirty = true;
+        }
+        inode_ref.put()
+    }
+}
+
+/// 设置权限位（只修改低12位，文件类型不变）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mode_set(path: *const c_char, mode: u32) -> i32 {
+    unsafe {
+        ext4_path_inode_op(path, true, |sb, inode| {
+            let old = ext4_inode_get_mode(sb, inode);
+            ext4_inode_set_mode(sb, inode, (old & !0xFFF) | (mode & 0xFFF));
+        })
+    }
+}
+
+/// 获取模式（包含文件类型）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mode_get(path: *const c_char, mode: *mut u32) -> i32 {
+    if mode.is_null() {
+        return EINVAL;
+    }
+    unsafe { ext4_path_inode_op(path, false, |sb, inode| *mode = ext4_inode_get_mode(sb, inode)) }
+}
+
+/// 设置所有者
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_owner_set(path: *const c_char, uid: u32, gid: u32) -> i32 {
+    unsafe {
+        ext4_path_inode_op(path, true, |_, inode| {
+            ext4_inode_set_uid(inode, uid);
+            ext4_inode_set_gid(inode, gid);
+        })
+    }
+}
+
+/// 获取所有者
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_owner_get(path: *const c_char, uid: *mut u32, gid: *mut u32) -> i32 {
+    if uid.is_null() || gid.is_null() {
+        return EINVAL;
+    }
+    unsafe {
+        ext4_path_inode_op(path, false, |_, inode| {
+            *uid = ext4_inode_get_uid(inode);
+            *gid = ext4_inode_get_gid(inode);
+        })
+    }
+}
+
+/// 设置访问时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_atime_set(path: *const c_char, atime: u32) -> i32 {
+    unsafe { ext4_path_inode_op(path, true, |_, inode| ext4_inode_set_access_time(inode, atime)) }
+}
+
+/// 设置修改时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mtime_set(path: *const c_char, mtime: u32) -> i32 {
+    unsafe { ext4_path_inode_op(path, true, |_, inode| ext4_inode_set_modif_time(inode, mtime)) }
+}
+
+/// 设置 inode 改变时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_ctime_set(path: *const c_char, ctime: u32) -> i32 {
+    unsafe { ext4_path_inode_op(path, true, |_, inode| ext4_inode_set_change_inode_time(inode, ctime)) }
+}
+
+/// 获取访问时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_atime_get(path: *const c_char, atime: *mut u32) -> i32 {
+    if atime.is_null() {
+        return EINVAL;
+    }
+    unsafe { ext4_path_inode_op(path, false, |_, inode| *atime = ext4_inode_get_access_time(inode)) }
+}
+
+/// 获取修改时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_mtime_get(path: *const c_char, mtime: *mut u32) -> i32 {
+    if mtime.is_null() {
+        return EINVAL;
+    }
+    unsafe { ext4_path_inode_op(path, false, |_, inode| *mtime = ext4_inode_get_modif_time(inode)) }
+}
+
+/// 获取 inode 改变时间
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_ctime_get(path: *const c_char, ctime: *mut u32) -> i32 {
+    if ctime.is_null() {
+        return EINVAL;
+    }
+    unsafe { ext4_path_inode_op(path, false, |_, inode| *ctime = ext4_inode_get_change_inode_time(inode)) }
+}
+
+// ===== 目录接口 =====
+
+/// 创建目录（缺失的上级目录一并创建），路径已存在时返回 EEXIST
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_mk(path: *const c_char) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+
+        let mut f: ext4_file = core::mem::zeroed();
+        let r = ext4_generic_open2(&mut f, path, O_RDONLY, EXT4_DE_UNKNOWN, None, None);
+        if r == EOK {
+            return EEXIST;
+        }
+        if r != ENOENT {
+            return r;
+        }
+        if (*fs).read_only {
+            return EROFS;
+        }
+
+        ext4_block_cache_write_back((*fs).bdev, 1);
+        let r = ext4_undoable(fs, || ext4_generic_open2(&mut f, path, O_CREAT, EXT4_DE_DIR, None, None));
+        let r2 = ext4_block_cache_write_back((*fs).bdev, 0);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 删除目录及其中的全部内容
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_rm(path: *const c_char) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        let mp = ext4_get_mount(path);
+        if mp.is_null() {
+            return ENOENT;
+        }
+        let fs: *mut Ext4Filesystem = &mut (*mp).fs;
+        if (*fs).read_only {
+            return EROFS;
+        }
+
+        let mut f: ext4_file = core::mem::zeroed();
+        let mut parent_inode = 0u32;
+        let mut name_off = 0usize;
+        let r = ext4_generic_open2(
+            &mut f,
+            path,
+            O_RDONLY,
+            EXT4_DE_DIR,
+            Some(&mut parent_inode),
+            Some(&mut name_off),
+        );
+        if r != EOK {
+            return r;
+        }
+        // 挂载点根目录
+        if parent_inode == 0 {
+            return EINVAL;
+        }
+
+        ext4_block_cache_write_back((*fs).bdev, 1);
+        let mut r = ext4_dir_rm_content(fs, f.inode);
+        if r == EOK {
+            r = match InodeRef::get(fs, parent_inode) {
+                Ok(mut parent) => {
+                    let r = ext4_remove_name(fs, &mut *parent, f.inode, ext4_path_name(path, name_off));
+                    let r2 = parent.put();
+                    if r != EOK { r } else { r2 }
+                }
+                Err(r) => r,
+            };
+        }
+        let r2 = ext4_block_cache_write_back((*fs).bdev, 0);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+// ===== 目录读取接口 =====
+
+/// 打开目录
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_open(dir: *mut ext4_dir, path: *const c_char) -> i32 {
+    unsafe {
+        let Some(path) = ext4_cstr(path) else {
+            return EINVAL;
+        };
+        if dir.is_null() {
+            return EINVAL;
+        }
+        let r = ext4_generic_open2(&mut (*dir).f, path, O_RDONLY, EXT4_DE_DIR, None, None);
+        (*dir).next_off = 0;
+        r
+    }
+}
+
+/// 关闭目录
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_close(dir: *mut ext4_dir) -> i32 {
+    if dir.is_null() {
+        return EINVAL;
+    }
+    unsafe { ext4_fclose(&mut (*dir).f) }
+}
+
+/// 读取下一个目录项（结束或出错时返回空指针）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_entry_next(dir: *mut ext4_dir) -> *const ext4_direntry {
+    unsafe {
+        if dir.is_null() || (*dir).f.mp.is_null() || (*dir).next_off == EXT4_DIR_ENTRY_OFFSET_TERM {
+            return ptr::null();
+        }
+        let fs: *mut Ext4Filesystem = &mut (*(*dir).f.mp).fs;
+
+        let Ok(mut dir_ref) = InodeRef::get(fs, (*dir).f.inode) else {
+            return ptr::null();
+        };
+
+        let mut de: *const ext4_direntry = ptr::null();
+        let mut it = Ext4DirIterator::new();
+        if ext4_dir_iterator_init(&mut it, &mut *dir_ref, (*dir).next_off) == EOK && !it.curr.is_null() {
+            let curr = it.curr;
+            let out = &mut (*dir).de;
+            let name_len = ext4_dir_en_get_name_len(&(*fs).sb, curr) as usize;
+            out.inode = u32::from_le((*curr).inode);
+            out.entry_length = ext4_dir_en_get_entry_len(curr);
+            out.name_length = name_len as u8;
+            out.inode_type = (*curr).in_.inode_type();
+            ptr::copy_nonoverlapping((*curr).name_mut_ptr(), out.name.as_mut_ptr(), name_len.min(out.name.len()));
+            de = out;
+
+            let r = ext4_dir_iterator_next(&mut it);
+            (*dir).next_off = if r == EOK && !it.curr.is_null() {
+                it.curr_off
+            } else {
+                EXT4_DIR_ENTRY_OFFSET_TERM
+            };
+        }
+
+        ext4_dir_iterator_fini(&mut it);
+        drop(dir_ref);
+        de
+    }
+}
+
+/// 回到目录开头
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ext4_dir_entry_rewind(dir: *mut ext4_dir) {
+    if !dir.is_null() {
+        unsafe { (*dir).next_off = 0 };
+    }
+}
+
+// ===== 调试输出 =====
+
+/// 打开掩码中各子系统的调试输出（需启用 debug-log 特性）
+#[unsafe(no_mangle)]
+pub extern "C" fn ext4_dmask_set(m: u32) {
+    crate::debug::ext4_dmask_set(m);
+}
+
+/// 关闭掩码中各子系统的调试输出
+#[unsafe(no_mangle)]
+pub extern "C" fn ext4_dmask_clr(m: u32) {
+    crate::debug::ext4_dmask_clr(m);
+}
+
+/// 获取已打开调试输出的子系统掩码
+#[unsafe(no_mangle)]
+pub extern "C" fn ext4_dmask_get() -> u32 {
+    crate::debug::ext4_dmask_get()
+}
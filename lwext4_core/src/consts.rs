@@ -12,6 +12,139 @@ pub const EXT4_SUPERBLOCK_SIZE: usize = 1024;
 /// ext4 魔数
 pub const EXT4_SUPERBLOCK_MAGIC: u16 = 0xEF53;
 
+/// 最小/最大块大小
+pub const EXT4_MIN_BLOCK_SIZE: u32 = 1024;
+pub const EXT4_MAX_BLOCK_SIZE: u32 = 65536;
+
+/// 块组描述符大小（非64bit / 64bit）
+pub const EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE: u16 = 32;
+pub const EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE: u16 = 64;
+
+/// Superblock 状态
+pub const EXT4_SUPERBLOCK_STATE_VALID_FS: u16 = 0x0001;
+pub const EXT4_SUPERBLOCK_STATE_ERROR_FS: u16 = 0x0002;
+pub const EXT4_SUPERBLOCK_STATE_ORPHAN_FS: u16 = 0x0004;
+
+/// 出错时的处理方式（errors 字段，对应 errors= 挂载选项）
+pub const EXT4_ERRORS_CONTINUE: u16 = 1;
+pub const EXT4_ERRORS_RO: u16 = 2;
+pub const EXT4_ERRORS_PANIC: u16 = 3;
+
+/// Superblock 杂项标志（flags 字段）
+pub const EXT4_SUPERBLOCK_FLAGS_SIGNED_HASH: u32 = 0x0001;
+pub const EXT4_SUPERBLOCK_FLAGS_UNSIGNED_HASH: u32 = 0x0002;
+pub const EXT4_SUPERBLOCK_FLAGS_TEST_FILESYS: u32 = 0x0004;
+
+/// 默认挂载选项（default_mount_opts 字段）
+pub const EXT4_DEFM_DEBUG: u32 = 0x0001;
+pub const EXT4_DEFM_BSDGROUPS: u32 = 0x0002;
+pub const EXT4_DEFM_XATTR_USER: u32 = 0x0004;
+pub const EXT4_DEFM_ACL: u32 = 0x0008;
+pub const EXT4_DEFM_UID16: u32 = 0x0010;
+pub const EXT4_DEFM_JMODE: u32 = 0x0060;
+pub const EXT4_DEFM_JMODE_DATA: u32 = 0x0020;
+pub const EXT4_DEFM_JMODE_ORDERED: u32 = 0x0040;
+pub const EXT4_DEFM_JMODE_WBACK: u32 = 0x0060;
+pub const EXT4_DEFM_NOBARRIER: u32 = 0x0100;
+pub const EXT4_DEFM_BLOCK_VALIDITY: u32 = 0x0200;
+pub const EXT4_DEFM_DISCARD: u32 = 0x0400;
+pub const EXT4_DEFM_NODELALLOC: u32 = 0x0800;
+/// 已定义的全部默认挂载选项
+pub const EXT4_DEFM_ALL: u32 = EXT4_DEFM_DEBUG
+    | EXT4_DEFM_BSDGROUPS
+    | EXT4_DEFM_XATTR_USER
+    | EXT4_DEFM_ACL
+    | EXT4_DEFM_UID16
+    | EXT4_DEFM_JMODE
+    | EXT4_DEFM_NOBARRIER
+    | EXT4_DEFM_BLOCK_VALIDITY
+    | EXT4_DEFM_DISCARD
+    | EXT4_DEFM_NODELALLOC;
+
+/// 元数据校验算法（checksum_type 字段）
+pub const EXT4_CRC32C_CHKSUM: u8 = 1;
+
+/// 兼容特性（feature_compat）
+pub const EXT4_FCOM_DIR_PREALLOC: u32 = 0x0001;
+pub const EXT4_FCOM_IMAGIC_INODES: u32 = 0x0002;
+pub const EXT4_FCOM_HAS_JOURNAL: u32 = 0x0004;
+pub const EXT4_FCOM_EXT_ATTR: u32 = 0x0008;
+pub const EXT4_FCOM_RESIZE_INODE: u32 = 0x0010;
+pub const EXT4_FCOM_DIR_INDEX: u32 = 0x0020;
+pub const EXT4_FCOM_SPARSE_SUPER2: u32 = 0x0200;
+pub const EXT4_FCOM_FAST_COMMIT: u32 = 0x0400;
+pub const EXT4_FCOM_STABLE_INODES: u32 = 0x0800;
+pub const EXT4_FCOM_ORPHAN_FILE: u32 = 0x1000;
+
+/// 只读兼容特性（feature_ro_compat）
+pub const EXT4_FRO_COM_SPARSE_SUPER: u32 = 0x0001;
+pub const EXT4_FRO_COM_LARGE_FILE: u32 = 0x0002;
+pub const EXT4_FRO_COM_BTREE_DIR: u32 = 0x0004;
+pub const EXT4_FRO_COM_HUGE_FILE: u32 = 0x0008;
+pub const EXT4_FRO_COM_GDT_CSUM: u32 = 0x0010;
+pub const EXT4_FRO_COM_DIR_NLINK: u32 = 0x0020;
+pub const EXT4_FRO_COM_EXTRA_ISIZE: u32 = 0x0040;
+pub const EXT4_FRO_COM_QUOTA: u32 = 0x0100;
+pub const EXT4_FRO_COM_BIGALLOC: u32 = 0x0200;
+pub const EXT4_FRO_COM_METADATA_CSUM: u32 = 0x0400;
+pub const EXT4_FRO_COM_READONLY: u32 = 0x1000;
+pub const EXT4_FRO_COM_PROJECT: u32 = 0x2000;
+pub const EXT4_FRO_COM_VERITY: u32 = 0x8000;
+pub const EXT4_FRO_COM_ORPHAN_PRESENT: u32 = 0x10000;
+
+/// 不兼容特性（feature_incompat）
+pub const EXT4_FINCOM_COMPRESSION: u32 = 0x0001;
+pub const EXT4_FINCOM_FILETYPE: u32 = 0x0002;
+pub const EXT4_FINCOM_RECOVER: u32 = 0x0004;
+pub const EXT4_FINCOM_JOURNAL_DEV: u32 = 0x0008;
+pub const EXT4_FINCOM_META_BG: u32 = 0x0010;
+pub const EXT4_FINCOM_EXTENTS: u32 = 0x0040;
+pub const EXT4_FINCOM_64BIT: u32 = 0x0080;
+pub const EXT4_FINCOM_MMP: u32 = 0x0100;
+pub const EXT4_FINCOM_FLEX_BG: u32 = 0x0200;
+pub const EXT4_FINCOM_EA_INODE: u32 = 0x0400;
+pub const EXT4_FINCOM_DIRDATA: u32 = 0x1000;
+pub const EXT4_FINCOM_CSUM_SEED: u32 = 0x2000;
+pub const EXT4_FINCOM_LARGEDIR: u32 = 0x4000;
+pub const EXT4_FINCOM_INLINE_DATA: u32 = 0x8000;
+pub const EXT4_FINCOM_ENCRYPT: u32 = 0x10000;
+pub const EXT4_FINCOM_CASEFOLD: u32 = 0x20000;
+
+/// 支持的不兼容特性（其余位被置位时拒绝挂载）
+///
+/// 对应C定义: EXT4_SUPPORTED_FINCOM | EXT4_FINCOM_IGNORED (ext4_types.h)。
+/// MMP 与C实现相同被忽略；RECOVER 由日志模块在挂载后回放。
+/// ENCRYPT 只影响设置了加密标志的 inode，读写这些 inode 时由上层拒绝。
+/// LARGEDIR 增加 htree 的层数，启用时索引目录的查找和添加目录项最多使用三层索引。
+pub const EXT4_SUPPORTED_FINCOM: u32 = EXT4_FINCOM_FILETYPE
+    | EXT4_FINCOM_RECOVER
+    | EXT4_FINCOM_META_BG
+    | EXT4_FINCOM_EXTENTS
+    | EXT4_FINCOM_64BIT
+    | EXT4_FINCOM_MMP
+    | EXT4_FINCOM_FLEX_BG
+    | EXT4_FINCOM_CSUM_SEED
+    | EXT4_FINCOM_LARGEDIR
+    | EXT4_FINCOM_ENCRYPT;
+
+/// 支持写入的只读兼容特性（其余位被置位时只能只读挂载）
+///
+/// 对应C定义: EXT4_SUPPORTED_FRO_COM (ext4_types.h)。VERITY 文件由上层拒绝修改。
+pub const EXT4_SUPPORTED_FRO_COM: u32 = EXT4_FRO_COM_SPARSE_SUPER
+    | EXT4_FRO_COM_LARGE_FILE
+    | EXT4_FRO_COM_BTREE_DIR
+    | EXT4_FRO_COM_HUGE_FILE
+    | EXT4_FRO_COM_GDT_CSUM
+    | EXT4_FRO_COM_DIR_NLINK
+    | EXT4_FRO_COM_EXTRA_ISIZE
+    | EXT4_FRO_COM_METADATA_CSUM
+    | EXT4_FRO_COM_VERITY;
+
+/// 块组标志
+pub const EXT4_BLOCK_GROUP_INODE_UNINIT: u16 = 0x0001;
+pub const EXT4_BLOCK_GROUP_BLOCK_UNINIT: u16 = 0x0002;
+pub const EXT4_BLOCK_GROUP_ITABLE_ZEROED: u16 = 0x0004;
+
 /// Inode 结构中的块指针数量（12个直接块 + 1个间接块 + 1个二级间接块 + 1个三级间接块）
 pub const EXT4_INODE_BLOCKS: usize = 15;
 
@@ -21,8 +154,108 @@ pub const EXT4_INODE_DIRECT_BLOCKS: usize = 12;
 /// 块设备缓存大小（缓存的块数量）
 pub const CONFIG_BLOCK_DEV_CACHE_SIZE: u32 = 8;
 
+/// extent 状态缓存的最大映射条目数（所有 inode 合计）
+pub const CONFIG_EXTENT_STATUS_CACHE_SIZE: usize = 256;
+
+/// 内存中保存分配目标的 inode 数上限（超出时丢弃编号最小的 inode 的目标）
+pub const CONFIG_ALLOC_GOAL_CACHE_SIZE: usize = 4096;
+
+/// 根目录 inode 编号
+pub const EXT4_INODE_ROOT_INDEX: u32 = 2;
+
+/// 日志 inode 编号
+pub const EXT4_INODE_JOURNAL_INDEX: u32 = 8;
+
+/// superblock 中 journal_blocks 保存的是日志 inode 的块映射（journal_backup_type）
+pub const EXT4_JNL_BACKUP_BLOCKS: u8 = 1;
+
+/// 旧版本（rev 0）的 inode 大小与第一个非保留 inode
+pub const EXT4_GOOD_OLD_INODE_SIZE: u16 = 128;
+pub const EXT4_GOOD_OLD_FIRST_INO: u32 = 11;
+/// 支持的最高版本级别（动态 inode 大小）
+pub const EXT4_DYNAMIC_REV: u32 = 1;
+
+/// i_blocks 字段的计数单位（字节）
+pub const EXT4_INODE_BLOCK_SIZE: u32 = 512;
+
+/// 硬链接数上限
+pub const EXT4_LINK_MAX: u16 = 65000;
+
+/// Inode flags
+pub const EXT4_INODE_FLAG_SECRM: u32 = 0x00000001;
+pub const EXT4_INODE_FLAG_UNRM: u32 = 0x00000002;
+pub const EXT4_INODE_FLAG_COMPR: u32 = 0x00000004;
+pub const EXT4_INODE_FLAG_SYNC: u32 = 0x00000008;
+pub const EXT4_INODE_FLAG_IMMUTABLE: u32 = 0x00000010;
+pub const EXT4_INODE_FLAG_APPEND: u32 = 0x00000020;
+pub const EXT4_INODE_FLAG_NODUMP: u32 = 0x00000040;
+pub const EXT4_INODE_FLAG_NOATIME: u32 = 0x00000080;
+pub const EXT4_INODE_FLAG_ENCRYPT: u32 = 0x00000800;
+pub const EXT4_INODE_FLAG_INDEX: u32 = 0x00001000;
+pub const EXT4_INODE_FLAG_IMAGIC: u32 = 0x00002000;
+pub const EXT4_INODE_FLAG_JOURNAL_DATA: u32 = 0x00004000;
+pub const EXT4_INODE_FLAG_HUGE_FILE: u32 = 0x00040000;
 /// Inode flags: 使用 extent 树
 pub const EXT4_INODE_FLAG_EXTENTS: u32 = 0x80000;
+pub const EXT4_INODE_FLAG_VERITY: u32 = 0x00100000;
+pub const EXT4_INODE_FLAG_EA_INODE: u32 = 0x00200000;
+pub const EXT4_INODE_FLAG_INLINE_DATA: u32 = 0x10000000;
+/// Inode flags: 目录按大小写不敏感方式查找（casefold）
+pub const EXT4_INODE_FLAG_CASEFOLD: u32 = 0x40000000;
+
+/// 扩展属性块（以及 inode 内扩展属性区）的魔数
+pub const EXT4_XATTR_MAGIC: u32 = 0xEA020000;
+
+/// 扩展属性名称前缀索引（对应C定义: EXT4_XATTR_INDEX_* (ext4_xattr.h)）
+pub const EXT4_XATTR_INDEX_USER: u8 = 1;
+pub const EXT4_XATTR_INDEX_POSIX_ACL_ACCESS: u8 = 2;
+pub const EXT4_XATTR_INDEX_POSIX_ACL_DEFAULT: u8 = 3;
+pub const EXT4_XATTR_INDEX_TRUSTED: u8 = 4;
+pub const EXT4_XATTR_INDEX_LUSTRE: u8 = 5;
+pub const EXT4_XATTR_INDEX_SECURITY: u8 = 6;
+pub const EXT4_XATTR_INDEX_SYSTEM: u8 = 7;
+pub const EXT4_XATTR_INDEX_RICHACL: u8 = 8;
+pub const EXT4_XATTR_INDEX_ENCRYPTION: u8 = 9;
+
+/// htree 目录哈希版本
+pub const EXT2_HTREE_LEGACY: u32 = 0;
+pub const EXT2_HTREE_HALF_MD4: u32 = 1;
+pub const EXT2_HTREE_TEA: u32 = 2;
+pub const EXT2_HTREE_LEGACY_UNSIGNED: u32 = 3;
+pub const EXT2_HTREE_HALF_MD4_UNSIGNED: u32 = 4;
+pub const EXT2_HTREE_TEA_UNSIGNED: u32 = 5;
+/// htree 哈希的结束标记
+pub const EXT2_HTREE_EOF: u32 = 0x7FFF_FFFF;
+
+/// Extent 树
+pub const EXT4_EXTENT_MAGIC: u16 = 0xF30A;
+/// 已初始化 extent 的最大长度
+pub const EXT4_EXT_MAX_LEN_INIT: u32 = 1 << 15;
+/// 未写入（unwritten）extent 的最大长度
+pub const EXT4_EXT_MAX_LEN_UNWRITTEN: u32 = (1 << 15) - 1;
+
+/// 目录项名称最大长度
+pub const EXT4_DIRECTORY_FILENAME_LEN: usize = 255;
+/// 目录块校验和尾部的伪目录项类型
+pub const EXT4_DIRENTRY_DIR_CSUM: u8 = 0xDE;
+
+/// 哈希位置偏移的标志位（线性目录的字节偏移不会设置该位）
+pub const EXT4_DIR_HASH_POS_FLAG: u64 = 1 << 63;
+/// 按哈希位置迭代时的目录末尾
+pub const EXT4_DIR_HASH_POS_EOF: u64 = u64::MAX;
+
+/// crc32c 初始值
+pub const EXT4_CRC32_INIT: u32 = 0xFFFFFFFF;
+
+/// 块缓存标志位（位序号）
+pub const BC_UPTODATE: i32 = 0;
+pub const BC_DIRTY: i32 = 1;
+pub const BC_FLUSH: i32 = 2;
+pub const BC_TMP: i32 = 3;
+/// 常驻缓存，不进入LRU（lwext4 中没有对应标志）
+pub const BC_PINNED: i32 = 4;
+/// 推迟到同步缓存时回写，逐个操作的回写和脏块限额回写都跳过（块组描述符，lwext4 中没有对应标志）
+pub const BC_DEFER: i32 = 5;
 
 /// 目录项类型常量
 pub const EXT4_DE_UNKNOWN: u32 = 0;
@@ -36,14 +269,33 @@ pub const EXT4_DE_SYMLINK: u32 = 7;
 
 /// 错误码（兼容 C errno）
 pub const EOK: i32 = 0;
+pub const EPERM: i32 = 1;
 pub const EINVAL: i32 = 22;
 pub const EIO: i32 = 5;
+pub const ENXIO: i32 = 6;
 pub const ENOMEM: i32 = 12;
 pub const ENOENT: i32 = 2;
+pub const EEXIST: i32 = 17;
+pub const EXDEV: i32 = 18;
+pub const ENODEV: i32 = 19;
+pub const ENOTDIR: i32 = 20;
+pub const EFBIG: i32 = 27;
 pub const ENOSPC: i32 = 28;
+pub const EROFS: i32 = 30;
+pub const ERANGE: i32 = 34;
+pub const ENAMETOOLONG: i32 = 36;
+pub const ENODATA: i32 = 61;
 pub const ENOTSUP: i32 = 95;
 pub const EISDIR: i32 = 21;
 pub const ENOTEMPTY: i32 = 39;
+pub const EBUSY: i32 = 16;
+pub const EMLINK: i32 = 31;
+pub const ETIMEDOUT: i32 = 110;
+pub const ESTALE: i32 = 116;
+/// 磁盘上的结构已损坏（Linux 的 EUCLEAN）
+pub const EFSCORRUPTED: i32 = 117;
+pub const ECANCELED: i32 = 125;
+pub const ENOKEY: i32 = 126;
 
 /// Inode 模式位
 pub const EXT4_INODE_MODE_FIFO: u16 = 0x1000;
@@ -0,0 +1,81 @@
+//! 校验和算法模块
+//!
+//! 对应C实现: ext4_crc32.c
+//! metadata_csum 特性使用 crc32c（Castagnoli，反射多项式 0x82F63B78），
+//! 与Linux内核一致：不做最终取反，初始值由调用者给出。
+//! 未启用 metadata_csum 的 gdt_csum（uninit_bg）文件系统用 crc16 校验块组描述符。
+
+use crate::consts::*;
+use crate::Ext4Superblock;
+
+/// 生成 crc32c 查找表
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+/// 计算 crc32c
+pub fn ext4_crc32c(crc: u32, buf: &[u8]) -> u32 {
+    let mut crc = crc;
+    for &b in buf {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// 生成 crc16 查找表（ANSI，反射多项式 0xA001）
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC16_TABLE: [u16; 256] = crc16_table();
+
+/// 计算 crc16（gdt_csum 特性的块组描述符校验和，与内核 lib/crc16.c 相同）
+pub fn ext4_crc16(crc: u16, buf: &[u8]) -> u16 {
+    let mut crc = crc;
+    for &b in buf {
+        crc = (crc >> 8) ^ CRC16_TABLE[((crc ^ b as u16) & 0xFF) as usize];
+    }
+    crc
+}
+
+/// 元数据校验和的初始种子
+///
+/// 启用 csum_seed 特性时使用 superblock 中预先计算的种子，
+/// 否则为 crc32c(~0, uuid)。
+pub fn ext4_sb_csum_seed(sb: &Ext4Superblock) -> u32 {
+    if u32::from_le(sb.feature_incompat) & EXT4_FINCOM_CSUM_SEED != 0 {
+        u32::from_le(sb.checksum_seed)
+    } else {
+        ext4_crc32c(EXT4_CRC32_INIT, &sb.uuid)
+    }
+}
@@ -0,0 +1,165 @@
+//! 调试输出模块
+//!
+//! 对应C实现: ext4_debug.h / ext4_debug.c
+//!
+//! 启用 `debug-log` 特性后，各子系统通过 log 门面输出调试信息，target 为
+//! `lwext4::<子系统>`。每个子系统有独立的输出级别（默认只输出 Warn 及以上），
+//! 可在运行时通过 `ext4_dmask_set` / `ext4_dbg_level_set` 调整。
+//! 未启用该特性时调试宏展开为空，不产生任何运行开销。
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use log::{Level, LevelFilter};
+
+/// 子系统掩码（与 lwext4 的 DEBUG_* 取值相同）
+pub const DEBUG_BALLOC: u32 = 1 << 0;
+pub const DEBUG_BCACHE: u32 = 1 << 1;
+pub const DEBUG_BITMAP: u32 = 1 << 2;
+pub const DEBUG_BLOCK_GROUP: u32 = 1 << 3;
+pub const DEBUG_BLOCKDEV: u32 = 1 << 4;
+pub const DEBUG_DIR_IDX: u32 = 1 << 5;
+pub const DEBUG_DIR: u32 = 1 << 6;
+pub const DEBUG_EXTENT: u32 = 1 << 7;
+pub const DEBUG_FS: u32 = 1 << 8;
+pub const DEBUG_HASH: u32 = 1 << 9;
+pub const DEBUG_IALLOC: u32 = 1 << 10;
+pub const DEBUG_INODE: u32 = 1 << 11;
+pub const DEBUG_SUPER: u32 = 1 << 12;
+pub const DEBUG_XATTR: u32 = 1 << 13;
+pub const DEBUG_MKFS: u32 = 1 << 14;
+pub const DEBUG_EXT4: u32 = 1 << 15;
+pub const DEBUG_JBD: u32 = 1 << 16;
+pub const DEBUG_MBR: u32 = 1 << 17;
+pub const DEBUG_ALL: u32 = 0xFFFF_FFFF;
+
+/// 子系统数量
+const DEBUG_SUBSYS_COUNT: usize = 18;
+
+/// 各子系统的 log target
+const TARGETS: [&str; DEBUG_SUBSYS_COUNT] = [
+    "lwext4::balloc",
+    "lwext4::bcache",
+    "lwext4::bitmap",
+    "lwext4::block_group",
+    "lwext4::blockdev",
+    "lwext4::dir_idx",
+    "lwext4::dir",
+    "lwext4::extent",
+    "lwext4::fs",
+    "lwext4::hash",
+    "lwext4::ialloc",
+    "lwext4::inode",
+    "lwext4::super",
+    "lwext4::xattr",
+    "lwext4::mkfs",
+    "lwext4::ext4",
+    "lwext4::jbd",
+    "lwext4::mbr",
+];
+
+/// 子系统的默认输出级别
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+
+/// 各子系统当前的输出级别（LevelFilter 的数值）
+static LEVELS: [AtomicU8; DEBUG_SUBSYS_COUNT] =
+    [const { AtomicU8::new(DEFAULT_LEVEL as u8) }; DEBUG_SUBSYS_COUNT];
+
+/// 将数值还原为 LevelFilter
+fn level_filter(v: u8) -> LevelFilter {
+    LevelFilter::iter().nth(v as usize).unwrap_or(LevelFilter::Trace)
+}
+
+/// 设置掩码中各子系统的输出级别
+pub fn ext4_dbg_level_set(m: u32, level: LevelFilter) {
+    for (i, l) in LEVELS.iter().enumerate() {
+        if m & (1 << i) != 0 {
+            l.store(level as u8, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 获取子系统的输出级别（掩码含多个子系统时取编号最小的一个）
+pub fn ext4_dbg_level_get(subsys: u32) -> LevelFilter {
+    match LEVELS.get(subsys.trailing_zeros() as usize) {
+        Some(l) => level_filter(l.load(Ordering::Relaxed)),
+        None => LevelFilter::Off,
+    }
+}
+
+/// 打开掩码中各子系统的全部调试输出
+pub fn ext4_dmask_set(m: u32) {
+    ext4_dbg_level_set(m, LevelFilter::Trace);
+}
+
+/// 将掩码中各子系统恢复为默认级别
+pub fn ext4_dmask_clr(m: u32) {
+    ext4_dbg_level_set(m, DEFAULT_LEVEL);
+}
+
+/// 获取调试输出级别高于默认级别的子系统掩码
+pub fn ext4_dmask_get() -> u32 {
+    LEVELS.iter().enumerate().fold(0, |m, (i, l)| {
+        if level_filter(l.load(Ordering::Relaxed)) > DEFAULT_LEVEL {
+            m | (1 << i)
+        } else {
+            m
+        }
+    })
+}
+
+/// 子系统是否输出指定级别的信息
+pub fn ext4_dbg_enabled(subsys: u32, level: Level) -> bool {
+    level <= ext4_dbg_level_get(subsys)
+}
+
+/// 子系统对应的 log target
+pub fn ext4_dbg_target(subsys: u32) -> &'static str {
+    TARGETS.get(subsys.trailing_zeros() as usize).copied().unwrap_or("lwext4")
+}
+
+/// 输出一条调试信息：`ext4_dbg!(DEBUG_BALLOC, Debug, "goal={}", goal)`
+macro_rules! ext4_dbg {
+    ($subsys:expr, $lvl:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "debug-log")]
+        if $crate::debug::ext4_dbg_enabled($subsys, log::Level::$lvl) {
+            log::log!(target: $crate::debug::ext4_dbg_target($subsys), log::Level::$lvl, $($arg)+);
+        }
+        #[cfg(not(feature = "debug-log"))]
+        if false {
+            let _ = ($subsys, format_args!($($arg)+));
+        }
+    }};
+}
+
+/// 调试作用域：创建时输出进入信息，离开作用域时输出退出信息（均为 Debug 级别）
+///
+/// `let _span = ext4_dbg_span!(DEBUG_FS, "ext4_fs_init", "read_only={}", read_only);`
+macro_rules! ext4_dbg_span {
+    ($subsys:expr, $name:expr) => {{
+        ext4_dbg!($subsys, Debug, "-> {}", $name);
+        $crate::debug::Ext4DbgSpan::new($subsys, $name)
+    }};
+    ($subsys:expr, $name:expr, $($arg:tt)+) => {{
+        ext4_dbg!($subsys, Debug, "-> {}: {}", $name, format_args!($($arg)+));
+        $crate::debug::Ext4DbgSpan::new($subsys, $name)
+    }};
+}
+
+/// 调试作用域的守卫，见 `ext4_dbg_span!`
+#[must_use]
+pub struct Ext4DbgSpan {
+    subsys: u32,
+    name: &'static str,
+}
+
+impl Ext4DbgSpan {
+    pub fn new(subsys: u32, name: &'static str) -> Self {
+        Self { subsys, name }
+    }
+}
+
+impl Drop for Ext4DbgSpan {
+    fn drop(&mut self) {
+        ext4_dbg!(self.subsys, Debug, "<- {}", self.name);
+    }
+}
@@ -1,77 +1,1338 @@
 //! 目录操作模块
+//!
+//! 对应C实现: ext4_dir.c（线性目录，htree 索引的查找和插入见 dir_idx 模块）
+//!
+//! 线性目录的迭代偏移是目录项的字节偏移。htree 目录与 Linux 内核一样按文件名哈希
+//! 给出迭代偏移（设置 EXT4_DIR_HASH_POS_FLAG），添加、删除目录项不会改变其他目录项的
+//! 偏移，长时间的 readdir 不会因目录修改而跳过或重复目录项。
 
-use log::debug;
-use crate::{Ext4InodeRef, Ext4DirIterator, Ext4DirEntry, Ext4DirSearchResult};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use alloc::collections::VecDeque;
+use core::mem::size_of;
+use core::ptr;
+
+
+use crate::block::*;
+use crate::crc::ext4_crc32c;
+use crate::hash::ext2_htree_hash;
+use crate::inode::*;
+use crate::superblock::*;
 use crate::consts::*;
+use crate::debug::*;
+use crate::dir_idx::*;
+use crate::tune::ext4_tune_for_each_inode;
+use crate::{
+    Ext4Block, Ext4DirEntry, Ext4DirEntryTail, Ext4DirIterator, Ext4DirSearchResult, Ext4Filesystem,
+    Ext4Inode, Ext4InodeRef, Ext4Superblock, EXT4_DIR_ENTRY_HEADER_SIZE,
+};
+
+// ===== 目录项字段访问 =====
+
+/// 获取目录项记录长度
+pub unsafe fn ext4_dir_en_get_entry_len(de: *const Ext4DirEntry) -> u16 {
+    unsafe { u16::from_le((*de).entry_len) }
+}
+
+/// 设置目录项记录长度
+pub unsafe fn ext4_dir_en_set_entry_len(de: *mut Ext4DirEntry, len: u16) {
+    unsafe { (*de).entry_len = len.to_le(); }
+}
+
+/// 获取目录项名称长度（旧版本包含高8位）
+pub unsafe fn ext4_dir_en_get_name_len(sb: &Ext4Superblock, de: *const Ext4DirEntry) -> u16 {
+    unsafe {
+        let mut len = (*de).name_len as u16;
+        if ext4_dir_old_version(sb) {
+            len |= ((*de).in_.name_length_high() as u16) << 8;
+        }
+        len
+    }
+}
+
+/// 设置目录项名称长度
+pub unsafe fn ext4_dir_en_set_name_len(sb: &Ext4Superblock, de: *mut Ext4DirEntry, len: u16) {
+    unsafe {
+        (*de).name_len = len as u8;
+        if ext4_dir_old_version(sb) {
+            (*de).in_.set_name_length_high((len >> 8) as u8);
+        }
+    }
+}
+
+/// 设置目录项 inode 类型（旧版本无类型字段；未启用 filetype 特性时类型字节写为0）
+pub unsafe fn ext4_dir_en_set_inode_type(sb: &Ext4Superblock, de: *mut Ext4DirEntry, ty: u8) {
+    unsafe {
+        if !ext4_dir_old_version(sb) {
+            let ty = if ext4_sb_feature_incom(sb, EXT4_FINCOM_FILETYPE) { ty } else { EXT4_DE_UNKNOWN as u8 };
+            (*de).in_.set_inode_type(ty);
+        }
+    }
+}
+
+/// 由 inode 的文件类型得到目录项的类型字节
+pub unsafe fn ext4_dir_entry_type(sb: &Ext4Superblock, inode: *const Ext4Inode) -> u8 {
+    unsafe {
+        (match ext4_inode_type(sb, inode) as u16 {
+            EXT4_INODE_MODE_DIRECTORY => EXT4_DE_DIR,
+            EXT4_INODE_MODE_FILE => EXT4_DE_REG_FILE,
+            EXT4_INODE_MODE_SOFTLINK => EXT4_DE_SYMLINK,
+            EXT4_INODE_MODE_CHARDEV => EXT4_DE_CHRDEV,
+            EXT4_INODE_MODE_BLOCKDEV => EXT4_DE_BLKDEV,
+            EXT4_INODE_MODE_FIFO => EXT4_DE_FIFO,
+            EXT4_INODE_MODE_SOCKET => EXT4_DE_SOCK,
+            _ => EXT4_DE_UNKNOWN,
+        }) as u8
+    }
+}
+
+/// rev 0 且 minor < 5 的文件系统目录项不含类型字段
+pub(crate) fn ext4_dir_old_version(sb: &Ext4Superblock) -> bool {
+    u32::from_le(sb.rev_level) == 0 && u16::from_le(sb.minor_rev_level) < 5
+}
+
+/// 目录项实际占用的长度（4字节对齐）
+pub(crate) fn ext4_dir_entry_used_len(name_len: u32) -> u32 {
+    (EXT4_DIR_ENTRY_HEADER_SIZE as u32 + name_len).next_multiple_of(4)
+}
+
+// ===== 目录块校验和 =====
+
+/// 获取目录块尾部（不存在时返回空指针）
+pub unsafe fn ext4_dir_get_tail(inode_ref: *mut Ext4InodeRef, de: *mut Ext4DirEntry) -> *mut Ext4DirEntryTail {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(&(*(*inode_ref).fs).sb) as usize;
+        let t = (de as *mut u8).add(block_size - size_of::<Ext4DirEntryTail>()) as *mut Ext4DirEntryTail;
+        if (*t).reserved_zero1 != 0 || (*t).reserved_zero2 != 0 {
+            return ptr::null_mut();
+        }
+        if u16::from_le((*t).rec_len) as usize != size_of::<Ext4DirEntryTail>() {
+            return ptr::null_mut();
+        }
+        if (*t).reserved_ft != EXT4_DIRENTRY_DIR_CSUM {
+            return ptr::null_mut();
+        }
+        t
+    }
+}
+
+/// 初始化目录块尾部
+pub unsafe fn ext4_dir_init_entry_tail(t: *mut Ext4DirEntryTail) {
+    unsafe {
+        ptr::write_bytes(t, 0, 1);
+        (*t).rec_len = (size_of::<Ext4DirEntryTail>() as u16).to_le();
+        (*t).reserved_ft = EXT4_DIRENTRY_DIR_CSUM;
+    }
+}
+
+/// 计算目录块校验和
+unsafe fn ext4_dir_csum(inode_ref: *mut Ext4InodeRef, dirent: *const u8, size: usize) -> u32 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let seed = ext4_inode_csum_seed(sb, (*inode_ref).index, ext4_inode_get_generation((*inode_ref).inode));
+        ext4_crc32c(seed, core::slice::from_raw_parts(dirent, size))
+    }
+}
+
+/// 校验目录块校验和
+pub unsafe fn ext4_dir_csum_verify(inode_ref: *mut Ext4InodeRef, dirent: *mut Ext4DirEntry) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return true;
+        }
+        let t = ext4_dir_get_tail(inode_ref, dirent);
+        if t.is_null() {
+            // htree 索引块按 dx_tail 校验，其他没有尾部的块不校验
+            if ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX) {
+                return ext4_dir_dx_csum_verify(inode_ref, dirent as *const u8).unwrap_or(true);
+            }
+            return true;
+        }
+        let size = t as usize - dirent as usize;
+        u32::from_le((*t).checksum) == ext4_dir_csum(inode_ref, dirent as *const u8, size)
+    }
+}
+
+/// 更新目录块校验和
+pub unsafe fn ext4_dir_set_csum(inode_ref: *mut Ext4InodeRef, dirent: *mut Ext4DirEntry) {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return;
+        }
+        let t = ext4_dir_get_tail(inode_ref, dirent);
+        if t.is_null() {
+            if ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX)
+                && ext4_dir_dx_set_csum(inode_ref, dirent as *mut u8)
+            {
+                return;
+            }
+            ext4_dbg!(DEBUG_DIR, Warn, "no space for directory leaf checksum: inode {}", (*inode_ref).index);
+            return;
+        }
+        let size = t as usize - dirent as usize;
+        (*t).checksum = ext4_dir_csum(inode_ref, dirent as *const u8, size).to_le();
+    }
+}
+
+/// 为目录块添加校验和尾部（已有尾部时只更新校验和）
+///
+/// 尾部占用块中最后一个目录项的空闲空间，空间不足时返回 ENOSPC。
+unsafe fn ext4_dir_block_add_tail(inode_ref: *mut Ext4InodeRef, data: *mut u8, check_only: bool) -> i32 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let tail_size = size_of::<Ext4DirEntryTail>();
+        let first = data as *mut Ext4DirEntry;
+        if ext4_dir_get_tail(inode_ref, first).is_null() {
+            let mut off = 0;
+            let mut last = first;
+            while off < block_size {
+                let de = data.add(off) as *mut Ext4DirEntry;
+                if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                    ext4_dbg!(DEBUG_DIR, Warn, "bad directory entry: inode {}, offset {}", (*inode_ref).index, off);
+                    return EIO;
+                }
+                last = de;
+                off += ext4_dir_en_get_entry_len(de) as usize;
+            }
+            let used = ext4_dir_entry_used_len(ext4_dir_en_get_name_len(sb, last) as u32) as usize;
+            let entry_len = ext4_dir_en_get_entry_len(last) as usize;
+            if entry_len < used + tail_size {
+                return ENOSPC;
+            }
+            if check_only {
+                return EOK;
+            }
+            ext4_dir_en_set_entry_len(last, (entry_len - tail_size) as u16);
+            ext4_dir_init_entry_tail(data.add(block_size - tail_size) as *mut Ext4DirEntryTail);
+        } else if check_only {
+            return EOK;
+        }
+        ext4_dir_set_csum(inode_ref, first);
+        EOK
+    }
+}
+
+/// 把目录项（原样的字节，长度为占用长度）依次紧凑地写入目录块
+///
+/// 最后一个目录项延伸到块末尾，启用 metadata_csum 时添加校验和尾部。entries 不能为空。
+pub(crate) unsafe fn ext4_dir_write_leaf(inode_ref: *mut Ext4InodeRef, dst: *mut u8, entries: &[&[u8]]) {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let has_csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM);
+        let usable = if has_csum { block_size - size_of::<Ext4DirEntryTail>() } else { block_size };
+
+        ptr::write_bytes(dst, 0, block_size);
+        let mut off = 0;
+        let mut last = ptr::null_mut();
+        for entry in entries {
+            ptr::copy_nonoverlapping(entry.as_ptr(), dst.add(off), entry.len());
+            last = dst.add(off) as *mut Ext4DirEntry;
+            ext4_dir_en_set_entry_len(last, entry.len() as u16);
+            off += entry.len();
+        }
+        let last_len = ext4_dir_en_get_entry_len(last) as usize;
+        ext4_dir_en_set_entry_len(last, (last_len + usable - off) as u16);
+        if has_csum {
+            ext4_dir_init_entry_tail(dst.add(usable) as *mut Ext4DirEntryTail);
+            ext4_dir_set_csum(inode_ref, dst as *mut Ext4DirEntry);
+        }
+    }
+}
+
+/// 把目录项依次紧凑地放入尽量少的目录块，返回各块的（第一个目录项的序号, 内容）
+pub(crate) unsafe fn ext4_dir_pack_blocks(inode_ref: *mut Ext4InodeRef, entries: &[&[u8]]) -> Vec<(usize, Vec<u8>)> {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let usable = if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            block_size - size_of::<Ext4DirEntryTail>()
+        } else {
+            block_size
+        };
+        let mut blocks = Vec::new();
+        let mut first = 0;
+        let mut used = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            if used + entry.len() > usable && i > first {
+                let mut block = vec![0u8; block_size];
+                ext4_dir_write_leaf(inode_ref, block.as_mut_ptr(), &entries[first..i]);
+                blocks.push((first, block));
+                first = i;
+                used = 0;
+            }
+            used += entry.len();
+        }
+        if first < entries.len() {
+            let mut block = vec![0u8; block_size];
+            ext4_dir_write_leaf(inode_ref, block.as_mut_ptr(), &entries[first..]);
+            blocks.push((first, block));
+        }
+        blocks
+    }
+}
+
+/// 为目录的全部块添加校验和尾部并更新校验和（启用 metadata_csum 时使用）
+///
+/// check_only 为真时只检查每个块是否留有尾部所需的空间，不修改目录。
+/// htree 索引节点按线性目录块处理，调用者需先清除目录的索引标志。
+pub unsafe fn ext4_dir_csum_init(inode_ref: *mut Ext4InodeRef, check_only: bool) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let total_blocks = (ext4_inode_get_size(sb, (*inode_ref).inode) / block_size as u64) as u32;
+
+        for iblock in 0..total_blocks {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(inode_ref, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_dir_block_add_tail(inode_ref, b.data, check_only);
+            if r == EOK && !check_only {
+                ext4_block_set_dirty(&mut b);
+            }
+            let r2 = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                ext4_dbg!(DEBUG_DIR, Debug, "ext4_dir_csum_init: inode {}, block {}: {}", (*inode_ref).index, iblock, r);
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// 检查目录中各目录项的类型字节是否与目标 inode 的文件类型一致
+///
+/// 未启用 filetype 特性时类型字节应为0；旧版本目录项没有类型字段，不做检查。
+/// 对每个不一致的目录项调用 f(目录项, 应有的类型)；fix 为真时就地改正并更新目录块校验和，
+/// 但 htree 根块中的 "." 和 ".." 只报告不改正（该块的校验和格式不同）。
+/// 指向无效或未使用 inode 的目录项被跳过。
+pub unsafe fn ext4_dir_check_types(
+    inode_ref: *mut Ext4InodeRef,
+    fix: bool,
+    f: &mut dyn FnMut(*const Ext4DirEntry, u8),
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        if ext4_dir_old_version(sb) {
+            return EOK;
+        }
+        let has_type = ext4_sb_feature_incom(sb, EXT4_FINCOM_FILETYPE);
+        let inodes_count = u32::from_le(sb.inodes_count);
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let total_blocks = ext4_inode_get_size(sb, (*inode_ref).inode).div_ceil(block_size as u64) as u32;
+        let indexed = ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX);
+
+        for iblock in 0..total_blocks {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(inode_ref, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            let mut changed = false;
+            let mut off = 0usize;
+            while off + EXT4_DIR_ENTRY_HEADER_SIZE <= block_size {
+                let de = b.data.add(off) as *mut Ext4DirEntry;
+                if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                    break;
+                }
+                off += ext4_dir_en_get_entry_len(de) as usize;
+                let ino = u32::from_le((*de).inode);
+                if ino == 0 || ino > inodes_count {
+                    continue;
+                }
+                let Ok(child) = InodeRef::get(fs, ino) else {
+                    continue;
+                };
+                let known = ext4_inode_type(sb, child.inode) != 0;
+                let expected = if has_type { ext4_dir_entry_type(sb, child.inode) } else { EXT4_DE_UNKNOWN as u8 };
+                let r = child.put();
+                if r != EOK {
+                    ext4_block_set((*fs).bdev, &mut b);
+                    return r;
+                }
+                if !known || (*de).in_.inode_type() == expected {
+                    continue;
+                }
+                f(de, expected);
+                if fix && !(indexed && iblock == 0) {
+                    (*de).in_.set_inode_type(expected);
+                    changed = true;
+                }
+            }
+            if changed {
+                ext4_dir_set_csum(inode_ref, b.data as *mut Ext4DirEntry);
+                ext4_block_set_dirty(&mut b);
+            }
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+/// 对文件系统中的每个目录执行 ext4_dir_check_types，f 的第一个参数为目录的 inode 号
+///
+/// 检查每个目录之前调用 visit(目录 inode 号)，返回非 EOK 时中止并返回该值。
+pub unsafe fn ext4_fs_check_dir_types(
+    fs: *mut Ext4Filesystem,
+    fix: bool,
+    visit: &mut dyn FnMut(u32) -> i32,
+    f: &mut dyn FnMut(u32, *const Ext4DirEntry, u8),
+) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        ext4_tune_for_each_inode(fs, &mut |inode_ref| {
+            if !ext4_inode_is_type(sb, (*inode_ref).inode, EXT4_INODE_MODE_DIRECTORY) {
+                return EOK;
+            }
+            let dir = (*inode_ref).index;
+            let r = visit(dir);
+            if r != EOK {
+                return r;
+            }
+            ext4_dir_check_types(inode_ref, fix, &mut |de, ty| f(dir, de, ty))
+        })
+    }
+}
+
+/// 目录块中可用于目录项的长度（不含校验和尾部）
+pub(crate) unsafe fn ext4_dir_block_usable_len(inode_ref: *mut Ext4InodeRef, data: *mut u8) -> usize {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(&(*(*inode_ref).fs).sb) as usize;
+        if ext4_sb_feature_ro_com(&(*(*inode_ref).fs).sb, EXT4_FRO_COM_METADATA_CSUM)
+            && !ext4_dir_get_tail(inode_ref, data as *mut Ext4DirEntry).is_null()
+        {
+            block_size - size_of::<Ext4DirEntryTail>()
+        } else {
+            block_size
+        }
+    }
+}
+
+// ===== 目录迭代器 =====
+
+/// 检查目录项是否合法
+pub(crate) unsafe fn ext4_dir_entry_is_valid(
+    sb: &Ext4Superblock,
+    de: *const Ext4DirEntry,
+    offset_in_block: usize,
+    block_size: usize,
+) -> bool {
+    unsafe {
+        let entry_len = ext4_dir_en_get_entry_len(de) as usize;
+        entry_len >= EXT4_DIR_ENTRY_HEADER_SIZE
+            && entry_len.is_multiple_of(4)
+            && offset_in_block + entry_len <= block_size
+            && EXT4_DIR_ENTRY_HEADER_SIZE + ext4_dir_en_get_name_len(sb, de) as usize <= entry_len
+    }
+}
+
+/// 将迭代器定位到指定偏移（必须位于目录项边界）
+unsafe fn ext4_dir_iterator_seek(it: *mut Ext4DirIterator, pos: u64) -> i32 {
+    unsafe {
+        let inode_ref = (*it).inode_ref;
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let size = ext4_inode_get_size(sb, (*inode_ref).inode);
+
+        // 到达目录末尾
+        if pos >= size {
+            if !(*it).curr_blk.buf.is_null() {
+                let r = ext4_block_set((*fs).bdev, &mut (*it).curr_blk);
+                (*it).curr_blk = Ext4Block::new();
+                if r != EOK {
+                    return r;
+                }
+            }
+            (*it).curr = ptr::null_mut();
+            (*it).curr_off = pos;
+            return EOK;
+        }
+
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let current_blk_idx = (*it).curr_off / block_size;
+        let offset_in_block = (pos % block_size) as usize;
+        let next_blk_idx = pos / block_size;
+
+        // 需要加载新块
+        if (*it).curr_blk.buf.is_null() || current_blk_idx != next_blk_idx {
+            if !(*it).curr_blk.buf.is_null() {
+                let r = ext4_block_set((*fs).bdev, &mut (*it).curr_blk);
+                (*it).curr_blk = Ext4Block::new();
+                if r != EOK {
+                    return r;
+                }
+            }
+
+            let mut next_blk = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(inode_ref, next_blk_idx as u32, &mut next_blk, false);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_block_get((*fs).bdev, &mut (*it).curr_blk, next_blk);
+            if r != EOK {
+                (*it).curr_blk = Ext4Block::new();
+                return r;
+            }
+            if !ext4_dir_csum_verify(inode_ref, (*it).curr_blk.data as *mut Ext4DirEntry) {
+                ext4_dbg!(
+                    DEBUG_DIR,
+                    Warn,
+                    "leaf block checksum failed: inode {}, block {}",
+                    (*inode_ref).index,
+                    next_blk_idx
+                );
+            }
+        }
+
+        (*it).curr_off = pos;
+        let de = (*it).curr_blk.data.add(offset_in_block) as *mut Ext4DirEntry;
+        if !offset_in_block.is_multiple_of(4)
+            || offset_in_block + EXT4_DIR_ENTRY_HEADER_SIZE > block_size as usize
+            || !ext4_dir_entry_is_valid(sb, de, offset_in_block, block_size as usize)
+        {
+            (*it).curr = ptr::null_mut();
+            return EIO;
+        }
+        (*it).curr = de;
+    }
+    EOK
+}
+
+/// 跳过 inode 为0的空目录项
+unsafe fn ext4_dir_iterator_skip_empty(it: *mut Ext4DirIterator) -> i32 {
+    unsafe {
+        while !(*it).curr.is_null() && u32::from_le((*(*it).curr).inode) == 0 {
+            let skip = ext4_dir_en_get_entry_len((*it).curr) as u64;
+            let r = ext4_dir_iterator_seek(it, (*it).curr_off + skip);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+// ===== 按哈希位置迭代 =====
+
+/// 按哈希位置迭代的状态
+///
+/// htree 目录每批读取起点哈希所在的叶子（哈希延续到后续叶子时一并读取），按位置排序后
+/// 逐个返回；索引不能使用时扫描一次整个目录作为唯一的一批。哈希位置相同的目录项总在同一批中。
+#[allow(non_camel_case_types)]
+pub struct ext4_dir_hash_batch {
+    /// (哈希位置, 字节偏移)
+    items: VecDeque<(u64, u64)>,
+    /// 下一批的起始哈希位置（EXT4_DIR_HASH_POS_EOF 表示没有更多）
+    next: u64,
+    /// 迭代器当前加载的块对应的字节偏移
+    blk_off: u64,
+    /// 当前批次是否因目录项位置过期而重新收集，之后仍然过期说明目录损坏
+    refilled: bool,
+}
+
+/// 目录使用的哈希版本（不支持时返回 None）
+pub(crate) fn ext4_dir_hash_version(sb: &Ext4Superblock) -> Option<u32> {
+    let mut version = sb.default_hash_version as u32;
+    if version > EXT2_HTREE_TEA {
+        return None;
+    }
+    if u32::from_le(sb.flags) & EXT4_SUPERBLOCK_FLAGS_UNSIGNED_HASH != 0 {
+        version += EXT2_HTREE_LEGACY_UNSIGNED;
+    }
+    Some(version)
+}
+
+/// 目录是否按哈希位置迭代（htree 索引目录）
+unsafe fn ext4_dir_use_hash_pos(inode_ref: *mut Ext4InodeRef) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        ext4_sb_feature_com(sb, EXT4_FCOM_DIR_INDEX)
+            && ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX)
+            && ext4_dir_hash_version(sb).is_some()
+    }
+}
+
+/// 目录项的哈希位置
+///
+/// "." 和 ".." 固定在最前面；其余为 (major >> 1) << 32 | minor，与内核 64 位 readdir 偏移相同。
+pub fn ext4_dir_entry_hash_pos(sb: &Ext4Superblock, name: &[u8]) -> u64 {
+    let pos = match name {
+        b"." => 0,
+        b".." => 1,
+        _ => {
+            let (mut major, mut minor) = (0, 0);
+            if let Some(version) = ext4_dir_hash_version(sb) {
+                ext2_htree_hash(name, Some(&sb.hash_seed), version, &mut major, &mut minor);
+            }
+            (((major >> 1) as u64) << 32 | minor as u64).max(2)
+        }
+    };
+    EXT4_DIR_HASH_POS_FLAG | pos
+}
+
+/// 收集目录第 iblock 块中哈希位置不小于 from 的目录项 (哈希位置, 字节偏移)
+///
+/// 目录项不合法或块是空洞时返回 EIO。
+pub(crate) unsafe fn ext4_dir_collect_hash_pos(
+    inode_ref: *mut Ext4InodeRef,
+    iblock: u32,
+    from: u64,
+    items: &mut Vec<(u64, u64)>,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let mut fblock = 0u64;
+        let r = ext4_fs_get_inode_dblk_idx(inode_ref, iblock, &mut fblock, false);
+        if r != EOK {
+            return r;
+        }
+        if fblock == 0 {
+            ext4_dbg!(DEBUG_DIR, Warn, "hole in directory: inode {}, block {}", (*inode_ref).index, iblock);
+            return EIO;
+        }
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+        if r != EOK {
+            return r;
+        }
 
-/// 查找目录项（占位实现）
-pub fn ext4_dir_find_entry(
+        let mut r = EOK;
+        let mut off = 0usize;
+        while off + EXT4_DIR_ENTRY_HEADER_SIZE <= block_size {
+            let de = b.data.add(off) as *mut Ext4DirEntry;
+            if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                ext4_dbg!(DEBUG_DIR, Warn, "bad directory entry: inode {}, block {}, offset {}", (*inode_ref).index, iblock, off);
+                r = EIO;
+                break;
+            }
+            if u32::from_le((*de).inode) != 0 {
+                let name_len = ext4_dir_en_get_name_len(sb, de) as usize;
+                let name = core::slice::from_raw_parts((*de).name_mut_ptr(), name_len);
+                let pos = ext4_dir_entry_hash_pos(sb, name);
+                if pos >= from {
+                    items.push((pos, iblock as u64 * block_size as u64 + off as u64));
+                }
+            }
+            off += ext4_dir_en_get_entry_len(de) as usize;
+        }
+
+        let r2 = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK { r } else { r2 }
+    }
+}
+
+/// 收集哈希位置不小于 from 的下一批目录项
+unsafe fn ext4_dir_hash_fill(it: *mut Ext4DirIterator, from: u64) -> i32 {
+    unsafe {
+        let inode_ref = (*it).inode_ref;
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+
+        let dx = if ext4_dir_use_hash_pos(inode_ref) {
+            ext4_dir_dx_hash_batch(inode_ref, from)
+        } else {
+            Err(ENOTSUP)
+        };
+        let (mut items, next) = match dx {
+            Ok(batch) => batch,
+            Err(ENOTSUP) => {
+                // 没有可用的索引：扫描整个目录，一次得到全部剩余的目录项
+                let blocks = ext4_inode_get_size(sb, (*inode_ref).inode) / ext4_sb_get_block_size(sb) as u64;
+                let mut items = Vec::new();
+                for iblock in 0..blocks {
+                    let r = ext4_dir_collect_hash_pos(inode_ref, iblock as u32, from, &mut items);
+                    if r != EOK {
+                        return r;
+                    }
+                }
+                items.sort_unstable();
+                (items, EXT4_DIR_HASH_POS_EOF)
+            }
+            Err(r) => return r,
+        };
+        items.dedup();
+
+        let batch = &mut *(*it).hash;
+        batch.items = items.into();
+        batch.next = next;
+        ext4_dbg!(
+            DEBUG_DIR,
+            Trace,
+            "ext4_dir_hash_fill: inode {}, from={:#x}, entries={}, next={:#x}",
+            (*inode_ref).index,
+            from,
+            batch.items.len(),
+            batch.next
+        );
+    }
+    EOK
+}
+
+/// 定位到下一个仍然存在的目录项
+///
+/// 已删除的目录项被跳过。目录项的字节偏移过期（目录在两批之间被修改）时从该位置重新收集
+/// 一次；重新收集后仍然找不到目录项说明目录损坏，返回 EIO。
+unsafe fn ext4_dir_hash_load(it: *mut Ext4DirIterator) -> i32 {
+    unsafe {
+        let sb = &(*(*(*it).inode_ref).fs).sb;
+        loop {
+            let batch = &mut *(*it).hash;
+            let Some((pos, byte_off)) = batch.items.pop_front() else {
+                if batch.next == EXT4_DIR_HASH_POS_EOF {
+                    // 释放当前块并停在目录末尾
+                    (*it).curr_off = batch.blk_off;
+                    let r = ext4_dir_iterator_seek(it, u64::MAX);
+                    (*it).curr_off = EXT4_DIR_HASH_POS_EOF;
+                    return r;
+                }
+                let r = ext4_dir_hash_fill(it, batch.next);
+                if r != EOK {
+                    return r;
+                }
+                continue;
+            };
+
+            (*it).curr_off = batch.blk_off;
+            let r = ext4_dir_iterator_seek(it, byte_off);
+            batch.blk_off = byte_off;
+            (*it).curr_off = pos;
+            let stale = match r {
+                // 超出目录末尾
+                EOK if (*it).curr.is_null() => true,
+                EOK => {
+                    let de = (*it).curr;
+                    if u32::from_le((*de).inode) == 0 {
+                        continue;
+                    }
+                    let name_len = ext4_dir_en_get_name_len(sb, de) as usize;
+                    let name = core::slice::from_raw_parts((*de).name_mut_ptr(), name_len);
+                    ext4_dir_entry_hash_pos(sb, name) != pos
+                }
+                // 偏移不在目录项边界上或超出目录
+                EIO => true,
+                _ => return r,
+            };
+            if !stale {
+                batch.refilled = false;
+                return EOK;
+            }
+            if batch.refilled {
+                ext4_dbg!(DEBUG_DIR, Warn, "directory entry vanished after refill: pos={:#x}", pos);
+                return EIO;
+            }
+            batch.refilled = true;
+            let r = ext4_dir_hash_fill(it, pos);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+}
+
+/// 初始化目录迭代器
+///
+/// pos 为0时从头开始；否则为之前迭代得到的偏移（curr_off）。
+pub unsafe fn ext4_dir_iterator_init(
+    it: *mut Ext4DirIterator,
+    inode_ref: *mut Ext4InodeRef,
+    pos: u64,
+) -> i32 {
+    ext4_dbg!(DEBUG_DIR, Debug, "ext4_dir_iterator_init: pos={:#x}", pos);
+    unsafe {
+        (*it).inode_ref = inode_ref;
+        (*it).curr = ptr::null_mut();
+        (*it).curr_off = 0;
+        (*it).curr_blk = Ext4Block::new();
+        (*it).hash = ptr::null_mut();
+
+        // 哈希位置偏移即使在目录退化为线性目录后也按哈希位置继续
+        if pos & EXT4_DIR_HASH_POS_FLAG != 0 || (pos == 0 && ext4_dir_use_hash_pos(inode_ref)) {
+            (*it).hash = Box::into_raw(Box::new(ext4_dir_hash_batch {
+                items: VecDeque::new(),
+                next: pos | EXT4_DIR_HASH_POS_FLAG,
+                blk_off: 0,
+                refilled: false,
+            }));
+            if pos == EXT4_DIR_HASH_POS_EOF {
+                (*(*it).hash).next = EXT4_DIR_HASH_POS_EOF;
+            }
+            return ext4_dir_hash_load(it);
+        }
+
+        let r = ext4_dir_iterator_seek(it, pos);
+        if r != EOK {
+            return r;
+        }
+        ext4_dir_iterator_skip_empty(it)
+    }
+}
+
+/// 移动到下一个目录项
+pub unsafe fn ext4_dir_iterator_next(it: *mut Ext4DirIterator) -> i32 {
+    unsafe {
+        if (*it).curr.is_null() {
+            return EOK;
+        }
+        if !(*it).hash.is_null() {
+            return ext4_dir_hash_load(it);
+        }
+        let skip = ext4_dir_en_get_entry_len((*it).curr) as u64;
+        let r = ext4_dir_iterator_seek(it, (*it).curr_off + skip);
+        if r != EOK {
+            return r;
+        }
+        ext4_dir_iterator_skip_empty(it)
+    }
+}
+
+/// 销毁目录迭代器
+pub unsafe fn ext4_dir_iterator_fini(it: *mut Ext4DirIterator) -> i32 {
+    unsafe {
+        (*it).curr = ptr::null_mut();
+        if !(*it).hash.is_null() {
+            drop(Box::from_raw((*it).hash));
+            (*it).hash = ptr::null_mut();
+        }
+        if (*it).curr_blk.buf.is_null() {
+            return EOK;
+        }
+        let fs = (*(*it).inode_ref).fs;
+        let r = ext4_block_set((*fs).bdev, &mut (*it).curr_blk);
+        (*it).curr_blk = Ext4Block::new();
+        r
+    }
+}
+
+// ===== 目录项查找与修改 =====
+
+/// 写入目录项
+pub unsafe fn ext4_dir_write_entry(
+    sb: &Ext4Superblock,
+    en: *mut Ext4DirEntry,
+    entry_len: u16,
+    child: *mut Ext4InodeRef,
+    name: *const u8,
+    name_len: usize,
+) {
+    unsafe {
+        ext4_dir_en_set_inode_type(sb, en, ext4_dir_entry_type(sb, (*child).inode));
+
+        (*en).inode = (*child).index.to_le();
+        ext4_dir_en_set_entry_len(en, entry_len);
+        ext4_dir_en_set_name_len(sb, en, name_len as u16);
+        ptr::copy_nonoverlapping(name, (*en).name_mut_ptr(), name_len);
+    }
+}
+
+/// 尝试在目录块中插入目录项（空间不足时返回 ENOSPC）
+pub unsafe fn ext4_dir_try_insert_entry(
+    sb: &Ext4Superblock,
+    inode_ref: *mut Ext4InodeRef,
+    dst_blk: *mut Ext4Block,
+    child: *mut Ext4InodeRef,
+    name: *const u8,
+    name_len: u32,
+) -> i32 {
+    unsafe {
+        let data = (*dst_blk).data;
+        let required_len = ext4_dir_entry_used_len(name_len) as usize;
+        let stop = ext4_dir_block_usable_len(inode_ref, data);
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+
+        let mut off = 0usize;
+        while off < stop {
+            let de = data.add(off) as *mut Ext4DirEntry;
+            if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                return EIO;
+            }
+            let rec_len = ext4_dir_en_get_entry_len(de) as usize;
+            let inode = u32::from_le((*de).inode);
+
+            if inode == 0 {
+                // 空目录项可直接复用
+                if rec_len >= required_len {
+                    ext4_dir_write_entry(sb, de, rec_len as u16, child, name, name_len as usize);
+                    ext4_dir_set_csum(inode_ref, data as *mut Ext4DirEntry);
+                    ext4_block_set_dirty(dst_blk);
+                    return EOK;
+                }
+            } else {
+                let used_len = ext4_dir_entry_used_len(ext4_dir_en_get_name_len(sb, de) as u32) as usize;
+                let free_space = rec_len - used_len;
+                if free_space >= required_len {
+                    // 拆分现有目录项的剩余空间
+                    ext4_dir_en_set_entry_len(de, used_len as u16);
+                    let new_entry = data.add(off + used_len) as *mut Ext4DirEntry;
+                    ext4_dir_write_entry(sb, new_entry, free_space as u16, child, name, name_len as usize);
+                    ext4_dir_set_csum(inode_ref, data as *mut Ext4DirEntry);
+                    ext4_block_set_dirty(dst_blk);
+                    return EOK;
+                }
+            }
+            off += rec_len;
+        }
+    }
+    ENOSPC
+}
+
+/// 在目录块中查找目录项
+pub unsafe fn ext4_dir_find_in_block(
+    block: *mut Ext4Block,
+    sb: &Ext4Superblock,
+    name: *const u8,
+    name_len: usize,
+    res_entry: *mut *mut Ext4DirEntry,
+) -> i32 {
+    unsafe {
+        let data = (*block).data;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let name = core::slice::from_raw_parts(name, name_len);
+
+        let mut off = 0usize;
+        while off + EXT4_DIR_ENTRY_HEADER_SIZE <= block_size {
+            let de = data.add(off) as *mut Ext4DirEntry;
+            if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                return EIO;
+            }
+            if u32::from_le((*de).inode) != 0 && ext4_dir_en_get_name_len(sb, de) as usize == name_len {
+                let de_name = core::slice::from_raw_parts((*de).name_mut_ptr(), name_len);
+                if de_name == name {
+                    *res_entry = de;
+                    return EOK;
+                }
+            }
+            off += ext4_dir_en_get_entry_len(de) as usize;
+        }
+    }
+    ENOENT
+}
+
+/// 调试输出用：目录项名称（非 UTF-8 时输出 "?"）
+unsafe fn dbg_name<'a>(name: *const u8, name_len: u32) -> &'a str {
+    if name.is_null() {
+        return "";
+    }
+    let name = unsafe { core::slice::from_raw_parts(name, name_len as usize) };
+    core::str::from_utf8(name).unwrap_or("?")
+}
+
+/// 查找目录项
+///
+/// 成功时 result 持有目录项所在块的引用，需调用 ext4_dir_destroy_result 释放。
+pub unsafe fn ext4_dir_find_entry(
     result: *mut Ext4DirSearchResult,
     parent: *mut Ext4InodeRef,
     name: *const u8,
     name_len: u32,
 ) -> i32 {
-    // TODO: 实现目录项查找
-    // 1. 遍历父目录的数据块
-    // 2. 解析每个目录项
-    // 3. 比较名称
+    unsafe {
+        ext4_dbg!(
+            DEBUG_DIR,
+            Debug,
+            "ext4_dir_find_entry: dir {}, name {:?}",
+            (*parent).index,
+            dbg_name(name, name_len)
+        );
+        (*result).block = Ext4Block::new();
+        (*result).dentry = ptr::null_mut();
+        (*result).dentry_ino = 0;
+        if name_len as usize > EXT4_DIRECTORY_FILENAME_LEN {
+            return ENAMETOOLONG;
+        }
+
+        // htree 目录先按索引查找；"." 和 ".." 位于索引根中，按线性方式查找
+        if ext4_inode_has_flag((*parent).inode, EXT4_INODE_FLAG_INDEX) {
+            let name_s = core::slice::from_raw_parts(name, name_len as usize);
+            if name_s != b"." && name_s != b".." {
+                let r = ext4_dir_dx_find_entry(result, parent, name, name_len);
+                if r != ENOTSUP {
+                    return r;
+                }
+            }
+        }
+
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let inode_size = ext4_inode_get_size(sb, (*parent).inode);
+        let total_blocks = inode_size / block_size;
+
+        for iblock in 0..total_blocks {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(parent, iblock as u32, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            if !ext4_dir_csum_verify(parent, b.data as *mut Ext4DirEntry) {
+                ext4_dbg!(DEBUG_DIR, Warn, "leaf block checksum failed: inode {}, block {}", (*parent).index, iblock);
+            }
 
-    debug!("ext4_dir_find_entry: name_len={}", name_len);
-    ENOENT  // 暂时返回未找到
+            let mut res_entry: *mut Ext4DirEntry = ptr::null_mut();
+            let r = ext4_dir_find_in_block(&mut b, sb, name, name_len as usize, &mut res_entry);
+            if r == EOK {
+                (*result).block = b;
+                (*result).dentry = res_entry;
+                (*result).dentry_ino = u32::from_le((*res_entry).inode);
+                return EOK;
+            }
+
+            let r2 = ext4_block_set((*fs).bdev, &mut b);
+            if r != ENOENT {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    ENOENT
 }
 
-/// 添加目录项（占位实现）
-pub fn ext4_dir_add_entry(
+/// 添加目录项
+///
+/// htree 目录按索引插入。线性目录先尝试在已有块中插入；只有一个块且已满时转换为
+/// htree 目录，否则在目录末尾追加新块。
+pub unsafe fn ext4_dir_add_entry(
     parent: *mut Ext4InodeRef,
     name: *const u8,
     name_len: u32,
     child: *mut Ext4InodeRef,
 ) -> i32 {
-    // TODO: 实现目录项添加
-    debug!("ext4_dir_add_entry: name_len={}", name_len);
+    unsafe {
+        ext4_dbg!(DEBUG_DIR, Debug, "ext4_dir_add_entry: dir {}, name {:?}", (*parent).index, dbg_name(name, name_len));
+        if name_len == 0 {
+            return EINVAL;
+        }
+        if name_len as usize > EXT4_DIRECTORY_FILENAME_LEN {
+            return ENAMETOOLONG;
+        }
+
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+
+        // htree 目录按索引插入；索引无法使用时退化为线性目录，
+        // 索引块此后按目录块处理，启用 metadata_csum 时为它们加上目录块校验和尾部
+        if ext4_inode_has_flag((*parent).inode, EXT4_INODE_FLAG_INDEX) {
+            let r = ext4_dir_dx_add_entry(parent, name, name_len, child);
+            if r != ENOTSUP {
+                return r;
+            }
+            ext4_inode_clear_flag((*parent).inode, EXT4_INODE_FLAG_INDEX);
+            (*parent).dirty = true;
+            if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+                let r = ext4_dir_csum_init(parent, false);
+                if r != EOK {
+                    return r;
+                }
+            }
+        }
+
+        let block_size = ext4_sb_get_block_size(sb);
+        let inode_size = ext4_inode_get_size(sb, (*parent).inode);
+        let total_blocks = (inode_size / block_size as u64) as u32;
+
+        for iblock in 0..total_blocks {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(parent, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            if !ext4_dir_csum_verify(parent, b.data as *mut Ext4DirEntry) {
+                ext4_dbg!(DEBUG_DIR, Warn, "leaf block checksum failed: inode {}, block {}", (*parent).index, iblock);
+            }
+
+            let r = ext4_dir_try_insert_entry(sb, parent, &mut b, child, name, name_len);
+            let r2 = ext4_block_set((*fs).bdev, &mut b);
+            if r == EOK {
+                return r2;
+            }
+            if r != ENOSPC {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+
+        // 唯一的块已满时转换为 htree 索引目录，只有一个块的小目录保持线性
+        if total_blocks == 1 {
+            let r = ext4_dir_dx_init(parent);
+            if r == EOK {
+                return ext4_dir_dx_add_entry(parent, name, name_len, child);
+            }
+            if r != ENOTSUP {
+                return r;
+            }
+        }
+
+        // 没有空闲空间，追加新块
+        let mut fblock = 0u64;
+        let mut iblock = 0u32;
+        let r = ext4_fs_append_inode_dblk(parent, &mut fblock, &mut iblock);
+        if r != EOK {
+            return r;
+        }
+
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get_noread((*fs).bdev, &mut b, fblock);
+        if r != EOK {
+            return r;
+        }
+        ptr::write_bytes(b.data, 0, block_size as usize);
+
+        let has_csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM);
+        let entry_len = if has_csum {
+            block_size as usize - size_of::<Ext4DirEntryTail>()
+        } else {
+            block_size as usize
+        };
+        ext4_dir_write_entry(sb, b.data as *mut Ext4DirEntry, entry_len as u16, child, name, name_len as usize);
+        if has_csum {
+            ext4_dir_init_entry_tail(b.data.add(entry_len) as *mut Ext4DirEntryTail);
+            ext4_dir_set_csum(parent, b.data as *mut Ext4DirEntry);
+        }
+
+        ext4_block_set_dirty(&mut b);
+        ext4_block_set((*fs).bdev, &mut b)
+    }
+}
+
+/// 压缩目录：把全部目录项紧凑地重写到尽量少的块中，截断释放其余的块
+///
+/// 重写后需要多个块的目录按哈希重建 htree 索引（能够使用索引时），否则为线性目录；
+/// 只需一个块的目录总是改为线性目录。released 返回释放的块数。
+/// 目录项的迭代偏移随之改变；重建后的块数不少于原块数时不修改目录。
+pub unsafe fn ext4_dir_compact(parent: *mut Ext4InodeRef, released: *mut u32) -> i32 {
+    unsafe {
+        *released = 0;
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+        if ext4_inode_has_flag((*parent).inode, EXT4_INODE_FLAG_INLINE_DATA) {
+            return ENOTSUP;
+        }
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let total_blocks = (ext4_inode_get_size(sb, (*parent).inode) / block_size as u64) as u32;
+
+        // 读出全部目录项（htree 的索引块中没有有效目录项，自然被跳过）
+        let mut dots: [Option<Vec<u8>>; 2] = [None, None];
+        let mut entries: Vec<Vec<u8>> = Vec::new();
+        for iblock in 0..total_blocks {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(parent, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                continue;
+            }
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            let usable = ext4_dir_block_usable_len(parent, b.data);
+            let mut off = 0;
+            while off < usable {
+                let de = b.data.add(off) as *mut Ext4DirEntry;
+                if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                    ext4_block_set((*fs).bdev, &mut b);
+                    return EIO;
+                }
+                off += ext4_dir_en_get_entry_len(de) as usize;
+                if u32::from_le((*de).inode) == 0 {
+                    continue;
+                }
+                let name_len = ext4_dir_en_get_name_len(sb, de) as usize;
+                let raw = core::slice::from_raw_parts(de as *const u8, ext4_dir_entry_used_len(name_len as u32) as usize);
+                let name = &raw[EXT4_DIR_ENTRY_HEADER_SIZE..][..name_len];
+                match name {
+                    b"." | b".." if iblock == 0 && dots[name_len - 1].is_none() => dots[name_len - 1] = Some(raw.to_vec()),
+                    _ => entries.push(raw.to_vec()),
+                }
+            }
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+        let [Some(dot), Some(dotdot)] = dots else {
+            ext4_dbg!(DEBUG_DIR, Warn, "ext4_dir_compact: missing dot entries: inode {}", (*parent).index);
+            return EIO;
+        };
+
+        let refs: Vec<&[u8]> = entries.iter().map(Vec::as_slice).collect();
+        let all: Vec<&[u8]> = [dot.as_slice(), dotdot.as_slice()].into_iter().chain(refs.iter().copied()).collect();
+        let linear: Vec<Vec<u8>> = ext4_dir_pack_blocks(parent, &all).into_iter().map(|b| b.1).collect();
+        let (blocks, indexed) = match linear.len() {
+            1 => (linear, false),
+            _ => match ext4_dir_dx_build(parent, &dot, &dotdot, &refs) {
+                Some(blocks) => (blocks, true),
+                None => (linear, false),
+            },
+        };
+        if blocks.len() >= total_blocks as usize {
+            return EOK;
+        }
+
+        for (iblock, data) in blocks.iter().enumerate() {
+            let mut fblock = 0u64;
+            let mut r = ext4_fs_get_inode_dblk_idx(parent, iblock as u32, &mut fblock, false);
+            if r == EOK && fblock == 0 {
+                r = ext4_fs_init_inode_dblk_idx(parent, iblock as u32, &mut fblock);
+            }
+            if r != EOK {
+                return r;
+            }
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get_noread((*fs).bdev, &mut b, fblock);
+            if r != EOK {
+                return r;
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), b.data, block_size);
+            ext4_block_set_dirty(&mut b);
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+        if indexed {
+            ext4_inode_set_flag((*parent).inode, EXT4_INODE_FLAG_INDEX);
+        } else {
+            ext4_inode_clear_flag((*parent).inode, EXT4_INODE_FLAG_INDEX);
+        }
+        (*parent).dirty = true;
+        let r = ext4_fs_truncate_inode(parent, (blocks.len() * block_size) as u64);
+        if r != EOK {
+            return r;
+        }
+        *released = total_blocks - blocks.len() as u32;
+        ext4_dbg!(DEBUG_DIR, Debug, "ext4_dir_compact: inode {}, {} -> {} blocks", (*parent).index, total_blocks, blocks.len());
+    }
     EOK
 }
 
-/// 删除目录项（占位实现）
-pub fn ext4_dir_remove_entry(
+/// 删除目录项（与前一个目录项合并）
+pub unsafe fn ext4_dir_remove_entry(
     parent: *mut Ext4InodeRef,
     name: *const u8,
     name_len: u32,
 ) -> i32 {
-    // TODO: 实现目录项删除
-    debug!("ext4_dir_remove_entry: name_len={}", name_len);
-    EOK
-}
+    unsafe {
+        ext4_dbg!(
+            DEBUG_DIR,
+            Debug,
+            "ext4_dir_remove_entry: dir {}, name {:?}",
+            (*parent).index,
+            dbg_name(name, name_len)
+        );
+        let mut result = Ext4DirSearchResult::new();
+        let r = ext4_dir_find_entry(&mut result, parent, name, name_len);
+        if r != EOK {
+            return r;
+        }
 
-/// 初始化目录迭代器（占位实现）
-pub fn ext4_dir_iterator_init(
-    it: *mut Ext4DirIterator,
-    inode_ref: *mut Ext4InodeRef,
-    pos: u64,
-) -> i32 {
-    // TODO: 初始化迭代器
-    debug!("ext4_dir_iterator_init: pos={}", pos);
-    EOK
-}
+        // 使目录项失效
+        (*result.dentry).inode = 0;
 
-/// 获取下一个目录项（占位实现）
-pub fn ext4_dir_iterator_next(it: *mut Ext4DirIterator) -> i32 {
-    // TODO: 移动到下一个目录项
-    debug!("ext4_dir_iterator_next");
-    ENOENT  // 暂时返回结束
-}
+        let data = result.block.data;
+        let pos = result.dentry as usize - data as usize;
+        // 不是块中第一个目录项时，与前驱合并
+        if pos != 0 {
+            let mut offset = 0usize;
+            let mut tmp_de = data as *mut Ext4DirEntry;
+            let mut de_len = ext4_dir_en_get_entry_len(tmp_de) as usize;
+            while offset + de_len < pos {
+                offset += de_len;
+                tmp_de = data.add(offset) as *mut Ext4DirEntry;
+                de_len = ext4_dir_en_get_entry_len(tmp_de) as usize;
+            }
+            if offset + de_len != pos {
+                ext4_dir_destroy_result(parent, &mut result);
+                return EIO;
+            }
+            let del_len = ext4_dir_en_get_entry_len(result.dentry) as usize;
+            ext4_dir_en_set_entry_len(tmp_de, (de_len + del_len) as u16);
+        }
 
-/// 销毁目录迭代器（占位实现）
-pub fn ext4_dir_iterator_fini(it: *mut Ext4DirIterator) -> i32 {
-    debug!("ext4_dir_iterator_fini");
-    EOK
+        ext4_dir_set_csum(parent, data as *mut Ext4DirEntry);
+        ext4_block_set_dirty(&mut result.block);
+        ext4_dir_destroy_result(parent, &mut result)
+    }
 }
 
-/// 销毁查找结果（占位实现）
-pub fn ext4_dir_destroy_result(
+/// 销毁查找结果
+///
+/// 调用者修改过目录项的 inode 编号时，重新计算目录块校验和。
+pub unsafe fn ext4_dir_destroy_result(
     parent: *mut Ext4InodeRef,
     result: *mut Ext4DirSearchResult,
-) {
-    debug!("ext4_dir_destroy_result");
-    // TODO: 释放查找结果占用的资源
+) -> i32 {
+    ext4_dbg!(DEBUG_DIR, Debug, "ext4_dir_destroy_result");
+    unsafe {
+        if (*result).block.buf.is_null() {
+            return EOK;
+        }
+        let dentry = (*result).dentry;
+        if !dentry.is_null() && u32::from_le((*dentry).inode) != (*result).dentry_ino {
+            ext4_dir_set_csum(parent, (*result).block.data as *mut Ext4DirEntry);
+            ext4_block_set_dirty(&mut (*result).block);
+        }
+        let fs = (*parent).fs;
+        let r = ext4_block_set((*fs).bdev, &mut (*result).block);
+        (*result).block = Ext4Block::new();
+        (*result).dentry = ptr::null_mut();
+        r
+    }
 }
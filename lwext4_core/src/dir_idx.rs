@@ -0,0 +1,944 @@
+//! htree 索引目录模块
+//!
+//! 对应C实现: ext4_dir_idx.c
+//!
+//! 目录的第一个块放不下新目录项时，与 Linux 的 make_indexed_dir 一样把目录转换为 htree：
+//! ".." 之后的目录项移到新的叶子块，第一个块改为索引根；只有一个块的小目录保持线性。
+//! 之后按文件名哈希找到叶子块插入，叶子块满时按哈希分裂，索引满时增加一层或分裂中间节点
+//! （最多两层索引；启用 large_dir 时三层）。
+//! 索引无法使用的目录（哈希版本不支持、索引损坏、大小写不敏感或加密目录）返回 ENOTSUP，
+//! 由调用者按线性目录处理。
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr;
+
+use crate::block::*;
+use crate::consts::*;
+use crate::crc::ext4_crc32c;
+use crate::debug::*;
+use crate::dir::*;
+use crate::hash::ext2_htree_hash;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::{
+    Ext4Block, Ext4DirEntry, Ext4DirSearchResult, Ext4Filesystem, Ext4InodeRef, Ext4Superblock, EXT4_DIR_ENTRY_HEADER_SIZE,
+};
+
+/// 索引根中 dx_root_info 的偏移（"." 和 ".." 目录项之后）
+const DX_ROOT_INFO_OFFSET: usize = 24;
+/// 索引根中计数/上限的偏移
+const DX_ROOT_COUNT_OFFSET: usize = 32;
+/// 中间节点中计数/上限的偏移（跳过占满整个块的空目录项）
+const DX_NODE_COUNT_OFFSET: usize = 8;
+/// 索引项大小（哈希 + 逻辑块号）
+const DX_ENTRY_SIZE: usize = 8;
+/// dx_tail 大小（保留字段 + 校验和）
+const DX_TAIL_SIZE: usize = 8;
+/// dx_root_info 的长度
+const DX_ROOT_INFO_LEN: u8 = 8;
+/// 未启用 large_dir 时的最大间接层数
+const DX_MAX_LEVELS: u8 = 1;
+/// 启用 large_dir 时的最大间接层数
+const DX_MAX_LEVELS_LARGEDIR: u8 = 2;
+
+unsafe fn get16(p: *const u8, off: usize) -> u16 {
+    unsafe { u16::from_le(ptr::read_unaligned(p.add(off) as *const u16)) }
+}
+
+unsafe fn get32(p: *const u8, off: usize) -> u32 {
+    unsafe { u32::from_le(ptr::read_unaligned(p.add(off) as *const u32)) }
+}
+
+unsafe fn put16(p: *mut u8, off: usize, v: u16) {
+    unsafe { ptr::write_unaligned(p.add(off) as *mut u16, v.to_le()) }
+}
+
+unsafe fn put32(p: *mut u8, off: usize, v: u32) {
+    unsafe { ptr::write_unaligned(p.add(off) as *mut u32, v.to_le()) }
+}
+
+/// 索引路径上的一层（根或中间节点）
+struct DxFrame {
+    block: Ext4Block,
+    count_off: usize, // 计数/上限在块中的偏移
+    at: usize,        // 选中的索引项序号
+}
+
+impl DxFrame {
+    unsafe fn limit(&self) -> usize {
+        unsafe { get16(self.block.data, self.count_off) as usize }
+    }
+
+    unsafe fn count(&self) -> usize {
+        unsafe { get16(self.block.data, self.count_off + 2) as usize }
+    }
+
+    unsafe fn set_count(&mut self, count: usize) {
+        unsafe { put16(self.block.data, self.count_off + 2, count as u16) }
+    }
+
+    /// 计数和上限是否合法
+    unsafe fn is_valid(&self, sb: &Ext4Superblock) -> bool {
+        unsafe {
+            let count = self.count();
+            self.limit() == dx_limit(sb, self.count_off) && count != 0 && count <= self.limit()
+        }
+    }
+
+    /// 索引项的哈希（第0项的哈希位置存放计数/上限，视为0）
+    unsafe fn hash(&self, i: usize) -> u32 {
+        if i == 0 {
+            return 0;
+        }
+        unsafe { get32(self.block.data, self.count_off + i * DX_ENTRY_SIZE) }
+    }
+
+    /// 索引项指向的逻辑块号（高4位保留）
+    unsafe fn block(&self, i: usize) -> u32 {
+        unsafe { get32(self.block.data, self.count_off + i * DX_ENTRY_SIZE + 4) & 0x0fff_ffff }
+    }
+
+    /// 在选中的索引项之后插入一项（调用者保证未满）
+    unsafe fn insert(&mut self, hash: u32, block: u32) {
+        unsafe {
+            let count = self.count();
+            let pos = self.at + 1;
+            let base = self.block.data.add(self.count_off);
+            ptr::copy(
+                base.add(pos * DX_ENTRY_SIZE),
+                base.add((pos + 1) * DX_ENTRY_SIZE),
+                (count - pos) * DX_ENTRY_SIZE,
+            );
+            put32(base, pos * DX_ENTRY_SIZE, hash);
+            put32(base, pos * DX_ENTRY_SIZE + 4, block);
+            self.set_count(count + 1);
+        }
+    }
+}
+
+/// 文件系统允许的最大间接层数（对应 Linux ext4_dir_htree_level - 1）
+fn dx_max_levels(sb: &Ext4Superblock) -> u8 {
+    if ext4_sb_feature_incom(sb, EXT4_FINCOM_LARGEDIR) {
+        DX_MAX_LEVELS_LARGEDIR
+    } else {
+        DX_MAX_LEVELS
+    }
+}
+
+/// 索引块的项数上限（启用 metadata_csum 时为 dx_tail 留出一项的空间）
+fn dx_limit(sb: &Ext4Superblock, count_off: usize) -> usize {
+    let block_size = ext4_sb_get_block_size(sb) as usize;
+    let csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM);
+    (block_size - count_off) / DX_ENTRY_SIZE - csum as usize
+}
+
+/// 索引块中计数/上限的偏移，不是索引块时返回 None（对应 Linux get_dx_countlimit）
+pub(crate) unsafe fn ext4_dir_dx_count_offset(sb: &Ext4Superblock, data: *const u8) -> Option<usize> {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let de = data as *const Ext4DirEntry;
+        let entry_len = ext4_dir_en_get_entry_len(de) as usize;
+        if entry_len == block_size && ext4_dir_en_get_name_len(sb, de) == 0 {
+            return Some(DX_NODE_COUNT_OFFSET);
+        }
+        if entry_len != 12 {
+            return None;
+        }
+        let dotdot = data.add(12) as *const Ext4DirEntry;
+        if ext4_dir_en_get_entry_len(dotdot) as usize != block_size - 12
+            || get32(data, DX_ROOT_INFO_OFFSET) != 0
+            || *data.add(DX_ROOT_INFO_OFFSET + 5) != DX_ROOT_INFO_LEN
+        {
+            return None;
+        }
+        Some(DX_ROOT_COUNT_OFFSET)
+    }
+}
+
+/// 计算索引块校验和（dx_tail 超出块范围时返回 None）
+unsafe fn ext4_dir_dx_csum(inode_ref: *mut Ext4InodeRef, data: *const u8, count_off: usize) -> Option<u32> {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let limit = get16(data, count_off) as usize;
+        let count = get16(data, count_off + 2) as usize;
+        let tail = count_off + limit * DX_ENTRY_SIZE;
+        if count > limit || tail + DX_TAIL_SIZE > block_size {
+            return None;
+        }
+        let seed = ext4_inode_csum_seed(sb, (*inode_ref).index, ext4_inode_get_generation((*inode_ref).inode));
+        let csum = ext4_crc32c(seed, core::slice::from_raw_parts(data, count_off + count * DX_ENTRY_SIZE));
+        // dx_tail 的保留字段参与计算，校验和字段按0计算
+        let csum = ext4_crc32c(csum, core::slice::from_raw_parts(data.add(tail), 4));
+        Some(ext4_crc32c(csum, &[0; 4]))
+    }
+}
+
+/// 校验索引块校验和，不是索引块时返回 None
+pub(crate) unsafe fn ext4_dir_dx_csum_verify(inode_ref: *mut Ext4InodeRef, data: *const u8) -> Option<bool> {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let count_off = ext4_dir_dx_count_offset(sb, data)?;
+        let csum = ext4_dir_dx_csum(inode_ref, data, count_off)?;
+        let tail = count_off + get16(data, count_off) as usize * DX_ENTRY_SIZE;
+        Some(get32(data, tail + 4) == csum)
+    }
+}
+
+/// 更新索引块校验和，不是索引块或没有 dx_tail 的空间时返回 false
+pub(crate) unsafe fn ext4_dir_dx_set_csum(inode_ref: *mut Ext4InodeRef, data: *mut u8) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return true;
+        }
+        let Some(count_off) = ext4_dir_dx_count_offset(sb, data) else {
+            return false;
+        };
+        let Some(csum) = ext4_dir_dx_csum(inode_ref, data, count_off) else {
+            return false;
+        };
+        let tail = count_off + get16(data, count_off) as usize * DX_ENTRY_SIZE;
+        put32(data, tail + 4, csum);
+        true
+    }
+}
+
+/// 目录能否按索引访问（不含索引根本身的检查）
+unsafe fn dx_supported(inode_ref: *mut Ext4InodeRef) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode = (*inode_ref).inode;
+        ext4_sb_feature_com(sb, EXT4_FCOM_DIR_INDEX)
+            && !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_CASEFOLD)
+            && !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_ENCRYPT)
+            && !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_INLINE_DATA)
+    }
+}
+
+/// 按索引根记录的哈希版本计算文件名哈希，版本不支持时返回 None
+fn dx_hash(sb: &Ext4Superblock, root_version: u8, name: &[u8]) -> Option<u32> {
+    let mut version = root_version as u32;
+    if version > EXT2_HTREE_TEA {
+        return None;
+    }
+    if u32::from_le(sb.flags) & EXT4_SUPERBLOCK_FLAGS_UNSIGNED_HASH != 0 {
+        version += EXT2_HTREE_LEGACY_UNSIGNED;
+    }
+    let (mut major, mut minor) = (0, 0);
+    if ext2_htree_hash(name, Some(&sb.hash_seed), version, &mut major, &mut minor) != EOK {
+        return None;
+    }
+    Some(major)
+}
+
+/// 读取目录的逻辑块（超出目录大小或是空洞时返回 ENOTSUP）
+unsafe fn dx_get_block(inode_ref: *mut Ext4InodeRef, iblock: u32, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let total_blocks = ext4_inode_get_size(sb, (*inode_ref).inode) / ext4_sb_get_block_size(sb) as u64;
+        if iblock as u64 >= total_blocks {
+            ext4_dbg!(DEBUG_DIR_IDX, Warn, "index points past directory end: inode {}, block {}", (*inode_ref).index, iblock);
+            return ENOTSUP;
+        }
+        let mut fblock = 0u64;
+        let r = ext4_fs_get_inode_dblk_idx(inode_ref, iblock, &mut fblock, false);
+        if r != EOK {
+            return r;
+        }
+        if fblock == 0 {
+            ext4_dbg!(DEBUG_DIR_IDX, Warn, "index points to a hole: inode {}, block {}", (*inode_ref).index, iblock);
+            return ENOTSUP;
+        }
+        ext4_block_get((*fs).bdev, b, fblock)
+    }
+}
+
+/// 释放路径上各层的块，返回 r 或第一个释放错误
+unsafe fn dx_put_frames(fs: *mut Ext4Filesystem, frames: &mut Vec<DxFrame>, r: i32) -> i32 {
+    let mut ret = r;
+    for mut frame in frames.drain(..) {
+        let r2 = unsafe { ext4_block_set((*fs).bdev, &mut frame.block) };
+        if ret == EOK {
+            ret = r2;
+        }
+    }
+    ret
+}
+
+/// 更新索引块的校验和并标记为脏
+unsafe fn dx_dirty(inode_ref: *mut Ext4InodeRef, b: *mut Ext4Block) {
+    unsafe {
+        ext4_dir_dx_set_csum(inode_ref, (*b).data);
+        ext4_block_set_dirty(b);
+    }
+}
+
+/// 从索引根下降到叶子，frames 收到路径上的各层（无论成功与否都需要调用者释放）
+///
+/// key 按索引根记录的哈希版本给出目标哈希（版本不支持时返回 None）。
+/// 返回目标哈希和叶子块的逻辑块号；索引无效或不支持时返回 Err(ENOTSUP)。
+unsafe fn dx_probe(
+    inode_ref: *mut Ext4InodeRef,
+    key: impl FnOnce(&Ext4Superblock, u8) -> Option<u32>,
+    frames: &mut Vec<DxFrame>,
+) -> Result<(u32, u32), i32> {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !dx_supported(inode_ref) {
+            return Err(ENOTSUP);
+        }
+        let mut root = Ext4Block::new();
+        let r = dx_get_block(inode_ref, 0, &mut root);
+        if r != EOK {
+            return Err(r);
+        }
+        let data = root.data;
+        let levels = *data.add(DX_ROOT_INFO_OFFSET + 6);
+        let hash = if ext4_dir_dx_count_offset(sb, data) == Some(DX_ROOT_COUNT_OFFSET) && levels <= dx_max_levels(sb) {
+            key(sb, *data.add(DX_ROOT_INFO_OFFSET + 4))
+        } else {
+            None
+        };
+        frames.push(DxFrame { block: root, count_off: DX_ROOT_COUNT_OFFSET, at: 0 });
+        let Some(hash) = hash else {
+            ext4_dbg!(DEBUG_DIR_IDX, Debug, "unsupported index root: inode {}", (*inode_ref).index);
+            return Err(ENOTSUP);
+        };
+
+        loop {
+            let frame = frames.last_mut().unwrap();
+            if !frame.is_valid(sb) {
+                ext4_dbg!(DEBUG_DIR_IDX, Warn, "bad index count/limit: inode {}", (*inode_ref).index);
+                return Err(ENOTSUP);
+            }
+            let count = frame.count();
+            // 最后一个哈希不大于目标哈希的索引项
+            let (mut lo, mut hi) = (1, count);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if frame.hash(mid) > hash {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            frame.at = lo - 1;
+            let next = frame.block(frame.at);
+            if frames.len() > levels as usize {
+                return Ok((hash, next));
+            }
+
+            let mut b = Ext4Block::new();
+            let r = dx_get_block(inode_ref, next, &mut b);
+            if r != EOK {
+                return Err(r);
+            }
+            let is_node = ext4_dir_dx_count_offset(sb, b.data) == Some(DX_NODE_COUNT_OFFSET);
+            frames.push(DxFrame { block: b, count_off: DX_NODE_COUNT_OFFSET, at: 0 });
+            if !is_node {
+                ext4_dbg!(DEBUG_DIR_IDX, Warn, "bad index node: inode {}, block {}", (*inode_ref).index, next);
+                return Err(ENOTSUP);
+            }
+        }
+    }
+}
+
+/// 路径上选中项之后的下一个索引项的哈希（没有更多叶子时返回 None）
+unsafe fn dx_next_hash(frames: &[DxFrame]) -> Option<u32> {
+    unsafe {
+        frames
+            .iter()
+            .rev()
+            .find(|f| f.at + 1 < f.count())
+            .map(|f| f.hash(f.at + 1))
+    }
+}
+
+/// 把路径移到下一个叶子（对应 Linux ext4_htree_next_block）
+///
+/// 返回下一个叶子的起始哈希和逻辑块号，没有更多叶子时返回 None。
+unsafe fn dx_next_leaf(inode_ref: *mut Ext4InodeRef, frames: &mut Vec<DxFrame>) -> Result<Option<(u32, u32)>, i32> {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let depth = frames.len();
+        let Some(level) = frames.iter().rposition(|f| f.at + 1 < f.count()) else {
+            return Ok(None);
+        };
+        let r = dx_put_frames(fs, &mut frames.split_off(level + 1), EOK);
+        if r != EOK {
+            return Err(r);
+        }
+        let frame = &mut frames[level];
+        frame.at += 1;
+        let hash = frame.hash(frame.at);
+        let mut next = frame.block(frame.at);
+
+        // 下层各节点从第一项开始
+        while frames.len() < depth {
+            let mut b = Ext4Block::new();
+            let r = dx_get_block(inode_ref, next, &mut b);
+            if r != EOK {
+                return Err(r);
+            }
+            let is_node = ext4_dir_dx_count_offset(sb, b.data) == Some(DX_NODE_COUNT_OFFSET);
+            frames.push(DxFrame { block: b, count_off: DX_NODE_COUNT_OFFSET, at: 0 });
+            let frame = frames.last().unwrap();
+            if !is_node || !frame.is_valid(sb) {
+                ext4_dbg!(DEBUG_DIR_IDX, Warn, "bad index node: inode {}, block {}", (*inode_ref).index, next);
+                return Err(ENOTSUP);
+            }
+            next = frame.block(0);
+        }
+        Ok(Some((hash, next)))
+    }
+}
+
+/// 收集哈希位置不小于 from 的一批目录项，用于按哈希位置迭代
+///
+/// 只读取 from 的哈希所在的叶子，以及该哈希延续到的后续叶子。返回按位置排序的
+/// (哈希位置, 字节偏移) 和下一批的起始哈希位置（没有更多时为 EXT4_DIR_HASH_POS_EOF）。
+/// 索引不能使用或索引根的哈希版本与超级块不同时返回 Err(ENOTSUP)，调用者应扫描整个目录。
+pub(crate) unsafe fn ext4_dir_dx_hash_batch(
+    inode_ref: *mut Ext4InodeRef,
+    from: u64,
+) -> Result<(Vec<(u64, u64)>, u64), i32> {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let key = (((from & !EXT4_DIR_HASH_POS_FLAG) >> 32) as u32) << 1;
+        let mut frames = Vec::new();
+        let probe = dx_probe(inode_ref, |sb, version| (version == sb.default_hash_version).then_some(key), &mut frames);
+        let mut items = Vec::new();
+        let mut next = EXT4_DIR_HASH_POS_EOF;
+        let r = match probe {
+            Err(r) => r,
+            Ok((_, mut leaf)) => {
+                // "." 和 ".." 位于索引根的开头
+                let dot_len = ext4_dir_en_get_entry_len(frames[0].block.data as *const Ext4DirEntry) as u64;
+                items.extend(
+                    [(EXT4_DIR_HASH_POS_FLAG, 0), (EXT4_DIR_HASH_POS_FLAG | 1, dot_len)]
+                        .into_iter()
+                        .filter(|&(pos, _)| pos >= from),
+                );
+                loop {
+                    let r = ext4_dir_collect_hash_pos(inode_ref, leaf, from, &mut items);
+                    if r != EOK {
+                        break r;
+                    }
+                    match dx_next_leaf(inode_ref, &mut frames) {
+                        Err(r) => break r,
+                        Ok(None) => break EOK,
+                        // 哈希最低位为1表示前一个叶子的最后一个哈希延续到该叶子
+                        Ok(Some((hash, block))) if hash & 1 != 0 => leaf = block,
+                        Ok(Some((hash, _))) => {
+                            next = EXT4_DIR_HASH_POS_FLAG | ((hash >> 1) as u64) << 32;
+                            break EOK;
+                        }
+                    }
+                }
+            }
+        };
+        let r = dx_put_frames(fs, &mut frames, r);
+        if r != EOK {
+            return Err(r);
+        }
+        if next <= from {
+            ext4_dbg!(DEBUG_DIR_IDX, Warn, "unsorted index: inode {}, from={:#x}", (*inode_ref).index, from);
+            return Err(ENOTSUP);
+        }
+        items.sort_unstable();
+        Ok((items, next))
+    }
+}
+
+/// 按 htree 索引查找目录项
+///
+/// 成功时 result 持有目录项所在块的引用。目录不能按索引访问，或同一哈希的目录项
+/// 延续到下一个叶子时返回 ENOTSUP，调用者应按线性目录查找。
+pub unsafe fn ext4_dir_dx_find_entry(
+    result: *mut Ext4DirSearchResult,
+    parent: *mut Ext4InodeRef,
+    name: *const u8,
+    name_len: u32,
+) -> i32 {
+    unsafe {
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+        let mut frames = Vec::new();
+        let key = |sb: &Ext4Superblock, version| dx_hash(sb, version, core::slice::from_raw_parts(name, name_len as usize));
+        let probe = dx_probe(parent, key, &mut frames);
+        let next_hash = dx_next_hash(&frames);
+        let r = dx_put_frames(fs, &mut frames, EOK);
+        let (hash, leaf) = match probe {
+            Ok(found) if r == EOK => found,
+            Ok(_) => return r,
+            Err(err) => return err,
+        };
+
+        let mut b = Ext4Block::new();
+        let r = dx_get_block(parent, leaf, &mut b);
+        if r != EOK {
+            return r;
+        }
+        if !ext4_dir_csum_verify(parent, b.data as *mut Ext4DirEntry) {
+            ext4_dbg!(DEBUG_DIR_IDX, Warn, "leaf block checksum failed: inode {}, block {}", (*parent).index, leaf);
+        }
+        let mut res_entry: *mut Ext4DirEntry = ptr::null_mut();
+        let r = ext4_dir_find_in_block(&mut b, sb, name, name_len as usize, &mut res_entry);
+        if r == EOK {
+            (*result).block = b;
+            (*result).dentry = res_entry;
+            (*result).dentry_ino = u32::from_le((*res_entry).inode);
+            return EOK;
+        }
+        let r2 = ext4_block_set((*fs).bdev, &mut b);
+        if r != ENOENT {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+        if next_hash.is_some_and(|next| next & !1 == hash) {
+            return ENOTSUP;
+        }
+        ENOENT
+    }
+}
+
+/// 追加一个新的中间节点块（空目录项占满整个块），返回其逻辑块号
+unsafe fn dx_append_node(inode_ref: *mut Ext4InodeRef, b: *mut Ext4Block) -> Result<u32, i32> {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let block_size = ext4_sb_get_block_size(&(*fs).sb) as usize;
+        let mut fblock = 0u64;
+        let mut iblock = 0u32;
+        let r = ext4_fs_append_inode_dblk(inode_ref, &mut fblock, &mut iblock);
+        if r != EOK {
+            return Err(r);
+        }
+        let r = ext4_block_get_noread((*fs).bdev, b, fblock);
+        if r != EOK {
+            return Err(r);
+        }
+        ptr::write_bytes((*b).data, 0, block_size);
+        ext4_dir_en_set_entry_len((*b).data as *mut Ext4DirEntry, block_size as u16);
+        Ok(iblock)
+    }
+}
+
+/// 保证最底层的索引块还能插入一项
+///
+/// 从最底层向上找到第一个未满的索引块，其下已满的中间节点自上而下逐个分裂为两个，
+/// 后一半的起始哈希插入上一层，路径随目标索引项进入相应的一半。路径上全部已满时，
+/// 根的索引项移到新的中间节点，索引增加一层；已达到最大层数时返回 ENOSPC。
+unsafe fn dx_make_room(inode_ref: *mut Ext4InodeRef, frames: &mut Vec<DxFrame>) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let full = |f: &DxFrame| f.count() >= f.limit();
+        if !full(frames.last().unwrap()) {
+            return EOK;
+        }
+        let mut first = frames.len() - 1;
+        while first > 0 && full(&frames[first - 1]) {
+            first -= 1;
+        }
+
+        let node_limit = dx_limit(sb, DX_NODE_COUNT_OFFSET);
+        if first == 0 {
+            let levels = frames.len() as u8 - 1;
+            if levels >= dx_max_levels(sb) {
+                ext4_dbg!(DEBUG_DIR_IDX, Warn, "directory index full: inode {}", (*inode_ref).index);
+                return ENOSPC;
+            }
+            let mut nb = Ext4Block::new();
+            let iblock = match dx_append_node(inode_ref, &mut nb) {
+                Ok(iblock) => iblock,
+                Err(r) => return r,
+            };
+            let root = &mut frames[0];
+            let count = root.count();
+            ptr::copy_nonoverlapping(
+                root.block.data.add(DX_ROOT_COUNT_OFFSET),
+                nb.data.add(DX_NODE_COUNT_OFFSET),
+                count * DX_ENTRY_SIZE,
+            );
+            put16(nb.data, DX_NODE_COUNT_OFFSET, node_limit as u16);
+            put16(nb.data, DX_NODE_COUNT_OFFSET + 2, count as u16);
+            root.set_count(1);
+            put32(root.block.data, DX_ROOT_COUNT_OFFSET + 4, iblock);
+            *root.block.data.add(DX_ROOT_INFO_OFFSET + 6) = levels + 1;
+            let at = root.at;
+            root.at = 0;
+            dx_dirty(inode_ref, &mut root.block);
+            dx_dirty(inode_ref, &mut nb);
+            frames.insert(1, DxFrame { block: nb, count_off: DX_NODE_COUNT_OFFSET, at });
+            ext4_dbg!(DEBUG_DIR_IDX, Debug, "index grows to {} levels: inode {}", levels + 2, (*inode_ref).index);
+            // 新节点的上限大于根，不需要分裂
+            first = 2;
+        }
+
+        for level in first..frames.len() {
+            let mut nb = Ext4Block::new();
+            let iblock = match dx_append_node(inode_ref, &mut nb) {
+                Ok(iblock) => iblock,
+                Err(r) => return r,
+            };
+            let (upper, lower) = frames.split_at_mut(level);
+            let (parent, node) = (upper.last_mut().unwrap(), &mut lower[0]);
+            let count = node.count();
+            let split = count / 2;
+            let split_hash = node.hash(split);
+            ptr::copy_nonoverlapping(
+                node.block.data.add(DX_NODE_COUNT_OFFSET + split * DX_ENTRY_SIZE),
+                nb.data.add(DX_NODE_COUNT_OFFSET),
+                (count - split) * DX_ENTRY_SIZE,
+            );
+            put16(nb.data, DX_NODE_COUNT_OFFSET, node_limit as u16);
+            put16(nb.data, DX_NODE_COUNT_OFFSET + 2, (count - split) as u16);
+            node.set_count(split);
+            parent.insert(split_hash, iblock);
+            if node.at >= split {
+                core::mem::swap(&mut node.block, &mut nb);
+                node.at -= split;
+                parent.at += 1;
+            }
+            dx_dirty(inode_ref, &mut parent.block);
+            dx_dirty(inode_ref, &mut node.block);
+            dx_dirty(inode_ref, &mut nb);
+            let r = ext4_block_set((*fs).bdev, &mut nb);
+            if r != EOK {
+                return r;
+            }
+            ext4_dbg!(DEBUG_DIR_IDX, Debug, "split index node: inode {}, level {}, new block {}", (*inode_ref).index, level, iblock);
+        }
+        EOK
+    }
+}
+
+/// 分裂已满的叶子块并插入目录项（对应 Linux do_split）
+///
+/// 按哈希排序后，把占用空间约一半的高哈希目录项移到新追加的叶子块，
+/// 新块的起始哈希插入最底层的索引块（与前一项哈希相同时置延续位）。
+#[allow(clippy::too_many_arguments)]
+unsafe fn dx_split_leaf(
+    inode_ref: *mut Ext4InodeRef,
+    frames: &mut Vec<DxFrame>,
+    leaf: *mut Ext4Block,
+    hash: u32,
+    child: *mut Ext4InodeRef,
+    name: *const u8,
+    name_len: u32,
+) -> i32 {
+    unsafe {
+        let r = dx_make_room(inode_ref, frames);
+        if r != EOK {
+            return r;
+        }
+
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        let data = (*leaf).data;
+        let usable = ext4_dir_block_usable_len(inode_ref, data);
+        let root_version = *frames[0].block.data.add(DX_ROOT_INFO_OFFSET + 4);
+
+        let mut map = Vec::new();
+        let mut off = 0;
+        while off < usable {
+            let de = data.add(off) as *mut Ext4DirEntry;
+            if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                return EIO;
+            }
+            if u32::from_le((*de).inode) != 0 {
+                let len = ext4_dir_en_get_name_len(sb, de) as usize;
+                let de_name = core::slice::from_raw_parts((*de).name_mut_ptr(), len);
+                let h = dx_hash(sb, root_version, de_name).unwrap_or(0);
+                map.push((h, off, ext4_dir_entry_used_len(len as u32) as usize));
+            }
+            off += ext4_dir_en_get_entry_len(de) as usize;
+        }
+        if map.len() < 2 {
+            return ENOSPC;
+        }
+        map.sort_by_key(|e| e.0);
+
+        // 从哈希最大的一端起，移动到新块的部分不超过半个块
+        let mut size = 0;
+        let mut split = map.len();
+        while split > 0 && size + map[split - 1].2 / 2 <= block_size / 2 {
+            size += map[split - 1].2;
+            split -= 1;
+        }
+        if split == 0 || split == map.len() {
+            split = map.len() / 2;
+        }
+        let split_hash = map[split].0;
+        let continued = (split_hash == map[split - 1].0) as u32;
+
+        let mut fblock = 0u64;
+        let mut iblock = 0u32;
+        let r = ext4_fs_append_inode_dblk(inode_ref, &mut fblock, &mut iblock);
+        if r != EOK {
+            return r;
+        }
+        let mut nb = Ext4Block::new();
+        let r = ext4_block_get_noread((*fs).bdev, &mut nb, fblock);
+        if r != EOK {
+            return r;
+        }
+        let copy = core::slice::from_raw_parts(data, block_size).to_vec();
+        let entries: Vec<&[u8]> = map.iter().map(|&(_, off, len)| &copy[off..off + len]).collect();
+        ext4_dir_write_leaf(inode_ref, nb.data, &entries[split..]);
+        ext4_dir_write_leaf(inode_ref, data, &entries[..split]);
+        ext4_block_set_dirty(&mut nb);
+        ext4_block_set_dirty(leaf);
+
+        let bottom = frames.last_mut().unwrap();
+        bottom.insert(split_hash | continued, iblock);
+        dx_dirty(inode_ref, &mut bottom.block);
+        ext4_dbg!(DEBUG_DIR_IDX, Debug, "split leaf: inode {}, new block {}, hash {:#x}", (*inode_ref).index, iblock, split_hash);
+
+        let target = if hash >= split_hash { &mut nb as *mut Ext4Block } else { leaf };
+        let r = ext4_dir_try_insert_entry(sb, inode_ref, target, child, name, name_len);
+        let r2 = ext4_block_set((*fs).bdev, &mut nb);
+        if r != EOK { r } else { r2 }
+    }
+}
+
+/// 按 htree 索引添加目录项
+///
+/// 叶子块已满时按哈希分裂。目录不能按索引访问时返回 ENOTSUP，调用者应按线性目录处理；
+/// 索引已满时返回 ENOSPC。
+pub unsafe fn ext4_dir_dx_add_entry(
+    parent: *mut Ext4InodeRef,
+    name: *const u8,
+    name_len: u32,
+    child: *mut Ext4InodeRef,
+) -> i32 {
+    unsafe {
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+        let mut frames = Vec::new();
+        let key = |sb: &Ext4Superblock, version| dx_hash(sb, version, core::slice::from_raw_parts(name, name_len as usize));
+        let (hash, leaf) = match dx_probe(parent, key, &mut frames) {
+            Ok(found) => found,
+            Err(r) => return dx_put_frames(fs, &mut frames, r),
+        };
+
+        let mut b = Ext4Block::new();
+        let r = dx_get_block(parent, leaf, &mut b);
+        if r != EOK {
+            return dx_put_frames(fs, &mut frames, r);
+        }
+        if !ext4_dir_csum_verify(parent, b.data as *mut Ext4DirEntry) {
+            ext4_dbg!(DEBUG_DIR_IDX, Warn, "leaf block checksum failed: inode {}, block {}", (*parent).index, leaf);
+        }
+        let mut r = ext4_dir_try_insert_entry(sb, parent, &mut b, child, name, name_len);
+        if r == ENOSPC {
+            r = dx_split_leaf(parent, &mut frames, &mut b, hash, child, name, name_len);
+        }
+        let r2 = ext4_block_set((*fs).bdev, &mut b);
+        dx_put_frames(fs, &mut frames, if r == EOK { r2 } else { r })
+    }
+}
+
+/// 把只有一个块的线性目录转换为 htree 索引目录（对应 Linux make_indexed_dir）
+///
+/// ".." 之后的目录项移到新追加的叶子块，第一个块改为只有一项的索引根。
+/// 未启用 dir_index、哈希版本不支持或目录不符合转换条件时返回 ENOTSUP。
+pub unsafe fn ext4_dir_dx_init(parent: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let fs = (*parent).fs;
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        if !dx_supported(parent)
+            || ext4_dir_hash_version(sb).is_none()
+            || ext4_inode_has_flag((*parent).inode, EXT4_INODE_FLAG_INDEX)
+            || ext4_inode_get_size(sb, (*parent).inode) != block_size as u64
+        {
+            return ENOTSUP;
+        }
+
+        let mut b = Ext4Block::new();
+        let r = dx_get_block(parent, 0, &mut b);
+        if r != EOK {
+            return r;
+        }
+        let data = b.data;
+        let usable = ext4_dir_block_usable_len(parent, data);
+        // "." 和 ".." 必须是前两个目录项，"." 只占12字节
+        let dot = data as *mut Ext4DirEntry;
+        let dotdot = data.add(12) as *mut Ext4DirEntry;
+        let is_name = |de: *mut Ext4DirEntry, name: &[u8]| {
+            ext4_dir_en_get_name_len(sb, de) as usize == name.len()
+                && core::slice::from_raw_parts((*de).name_mut_ptr(), name.len()) == name
+        };
+        if ext4_dir_en_get_entry_len(dot) != 12
+            || !is_name(dot, b".")
+            || !ext4_dir_entry_is_valid(sb, dotdot, 12, usable)
+            || !is_name(dotdot, b"..")
+        {
+            ext4_block_set((*fs).bdev, &mut b);
+            return ENOTSUP;
+        }
+
+        let mut entries = Vec::new();
+        let mut off = 12 + ext4_dir_en_get_entry_len(dotdot) as usize;
+        while off < usable {
+            let de = data.add(off) as *mut Ext4DirEntry;
+            if !ext4_dir_entry_is_valid(sb, de, off, block_size) {
+                ext4_block_set((*fs).bdev, &mut b);
+                return EIO;
+            }
+            if u32::from_le((*de).inode) != 0 {
+                let used = ext4_dir_entry_used_len(ext4_dir_en_get_name_len(sb, de) as u32) as usize;
+                entries.push((off, used));
+            }
+            off += ext4_dir_en_get_entry_len(de) as usize;
+        }
+        if entries.is_empty() {
+            ext4_block_set((*fs).bdev, &mut b);
+            return ENOTSUP;
+        }
+
+        let mut fblock = 0u64;
+        let mut iblock = 0u32;
+        let mut r = ext4_fs_append_inode_dblk(parent, &mut fblock, &mut iblock);
+        let mut nb = Ext4Block::new();
+        if r == EOK {
+            r = ext4_block_get_noread((*fs).bdev, &mut nb, fblock);
+        }
+        if r != EOK {
+            ext4_block_set((*fs).bdev, &mut b);
+            return r;
+        }
+        let copy = core::slice::from_raw_parts(data, block_size).to_vec();
+        let entries: Vec<&[u8]> = entries.iter().map(|&(off, len)| &copy[off..off + len]).collect();
+        ext4_dir_write_leaf(parent, nb.data, &entries);
+        ext4_block_set_dirty(&mut nb);
+        let r = ext4_block_set((*fs).bdev, &mut nb);
+        if r != EOK {
+            ext4_block_set((*fs).bdev, &mut b);
+            return r;
+        }
+
+        ext4_dir_en_set_entry_len(dotdot, (block_size - 12) as u16);
+        ptr::write_bytes(data.add(DX_ROOT_INFO_OFFSET), 0, block_size - DX_ROOT_INFO_OFFSET);
+        *data.add(DX_ROOT_INFO_OFFSET + 4) = sb.default_hash_version;
+        *data.add(DX_ROOT_INFO_OFFSET + 5) = DX_ROOT_INFO_LEN;
+        put16(data, DX_ROOT_COUNT_OFFSET, dx_limit(sb, DX_ROOT_COUNT_OFFSET) as u16);
+        put16(data, DX_ROOT_COUNT_OFFSET + 2, 1);
+        put32(data, DX_ROOT_COUNT_OFFSET + 4, iblock);
+        ext4_inode_set_flag((*parent).inode, EXT4_INODE_FLAG_INDEX);
+        (*parent).dirty = true;
+        dx_dirty(parent, &mut b);
+        ext4_dbg!(DEBUG_DIR_IDX, Debug, "directory converted to htree: inode {}", (*parent).index);
+        ext4_block_set((*fs).bdev, &mut b)
+    }
+}
+
+/// 按哈希重建 htree 目录的全部块（压缩目录时使用），返回从逻辑块0起的块内容
+///
+/// entries 为 "." 和 ".." 之外的目录项（原样的字节，长度为占用长度）。叶子块按哈希顺序
+/// 尽量填满，叶子块数超过根的上限时增加中间节点层。目录不能使用索引或最大层数的索引放不下时
+/// 返回 None。
+pub(crate) unsafe fn ext4_dir_dx_build(
+    parent: *mut Ext4InodeRef,
+    dot: &[u8],
+    dotdot: &[u8],
+    entries: &[&[u8]],
+) -> Option<Vec<Vec<u8>>> {
+    unsafe {
+        let sb = &(*(*parent).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        if !dx_supported(parent) || ext4_dir_hash_version(sb).is_none() || entries.is_empty() {
+            return None;
+        }
+        let version = sb.default_hash_version;
+        let mut sorted = Vec::with_capacity(entries.len());
+        for &entry in entries {
+            let de = entry.as_ptr() as *const Ext4DirEntry;
+            let name = &entry[EXT4_DIR_ENTRY_HEADER_SIZE..][..ext4_dir_en_get_name_len(sb, de) as usize];
+            sorted.push((dx_hash(sb, version, name)?, entry));
+        }
+        sorted.sort_by_key(|e| e.0);
+        let ordered: Vec<&[u8]> = sorted.iter().map(|e| e.1).collect();
+
+        // 各叶子块的（起始哈希, 内容），起始哈希与前一块的最后一项相同时置延续位
+        let leaves: Vec<(u32, Vec<u8>)> = ext4_dir_pack_blocks(parent, &ordered)
+            .into_iter()
+            .map(|(first, block)| {
+                let hash = sorted[first].0;
+                let continued = first > 0 && sorted[first - 1].0 == hash;
+                (hash | continued as u32, block)
+            })
+            .collect();
+        let root_limit = dx_limit(sb, DX_ROOT_COUNT_OFFSET);
+        let node_limit = dx_limit(sb, DX_NODE_COUNT_OFFSET);
+        // 各层中间节点数（自下而上）
+        let mut level_sizes = Vec::new();
+        let mut n = leaves.len();
+        while n > root_limit {
+            n = n.div_ceil(node_limit);
+            level_sizes.push(n);
+        }
+        if level_sizes.len() > dx_max_levels(sb) as usize {
+            return None;
+        }
+        let nodes: usize = level_sizes.iter().sum();
+
+        let mut root = vec![0u8; block_size];
+        ptr::copy_nonoverlapping(dot.as_ptr(), root.as_mut_ptr(), 12);
+        ptr::copy_nonoverlapping(dotdot.as_ptr(), root.as_mut_ptr().add(12), 12);
+        ext4_dir_en_set_entry_len(root.as_mut_ptr() as *mut Ext4DirEntry, 12);
+        ext4_dir_en_set_entry_len(root.as_mut_ptr().add(12) as *mut Ext4DirEntry, (block_size - 12) as u16);
+        root[DX_ROOT_INFO_OFFSET + 4] = version;
+        root[DX_ROOT_INFO_OFFSET + 5] = DX_ROOT_INFO_LEN;
+        root[DX_ROOT_INFO_OFFSET + 6] = level_sizes.len() as u8;
+
+        // 向索引块写入（哈希, 逻辑块号）列表
+        let fill = |block: *mut u8, count_off: usize, limit: usize, items: &[(u32, u32)]| {
+            put16(block, count_off, limit as u16);
+            put16(block, count_off + 2, items.len() as u16);
+            for (i, &(hash, iblock)) in items.iter().enumerate() {
+                if i > 0 {
+                    put32(block, count_off + i * DX_ENTRY_SIZE, hash);
+                }
+                put32(block, count_off + i * DX_ENTRY_SIZE + 4, iblock);
+            }
+        };
+        // 块布局：根、自上而下的各层中间节点、叶子
+        let first_leaf = 1 + nodes as u32;
+        let mut items: Vec<(u32, u32)> =
+            leaves.iter().enumerate().map(|(i, leaf)| (leaf.0, first_leaf + i as u32)).collect();
+        let mut levels: Vec<Vec<Vec<u8>>> = Vec::with_capacity(level_sizes.len());
+        let mut level_start = first_leaf;
+        for &size in &level_sizes {
+            level_start -= size as u32;
+            let mut level = Vec::with_capacity(size);
+            let mut upper = Vec::with_capacity(size);
+            for (i, chunk) in items.chunks(node_limit).enumerate() {
+                let mut node = vec![0u8; block_size];
+                ext4_dir_en_set_entry_len(node.as_mut_ptr() as *mut Ext4DirEntry, block_size as u16);
+                fill(node.as_mut_ptr(), DX_NODE_COUNT_OFFSET, node_limit, chunk);
+                level.push(node);
+                upper.push((chunk[0].0, level_start + i as u32));
+            }
+            levels.push(level);
+            items = upper;
+        }
+        fill(root.as_mut_ptr(), DX_ROOT_COUNT_OFFSET, root_limit, &items);
+        let mut blocks = Vec::with_capacity(1 + nodes + leaves.len());
+        blocks.push(root);
+        blocks.extend(levels.into_iter().rev().flatten());
+        for block in &mut blocks {
+            ext4_dir_dx_set_csum(parent, block.as_mut_ptr());
+        }
+        blocks.extend(leaves.into_iter().map(|leaf| leaf.1));
+        Some(blocks)
+    }
+}
@@ -1,43 +1,407 @@
 //! 错误处理模块
+//!
+//! C 风格接口直接返回 errno 错误码（EOK 表示成功），各模块遵循统一的约定：
+//! - EIO：磁盘上的元数据损坏（校验失败、结构非法、引用越界）或设备读写失败
+//! - EINVAL：调用者传入的参数非法
+//! - ENOENT / EEXIST：名称不存在 / 已存在
+//! - ENOSPC：没有空闲的块、inode 或目录项空间
+//! - ENXIO：访问超出块设备范围
+//! - ENOTSUP：不支持的文件系统特性或格式
+//! - EFSCORRUPTED：挂载时 superblock 中的几何参数不合法
+//! - ENAMETOOLONG、EFBIG、EROFS、ENOMEM 等：含义与 POSIX 相同
+
+use core::{
+    error::Error,
+    fmt::{Debug, Display},
+};
 
-use core::fmt;
 use crate::consts::*;
 
-/// ext4 错误类型
-#[derive(Debug, Clone)]
+/// 错误类别，与 POSIX errno 一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ext4ErrorKind {
+    /// EPERM
+    NotPermitted,
+    /// ENOENT
+    NotFound,
+    /// EIO
+    Io,
+    /// ENXIO
+    OutOfBounds,
+    /// ENOMEM
+    NoMemory,
+    /// EEXIST
+    AlreadyExists,
+    /// EXDEV
+    CrossesDevices,
+    /// ENODEV
+    NoDevice,
+    /// ENOTDIR
+    NotADirectory,
+    /// EISDIR
+    IsADirectory,
+    /// EINVAL
+    InvalidInput,
+    /// EFBIG
+    FileTooLarge,
+    /// ENOSPC
+    NoSpace,
+    /// EROFS
+    ReadOnly,
+    /// ENAMETOOLONG
+    NameTooLong,
+    /// ENOTEMPTY
+    DirectoryNotEmpty,
+    /// ENOTSUP
+    Unsupported,
+    /// ENOKEY（fscrypt 加密的 inode，没有可用的密钥）
+    Encrypted,
+    /// EMLINK
+    TooManyLinks,
+    /// ESTALE（文件句柄指向的 inode 已被释放或重新分配）
+    Stale,
+    /// EBUSY
+    Busy,
+    /// EFSCORRUPTED（superblock 等磁盘结构不合法）
+    Corrupted,
+    /// ECANCELED（长时间操作被中断回调取消）
+    Canceled,
+    /// ETIMEDOUT（长时间操作超过截止时间）
+    TimedOut,
+    /// 无法归类的错误码（原始值保存在 Ext4Error::code 中）
+    Other,
+}
+
+impl Ext4ErrorKind {
+    /// 对应的 errno（Other 按 EIO 处理）
+    pub const fn to_errno(self) -> i32 {
+        match self {
+            Self::NotPermitted => EPERM,
+            Self::NotFound => ENOENT,
+            Self::Io | Self::Other => EIO,
+            Self::OutOfBounds => ENXIO,
+            Self::NoMemory => ENOMEM,
+            Self::AlreadyExists => EEXIST,
+            Self::CrossesDevices => EXDEV,
+            Self::NoDevice => ENODEV,
+            Self::NotADirectory => ENOTDIR,
+            Self::IsADirectory => EISDIR,
+            Self::InvalidInput => EINVAL,
+            Self::FileTooLarge => EFBIG,
+            Self::NoSpace => ENOSPC,
+            Self::ReadOnly => EROFS,
+            Self::NameTooLong => ENAMETOOLONG,
+            Self::DirectoryNotEmpty => ENOTEMPTY,
+            Self::Unsupported => ENOTSUP,
+            Self::Encrypted => ENOKEY,
+            Self::TooManyLinks => EMLINK,
+            Self::Stale => ESTALE,
+            Self::Busy => EBUSY,
+            Self::Corrupted => EFSCORRUPTED,
+            Self::Canceled => ECANCELED,
+            Self::TimedOut => ETIMEDOUT,
+        }
+    }
+
+    /// 由 errno 得到错误类别（未知错误码和 EOK 归为 Other）
+    pub const fn from_errno(errno: i32) -> Self {
+        match errno {
+            EPERM => Self::NotPermitted,
+            ENOENT => Self::NotFound,
+            EIO => Self::Io,
+            ENXIO => Self::OutOfBounds,
+            ENOMEM => Self::NoMemory,
+            EEXIST => Self::AlreadyExists,
+            EXDEV => Self::CrossesDevices,
+            ENODEV => Self::NoDevice,
+            ENOTDIR => Self::NotADirectory,
+            EISDIR => Self::IsADirectory,
+            EINVAL => Self::InvalidInput,
+            EFBIG => Self::FileTooLarge,
+            ENOSPC => Self::NoSpace,
+            EROFS => Self::ReadOnly,
+            ENAMETOOLONG => Self::NameTooLong,
+            ENOTEMPTY => Self::DirectoryNotEmpty,
+            ENOTSUP => Self::Unsupported,
+            ENOKEY => Self::Encrypted,
+            EMLINK => Self::TooManyLinks,
+            ESTALE => Self::Stale,
+            EBUSY => Self::Busy,
+            EFSCORRUPTED => Self::Corrupted,
+            ECANCELED => Self::Canceled,
+            ETIMEDOUT => Self::TimedOut,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// 上下文链最多记录的操作帧数（超出时丢弃外层帧，只记录截断标记）
+const MAX_CONTEXT_DEPTH: usize = 3;
+/// Ext4Error中表示没有块地址
+const NO_BLOCK: u64 = u64::MAX;
+/// 路径分量最多保存的字节数（超出部分按字符边界截断）
+const MAX_SEGMENT_LEN: usize = 22;
+
+/// 错误上下文：出错的操作，以及可选的inode号、块地址和路径分量
+///
+/// 使用固定大小的内联存储，不依赖堆分配，内存不足时也能记录。
+#[derive(Clone, Copy)]
+pub struct ErrorContext {
+    op: &'static str,
+    ino: Option<u32>,
+    block: Option<u64>,
+    segment: Segment,
+}
+
+impl ErrorContext {
+    /// 创建只包含操作名的上下文
+    pub const fn new(op: &'static str) -> Self {
+        ErrorContext {
+            op,
+            ino: None,
+            block: None,
+            segment: Segment::EMPTY,
+        }
+    }
+
+    /// 附加inode号
+    pub const fn ino(mut self, ino: u32) -> Self {
+        self.ino = Some(ino);
+        self
+    }
+
+    /// 附加块地址（逻辑块号或物理块号，由操作决定）
+    pub const fn block(mut self, block: u64) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// 附加路径分量（目录项名称，可以不是UTF-8）
+    pub fn path_segment(mut self, name: impl AsRef<[u8]>) -> Self {
+        self.segment = Segment::new(name.as_ref());
+        self
+    }
+}
+
+/// 内联保存的路径分量
+#[derive(Clone, Copy)]
+struct Segment {
+    buf: [u8; MAX_SEGMENT_LEN],
+    len: u8,
+    truncated: bool,
+}
+
+impl Segment {
+    const EMPTY: Segment = Segment {
+        buf: [0; MAX_SEGMENT_LEN],
+        len: 0,
+        truncated: false,
+    };
+
+    fn new(name: &[u8]) -> Self {
+        // 按字符边界截断，非UTF-8的字节替换为U+FFFD
+        let mut segment = Segment::EMPTY;
+        let mut len = 0;
+        let chunks = name.utf8_chunks().flat_map(|chunk| {
+            let invalid = (!chunk.invalid().is_empty()).then_some(char::REPLACEMENT_CHARACTER);
+            chunk.valid().chars().chain(invalid)
+        });
+        for c in chunks {
+            if len + c.len_utf8() > MAX_SEGMENT_LEN {
+                segment.truncated = true;
+                break;
+            }
+            len += c.encode_utf8(&mut segment.buf[len..]).len();
+        }
+        segment.len = len as u8;
+        segment
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0 && !self.truncated
+    }
+
+    fn as_str(&self) -> &str {
+        // 构造时已按字符边界截断
+        core::str::from_utf8(&self.buf[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+/// ext4错误类型，包含错误类别、原始错误码和上下文链
+///
+/// 上下文链按从内到外的顺序记录操作名；inode号、块地址和路径分量
+/// 取最内层提供了该信息的帧（越深的帧越接近真正出错的位置）。
+#[derive(Clone)]
 pub struct Ext4Error {
-    pub code: i32,
-    pub message: Option<&'static str>,
+    pub kind: Ext4ErrorKind, // 错误类别
+    pub code: Option<i32>, // 原始错误码（由C接口返回码构造时保留）
+    /// 最外层的操作名（上下文链超出深度时也记录最外层的帧）
+    #[deprecated(note = "use Ext4Error::contexts() for the whole context chain")]
+    pub context: Option<&'static str>,
+    ops: [&'static str; MAX_CONTEXT_DEPTH], // 操作名（从内到外）
+    depth: u8, // 已记录的操作帧数
+    truncated: bool, // 是否有外层帧因超出深度被丢弃
+    ino: Option<u32>,
+    block: u64, // 出错的块地址（NO_BLOCK表示没有，使错误类型保持紧凑）
+    segment: Segment,
 }
 
 impl Ext4Error {
-    pub fn new(code: i32, message: impl Into<Option<&'static str>>) -> Self {
-        Self {
-            code,
-            message: message.into(),
+    /// 由错误码创建新的Ext4Error，context为最内层的操作名
+    pub fn new(code: i32, context: impl Into<Option<&'static str>>) -> Self {
+        Ext4Error {
+            code: Some(code),
+            ..Ext4Error::from_kind(Ext4ErrorKind::from_errno(code), context)
         }
     }
 
+    /// 由错误码创建不带上下文的Ext4Error
     pub fn from_code(code: i32) -> Self {
-        Self {
-            code,
-            message: None,
+        Self::new(code, None)
+    }
+
+    /// 由错误类别创建新的Ext4Error
+    pub fn from_kind(kind: Ext4ErrorKind, context: impl Into<Option<&'static str>>) -> Self {
+        #[allow(deprecated)]
+        let err = Ext4Error {
+            kind,
+            code: None,
+            context: None,
+            ops: [""; MAX_CONTEXT_DEPTH],
+            depth: 0,
+            truncated: false,
+            ino: None,
+            block: NO_BLOCK,
+            segment: Segment::EMPTY,
+        };
+        match context.into() {
+            Some(op) => err.with_context(ErrorContext::new(op)),
+            None => err,
         }
     }
-}
 
-impl fmt::Display for Ext4Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(msg) = self.message {
-            write!(f, "Ext4Error(code={}, msg={})", self.code, msg)
+    /// 在上下文链外层追加一帧
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        #[allow(deprecated)]
+        {
+            self.context = Some(context.op);
+        }
+        if (self.depth as usize) < MAX_CONTEXT_DEPTH {
+            self.ops[self.depth as usize] = context.op;
+            self.depth += 1;
         } else {
-            write!(f, "Ext4Error(code={})", self.code)
+            self.truncated = true;
         }
+        self.ino = self.ino.or(context.ino);
+        if let (NO_BLOCK, Some(block)) = (self.block, context.block) {
+            self.block = block;
+        }
+        if self.segment.is_empty() {
+            self.segment = context.segment;
+        }
+        self
+    }
+
+    /// 错误类别
+    pub fn kind(&self) -> Ext4ErrorKind {
+        self.kind
+    }
+
+    /// 对应的errno（优先使用原始错误码，也是返回给 C 调用者的值）
+    pub fn errno(&self) -> i32 {
+        self.code.unwrap_or(self.kind.to_errno())
+    }
+
+    /// 上下文链中的操作名（从内到外）
+    pub fn contexts(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.ops[..self.depth as usize].iter().copied()
+    }
+
+    /// 出错的inode号
+    pub fn ino(&self) -> Option<u32> {
+        self.ino
+    }
+
+    /// 出错的块地址
+    pub fn block(&self) -> Option<u64> {
+        (self.block != NO_BLOCK).then_some(self.block)
+    }
+
+    /// 补充出错的块地址（已有块地址时不变）
+    pub fn or_block(mut self, block: u64) -> Self {
+        if self.block == NO_BLOCK {
+            self.block = block;
+        }
+        self
+    }
+
+    /// 出错的路径分量（可能被截断）
+    pub fn path_segment(&self) -> Option<&str> {
+        (!self.segment.is_empty()).then(|| self.segment.as_str())
+    }
+}
+
+/// 从错误码转换为Ext4Error
+impl From<i32> for Ext4Error {
+    fn from(code: i32) -> Self {
+        Ext4Error::new(code, None)
     }
 }
 
+/// 从错误类别转换为Ext4Error
+impl From<Ext4ErrorKind> for Ext4Error {
+    fn from(kind: Ext4ErrorKind) -> Self {
+        Ext4Error::from_kind(kind, None)
+    }
+}
+
+/// 实现Display trait，用于格式化错误信息
+///
+/// 格式：`ext4 error 5 (Io): 内层操作 <- 外层操作 (ino 12, block 345, name "foo")`
+impl Display for Ext4Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ext4 error {} ({:?})", self.errno(), self.kind)?;
+        for (i, op) in self.contexts().enumerate() {
+            f.write_str(if i == 0 { ": " } else { " <- " })?;
+            f.write_str(op)?;
+        }
+        if self.truncated {
+            f.write_str(" <- ...")?;
+        }
+
+        let mut sep = " (";
+        if let Some(ino) = self.ino {
+            write!(f, "{sep}ino {ino}")?;
+            sep = ", ";
+        }
+        if let Some(block) = self.block() {
+            write!(f, "{sep}block {block}")?;
+            sep = ", ";
+        }
+        if let Some(name) = self.path_segment() {
+            let ellipsis = if self.segment.truncated { "..." } else { "" };
+            write!(f, "{sep}name {name:?}{ellipsis}")?;
+            sep = ", ";
+        }
+        if sep == ", " {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+/// 实现Debug trait，复用Display的实现
+impl Debug for Ext4Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// 实现Error trait，使Ext4Error符合标准错误类型
+impl Error for Ext4Error {}
+
 /// ext4 Result 类型
-pub type Ext4Result<T> = Result<T, Ext4Error>;
+pub type Ext4Result<T = ()> = Result<T, Ext4Error>;
 
 /// 辅助函数：检查返回码
 pub fn check_result(code: i32) -> Ext4Result<()> {
@@ -0,0 +1,1209 @@
+//! Extent 树模块
+//!
+//! 对应C实现: ext4_extent.c
+//!
+//! 根节点位于 inode 的 blocks 字段（最多4个条目），其余节点各占一个块。
+//! 叶子条目（ext4_extent）与索引条目（ext4_extent_index）都是12字节，
+//! 且第一个字段都是起始逻辑块，因此节点内的插入、删除按条目统一处理。
+
+use alloc::vec;
+use core::mem::size_of;
+use core::ptr;
+
+
+use crate::balloc::*;
+use crate::block::*;
+use crate::crc::ext4_crc32c;
+use crate::extent_status::*;
+use crate::quota::ext4_quota_charge_inode;
+use crate::inode::{
+    ext4_inode_csum_seed, ext4_inode_get_blocks_count, ext4_inode_has_flag, ext4_inode_set_blocks_count,
+};
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4Extent, Ext4ExtentHeader, Ext4ExtentIndex, Ext4InodeRef};
+
+/// 节点条目大小（extent 与 index 相同）
+const EXT4_EXTENT_ENTRY_SIZE: usize = 12;
+
+/// 根节点（inode 内）的最大条目数
+const EXT4_EXTENT_ROOT_MAX_ENTRIES: u16 = 4;
+
+const _: () = assert!(size_of::<Ext4Extent>() == EXT4_EXTENT_ENTRY_SIZE);
+const _: () = assert!(size_of::<Ext4ExtentIndex>() == EXT4_EXTENT_ENTRY_SIZE);
+const _: () = assert!(size_of::<Ext4ExtentHeader>() == EXT4_EXTENT_ENTRY_SIZE);
+
+// ===== 条目访问 =====
+
+/// extent 起始物理块
+pub fn ext4_ext_pblock(ex: &Ext4Extent) -> u64 {
+    u32::from_le(ex.start_lo) as u64 | (u16::from_le(ex.start_hi) as u64) << 32
+}
+
+/// 设置 extent 起始物理块
+pub fn ext4_ext_store_pblock(ex: &mut Ext4Extent, pb: u64) {
+    ex.start_lo = (pb as u32).to_le();
+    ex.start_hi = ((pb >> 32) as u16).to_le();
+}
+
+/// 索引指向的下一级节点物理块
+pub fn ext4_idx_pblock(ix: &Ext4ExtentIndex) -> u64 {
+    u32::from_le(ix.leaf_lo) as u64 | (u16::from_le(ix.leaf_hi) as u64) << 32
+}
+
+/// 设置索引指向的物理块
+pub fn ext4_idx_store_pblock(ix: &mut Ext4ExtentIndex, pb: u64) {
+    ix.leaf_lo = (pb as u32).to_le();
+    ix.leaf_hi = ((pb >> 32) as u16).to_le();
+}
+
+/// extent 是否为未写入（unwritten）状态
+pub fn ext4_ext_is_unwritten(ex: &Ext4Extent) -> bool {
+    u16::from_le(ex.block_count) as u32 > EXT4_EXT_MAX_LEN_INIT
+}
+
+/// extent 的实际长度
+pub fn ext4_ext_get_actual_len(ex: &Ext4Extent) -> u32 {
+    let len = u16::from_le(ex.block_count) as u32;
+    if len <= EXT4_EXT_MAX_LEN_INIT {
+        len
+    } else {
+        len - EXT4_EXT_MAX_LEN_INIT
+    }
+}
+
+/// 设置 extent 长度（保留未写入标志）
+fn ext4_ext_set_len(ex: &mut Ext4Extent, len: u32, unwritten: bool) {
+    let len = if unwritten { len + EXT4_EXT_MAX_LEN_INIT } else { len };
+    ex.block_count = (len as u16).to_le();
+}
+
+unsafe fn hdr_entries(hdr: *const Ext4ExtentHeader) -> u16 {
+    unsafe { u16::from_le((*hdr).entries_count) }
+}
+
+unsafe fn hdr_set_entries(hdr: *mut Ext4ExtentHeader, n: u16) {
+    unsafe { (*hdr).entries_count = n.to_le() };
+}
+
+unsafe fn hdr_max(hdr: *const Ext4ExtentHeader) -> u16 {
+    unsafe { u16::from_le((*hdr).max_entries_count) }
+}
+
+unsafe fn hdr_depth(hdr: *const Ext4ExtentHeader) -> u16 {
+    unsafe { u16::from_le((*hdr).depth) }
+}
+
+/// 节点中第 i 个条目的地址
+unsafe fn entry_ptr(hdr: *mut Ext4ExtentHeader, i: usize) -> *mut u8 {
+    unsafe { (hdr as *mut u8).add(EXT4_EXTENT_ENTRY_SIZE * (i + 1)) }
+}
+
+unsafe fn ext_at<'a>(hdr: *mut Ext4ExtentHeader, i: usize) -> &'a mut Ext4Extent {
+    unsafe { &mut *(entry_ptr(hdr, i) as *mut Ext4Extent) }
+}
+
+unsafe fn idx_at<'a>(hdr: *mut Ext4ExtentHeader, i: usize) -> &'a mut Ext4ExtentIndex {
+    unsafe { &mut *(entry_ptr(hdr, i) as *mut Ext4ExtentIndex) }
+}
+
+/// 第 i 个条目的起始逻辑块
+unsafe fn entry_key(hdr: *mut Ext4ExtentHeader, i: usize) -> u32 {
+    unsafe { u32::from_le(ptr::read(entry_ptr(hdr, i) as *const u32)) }
+}
+
+/// 最后一个起始逻辑块不大于 iblock 的条目
+unsafe fn ext4_ext_search(hdr: *mut Ext4ExtentHeader, iblock: u32) -> Option<usize> {
+    unsafe {
+        let n = hdr_entries(hdr) as usize;
+        (0..n).take_while(|&i| entry_key(hdr, i) <= iblock).last()
+    }
+}
+
+/// 根节点（inode 的 blocks 字段）
+unsafe fn ext4_ext_inode_hdr(inode_ref: *mut Ext4InodeRef) -> *mut Ext4ExtentHeader {
+    unsafe { (*(*inode_ref).inode).blocks.as_mut_ptr() as *mut Ext4ExtentHeader }
+}
+
+/// 块节点的最大条目数
+unsafe fn ext4_ext_block_max_entries(inode_ref: *mut Ext4InodeRef) -> u16 {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(&(*(*inode_ref).fs).sb) as usize;
+        ((block_size - size_of::<Ext4ExtentHeader>()) / EXT4_EXTENT_ENTRY_SIZE) as u16
+    }
+}
+
+// ===== 校验和 =====
+
+/// 计算 extent 块校验和（覆盖节点头和全部条目槽位）
+unsafe fn ext4_ext_block_csum(inode_ref: *mut Ext4InodeRef, hdr: *mut Ext4ExtentHeader) -> u32 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let gen = u32::from_le((*(*inode_ref).inode).generation);
+        let seed = ext4_inode_csum_seed(sb, (*inode_ref).index, gen);
+        let size = EXT4_EXTENT_ENTRY_SIZE * (hdr_max(hdr) as usize + 1);
+        ext4_crc32c(seed, core::slice::from_raw_parts(hdr as *const u8, size))
+    }
+}
+
+unsafe fn ext4_ext_tail(hdr: *mut Ext4ExtentHeader) -> *mut u32 {
+    unsafe { entry_ptr(hdr, hdr_max(hdr) as usize) as *mut u32 }
+}
+
+/// 更新 extent 块校验和
+unsafe fn ext4_extent_block_csum_set(inode_ref: *mut Ext4InodeRef, hdr: *mut Ext4ExtentHeader) {
+    unsafe {
+        if !ext4_sb_feature_ro_com(&(*(*inode_ref).fs).sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return;
+        }
+        *ext4_ext_tail(hdr) = ext4_ext_block_csum(inode_ref, hdr).to_le();
+    }
+}
+
+/// 重新计算 inode 全部 extent 块节点的校验和（启用 metadata_csum 时使用，不校验旧值）
+pub unsafe fn ext4_extent_tree_csum_set(inode_ref: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            return EIO;
+        }
+        ext4_ext_reown_tree(inode_ref, hdr)
+    }
+}
+
+unsafe fn ext4_extent_verify_block_csum(inode_ref: *mut Ext4InodeRef, hdr: *mut Ext4ExtentHeader) -> bool {
+    unsafe {
+        if !ext4_sb_feature_ro_com(&(*(*inode_ref).fs).sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return true;
+        }
+        u32::from_le(*ext4_ext_tail(hdr)) == ext4_ext_block_csum(inode_ref, hdr)
+    }
+}
+
+// ===== 节点读写 =====
+
+/// 读取 extent 块节点并检查节点头
+/// 叶子节点中各 extent 的逻辑块范围是否都在32位逻辑块号以内
+unsafe fn ext4_ext_leaf_in_range(hdr: *mut Ext4ExtentHeader) -> bool {
+    unsafe {
+        (0..hdr_entries(hdr) as usize).all(|i| {
+            let ex = ext_at(hdr, i);
+            u32::from_le(ex.first_block) as u64 + ext4_ext_get_actual_len(ex) as u64 <= 1 << 32
+        })
+    }
+}
+
+unsafe fn ext4_ext_get_node(
+    inode_ref: *mut Ext4InodeRef,
+    pblk: u64,
+    depth: u16,
+    b: *mut Ext4Block,
+) -> i32 {
+    unsafe {
+        let bdev = (*(*inode_ref).fs).bdev;
+        let r = ext4_block_get(bdev, b, pblk);
+        if r != EOK {
+            return r;
+        }
+        let hdr = (*b).data as *mut Ext4ExtentHeader;
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC
+            || hdr_depth(hdr) != depth
+            || hdr_max(hdr) == 0
+            || hdr_max(hdr) > ext4_ext_block_max_entries(inode_ref)
+            || hdr_entries(hdr) > hdr_max(hdr)
+            || (depth == 0 && !ext4_ext_leaf_in_range(hdr))
+        {
+            ext4_dbg!(DEBUG_EXTENT, Warn, "bad extent node: inode {}, block {}", (*inode_ref).index, pblk);
+            ext4_block_set(bdev, b);
+            return EIO;
+        }
+        if !ext4_extent_verify_block_csum(inode_ref, hdr) {
+            ext4_dbg!(DEBUG_EXTENT, Warn, "extent block checksum failed: inode {}, block {}", (*inode_ref).index, pblk);
+        }
+    }
+    EOK
+}
+
+/// 释放 extent 块节点（修改过时更新校验和并标记为脏）
+unsafe fn ext4_ext_put_node(inode_ref: *mut Ext4InodeRef, b: *mut Ext4Block, dirty: bool) -> i32 {
+    unsafe {
+        if dirty {
+            ext4_extent_block_csum_set(inode_ref, (*b).data as *mut Ext4ExtentHeader);
+            ext4_block_set_dirty(b);
+        }
+        ext4_block_set((*(*inode_ref).fs).bdev, b)
+    }
+}
+
+/// 分配并初始化一个新的 extent 块节点
+unsafe fn ext4_ext_new_node(
+    inode_ref: *mut Ext4InodeRef,
+    goal: u64,
+    depth: u16,
+    b: *mut Ext4Block,
+) -> i32 {
+    unsafe {
+        let mut pblk = 0u64;
+        let r = ext4_balloc_alloc_block(inode_ref, goal, &mut pblk);
+        if r != EOK {
+            return r;
+        }
+        let fs = (*inode_ref).fs;
+        let r = ext4_block_get_noread((*fs).bdev, b, pblk);
+        if r != EOK {
+            ext4_balloc_free_block(inode_ref, pblk);
+            return r;
+        }
+        ptr::write_bytes((*b).data, 0, ext4_sb_get_block_size(&(*fs).sb) as usize);
+        let hdr = (*b).data as *mut Ext4ExtentHeader;
+        (*hdr).magic = EXT4_EXTENT_MAGIC.to_le();
+        (*hdr).entries_count = 0;
+        (*hdr).max_entries_count = ext4_ext_block_max_entries(inode_ref).to_le();
+        (*hdr).depth = depth.to_le();
+        (*hdr).generation = 0;
+    }
+    EOK
+}
+
+/// 初始化 inode 中的 extent 树（空的根节点）
+pub unsafe fn ext4_extent_tree_init(inode_ref: *mut Ext4InodeRef) {
+    unsafe {
+        ext4_es_drop_inode(&mut (*(*inode_ref).fs).es_cache, (*inode_ref).index);
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        (*hdr).magic = EXT4_EXTENT_MAGIC.to_le();
+        (*hdr).entries_count = 0;
+        (*hdr).max_entries_count = EXT4_EXTENT_ROOT_MAX_ENTRIES.to_le();
+        (*hdr).depth = 0;
+        (*hdr).generation = 0;
+        (*inode_ref).dirty = true;
+    }
+}
+
+// ===== 查找 =====
+
+/// 查找逻辑块所在的 extent
+///
+/// 找到时返回 (物理块, 从 iblock 起的连续块数, 是否未写入)；
+/// 未找到时在 goal 中给出就近的物理块作为分配目标（无参考时为0）。
+unsafe fn ext4_ext_find(
+    inode_ref: *mut Ext4InodeRef,
+    iblock: u32,
+    goal: &mut u64,
+) -> Result<Option<(u64, u32, bool)>, i32> {
+    unsafe {
+        let bdev = (*(*inode_ref).fs).bdev;
+        let mut hdr = ext4_ext_inode_hdr(inode_ref);
+        let mut b = Ext4Block::new();
+        *goal = 0;
+
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            ext4_dbg!(DEBUG_EXTENT, Warn, "bad extent root: inode {}", (*inode_ref).index);
+            return Err(EIO);
+        }
+
+        loop {
+            let depth = hdr_depth(hdr);
+            let n = hdr_entries(hdr) as usize;
+            if depth == 0 {
+                let mut found = None;
+                match ext4_ext_search(hdr, iblock) {
+                    Some(i) => {
+                        let ex = ext_at(hdr, i);
+                        let first = u32::from_le(ex.first_block);
+                        let len = ext4_ext_get_actual_len(ex);
+                        let off = iblock - first;
+                        if off < len {
+                            // 映射超出文件系统范围的 extent 视为损坏
+                            if ext4_ext_pblock(ex) + len as u64 > ext4_sb_get_blocks_cnt(&(*(*inode_ref).fs).sb) {
+                                ext4_block_set(bdev, &mut b);
+                                return Err(EIO);
+                            }
+                            found = Some((ext4_ext_pblock(ex) + off as u64, len - off, ext4_ext_is_unwritten(ex)));
+                        } else {
+                            *goal = ext4_ext_pblock(ex) + off as u64;
+                        }
+                    }
+                    None if n > 0 => {
+                        let ex = ext_at(hdr, 0);
+                        let dist = (u32::from_le(ex.first_block) - iblock) as u64;
+                        *goal = ext4_ext_pblock(ex).saturating_sub(dist);
+                    }
+                    None if !b.buf.is_null() => *goal = b.lb_id + 1,
+                    None => {}
+                }
+                ext4_block_set(bdev, &mut b);
+                return Ok(found);
+            }
+
+            if n == 0 {
+                ext4_block_set(bdev, &mut b);
+                return Err(EIO);
+            }
+            let i = ext4_ext_search(hdr, iblock).unwrap_or(0);
+            let child = ext4_idx_pblock(idx_at(hdr, i));
+            let mut nb = Ext4Block::new();
+            let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut nb);
+            ext4_block_set(bdev, &mut b);
+            if r != EOK {
+                return Err(r);
+            }
+            b = nb;
+            hdr = b.data as *mut Ext4ExtentHeader;
+        }
+    }
+}
+
+/// 最后一个 extent 之后的逻辑块号（没有 extent 时为0）
+///
+/// 与 i_size 无关，可用于定位文件末尾之后的数据（如 fs-verity 元数据）。
+pub unsafe fn ext4_extent_logical_end(inode_ref: *mut Ext4InodeRef, end: &mut u32) -> i32 {
+    unsafe {
+        let bdev = (*(*inode_ref).fs).bdev;
+        let mut hdr = ext4_ext_inode_hdr(inode_ref);
+        let mut b = Ext4Block::new();
+        *end = 0;
+
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            return EIO;
+        }
+        loop {
+            let depth = hdr_depth(hdr);
+            let n = hdr_entries(hdr) as usize;
+            if n == 0 {
+                ext4_block_set(bdev, &mut b);
+                return if depth == 0 { EOK } else { EIO };
+            }
+            if depth == 0 {
+                let ex = ext_at(hdr, n - 1);
+                let last = u32::from_le(ex.first_block).checked_add(ext4_ext_get_actual_len(ex));
+                ext4_block_set(bdev, &mut b);
+                let Some(last) = last else {
+                    return EIO;
+                };
+                *end = last;
+                return EOK;
+            }
+            // 沿最右侧的索引下降
+            let child = ext4_idx_pblock(idx_at(hdr, n - 1));
+            let mut nb = Ext4Block::new();
+            let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut nb);
+            ext4_block_set(bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+            b = nb;
+            hdr = b.data as *mut Ext4ExtentHeader;
+        }
+    }
+}
+
+// ===== 插入 =====
+
+/// 将条目写入有空位的节点（按起始逻辑块排序）
+unsafe fn ext4_ext_insert_at(hdr: *mut Ext4ExtentHeader, entry: &[u8; EXT4_EXTENT_ENTRY_SIZE]) {
+    unsafe {
+        let n = hdr_entries(hdr) as usize;
+        let key = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+        let pos = (0..n).find(|&i| entry_key(hdr, i) > key).unwrap_or(n);
+        ptr::copy(entry_ptr(hdr, pos), entry_ptr(hdr, pos + 1), (n - pos) * EXT4_EXTENT_ENTRY_SIZE);
+        ptr::copy_nonoverlapping(entry.as_ptr(), entry_ptr(hdr, pos), EXT4_EXTENT_ENTRY_SIZE);
+        hdr_set_entries(hdr, n as u16 + 1);
+    }
+}
+
+/// 向节点插入条目，节点已满时分裂
+///
+/// 根节点已满时将其内容下移到新分配的块中，树的深度加一；
+/// 其他节点分裂后通过 split 返回新节点的 (起始逻辑块, 物理块)，由上一级插入索引。
+unsafe fn ext4_ext_insert_entry(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    is_root: bool,
+    entry: &[u8; EXT4_EXTENT_ENTRY_SIZE],
+    split: &mut Option<(u32, u64)>,
+) -> i32 {
+    unsafe {
+        let n = hdr_entries(hdr) as usize;
+        if n < hdr_max(hdr) as usize {
+            ext4_ext_insert_at(hdr, entry);
+            return EOK;
+        }
+
+        let depth = hdr_depth(hdr);
+        let mut goal = 0u64;
+        let r = ext4_balloc_bg_goal(inode_ref, &mut goal);
+        if r != EOK {
+            return r;
+        }
+        let mut nb = Ext4Block::new();
+        let r = ext4_ext_new_node(inode_ref, goal, depth, &mut nb);
+        if r != EOK {
+            return r;
+        }
+        let new_hdr = nb.data as *mut Ext4ExtentHeader;
+
+        if is_root {
+            // 根节点下移：原有条目全部移入新节点，根节点只保留一个索引
+            ptr::copy_nonoverlapping(entry_ptr(hdr, 0), entry_ptr(new_hdr, 0), n * EXT4_EXTENT_ENTRY_SIZE);
+            hdr_set_entries(new_hdr, n as u16);
+            ext4_ext_insert_at(new_hdr, entry);
+
+            ptr::write_bytes(entry_ptr(hdr, 0), 0, n * EXT4_EXTENT_ENTRY_SIZE);
+            (*hdr).depth = (depth + 1).to_le();
+            hdr_set_entries(hdr, 1);
+            let ix = idx_at(hdr, 0);
+            ix.first_block = entry_key(new_hdr, 0).to_le();
+            ext4_idx_store_pblock(ix, nb.lb_id);
+            (*inode_ref).dirty = true;
+            ext4_dbg!(DEBUG_EXTENT, Debug, "extent tree of inode {} grows to depth {}", (*inode_ref).index, depth + 1);
+            return ext4_ext_put_node(inode_ref, &mut nb, true);
+        }
+
+        // 在末尾追加时新节点只放新条目（顺序写入的常见情况），否则对半分裂
+        let key = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+        let pos = (0..n).find(|&i| entry_key(hdr, i) > key).unwrap_or(n);
+        let mid = if pos == n { n } else { n / 2 };
+        ptr::copy_nonoverlapping(entry_ptr(hdr, mid), entry_ptr(new_hdr, 0), (n - mid) * EXT4_EXTENT_ENTRY_SIZE);
+        ptr::write_bytes(entry_ptr(hdr, mid), 0, (n - mid) * EXT4_EXTENT_ENTRY_SIZE);
+        hdr_set_entries(new_hdr, (n - mid) as u16);
+        hdr_set_entries(hdr, mid as u16);
+        if pos >= mid {
+            ext4_ext_insert_at(new_hdr, entry);
+        } else {
+            ext4_ext_insert_at(hdr, entry);
+        }
+
+        *split = Some((entry_key(new_hdr, 0), nb.lb_id));
+        ext4_ext_put_node(inode_ref, &mut nb, true)
+    }
+}
+
+/// 在叶子中尝试与相邻 extent 合并
+unsafe fn ext4_ext_try_merge(hdr: *mut Ext4ExtentHeader, newext: &Ext4Extent) -> bool {
+    unsafe {
+        // 未写入的 extent 不能并入已写入的 extent（否则未清零的块变为可读）
+        if ext4_ext_is_unwritten(newext) {
+            return false;
+        }
+        let n = hdr_entries(hdr) as usize;
+        let first = u32::from_le(newext.first_block);
+        let len = ext4_ext_get_actual_len(newext);
+        let pblk = ext4_ext_pblock(newext);
+        let pos = (0..n).find(|&i| entry_key(hdr, i) > first).unwrap_or(n);
+
+        // 与前一个 extent 尾部相接
+        if pos > 0 {
+            let prev = ext_at(hdr, pos - 1);
+            let prev_len = ext4_ext_get_actual_len(prev);
+            if !ext4_ext_is_unwritten(prev)
+                && u32::from_le(prev.first_block) as u64 + prev_len as u64 == first as u64
+                && ext4_ext_pblock(prev) + prev_len as u64 == pblk
+                && prev_len + len <= EXT4_EXT_MAX_LEN_INIT
+            {
+                ext4_ext_set_len(prev, prev_len + len, false);
+                return true;
+            }
+        }
+        // 与后一个 extent 头部相接
+        if pos < n {
+            let next = ext_at(hdr, pos);
+            let next_len = ext4_ext_get_actual_len(next);
+            if !ext4_ext_is_unwritten(next)
+                && first as u64 + len as u64 == u32::from_le(next.first_block) as u64
+                && pblk + len as u64 == ext4_ext_pblock(next)
+                && next_len + len <= EXT4_EXT_MAX_LEN_INIT
+            {
+                next.first_block = first.to_le();
+                ext4_ext_store_pblock(next, pblk);
+                ext4_ext_set_len(next, next_len + len, false);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// 递归插入 extent
+unsafe fn ext4_ext_insert_rec(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    is_root: bool,
+    newext: &Ext4Extent,
+    split: &mut Option<(u32, u64)>,
+) -> i32 {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        if depth == 0 {
+            if ext4_ext_try_merge(hdr, newext) {
+                return EOK;
+            }
+            let entry: [u8; EXT4_EXTENT_ENTRY_SIZE] = core::mem::transmute(*newext);
+            return ext4_ext_insert_entry(inode_ref, hdr, is_root, &entry, split);
+        }
+
+        if hdr_entries(hdr) == 0 {
+            return EIO;
+        }
+        let first = u32::from_le(newext.first_block);
+        let i = ext4_ext_search(hdr, first).unwrap_or(0);
+        let child = ext4_idx_pblock(idx_at(hdr, i));
+        let mut b = Ext4Block::new();
+        let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut b);
+        if r != EOK {
+            return r;
+        }
+        let child_hdr = b.data as *mut Ext4ExtentHeader;
+        let mut child_split = None;
+        let r = ext4_ext_insert_rec(inode_ref, child_hdr, false, newext, &mut child_split);
+        // 索引的起始逻辑块与子节点的第一个条目保持一致
+        if hdr_entries(child_hdr) > 0 {
+            idx_at(hdr, i).first_block = entry_key(child_hdr, 0).to_le();
+        }
+        let r2 = ext4_ext_put_node(inode_ref, &mut b, true);
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+
+        if let Some((key, pblk)) = child_split {
+            let mut ix = Ext4ExtentIndex {
+                first_block: key.to_le(),
+                leaf_lo: 0,
+                leaf_hi: 0,
+                padding: 0,
+            };
+            ext4_idx_store_pblock(&mut ix, pblk);
+            let entry: [u8; EXT4_EXTENT_ENTRY_SIZE] = core::mem::transmute(ix);
+            return ext4_ext_insert_entry(inode_ref, hdr, is_root, &entry, split);
+        }
+    }
+    EOK
+}
+
+/// 将未写入的 extent 转换为已初始化（整个 extent 清零）
+unsafe fn ext4_ext_convert_rec(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    iblock: u32,
+) -> i32 {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        let Some(i) = ext4_ext_search(hdr, iblock) else {
+            return EIO;
+        };
+        if depth == 0 {
+            let ex = ext_at(hdr, i);
+            let len = ext4_ext_get_actual_len(ex);
+            let pblk = ext4_ext_pblock(ex);
+            let fs = (*inode_ref).fs;
+            let block_size = ext4_sb_get_block_size(&(*fs).sb);
+            let zero = vec![0u8; block_size as usize];
+            for blk in pblk..pblk + len as u64 {
+                let r = ext4_blocks_set_direct((*fs).bdev, zero.as_ptr() as _, blk, 1);
+                if r != EOK {
+                    return r;
+                }
+            }
+            ext4_ext_set_len(ex, len, false);
+            return EOK;
+        }
+
+        let child = ext4_idx_pblock(idx_at(hdr, i));
+        let mut b = Ext4Block::new();
+        let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut b);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_ext_convert_rec(inode_ref, b.data as *mut Ext4ExtentHeader, iblock);
+        let r2 = ext4_ext_put_node(inode_ref, &mut b, r == EOK);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 从包含 iblock 的未写入 extent 开头去掉一块（iblock 须是该 extent 的第一块）
+///
+/// extent 只有一块或 iblock 不在开头时不修改并返回 false。
+unsafe fn ext4_ext_trim_head_rec(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    iblock: u32,
+) -> Result<bool, i32> {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        let Some(i) = ext4_ext_search(hdr, iblock) else {
+            return Err(EIO);
+        };
+        if depth == 0 {
+            let ex = ext_at(hdr, i);
+            let len = ext4_ext_get_actual_len(ex);
+            if u32::from_le(ex.first_block) != iblock || len < 2 || !ext4_ext_is_unwritten(ex) {
+                return Ok(false);
+            }
+            let pblk = ext4_ext_pblock(ex);
+            ex.first_block = (iblock + 1).to_le();
+            ext4_ext_store_pblock(ex, pblk + 1);
+            ext4_ext_set_len(ex, len - 1, true);
+            return Ok(true);
+        }
+
+        let child = ext4_idx_pblock(idx_at(hdr, i));
+        let mut b = Ext4Block::new();
+        let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut b);
+        if r != EOK {
+            return Err(r);
+        }
+        let r = ext4_ext_trim_head_rec(inode_ref, b.data as *mut Ext4ExtentHeader, iblock);
+        let r2 = ext4_ext_put_node(inode_ref, &mut b, r == Ok(true));
+        if r2 != EOK {
+            return Err(r2);
+        }
+        r
+    }
+}
+
+/// 把未写入的逻辑块 iblock（物理块 pblk）转换为已写入，返回转换后从 iblock 起连续映射的块数
+///
+/// iblock 位于未写入 extent 开头时只转换这一块：清零后作为单独的已写入 extent 插入
+/// （可与前一个 extent 合并），其余部分仍为未写入，文件末尾之后的预分配块不会变为已写入。
+/// 否则清零并转换整个 extent。
+unsafe fn ext4_ext_convert_block(inode_ref: *mut Ext4InodeRef, iblock: u32, pblk: u64, count: u32) -> Result<u32, i32> {
+    unsafe {
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        (*inode_ref).dirty = true;
+        if !ext4_ext_trim_head_rec(inode_ref, hdr, iblock)? {
+            let r = ext4_ext_convert_rec(inode_ref, hdr, iblock);
+            return if r == EOK { Ok(count) } else { Err(r) };
+        }
+
+        let fs = (*inode_ref).fs;
+        let zero = vec![0u8; ext4_sb_get_block_size(&(*fs).sb) as usize];
+        let mut r = ext4_blocks_set_direct((*fs).bdev, zero.as_ptr() as _, pblk, 1);
+        if r == EOK {
+            let mut newext = Ext4Extent {
+                first_block: iblock.to_le(),
+                block_count: 0,
+                start_hi: 0,
+                start_lo: 0,
+            };
+            ext4_ext_set_len(&mut newext, 1, false);
+            ext4_ext_store_pblock(&mut newext, pblk);
+            let mut split = None;
+            r = ext4_ext_insert_rec(inode_ref, hdr, true, &newext, &mut split);
+        }
+        if r != EOK {
+            // 该块已从未写入 extent 中去掉，不再被引用
+            ext4_balloc_free_block(inode_ref, pblk);
+            return Err(r);
+        }
+        Ok(1)
+    }
+}
+
+/// 在 [iblock, iblock + n) 预分配紧接 pblk 之后的物理块，作为未写入 extent 插入
+///
+/// 只使用与 pblk 物理连续的块，遇到不连续或分配失败时停止；预分配是尽力而为的，失败不报错。
+unsafe fn ext4_ext_prealloc(inode_ref: *mut Ext4InodeRef, iblock: u32, pblk: u64, n: u32) {
+    unsafe {
+        let n = n.min(EXT4_EXT_MAX_LEN_UNWRITTEN).min(u32::MAX - iblock);
+        let mut len = 0u32;
+        while len < n {
+            let want = pblk + len as u64;
+            let mut blk = 0u64;
+            if ext4_balloc_alloc_block(inode_ref, want, &mut blk) != EOK {
+                break;
+            }
+            if blk != want {
+                ext4_balloc_free_block(inode_ref, blk);
+                break;
+            }
+            len += 1;
+        }
+        if len == 0 {
+            return;
+        }
+        if ext4_extent_insert_range(inode_ref, iblock, pblk, len, true) != EOK {
+            ext4_balloc_free_blocks(inode_ref, pblk, len);
+            return;
+        }
+        ext4_dbg!(
+            DEBUG_EXTENT,
+            Debug,
+            "ext4_ext_prealloc: inode {}, iblock={}, pblk={}, len={}",
+            (*inode_ref).index,
+            iblock,
+            pblk,
+            len
+        );
+    }
+}
+
+/// 获取逻辑块对应的物理块
+///
+/// 未映射时若 create 为真则分配新块并插入 extent 树，否则 result 为0；
+/// 未写入的 extent 在不创建时同样视为空洞。
+/// 已写入的映射记入 extent 状态缓存，再次查找时不遍历 extent 树。
+pub unsafe fn ext4_extent_get_blocks(
+    inode_ref: *mut Ext4InodeRef,
+    iblock: u32,
+    max_blocks: u32,
+    result: *mut u64,
+    create: bool,
+    blocks_count: *mut u32,
+) -> i32 {
+    unsafe {
+        *result = 0;
+        if !blocks_count.is_null() {
+            *blocks_count = 0;
+        }
+
+        let fs = (*inode_ref).fs;
+        let ino = (*inode_ref).index;
+        if let Some((pblk, count)) = ext4_es_lookup(&(*fs).es_cache, ino, iblock) {
+            (*fs).metrics.es_hits += 1;
+            *result = pblk;
+            if !blocks_count.is_null() {
+                *blocks_count = count.min(max_blocks);
+            }
+            return EOK;
+        }
+        (*fs).metrics.es_misses += 1;
+
+        let mut goal = 0u64;
+        let found = match ext4_ext_find(inode_ref, iblock, &mut goal) {
+            Ok(found) => found,
+            Err(r) => {
+                ext4_dbg!(
+                    DEBUG_EXTENT,
+                    Warn,
+                    "extent lookup failed: inode {}, iblock {}, r={}",
+                    (*inode_ref).index,
+                    iblock,
+                    r
+                );
+                return r;
+            }
+        };
+        ext4_dbg!(
+            DEBUG_EXTENT,
+            Trace,
+            "ext4_extent_get_blocks: inode {}, iblock {} -> {:?}",
+            (*inode_ref).index,
+            iblock,
+            found
+        );
+
+        if let Some((pblk, mut count, unwritten)) = found {
+            if unwritten {
+                if !create {
+                    return EOK;
+                }
+                count = match ext4_ext_convert_block(inode_ref, iblock, pblk, count) {
+                    Ok(count) => count,
+                    Err(r) => return r,
+                };
+            }
+            ext4_es_insert(&mut (*fs).es_cache, ino, iblock, count, pblk);
+            *result = pblk;
+            if !blocks_count.is_null() {
+                *blocks_count = count.min(max_blocks);
+            }
+            return EOK;
+        }
+
+        if !create {
+            return EOK;
+        }
+
+        // 有预分配提示且在最后一个 extent 之后分配（追加写入）时，随后预分配额外的块
+        let mut prealloc = (*fs).prealloc.get(&ino).copied().unwrap_or(0);
+        if prealloc > 0 {
+            let mut end = 0u32;
+            if ext4_extent_logical_end(inode_ref, &mut end) != EOK || iblock < end {
+                prealloc = 0;
+            }
+        }
+        if goal == 0 {
+            let r = ext4_balloc_bg_goal(inode_ref, &mut goal);
+            if r != EOK {
+                return r;
+            }
+        }
+        let mut pblk = 0u64;
+        let r = ext4_balloc_alloc_block(inode_ref, goal, &mut pblk);
+        if r != EOK {
+            return r;
+        }
+
+        let mut newext = Ext4Extent {
+            first_block: iblock.to_le(),
+            block_count: 0,
+            start_hi: 0,
+            start_lo: 0,
+        };
+        ext4_ext_set_len(&mut newext, 1, false);
+        ext4_ext_store_pblock(&mut newext, pblk);
+
+        let mut split = None;
+        let r = ext4_ext_insert_rec(inode_ref, ext4_ext_inode_hdr(inode_ref), true, &newext, &mut split);
+        (*inode_ref).dirty = true;
+        if r != EOK {
+            ext4_balloc_free_block(inode_ref, pblk);
+            return r;
+        }
+
+        ext4_dbg!(
+            DEBUG_EXTENT,
+            Debug,
+            "ext4_extent_get_blocks: inode {}, iblock {} mapped to new block {}",
+            (*inode_ref).index,
+            iblock,
+            pblk
+        );
+        ext4_es_insert(&mut (*fs).es_cache, ino, iblock, 1, pblk);
+        ext4_balloc_set_goal(fs, ino, pblk + 1);
+        if prealloc > 0 && iblock < u32::MAX {
+            ext4_ext_prealloc(inode_ref, iblock + 1, pblk + 1, prealloc);
+        }
+        *result = pblk;
+        if !blocks_count.is_null() {
+            *blocks_count = 1;
+        }
+    }
+    EOK
+}
+
+/// 将 [iblock, iblock + len) 映射到从 pblk 开始的连续物理块
+///
+/// 调用者需保证该逻辑范围尚未映射且物理块已被占用，不分配数据块（日志回放使用）。
+pub unsafe fn ext4_extent_insert_range(
+    inode_ref: *mut Ext4InodeRef,
+    iblock: u32,
+    pblk: u64,
+    len: u32,
+    unwritten: bool,
+) -> i32 {
+    unsafe {
+        let max_len = if unwritten { EXT4_EXT_MAX_LEN_UNWRITTEN } else { EXT4_EXT_MAX_LEN_INIT };
+        if len == 0 || len > max_len {
+            return EINVAL;
+        }
+        ext4_dbg!(
+            DEBUG_EXTENT,
+            Debug,
+            "ext4_extent_insert_range: inode {}, iblock={}, pblk={}, len={}",
+            (*inode_ref).index,
+            iblock,
+            pblk,
+            len
+        );
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            return EIO;
+        }
+
+        let mut newext = Ext4Extent {
+            first_block: iblock.to_le(),
+            block_count: 0,
+            start_hi: 0,
+            start_lo: 0,
+        };
+        ext4_ext_set_len(&mut newext, len, unwritten);
+        ext4_ext_store_pblock(&mut newext, pblk);
+
+        let mut split = None;
+        let r = ext4_ext_insert_rec(inode_ref, hdr, true, &newext, &mut split);
+        (*inode_ref).dirty = true;
+        ext4_es_remove(&mut (*(*inode_ref).fs).es_cache, (*inode_ref).index, iblock, iblock + (len - 1));
+        r
+    }
+}
+
+// ===== 删除 =====
+
+/// 从节点中删除第 i 个条目
+unsafe fn ext4_ext_remove_at(hdr: *mut Ext4ExtentHeader, i: usize) {
+    unsafe {
+        let n = hdr_entries(hdr) as usize;
+        ptr::copy(entry_ptr(hdr, i + 1), entry_ptr(hdr, i), (n - i - 1) * EXT4_EXTENT_ENTRY_SIZE);
+        ptr::write_bytes(entry_ptr(hdr, n - 1), 0, EXT4_EXTENT_ENTRY_SIZE);
+        hdr_set_entries(hdr, n as u16 - 1);
+    }
+}
+
+/// 递归删除 [from, to] 范围内的映射并释放对应的块
+unsafe fn ext4_ext_remove_rec(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    from: u32,
+    to: u32,
+) -> i32 {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        let mut i = 0usize;
+
+        if depth == 0 {
+            while i < hdr_entries(hdr) as usize {
+                let ex = ext_at(hdr, i);
+                let start = u32::from_le(ex.first_block);
+                let len = ext4_ext_get_actual_len(ex);
+                let end = start.saturating_add(len.saturating_sub(1));
+                let pblk = ext4_ext_pblock(ex);
+                let unwritten = ext4_ext_is_unwritten(ex);
+
+                if end < from || start > to {
+                    i += 1;
+                    continue;
+                }
+
+                let r;
+                if from <= start && end <= to {
+                    // 整个 extent 被删除
+                    r = ext4_balloc_free_blocks(inode_ref, pblk, len);
+                    ext4_ext_remove_at(hdr, i);
+                } else if start < from && end <= to {
+                    // 删除尾部
+                    r = ext4_balloc_free_blocks(inode_ref, pblk + (from - start) as u64, end - from + 1);
+                    ext4_ext_set_len(ex, from - start, unwritten);
+                    i += 1;
+                } else if from <= start {
+                    // 删除头部
+                    let cnt = to - start + 1;
+                    r = ext4_balloc_free_blocks(inode_ref, pblk, cnt);
+                    ex.first_block = (to + 1).to_le();
+                    ext4_ext_store_pblock(ex, pblk + cnt as u64);
+                    ext4_ext_set_len(ex, len - cnt, unwritten);
+                    i += 1;
+                } else {
+                    // 删除中间部分，extent 一分为二
+                    if hdr_entries(hdr) >= hdr_max(hdr) {
+                        return ENOTSUP;
+                    }
+                    r = ext4_balloc_free_blocks(inode_ref, pblk + (from - start) as u64, to - from + 1);
+                    let mut tail = *ex;
+                    tail.first_block = (to + 1).to_le();
+                    ext4_ext_store_pblock(&mut tail, pblk + (to + 1 - start) as u64);
+                    ext4_ext_set_len(&mut tail, end - to, unwritten);
+                    ext4_ext_set_len(ex, from - start, unwritten);
+                    let entry: [u8; EXT4_EXTENT_ENTRY_SIZE] = core::mem::transmute(tail);
+                    ext4_ext_insert_at(hdr, &entry);
+                    i += 2;
+                }
+                if r != EOK {
+                    return r;
+                }
+            }
+            return EOK;
+        }
+
+        while i < hdr_entries(hdr) as usize {
+            let n = hdr_entries(hdr) as usize;
+            let start = entry_key(hdr, i);
+            let end = if i + 1 < n { entry_key(hdr, i + 1).saturating_sub(1) } else { u32::MAX };
+            if end < from || start > to {
+                i += 1;
+                continue;
+            }
+
+            let child = ext4_idx_pblock(idx_at(hdr, i));
+            let mut b = Ext4Block::new();
+            let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut b);
+            if r != EOK {
+                return r;
+            }
+            let child_hdr = b.data as *mut Ext4ExtentHeader;
+            let r = ext4_ext_remove_rec(inode_ref, child_hdr, from, to);
+
+            if hdr_entries(child_hdr) == 0 {
+                // 子节点已空，释放节点块并删除索引
+                ext4_block_set((*(*inode_ref).fs).bdev, &mut b);
+                ext4_ext_remove_at(hdr, i);
+                let r2 = ext4_balloc_free_block(inode_ref, child);
+                if r != EOK {
+                    return r;
+                }
+                if r2 != EOK {
+                    return r2;
+                }
+                continue;
+            }
+
+            idx_at(hdr, i).first_block = entry_key(child_hdr, 0).to_le();
+            let r2 = ext4_ext_put_node(inode_ref, &mut b, true);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+            i += 1;
+        }
+    }
+    EOK
+}
+
+/// 删除逻辑块 [from, to] 的映射并释放对应的块
+pub unsafe fn ext4_extent_remove_space(inode_ref: *mut Ext4InodeRef, from: u32, to: u32) -> i32 {
+    unsafe {
+        ext4_dbg!(
+            DEBUG_EXTENT,
+            Debug,
+            "ext4_extent_remove_space: inode {}, from={}, to={}",
+            (*inode_ref).index,
+            from,
+            to
+        );
+        ext4_es_remove(&mut (*(*inode_ref).fs).es_cache, (*inode_ref).index, from, to);
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            return EIO;
+        }
+        let r = ext4_ext_remove_rec(inode_ref, hdr, from, to);
+        // 树已空时恢复为深度0的根节点
+        if hdr_entries(hdr) == 0 && hdr_depth(hdr) != 0 {
+            (*hdr).depth = 0;
+            (*hdr).max_entries_count = EXT4_EXTENT_ROOT_MAX_ENTRIES.to_le();
+        }
+        (*inode_ref).dirty = true;
+        r
+    }
+}
+
+// ===== 遍历 =====
+
+/// 递归遍历子树，对每个块节点（长度1）和每个 extent 的物理范围调用 f
+unsafe fn ext4_ext_walk_rec(
+    inode_ref: *mut Ext4InodeRef,
+    hdr: *mut Ext4ExtentHeader,
+    f: &mut dyn FnMut(u64, u32) -> i32,
+) -> i32 {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        if depth == 0 {
+            for i in 0..hdr_entries(hdr) as usize {
+                let ex = ext_at(hdr, i);
+                let r = f(ext4_ext_pblock(ex), ext4_ext_get_actual_len(ex));
+                if r != EOK {
+                    return r;
+                }
+            }
+            return EOK;
+        }
+        for i in 0..hdr_entries(hdr) as usize {
+            let child = ext4_idx_pblock(idx_at(hdr, i));
+            let r = f(child, 1);
+            if r != EOK {
+                return r;
+            }
+            let mut b = Ext4Block::new();
+            let r = ext4_ext_get_node(inode_ref, child, depth - 1, &mut b);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_ext_walk_rec(inode_ref, b.data as *mut Ext4ExtentHeader, f);
+            ext4_block_set((*(*inode_ref).fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+/// 遍历 inode 占用的全部块：每个块节点以 (块号, 1) 报告，每个 extent 以 (起始物理块, 长度) 报告
+///
+/// f 返回非 EOK 时停止遍历并返回该值。
+pub unsafe fn ext4_extent_for_each_block(
+    inode_ref: *mut Ext4InodeRef,
+    f: &mut dyn FnMut(u64, u32) -> i32,
+) -> i32 {
+    unsafe {
+        let hdr = ext4_ext_inode_hdr(inode_ref);
+        if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+            return EIO;
+        }
+        ext4_ext_walk_rec(inode_ref, hdr, f)
+    }
+}
+
+// ===== 交换 =====
+
+/// 按新的属主 inode 重新计算子树中全部块节点的校验和
+unsafe fn ext4_ext_reown_tree(inode_ref: *mut Ext4InodeRef, hdr: *mut Ext4ExtentHeader) -> i32 {
+    unsafe {
+        let depth = hdr_depth(hdr);
+        if depth == 0 {
+            return EOK;
+        }
+        let bdev = (*(*inode_ref).fs).bdev;
+        for i in 0..hdr_entries(hdr) as usize {
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get(bdev, &mut b, ext4_idx_pblock(idx_at(hdr, i)));
+            if r != EOK {
+                return r;
+            }
+            let child = b.data as *mut Ext4ExtentHeader;
+            if u16::from_le((*child).magic) != EXT4_EXTENT_MAGIC || hdr_depth(child) != depth - 1 {
+                ext4_block_set(bdev, &mut b);
+                return EIO;
+            }
+            let r = ext4_ext_reown_tree(inode_ref, child);
+            let r2 = ext4_ext_put_node(inode_ref, &mut b, true);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// 交换两个 inode 的 extent 树及块计数（碎片整理的 move extent 操作）
+///
+/// 块节点的校验和包含属主 inode 号和代数，交换后按新属主重新计算；
+/// 两个 inode 的 extent 状态缓存同时丢弃。文件大小等其余字段保持不变。
+pub unsafe fn ext4_extent_swap_trees(a: *mut Ext4InodeRef, b: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let fs = (*a).fs;
+        let sb = &(*fs).sb;
+        let (ia, ib) = ((*a).inode, (*b).inode);
+        if !ext4_inode_has_flag(ia, EXT4_INODE_FLAG_EXTENTS) || !ext4_inode_has_flag(ib, EXT4_INODE_FLAG_EXTENTS) {
+            return EINVAL;
+        }
+        ext4_dbg!(DEBUG_EXTENT, Debug, "ext4_extent_swap_trees: inode {} <-> {}", (*a).index, (*b).index);
+
+        let (ca, cb) = (ext4_inode_get_blocks_count(sb, ia), ext4_inode_get_blocks_count(sb, ib));
+        let r = ext4_inode_set_blocks_count(sb, ia, cb);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_inode_set_blocks_count(sb, ib, ca);
+        if r != EOK {
+            ext4_inode_set_blocks_count(sb, ia, ca);
+            return r;
+        }
+        core::mem::swap(&mut (*ia).blocks, &mut (*ib).blocks);
+        (*a).dirty = true;
+        (*b).dirty = true;
+        // 块随 extent 树交换，用量在两个属主之间转移
+        let delta = (cb as i64 - ca as i64) * EXT4_INODE_BLOCK_SIZE as i64;
+        ext4_quota_charge_inode(a, delta, 0);
+        ext4_quota_charge_inode(b, -delta, 0);
+
+        ext4_es_drop_inode(&mut (*fs).es_cache, (*a).index);
+        ext4_es_drop_inode(&mut (*fs).es_cache, (*b).index);
+
+        let r = ext4_ext_reown_tree(a, ext4_ext_inode_hdr(a));
+        if r != EOK {
+            return r;
+        }
+        ext4_ext_reown_tree(b, ext4_ext_inode_hdr(b))
+    }
+}
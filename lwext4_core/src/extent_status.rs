@@ -0,0 +1,156 @@
+//! Extent 状态缓存模块
+//!
+//! 对应 Linux 内核的 extent status tree（fs/ext4/extents_status.c），lwext4 中没有对应实现。
+//!
+//! 在内存中按 inode 缓存已查到的 逻辑块 -> 物理块 映射，ext4_extent_get_blocks
+//! 命中缓存时不再遍历 extent 树。只缓存已写入的映射；截断、删除映射时按范围失效，
+//! inode 释放或重新初始化 extent 树时整体丢弃。缓存条目总数超过上限（默认
+//! CONFIG_EXTENT_STATUS_CACHE_SIZE，由挂载参数 es_cache_size 调整）时按 inode 整体淘汰。
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::consts::*;
+use crate::debug::*;
+
+/// 一段连续的映射
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+struct ext4_es_entry {
+    len: u32,  // 块数
+    pblk: u64, // 起始物理块
+}
+
+/// 文件系统的 extent 状态缓存
+#[allow(non_camel_case_types)]
+pub struct ext4_es_cache {
+    /// inode 编号 -> (起始逻辑块 -> 映射)，同一 inode 内的映射互不重叠
+    trees: BTreeMap<u32, BTreeMap<u32, ext4_es_entry>>,
+    /// 所有 inode 的条目总数
+    count: usize,
+    /// 条目总数上限
+    limit: usize,
+}
+
+impl ext4_es_cache {
+    pub const fn new() -> Self {
+        Self {
+            trees: BTreeMap::new(),
+            count: 0,
+            limit: CONFIG_EXTENT_STATUS_CACHE_SIZE,
+        }
+    }
+}
+
+/// 查找逻辑块的缓存映射，返回 (物理块, 从 lblk 起的连续块数)
+pub fn ext4_es_lookup(es: &ext4_es_cache, ino: u32, lblk: u32) -> Option<(u64, u32)> {
+    let (&start, e) = es.trees.get(&ino)?.range(..=lblk).next_back()?;
+    let off = lblk - start;
+    (off < e.len).then(|| (e.pblk + off as u64, e.len - off))
+}
+
+/// 缓存一段映射 [lblk, lblk + len) -> pblk
+///
+/// 与前一段在逻辑和物理上都连续时合并；覆盖的旧映射被替换。
+pub fn ext4_es_insert(es: &mut ext4_es_cache, ino: u32, lblk: u32, len: u32, pblk: u64) {
+    if len == 0 || lblk as u64 + len as u64 > u32::MAX as u64 + 1 {
+        return;
+    }
+    ext4_es_remove(es, ino, lblk, lblk + (len - 1));
+    if es.limit == 0 {
+        return;
+    }
+    if es.count >= es.limit {
+        ext4_es_shrink(es, ino);
+    }
+
+    let tree = es.trees.entry(ino).or_default();
+    if let Some((&start, prev)) = tree.range_mut(..lblk).next_back() {
+        if start as u64 + prev.len as u64 == lblk as u64 && prev.pblk + prev.len as u64 == pblk {
+            prev.len += len;
+            return;
+        }
+    }
+    tree.insert(lblk, ext4_es_entry { len, pblk });
+    es.count += 1;
+}
+
+/// 映射在 to 之后的部分（不超出 to 时返回 None）
+fn ext4_es_tail(start: u32, e: &ext4_es_entry, to: u32) -> Option<(u32, ext4_es_entry)> {
+    let end = start as u64 + e.len as u64; // 不含
+    if end <= to as u64 + 1 {
+        return None;
+    }
+    let skip = to - start + 1;
+    Some((to + 1, ext4_es_entry { len: e.len - skip, pblk: e.pblk + skip as u64 }))
+}
+
+/// 使逻辑块 [from, to] 范围内的缓存映射失效（部分重叠的映射被裁剪）
+pub fn ext4_es_remove(es: &mut ext4_es_cache, ino: u32, from: u32, to: u32) {
+    let Some(tree) = es.trees.get_mut(&ino) else {
+        return;
+    };
+    let before = tree.len();
+    let mut tail = None;
+
+    // 起点在范围之前、与范围重叠的映射：保留范围之前的部分
+    if let Some((&start, e)) = tree.range_mut(..from).next_back() {
+        if start as u64 + e.len as u64 > from as u64 {
+            tail = ext4_es_tail(start, e, to);
+            e.len = from - start;
+        }
+    }
+
+    // 起点在范围内的映射：删除，超出范围的部分保留
+    let inside: Vec<u32> = tree.range(from..=to).map(|(&k, _)| k).collect();
+    for start in inside {
+        if let Some(e) = tree.remove(&start) {
+            tail = tail.or(ext4_es_tail(start, &e, to));
+        }
+    }
+    if let Some((start, e)) = tail {
+        tree.insert(start, e);
+    }
+
+    let after = tree.len();
+    if after == 0 {
+        es.trees.remove(&ino);
+    }
+    es.count = es.count + after - before;
+}
+
+/// 丢弃 inode 的全部缓存映射
+pub fn ext4_es_drop_inode(es: &mut ext4_es_cache, ino: u32) {
+    if let Some(tree) = es.trees.remove(&ino) {
+        es.count -= tree.len();
+    }
+}
+
+/// 丢弃全部缓存映射
+pub fn ext4_es_clear(es: &mut ext4_es_cache) {
+    es.trees.clear();
+    es.count = 0;
+}
+
+/// 设置条目总数上限（0表示不缓存），超出新上限的映射按 inode 整体淘汰
+pub fn ext4_es_set_limit(es: &mut ext4_es_cache, limit: usize) {
+    es.limit = limit;
+    while es.count > limit {
+        let Some(&ino) = es.trees.keys().next() else {
+            break;
+        };
+        ext4_es_drop_inode(es, ino);
+    }
+}
+
+/// 缓存已满时淘汰其他 inode 的映射（只剩当前 inode 时清空它）
+fn ext4_es_shrink(es: &mut ext4_es_cache, keep: u32) {
+    ext4_dbg!(DEBUG_EXTENT, Debug, "ext4_es_shrink: entries={}", es.count);
+    while es.count >= es.limit {
+        let victim = match es.trees.keys().find(|&&ino| ino != keep) {
+            Some(&ino) => ino,
+            None => keep,
+        };
+        ext4_es_drop_inode(es, victim);
+    }
+}
@@ -0,0 +1,515 @@
+//! 快速提交回放模块
+//!
+//! 对应C实现: Linux fs/ext4/fast_commit.c 的回放部分（lwext4 中没有对应实现）
+//!
+//! 快速提交区域由 TLV 标签组成（le16 标签、le16 长度、值），每次快速提交以 HEAD 开始、
+//! 以 TAIL 结束，TAIL 中记录事务号和此前所有标签的 crc32c。扫描只接受事务号等于日志中
+//! 下一个事务、且校验和正确的标签；回放按顺序重做 inode、extent 和目录项的修改，
+//! 最后根据被修改 inode 的 extent 树重建块位图、inode 位图和块计数。
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::mem::offset_of;
+use core::ptr;
+
+use crate::balloc::ext4_balloc_mark_range;
+use crate::crc::ext4_crc32c;
+use crate::dir::*;
+use crate::extent::*;
+use crate::extent_status::ext4_es_drop_inode;
+use crate::ialloc::ext4_ialloc_mark_used;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4DirSearchResult, Ext4Extent, Ext4ExtentHeader, Ext4Filesystem, Ext4Inode, Ext4InodeRef};
+
+/// 快速提交标签
+pub const EXT4_FC_TAG_ADD_RANGE: u16 = 0x0001;
+pub const EXT4_FC_TAG_DEL_RANGE: u16 = 0x0002;
+pub const EXT4_FC_TAG_CREAT: u16 = 0x0003;
+pub const EXT4_FC_TAG_LINK: u16 = 0x0004;
+pub const EXT4_FC_TAG_UNLINK: u16 = 0x0005;
+pub const EXT4_FC_TAG_INODE: u16 = 0x0006;
+pub const EXT4_FC_TAG_PAD: u16 = 0x0007;
+pub const EXT4_FC_TAG_TAIL: u16 = 0x0008;
+pub const EXT4_FC_TAG_HEAD: u16 = 0x0009;
+
+/// 标签头大小（le16 标签 + le16 长度）
+const EXT4_FC_TAG_BASE_LEN: usize = 4;
+/// 目录项标签中父目录和 inode 编号的长度
+const EXT4_FC_DENTRY_INFO_LEN: usize = 8;
+
+fn le16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+}
+
+/// 标签值长度是否合法
+fn ext4_fc_value_len_isvalid(tag: u16, len: usize, inode_size: usize) -> bool {
+    match tag {
+        EXT4_FC_TAG_ADD_RANGE => len == 4 + size_of::<Ext4Extent>(),
+        EXT4_FC_TAG_DEL_RANGE => len == 12,
+        EXT4_FC_TAG_CREAT | EXT4_FC_TAG_LINK | EXT4_FC_TAG_UNLINK => len > EXT4_FC_DENTRY_INFO_LEN,
+        EXT4_FC_TAG_INODE => {
+            len >= 4 + EXT4_GOOD_OLD_INODE_SIZE as usize && len - 4 <= inode_size
+        }
+        EXT4_FC_TAG_PAD => true,
+        EXT4_FC_TAG_TAIL => len >= 8,
+        EXT4_FC_TAG_HEAD => len == 8,
+        _ => false,
+    }
+}
+
+/// 扫描快速提交区域，返回最后一个完整快速提交之前的全部标签
+fn ext4_fc_scan(area: &[u8], block_size: usize, tid: u32, inode_size: usize) -> Vec<(u16, &[u8])> {
+    let mut tags = Vec::new();
+    let mut valid = 0;
+    let mut crc = 0u32;
+
+    if area.len() < EXT4_FC_TAG_BASE_LEN || le16(area, 0) != EXT4_FC_TAG_HEAD {
+        return tags;
+    }
+    'blocks: for block in area.chunks(block_size) {
+        let mut off = 0;
+        while off + EXT4_FC_TAG_BASE_LEN <= block.len() {
+            let tag = le16(block, off);
+            let len = le16(block, off + 2) as usize;
+            let val_off = off + EXT4_FC_TAG_BASE_LEN;
+            if len > block.len() - val_off || !ext4_fc_value_len_isvalid(tag, len, inode_size) {
+                break 'blocks;
+            }
+            let val = &block[val_off..val_off + len];
+            match tag {
+                EXT4_FC_TAG_TAIL => {
+                    tags.push((tag, val));
+                    // 校验和覆盖标签头和事务号
+                    crc = ext4_crc32c(crc, &block[off..val_off + 4]);
+                    if le32(val, 0) != tid || le32(val, 4) != crc {
+                        break 'blocks;
+                    }
+                    valid = tags.len();
+                    crc = 0;
+                }
+                EXT4_FC_TAG_HEAD => {
+                    if le32(val, 0) != 0 {
+                        ext4_dbg!(DEBUG_JBD, Warn, "unsupported fast commit features: {:#x}", le32(val, 0));
+                        break 'blocks;
+                    }
+                    if le32(val, 4) != tid {
+                        break 'blocks;
+                    }
+                    tags.push((tag, val));
+                    crc = ext4_crc32c(crc, &block[off..val_off + len]);
+                }
+                _ => {
+                    tags.push((tag, val));
+                    crc = ext4_crc32c(crc, &block[off..val_off + len]);
+                }
+            }
+            off = val_off + len;
+        }
+    }
+    tags.truncate(valid);
+    tags
+}
+
+/// 回放状态
+struct FcReplay {
+    fs: *mut Ext4Filesystem,
+    /// ADD_RANGE 引用的物理块范围，回放期间保持占用，避免被新分配的块覆盖
+    regions: Vec<(u64, u32)>,
+    /// 回放中修改过的 inode
+    modified: BTreeSet<u32>,
+}
+
+impl FcReplay {
+    /// 物理块范围是否位于文件系统数据区内
+    unsafe fn range_valid(&self, pblk: u64, len: u32) -> bool {
+        unsafe {
+            let sb = &(*self.fs).sb;
+            len != 0
+                && pblk >= u32::from_le(sb.first_data_block) as u64
+                && pblk + len as u64 <= ext4_sb_get_blocks_cnt(sb)
+        }
+    }
+
+    /// 将全部 ADD_RANGE 范围标记为已占用
+    unsafe fn reserve_regions(&self) -> i32 {
+        unsafe {
+            for &(pblk, len) in &self.regions {
+                let r = ext4_balloc_mark_range(self.fs, pblk, len, true);
+                if r != EOK {
+                    return r;
+                }
+            }
+        }
+        EOK
+    }
+}
+
+/// 获取回放涉及的 inode（编号非法时返回 Ok(None)）
+unsafe fn ext4_fc_get_inode(fs: *mut Ext4Filesystem, ino: u32) -> Result<Option<InodeRef>, i32> {
+    unsafe {
+        if ino == 0 || ino > u32::from_le((*fs).sb.inodes_count) {
+            ext4_dbg!(DEBUG_JBD, Warn, "fast commit references invalid inode {}", ino);
+            return Ok(None);
+        }
+        InodeRef::get(fs, ino).map(Some)
+    }
+}
+
+/// 读取 extent 标签中的 (逻辑块, 物理块, 长度, 是否未写入)
+fn ext4_fc_parse_extent(val: &[u8]) -> (u32, u64, u32, bool) {
+    let ex: Ext4Extent = unsafe { ptr::read_unaligned(val.as_ptr() as *const Ext4Extent) };
+    (
+        u32::from_le(ex.first_block),
+        ext4_ext_pblock(&ex),
+        ext4_ext_get_actual_len(&ex),
+        ext4_ext_is_unwritten(&ex),
+    )
+}
+
+/// INODE：用日志中的 inode 覆盖磁盘上的 inode，块映射（i_block）保持磁盘上的内容
+unsafe fn ext4_fc_replay_inode(st: &mut FcReplay, val: &[u8]) -> i32 {
+    unsafe {
+        let fs = st.fs;
+        let ino = le32(val, 0);
+        let raw = &val[4..];
+        let mut inode_ref = match ext4_fc_get_inode(fs, ino) {
+            Ok(Some(inode_ref)) => inode_ref,
+            Ok(None) => return EOK,
+            Err(r) => return r,
+        };
+
+        let len = raw.len().min((*fs).inode_size as usize);
+        let off_block = offset_of!(Ext4Inode, blocks);
+        let off_gen = offset_of!(Ext4Inode, generation);
+        let dst = inode_ref.inode as *mut u8;
+        ptr::copy_nonoverlapping(raw.as_ptr(), dst, off_block);
+        ptr::copy_nonoverlapping(raw[off_gen..].as_ptr(), dst.add(off_gen), len - off_gen);
+
+        let inode = inode_ref.inode;
+        if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) {
+            let hdr = (*inode).blocks.as_ptr() as *const Ext4ExtentHeader;
+            if u16::from_le((*hdr).magic) != EXT4_EXTENT_MAGIC {
+                (*inode).blocks = [0; EXT4_INODE_BLOCKS];
+                ext4_extent_tree_init(&mut *inode_ref);
+            }
+        } else if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_INLINE_DATA) {
+            ptr::copy_nonoverlapping(raw[off_block..].as_ptr(), dst.add(off_block), off_gen - off_block);
+        }
+        ext4_es_drop_inode(&mut (*fs).es_cache, ino);
+        inode_ref.dirty = true;
+        st.modified.insert(ino);
+        inode_ref.put()
+    }
+}
+
+/// ADD_RANGE / DEL_RANGE：先删除范围内原有的映射，ADD_RANGE 再插入新的 extent
+unsafe fn ext4_fc_replay_range(st: &mut FcReplay, tag: u16, val: &[u8]) -> i32 {
+    unsafe {
+        let fs = st.fs;
+        let ino = le32(val, 0);
+        let (lblk, pblk, len, unwritten) = if tag == EXT4_FC_TAG_ADD_RANGE {
+            ext4_fc_parse_extent(&val[4..])
+        } else {
+            (le32(val, 4), 0, le32(val, 8), false)
+        };
+        if len == 0 || (tag == EXT4_FC_TAG_ADD_RANGE && !st.range_valid(pblk, len)) {
+            ext4_dbg!(DEBUG_JBD, Warn, "fast commit: bad range for inode {}", ino);
+            return EOK;
+        }
+
+        let mut inode_ref = match ext4_fc_get_inode(fs, ino) {
+            Ok(Some(inode_ref)) => inode_ref,
+            Ok(None) => return EOK,
+            Err(r) => return r,
+        };
+        if !ext4_inode_has_flag(inode_ref.inode, EXT4_INODE_FLAG_EXTENTS) {
+            ext4_dbg!(DEBUG_JBD, Warn, "fast commit: inode {} does not use extents", ino);
+            return inode_ref.put();
+        }
+
+        let mut r = ext4_extent_remove_space(&mut *inode_ref, lblk, lblk.saturating_add(len - 1));
+        // 删除映射时释放的块可能仍被后续标签引用
+        if r == EOK {
+            r = st.reserve_regions();
+        }
+        if r == EOK && tag == EXT4_FC_TAG_ADD_RANGE {
+            r = ext4_extent_insert_range(&mut *inode_ref, lblk, pblk, len, unwritten);
+        }
+        st.modified.insert(ino);
+        let r2 = inode_ref.put();
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 在目录中查找名称，返回目录项指向的 inode 编号
+unsafe fn ext4_fc_lookup(parent: *mut Ext4InodeRef, name: &[u8]) -> Result<Option<u32>, i32> {
+    unsafe {
+        let mut result = Ext4DirSearchResult::new();
+        match ext4_dir_find_entry(&mut result, parent, name.as_ptr(), name.len() as u32) {
+            EOK => {
+                let ino = u32::from_le((*result.dentry).inode);
+                let r = ext4_dir_destroy_result(parent, &mut result);
+                if r != EOK {
+                    return Err(r);
+                }
+                Ok(Some(ino))
+            }
+            ENOENT => Ok(None),
+            r => Err(r),
+        }
+    }
+}
+
+/// CREAT / LINK / UNLINK：重做目录项的修改
+unsafe fn ext4_fc_replay_dentry(st: &mut FcReplay, tag: u16, val: &[u8]) -> i32 {
+    unsafe {
+        let fs = st.fs;
+        let parent_ino = le32(val, 0);
+        let ino = le32(val, 4);
+        let name = &val[EXT4_FC_DENTRY_INFO_LEN..];
+
+        let mut parent = match ext4_fc_get_inode(fs, parent_ino) {
+            Ok(Some(parent)) => parent,
+            Ok(None) => return EOK,
+            Err(r) => return r,
+        };
+        // 失败时 parent 在 Drop 中归还
+        let mut child = match ext4_fc_get_inode(fs, ino) {
+            Ok(Some(child)) => child,
+            Ok(None) => return parent.put(),
+            Err(r) => return r,
+        };
+
+        let r = ext4_fc_replay_dentry_ops(fs, tag, &mut *parent, &mut *child, name);
+        st.modified.insert(ino);
+        let r2 = child.put();
+        let r3 = parent.put();
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+        r3
+    }
+}
+
+unsafe fn ext4_fc_replay_dentry_ops(
+    fs: *mut Ext4Filesystem,
+    tag: u16,
+    parent: *mut Ext4InodeRef,
+    child: *mut Ext4InodeRef,
+    name: &[u8],
+) -> i32 {
+    unsafe {
+        let is_dir = ext4_inode_is_type(&(*fs).sb, (*child).inode, EXT4_INODE_MODE_DIRECTORY);
+        let existing = match ext4_fc_lookup(parent, name) {
+            Ok(ino) => ino,
+            Err(r) => return r,
+        };
+
+        if tag == EXT4_FC_TAG_UNLINK {
+            if existing != Some((*child).index) {
+                return EOK;
+            }
+            let r = ext4_dir_remove_entry(parent, name.as_ptr(), name.len() as u32);
+            if r != EOK {
+                return r;
+            }
+            if is_dir {
+                ext4_inode_set_links_cnt((*child).inode, 0);
+                (*child).dirty = true;
+            } else {
+                ext4_fs_inode_links_count_dec(child);
+            }
+            return EOK;
+        }
+
+        if existing.is_some() {
+            // 目录项已经存在（回放前已写入磁盘）
+            return EOK;
+        }
+        if tag == EXT4_FC_TAG_CREAT && is_dir {
+            // 新目录的内容由"."和".."重新构建
+            ext4_inode_set_size((*child).inode, 0);
+            let r = ext4_dir_add_entry(child, b".".as_ptr(), 1, child);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_dir_add_entry(child, b"..".as_ptr(), 2, parent);
+            if r != EOK {
+                return r;
+            }
+        }
+        let r = ext4_dir_add_entry(parent, name.as_ptr(), name.len() as u32, child);
+        if r != EOK {
+            return r;
+        }
+        match tag {
+            EXT4_FC_TAG_CREAT => ext4_inode_set_links_cnt((*child).inode, if is_dir { 2 } else { 1 }),
+            _ => ext4_fs_inode_links_count_inc(child),
+        }
+        (*child).dirty = true;
+    }
+    EOK
+}
+
+/// 释放回放结束后链接数为0的 inode 及其全部块
+unsafe fn ext4_fc_release_inode(inode_ref: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let inode = (*inode_ref).inode;
+        if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) {
+            let r = ext4_extent_remove_space(inode_ref, 0, u32::MAX);
+            if r != EOK {
+                return r;
+            }
+        }
+        ext4_inode_set_size(inode, 0);
+        if ext4_inode_get_del_time(inode) == 0 {
+            ext4_inode_set_del_time(inode, u32::MAX);
+        }
+        ext4_fs_free_inode(inode_ref)
+    }
+}
+
+/// 按被修改 inode 的最终状态重建位图和块计数
+unsafe fn ext4_fc_set_bitmaps_and_counters(st: &mut FcReplay) -> i32 {
+    unsafe {
+        let fs = st.fs;
+        let units_per_block = ((*fs).block_size / EXT4_INODE_BLOCK_SIZE) as u64;
+
+        // 链接数为0的 inode 整体释放（区域仍保持占用，释放只影响其自身的块）
+        for &ino in &st.modified {
+            let mut inode_ref = match InodeRef::get(fs, ino) {
+                Ok(inode_ref) => inode_ref,
+                Err(r) => return r,
+            };
+            let inode = inode_ref.inode;
+            let mut r = EOK;
+            if ext4_inode_get_links_cnt(inode) == 0 && ext4_inode_get_mode(&(*fs).sb, inode) != 0 {
+                ext4_dbg!(DEBUG_JBD, Debug, "fast commit: releasing unlinked inode {}", ino);
+                r = ext4_fc_release_inode(&mut *inode_ref);
+            }
+            let r2 = inode_ref.put();
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+
+        // 解除回放期间的占用，再按仍在使用的 inode 的 extent 树重新标记
+        for &(pblk, len) in &st.regions {
+            let r = ext4_balloc_mark_range(fs, pblk, len, false);
+            if r != EOK {
+                return r;
+            }
+        }
+        for &ino in &st.modified {
+            let mut inode_ref = match InodeRef::get(fs, ino) {
+                Ok(inode_ref) => inode_ref,
+                Err(r) => return r,
+            };
+            let sb = &(*fs).sb;
+            let inode = inode_ref.inode;
+            if ext4_inode_get_links_cnt(inode) == 0 {
+                let r = inode_ref.put();
+                if r != EOK {
+                    return r;
+                }
+                continue;
+            }
+
+            let is_dir = ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_DIRECTORY);
+            let mut r = ext4_ialloc_mark_used(fs, ino, is_dir);
+            if r == EOK
+                && ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS)
+                && !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_INLINE_DATA)
+            {
+                let mut count = 0u64;
+                r = ext4_extent_for_each_block(&mut *inode_ref, &mut |pblk, len| {
+                    count += len as u64;
+                    ext4_balloc_mark_range(fs, pblk, len, true)
+                });
+                let acl = ext4_inode_get_file_acl(inode, sb);
+                if r == EOK && acl != 0 {
+                    count += 1;
+                    r = ext4_balloc_mark_range(fs, acl, 1, true);
+                }
+                if r == EOK {
+                    r = ext4_inode_set_blocks_count(sb, inode, count * units_per_block);
+                    inode_ref.dirty = true;
+                }
+            }
+            let r2 = inode_ref.put();
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// 回放快速提交区域
+///
+/// area 为日志中快速提交区域的全部块，tid 为日志中最后一个完整事务之后的事务号。
+/// 没有有效的快速提交时直接返回。
+pub unsafe fn ext4_fc_replay(fs: *mut Ext4Filesystem, area: &[u8], block_size: usize, tid: u32) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_JBD, "ext4_fc_replay", "tid={}", tid);
+    unsafe {
+        let tags = ext4_fc_scan(area, block_size, tid, (*fs).inode_size as usize);
+        if tags.is_empty() {
+            ext4_dbg!(DEBUG_JBD, Info, "no fast commit to replay");
+            return EOK;
+        }
+        ext4_dbg!(DEBUG_JBD, Info, "replaying {} fast commit tags", tags.len());
+
+        let mut st = FcReplay {
+            fs,
+            regions: Vec::new(),
+            modified: BTreeSet::new(),
+        };
+        for &(tag, val) in &tags {
+            if tag == EXT4_FC_TAG_ADD_RANGE {
+                let (_, pblk, len, _) = ext4_fc_parse_extent(&val[4..]);
+                if st.range_valid(pblk, len) {
+                    st.regions.push((pblk, len));
+                }
+            }
+        }
+        let r = st.reserve_regions();
+        if r != EOK {
+            return r;
+        }
+
+        for &(tag, val) in &tags {
+            let r = match tag {
+                EXT4_FC_TAG_INODE => ext4_fc_replay_inode(&mut st, val),
+                EXT4_FC_TAG_ADD_RANGE | EXT4_FC_TAG_DEL_RANGE => ext4_fc_replay_range(&mut st, tag, val),
+                EXT4_FC_TAG_CREAT | EXT4_FC_TAG_LINK | EXT4_FC_TAG_UNLINK => {
+                    ext4_fc_replay_dentry(&mut st, tag, val)
+                }
+                _ => EOK,
+            };
+            if r != EOK {
+                ext4_dbg!(DEBUG_JBD, Warn, "fast commit tag {} replay failed: {}", tag, r);
+                return r;
+            }
+        }
+        ext4_fc_set_bitmaps_and_counters(&mut st)
+    }
+}
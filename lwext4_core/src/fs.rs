@@ -1,42 +1,259 @@
 //! 文件系统核心操作模块
+//!
+//! 对应C实现: ext4_fs.c（挂载与卸载部分）
 
-use log::debug;
-use crate::{Ext4Filesystem, Ext4BlockDevice, superblock};
+use crate::{Ext4Filesystem, Ext4BlockDevice, Ext4MountConfig, Ext4Superblock};
+use crate::block::ext4_block_cache_flush;
+use crate::superblock::*;
+use crate::extent_status::{ext4_es_clear, ext4_es_set_limit};
 use crate::consts::*;
+use crate::debug::*;
 
-/// 初始化文件系统（占位实现）
-pub fn ext4_fs_init(
+/// 检查 superblock 中的特性位
+///
+/// 对应C实现: ext4_fs_check_features (ext4_fs.c)。
+/// 包含不支持的不兼容特性时返回 ENOTSUP；包含不支持写入的只读兼容特性时将 read_only 置为真。
+pub fn ext4_fs_check_features(sb: &Ext4Superblock, read_only: &mut bool) -> i32 {
+    *read_only = false;
+    // 版本0没有特性字段
+    if u32::from_le(sb.rev_level) == 0 {
+        return EOK;
+    }
+
+    let v = u32::from_le(sb.feature_incompat) & !EXT4_SUPPORTED_FINCOM;
+    if v != 0 {
+        ext4_dbg!(DEBUG_FS, Warn, "superblock has unsupported incompatible features: {:#x}", v);
+        return ENOTSUP;
+    }
+
+    let v = u32::from_le(sb.feature_ro_compat) & !EXT4_SUPPORTED_FRO_COM;
+    if v != 0 {
+        ext4_dbg!(DEBUG_FS, Warn, "superblock has unsupported read-only features: {:#x}", v);
+        *read_only = true;
+    }
+    EOK
+}
+
+/// 建议运行 fsck 的原因（ext4_fs_fsck_reasons 的返回值，可以组合）
+pub const EXT4_FSCK_NOT_CLEAN: u32 = 0x1; // 上次未正常卸载，或记录了错误
+pub const EXT4_FSCK_MAX_MNT_COUNT: u32 = 0x2; // 挂载次数达到上限
+pub const EXT4_FSCK_CHECKINTERVAL: u32 = 0x4; // 距上次检查超过检查间隔
+
+/// 按 superblock 判断是否建议运行 fsck（对应Linux ext4_setup_super 中的警告）
+///
+/// 挂载次数上限按有符号数解释，不大于0时不检查；检查间隔为0或 now（秒）为 None 时不按时间检查。
+/// 应在挂载增加挂载计数之前调用。
+pub fn ext4_fs_fsck_reasons(sb: &Ext4Superblock, now: Option<u64>) -> u32 {
+    let mut reasons = 0;
+    let state = u16::from_le(sb.state);
+    if state & EXT4_SUPERBLOCK_STATE_VALID_FS == 0 || state & EXT4_SUPERBLOCK_STATE_ERROR_FS != 0 {
+        reasons |= EXT4_FSCK_NOT_CLEAN;
+    }
+    let max_mnt_count = u16::from_le(sb.max_mnt_count) as i16;
+    if max_mnt_count > 0 && u16::from_le(sb.mnt_count) >= max_mnt_count as u16 {
+        reasons |= EXT4_FSCK_MAX_MNT_COUNT;
+    }
+    let interval = u32::from_le(sb.checkinterval) as u64;
+    let lastcheck = u32::from_le(sb.lastcheck) as u64 | (sb.lastcheck_hi as u64) << 32;
+    if let Some(now) = now.filter(|_| interval != 0) {
+        if lastcheck + interval <= now {
+            reasons |= EXT4_FSCK_CHECKINTERVAL;
+        }
+    }
+    reasons
+}
+
+/// 初始化文件系统
+///
+/// 读取并检查 superblock，计算间接块映射的各级上限。
+/// 包含不支持的不兼容特性时拒绝挂载（ENOTSUP），包含不支持写入的只读兼容特性时拒绝
+/// 可写挂载（EROFS）；调用前设置 force_features 可以跳过这两项检查。
+/// 可写挂载时清除 VALID 状态并增加挂载计数，卸载时恢复。
+pub unsafe fn ext4_fs_init(
     fs: *mut Ext4Filesystem,
     bdev: *mut Ext4BlockDevice,
     read_only: bool,
 ) -> i32 {
-    // TODO: 实现文件系统初始化
-    // 1. 读取 superblock
-    // 2. 验证魔数
-    // 3. 初始化文件系统结构
-    // 4. 计算块组数量等参数
+    let _span = ext4_dbg_span!(DEBUG_FS, "ext4_fs_init", "read_only={}", read_only);
+    unsafe {
+        (*fs).bdev = bdev;
+        (*fs).read_only = read_only;
+
+        let r = ext4_sb_read(bdev, &mut (*fs).sb);
+        if r != EOK {
+            ext4_dbg!(DEBUG_FS, Warn, "superblock read failed: {}", r);
+            return r;
+        }
+        let r = ext4_sb_check(&(*fs).sb);
+        if r != EOK {
+            ext4_dbg!(DEBUG_FS, Warn, "superblock check failed: {}", r);
+            return r;
+        }
+        // 块数超出设备容量的 superblock 不可信
+        let fs_size = ext4_sb_get_blocks_cnt(&(*fs).sb) * ext4_sb_get_block_size(&(*fs).sb) as u64;
+        if fs_size > (*bdev).part_size {
+            ext4_dbg!(DEBUG_FS, Warn, "filesystem size {} exceeds device size {}", fs_size, (*bdev).part_size);
+            return EFSCORRUPTED;
+        }
+        let mut ro_features = false;
+        let r = ext4_fs_check_features(&(*fs).sb, &mut ro_features);
+        if (*fs).force_features {
+            if r != EOK || (ro_features && !read_only) {
+                ext4_dbg!(DEBUG_FS, Warn, "mounting despite unsupported features");
+            }
+        } else if r != EOK {
+            return r;
+        } else if ro_features && !read_only {
+            return EROFS;
+        }
+
+        (*fs).fsck_reasons = ext4_fs_fsck_reasons(&(*fs).sb, None);
+        if (*fs).fsck_reasons != 0 {
+            ext4_dbg!(DEBUG_FS, Warn, "running fsck is recommended: {:#x}", (*fs).fsck_reasons);
+        }
+
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        (*fs).block_size = block_size;
+        (*fs).inode_size = get_inode_size(sb) as u32;
+        (*fs).inodes_per_group = u32::from_le(sb.inodes_per_group);
+        (*fs).blocks_per_group = u32::from_le(sb.blocks_per_group);
+        (*fs).block_group_count = ext4_block_group_cnt(sb);
+        (*fs).last_inode_bg_id = 0;
+        (*fs).bytes_written = 0;
+        ext4_es_clear(&mut (*fs).es_cache);
+        (*fs).prealloc.clear();
+        (*fs).alloc_goal.clear();
+        (*fs).quota = None;
+
+        // 间接块映射：各级可寻址的逻辑块上限
+        let block_ids_per_block = (block_size / 4) as u64;
+        (*fs).inode_block_limits[0] = EXT4_INODE_DIRECT_BLOCKS as u64;
+        (*fs).inode_blocks_per_level[0] = 1;
+        for i in 1..4 {
+            (*fs).inode_blocks_per_level[i] = (*fs).inode_blocks_per_level[i - 1] * block_ids_per_block;
+            (*fs).inode_block_limits[i] = (*fs).inode_block_limits[i - 1] + (*fs).inode_blocks_per_level[i];
+        }
 
-    debug!("ext4_fs_init: read_only={}", read_only);
+        (*bdev).fs = fs;
+        ext4_dbg!(
+            DEBUG_FS,
+            Info,
+            "mounted: block_size={}, groups={}, blocks={}, inodes={}, read_only={}",
+            block_size,
+            (*fs).block_group_count,
+            ext4_sb_get_blocks_cnt(sb),
+            u32::from_le(sb.inodes_count),
+            read_only
+        );
+
+        if read_only {
+            return EOK;
+        }
+
+        // 标记为已挂载（未正常卸载）
+        let state = u16::from_le(sb.state);
+        sb.state = (state & !EXT4_SUPERBLOCK_STATE_VALID_FS).to_le();
+        let mnt_count = u16::from_le(sb.mnt_count);
+        sb.mnt_count = mnt_count.wrapping_add(1).to_le();
+        ext4_sb_write(bdev, sb)
+    }
+}
+
+/// 应用挂载参数（块缓存需已绑定；bcache_size 只在创建缓存时使用，这里忽略）
+pub unsafe fn ext4_fs_set_config(fs: *mut Ext4Filesystem, config: &Ext4MountConfig) -> i32 {
+    unsafe {
+        let bc = (*(*fs).bdev).bc;
+        if bc.is_null() {
+            return EINVAL;
+        }
+        (*bc).max_dirty = config.max_dirty_blocks;
+        ext4_es_set_limit(&mut (*fs).es_cache, config.es_cache_size);
+    }
     EOK
 }
 
-/// 关闭文件系统（占位实现）
-pub fn ext4_fs_fini(fs: *mut Ext4Filesystem) -> i32 {
-    // TODO: 实现文件系统关闭
-    // 1. 刷新缓存
-    // 2. 写回 superblock
-    // 3. 清理资源
+/// 长时间操作的让出点：每经过 yield_interval 个让出点调用一次 yield_hook（未设置时什么也不做）
+///
+/// 日志回放每读取一个日志块、缓存回写每写回一个块时经过一次，宿主在钩子中让出 CPU，
+/// 避免在大镜像上长时间占用调度器。
+pub unsafe fn ext4_fs_yield_point(fs: *mut Ext4Filesystem) {
+    unsafe {
+        let Some(hook) = (*fs).yield_hook else {
+            return;
+        };
+        (*fs).yield_ticks += 1;
+        if (*fs).yield_ticks >= (*fs).yield_interval {
+            (*fs).yield_ticks = 0;
+            hook();
+        }
+    }
+}
 
-    debug!("ext4_fs_fini");
-    EOK
+/// 把累计的设备写入量中的整 KiB 计入 s_kbytes_written（对应Linux ext4_update_super 中的计算），
+/// 返回是否修改了 superblock
+unsafe fn ext4_fs_update_kbytes_written(fs: *mut Ext4Filesystem) -> bool {
+    unsafe {
+        let kbytes = (*fs).bytes_written / 1024;
+        if kbytes == 0 {
+            return false;
+        }
+        (*fs).bytes_written %= 1024;
+        let sb = &mut (*fs).sb;
+        sb.kbytes_written = u64::from_le(sb.kbytes_written).wrapping_add(kbytes).to_le();
+        true
+    }
 }
 
-/// 初始化 inode 数据块索引（占位实现）
-pub fn ext4_fs_init_inode_dblk_idx(
-    inode_ref: *mut crate::Ext4InodeRef,
-    iblock: u32,           // ext4_lblk_t
-    fblock: *mut u64,      // ext4_fsblk_t*
-) -> i32 {
-    debug!("ext4_fs_init_inode_dblk_idx: iblock={}", iblock);
-    EOK
+/// 写回修改过的 superblock
+///
+/// 分配和释放块、inode 时只在内存中更新 superblock 的空闲计数，不逐次写回设备；
+/// 由调用方在同步缓存时或按固定间隔调用这里统一写回。同时更新 s_kbytes_written，
+/// 此后有数据写入设备时也会写回 superblock（写回 superblock 本身不计入，避免每次同步都再写一次）。
+/// 只读挂载或没有修改时不做任何事。
+pub unsafe fn ext4_fs_commit_sb(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        if (*fs).read_only {
+            return EOK;
+        }
+        if ext4_fs_update_kbytes_written(fs) {
+            (*fs).sb_dirty = true;
+        }
+        if !(*fs).sb_dirty {
+            return EOK;
+        }
+        let pending = (*fs).bytes_written;
+        let r = ext4_sb_write((*fs).bdev, &mut (*fs).sb);
+        (*fs).bytes_written = pending;
+        if r == EOK {
+            (*fs).sb_dirty = false;
+        }
+        r
+    }
+}
+
+/// 关闭文件系统
+///
+/// 写回缓存中的脏块，可写挂载时恢复 VALID 状态并写回 superblock。
+pub unsafe fn ext4_fs_fini(fs: *mut Ext4Filesystem) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_FS, "ext4_fs_fini");
+    unsafe {
+        let bdev = (*fs).bdev;
+        if !(*bdev).bc.is_null() {
+            let r = ext4_block_cache_flush(bdev);
+            if r != EOK {
+                return r;
+            }
+        }
+
+        if (*fs).read_only {
+            return EOK;
+        }
+
+        ext4_fs_update_kbytes_written(fs);
+        let sb = &mut (*fs).sb;
+        let state = u16::from_le(sb.state);
+        sb.state = (state | EXT4_SUPERBLOCK_STATE_VALID_FS).to_le();
+        ext4_sb_write(bdev, sb)
+    }
 }
@@ -0,0 +1,200 @@
+//! 目录哈希模块
+//!
+//! 对应C实现: ext4_hash.c
+//!
+//! htree 目录按文件名哈希排序，支持 legacy、half_md4、tea 三种算法，
+//! 各有按有符号 / 无符号字符处理文件名的两个版本。
+
+use crate::consts::*;
+use crate::debug::*;
+
+const K1: u32 = 0;
+const K2: u32 = 0o13240474631;
+const K3: u32 = 0o15666365641;
+
+fn f(x: u32, y: u32, z: u32) -> u32 {
+    z ^ (x & (y ^ z))
+}
+
+fn g(x: u32, y: u32, z: u32) -> u32 {
+    (x & y).wrapping_add((x ^ y) & z)
+}
+
+fn h(x: u32, y: u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+/// MD4 的一步：a = (a + f(b, c, d) + x) <<< s
+fn round(func: fn(u32, u32, u32) -> u32, a: &mut u32, b: u32, c: u32, d: u32, x: u32, s: u32) {
+    *a = a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s);
+}
+
+/// 半轮 MD4
+fn ext2_half_md4(hash: &mut [u32; 4], data: &[u32; 8]) {
+    let [mut a, mut b, mut c, mut d] = *hash;
+
+    // 第一轮
+    for i in (0..8).step_by(4) {
+        round(f, &mut a, b, c, d, data[i].wrapping_add(K1), 3);
+        round(f, &mut d, a, b, c, data[i + 1].wrapping_add(K1), 7);
+        round(f, &mut c, d, a, b, data[i + 2].wrapping_add(K1), 11);
+        round(f, &mut b, c, d, a, data[i + 3].wrapping_add(K1), 19);
+    }
+
+    // 第二轮
+    for i in [1, 0] {
+        round(g, &mut a, b, c, d, data[i].wrapping_add(K2), 3);
+        round(g, &mut d, a, b, c, data[i + 2].wrapping_add(K2), 5);
+        round(g, &mut c, d, a, b, data[i + 4].wrapping_add(K2), 9);
+        round(g, &mut b, c, d, a, data[i + 6].wrapping_add(K2), 13);
+    }
+
+    // 第三轮
+    for i in [3, 1] {
+        round(h, &mut a, b, c, d, data[i].wrapping_add(K3), 3);
+        round(h, &mut d, a, b, c, data[i + 4].wrapping_add(K3), 9);
+        round(h, &mut c, d, a, b, data[i - 1].wrapping_add(K3), 11);
+        round(h, &mut b, c, d, a, data[i + 3].wrapping_add(K3), 15);
+    }
+
+    hash[0] = hash[0].wrapping_add(a);
+    hash[1] = hash[1].wrapping_add(b);
+    hash[2] = hash[2].wrapping_add(c);
+    hash[3] = hash[3].wrapping_add(d);
+}
+
+/// TEA 加密
+fn ext2_tea(hash: &mut [u32; 4], data: &[u32; 8]) {
+    const TEA_DELTA: u32 = 0x9E37_79B9;
+    let (mut x, mut y) = (hash[0], hash[1]);
+
+    for i in 1..=16u32 {
+        let sum = i.wrapping_mul(TEA_DELTA);
+        x = x.wrapping_add(
+            (y << 4).wrapping_add(data[0]) ^ y.wrapping_add(sum) ^ (y >> 5).wrapping_add(data[1]),
+        );
+        y = y.wrapping_add(
+            (x << 4).wrapping_add(data[2]) ^ x.wrapping_add(sum) ^ (x >> 5).wrapping_add(data[3]),
+        );
+    }
+
+    hash[0] = hash[0].wrapping_add(x);
+    hash[1] = hash[1].wrapping_add(y);
+}
+
+/// 按有符号或无符号字符取文件名中的字节
+fn name_byte(b: u8, unsigned_char: bool) -> u32 {
+    if unsigned_char {
+        b as u32
+    } else {
+        b as i8 as i32 as u32
+    }
+}
+
+/// legacy 哈希
+fn ext2_legacy_hash(name: &[u8], unsigned_char: bool) -> u32 {
+    let (mut h1, mut h2) = (0x12A3_FE2Du32, 0x37AB_E8F9u32);
+    const MULTI: u32 = 0x006D_22F5;
+
+    for &b in name {
+        let val = name_byte(b, unsigned_char);
+        let mut h0 = h2.wrapping_add(h1 ^ val.wrapping_mul(MULTI));
+        if h0 & 0x8000_0000 != 0 {
+            h0 = h0.wrapping_sub(0x7FFF_FFFF);
+        }
+        h2 = h1;
+        h1 = h0;
+    }
+    h1 << 1
+}
+
+/// 将文件名剩余部分的前 dlen 字节按大端拼成32位字，不足部分用剩余长度填充
+fn ext2_prep_hashbuf(src: &[u8], dst: &mut [u32; 8], dlen: usize, unsigned_char: bool) {
+    let slen = src.len() as u32;
+    let padding = slen | (slen << 8) | (slen << 16) | (slen << 24);
+    let len = src.len().min(dlen);
+    let words = dlen / 4;
+
+    let mut buf_val = padding;
+    let mut n = 0;
+    for (i, &b) in src[..len].iter().enumerate() {
+        if i % 4 == 0 {
+            buf_val = padding;
+        }
+        buf_val = (buf_val << 8).wrapping_add(name_byte(b, unsigned_char));
+        if i % 4 == 3 {
+            dst[n] = buf_val;
+            n += 1;
+            buf_val = padding;
+        }
+    }
+
+    if n < words {
+        dst[n] = buf_val;
+        n += 1;
+    }
+    for w in &mut dst[n..words] {
+        *w = padding;
+    }
+}
+
+/// 计算文件名的 htree 哈希
+///
+/// hash_seed 全为0时使用默认初值。哈希版本不支持或文件名长度不在 1..=255 时返回 ENOTSUP。
+pub fn ext2_htree_hash(
+    name: &[u8],
+    hash_seed: Option<&[u32; 4]>,
+    hash_version: u32,
+    hash_major: &mut u32,
+    hash_minor: &mut u32,
+) -> i32 {
+    *hash_major = 0;
+    *hash_minor = 0;
+    if name.is_empty() || name.len() > 255 {
+        return ENOTSUP;
+    }
+
+    let mut hash = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    if let Some(seed) = hash_seed.filter(|s| s.iter().any(|&w| w != 0)) {
+        hash = *seed;
+    }
+
+    let mut data = [0u32; 8];
+    let unsigned_char = matches!(
+        hash_version,
+        EXT2_HTREE_LEGACY_UNSIGNED | EXT2_HTREE_HALF_MD4_UNSIGNED | EXT2_HTREE_TEA_UNSIGNED
+    );
+    let (major, minor) = match hash_version {
+        EXT2_HTREE_TEA | EXT2_HTREE_TEA_UNSIGNED => {
+            let mut rest = name;
+            while !rest.is_empty() {
+                ext2_prep_hashbuf(rest, &mut data, 16, unsigned_char);
+                ext2_tea(&mut hash, &data);
+                rest = &rest[rest.len().min(16)..];
+            }
+            (hash[0], hash[1])
+        }
+        EXT2_HTREE_LEGACY | EXT2_HTREE_LEGACY_UNSIGNED => (ext2_legacy_hash(name, unsigned_char), 0),
+        EXT2_HTREE_HALF_MD4 | EXT2_HTREE_HALF_MD4_UNSIGNED => {
+            let mut rest = name;
+            while !rest.is_empty() {
+                ext2_prep_hashbuf(rest, &mut data, 32, unsigned_char);
+                ext2_half_md4(&mut hash, &data);
+                rest = &rest[rest.len().min(32)..];
+            }
+            (hash[1], hash[2])
+        }
+        _ => {
+            ext4_dbg!(DEBUG_HASH, Warn, "unsupported hash version {}", hash_version);
+            return ENOTSUP;
+        }
+    };
+
+    let mut major = major & !1;
+    if major == EXT2_HTREE_EOF << 1 {
+        major = (EXT2_HTREE_EOF - 1) << 1;
+    }
+    *hash_major = major;
+    *hash_minor = minor;
+    EOK
+}
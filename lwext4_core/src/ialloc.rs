@@ -0,0 +1,329 @@
+//! Inode 分配模块
+//!
+//! 对应C实现: ext4_ialloc.c
+
+
+use crate::bitmap::*;
+use crate::block::{ext4_block_get, ext4_block_pin, ext4_block_set, ext4_block_set_dirty};
+use crate::block_group::*;
+use crate::crc::{ext4_crc32c, ext4_sb_csum_seed};
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockGroup, Ext4BlockGroupRef, Ext4Filesystem, Ext4Superblock};
+
+/// inode 编号所在的块组
+pub fn ext4_ialloc_get_bgid_of_inode(sb: &Ext4Superblock, inode: u32) -> u32 {
+    (inode - 1) / u32::from_le(sb.inodes_per_group)
+}
+
+/// inode 编号在块组内的索引
+pub fn ext4_ialloc_inode_to_bgidx(sb: &Ext4Superblock, inode: u32) -> u32 {
+    (inode - 1) % u32::from_le(sb.inodes_per_group)
+}
+
+/// 块组内索引对应的 inode 编号
+pub fn ext4_ialloc_bgidx_to_inode(sb: &Ext4Superblock, index: u32, bgid: u32) -> u32 {
+    bgid * u32::from_le(sb.inodes_per_group) + index + 1
+}
+
+/// 计算 inode 位图校验和
+pub fn ext4_ialloc_bitmap_csum(sb: &Ext4Superblock, bitmap: &[u8]) -> u32 {
+    let inodes_per_group = u32::from_le(sb.inodes_per_group);
+    let checksum = ext4_sb_csum_seed(sb);
+    ext4_crc32c(checksum, &bitmap[..inodes_per_group.div_ceil(8) as usize])
+}
+
+/// 更新块组描述符中的 inode 位图校验和
+pub fn ext4_ialloc_set_bitmap_csum(sb: &Ext4Superblock, bg: &mut Ext4BlockGroup, bitmap: &[u8]) {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return;
+    }
+    let csum = ext4_ialloc_bitmap_csum(sb, bitmap);
+    bg.inode_bitmap_csum_lo = (csum as u16).to_le();
+    if ext4_sb_get_desc_size(sb) == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE {
+        bg.inode_bitmap_csum_hi = ((csum >> 16) as u16).to_le();
+    }
+}
+
+/// 校验 inode 位图校验和
+pub fn ext4_ialloc_verify_bitmap_csum(sb: &Ext4Superblock, bg: &Ext4BlockGroup, bitmap: &[u8]) -> bool {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return true;
+    }
+    let csum = ext4_ialloc_bitmap_csum(sb, bitmap);
+    if u16::from_le(bg.inode_bitmap_csum_lo) != csum as u16 {
+        return false;
+    }
+    if ext4_sb_get_desc_size(sb) == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE
+        && u16::from_le(bg.inode_bitmap_csum_hi) != (csum >> 16) as u16
+    {
+        return false;
+    }
+    true
+}
+
+/// 加载块组的 inode 位图（INODE_UNINIT 的块组先初始化位图）
+unsafe fn ext4_ialloc_load_bitmap(bg_ref: *mut Ext4BlockGroupRef, b: *mut Ext4Block) -> i32 {
+    unsafe {
+        let fs = (*bg_ref).fs;
+        let sb = &(*fs).sb;
+        if ext4_bg_has_flag(&*(*bg_ref).block_group, EXT4_BLOCK_GROUP_INODE_UNINIT) {
+            let r = ext4_fs_init_inode_bitmap(bg_ref);
+            if r != EOK {
+                return r;
+            }
+            ext4_bg_clear_flag(&mut *(*bg_ref).block_group, EXT4_BLOCK_GROUP_INODE_UNINIT);
+            (*bg_ref).dirty = true;
+        }
+
+        let bg = &*(*bg_ref).block_group;
+        let r = ext4_block_get((*fs).bdev, b, ext4_bg_get_inode_bitmap(bg, sb));
+        if r != EOK {
+            return r;
+        }
+        // 位图访问频繁，尽量常驻缓存
+        ext4_block_pin((*fs).bdev, b);
+        let bitmap = core::slice::from_raw_parts((*b).data, ext4_sb_get_block_size(sb) as usize);
+        if !ext4_ialloc_verify_bitmap_csum(sb, bg, bitmap) {
+            ext4_dbg!(DEBUG_IALLOC, Warn, "inode bitmap checksum failed: bg {}", (*bg_ref).index);
+        }
+    }
+    EOK
+}
+
+/// 释放 inode
+pub unsafe fn ext4_ialloc_free_inode(fs: *mut Ext4Filesystem, index: u32, is_dir: bool) -> i32 {
+    ext4_dbg!(DEBUG_IALLOC, Debug, "ext4_ialloc_free_inode: index={}, is_dir={}", index, is_dir);
+    unsafe {
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let bgid = ext4_ialloc_get_bgid_of_inode(sb, index);
+
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        let mut b = Ext4Block::new();
+        let r = ext4_ialloc_load_bitmap(&mut bg_ref, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        let bg = &mut *bg_ref.block_group;
+        let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+        let index_in_group = ext4_ialloc_inode_to_bgidx(sb, index);
+        // 重复释放不改动计数
+        if !ext4_bmap_is_bit_set(bitmap, index_in_group) {
+            ext4_dbg!(DEBUG_IALLOC, Warn, "freeing already free inode {}", index);
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            return if r != EOK { r } else { r2 };
+        }
+        ext4_bmap_bit_clr(bitmap, index_in_group);
+        ext4_ialloc_set_bitmap_csum(sb, bg, bitmap);
+        ext4_block_set_dirty(&mut b);
+        let r = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        // 更新块组计数
+        if is_dir {
+            let used_dirs = ext4_bg_get_used_dirs_count(bg, sb);
+            ext4_bg_set_used_dirs_count(bg, sb, used_dirs.saturating_sub(1));
+        }
+        let free_inodes = ext4_bg_get_free_inodes_count(bg, sb);
+        ext4_bg_set_free_inodes_count(bg, sb, free_inodes + 1);
+        bg_ref.dirty = true;
+
+        let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+
+        // 更新 superblock 计数
+        let sb_free_inodes = u32::from_le(sb.free_inodes_count);
+        sb.free_inodes_count = (sb_free_inodes + 1).to_le();
+        (*fs).sb_dirty = true;
+    }
+    EOK
+}
+
+/// 将指定 inode 标记为已使用（已使用时不做改动）
+///
+/// 供日志回放时补全位图使用，计数与分配 inode 时的更新方式相同。
+pub unsafe fn ext4_ialloc_mark_used(fs: *mut Ext4Filesystem, index: u32, is_dir: bool) -> i32 {
+    ext4_dbg!(DEBUG_IALLOC, Debug, "ext4_ialloc_mark_used: index={}, is_dir={}", index, is_dir);
+    unsafe {
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let bgid = ext4_ialloc_get_bgid_of_inode(sb, index);
+
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        let mut b = Ext4Block::new();
+        let r = ext4_ialloc_load_bitmap(&mut bg_ref, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        let bg = &mut *bg_ref.block_group;
+        let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+        let idx_in_bg = ext4_ialloc_inode_to_bgidx(sb, index);
+        if ext4_bmap_is_bit_set(bitmap, idx_in_bg) {
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            return if r != EOK { r } else { r2 };
+        }
+
+        ext4_bmap_bit_set(bitmap, idx_in_bg);
+        ext4_ialloc_set_bitmap_csum(sb, bg, bitmap);
+        ext4_block_set_dirty(&mut b);
+        let r = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK {
+            ext4_fs_put_block_group_ref(&mut bg_ref);
+            return r;
+        }
+
+        let free_inodes = ext4_bg_get_free_inodes_count(bg, sb);
+        ext4_bg_set_free_inodes_count(bg, sb, free_inodes.saturating_sub(1));
+        if is_dir {
+            let used_dirs = ext4_bg_get_used_dirs_count(bg, sb);
+            ext4_bg_set_used_dirs_count(bg, sb, used_dirs + 1);
+        }
+        let inodes_in_bg = ext4_inodes_in_group_cnt(sb, bgid);
+        let unused = ext4_bg_get_itable_unused(bg, sb);
+        if idx_in_bg >= inodes_in_bg - unused {
+            ext4_bg_set_itable_unused(bg, sb, inodes_in_bg - (idx_in_bg + 1));
+        }
+        bg_ref.dirty = true;
+
+        let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+
+        let sb_free_inodes = u32::from_le(sb.free_inodes_count);
+        sb.free_inodes_count = sb_free_inodes.saturating_sub(1).to_le();
+        (*fs).sb_dirty = true;
+    }
+    EOK
+}
+
+/// 在块组位图中查找第一个空闲且不是保留 inode 的位置
+///
+/// first_ino 可能落在块组位图中间（inodes_per_group 较小时），此前的位即使被清除也不能分配；
+/// 日志 inode 不在保留范围内时同样跳过。
+fn ext4_ialloc_find_free(sb: &Ext4Superblock, bitmap: &[u8], bgid: u32, inodes_in_bg: u32, idx: &mut u32) -> bool {
+    let group_first = bgid * u32::from_le(sb.inodes_per_group) + 1;
+    let mut from = ext4_sb_first_ino(sb).saturating_sub(group_first).min(inodes_in_bg);
+    while ext4_bmap_bit_find_clr(bitmap, from, inodes_in_bg, idx) == EOK {
+        if !ext4_sb_is_reserved_ino(sb, ext4_ialloc_bgidx_to_inode(sb, *idx, bgid)) {
+            return true;
+        }
+        from = *idx + 1;
+    }
+    false
+}
+
+/// 分配 inode
+///
+/// 从上次分配的块组开始查找，到达末尾后回到第0组继续。
+pub unsafe fn ext4_ialloc_alloc_inode(fs: *mut Ext4Filesystem, idx: *mut u32, is_dir: bool) -> i32 {
+    unsafe {
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let bg_count = ext4_block_group_cnt(sb);
+        let start = (*fs).last_inode_bg_id % bg_count;
+
+        for i in 0..bg_count {
+            let bgid = (start + i) % bg_count;
+
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+
+            if ext4_bg_get_free_inodes_count(&*bg_ref.block_group, sb) == 0 {
+                let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+                if r != EOK {
+                    return r;
+                }
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_ialloc_load_bitmap(&mut bg_ref, &mut b);
+            if r != EOK {
+                ext4_fs_put_block_group_ref(&mut bg_ref);
+                return r;
+            }
+
+            let bg = &mut *bg_ref.block_group;
+            let bitmap = core::slice::from_raw_parts_mut(b.data, block_size as usize);
+            let inodes_in_bg = ext4_inodes_in_group_cnt(sb, bgid);
+            let mut idx_in_bg = 0;
+            if !ext4_ialloc_find_free(sb, bitmap, bgid, inodes_in_bg, &mut idx_in_bg) {
+                // 计数与位图不一致，尝试下一个块组
+                let r = ext4_block_set((*fs).bdev, &mut b);
+                let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+                if r != EOK {
+                    return r;
+                }
+                if r2 != EOK {
+                    return r2;
+                }
+                continue;
+            }
+
+            ext4_bmap_bit_set(bitmap, idx_in_bg);
+            ext4_ialloc_set_bitmap_csum(sb, bg, bitmap);
+            ext4_block_set_dirty(&mut b);
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                ext4_fs_put_block_group_ref(&mut bg_ref);
+                return r;
+            }
+
+            // 更新块组计数
+            let free_inodes = ext4_bg_get_free_inodes_count(bg, sb);
+            ext4_bg_set_free_inodes_count(bg, sb, free_inodes - 1);
+            if is_dir {
+                let used_dirs = ext4_bg_get_used_dirs_count(bg, sb);
+                ext4_bg_set_used_dirs_count(bg, sb, used_dirs + 1);
+            }
+            // inode 表尾部未使用部分缩小
+            let unused = ext4_bg_get_itable_unused(bg, sb);
+            if idx_in_bg >= inodes_in_bg - unused {
+                ext4_bg_set_itable_unused(bg, sb, inodes_in_bg - (idx_in_bg + 1));
+            }
+            bg_ref.dirty = true;
+
+            let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+
+            // 更新 superblock 计数
+            let sb_free_inodes = u32::from_le(sb.free_inodes_count);
+            sb.free_inodes_count = (sb_free_inodes - 1).to_le();
+            (*fs).sb_dirty = true;
+
+            *idx = ext4_ialloc_bgidx_to_inode(sb, idx_in_bg, bgid);
+            (*fs).last_inode_bg_id = bgid;
+            ext4_dbg!(DEBUG_IALLOC, Debug, "ext4_ialloc_alloc_inode: index={}, is_dir={}", *idx, is_dir);
+            return EOK;
+        }
+        ext4_dbg!(DEBUG_IALLOC, Info, "ext4_ialloc_alloc_inode: no free inodes");
+    }
+    ENOSPC
+}
@@ -1,37 +1,29 @@
 //! Inode 操作模块
+//!
+//! 对应C实现: ext4_inode.c 以及 ext4_fs.c 中的 inode 引用、块映射部分
 
-use log::debug;
-use crate::{Ext4Result, Ext4Error, Ext4Filesystem, Ext4InodeRef, Ext4Inode, Ext4Superblock, BlockDevice};
-use crate::consts::*;
-
-/// 初始化文件系统并获取 inode 引用（占位实现）
-pub fn ext4_fs_get_inode_ref(
-    fs: *mut Ext4Filesystem,
-    ino: u32,
-    inode_ref: *mut Ext4InodeRef,
-) -> i32 {
-    // TODO: 实现 inode 读取逻辑
-    // 1. 计算 inode 所在的块组
-    // 2. 计算 inode 在块组中的偏移
-    // 3. 读取 inode 数据
-    // 4. 填充 inode_ref
+use core::mem::offset_of;
+use core::ptr;
 
-    debug!("ext4_fs_get_inode_ref: ino={}", ino);
-    EOK  // 临时返回成功
-}
+use alloc::boxed::Box;
 
-/// 释放 inode 引用（占位实现）
-pub fn ext4_fs_put_inode_ref(inode_ref: *mut Ext4InodeRef) -> i32 {
-    // TODO: 实现 inode 释放逻辑
-    // 1. 如果 dirty，写回磁盘
-    // 2. 释放内存
+use crate::balloc::*;
+use crate::block::*;
+use crate::block_group::*;
+use crate::crc::{ext4_crc32c, ext4_sb_csum_seed};
+use crate::extent::*;
+use crate::extent_status::ext4_es_drop_inode;
+use crate::ialloc::*;
+use crate::quota::{ext4_quota_charge_inode, ext4_quota_release_inode};
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockGroupRef, Ext4Filesystem, Ext4Inode, Ext4InodeRef, Ext4Superblock};
 
-    debug!("ext4_fs_put_inode_ref");
-    EOK
-}
+// ===== inode 字段访问（对应 ext4_inode.c） =====
 
 /// 获取 inode 大小
-pub fn ext4_inode_get_size(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u64 {
+pub unsafe fn ext4_inode_get_size(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u64 {
     // sb参数在此函数中未使用，但为了与C API一致性保留
     let _ = sb;
     unsafe {
@@ -42,102 +34,1258 @@ pub fn ext4_inode_get_size(sb: *const Ext4Superblock, inode: *const Ext4Inode) -
 }
 
 /// 设置 inode 大小
-pub fn ext4_inode_set_size(inode: *mut Ext4Inode, size: u64) {
+pub unsafe fn ext4_inode_set_size(inode: *mut Ext4Inode, size: u64) {
     unsafe {
         (*inode).size_lo = (size as u32).to_le();
         (*inode).size_hi = ((size >> 32) as u32).to_le();
     }
 }
 
+/// inode 能够表示的最大文件大小（对应 Linux 的 ext4_max_size 与 ext4_max_bitmap_size）
+///
+/// 受逻辑块号（32位）、间接块映射的层数，以及 i_blocks 的位数（没有 huge_file 时为32位）限制。
+/// 间接映射的文件中，间接块本身也计入 i_blocks，需要从限制中扣除。
+pub unsafe fn ext4_inode_max_size(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u64 {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(&*sb) as u64;
+        let huge_file = ext4_sb_feature_ro_com(&*sb, EXT4_FRO_COM_HUGE_FILE);
+        if !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) {
+            return ext4_max_bitmap_blocks(block_size, huge_file).min(u32::MAX as u64) * block_size;
+        }
+        let mut max = u32::MAX as u64 * block_size;
+        if !huge_file {
+            max = max.min(u32::MAX as u64 * EXT4_INODE_BLOCK_SIZE as u64);
+        }
+        max
+    }
+}
+
+/// 间接块映射的文件最多能有的数据块数（对应 Linux 的 ext4_max_bitmap_size）
+///
+/// 数据块与映射它们所需的一级、二级、三级间接块合计不能超过 i_blocks 能表示的块数。
+fn ext4_max_bitmap_blocks(block_size: u64, huge_file: bool) -> u64 {
+    let ppb = block_size / 4;
+    let sectors_per_block = block_size / EXT4_INODE_BLOCK_SIZE as u64;
+    let i_blocks_bits = if huge_file { 48 } else { 32 };
+    let mut upper = ((1u64 << i_blocks_bits) - 1) / sectors_per_block;
+
+    // 映射树本身能寻址的数据块数，以及全部映射时需要的间接块数
+    let direct = EXT4_INODE_DIRECT_BLOCKS as u64;
+    let res = direct + ppb + ppb * ppb + ppb * ppb * ppb;
+    let meta = 1 + (1 + ppb) + (1 + ppb + ppb * ppb);
+    if res + meta <= upper {
+        return res;
+    }
+
+    // i_blocks 先达到上限：计算寻址 upper 个块需要的间接块数并扣除
+    let res = upper;
+    upper -= direct;
+    let mut meta = 1;
+    upper -= ppb;
+    if upper < ppb * ppb {
+        meta += 1 + upper.div_ceil(ppb);
+        return res - meta;
+    }
+    meta += 1 + ppb;
+    upper -= ppb * ppb;
+    meta += 1 + upper.div_ceil(ppb) + upper.div_ceil(ppb * ppb);
+    res - meta
+}
+
+/// 设置 inode 大小，超过 2 GiB 时启用 large_file 特性（对应 Linux 的 ext4_update_inode_size）
+///
+/// 特性标志新设置时立即写回 superblock。
+pub unsafe fn ext4_fs_set_inode_size(inode_ref: *mut Ext4InodeRef, size: u64) -> i32 {
+    unsafe {
+        ext4_inode_set_size((*inode_ref).inode, size);
+        (*inode_ref).dirty = true;
+        let fs = (*inode_ref).fs;
+        let sb = &mut (*fs).sb;
+        if size > i32::MAX as u64 && !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_LARGE_FILE) {
+            sb.feature_ro_compat = (u32::from_le(sb.feature_ro_compat) | EXT4_FRO_COM_LARGE_FILE).to_le();
+            return ext4_sb_write((*fs).bdev, sb);
+        }
+    }
+    EOK
+}
+
 /// 获取 inode 模式
-pub fn ext4_inode_get_mode(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u32 {
+pub unsafe fn ext4_inode_get_mode(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u32 {
     // sb参数在此函数中未使用，但为了与C API一致性保留
     let _ = sb;
     unsafe { u16::from_le((*inode).mode) as u32 }
 }
 
 /// 设置 inode 模式
-pub fn ext4_inode_set_mode(sb: *mut Ext4Superblock, inode: *mut Ext4Inode, mode: u32) {
+pub unsafe fn ext4_inode_set_mode(sb: *mut Ext4Superblock, inode: *mut Ext4Inode, mode: u32) {
     // sb参数在此函数中未使用，但为了与C API一致性保留
     let _ = sb;
     unsafe { (*inode).mode = (mode as u16).to_le(); }
 }
 
-/// 获取 inode 块数
-pub fn ext4_inode_get_blocks_count(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u64 {
-    // sb参数在此函数中未使用，但为了与C API一致性保留
-    let _ = sb;
-    unsafe { u32::from_le((*inode).blocks_count_lo) as u64 }
+/// 获取 inode 类型（模式的高4位）
+pub unsafe fn ext4_inode_type(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u32 {
+    unsafe { ext4_inode_get_mode(sb, inode) & EXT4_INODE_MODE_TYPE_MASK as u32 }
 }
 
-/// 设置 inode 删除时间
-pub fn ext4_inode_set_del_time(inode: *mut Ext4Inode, time: u32) {
-    unsafe { (*inode).deletion_time = time.to_le(); }
+/// 判断 inode 类型
+pub unsafe fn ext4_inode_is_type(sb: *const Ext4Superblock, inode: *const Ext4Inode, ty: u16) -> bool {
+    unsafe { ext4_inode_type(sb, inode) == ty as u32 }
+}
+
+/// 获取硬链接数
+pub unsafe fn ext4_inode_get_links_cnt(inode: *const Ext4Inode) -> u16 {
+    unsafe { u16::from_le((*inode).links_count) }
+}
+
+/// 设置硬链接数
+pub unsafe fn ext4_inode_set_links_cnt(inode: *mut Ext4Inode, cnt: u16) {
+    unsafe { (*inode).links_count = cnt.to_le(); }
+}
+
+/// 获取 inode 块数（以512字节为单位）
+pub unsafe fn ext4_inode_get_blocks_count(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u64 {
+    unsafe {
+        let mut cnt = u32::from_le((*inode).blocks_count_lo) as u64;
+        if ext4_sb_feature_ro_com(&*sb, EXT4_FRO_COM_HUGE_FILE) {
+            // 48位字段
+            cnt |= (u16::from_le((*inode).blocks_high) as u64) << 32;
+            if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_HUGE_FILE) {
+                // 以文件系统块为单位
+                let block_size = ext4_sb_get_block_size(&*sb);
+                return cnt * (block_size / EXT4_INODE_BLOCK_SIZE) as u64;
+            }
+        }
+        cnt
+    }
+}
+
+/// inode 块数（以512字节为单位）能否用当前特性表示
+///
+/// 没有 huge_file 时为32位；有 huge_file 时为48位，超出后以文件系统块为单位（同样48位）。
+pub fn ext4_inode_blocks_count_fits(sb: &Ext4Superblock, count: u64) -> bool {
+    if count <= u32::MAX as u64 {
+        return true;
+    }
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_HUGE_FILE) {
+        return false;
+    }
+    let block_bits = ext4_sb_get_block_size(sb).trailing_zeros();
+    count < 1 << 48 || count >> (block_bits - 9) < 1 << 48
+}
+
+/// 设置 inode 块数（以512字节为单位），无法表示时返回 EFBIG
+pub unsafe fn ext4_inode_set_blocks_count(sb: *const Ext4Superblock, inode: *mut Ext4Inode, count: u64) -> i32 {
+    unsafe {
+        // 32位上限
+        if count <= u32::MAX as u64 {
+            (*inode).blocks_count_lo = (count as u32).to_le();
+            (*inode).blocks_high = 0;
+            ext4_inode_clear_flag(inode, EXT4_INODE_FLAG_HUGE_FILE);
+            return EOK;
+        }
+
+        if !ext4_inode_blocks_count_fits(&*sb, count) {
+            return EFBIG;
+        }
+
+        // 48位上限
+        let mut count = count;
+        if count < 1 << 48 {
+            ext4_inode_clear_flag(inode, EXT4_INODE_FLAG_HUGE_FILE);
+        } else {
+            let block_bits = ext4_sb_get_block_size(&*sb).trailing_zeros();
+            ext4_inode_set_flag(inode, EXT4_INODE_FLAG_HUGE_FILE);
+            count >>= block_bits - 9;
+        }
+        (*inode).blocks_count_lo = (count as u32).to_le();
+        (*inode).blocks_high = ((count >> 32) as u16).to_le();
+    }
+    EOK
+}
+
+/// 获取 inode 标志
+pub unsafe fn ext4_inode_get_flags(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).flags) }
+}
+
+/// 设置 inode 标志
+pub unsafe fn ext4_inode_set_flags(inode: *mut Ext4Inode, flags: u32) {
+    unsafe { (*inode).flags = flags.to_le(); }
+}
+
+/// 检查 inode 标志
+pub unsafe fn ext4_inode_has_flag(inode: *const Ext4Inode, flag: u32) -> bool {
+    unsafe { ext4_inode_get_flags(inode) & flag != 0 }
+}
+
+/// 设置 inode 标志位
+pub unsafe fn ext4_inode_set_flag(inode: *mut Ext4Inode, flag: u32) {
+    unsafe { ext4_inode_set_flags(inode, ext4_inode_get_flags(inode) | flag) }
 }
 
 /// 清除 inode 标志
-pub fn ext4_inode_clear_flag(inode: *mut Ext4Inode, flag: u32) {
+pub unsafe fn ext4_inode_clear_flag(inode: *mut Ext4Inode, flag: u32) {
     unsafe {
         let flags = u32::from_le((*inode).flags);
         (*inode).flags = (flags & !flag).to_le();
     }
 }
 
-/// 增加硬链接计数（占位实现）
-pub fn ext4_fs_inode_links_count_inc(inode_ref: *mut Ext4InodeRef) {
-    // TODO: 实现链接计数增加
-    debug!("ext4_fs_inode_links_count_inc");
+/// 获取 inode 版本号
+pub unsafe fn ext4_inode_get_generation(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).generation) }
+}
+
+/// 设置 inode 版本号
+pub unsafe fn ext4_inode_set_generation(inode: *mut Ext4Inode, gen: u32) {
+    unsafe { (*inode).generation = gen.to_le(); }
+}
+
+/// 获取扩展属性块号
+pub unsafe fn ext4_inode_get_file_acl(inode: *const Ext4Inode, sb: *const Ext4Superblock) -> u64 {
+    unsafe {
+        let mut v = u32::from_le((*inode).file_acl_lo) as u64;
+        if u32::from_le((*sb).creator_os) == 0 {
+            // Linux
+            v |= (u16::from_le((*inode).file_acl_high) as u64) << 32;
+        }
+        v
+    }
+}
+
+/// 是否为快速符号链接（目标路径直接存放在 inode 的 blocks 字段中）
+///
+/// 与 Linux 一致：目标长度小于 blocks 字段，且除扩展属性块外没有占用数据块。
+/// 长度小于60的符号链接也可能存放在数据块中（旧版本内核或其他实现创建），不能只看长度。
+pub unsafe fn ext4_inode_is_fast_symlink(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> bool {
+    unsafe {
+        if !ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_SOFTLINK) {
+            return false;
+        }
+        let inline_len = core::mem::size_of_val(&(*inode).blocks) as u64;
+        if ext4_inode_get_size(sb, inode) >= inline_len {
+            return false;
+        }
+        let ea_blocks = if ext4_inode_get_file_acl(inode, sb) != 0 {
+            (ext4_sb_get_block_size(&*sb) / EXT4_INODE_BLOCK_SIZE) as u64
+        } else {
+            0
+        };
+        ext4_inode_get_blocks_count(sb, inode) <= ea_blocks
+    }
+}
+
+/// 获取设备文件的设备号（ext4 编码：主次设备号都小于256时为旧格式，存放在 blocks[0]，否则存放在 blocks[1]）
+pub unsafe fn ext4_inode_get_dev(inode: *const Ext4Inode) -> u32 {
+    unsafe {
+        let dev = u32::from_le((*inode).blocks[0]);
+        if dev != 0 {
+            dev
+        } else {
+            u32::from_le((*inode).blocks[1])
+        }
+    }
+}
+
+/// 设置设备文件的设备号（ext4 编码：次设备号低8位、主设备号12位、次设备号高12位）
+pub unsafe fn ext4_inode_set_dev(inode: *mut Ext4Inode, dev: u32) {
+    unsafe {
+        // 旧格式只有16位，放不下时改用 blocks[1]
+        if dev & !0xffff != 0 {
+            (*inode).blocks[0] = 0;
+            (*inode).blocks[1] = dev.to_le();
+        } else {
+            (*inode).blocks[0] = dev.to_le();
+            (*inode).blocks[1] = 0;
+        }
+        (*inode).blocks[2] = 0;
+    }
+}
+
+/// 设置扩展属性块号
+pub unsafe fn ext4_inode_set_file_acl(inode: *mut Ext4Inode, sb: *const Ext4Superblock, acl: u64) {
+    unsafe {
+        (*inode).file_acl_lo = (acl as u32).to_le();
+        if u32::from_le((*sb).creator_os) == 0 {
+            (*inode).file_acl_high = ((acl >> 32) as u16).to_le();
+        }
+    }
+}
+
+/// 获取 inode 删除时间
+pub unsafe fn ext4_inode_get_del_time(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).deletion_time) }
+}
+
+/// 设置 inode 删除时间
+pub unsafe fn ext4_inode_set_del_time(inode: *mut Ext4Inode, time: u32) {
+    unsafe { (*inode).deletion_time = time.to_le(); }
+}
+
+/// 获取 inode 访问时间
+pub unsafe fn ext4_inode_get_access_time(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).access_time) }
+}
+
+/// 设置 inode 访问时间
+pub unsafe fn ext4_inode_set_access_time(inode: *mut Ext4Inode, time: u32) {
+    unsafe { (*inode).access_time = time.to_le(); }
+}
+
+/// 获取 inode 改变时间
+pub unsafe fn ext4_inode_get_change_inode_time(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).change_inode_time) }
+}
+
+/// 设置 inode 改变时间
+pub unsafe fn ext4_inode_set_change_inode_time(inode: *mut Ext4Inode, time: u32) {
+    unsafe { (*inode).change_inode_time = time.to_le(); }
+}
+
+/// 获取 inode 修改时间
+pub unsafe fn ext4_inode_get_modif_time(inode: *const Ext4Inode) -> u32 {
+    unsafe { u32::from_le((*inode).modification_time) }
+}
+
+/// 设置 inode 修改时间
+pub unsafe fn ext4_inode_set_modif_time(inode: *mut Ext4Inode, time: u32) {
+    unsafe { (*inode).modification_time = time.to_le(); }
+}
+
+/// 获取 inode 所有者 uid（包含 osd2 中的高16位）
+pub unsafe fn ext4_inode_get_uid(inode: *const Ext4Inode) -> u32 {
+    unsafe { ((u16::from_le((*inode).uid_high) as u32) << 16) | u16::from_le((*inode).uid) as u32 }
+}
+
+/// 设置 inode 所有者 uid
+pub unsafe fn ext4_inode_set_uid(inode: *mut Ext4Inode, uid: u32) {
+    unsafe {
+        (*inode).uid = (uid as u16).to_le();
+        (*inode).uid_high = ((uid >> 16) as u16).to_le();
+    }
+}
+
+/// 获取 inode 组 gid（包含 osd2 中的高16位）
+pub unsafe fn ext4_inode_get_gid(inode: *const Ext4Inode) -> u32 {
+    unsafe { ((u16::from_le((*inode).gid_high) as u32) << 16) | u16::from_le((*inode).gid) as u32 }
+}
+
+/// 设置 inode 组 gid
+pub unsafe fn ext4_inode_set_gid(inode: *mut Ext4Inode, gid: u32) {
+    unsafe {
+        (*inode).gid = (gid as u16).to_le();
+        (*inode).gid_high = ((gid >> 16) as u16).to_le();
+    }
+}
+
+/// 获取额外 inode 大小
+pub unsafe fn ext4_inode_get_extra_isize(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> u16 {
+    unsafe {
+        if get_inode_size(&*sb) > EXT4_GOOD_OLD_INODE_SIZE {
+            u16::from_le((*inode).extra_isize)
+        } else {
+            0
+        }
+    }
+}
+
+/// 设置额外 inode 大小
+pub unsafe fn ext4_inode_set_extra_isize(sb: *const Ext4Superblock, inode: *mut Ext4Inode, size: u16) {
+    unsafe {
+        if get_inode_size(&*sb) > EXT4_GOOD_OLD_INODE_SIZE {
+            (*inode).extra_isize = size.to_le();
+        }
+    }
+}
+
+/// 获取直接块指针
+pub unsafe fn ext4_inode_get_direct_block(inode: *const Ext4Inode, idx: u32) -> u32 {
+    unsafe { u32::from_le((*inode).blocks[idx as usize]) }
 }
 
-/// 初始化 inode 块结构（占位实现）
-pub fn ext4_fs_inode_blocks_init(fs: *mut Ext4Filesystem, inode_ref: *mut Ext4InodeRef) {
-    // TODO: 初始化 inode 的块指针
-    debug!("ext4_fs_inode_blocks_init");
+/// 设置直接块指针
+pub unsafe fn ext4_inode_set_direct_block(inode: *mut Ext4Inode, idx: u32, fblock: u32) {
+    unsafe { (*inode).blocks[idx as usize] = fblock.to_le(); }
 }
 
-/// 获取 inode 的第 iblock 个数据块号（占位实现）
-pub fn ext4_fs_get_inode_dblk_idx(
+/// 获取间接块指针（0：一级，1：二级，2：三级）
+pub unsafe fn ext4_inode_get_indirect_block(inode: *const Ext4Inode, idx: u32) -> u32 {
+    unsafe { u32::from_le((*inode).blocks[EXT4_INODE_DIRECT_BLOCKS + idx as usize]) }
+}
+
+/// 设置间接块指针
+pub unsafe fn ext4_inode_set_indirect_block(inode: *mut Ext4Inode, idx: u32, fblock: u32) {
+    unsafe { (*inode).blocks[EXT4_INODE_DIRECT_BLOCKS + idx as usize] = fblock.to_le(); }
+}
+
+/// inode 是否可以截断
+pub unsafe fn ext4_inode_can_truncate(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> bool {
+    unsafe {
+        if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_APPEND)
+            || ext4_inode_has_flag(inode, EXT4_INODE_FLAG_IMMUTABLE)
+        {
+            return false;
+        }
+        ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_FILE)
+            || ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_DIRECTORY)
+            || ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_SOFTLINK)
+    }
+}
+
+// ===== inode 校验和 =====
+
+/// inode 相关元数据校验和的种子：crc32c(fs种子, inode编号, 版本号)
+pub fn ext4_inode_csum_seed(sb: &Ext4Superblock, index: u32, generation: u32) -> u32 {
+    let mut seed = ext4_sb_csum_seed(sb);
+    seed = ext4_crc32c(seed, &index.to_le_bytes());
+    ext4_crc32c(seed, &generation.to_le_bytes())
+}
+
+/// 校验和高16位是否位于 inode 中
+unsafe fn ext4_inode_has_csum_hi(sb: &Ext4Superblock, inode: *const Ext4Inode) -> bool {
+    unsafe {
+        get_inode_size(sb) > EXT4_GOOD_OLD_INODE_SIZE
+            && u16::from_le((*inode).extra_isize) as usize + EXT4_GOOD_OLD_INODE_SIZE as usize
+                >= offset_of!(Ext4Inode, checksum_hi) + 2
+    }
+}
+
+/// 计算 inode 校验和（校验和字段视为0）
+unsafe fn ext4_fs_inode_checksum(inode_ref: *mut Ext4InodeRef) -> u32 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode = (*inode_ref).inode;
+        let inode_size = get_inode_size(sb) as usize;
+        let raw = core::slice::from_raw_parts(inode as *const u8, inode_size);
+        let zero = [0u8; 2];
+
+        let mut checksum = ext4_inode_csum_seed(sb, (*inode_ref).index, ext4_inode_get_generation(inode));
+        let lo = offset_of!(Ext4Inode, checksum_lo);
+        checksum = ext4_crc32c(checksum, &raw[..lo]);
+        checksum = ext4_crc32c(checksum, &zero);
+        checksum = ext4_crc32c(checksum, &raw[lo + 2..EXT4_GOOD_OLD_INODE_SIZE as usize]);
+        if inode_size > EXT4_GOOD_OLD_INODE_SIZE as usize {
+            let hi = offset_of!(Ext4Inode, checksum_hi);
+            checksum = ext4_crc32c(checksum, &raw[EXT4_GOOD_OLD_INODE_SIZE as usize..hi]);
+            let mut off = hi;
+            if ext4_inode_has_csum_hi(sb, inode) {
+                checksum = ext4_crc32c(checksum, &zero);
+                off += 2;
+            }
+            checksum = ext4_crc32c(checksum, &raw[off..]);
+        } else {
+            checksum &= 0xFFFF;
+        }
+        checksum
+    }
+}
+
+/// 更新 inode 校验和
+unsafe fn ext4_fs_set_inode_checksum(inode_ref: *mut Ext4InodeRef) {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return;
+        }
+        let csum = ext4_fs_inode_checksum(inode_ref);
+        let inode = (*inode_ref).inode;
+        (*inode).checksum_lo = (csum as u16).to_le();
+        if ext4_inode_has_csum_hi(sb, inode) {
+            (*inode).checksum_hi = ((csum >> 16) as u16).to_le();
+        }
+    }
+}
+
+/// 校验 inode 校验和
+unsafe fn ext4_fs_verify_inode_csum(inode_ref: *mut Ext4InodeRef) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return true;
+        }
+        let inode = (*inode_ref).inode;
+        let mut stored = u16::from_le((*inode).checksum_lo) as u32;
+        let mut csum = ext4_fs_inode_checksum(inode_ref);
+        if ext4_inode_has_csum_hi(sb, inode) {
+            stored |= (u16::from_le((*inode).checksum_hi) as u32) << 16;
+        } else {
+            csum &= 0xFFFF;
+        }
+        stored == csum
+    }
+}
+
+// ===== inode 引用 =====
+
+/// 重新计算 inode 校验和（启用 metadata_csum 时使用，不校验旧值）
+///
+/// 全零的 inode（从未使用过）保持不变。
+pub unsafe fn ext4_fs_inode_csum_init(fs: *mut Ext4Filesystem, index: u32) -> i32 {
+    unsafe {
+        let mut inode_ref = Ext4InodeRef::new();
+        let r = __ext4_fs_get_inode_ref(fs, index, &mut inode_ref, false);
+        if r != EOK {
+            return r;
+        }
+        let inode_size = get_inode_size(&(*fs).sb) as usize;
+        let raw = core::slice::from_raw_parts(inode_ref.inode as *const u8, inode_size);
+        if raw.iter().any(|&b| b != 0) {
+            inode_ref.dirty = true;
+        }
+        ext4_fs_put_inode_ref(&mut inode_ref)
+    }
+}
+
+/// 获取 inode 引用
+///
+/// inode 指针直接指向块缓存中的 inode 表块，同一 inode 的多个引用共享同一份数据。
+pub unsafe fn ext4_fs_get_inode_ref(
+    fs: *mut Ext4Filesystem,
+    index: u32,
+    inode_ref: *mut Ext4InodeRef,
+) -> i32 {
+    unsafe { __ext4_fs_get_inode_ref(fs, index, inode_ref, true) }
+}
+
+/// 获取 inode 引用，initialized 为假时（新分配的 inode）不校验校验和
+pub(crate) unsafe fn __ext4_fs_get_inode_ref(
+    fs: *mut Ext4Filesystem,
+    index: u32,
+    inode_ref: *mut Ext4InodeRef,
+    initialized: bool,
+) -> i32 {
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_get_inode_ref: ino={}", index);
+    unsafe {
+        let sb = &(*fs).sb;
+        if index == 0 || index > u32::from_le(sb.inodes_count) {
+            return EINVAL;
+        }
+
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+        let block_group = (index - 1) / inodes_per_group;
+        let offset_in_group = (index - 1) % inodes_per_group;
+
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, block_group, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        let inode_table_start = ext4_bg_get_inode_table_first_block(&*bg_ref.block_group, sb);
+        let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+
+        let inode_size = get_inode_size(sb) as u64;
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let byte_offset_in_group = offset_in_group as u64 * inode_size;
+        // 损坏的描述符中的inode表位置可能使块号溢出
+        let Some(block_id) = inode_table_start.checked_add(byte_offset_in_group / block_size) else {
+            return EIO;
+        };
+
+        let r = ext4_block_get((*fs).bdev, &mut (*inode_ref).block, block_id);
+        if r != EOK {
+            return r;
+        }
+
+        let offset_in_block = (byte_offset_in_group % block_size) as usize;
+        (*inode_ref).inode = (*inode_ref).block.data.add(offset_in_block) as *mut Ext4Inode;
+        (*inode_ref).index = index;
+        (*inode_ref).fs = fs;
+        (*inode_ref).dirty = false;
+        (*inode_ref).block_group = block_group;
+
+        if initialized && !ext4_fs_verify_inode_csum(inode_ref) {
+            ext4_dbg!(DEBUG_INODE, Warn, "inode checksum failed: inode {}", index);
+        }
+    }
+    EOK
+}
+
+/// 释放 inode 引用（修改过时更新校验和并标记为脏）
+pub unsafe fn ext4_fs_put_inode_ref(inode_ref: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        // 未加载的引用无需处理
+        if (*inode_ref).block.buf.is_null() {
+            return EOK;
+        }
+        let fs = (*inode_ref).fs;
+        if (*inode_ref).dirty {
+            ext4_fs_set_inode_checksum(inode_ref);
+            ext4_block_set_dirty(&mut (*inode_ref).block);
+            (*inode_ref).dirty = false;
+        }
+        ext4_block_set((*fs).bdev, &mut (*inode_ref).block)
+    }
+}
+
+/// 拥有所有权的 inode 引用
+///
+/// 封装 ext4_inode_ref：由 get/alloc 获取，Drop 时调用 ext4_fs_put_inode_ref 归还，
+/// 提前返回的路径不会漏掉归还；需要归还结果时用 put。它只持有 inode 表块在块缓存中的引用
+/// 和指向文件系统的原始指针，不借用 ext4_fs，父目录和子项等多个引用可以同时存在。
+/// extent、目录等模块的函数通过 DerefMut 得到 &mut ext4_inode_ref。
+/// lwext4_arce::InodeRef 在纯 Rust 后端下也基于它。
+pub struct InodeRef(Box<Ext4InodeRef>);
+
+impl InodeRef {
+    /// 未关联 inode 的引用（释放时不做任何事）
+    pub fn new() -> Self {
+        Self(Box::new(Ext4InodeRef::new()))
+    }
+
+    /// 获取编号为 index 的 inode
+    pub unsafe fn get(fs: *mut Ext4Filesystem, index: u32) -> Result<Self, i32> {
+        let mut inode_ref = Self::new();
+        match unsafe { ext4_fs_get_inode_ref(fs, index, inode_ref.as_mut()) } {
+            EOK => Ok(inode_ref),
+            r => Err(r),
+        }
+    }
+
+    /// 分配新的 inode（filetype 为 EXT4_DE_* 目录项类型）
+    pub unsafe fn alloc(fs: *mut Ext4Filesystem, filetype: u32) -> Result<Self, i32> {
+        let mut inode_ref = Self::new();
+        match unsafe { ext4_fs_alloc_inode(fs, inode_ref.as_mut(), filetype) } {
+            EOK => Ok(inode_ref),
+            r => Err(r),
+        }
+    }
+
+    /// inode 编号
+    pub fn ino(&self) -> u32 {
+        self.0.index
+    }
+
+    /// 归还引用并返回 ext4_fs_put_inode_ref 的结果
+    pub fn put(mut self) -> i32 {
+        let r = unsafe { ext4_fs_put_inode_ref(self.as_mut()) };
+        // 写回失败时缓存块的引用也已归还，Drop 不能再归还一次
+        self.0.block = Ext4Block::new();
+        r
+    }
+}
+
+impl Default for InodeRef {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::ops::Deref for InodeRef {
+    type Target = Ext4InodeRef;
+
+    fn deref(&self) -> &Ext4InodeRef {
+        &self.0
+    }
+}
+
+impl core::ops::DerefMut for InodeRef {
+    fn deref_mut(&mut self) -> &mut Ext4InodeRef {
+        &mut self.0
+    }
+}
+
+impl AsMut<Ext4InodeRef> for InodeRef {
+    fn as_mut(&mut self) -> &mut Ext4InodeRef {
+        &mut self.0
+    }
+}
+
+impl Drop for InodeRef {
+    fn drop(&mut self) {
+        // put 之后 block.buf 为空，ext4_fs_put_inode_ref 不做任何事
+        let r = unsafe { ext4_fs_put_inode_ref(self.as_mut()) };
+        if r != EOK {
+            ext4_dbg!(DEBUG_INODE, Warn, "put inode {} failed: {}", self.0.index, r);
+        }
+    }
+}
+
+/// 目录项类型对应的 inode 模式
+pub fn ext4_fs_correspond_inode_mode(filetype: u32) -> u32 {
+    let mode = match filetype {
+        EXT4_DE_DIR => EXT4_INODE_MODE_DIRECTORY,
+        EXT4_DE_REG_FILE => EXT4_INODE_MODE_FILE,
+        EXT4_DE_SYMLINK => EXT4_INODE_MODE_SOFTLINK,
+        EXT4_DE_CHRDEV => EXT4_INODE_MODE_CHARDEV,
+        EXT4_DE_BLKDEV => EXT4_INODE_MODE_BLOCKDEV,
+        EXT4_DE_FIFO => EXT4_INODE_MODE_FIFO,
+        EXT4_DE_SOCK => EXT4_INODE_MODE_SOCKET,
+        // 默认按普通文件处理
+        _ => EXT4_INODE_MODE_FILE,
+    };
+    mode as u32
+}
+
+/// 分配并初始化新的 inode
+pub unsafe fn ext4_fs_alloc_inode(
+    fs: *mut Ext4Filesystem,
+    inode_ref: *mut Ext4InodeRef,
+    filetype: u32,
+) -> i32 {
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_alloc_inode: type={}", filetype);
+    unsafe {
+        let is_dir = filetype == EXT4_DE_DIR;
+
+        let mut index = 0u32;
+        let r = ext4_ialloc_alloc_inode(fs, &mut index, is_dir);
+        if r != EOK {
+            return r;
+        }
+
+        let r = __ext4_fs_get_inode_ref(fs, index, inode_ref, false);
+        if r != EOK {
+            ext4_ialloc_free_inode(fs, index, is_dir);
+            return r;
+        }
+
+        let sb = &mut (*fs).sb;
+        let inode_size = get_inode_size(sb);
+        let inode = (*inode_ref).inode;
+        // 重新使用的inode编号递增版本号，使指向旧文件的句柄失效
+        let generation = ext4_inode_get_generation(inode).wrapping_add(1);
+        ptr::write_bytes(inode as *mut u8, 0, inode_size as usize);
+        ext4_inode_set_generation(inode, generation);
+
+        // 默认权限：目录和符号链接 0777，其他 0666
+        let mode = if is_dir || filetype == EXT4_DE_SYMLINK { 0o777 } else { 0o666 };
+        ext4_inode_set_mode(sb, inode, mode | ext4_fs_correspond_inode_mode(filetype));
+
+        if inode_size > EXT4_GOOD_OLD_INODE_SIZE {
+            let mut extra = u16::from_le(sb.want_extra_isize);
+            if extra == 0 {
+                extra = 32.min(inode_size - EXT4_GOOD_OLD_INODE_SIZE);
+            }
+            ext4_inode_set_extra_isize(sb, inode, extra);
+        }
+
+        // 新 inode 的属主为 0，之后修改属主时用量随之转移
+        ext4_quota_charge_inode(inode_ref, 0, 1);
+        (*inode_ref).dirty = true;
+    }
+    EOK
+}
+
+/// 扩展属性块是否以正确的魔数开头
+pub unsafe fn ext4_fs_xattr_block_valid(data: *const u8) -> bool {
+    unsafe { u32::from_le(*(data as *const u32)) == EXT4_XATTR_MAGIC }
+}
+
+/// 更新扩展属性块校验和：crc32c(fs种子, 块号, 块内容（校验和字段视为0）)
+pub unsafe fn ext4_fs_xattr_block_set_csum(sb: &Ext4Superblock, xattr_block: u64, data: *mut u8) {
+    unsafe {
+        if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return;
+        }
+        let hdr = data as *mut u32;
+        let block_size = ext4_sb_get_block_size(sb) as usize;
+        *hdr.add(4) = 0;
+        let raw = core::slice::from_raw_parts(data, block_size);
+        let mut csum = ext4_crc32c(ext4_sb_csum_seed(sb), &xattr_block.to_le_bytes());
+        csum = ext4_crc32c(csum, raw);
+        *hdr.add(4) = csum.to_le();
+    }
+}
+
+/// 释放扩展属性块（被多个 inode 共享时只减少引用计数）
+unsafe fn ext4_fs_release_xattr_block(inode_ref: *mut Ext4InodeRef, xattr_block: u64) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get((*fs).bdev, &mut b, xattr_block);
+        if r != EOK {
+            return r;
+        }
+        let hdr = b.data as *mut u32;
+        let magic = u32::from_le(*hdr);
+        let refcount = u32::from_le(*hdr.add(1));
+        if magic == EXT4_XATTR_MAGIC && refcount > 1 {
+            *hdr.add(1) = (refcount - 1).to_le();
+            ext4_fs_xattr_block_set_csum(sb, xattr_block, b.data);
+            ext4_block_set_dirty(&mut b);
+            return ext4_block_set((*fs).bdev, &mut b);
+        }
+        let r = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK {
+            return r;
+        }
+        ext4_balloc_free_block(inode_ref, xattr_block)
+    }
+}
+
+/// 释放 inode（数据块应已通过截断释放；保留 inode 返回 EPERM）
+pub unsafe fn ext4_fs_free_inode(inode_ref: *mut Ext4InodeRef) -> i32 {
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_free_inode: ino={}", (*inode_ref).index);
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let inode = (*inode_ref).inode;
+        if ext4_sb_is_reserved_ino(sb, (*inode_ref).index) {
+            ext4_dbg!(DEBUG_INODE, Warn, "refusing to free reserved inode {}", (*inode_ref).index);
+            return EPERM;
+        }
+
+        // 间接块映射：释放残留的间接块
+        if !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) && ext4_inode_can_truncate(sb, inode) {
+            let r = ext4_fs_release_indirect(inode_ref, 0);
+            if r != EOK {
+                return r;
+            }
+        }
+        (*inode_ref).dirty = true;
+
+        let xattr_block = ext4_inode_get_file_acl(inode, sb);
+        if xattr_block != 0 {
+            let r = ext4_fs_release_xattr_block(inode_ref, xattr_block);
+            if r != EOK {
+                return r;
+            }
+            ext4_inode_set_file_acl(inode, sb, 0);
+        }
+
+        ext4_es_drop_inode(&mut (*fs).es_cache, (*inode_ref).index);
+        (*fs).prealloc.remove(&(*inode_ref).index);
+        (*fs).alloc_goal.remove(&(*inode_ref).index);
+        ext4_quota_release_inode(inode_ref);
+        let is_dir = ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_DIRECTORY);
+        ext4_ialloc_free_inode(fs, (*inode_ref).index, is_dir)
+    }
+}
+
+/// 初始化 inode 的块映射结构（支持 extent 时使用 extent 树）
+pub unsafe fn ext4_fs_inode_blocks_init(fs: *mut Ext4Filesystem, inode_ref: *mut Ext4InodeRef) {
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_inode_blocks_init");
+    unsafe {
+        let sb = &(*fs).sb;
+        let inode = (*inode_ref).inode;
+
+        // 只有普通文件、目录和符号链接需要数据块
+        match ext4_inode_type(sb, inode) as u16 {
+            EXT4_INODE_MODE_FILE | EXT4_INODE_MODE_DIRECTORY | EXT4_INODE_MODE_SOFTLINK => {}
+            _ => return,
+        }
+
+        if ext4_sb_feature_incom(sb, EXT4_FINCOM_EXTENTS) {
+            ext4_inode_set_flag(inode, EXT4_INODE_FLAG_EXTENTS);
+            ext4_extent_tree_init(inode_ref);
+        }
+        (*inode_ref).dirty = true;
+    }
+}
+
+/// 目录的链接数是否按 dir_nlink 处理（启用 dir_nlink 时的目录）
+///
+/// 这样的目录子目录数超过 EXT4_LINK_MAX 后链接数记为1，表示不再计数。Linux 只对 htree 目录这样处理，
+/// 这里添加目录项时会退化为线性目录，因此不要求 htree（e2fsck 同样不要求）。
+pub unsafe fn ext4_inode_dir_nlink(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> bool {
+    unsafe {
+        ext4_sb_feature_ro_com(&*sb, EXT4_FRO_COM_DIR_NLINK)
+            && ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_DIRECTORY)
+    }
+}
+
+/// 链接数是否已达上限，不能再增加（对应 Linux 的 EXT4_DIR_LINK_MAX，按 dir_nlink 处理的目录没有上限）
+pub unsafe fn ext4_inode_links_max(sb: *const Ext4Superblock, inode: *const Ext4Inode) -> bool {
+    unsafe { ext4_inode_get_links_cnt(inode) >= EXT4_LINK_MAX && !ext4_inode_dir_nlink(sb, inode) }
+}
+
+/// 增加硬链接计数（按 dir_nlink 处理的目录超过 EXT4_LINK_MAX 或已为1时记为1）
+///
+/// 新建目录的链接数由调用者直接设为2。
+pub unsafe fn ext4_fs_inode_links_count_inc(inode_ref: *mut Ext4InodeRef) {
+    unsafe {
+        let inode = (*inode_ref).inode;
+        let links = ext4_inode_get_links_cnt(inode);
+        let dir_nlink = ext4_inode_dir_nlink(&(*(*inode_ref).fs).sb, inode);
+        let links = if dir_nlink && (links == 1 || links >= EXT4_LINK_MAX) { 1 } else { links.saturating_add(1) };
+        ext4_inode_set_links_cnt(inode, links);
+        (*inode_ref).dirty = true;
+    }
+}
+
+/// 减少硬链接计数（按 dir_nlink 处理、链接数为1的目录保持为1）
+pub unsafe fn ext4_fs_inode_links_count_dec(inode_ref: *mut Ext4InodeRef) {
+    unsafe {
+        let inode = (*inode_ref).inode;
+        let links = ext4_inode_get_links_cnt(inode);
+        if links == 1 && ext4_inode_dir_nlink(&(*(*inode_ref).fs).sb, inode) {
+            return;
+        }
+        ext4_inode_set_links_cnt(inode, links.saturating_sub(1));
+        (*inode_ref).dirty = true;
+    }
+}
+
+// ===== 数据块映射 =====
+
+/// inode 是否使用 extent 树
+unsafe fn ext4_fs_inode_uses_extents(inode_ref: *mut Ext4InodeRef) -> bool {
+    unsafe {
+        ext4_sb_feature_incom(&(*(*inode_ref).fs).sb, EXT4_FINCOM_EXTENTS)
+            && ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_EXTENTS)
+    }
+}
+
+/// 逻辑块所在的间接层级（0 表示直接块）
+unsafe fn ext4_fs_indirect_level(fs: *mut Ext4Filesystem, iblock: u64) -> Option<usize> {
+    unsafe { (0..4).find(|&l| iblock < (*fs).inode_block_limits[l]) }
+}
+
+/// 间接块映射：查找逻辑块（create 为真时分配缺失的间接块和数据块）
+unsafe fn ext4_fs_indirect_map(
+    inode_ref: *mut Ext4InodeRef,
+    iblock: u32,
+    fblock: *mut u64,
+    create: bool,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let inode = (*inode_ref).inode;
+        let bdev = (*fs).bdev;
+        let block_size = ext4_sb_get_block_size(&(*fs).sb);
+        *fblock = 0;
+
+        let Some(level) = ext4_fs_indirect_level(fs, iblock as u64) else {
+            return EFBIG;
+        };
+
+        // 分配一个块（is_meta 为真时清零，作为间接块）
+        let alloc = move |is_meta: bool, out: &mut u64| -> i32 {
+            let mut goal = 0u64;
+            let r = ext4_balloc_find_goal(inode_ref, &mut goal);
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_balloc_alloc_block(inode_ref, goal, out);
+            if r != EOK {
+                return r;
+            }
+            if !is_meta {
+                ext4_balloc_set_goal(fs, (*inode_ref).index, *out + 1);
+                return EOK;
+            }
+            let mut nb = Ext4Block::new();
+            let r = ext4_block_get_noread(bdev, &mut nb, *out);
+            if r != EOK {
+                return r;
+            }
+            ptr::write_bytes(nb.data, 0, block_size as usize);
+            ext4_block_set_dirty(&mut nb);
+            ext4_block_set(bdev, &mut nb)
+        };
+
+        if level == 0 {
+            let mut cur = ext4_inode_get_direct_block(inode, iblock) as u64;
+            if cur == 0 && create {
+                let r = alloc(false, &mut cur);
+                if r != EOK {
+                    return r;
+                }
+                ext4_inode_set_direct_block(inode, iblock, cur as u32);
+                (*inode_ref).dirty = true;
+            }
+            *fblock = cur;
+            return EOK;
+        }
+
+        let mut cur = ext4_inode_get_indirect_block(inode, level as u32 - 1) as u64;
+        if cur == 0 {
+            if !create {
+                return EOK;
+            }
+            let r = alloc(true, &mut cur);
+            if r != EOK {
+                return r;
+            }
+            ext4_inode_set_indirect_block(inode, level as u32 - 1, cur as u32);
+            (*inode_ref).dirty = true;
+        }
+
+        let mut offset_in_level = iblock as u64 - (*fs).inode_block_limits[level - 1];
+        let mut l = level;
+        while l > 0 {
+            let per_entry = (*fs).inode_blocks_per_level[l - 1];
+            let offset_in_block = (offset_in_level / per_entry) as usize;
+            offset_in_level %= per_entry;
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get(bdev, &mut b, cur);
+            if r != EOK {
+                return r;
+            }
+            let entries = b.data as *mut u32;
+            let mut next = u32::from_le(*entries.add(offset_in_block)) as u64;
+            if next == 0 && create {
+                let r = alloc(l > 1, &mut next);
+                if r != EOK {
+                    ext4_block_set(bdev, &mut b);
+                    return r;
+                }
+                *entries.add(offset_in_block) = (next as u32).to_le();
+                ext4_block_set_dirty(&mut b);
+            }
+            let r = ext4_block_set(bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+            if next == 0 {
+                // 稀疏文件
+                return EOK;
+            }
+            cur = next;
+            l -= 1;
+        }
+        *fblock = cur;
+    }
+    EOK
+}
+
+/// 释放间接块子树中逻辑块号不小于 from 的数据块
+///
+/// base 为该间接块覆盖的第一个逻辑块，level 为其层级（1 为一级间接块）。
+unsafe fn ext4_fs_release_indirect_tree(
+    inode_ref: *mut Ext4InodeRef,
+    blk: u64,
+    level: usize,
+    base: u64,
+    from: u64,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let bdev = (*fs).bdev;
+        let ids_per_block = ext4_sb_get_block_size(&(*fs).sb) as usize / 4;
+        let per_entry = (*fs).inode_blocks_per_level[level - 1];
+
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get(bdev, &mut b, blk);
+        if r != EOK {
+            return r;
+        }
+        let entries = b.data as *mut u32;
+        let mut dirty = false;
+        let mut r = EOK;
+        for j in 0..ids_per_block {
+            let start = base + j as u64 * per_entry;
+            if start + per_entry <= from {
+                continue;
+            }
+            let child = u32::from_le(*entries.add(j)) as u64;
+            if child == 0 {
+                continue;
+            }
+            if level > 1 {
+                r = ext4_fs_release_indirect_tree(inode_ref, child, level - 1, start, from);
+                if r != EOK {
+                    break;
+                }
+                if start < from {
+                    // 子树只被部分释放，保留子间接块
+                    continue;
+                }
+            }
+            r = ext4_balloc_free_block(inode_ref, child);
+            if r != EOK {
+                break;
+            }
+            *entries.add(j) = 0;
+            dirty = true;
+        }
+        if dirty {
+            ext4_block_set_dirty(&mut b);
+        }
+        let r2 = ext4_block_set(bdev, &mut b);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 间接块映射：释放逻辑块号不小于 from 的所有数据块及不再需要的间接块
+unsafe fn ext4_fs_release_indirect(inode_ref: *mut Ext4InodeRef, from: u64) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let inode = (*inode_ref).inode;
+
+        for i in from.min(EXT4_INODE_DIRECT_BLOCKS as u64)..EXT4_INODE_DIRECT_BLOCKS as u64 {
+            let blk = ext4_inode_get_direct_block(inode, i as u32) as u64;
+            if blk != 0 {
+                let r = ext4_balloc_free_block(inode_ref, blk);
+                if r != EOK {
+                    return r;
+                }
+                ext4_inode_set_direct_block(inode, i as u32, 0);
+            }
+        }
+
+        for level in 1..4 {
+            let base = (*fs).inode_block_limits[level - 1];
+            let end = (*fs).inode_block_limits[level];
+            if end <= from {
+                continue;
+            }
+            let blk = ext4_inode_get_indirect_block(inode, level as u32 - 1) as u64;
+            if blk == 0 {
+                continue;
+            }
+            let r = ext4_fs_release_indirect_tree(inode_ref, blk, level, base, from);
+            if r != EOK {
+                return r;
+            }
+            if base >= from {
+                let r = ext4_balloc_free_block(inode_ref, blk);
+                if r != EOK {
+                    return r;
+                }
+                ext4_inode_set_indirect_block(inode, level as u32 - 1, 0);
+            }
+        }
+        (*inode_ref).dirty = true;
+    }
+    EOK
+}
+
+/// 获取 inode 的第 iblock 个数据块号（空洞返回0）
+pub unsafe fn ext4_fs_get_inode_dblk_idx(
     inode_ref: *mut Ext4InodeRef,
     iblock: u32,           // ext4_lblk_t
     fblock: *mut u64,      // ext4_fsblk_t*
     support_unwritten: bool,
 ) -> i32 {
-    // TODO: 实现块映射逻辑（extent 或传统间接块）
-    debug!("ext4_fs_get_inode_dblk_idx: iblock={}, support_unwritten={}", iblock, support_unwritten);
-    EOK
+    let _ = support_unwritten;
+    unsafe {
+        if ext4_fs_inode_uses_extents(inode_ref) {
+            return ext4_extent_get_blocks(inode_ref, iblock, 1, fblock, false, ptr::null_mut());
+        }
+        ext4_fs_indirect_map(inode_ref, iblock, fblock, false)
+    }
 }
 
-/// 为 inode 追加数据块（占位实现）
-pub fn ext4_fs_append_inode_dblk(
+/// 获取 inode 的第 iblock 个数据块号，未分配时分配新块
+pub unsafe fn ext4_fs_init_inode_dblk_idx(
     inode_ref: *mut Ext4InodeRef,
+    iblock: u32,           // ext4_lblk_t
     fblock: *mut u64,      // ext4_fsblk_t*
-    iblock: *mut u32,      // ext4_lblk_t*
 ) -> i32 {
-    // TODO: 实现块分配和追加
-    debug!("ext4_fs_append_inode_dblk");
-    EOK
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_init_inode_dblk_idx: iblock={}", iblock);
+    unsafe {
+        if ext4_fs_inode_uses_extents(inode_ref) {
+            return ext4_extent_get_blocks(inode_ref, iblock, 1, fblock, true, ptr::null_mut());
+        }
+        ext4_fs_indirect_map(inode_ref, iblock, fblock, true)
+    }
 }
 
-/// 分配 inode（占位实现）
-pub fn ext4_fs_alloc_inode(
-    fs: *mut Ext4Filesystem,
+/// 为 inode 在文件末尾追加一个数据块（文件大小按块对齐后增加一块）
+pub unsafe fn ext4_fs_append_inode_dblk(
     inode_ref: *mut Ext4InodeRef,
-    inode_type: u32,
+    fblock: *mut u64,      // ext4_fsblk_t*
+    iblock: *mut u32,      // ext4_lblk_t*
 ) -> i32 {
-    // TODO: 实现 inode 分配（位图操作）
-    debug!("ext4_fs_alloc_inode: type={}", inode_type);
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let inode_size = ext4_inode_get_size(sb, (*inode_ref).inode).next_multiple_of(block_size);
+        let new_block_idx = inode_size / block_size;
+        if new_block_idx > u32::MAX as u64 {
+            return EFBIG;
+        }
+
+        let mut phys_block = 0u64;
+        let r = ext4_fs_init_inode_dblk_idx(inode_ref, new_block_idx as u32, &mut phys_block);
+        if r != EOK {
+            return r;
+        }
+
+        let r = ext4_fs_set_inode_size(inode_ref, inode_size + block_size);
+        if r != EOK {
+            return r;
+        }
+        *fblock = phys_block;
+        *iblock = new_block_idx as u32;
+        ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_append_inode_dblk: iblock={}, fblock={}", *iblock, *fblock);
+    }
     EOK
 }
 
-/// 释放 inode（占位实现）
-pub fn ext4_fs_free_inode(inode_ref: *mut Ext4InodeRef) {
-    // TODO: 实现 inode 释放（位图操作）
-    debug!("ext4_fs_free_inode");
-}
+/// 截断 inode（只能缩小文件）
+///
+/// new_size 等于当前大小时只回收文件末尾之后的块（追加写入时预分配的未写入块）。
+pub unsafe fn ext4_fs_truncate_inode(inode_ref: *mut Ext4InodeRef, new_size: u64) -> i32 {
+    ext4_dbg!(DEBUG_INODE, Debug, "ext4_fs_truncate_inode: new_size={}", new_size);
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode = (*inode_ref).inode;
+
+        // 设备文件、FIFO和套接字没有数据块
+        match ext4_inode_type(sb, inode) as u16 {
+            EXT4_INODE_MODE_CHARDEV | EXT4_INODE_MODE_BLOCKDEV | EXT4_INODE_MODE_FIFO
+            | EXT4_INODE_MODE_SOCKET => {
+                (*inode).blocks[0] = 0;
+                (*inode).blocks[1] = 0;
+                (*inode_ref).dirty = true;
+                return EOK;
+            }
+            _ => {}
+        }
+        if !ext4_inode_can_truncate(sb, inode) {
+            return EINVAL;
+        }
+
+        let old_size = ext4_inode_get_size(sb, inode);
+        // fs-verity 元数据位于 i_size 之后，截断时一并释放，文件不再受 verity 保护
+        let verity = ext4_inode_has_flag(inode, EXT4_INODE_FLAG_VERITY);
+        if old_size < new_size {
+            return EINVAL;
+        }
+        // 文件末尾之后可能有预分配的未写入块，截断到当前大小时一并回收
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let new_blocks_cnt = new_size.div_ceil(block_size);
+        let mut past_eof = false;
+        if ext4_fs_inode_uses_extents(inode_ref) && !verity {
+            let mut end = 0u32;
+            let r = ext4_extent_logical_end(inode_ref, &mut end);
+            if r != EOK {
+                return r;
+            }
+            past_eof = end as u64 > new_blocks_cnt;
+        }
+        if old_size == new_size && !verity && !past_eof {
+            return EOK;
+        }
 
-/// 截断 inode（占位实现）
-pub fn ext4_fs_truncate_inode(inode_ref: *mut Ext4InodeRef, new_size: u64) -> i32 {
-    // TODO: 实现文件截断
-    debug!("ext4_fs_truncate_inode: new_size={}", new_size);
+        // 数据直接存放在 inode 中的短符号链接
+        if ext4_inode_is_fast_symlink(sb, inode) {
+            let inline_len = core::mem::size_of_val(&(*inode).blocks) as u64;
+            let content = ((*inode).blocks.as_mut_ptr() as *mut u8).add(new_size as usize);
+            ptr::write_bytes(content, 0, (inline_len - new_size) as usize);
+            ext4_inode_set_size(inode, new_size);
+            (*inode_ref).dirty = true;
+            return EOK;
+        }
+
+        let old_blocks_cnt = old_size.div_ceil(block_size);
+
+        if old_blocks_cnt > new_blocks_cnt || verity || past_eof {
+            let r = if ext4_fs_inode_uses_extents(inode_ref) {
+                ext4_extent_remove_space(inode_ref, new_blocks_cnt as u32, u32::MAX)
+            } else {
+                ext4_fs_release_indirect(inode_ref, new_blocks_cnt)
+            };
+            if r != EOK {
+                return r;
+            }
+        }
+        if verity {
+            ext4_inode_clear_flag(inode, EXT4_INODE_FLAG_VERITY);
+        }
+
+        ext4_inode_set_size(inode, new_size);
+        (*inode_ref).dirty = true;
+    }
     EOK
 }
@@ -0,0 +1,942 @@
+//! 日志模块
+//!
+//! 对应C实现: ext4_journal.c（jbd_recover、jbd_trans 部分）
+//!
+//! 日志（jbd2）中的所有字段都是大端序。恢复分三遍扫描：SCAN 找到最后一个完整提交的事务，
+//! REVOKE 收集撤销记录，REPLAY 将事务中记录的块写回文件系统。启用 fast_commit 时，
+//! 日志末尾的快速提交区域在完整事务回放之后交给 fast_commit 模块回放。
+//!
+//! 事务期间修改的元数据块全部留在块缓存中；提交时先把它们写入日志并写入提交块，
+//! 再写回原位置并清空日志，中途断电由挂载时的恢复补齐。中止时丢弃这些修改。
+//! 文件数据不经过日志，提交前就已写入设备（相当于 ordered 模式）。
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr;
+
+use crate::bcache::*;
+use crate::block::*;
+use crate::crc::ext4_crc32c;
+use crate::extent_status::ext4_es_clear;
+use crate::fast_commit::ext4_fc_replay;
+use crate::fs::ext4_fs_yield_point;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4Filesystem, Ext4InodeRef, Ext4Superblock};
+
+/// 日志块头魔数
+pub const JBD_MAGIC_NUMBER: u32 = 0xC03B3998;
+
+/// 日志块类型
+pub const JBD_DESCRIPTOR_BLOCK: u32 = 1;
+pub const JBD_COMMIT_BLOCK: u32 = 2;
+pub const JBD_SUPERBLOCK: u32 = 3;
+pub const JBD_SUPERBLOCK_V2: u32 = 4;
+pub const JBD_REVOKE_BLOCK: u32 = 5;
+
+/// 描述块中标签的标志位
+pub const JBD_FLAG_ESCAPE: u32 = 1;
+pub const JBD_FLAG_SAME_UUID: u32 = 2;
+pub const JBD_FLAG_DELETED: u32 = 4;
+pub const JBD_FLAG_LAST_TAG: u32 = 8;
+
+/// 日志兼容特性
+pub const JBD_FEATURE_COMPAT_CHECKSUM: u32 = 0x1;
+
+/// 日志不兼容特性
+pub const JBD_FEATURE_INCOMPAT_REVOKE: u32 = 0x1;
+pub const JBD_FEATURE_INCOMPAT_64BIT: u32 = 0x2;
+pub const JBD_FEATURE_INCOMPAT_ASYNC_COMMIT: u32 = 0x4;
+pub const JBD_FEATURE_INCOMPAT_CSUM_V2: u32 = 0x8;
+pub const JBD_FEATURE_INCOMPAT_CSUM_V3: u32 = 0x10;
+pub const JBD_FEATURE_INCOMPAT_FAST_COMMIT: u32 = 0x20;
+
+/// 支持回放的不兼容特性
+pub const JBD_KNOWN_INCOMPAT_FEATURES: u32 = JBD_FEATURE_INCOMPAT_REVOKE
+    | JBD_FEATURE_INCOMPAT_64BIT
+    | JBD_FEATURE_INCOMPAT_ASYNC_COMMIT
+    | JBD_FEATURE_INCOMPAT_CSUM_V2
+    | JBD_FEATURE_INCOMPAT_CSUM_V3
+    | JBD_FEATURE_INCOMPAT_FAST_COMMIT;
+
+/// 日志校验算法（s_checksum_type）
+pub const JBD_CRC32C_CHKSUM: u8 = 4;
+
+/// 未指定时快速提交区域的块数
+pub const JBD_DEFAULT_FAST_COMMIT_BLOCKS: u32 = 256;
+
+/// 日志超级块中的字段偏移
+const JBD_SB_BLOCKSIZE: usize = 0x0C;
+const JBD_SB_MAXLEN: usize = 0x10;
+const JBD_SB_FIRST: usize = 0x14;
+const JBD_SB_SEQUENCE: usize = 0x18;
+const JBD_SB_START: usize = 0x1C;
+const JBD_SB_FEATURE_INCOMPAT: usize = 0x28;
+const JBD_SB_UUID: usize = 0x30;
+const JBD_SB_NR_USERS: usize = 0x40;
+const JBD_SB_CHECKSUM_TYPE: usize = 0x50;
+const JBD_SB_NUM_FC_BLKS: usize = 0x54;
+const JBD_SB_CHECKSUM: usize = 0xFC;
+/// 日志超级块大小
+const JBD_SB_SIZE: usize = 1024;
+
+/// 块头大小（魔数、块类型、事务序号）
+const JBD_HEADER_SIZE: usize = 12;
+/// 提交块中校验和的偏移（h_chksum[0]）
+const JBD_COMMIT_CHECKSUM: usize = 16;
+/// 撤销块头大小（块头 + r_count）
+const JBD_REVOKE_HEADER_SIZE: usize = 16;
+
+fn be32(buf: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+}
+
+fn put_be32(buf: &mut [u8], off: usize, v: u32) {
+    buf[off..off + 4].copy_from_slice(&v.to_be_bytes());
+}
+
+/// 事务序号比较（考虑回绕）：a >= b
+fn tid_geq(a: u32, b: u32) -> bool {
+    a.wrapping_sub(b) as i32 >= 0
+}
+
+/// 恢复过程中的日志状态
+struct JbdFs {
+    fs: *mut Ext4Filesystem,
+    inode: Ext4InodeRef,
+    sb: Vec<u8>,
+    block_size: usize,
+    incompat: u32,
+    /// 日志区域 [first, last)
+    first: u32,
+    last: u32,
+    /// 描述块中每个标签的大小
+    tag_bytes: usize,
+    csum_seed: u32,
+}
+
+/// 恢复扫描的遍数
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JbdPass {
+    Scan,
+    Revoke,
+    Replay,
+}
+
+/// 扫描得到的事务范围和撤销记录
+struct JbdRecoverInfo {
+    start_transaction: u32,
+    end_transaction: u32,
+    /// 块号 -> 撤销该块的最新事务序号
+    revoked: BTreeMap<u64, u32>,
+    replayed: u32,
+}
+
+impl JbdFs {
+    fn has_incompat(&self, feature: u32) -> bool {
+        self.incompat & feature != 0
+    }
+
+    fn has_csum(&self) -> bool {
+        self.has_incompat(JBD_FEATURE_INCOMPAT_CSUM_V2 | JBD_FEATURE_INCOMPAT_CSUM_V3)
+    }
+
+    /// 下一个日志块（到达末尾时回到开头）
+    fn wrap(&self, blk: u32) -> u32 {
+        if blk >= self.last { blk - (self.last - self.first) } else { blk }
+    }
+
+    /// 读取日志中的第 iblock 块
+    unsafe fn read(&mut self, iblock: u32, buf: &mut [u8]) -> i32 {
+        unsafe {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(&mut self.inode, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                ext4_dbg!(DEBUG_JBD, Warn, "journal block {} is not mapped", iblock);
+                return EIO;
+            }
+            ext4_blocks_get_direct((*self.fs).bdev, buf.as_mut_ptr() as _, fblock, 1)
+        }
+    }
+
+    /// 写回日志中的第 iblock 块
+    unsafe fn write(&mut self, iblock: u32, buf: &[u8]) -> i32 {
+        unsafe {
+            let mut fblock = 0u64;
+            let r = ext4_fs_get_inode_dblk_idx(&mut self.inode, iblock, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                return EIO;
+            }
+            ext4_blocks_set_direct((*self.fs).bdev, buf.as_ptr() as _, fblock, 1)
+        }
+    }
+
+    /// 校验描述块、撤销块尾部的校验和
+    fn verify_tail_csum(&self, buf: &mut [u8]) -> bool {
+        if !self.has_csum() {
+            return true;
+        }
+        let off = self.block_size - 4;
+        let provided = be32(buf, off);
+        put_be32(buf, off, 0);
+        let calculated = ext4_crc32c(self.csum_seed, buf);
+        put_be32(buf, off, provided);
+        provided == calculated
+    }
+
+    /// 校验提交块的校验和
+    fn verify_commit_csum(&self, buf: &mut [u8]) -> bool {
+        if !self.has_csum() {
+            return true;
+        }
+        let provided = be32(buf, JBD_COMMIT_CHECKSUM);
+        put_be32(buf, JBD_COMMIT_CHECKSUM, 0);
+        let calculated = ext4_crc32c(self.csum_seed, buf);
+        put_be32(buf, JBD_COMMIT_CHECKSUM, provided);
+        provided == calculated
+    }
+
+    /// 日志中数据块的校验和
+    fn data_csum(&self, seq: u32, data: &[u8]) -> u32 {
+        let csum = ext4_crc32c(self.csum_seed, &seq.to_be_bytes());
+        ext4_crc32c(csum, data)
+    }
+
+    /// 校验日志中数据块的校验和（记录在标签中）
+    fn verify_data_csum(&self, tag: &[u8], seq: u32, data: &[u8]) -> bool {
+        if !self.has_csum() {
+            return true;
+        }
+        let csum = self.data_csum(seq, data);
+        if self.has_incompat(JBD_FEATURE_INCOMPAT_CSUM_V3) {
+            be32(tag, 12) == csum
+        } else {
+            u16::from_be_bytes([tag[4], tag[5]]) == csum as u16
+        }
+    }
+
+    /// 设置描述块、撤销块尾部的校验和
+    fn set_tail_csum(&self, buf: &mut [u8]) {
+        if self.has_csum() {
+            let off = self.block_size - 4;
+            put_be32(buf, off, 0);
+            let csum = ext4_crc32c(self.csum_seed, buf);
+            put_be32(buf, off, csum);
+        }
+    }
+
+    /// 写入标签（parse_tag 的逆操作）
+    fn put_tag(&self, tag: &mut [u8], blocknr: u64, flags: u32, csum: u32) {
+        put_be32(tag, 0, blocknr as u32);
+        if self.has_incompat(JBD_FEATURE_INCOMPAT_64BIT) {
+            put_be32(tag, 8, (blocknr >> 32) as u32);
+        }
+        if self.has_incompat(JBD_FEATURE_INCOMPAT_CSUM_V3) {
+            put_be32(tag, 4, flags);
+            put_be32(tag, 12, csum);
+        } else {
+            tag[4..6].copy_from_slice(&(csum as u16).to_be_bytes());
+            tag[6..8].copy_from_slice(&(flags as u16).to_be_bytes());
+        }
+    }
+
+    /// 解析标签，返回 (目标块号, 标志)
+    fn parse_tag(&self, tag: &[u8]) -> (u64, u32) {
+        let mut blocknr = be32(tag, 0) as u64;
+        if self.has_incompat(JBD_FEATURE_INCOMPAT_64BIT) {
+            blocknr |= (be32(tag, 8) as u64) << 32;
+        }
+        let flags = if self.has_incompat(JBD_FEATURE_INCOMPAT_CSUM_V3) {
+            be32(tag, 4)
+        } else {
+            u16::from_be_bytes([tag[6], tag[7]]) as u32
+        };
+        (blocknr, flags)
+    }
+}
+
+/// 描述块中每个标签的大小
+fn jbd_tag_bytes(incompat: u32) -> usize {
+    if incompat & JBD_FEATURE_INCOMPAT_CSUM_V3 != 0 {
+        return 16;
+    }
+    let mut sz = 12;
+    if incompat & JBD_FEATURE_INCOMPAT_CSUM_V2 != 0 {
+        sz += 2;
+    }
+    if incompat & JBD_FEATURE_INCOMPAT_64BIT != 0 { sz } else { sz - 4 }
+}
+
+/// 将日志中的块写回文件系统（经过块缓存，保证缓存内容一致）
+unsafe fn jbd_replay_block(fs: *mut Ext4Filesystem, blocknr: u64, data: &[u8]) -> i32 {
+    unsafe {
+        if blocknr >= ext4_sb_get_blocks_cnt(&(*fs).sb) {
+            ext4_dbg!(DEBUG_JBD, Warn, "journal block target {} out of range", blocknr);
+            return EIO;
+        }
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get_noread((*fs).bdev, &mut b, blocknr);
+        if r != EOK {
+            return r;
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), b.data, data.len());
+        ext4_block_set_dirty(&mut b);
+        ext4_block_set((*fs).bdev, &mut b)
+    }
+}
+
+/// 按遍数扫描一次日志
+unsafe fn jbd_do_one_pass(jbd: &mut JbdFs, info: &mut JbdRecoverInfo, pass: JbdPass) -> i32 {
+    unsafe {
+        let mut next_seq = be32(&jbd.sb, JBD_SB_SEQUENCE);
+        let mut next_blk = be32(&jbd.sb, JBD_SB_START);
+        if pass == JbdPass::Scan {
+            info.start_transaction = next_seq;
+        }
+        let bs = jbd.block_size;
+        let mut buf = vec![0u8; bs];
+        let mut data = vec![0u8; bs];
+
+        loop {
+            if pass != JbdPass::Scan && tid_geq(next_seq, info.end_transaction) {
+                break;
+            }
+            ext4_fs_yield_point(jbd.fs);
+            let r = jbd.read(next_blk, &mut buf);
+            if r != EOK {
+                return r;
+            }
+            let this_blk = next_blk;
+            next_blk = jbd.wrap(next_blk + 1);
+
+            if be32(&buf, 0) != JBD_MAGIC_NUMBER || be32(&buf, 8) != next_seq {
+                break;
+            }
+            match be32(&buf, 4) {
+                JBD_DESCRIPTOR_BLOCK => {
+                    if !jbd.verify_tail_csum(&mut buf) {
+                        ext4_dbg!(DEBUG_JBD, Warn, "descriptor block {} checksum failed", this_blk);
+                        break;
+                    }
+                    let tail = if jbd.has_csum() { 4 } else { 0 };
+                    let mut off = JBD_HEADER_SIZE;
+                    while off + jbd.tag_bytes <= bs - tail {
+                        ext4_fs_yield_point(jbd.fs);
+                        let tag = &buf[off..off + jbd.tag_bytes];
+                        let (blocknr, flags) = jbd.parse_tag(tag);
+                        let log_blk = next_blk;
+                        next_blk = jbd.wrap(next_blk + 1);
+
+                        if pass == JbdPass::Replay {
+                            let revoked = info.revoked.get(&blocknr).is_some_and(|&s| tid_geq(s, next_seq));
+                            if !revoked {
+                                let r = jbd.read(log_blk, &mut data);
+                                if r != EOK {
+                                    return r;
+                                }
+                                if !jbd.verify_data_csum(tag, next_seq, &data) {
+                                    ext4_dbg!(DEBUG_JBD, Warn, "data block checksum failed: log block {}, target {}", log_blk, blocknr);
+                                } else {
+                                    if flags & JBD_FLAG_ESCAPE != 0 {
+                                        put_be32(&mut data, 0, JBD_MAGIC_NUMBER);
+                                    }
+                                    let r = jbd_replay_block(jbd.fs, blocknr, &data);
+                                    if r != EOK {
+                                        return r;
+                                    }
+                                    info.replayed += 1;
+                                }
+                            }
+                        }
+
+                        off += jbd.tag_bytes;
+                        if flags & JBD_FLAG_SAME_UUID == 0 {
+                            off += 16;
+                        }
+                        if flags & JBD_FLAG_LAST_TAG != 0 {
+                            break;
+                        }
+                    }
+                }
+                JBD_COMMIT_BLOCK => {
+                    if pass == JbdPass::Scan && !jbd.verify_commit_csum(&mut buf) {
+                        ext4_dbg!(DEBUG_JBD, Warn, "commit block {} checksum failed", this_blk);
+                        break;
+                    }
+                    next_seq = next_seq.wrapping_add(1);
+                }
+                JBD_REVOKE_BLOCK => {
+                    if !jbd.verify_tail_csum(&mut buf) {
+                        ext4_dbg!(DEBUG_JBD, Warn, "revoke block {} checksum failed", this_blk);
+                        break;
+                    }
+                    if pass != JbdPass::Revoke {
+                        continue;
+                    }
+                    let rec_size = if jbd.has_incompat(JBD_FEATURE_INCOMPAT_64BIT) { 8 } else { 4 };
+                    let count = (be32(&buf, JBD_HEADER_SIZE) as usize).min(bs);
+                    let mut off = JBD_REVOKE_HEADER_SIZE;
+                    while off + rec_size <= count {
+                        let blocknr = if rec_size == 8 {
+                            ((be32(&buf, off) as u64) << 32) | be32(&buf, off + 4) as u64
+                        } else {
+                            be32(&buf, off) as u64
+                        };
+                        let seq = info.revoked.entry(blocknr).or_insert(next_seq);
+                        if tid_geq(next_seq, *seq) {
+                            *seq = next_seq;
+                        }
+                        off += rec_size;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        if pass == JbdPass::Scan {
+            info.end_transaction = next_seq;
+        }
+    }
+    EOK
+}
+
+/// 读取日志 inode 和日志超级块
+unsafe fn jbd_get_fs(fs: *mut Ext4Filesystem, jbd: *mut Option<JbdFs>) -> i32 {
+    unsafe {
+        let ino = u32::from_le((*fs).sb.journal_inode_number);
+        let mut inode = Ext4InodeRef::new();
+        let r = ext4_fs_get_inode_ref(fs, ino, &mut inode);
+        if r != EOK {
+            return r;
+        }
+        let block_size = (*fs).block_size as usize;
+        let mut j = JbdFs {
+            fs,
+            inode,
+            sb: vec![0u8; block_size],
+            block_size,
+            incompat: 0,
+            first: 0,
+            last: 0,
+            tag_bytes: 0,
+            csum_seed: 0,
+        };
+        let mut sb = vec![0u8; block_size];
+        let r = j.read(0, &mut sb);
+        if r != EOK {
+            ext4_fs_put_inode_ref(&mut j.inode);
+            return r;
+        }
+        j.sb = sb;
+
+        let blocktype = be32(&j.sb, 4);
+        if be32(&j.sb, 0) != JBD_MAGIC_NUMBER || !(blocktype == JBD_SUPERBLOCK || blocktype == JBD_SUPERBLOCK_V2) {
+            ext4_dbg!(DEBUG_JBD, Warn, "bad journal superblock");
+            ext4_fs_put_inode_ref(&mut j.inode);
+            return EIO;
+        }
+        if be32(&j.sb, JBD_SB_BLOCKSIZE) as usize != block_size {
+            ext4_dbg!(DEBUG_JBD, Warn, "journal block size differs from filesystem");
+            ext4_fs_put_inode_ref(&mut j.inode);
+            return ENOTSUP;
+        }
+        if blocktype == JBD_SUPERBLOCK_V2 {
+            j.incompat = be32(&j.sb, JBD_SB_FEATURE_INCOMPAT);
+        }
+        if j.incompat & !JBD_KNOWN_INCOMPAT_FEATURES != 0 {
+            ext4_dbg!(DEBUG_JBD, Warn, "unsupported journal features: {:#x}", j.incompat);
+            ext4_fs_put_inode_ref(&mut j.inode);
+            return ENOTSUP;
+        }
+        if j.has_csum() {
+            let provided = be32(&j.sb, JBD_SB_CHECKSUM);
+            put_be32(&mut j.sb, JBD_SB_CHECKSUM, 0);
+            let calculated = ext4_crc32c(EXT4_CRC32_INIT, &j.sb[..JBD_SB_SIZE]);
+            put_be32(&mut j.sb, JBD_SB_CHECKSUM, provided);
+            if provided != calculated {
+                ext4_dbg!(DEBUG_JBD, Warn, "journal superblock checksum failed");
+                ext4_fs_put_inode_ref(&mut j.inode);
+                return EIO;
+            }
+            j.csum_seed = ext4_crc32c(EXT4_CRC32_INIT, &j.sb[JBD_SB_UUID..JBD_SB_UUID + 16]);
+        }
+
+        j.first = be32(&j.sb, JBD_SB_FIRST);
+        j.last = be32(&j.sb, JBD_SB_MAXLEN);
+        if j.has_incompat(JBD_FEATURE_INCOMPAT_FAST_COMMIT) {
+            let num_fc = match be32(&j.sb, JBD_SB_NUM_FC_BLKS) {
+                0 => JBD_DEFAULT_FAST_COMMIT_BLOCKS,
+                n => n,
+            };
+            if j.last > num_fc {
+                j.last -= num_fc;
+            }
+        }
+        if j.first == 0 || j.first >= j.last {
+            ext4_fs_put_inode_ref(&mut j.inode);
+            return EIO;
+        }
+        j.tag_bytes = jbd_tag_bytes(j.incompat);
+        *jbd = Some(j);
+    }
+    EOK
+}
+
+/// 更新校验和并写回日志超级块
+unsafe fn jbd_write_sb(jbd: &mut JbdFs) -> i32 {
+    unsafe {
+        if jbd.has_csum() {
+            put_be32(&mut jbd.sb, JBD_SB_CHECKSUM, 0);
+            let csum = ext4_crc32c(EXT4_CRC32_INIT, &jbd.sb[..JBD_SB_SIZE]);
+            put_be32(&mut jbd.sb, JBD_SB_CHECKSUM, csum);
+        }
+        let sb = core::mem::take(&mut jbd.sb);
+        let r = jbd.write(0, &sb);
+        jbd.sb = sb;
+        r
+    }
+}
+
+/// 回放快速提交区域 [last + 1, maxlen)
+unsafe fn jbd_fc_replay(jbd: &mut JbdFs, tid: u32) -> i32 {
+    unsafe {
+        let maxlen = be32(&jbd.sb, JBD_SB_MAXLEN);
+        let fc_first = jbd.last + 1;
+        if fc_first >= maxlen {
+            return EOK;
+        }
+        let bs = jbd.block_size;
+        let mut area = vec![0u8; (maxlen - fc_first) as usize * bs];
+        for (i, chunk) in area.chunks_mut(bs).enumerate() {
+            let r = jbd.read(fc_first + i as u32, chunk);
+            if r != EOK {
+                return r;
+            }
+        }
+        ext4_fc_replay(jbd.fs, &area, bs, tid)
+    }
+}
+
+/// 恢复日志
+///
+/// 文件系统带有 RECOVER 标志时回放日志中已提交的事务（包括快速提交），随后清空日志
+/// 并清除 RECOVER 标志。只读挂载时不做任何修改，只给出警告。需在绑定块缓存之后调用。
+pub unsafe fn ext4_journal_recover(fs: *mut Ext4Filesystem) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_JBD, "ext4_journal_recover");
+    unsafe {
+        let sb = &(*fs).sb;
+        if !ext4_sb_feature_com(sb, EXT4_FCOM_HAS_JOURNAL) || !ext4_sb_feature_incom(sb, EXT4_FINCOM_RECOVER) {
+            return EOK;
+        }
+        if ext4_sb_feature_incom(sb, EXT4_FINCOM_JOURNAL_DEV) || u32::from_le(sb.journal_inode_number) == 0 {
+            ext4_dbg!(DEBUG_JBD, Warn, "external journal is not supported");
+            return ENOTSUP;
+        }
+        if (*fs).read_only {
+            ext4_dbg!(DEBUG_JBD, Warn, "journal needs recovery, skipped on read-only mount");
+            return EOK;
+        }
+
+        let mut jbd = None;
+        let r = jbd_get_fs(fs, &mut jbd);
+        if r != EOK {
+            return r;
+        }
+        let mut jbd = jbd.unwrap();
+        let r = jbd_recover(&mut jbd);
+        let r2 = ext4_fs_put_inode_ref(&mut jbd.inode);
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+
+        let sb = &mut (*fs).sb;
+        sb.feature_incompat = (u32::from_le(sb.feature_incompat) & !EXT4_FINCOM_RECOVER).to_le();
+        let r = ext4_sb_write((*fs).bdev, sb);
+        if r != EOK {
+            return r;
+        }
+        ext4_block_cache_flush((*fs).bdev)
+    }
+}
+
+/// 执行三遍扫描和快速提交回放，最后清空日志
+unsafe fn jbd_recover(jbd: &mut JbdFs) -> i32 {
+    unsafe {
+        let fs = jbd.fs;
+        let mut info = JbdRecoverInfo {
+            start_transaction: 0,
+            end_transaction: 0,
+            revoked: BTreeMap::new(),
+            replayed: 0,
+        };
+
+        if be32(&jbd.sb, JBD_SB_START) == 0 {
+            ext4_dbg!(DEBUG_JBD, Info, "journal is empty, nothing to recover");
+            return EOK;
+        }
+
+        for pass in [JbdPass::Scan, JbdPass::Revoke, JbdPass::Replay] {
+            let r = jbd_do_one_pass(jbd, &mut info, pass);
+            if r != EOK {
+                ext4_dbg!(DEBUG_JBD, Warn, "journal recovery failed: {}", r);
+                return r;
+            }
+        }
+        ext4_dbg!(
+            DEBUG_JBD,
+            Info,
+            "journal recovery: transactions {}..{}, {} blocks replayed, {} revoked",
+            info.start_transaction,
+            info.end_transaction,
+            info.replayed,
+            info.revoked.len()
+        );
+
+        // 回放的块可能包含 superblock，写回后重新读取；挂载状态和计数保持不变
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+        let sb = &mut (*fs).sb;
+        let (state, mnt_count) = (sb.state, sb.mnt_count);
+        let r = ext4_sb_read((*fs).bdev, sb);
+        if r != EOK {
+            return r;
+        }
+        sb.state = state;
+        sb.mnt_count = mnt_count;
+
+        if jbd.has_incompat(JBD_FEATURE_INCOMPAT_FAST_COMMIT) {
+            let r = jbd_fc_replay(jbd, info.end_transaction);
+            if r != EOK {
+                ext4_dbg!(DEBUG_JBD, Warn, "fast commit replay failed: {}", r);
+                return r;
+            }
+        }
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+
+        // 清空日志：下一个事务从恢复出的最后序号之后开始
+        put_be32(&mut jbd.sb, JBD_SB_SEQUENCE, info.end_transaction.wrapping_add(1));
+        put_be32(&mut jbd.sb, JBD_SB_START, 0);
+        jbd_write_sb(jbd)
+    }
+}
+
+// ===== 事务 =====
+
+/// 进行中的事务
+///
+/// 对应C定义: struct jbd_trans (ext4_journal.h)，但只记录中止时需要恢复的内存状态，
+/// 事务修改的元数据块由块缓存保存。
+#[derive(Default)]
+pub struct Ext4Transaction {
+    active: bool,                    // 事务是否进行中
+    sb: Ext4Superblock,              // 开始时的 superblock
+    last_inode_bg_id: u32,           // 开始时的 inode 分配位置
+}
+
+impl Ext4Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 事务是否进行中
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// 写入日志块头
+fn jbd_put_header(buf: &mut [u8], blocktype: u32, seq: u32) {
+    put_be32(buf, 0, JBD_MAGIC_NUMBER);
+    put_be32(buf, 4, blocktype);
+    put_be32(buf, 8, seq);
+}
+
+/// 初始化一个空日志的超级块（写在日志的第0块）
+///
+/// 日志占 maxlen 块，从第1块开始记录，序号从1开始；incompat 含 CSUM_V3 时同时写入校验和。
+pub fn ext4_journal_sb_init(buf: &mut [u8], block_size: u32, maxlen: u32, incompat: u32, uuid: &[u8; 16]) {
+    buf[..JBD_SB_SIZE].fill(0);
+    jbd_put_header(buf, JBD_SUPERBLOCK_V2, 0);
+    put_be32(buf, JBD_SB_BLOCKSIZE, block_size);
+    put_be32(buf, JBD_SB_MAXLEN, maxlen);
+    put_be32(buf, JBD_SB_FIRST, 1);
+    put_be32(buf, JBD_SB_SEQUENCE, 1);
+    put_be32(buf, JBD_SB_START, 0);
+    put_be32(buf, JBD_SB_FEATURE_INCOMPAT, incompat);
+    buf[JBD_SB_UUID..JBD_SB_UUID + 16].copy_from_slice(uuid);
+    put_be32(buf, JBD_SB_NR_USERS, 1);
+    if incompat & (JBD_FEATURE_INCOMPAT_CSUM_V2 | JBD_FEATURE_INCOMPAT_CSUM_V3) != 0 {
+        buf[JBD_SB_CHECKSUM_TYPE] = JBD_CRC32C_CHKSUM;
+        let csum = ext4_crc32c(EXT4_CRC32_INIT, &buf[..JBD_SB_SIZE]);
+        put_be32(buf, JBD_SB_CHECKSUM, csum);
+    }
+}
+
+/// 包含 superblock 的块，其中的 superblock 替换为内存中的版本
+unsafe fn jbd_sb_block(fs: *mut Ext4Filesystem) -> Result<(u64, Vec<u8>), i32> {
+    unsafe {
+        let bs = (*fs).block_size as u64;
+        let lba = EXT4_SUPERBLOCK_OFFSET / bs;
+        let off = (EXT4_SUPERBLOCK_OFFSET % bs) as usize;
+        let mut data = vec![0u8; bs as usize];
+        let r = ext4_blocks_get_direct((*fs).bdev, data.as_mut_ptr() as _, lba, 1);
+        if r != EOK {
+            return Err(r);
+        }
+        let mut sb = (*fs).sb;
+        ext4_sb_set_csum(&mut sb);
+        ptr::copy_nonoverlapping(&sb as *const _ as *const u8, data.as_mut_ptr().add(off), EXT4_SUPERBLOCK_SIZE);
+        Ok((lba, data))
+    }
+}
+
+/// 将事务中的块写入日志并写入提交块
+///
+/// 提交块之前先让日志超级块指向事务起点，并以事务开始时的 superblock 设置 RECOVER 标志；
+/// 提交块写入之前断电时日志中没有完整事务，文件系统保持事务开始时的状态。
+unsafe fn jbd_write_trans(jbd: &mut JbdFs, blocks: &[(u64, *const u8)], old_sb: &Ext4Superblock) -> i32 {
+    unsafe {
+        let fs = jbd.fs;
+        let bs = jbd.block_size;
+        let tid = be32(&jbd.sb, JBD_SB_SEQUENCE);
+        let tail = if jbd.has_csum() { 4 } else { 0 };
+        // 每个描述块的第一个标签后面跟着 16 字节的 UUID
+        let per_desc = (bs - JBD_HEADER_SIZE - tail - 16) / jbd.tag_bytes;
+        let needed = blocks.len().div_ceil(per_desc) + blocks.len() + 1;
+        if needed > (jbd.last - jbd.first) as usize {
+            ext4_dbg!(DEBUG_JBD, Warn, "transaction of {} blocks does not fit in the journal", blocks.len());
+            return ENOSPC;
+        }
+
+        let mut blk = jbd.first;
+        let mut desc = vec![0u8; bs];
+        let mut data = vec![0u8; bs];
+        for chunk in blocks.chunks(per_desc) {
+            desc.fill(0);
+            jbd_put_header(&mut desc, JBD_DESCRIPTOR_BLOCK, tid);
+            let desc_blk = blk;
+            blk += 1;
+            let mut off = JBD_HEADER_SIZE;
+            for (i, &(lba, src)) in chunk.iter().enumerate() {
+                ptr::copy_nonoverlapping(src, data.as_mut_ptr(), bs);
+                let mut flags = 0;
+                if be32(&data, 0) == JBD_MAGIC_NUMBER {
+                    put_be32(&mut data, 0, 0);
+                    flags |= JBD_FLAG_ESCAPE;
+                }
+                if i > 0 {
+                    flags |= JBD_FLAG_SAME_UUID;
+                }
+                if i + 1 == chunk.len() {
+                    flags |= JBD_FLAG_LAST_TAG;
+                }
+                let csum = if jbd.has_csum() { jbd.data_csum(tid, &data) } else { 0 };
+                jbd.put_tag(&mut desc[off..off + jbd.tag_bytes], lba, flags, csum);
+                off += jbd.tag_bytes;
+                if i == 0 {
+                    desc[off..off + 16].copy_from_slice(&jbd.sb[JBD_SB_UUID..JBD_SB_UUID + 16]);
+                    off += 16;
+                }
+                let r = jbd.write(blk, &data);
+                if r != EOK {
+                    return r;
+                }
+                blk += 1;
+            }
+            jbd.set_tail_csum(&mut desc);
+            let r = jbd.write(desc_blk, &desc);
+            if r != EOK {
+                return r;
+            }
+        }
+
+        put_be32(&mut jbd.sb, JBD_SB_START, jbd.first);
+        let r = jbd_write_sb(jbd);
+        if r != EOK {
+            return r;
+        }
+        let mut sb = *old_sb;
+        sb.feature_incompat = (u32::from_le(sb.feature_incompat) | EXT4_FINCOM_RECOVER).to_le();
+        let r = ext4_sb_write((*fs).bdev, &mut sb);
+        if r != EOK {
+            return r;
+        }
+
+        let mut commit = vec![0u8; bs];
+        jbd_put_header(&mut commit, JBD_COMMIT_BLOCK, tid);
+        if jbd.has_csum() {
+            let csum = ext4_crc32c(jbd.csum_seed, &commit);
+            put_be32(&mut commit, JBD_COMMIT_CHECKSUM, csum);
+        }
+        let r = jbd.write(blk, &commit);
+        ext4_dbg!(DEBUG_JBD, Debug, "transaction {} committed: {} blocks", tid, blocks.len());
+        r
+    }
+}
+
+/// 将已提交的事务写回原位置，然后清空日志并清除 RECOVER 标志
+unsafe fn jbd_checkpoint(jbd: &mut JbdFs) -> i32 {
+    unsafe {
+        let fs = jbd.fs;
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+        let tid = be32(&jbd.sb, JBD_SB_SEQUENCE);
+        put_be32(&mut jbd.sb, JBD_SB_SEQUENCE, tid.wrapping_add(1));
+        put_be32(&mut jbd.sb, JBD_SB_START, 0);
+        let r = jbd_write_sb(jbd);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_sb_write((*fs).bdev, &mut (*fs).sb);
+        if r == EOK {
+            (*fs).sb_dirty = false;
+        }
+        r
+    }
+}
+
+/// 开始事务
+///
+/// 先回写缓存中已有的修改，此后的元数据修改在提交前不会写回设备。文件系统没有内部日志时
+/// 返回 ENOTSUP，只读挂载返回 EROFS，已有事务进行中返回 EBUSY，日志尚未恢复时返回 EIO。
+pub unsafe fn ext4_trans_start(fs: *mut Ext4Filesystem, trans: *mut Ext4Transaction) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_JBD, "ext4_trans_start");
+    unsafe {
+        let sb = &(*fs).sb;
+        if !ext4_sb_feature_com(sb, EXT4_FCOM_HAS_JOURNAL)
+            || ext4_sb_feature_incom(sb, EXT4_FINCOM_JOURNAL_DEV)
+            || u32::from_le(sb.journal_inode_number) == 0
+        {
+            return ENOTSUP;
+        }
+        if (*fs).read_only {
+            return EROFS;
+        }
+        let bc = (*(*fs).bdev).bc;
+        if (*bc).in_trans || (*trans).active {
+            return EBUSY;
+        }
+
+        let mut jbd = None;
+        let r = jbd_get_fs(fs, &mut jbd);
+        if r != EOK {
+            return r;
+        }
+        let mut jbd = jbd.unwrap();
+        let start = be32(&jbd.sb, JBD_SB_START);
+        let r = ext4_fs_put_inode_ref(&mut jbd.inode);
+        if r != EOK {
+            return r;
+        }
+        if start != 0 {
+            ext4_dbg!(DEBUG_JBD, Warn, "journal needs recovery, cannot start a transaction");
+            return EIO;
+        }
+
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+        (*bc).in_trans = true;
+        *trans = Ext4Transaction {
+            active: true,
+            sb: (*fs).sb,
+            last_inode_bg_id: (*fs).last_inode_bg_id,
+        };
+    }
+    EOK
+}
+
+/// 提交事务
+///
+/// 事务修改的元数据块（以及 superblock）先写入日志，写入提交块后再写回原位置并清空日志。
+/// 提交块写入之前失败时事务被中止；之后失败时修改保留在日志中，下次挂载时恢复。
+pub unsafe fn ext4_trans_stop(fs: *mut Ext4Filesystem, trans: *mut Ext4Transaction) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_JBD, "ext4_trans_stop");
+    unsafe {
+        if !(*trans).active {
+            return EINVAL;
+        }
+        let bc = (*(*fs).bdev).bc;
+        let bufs = ext4_bcache_dirty_bufs(bc);
+        if bufs.is_empty() {
+            (*bc).in_trans = false;
+            (*trans).active = false;
+            return EOK;
+        }
+
+        let (sb_lba, sb_block) = match jbd_sb_block(fs) {
+            Ok(b) => b,
+            Err(r) => {
+                ext4_trans_abort(fs, trans);
+                return r;
+            }
+        };
+        let mut blocks: Vec<(u64, *const u8)> = bufs.iter().map(|&buf| ((*buf).lba, (*buf).data as *const u8)).collect();
+        blocks.push((sb_lba, sb_block.as_ptr()));
+
+        let mut jbd = None;
+        let r = jbd_get_fs(fs, &mut jbd);
+        if r != EOK {
+            ext4_trans_abort(fs, trans);
+            return r;
+        }
+        let mut jbd = jbd.unwrap();
+        let r = jbd_write_trans(&mut jbd, &blocks, &(*trans).sb);
+        if r != EOK {
+            ext4_fs_put_inode_ref(&mut jbd.inode);
+            ext4_trans_abort(fs, trans);
+            return r;
+        }
+
+        // 提交块已写入，修改已经持久
+        (*bc).in_trans = false;
+        (*trans).active = false;
+        (*fs).metrics.journal_commits += 1;
+        let r = jbd_checkpoint(&mut jbd);
+        let r2 = ext4_fs_put_inode_ref(&mut jbd.inode);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 中止事务：丢弃事务期间的全部元数据修改
+///
+/// 事务中新分配的块和 inode 重新变为空闲；已经写入设备的文件数据不会恢复。
+pub unsafe fn ext4_trans_abort(fs: *mut Ext4Filesystem, trans: *mut Ext4Transaction) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_JBD, "ext4_trans_abort");
+    unsafe {
+        if !(*trans).active {
+            return EINVAL;
+        }
+        let bc = (*(*fs).bdev).bc;
+        ext4_bcache_invalidate_dirty(bc);
+        (*fs).sb = (*trans).sb;
+        (*fs).last_inode_bg_id = (*trans).last_inode_bg_id;
+        ext4_es_clear(&mut (*fs).es_cache);
+        (*bc).in_trans = false;
+        (*trans).active = false;
+    }
+    EOK
+}
@@ -0,0 +1,202 @@
+//! 文件系统布局计算模块
+//!
+//! 根据 superblock 计算每个块组中 superblock 备份、GDT、预留GDT、位图和 inode 表的位置
+//! （支持 sparse_super、sparse_super2、meta_bg 和 flex_bg），供格式化、分配器的元数据保护、
+//! 挂载时的描述符检查和调试工具使用。
+//!
+//! 对应 e2fsprogs 中 ext2fs_super_and_bgd_loc2 与 ext2fs_allocate_group_table 的布局规则。
+
+use crate::block_group::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4BlockGroupRef, Ext4Filesystem, Ext4Superblock};
+
+/// 块组布局（均为文件系统绝对块号，块数为0表示没有该区域）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ext4GroupLayout {
+    pub group: u32,               // 块组号
+    pub first_block: u64,         // 块组第一个块
+    pub blocks: u32,              // 块组块数
+    pub super_block: Option<u64>, // superblock（或其备份）所在块
+    pub gdt_start: u64,           // GDT（或其备份）起始块
+    pub gdt_blocks: u32,          // GDT块数
+    pub reserved_gdt_start: u64,  // 预留GDT起始块
+    pub reserved_gdt_blocks: u32, // 预留GDT块数
+    pub block_bitmap: u64,        // 块位图
+    pub inode_bitmap: u64,        // inode位图
+    pub inode_table: u64,         // inode表起始块
+    pub inode_table_blocks: u32,  // inode表块数
+}
+
+/// 每个块组的 inode 表块数
+pub fn ext4_layout_inode_table_blocks(sb: &Ext4Superblock) -> u32 {
+    (u32::from_le(sb.inodes_per_group) as u64 * get_inode_size(sb) as u64)
+        .div_ceil(ext4_sb_get_block_size(sb) as u64) as u32
+}
+
+/// 计算块组开头的固定元数据（superblock、GDT、预留GDT），位图和 inode 表位置置0
+pub fn ext4_layout_base(sb: &Ext4Superblock, group: u32) -> Ext4GroupLayout {
+    let first_block = ext4_balloc_get_block_of_bgid(sb, group);
+    let has_super = ext4_sb_is_super_in_bg(sb, group);
+    let mut layout = Ext4GroupLayout {
+        group,
+        first_block,
+        blocks: ext4_blocks_in_group_cnt(sb, group),
+        super_block: has_super.then_some(first_block),
+        inode_table_blocks: ext4_layout_inode_table_blocks(sb),
+        ..Default::default()
+    };
+
+    let next = first_block + has_super as u64;
+    layout.gdt_start = next;
+    layout.gdt_blocks = ext4_bg_num_gdb(sb, group);
+    // meta_bg 部分的块组没有预留GDT
+    let meta_bg = ext4_sb_feature_incom(sb, EXT4_FINCOM_META_BG)
+        && group >= ext4_sb_first_meta_bg(sb) * ext4_sb_dsc_per_block(sb);
+    if has_super && !meta_bg {
+        layout.reserved_gdt_start = next + layout.gdt_blocks as u64;
+        layout.reserved_gdt_blocks = u16::from_le(sb.s_reserved_gdt_blocks) as u32;
+    }
+    layout
+}
+
+/// 块组开头固定元数据的块数（与 ext4_num_base_meta_clusters 相同）
+pub fn ext4_layout_base_blocks(layout: &Ext4GroupLayout) -> u32 {
+    layout.super_block.is_some() as u32 + layout.gdt_blocks + layout.reserved_gdt_blocks
+}
+
+/// 从 pos 开始第一个不与任何块组固定元数据重叠、长度为 len 的区域
+fn ext4_layout_skip_base(sb: &Ext4Superblock, mut pos: u64, len: u64) -> u64 {
+    'retry: loop {
+        let first = ext4_balloc_get_bgid_of_block(sb, pos);
+        let last = ext4_balloc_get_bgid_of_block(sb, pos + len - 1);
+        for g in first..=last {
+            let base = ext4_layout_base(sb, g);
+            let base_end = base.first_block + ext4_layout_base_blocks(&base) as u64;
+            if base.first_block < pos + len && pos < base_end {
+                pos = base_end;
+                continue 'retry;
+            }
+        }
+        return pos;
+    }
+}
+
+/// 按格式化时的默认规则计算块组布局
+///
+/// 没有 flex_bg 时位图和 inode 表紧接在本组的固定元数据之后；启用 flex_bg 时同一 flex 组的
+/// 块位图、inode 位图和 inode 表依次集中存放在 flex 组第一个块组的固定元数据之后，
+/// 跨越其他块组的 superblock/GDT 备份时跳到备份之后（与 mke2fs 相同）。
+pub fn ext4_layout_default(sb: &Ext4Superblock, group: u32) -> Ext4GroupLayout {
+    let mut layout = ext4_layout_base(sb, group);
+    let itb = layout.inode_table_blocks as u64;
+
+    if !ext4_sb_feature_incom(sb, EXT4_FINCOM_FLEX_BG) || sb.log_groups_per_flex == 0 {
+        layout.block_bitmap = layout.first_block + ext4_layout_base_blocks(&layout) as u64;
+        layout.inode_bitmap = layout.block_bitmap + 1;
+        layout.inode_table = layout.block_bitmap + 2;
+        return layout;
+    }
+
+    let flex = 1u32 << sb.log_groups_per_flex.min(31);
+    let first = group & !(flex - 1);
+    let last = ext4_block_group_cnt(sb) - 1;
+    // 最后一个 flex 组不满时各表之间不留空位（与 mke2fs 相同）
+    let mut table_offset = flex as u64;
+    if first + flex > last && last & (flex - 1) != 0 {
+        table_offset = (last & (flex - 1)) as u64 + 1;
+    }
+    let head = ext4_layout_base(sb, first);
+    let start = head.first_block + ext4_layout_base_blocks(&head) as u64;
+    let (mut bb, mut ib, mut it) = (start, start + table_offset, start + 2 * table_offset);
+    for g in first..=group {
+        if g != first {
+            bb += 1;
+            ib += 1;
+            it += itb;
+        }
+        bb = ext4_layout_skip_base(sb, bb, 1);
+        ib = ext4_layout_skip_base(sb, ib, 1);
+        it = ext4_layout_skip_base(sb, it, itb);
+    }
+    layout.block_bitmap = bb;
+    layout.inode_bitmap = ib;
+    layout.inode_table = it;
+    layout
+}
+
+/// 读取块组的实际布局（位图和 inode 表位置取自块组描述符）
+pub unsafe fn ext4_layout_get(fs: *mut Ext4Filesystem, group: u32, layout: *mut Ext4GroupLayout) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let mut bg_ref = Ext4BlockGroupRef::new();
+        let r = ext4_fs_get_block_group_ref(fs, group, &mut bg_ref);
+        if r != EOK {
+            return r;
+        }
+        let bg = &*bg_ref.block_group;
+        let mut l = ext4_layout_base(sb, group);
+        l.block_bitmap = ext4_bg_get_block_bitmap(bg, sb);
+        l.inode_bitmap = ext4_bg_get_inode_bitmap(bg, sb);
+        l.inode_table = ext4_bg_get_inode_table_first_block(bg, sb);
+        *layout = l;
+        ext4_fs_put_block_group_ref(&mut bg_ref)
+    }
+}
+
+/// 块组布局中的各元数据区域（起始块, 块数）：固定元数据、块位图、inode位图、inode表
+pub fn ext4_layout_meta_ranges(layout: &Ext4GroupLayout) -> [(u64, u64); 4] {
+    [
+        (layout.first_block, ext4_layout_base_blocks(layout) as u64),
+        (layout.block_bitmap, 1),
+        (layout.inode_bitmap, 1),
+        (layout.inode_table, layout.inode_table_blocks as u64),
+    ]
+}
+
+/// 检查各块组描述符中的位图和 inode 表位置（对应 Linux 的 ext4_check_descriptors）
+///
+/// 要求它们位于文件系统范围内（没有 flex_bg 时位于本组内），且不与任何块组的
+/// superblock/GDT/预留GDT 重叠。发现问题时返回 EIO。
+pub unsafe fn ext4_layout_check(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let first_data_block = u32::from_le(sb.first_data_block) as u64;
+        let blocks_count = ext4_sb_get_blocks_cnt(sb);
+        let flex_bg = ext4_sb_feature_incom(sb, EXT4_FINCOM_FLEX_BG);
+
+        for group in 0..ext4_block_group_cnt(sb) {
+            let mut layout = Ext4GroupLayout::default();
+            let r = ext4_layout_get(fs, group, &mut layout);
+            if r != EOK {
+                return r;
+            }
+            let (low, high) = if flex_bg {
+                (first_data_block, blocks_count)
+            } else {
+                (layout.first_block, layout.first_block + layout.blocks as u64)
+            };
+            for (what, start, len) in [
+                ("block bitmap", layout.block_bitmap, 1),
+                ("inode bitmap", layout.inode_bitmap, 1),
+                ("inode table", layout.inode_table, layout.inode_table_blocks as u64),
+            ] {
+                let end = start.saturating_add(len);
+                let bad = start < low
+                    || end > high
+                    || (ext4_balloc_get_bgid_of_block(sb, start)..=ext4_balloc_get_bgid_of_block(sb, end - 1))
+                        .any(|g| {
+                            let base = ext4_layout_base(sb, g);
+                            base.first_block < end
+                                && start < base.first_block + ext4_layout_base_blocks(&base) as u64
+                        });
+                if bad {
+                    ext4_dbg!(DEBUG_BLOCK_GROUP, Warn, "bg {}: {} at {} is not valid", group, what, start);
+                    return EIO;
+                }
+            }
+        }
+    }
+    EOK
+}
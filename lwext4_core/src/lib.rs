@@ -4,27 +4,75 @@
 
 #![no_std]
 #![allow(dead_code)]
+// C 风格 API：裸指针参数的安全约定与 lwext4 相同，不逐一撰写 Safety 文档
+#![allow(clippy::missing_safety_doc)]
+#![allow(clippy::new_without_default)]
 
 extern crate alloc;
 
+// 调试输出宏（ext4_dbg! 等）需先于其他模块定义
+#[macro_use]
+pub mod debug;
+
 // 公共模块
 pub mod consts;
 pub mod types;
 pub mod error;
+pub mod crc;
 pub mod superblock;
-pub mod inode;
+pub mod bitmap;
+pub mod bcache;
 pub mod block;
+pub mod block_group;
+pub mod layout;
+pub mod balloc;
+pub mod ialloc;
+pub mod extent;
+pub mod extent_status;
+pub mod inode;
 pub mod dir;
+pub mod dir_idx;
+pub mod hash;
 pub mod fs;
+pub mod journal;
+pub mod fast_commit;
+pub mod undo;
+pub mod tune;
+pub mod mkfs;
+pub mod xattr;
+pub mod verity;
+pub mod quota;
+
+// lwext4 兼容的 C 接口
+#[cfg(feature = "c-api")]
+pub mod c_api;
 
 // 重新导出常用类型
 pub use consts::*;
-pub use error::{Ext4Error, Ext4Result};
+pub use error::{ErrorContext, Ext4Error, Ext4ErrorKind, Ext4Result};
 pub use types::*;
 
 // 重新导出所有API函数
 pub use fs::*;
+pub use bcache::*;
 pub use block::*;
+pub use block_group::*;
+pub use layout::*;
+pub use balloc::*;
+pub use ialloc::*;
+pub use extent::*;
+pub use extent_status::*;
 pub use inode::*;
 pub use dir::*;
+pub use dir_idx::*;
+pub use hash::*;
 pub use superblock::*;
+pub use debug::*;
+pub use journal::*;
+pub use fast_commit::*;
+pub use undo::*;
+pub use tune::*;
+pub use mkfs::*;
+pub use xattr::*;
+pub use verity::*;
+pub use quota::*;
@@ -0,0 +1,714 @@
+//! 格式化模块
+//!
+//! 对应C实现: ext4_mkfs.c
+//!
+//! 布局比 mke2fs 简单：每个块组依次存放 superblock 与 GDT 备份（按 sparse_super 规则）、
+//! 块位图、inode 位图和 inode 表，其余都是数据块；不使用 flex_bg、meta_bg 和 resize_inode。
+//! 先直接向设备写入这些元数据，再挂载新文件系统，用常规接口创建根目录、lost+found 和日志。
+
+use alloc::vec;
+use core::ptr;
+
+use crate::balloc::*;
+use crate::bcache::*;
+use crate::bitmap::*;
+use crate::block::*;
+use crate::block_group::*;
+use crate::dir::*;
+use crate::fs::*;
+use crate::ialloc::*;
+use crate::inode::*;
+use crate::journal::*;
+use crate::layout::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{
+    Ext4BlockDevice, Ext4BlockGroup, Ext4Filesystem, Ext4InodeRef, Ext4MkfsInfo, Ext4Superblock,
+};
+
+/// 未指定时的块大小
+pub const EXT4_MKFS_DEFAULT_BLOCK_SIZE: u32 = 4096;
+
+/// 未指定时的 inode 大小
+pub const EXT4_MKFS_DEFAULT_INODE_SIZE: u16 = 256;
+
+/// 未指定 inode 数时，每多少字节分配一个 inode（与 mke2fs 相同）
+pub const EXT4_MKFS_INODE_RATIO: u64 = 16384;
+
+/// 日志的最小块数（与 jbd2 相同）
+pub const EXT4_MKFS_MIN_JOURNAL_BLOCKS: u32 = 1024;
+
+/// 格式化支持的特性（布局相关的 flex_bg、meta_bg 等不在其中）
+pub const EXT4_MKFS_SUPPORTED_FCOM: u32 =
+    EXT4_FCOM_HAS_JOURNAL | EXT4_FCOM_EXT_ATTR | EXT4_FCOM_DIR_INDEX;
+pub const EXT4_MKFS_SUPPORTED_FINCOM: u32 =
+    EXT4_FINCOM_FILETYPE | EXT4_FINCOM_EXTENTS | EXT4_FINCOM_64BIT;
+pub const EXT4_MKFS_SUPPORTED_FRO_COM: u32 = EXT4_FRO_COM_SPARSE_SUPER
+    | EXT4_FRO_COM_LARGE_FILE
+    | EXT4_FRO_COM_HUGE_FILE
+    | EXT4_FRO_COM_DIR_NLINK
+    | EXT4_FRO_COM_EXTRA_ISIZE
+    | EXT4_FRO_COM_METADATA_CSUM;
+
+/// 三组特性都为0时使用的默认特性（ext4）
+pub const EXT4_MKFS_DEFAULT_FCOM: u32 = EXT4_FCOM_EXT_ATTR | EXT4_FCOM_DIR_INDEX;
+pub const EXT4_MKFS_DEFAULT_FINCOM: u32 = EXT4_MKFS_SUPPORTED_FINCOM;
+pub const EXT4_MKFS_DEFAULT_FRO_COM: u32 = EXT4_MKFS_SUPPORTED_FRO_COM;
+
+/// 每组 inode 数下限（保证第一个块组放得下保留 inode 和 lost+found）
+const EXT4_MKFS_MIN_INODES_PER_GROUP: u32 = 16;
+
+/// 最后一个块组除元数据外至少要有的块数，不足时丢弃该块组（与 mke2fs 相同）
+const EXT4_MKFS_MIN_GROUP_DATA_BLOCKS: u64 = 50;
+
+/// 一次写入的清零块数上限
+const EXT4_MKFS_ZERO_CHUNK: u32 = 64;
+
+/// 格式化进度的阶段（见 ext4_mkfs_progress）
+pub const EXT4_MKFS_PROGRESS_GROUPS: u32 = 0; // 写入块组元数据，单位为块组
+pub const EXT4_MKFS_PROGRESS_JOURNAL: u32 = 1; // 创建日志，单位为日志块
+
+/// 按文件系统大小选择日志块数（与 mke2fs 的默认值一致），太小时返回0（不创建日志）
+pub fn ext4_mkfs_journal_size(blocks: u64) -> u32 {
+    match blocks {
+        0..2048 => 0,
+        2048..32768 => 1024,
+        32768..262144 => 4096,
+        262144..524288 => 8192,
+        524288..1048576 => 16384,
+        _ => 32768,
+    }
+}
+
+/// 检查参数并填入默认值
+unsafe fn ext4_mkfs_fill_info(bd: *mut Ext4BlockDevice, info: &mut Ext4MkfsInfo) -> i32 {
+    unsafe {
+        let part_size = (*bd).part_size;
+        if info.len == 0 {
+            info.len = part_size;
+        }
+        if info.len > part_size {
+            return EINVAL;
+        }
+    }
+
+    if info.block_size == 0 {
+        info.block_size = EXT4_MKFS_DEFAULT_BLOCK_SIZE;
+    }
+    if !info.block_size.is_power_of_two() || !(1024..=65536).contains(&info.block_size) {
+        return EINVAL;
+    }
+    if info.blocks_per_group == 0 {
+        info.blocks_per_group = info.block_size * 8;
+    }
+    if info.blocks_per_group > info.block_size * 8 || !info.blocks_per_group.is_multiple_of(8) {
+        return EINVAL;
+    }
+    if info.inode_size == 0 {
+        info.inode_size = EXT4_MKFS_DEFAULT_INODE_SIZE;
+    }
+    if !info.inode_size.is_power_of_two()
+        || info.inode_size < EXT4_GOOD_OLD_INODE_SIZE
+        || info.inode_size as u32 > info.block_size
+    {
+        return EINVAL;
+    }
+
+    if info.feat_compat == 0 && info.feat_incompat == 0 && info.feat_ro_compat == 0 {
+        info.feat_compat = EXT4_MKFS_DEFAULT_FCOM;
+        info.feat_incompat = EXT4_MKFS_DEFAULT_FINCOM;
+        info.feat_ro_compat = EXT4_MKFS_DEFAULT_FRO_COM;
+    }
+    if info.feat_compat & !EXT4_MKFS_SUPPORTED_FCOM != 0
+        || info.feat_incompat & !EXT4_MKFS_SUPPORTED_FINCOM != 0
+        || info.feat_ro_compat & !EXT4_MKFS_SUPPORTED_FRO_COM != 0
+    {
+        ext4_dbg!(DEBUG_MKFS, Warn, "unsupported features requested");
+        return ENOTSUP;
+    }
+
+    let is_64bit = info.feat_incompat & EXT4_FINCOM_64BIT != 0;
+    if info.dsc_size == 0 {
+        info.dsc_size = if is_64bit {
+            EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE
+        } else {
+            EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE
+        };
+    }
+    if is_64bit != (info.dsc_size == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE)
+        || !(info.dsc_size == EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE
+            || info.dsc_size == EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE)
+    {
+        return EINVAL;
+    }
+
+    if info.journal {
+        if info.journal_blocks == 0 {
+            info.journal_blocks = ext4_mkfs_journal_size(info.len / info.block_size as u64);
+        }
+        if info.journal_blocks == 0 {
+            ext4_dbg!(DEBUG_MKFS, Info, "filesystem too small for a journal");
+            info.journal = false;
+        } else if info.journal_blocks < EXT4_MKFS_MIN_JOURNAL_BLOCKS {
+            return EINVAL;
+        }
+    }
+    if info.journal {
+        info.feat_compat |= EXT4_FCOM_HAS_JOURNAL;
+    } else {
+        info.feat_compat &= !EXT4_FCOM_HAS_JOURNAL;
+        info.journal_blocks = 0;
+    }
+    EOK
+}
+
+/// 块组开头的元数据块数（superblock、GDT、两个位图和 inode 表）
+fn ext4_mkfs_group_overhead(sb: &Ext4Superblock, group: u32) -> u64 {
+    let layout = ext4_layout_base(sb, group);
+    ext4_layout_base_blocks(&layout) as u64 + 2 + layout.inode_table_blocks as u64
+}
+
+/// 按块组数确定每组 inode 数
+fn ext4_mkfs_inodes_per_group(info: &Ext4MkfsInfo, blocks: u64, groups: u32) -> u32 {
+    let per_block = info.block_size / info.inode_size as u32;
+    let align = per_block.max(8);
+    let max = info.block_size * 8;
+
+    let ipg = if info.inodes_per_group != 0 {
+        info.inodes_per_group as u64
+    } else {
+        let inodes = if info.inodes != 0 {
+            info.inodes as u64
+        } else {
+            blocks * info.block_size as u64 / EXT4_MKFS_INODE_RATIO
+        };
+        inodes.div_ceil(groups as u64)
+    };
+    let mut ipg = (ipg.max(EXT4_MKFS_MIN_INODES_PER_GROUP as u64).next_multiple_of(align as u64))
+        .min(max as u64) as u32;
+    // 总 inode 数不能超过32位
+    if ipg as u64 * groups as u64 > u32::MAX as u64 {
+        ipg = (u32::MAX / groups) / align * align;
+    }
+    ipg
+}
+
+/// 根据格式化参数生成 superblock（块组数、每组 inode 数、空闲计数等）
+fn ext4_mkfs_fill_sb(info: &mut Ext4MkfsInfo, sb: &mut Ext4Superblock) -> i32 {
+    *sb = Ext4Superblock::default();
+    let block_size = info.block_size;
+    let blocks_per_group = info.blocks_per_group;
+    let first_data_block: u64 = if block_size == 1024 { 1 } else { 0 };
+
+    sb.magic = EXT4_SUPERBLOCK_MAGIC.to_le();
+    sb.rev_level = 1u32.to_le();
+    sb.log_block_size = (block_size.trailing_zeros() - 10).to_le();
+    sb.log_cluster_size = sb.log_block_size;
+    sb.blocks_per_group = blocks_per_group.to_le();
+    sb.clusters_per_group = blocks_per_group.to_le();
+    sb.first_data_block = (first_data_block as u32).to_le();
+    sb.first_ino = EXT4_GOOD_OLD_FIRST_INO.to_le();
+    sb.inode_size = info.inode_size.to_le();
+    sb.feature_compat = info.feat_compat.to_le();
+    sb.feature_incompat = info.feat_incompat.to_le();
+    sb.feature_ro_compat = info.feat_ro_compat.to_le();
+    if info.feat_incompat & EXT4_FINCOM_64BIT != 0 {
+        sb.desc_size = info.dsc_size.to_le();
+    }
+
+    let mut blocks = info.len / block_size as u64;
+    if info.feat_incompat & EXT4_FINCOM_64BIT == 0 {
+        blocks = blocks.min(u32::MAX as u64);
+    }
+
+    // 最后一个块组放不下元数据时丢弃它
+    let groups = loop {
+        if blocks <= first_data_block {
+            return EINVAL;
+        }
+        let groups = (blocks - first_data_block).div_ceil(blocks_per_group as u64);
+        if groups > u32::MAX as u64 {
+            return EINVAL;
+        }
+        let groups = groups as u32;
+        ext4_sb_set_blocks_cnt(sb, blocks);
+        let ipg = ext4_mkfs_inodes_per_group(info, blocks, groups);
+        sb.inodes_per_group = ipg.to_le();
+        sb.inodes_count = (ipg * groups).to_le();
+
+        if ext4_mkfs_group_overhead(sb, 0) >= blocks_per_group as u64 {
+            return EINVAL;
+        }
+        let last = groups - 1;
+        let last_blocks = blocks - first_data_block - last as u64 * blocks_per_group as u64;
+        if last_blocks >= ext4_mkfs_group_overhead(sb, last) + EXT4_MKFS_MIN_GROUP_DATA_BLOCKS {
+            break groups;
+        }
+        if groups == 1 {
+            ext4_dbg!(DEBUG_MKFS, Warn, "device too small: {} blocks", blocks);
+            return EINVAL;
+        }
+        blocks = first_data_block + last as u64 * blocks_per_group as u64;
+    };
+
+    let ipg = u32::from_le(sb.inodes_per_group);
+    info.inodes_per_group = ipg;
+    info.inodes = ipg * groups;
+    info.len = blocks * block_size as u64;
+
+    let mut free_blocks = 0u64;
+    for group in 0..groups {
+        free_blocks += ext4_blocks_in_group_cnt(sb, group) as u64 - ext4_mkfs_group_overhead(sb, group);
+    }
+    ext4_sb_set_free_blocks_cnt(sb, free_blocks);
+    sb.free_inodes_count = (info.inodes - (EXT4_GOOD_OLD_FIRST_INO - 1)).to_le();
+
+    sb.max_mnt_count = u16::MAX.to_le();
+    sb.state = EXT4_SUPERBLOCK_STATE_VALID_FS.to_le();
+    sb.errors = EXT4_ERRORS_CONTINUE.to_le();
+    sb.uuid = info.uuid;
+    sb.volume_name = info.label;
+    for (i, seed) in sb.hash_seed.iter_mut().enumerate() {
+        let b = &info.hash_seed[i * 4..i * 4 + 4];
+        *seed = u32::from_le_bytes([b[0], b[1], b[2], b[3]]).to_le();
+    }
+    sb.default_hash_version = EXT2_HTREE_HALF_MD4 as u8;
+    sb.flags = EXT4_SUPERBLOCK_FLAGS_SIGNED_HASH.to_le();
+    sb.default_mount_opts = (EXT4_DEFM_XATTR_USER | EXT4_DEFM_ACL).to_le();
+    if info.inode_size > EXT4_GOOD_OLD_INODE_SIZE {
+        let extra = 32u16.min(info.inode_size - EXT4_GOOD_OLD_INODE_SIZE);
+        sb.min_extra_isize = extra.to_le();
+        sb.want_extra_isize = extra.to_le();
+    }
+    if info.journal {
+        sb.journal_inode_number = EXT4_INODE_JOURNAL_INDEX.to_le();
+    }
+    if info.feat_ro_compat & EXT4_FRO_COM_METADATA_CSUM != 0 {
+        sb.checksum_type = EXT4_CRC32C_CHKSUM;
+    }
+    EOK
+}
+
+/// 从 start 开始写入 cnt 个全零块
+unsafe fn ext4_mkfs_zero_blocks(bd: *mut Ext4BlockDevice, start: u64, cnt: u32) -> i32 {
+    unsafe {
+        let block_size = (*bd).lg_bsize as usize;
+        let zero = vec![0u8; block_size * cnt.min(EXT4_MKFS_ZERO_CHUNK) as usize];
+        let mut done = 0;
+        while done < cnt {
+            let n = (cnt - done).min(EXT4_MKFS_ZERO_CHUNK);
+            let r = ext4_blocks_set_direct(bd, zero.as_ptr() as *const _, start + done as u64, n);
+            if r != EOK {
+                return r;
+            }
+            done += n;
+        }
+    }
+    EOK
+}
+
+/// 写入各块组的位图、清零的 inode 表、GDT 以及 superblock（含备份）
+unsafe fn ext4_mkfs_write_groups(
+    bd: *mut Ext4BlockDevice,
+    sb: &mut Ext4Superblock,
+    progress: &mut dyn FnMut(u32, u64, u64),
+) -> i32 {
+    unsafe {
+        let block_size = ext4_sb_get_block_size(sb);
+        let groups = ext4_block_group_cnt(sb);
+        let ipg = u32::from_le(sb.inodes_per_group);
+        let desc_size = ext4_sb_get_desc_size(sb) as usize;
+        let is_64bit = ext4_sb_feature_incom(sb, EXT4_FINCOM_64BIT);
+        let has_csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM);
+        let gdt_blocks = ext4_bg_num_gdb(sb, 0);
+
+        let mut gdt = vec![0u8; (gdt_blocks * block_size) as usize];
+        let mut bitmap = vec![0u8; block_size as usize];
+        for group in 0..groups {
+            let layout = ext4_layout_default(sb, group);
+            let group_blocks = layout.blocks;
+            let overhead = ext4_mkfs_group_overhead(sb, group) as u32;
+            let block_bitmap = layout.block_bitmap;
+            let inode_bitmap = layout.inode_bitmap;
+            let inode_table = layout.inode_table;
+            // 第一个块组中的保留 inode（1 ~ first_ino-1）
+            let reserved = if group == 0 { EXT4_GOOD_OLD_FIRST_INO - 1 } else { 0 };
+
+            let mut bg: Ext4BlockGroup = core::mem::zeroed();
+            bg.block_bitmap_lo = (block_bitmap as u32).to_le();
+            bg.inode_bitmap_lo = (inode_bitmap as u32).to_le();
+            bg.inode_table_first_block_lo = (inode_table as u32).to_le();
+            if is_64bit {
+                bg.block_bitmap_hi = ((block_bitmap >> 32) as u32).to_le();
+                bg.inode_bitmap_hi = ((inode_bitmap >> 32) as u32).to_le();
+                bg.inode_table_first_block_hi = ((inode_table >> 32) as u32).to_le();
+            }
+            ext4_bg_set_free_blocks_count(&mut bg, sb, group_blocks - overhead);
+            ext4_bg_set_free_inodes_count(&mut bg, sb, ipg - reserved);
+            // 根目录（inode 2）随后在第一个块组中创建
+            ext4_bg_set_used_dirs_count(&mut bg, sb, (group == 0) as u32);
+            if has_csum {
+                ext4_bg_set_itable_unused(&mut bg, sb, ipg - reserved);
+                ext4_bg_set_flag(&mut bg, EXT4_BLOCK_GROUP_ITABLE_ZEROED);
+            }
+
+            bitmap.fill(0);
+            for bit in 0..overhead {
+                ext4_bmap_bit_set(&mut bitmap, bit);
+            }
+            ext4_fs_mark_bitmap_end(group_blocks, block_size * 8, &mut bitmap);
+            ext4_balloc_set_bitmap_csum(sb, &mut bg, &bitmap);
+            let r = ext4_blocks_set_direct(bd, bitmap.as_ptr() as *const _, block_bitmap, 1);
+            if r != EOK {
+                return r;
+            }
+
+            bitmap.fill(0);
+            for bit in 0..reserved {
+                ext4_bmap_bit_set(&mut bitmap, bit);
+            }
+            ext4_fs_mark_bitmap_end(ipg, block_size * 8, &mut bitmap);
+            ext4_ialloc_set_bitmap_csum(sb, &mut bg, &bitmap);
+            let r = ext4_blocks_set_direct(bd, bitmap.as_ptr() as *const _, inode_bitmap, 1);
+            if r != EOK {
+                return r;
+            }
+
+            let r = ext4_mkfs_zero_blocks(bd, inode_table, layout.inode_table_blocks);
+            if r != EOK {
+                return r;
+            }
+
+            bg.checksum = ext4_fs_bg_checksum(sb, group, &bg).to_le();
+            ptr::copy_nonoverlapping(
+                &bg as *const _ as *const u8,
+                gdt.as_mut_ptr().add(group as usize * desc_size),
+                desc_size,
+            );
+            progress(EXT4_MKFS_PROGRESS_GROUPS, group as u64 + 1, groups as u64);
+        }
+
+        // superblock 与 GDT（备份中的 block_group_nr 为所在块组号）
+        let mut backup = vec![0u8; block_size as usize];
+        for group in 0..groups {
+            let layout = ext4_layout_base(sb, group);
+            let Some(start) = layout.super_block else {
+                continue;
+            };
+            let r = if group == 0 {
+                ext4_sb_write(bd, sb)
+            } else {
+                let mut copy = *sb;
+                copy.block_group_nr = (group as u16).to_le();
+                ext4_sb_set_csum(&mut copy);
+                ptr::copy_nonoverlapping(
+                    &copy as *const _ as *const u8,
+                    backup.as_mut_ptr(),
+                    EXT4_SUPERBLOCK_SIZE,
+                );
+                ext4_blocks_set_direct(bd, backup.as_ptr() as *const _, start, 1)
+            };
+            if r != EOK {
+                return r;
+            }
+            let r = ext4_blocks_set_direct(bd, gdt.as_ptr() as *const _, layout.gdt_start, gdt_blocks);
+            if r != EOK {
+                return r;
+            }
+        }
+        ext4_dbg!(
+            DEBUG_MKFS,
+            Info,
+            "wrote {} groups: blocks={}, inodes={}, gdt_blocks={}",
+            groups,
+            ext4_sb_get_blocks_cnt(sb),
+            u32::from_le(sb.inodes_count),
+            gdt_blocks
+        );
+    }
+    EOK
+}
+
+/// 初始化一个保留 inode（清零后设置类型和权限）
+unsafe fn ext4_mkfs_inode_init(
+    fs: *mut Ext4Filesystem,
+    index: u32,
+    inode_ref: *mut Ext4InodeRef,
+    mode: u32,
+) -> i32 {
+    unsafe {
+        let r = __ext4_fs_get_inode_ref(fs, index, inode_ref, false);
+        if r != EOK {
+            return r;
+        }
+        let sb = &mut (*fs).sb;
+        let inode_size = get_inode_size(sb);
+        let inode = (*inode_ref).inode;
+        ptr::write_bytes(inode as *mut u8, 0, inode_size as usize);
+        ext4_inode_set_mode(sb, inode, mode);
+        if inode_size > EXT4_GOOD_OLD_INODE_SIZE {
+            ext4_inode_set_extra_isize(sb, inode, u16::from_le(sb.want_extra_isize));
+        }
+        ext4_fs_inode_blocks_init(fs, inode_ref);
+        (*inode_ref).dirty = true;
+    }
+    EOK
+}
+
+/// 在目录中添加"."和".."
+unsafe fn ext4_mkfs_dir_init(dir: *mut Ext4InodeRef, parent: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let r = ext4_dir_add_entry(dir, b".".as_ptr(), 1, dir);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_dir_add_entry(dir, b"..".as_ptr(), 2, parent);
+        if r != EOK {
+            return r;
+        }
+        ext4_inode_set_links_cnt((*dir).inode, 2);
+        (*dir).dirty = true;
+    }
+    EOK
+}
+
+/// 创建根目录和 lost+found
+unsafe fn ext4_mkfs_create_dirs(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        let mut root = Ext4InodeRef::new();
+        let r = ext4_mkfs_inode_init(
+            fs,
+            EXT4_INODE_ROOT_INDEX,
+            &mut root,
+            EXT4_INODE_MODE_DIRECTORY as u32 | 0o755,
+        );
+        if r != EOK {
+            return r;
+        }
+        let root_ptr: *mut Ext4InodeRef = &mut root;
+        let r = ext4_mkfs_dir_init(root_ptr, root_ptr);
+        if r != EOK {
+            ext4_fs_put_inode_ref(&mut root);
+            return r;
+        }
+
+        let mut lpf = Ext4InodeRef::new();
+        let r = ext4_fs_alloc_inode(fs, &mut lpf, EXT4_DE_DIR);
+        if r != EOK {
+            ext4_fs_put_inode_ref(&mut root);
+            return r;
+        }
+        ext4_inode_set_mode(&mut (*fs).sb, lpf.inode, EXT4_INODE_MODE_DIRECTORY as u32 | 0o700);
+        ext4_fs_inode_blocks_init(fs, &mut lpf);
+        let mut r = ext4_dir_add_entry(&mut root, b"lost+found".as_ptr(), 10, &mut lpf);
+        if r == EOK {
+            r = ext4_mkfs_dir_init(&mut lpf, &mut root);
+        }
+        if r == EOK {
+            ext4_fs_inode_links_count_inc(&mut root);
+            (*fs).sb.lpf_ino = lpf.index.to_le();
+        }
+
+        let r2 = ext4_fs_put_inode_ref(&mut lpf);
+        let r3 = ext4_fs_put_inode_ref(&mut root);
+        if r != EOK {
+            return r;
+        }
+        if r2 != EOK {
+            return r2;
+        }
+        r3
+    }
+}
+
+/// 为日志 inode 分配块，清零后在第0块写入日志超级块
+unsafe fn ext4_mkfs_journal_fill(
+    fs: *mut Ext4Filesystem,
+    jnl: *mut Ext4InodeRef,
+    blocks: u32,
+    progress: &mut dyn FnMut(u32, u64, u64),
+) -> i32 {
+    unsafe {
+        let bd = (*fs).bdev;
+        // 块号连续的部分合并写入
+        let mut run_start = 0u64;
+        let mut run_len = 0u32;
+        for i in 0..blocks {
+            progress(EXT4_MKFS_PROGRESS_JOURNAL, i as u64, blocks as u64);
+            let mut fblock = 0u64;
+            let mut iblock = 0u32;
+            let r = ext4_fs_append_inode_dblk(jnl, &mut fblock, &mut iblock);
+            if r != EOK {
+                return r;
+            }
+            if run_len != 0 && fblock == run_start + run_len as u64 {
+                run_len += 1;
+                continue;
+            }
+            if run_len != 0 {
+                let r = ext4_mkfs_zero_blocks(bd, run_start, run_len);
+                if r != EOK {
+                    return r;
+                }
+            }
+            run_start = fblock;
+            run_len = 1;
+        }
+        let r = ext4_mkfs_zero_blocks(bd, run_start, run_len);
+        if r != EOK {
+            return r;
+        }
+        progress(EXT4_MKFS_PROGRESS_JOURNAL, blocks as u64, blocks as u64);
+
+        let sb = &mut (*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let mut incompat = 0;
+        if ext4_sb_feature_incom(sb, EXT4_FINCOM_64BIT) {
+            incompat |= JBD_FEATURE_INCOMPAT_64BIT;
+        }
+        if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            incompat |= JBD_FEATURE_INCOMPAT_CSUM_V3;
+        }
+        let mut buf = vec![0u8; block_size as usize];
+        ext4_journal_sb_init(&mut buf, block_size, blocks, incompat, &sb.uuid);
+        let mut first = 0u64;
+        let r = ext4_fs_get_inode_dblk_idx(jnl, 0, &mut first, false);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_blocks_set_direct(bd, buf.as_ptr() as *const _, first, 1);
+        if r != EOK {
+            return r;
+        }
+
+        // superblock 中备份日志 inode 的块映射和大小
+        let inode = (*jnl).inode;
+        sb.journal_blocks[..EXT4_INODE_BLOCKS].copy_from_slice(&(*inode).blocks);
+        sb.journal_blocks[EXT4_INODE_BLOCKS] = (*inode).size_hi;
+        sb.journal_blocks[EXT4_INODE_BLOCKS + 1] = (*inode).size_lo;
+        sb.journal_backup_type = EXT4_JNL_BACKUP_BLOCKS;
+    }
+    EOK
+}
+
+/// 创建日志 inode
+unsafe fn ext4_mkfs_create_journal(
+    fs: *mut Ext4Filesystem,
+    blocks: u32,
+    progress: &mut dyn FnMut(u32, u64, u64),
+) -> i32 {
+    unsafe {
+        let mut jnl = Ext4InodeRef::new();
+        let r = ext4_mkfs_inode_init(
+            fs,
+            EXT4_INODE_JOURNAL_INDEX,
+            &mut jnl,
+            EXT4_INODE_MODE_FILE as u32 | 0o600,
+        );
+        if r != EOK {
+            return r;
+        }
+        ext4_inode_set_links_cnt(jnl.inode, 1);
+        let r = ext4_mkfs_journal_fill(fs, &mut jnl, blocks, progress);
+        let r2 = ext4_fs_put_inode_ref(&mut jnl);
+        if r != EOK {
+            return r;
+        }
+        r2
+    }
+}
+
+/// 在已挂载的新文件系统上创建根目录、lost+found 和日志
+unsafe fn ext4_mkfs_populate(
+    fs: *mut Ext4Filesystem,
+    info: &Ext4MkfsInfo,
+    progress: &mut dyn FnMut(u32, u64, u64),
+) -> i32 {
+    unsafe {
+        let r = ext4_mkfs_create_dirs(fs);
+        if r != EOK {
+            return r;
+        }
+        if info.journal {
+            let r = ext4_mkfs_create_journal(fs, info.journal_blocks, progress);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+/// 在块设备上创建 ext4 文件系统
+///
+/// 块设备需已通过 ext4_block_init 打开，块缓存（bd.bc）由本函数初始化并在返回前销毁；
+/// fs 只在格式化期间使用，结束后按常规流程挂载即可。info 中为0的参数填入默认值并写回。
+/// 对应C实现: ext4_mkfs (ext4_mkfs.c)，不支持的特性返回 ENOTSUP。
+pub unsafe fn ext4_mkfs(
+    fs: *mut Ext4Filesystem,
+    bd: *mut Ext4BlockDevice,
+    info: *mut Ext4MkfsInfo,
+) -> i32 {
+    unsafe { ext4_mkfs_progress(fs, bd, info, &mut |_, _, _| {}) }
+}
+
+/// 与 ext4_mkfs 相同，格式化期间调用 progress(阶段, 已完成数, 总数) 报告进度
+///
+/// 阶段为 EXT4_MKFS_PROGRESS_GROUPS（每写完一个块组）和 EXT4_MKFS_PROGRESS_JOURNAL
+/// （按日志块，仅创建日志时）。
+pub unsafe fn ext4_mkfs_progress(
+    fs: *mut Ext4Filesystem,
+    bd: *mut Ext4BlockDevice,
+    info: *mut Ext4MkfsInfo,
+    progress: &mut dyn FnMut(u32, u64, u64),
+) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_MKFS, "ext4_mkfs");
+    unsafe {
+        let info = &mut *info;
+        let r = ext4_mkfs_fill_info(bd, info);
+        if r != EOK {
+            return r;
+        }
+        let mut sb = Ext4Superblock::default();
+        let r = ext4_mkfs_fill_sb(info, &mut sb);
+        if r != EOK {
+            return r;
+        }
+        ext4_dbg!(
+            DEBUG_MKFS,
+            Info,
+            "mkfs: len={}, block_size={}, inodes={}, inode_size={}, journal_blocks={}",
+            info.len,
+            info.block_size,
+            info.inodes,
+            info.inode_size,
+            info.journal_blocks
+        );
+
+        ext4_block_set_lb_size(bd, info.block_size);
+        let r = ext4_mkfs_write_groups(bd, &mut sb, progress);
+        if r != EOK {
+            return r;
+        }
+
+        let bc = (*bd).bc;
+        let r = ext4_bcache_init_dynamic(bc, CONFIG_BLOCK_DEV_CACHE_SIZE, info.block_size);
+        if r != EOK {
+            return r;
+        }
+        ext4_block_bind_bcache(bd, bc);
+        let mut r = ext4_fs_init(fs, bd, false);
+        if r == EOK {
+            r = ext4_mkfs_populate(fs, info, progress);
+            // 格式化期间的挂载不计入挂载次数
+            (*fs).sb.mnt_count = 0;
+            let r2 = ext4_fs_fini(fs);
+            if r == EOK {
+                r = r2;
+            }
+        }
+        ext4_bcache_fini_dynamic(bc);
+        r
+    }
+}
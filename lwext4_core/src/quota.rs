@@ -0,0 +1,118 @@
+//! 内存中的配额用量统计模块
+//!
+//! lwext4 中没有对应实现。不读写配额 inode（quota 特性），只在挂载期间按 uid/gid
+//! 累计 inode 占用的空间和 inode 数，供管理程序上报用量。
+//! 启用时扫描一次全部已使用的 inode 得到初始用量，此后在块分配/释放、inode 分配/释放
+//! 和修改属主时增量更新。空间按 inode 的块数（i_blocks，含索引块和扩展属性块）统计，
+//! 共享的扩展属性块与内核配额相同，计入每个引用它的 inode。
+
+use alloc::collections::BTreeMap;
+
+use crate::consts::*;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::tune::ext4_tune_for_each_inode;
+use crate::{Ext4Filesystem, Ext4InodeRef};
+
+/// 一个用户或组的用量
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ext4_quota_entry {
+    pub space: u64,  // 占用空间（字节）
+    pub inodes: u64, // inode 数
+}
+
+/// 按 uid 和 gid 分别统计的用量
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Default)]
+pub struct ext4_quota_usage {
+    pub users: BTreeMap<u32, ext4_quota_entry>,
+    pub groups: BTreeMap<u32, ext4_quota_entry>,
+}
+
+/// 累加一项用量（减到 0 的条目删除）
+fn ext4_quota_add(map: &mut BTreeMap<u32, ext4_quota_entry>, id: u32, space: i64, inodes: i64) {
+    let entry = map.entry(id).or_default();
+    entry.space = entry.space.saturating_add_signed(space);
+    entry.inodes = entry.inodes.saturating_add_signed(inodes);
+    if *entry == ext4_quota_entry::default() {
+        map.remove(&id);
+    }
+}
+
+/// 把空间（字节）和 inode 数的变化计入 uid 和 gid（未启用统计时什么也不做）
+pub unsafe fn ext4_quota_charge(fs: *mut Ext4Filesystem, uid: u32, gid: u32, space: i64, inodes: i64) {
+    unsafe {
+        if let Some(usage) = (*fs).quota.as_mut() {
+            ext4_quota_add(&mut usage.users, uid, space, inodes);
+            ext4_quota_add(&mut usage.groups, gid, space, inodes);
+        }
+    }
+}
+
+/// 把变化计入 inode 当前的属主
+pub unsafe fn ext4_quota_charge_inode(inode_ref: *mut Ext4InodeRef, space: i64, inodes: i64) {
+    unsafe {
+        let inode = (*inode_ref).inode;
+        let (uid, gid) = (ext4_inode_get_uid(inode), ext4_inode_get_gid(inode));
+        ext4_quota_charge((*inode_ref).fs, uid, gid, space, inodes);
+    }
+}
+
+/// inode 当前占用的空间（字节）
+unsafe fn ext4_quota_inode_space(inode_ref: *mut Ext4InodeRef) -> i64 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        (ext4_inode_get_blocks_count(sb, (*inode_ref).inode) * EXT4_INODE_BLOCK_SIZE as u64) as i64
+    }
+}
+
+/// 修改 inode 的属主，同时把它的用量从原属主转移到新属主
+pub unsafe fn ext4_quota_set_owner(inode_ref: *mut Ext4InodeRef, uid: u32, gid: u32) {
+    unsafe {
+        let space = ext4_quota_inode_space(inode_ref);
+        ext4_quota_charge_inode(inode_ref, -space, -1);
+        ext4_inode_set_uid((*inode_ref).inode, uid);
+        ext4_inode_set_gid((*inode_ref).inode, gid);
+        ext4_quota_charge_inode(inode_ref, space, 1);
+        (*inode_ref).dirty = true;
+    }
+}
+
+/// 释放 inode 时撤销它剩余的用量（共享扩展属性块等未经块释放的部分）
+pub unsafe fn ext4_quota_release_inode(inode_ref: *mut Ext4InodeRef) {
+    unsafe {
+        let space = ext4_quota_inode_space(inode_ref);
+        ext4_quota_charge_inode(inode_ref, -space, -1);
+    }
+}
+
+/// 启用用量统计：扫描全部已使用的 inode 得到初始用量（已启用时重新扫描）
+///
+/// 除根目录外的保留 inode（日志、调整大小等）不计入。
+pub unsafe fn ext4_quota_enable(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        let mut usage = ext4_quota_usage::default();
+        let sb = &(*fs).sb;
+        let r = ext4_tune_for_each_inode(fs, &mut |inode_ref| {
+            let ino = (*inode_ref).index;
+            if ino == EXT4_INODE_ROOT_INDEX || !ext4_sb_is_reserved_ino(sb, ino) {
+                let inode = (*inode_ref).inode;
+                let space = ext4_quota_inode_space(inode_ref);
+                ext4_quota_add(&mut usage.users, ext4_inode_get_uid(inode), space, 1);
+                ext4_quota_add(&mut usage.groups, ext4_inode_get_gid(inode), space, 1);
+            }
+            EOK
+        });
+        if r != EOK {
+            return r;
+        }
+        (*fs).quota = Some(usage);
+        EOK
+    }
+}
+
+/// 停止用量统计并丢弃已有数据
+pub unsafe fn ext4_quota_disable(fs: *mut Ext4Filesystem) {
+    unsafe { (*fs).quota = None }
+}
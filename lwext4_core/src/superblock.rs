@@ -1,24 +1,36 @@
 //! Superblock 操作模块
+//!
+//! 对应C实现: ext4_super.c
 
-use crate::{Ext4Result, Ext4Error, Ext4Superblock, BlockDevice};
+use crate::{Ext4Result, Ext4Error, Ext4Superblock, Ext4BlockDevice, BlockDevice};
+use crate::block::{ext4_block_readbytes, ext4_block_writebytes};
+use crate::crc::ext4_crc32c;
 use crate::consts::*;
+use crate::debug::*;
 
 /// 读取并解析 superblock
 pub fn read_superblock<D: BlockDevice>(dev: &mut D) -> Ext4Result<Ext4Superblock> {
-    let mut sb_buf = [0u8; EXT4_SUPERBLOCK_SIZE];
+    let ph_bsize = dev.block_size() as u64;
+    if !ph_bsize.is_power_of_two() {
+        return Err(Ext4Error::new(EINVAL, "device block size is not a power of two"));
+    }
 
-    // 读取 superblock（从偏移 1024 开始）
-    let start_block = EXT4_SUPERBLOCK_OFFSET / EXT4_DEV_BSIZE as u64;
+    // 读取覆盖 superblock 的物理块（从偏移 1024 开始，物理块大于 1024 时只占其中一部分）
+    let start_block = EXT4_SUPERBLOCK_OFFSET / ph_bsize;
+    let end_block = (EXT4_SUPERBLOCK_OFFSET + EXT4_SUPERBLOCK_SIZE as u64).div_ceil(ph_bsize);
+    let mut sb_buf = alloc::vec![0u8; ((end_block - start_block) * ph_bsize) as usize];
     dev.read_blocks(start_block, &mut sb_buf)?;
+    let start = (EXT4_SUPERBLOCK_OFFSET - start_block * ph_bsize) as usize;
 
     // 解析 superblock（暂时简化，直接转换）
     let sb = unsafe {
-        core::ptr::read_unaligned(sb_buf.as_ptr() as *const Ext4Superblock)
+        core::ptr::read_unaligned(sb_buf[start..].as_ptr() as *const Ext4Superblock)
     };
 
-    // 验证魔数
-    if u16::from_le(sb.magic) != EXT4_SUPERBLOCK_MAGIC {
-        return Err(Ext4Error::new(EINVAL, "Invalid ext4 magic number"));
+    // 验证魔数和几何参数
+    let r = ext4_sb_check(&sb);
+    if r != EOK {
+        return Err(Ext4Error::new(r, "Invalid ext4 superblock"));
     }
 
     Ok(sb)
@@ -31,6 +43,9 @@ pub fn get_block_size(sb: &Ext4Superblock) -> u32 {
 
 /// 获取 inode 大小
 pub fn get_inode_size(sb: &Ext4Superblock) -> u16 {
+    if u32::from_le(sb.rev_level) == 0 {
+        return EXT4_GOOD_OLD_INODE_SIZE;
+    }
     let size = u16::from_le(sb.inode_size);
     if size == 0 {
         128  // 默认值
@@ -41,8 +56,327 @@ pub fn get_inode_size(sb: &Ext4Superblock) -> u16 {
 
 /// 计算块组数量
 pub fn get_block_group_count(sb: &Ext4Superblock) -> u32 {
-    let blocks_count = u32::from_le(sb.blocks_count_lo);
+    let blocks_count = ext4_sb_get_blocks_cnt(sb) - u32::from_le(sb.first_data_block) as u64;
+    let blocks_per_group = u32::from_le(sb.blocks_per_group) as u64;
+
+    blocks_count.div_ceil(blocks_per_group) as u32
+}
+
+// ===== C 风格访问函数（对应 ext4_super.h） =====
+
+/// 获取块大小
+pub fn ext4_sb_get_block_size(sb: &Ext4Superblock) -> u32 {
+    get_block_size(sb)
+}
+
+/// 获取总块数
+pub fn ext4_sb_get_blocks_cnt(sb: &Ext4Superblock) -> u64 {
+    (u32::from_le(sb.blocks_count_hi) as u64) << 32 | u32::from_le(sb.blocks_count_lo) as u64
+}
+
+/// 设置总块数
+pub fn ext4_sb_set_blocks_cnt(sb: &mut Ext4Superblock, cnt: u64) {
+    sb.blocks_count_lo = (cnt as u32).to_le();
+    sb.blocks_count_hi = ((cnt >> 32) as u32).to_le();
+}
+
+/// 获取空闲块数
+pub fn ext4_sb_get_free_blocks_cnt(sb: &Ext4Superblock) -> u64 {
+    (u32::from_le(sb.free_blocks_count_hi) as u64) << 32
+        | u32::from_le(sb.free_blocks_count_lo) as u64
+}
+
+/// 设置空闲块数
+pub fn ext4_sb_set_free_blocks_cnt(sb: &mut Ext4Superblock, cnt: u64) {
+    sb.free_blocks_count_lo = (cnt as u32).to_le();
+    sb.free_blocks_count_hi = ((cnt >> 32) as u32).to_le();
+}
+
+/// 获取保留块数
+pub fn ext4_sb_get_r_blocks_cnt(sb: &Ext4Superblock) -> u64 {
+    (u32::from_le(sb.r_blocks_count_hi) as u64) << 32 | u32::from_le(sb.r_blocks_count_lo) as u64
+}
+
+/// 设置保留块数
+pub fn ext4_sb_set_r_blocks_cnt(sb: &mut Ext4Superblock, cnt: u64) {
+    sb.r_blocks_count_lo = (cnt as u32).to_le();
+    sb.r_blocks_count_hi = ((cnt >> 32) as u32).to_le();
+}
+
+/// 获取块组描述符大小
+pub fn ext4_sb_get_desc_size(sb: &Ext4Superblock) -> u16 {
+    let size = u16::from_le(sb.desc_size);
+    if ext4_sb_feature_incom(sb, EXT4_FINCOM_64BIT) && size >= EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
+        size
+    } else {
+        EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE
+    }
+}
+
+/// 检查兼容特性
+pub fn ext4_sb_feature_com(sb: &Ext4Superblock, feature: u32) -> bool {
+    u32::from_le(sb.feature_compat) & feature != 0
+}
+
+/// 第一个非保留 inode 编号（rev 0 固定为 11）
+pub fn ext4_sb_first_ino(sb: &Ext4Superblock) -> u32 {
+    if u32::from_le(sb.rev_level) < EXT4_DYNAMIC_REV {
+        EXT4_GOOD_OLD_FIRST_INO
+    } else {
+        u32::from_le(sb.first_ino)
+    }
+}
+
+/// 是否为保留 inode（编号小于 first_ino，包括根目录和 resize inode，以及日志 inode）
+///
+/// 保留 inode 不会被分配，也不能通过目录操作删除或新增链接。
+pub fn ext4_sb_is_reserved_ino(sb: &Ext4Superblock, ino: u32) -> bool {
+    ino < ext4_sb_first_ino(sb) || ino == u32::from_le(sb.journal_inode_number)
+}
+
+/// 检查不兼容特性
+pub fn ext4_sb_feature_incom(sb: &Ext4Superblock, feature: u32) -> bool {
+    u32::from_le(sb.feature_incompat) & feature != 0
+}
+
+/// 检查只读兼容特性
+pub fn ext4_sb_feature_ro_com(sb: &Ext4Superblock, feature: u32) -> bool {
+    u32::from_le(sb.feature_ro_compat) & feature != 0
+}
+
+/// 第一个 meta 块组
+pub fn ext4_sb_first_meta_bg(sb: &Ext4Superblock) -> u32 {
+    u32::from_le(sb.first_meta_bg)
+}
+
+/// 块组总数
+pub fn ext4_block_group_cnt(sb: &Ext4Superblock) -> u32 {
+    get_block_group_count(sb)
+}
+
+/// 指定块组中的块数（最后一个块组可能不满）
+pub fn ext4_blocks_in_group_cnt(sb: &Ext4Superblock, bgid: u32) -> u32 {
+    let block_group_count = ext4_block_group_cnt(sb);
     let blocks_per_group = u32::from_le(sb.blocks_per_group);
+    let total_blocks = ext4_sb_get_blocks_cnt(sb);
 
-    (blocks_count + blocks_per_group - 1) / blocks_per_group
+    if bgid < block_group_count - 1 {
+        return blocks_per_group;
+    }
+
+    (total_blocks - u32::from_le(sb.first_data_block) as u64
+        - (block_group_count as u64 - 1) * blocks_per_group as u64) as u32
+}
+
+/// 指定块组中的 inode 数（最后一个块组可能不满）
+pub fn ext4_inodes_in_group_cnt(sb: &Ext4Superblock, bgid: u32) -> u32 {
+    let block_group_count = ext4_block_group_cnt(sb);
+    let inodes_per_group = u32::from_le(sb.inodes_per_group);
+    let total_inodes = u32::from_le(sb.inodes_count);
+
+    if bgid < block_group_count - 1 {
+        return inodes_per_group;
+    }
+
+    total_inodes - (block_group_count - 1) * inodes_per_group
+}
+
+/// 判断 group 是否为 3、5、7 的幂
+fn ext4_sb_sparse(group: u32) -> bool {
+    if group <= 1 {
+        return true;
+    }
+    if group & 1 == 0 {
+        return false;
+    }
+    [3u32, 5, 7].iter().any(|&base| {
+        let mut n = base;
+        while n < group {
+            n = match n.checked_mul(base) {
+                Some(n) => n,
+                None => return false,
+            };
+        }
+        n == group
+    })
+}
+
+/// 判断块组中是否存放 superblock 备份
+pub fn ext4_sb_is_super_in_bg(sb: &Ext4Superblock, group: u32) -> bool {
+    if group == 0 {
+        return true;
+    }
+    if ext4_sb_feature_com(sb, EXT4_FCOM_SPARSE_SUPER2) {
+        return sb.backup_bgs.iter().any(|&bg| u32::from_le(bg) == group);
+    }
+    if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_SPARSE_SUPER) && !ext4_sb_sparse(group) {
+        return false;
+    }
+    true
+}
+
+/// 每个块可容纳的块组描述符数
+pub fn ext4_sb_dsc_per_block(sb: &Ext4Superblock) -> u32 {
+    ext4_sb_get_block_size(sb) / ext4_sb_get_desc_size(sb) as u32
+}
+
+fn ext4_bg_num_gdb_meta(sb: &Ext4Superblock, group: u32) -> u32 {
+    let dsc_per_block = ext4_sb_dsc_per_block(sb);
+    let metagroup = group / dsc_per_block;
+    let first = metagroup * dsc_per_block;
+    let last = first + dsc_per_block - 1;
+
+    if group == first || group == first + 1 || group == last {
+        return 1;
+    }
+    0
+}
+
+fn ext4_bg_num_gdb_nometa(sb: &Ext4Superblock, group: u32) -> u32 {
+    if !ext4_sb_is_super_in_bg(sb, group) {
+        return 0;
+    }
+    if ext4_sb_feature_incom(sb, EXT4_FINCOM_META_BG) {
+        return ext4_sb_first_meta_bg(sb);
+    }
+    ext4_block_group_cnt(sb).div_ceil(ext4_sb_dsc_per_block(sb))
+}
+
+/// 块组中块组描述符表（GDT）占用的块数
+pub fn ext4_bg_num_gdb(sb: &Ext4Superblock, group: u32) -> u32 {
+    let metagroup = group / ext4_sb_dsc_per_block(sb);
+
+    if !ext4_sb_feature_incom(sb, EXT4_FINCOM_META_BG) || metagroup < ext4_sb_first_meta_bg(sb) {
+        return ext4_bg_num_gdb_nometa(sb, group);
+    }
+    ext4_bg_num_gdb_meta(sb, group)
+}
+
+/// 块组开头的基础元数据块数（superblock、GDT、预留GDT）
+pub fn ext4_num_base_meta_clusters(sb: &Ext4Superblock, group: u32) -> u32 {
+    let mut num = ext4_sb_is_super_in_bg(sb, group) as u32;
+
+    if !ext4_sb_feature_incom(sb, EXT4_FINCOM_META_BG)
+        || group < ext4_sb_first_meta_bg(sb) * ext4_sb_dsc_per_block(sb)
+    {
+        if num != 0 {
+            num += ext4_bg_num_gdb(sb, group);
+            num += u16::from_le(sb.s_reserved_gdt_blocks) as u32;
+        }
+    } else {
+        num += ext4_bg_num_gdb(sb, group);
+    }
+    num
+}
+
+/// 计算 superblock 校验和
+pub fn ext4_sb_csum(sb: &Ext4Superblock) -> u32 {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(sb as *const _ as *const u8, core::mem::offset_of!(Ext4Superblock, checksum))
+    };
+    ext4_crc32c(EXT4_CRC32_INIT, bytes)
+}
+
+/// 更新 superblock 校验和
+pub fn ext4_sb_set_csum(sb: &mut Ext4Superblock) {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return;
+    }
+    sb.checksum = ext4_sb_csum(sb).to_le();
+}
+
+/// 校验 superblock 校验和
+pub fn ext4_sb_verify_csum(sb: &Ext4Superblock) -> bool {
+    if !ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+        return true;
+    }
+    u32::from_le(sb.checksum) == ext4_sb_csum(sb)
+}
+
+/// 从块设备读取 superblock
+pub unsafe fn ext4_sb_read(bdev: *mut Ext4BlockDevice, sb: *mut Ext4Superblock) -> i32 {
+    ext4_block_readbytes(bdev, EXT4_SUPERBLOCK_OFFSET, sb as *mut u8, EXT4_SUPERBLOCK_SIZE)
+}
+
+/// 更新校验和并将 superblock 写回块设备
+pub unsafe fn ext4_sb_write(bdev: *mut Ext4BlockDevice, sb: *mut Ext4Superblock) -> i32 {
+    unsafe {
+        ext4_sb_set_csum(&mut *sb);
+        ext4_block_writebytes(bdev, EXT4_SUPERBLOCK_OFFSET, sb as *const u8, EXT4_SUPERBLOCK_SIZE)
+    }
+}
+
+/// superblock 合法性检查
+///
+/// 魔数不对或版本级别不支持时返回 ENOTSUP；几何参数不合法（块大小超出 1K~64K、每组块数/inode数为0
+/// 或超出位图容量、inode 总数与块组数不符、inode 大小或第一个非保留 inode 不合理等）时返回
+/// EFSCORRUPTED，避免挂载后在除法或索引计算中出错；校验和不符时返回 EIO。
+pub fn ext4_sb_check(sb: &Ext4Superblock) -> i32 {
+    if u16::from_le(sb.magic) != EXT4_SUPERBLOCK_MAGIC {
+        ext4_dbg!(DEBUG_SUPER, Warn, "bad superblock magic: {:#x}", u16::from_le(sb.magic));
+        return ENOTSUP;
+    }
+    let rev_level = u32::from_le(sb.rev_level);
+    if rev_level > EXT4_DYNAMIC_REV {
+        ext4_dbg!(DEBUG_SUPER, Warn, "unsupported revision level: {}", rev_level);
+        return ENOTSUP;
+    }
+    let corrupted = |what: &str| {
+        ext4_dbg!(DEBUG_SUPER, Warn, "corrupted superblock: {}", what);
+        EFSCORRUPTED
+    };
+
+    if u32::from_le(sb.log_block_size) > 6 {
+        return corrupted("block size out of range");
+    }
+    let block_size = ext4_sb_get_block_size(sb);
+    let blocks_per_group = u32::from_le(sb.blocks_per_group);
+    let inodes_per_group = u32::from_le(sb.inodes_per_group);
+    let inodes_count = u32::from_le(sb.inodes_count);
+    if inodes_count == 0 || blocks_per_group == 0 || inodes_per_group == 0 {
+        return corrupted("zero inode/group counts");
+    }
+    // 每组的块和inode都由一个块大小的位图描述
+    if blocks_per_group > block_size * 8 || inodes_per_group > block_size * 8 {
+        return corrupted("group larger than its bitmap");
+    }
+
+    let inode_size = get_inode_size(sb) as u32;
+    if inode_size < EXT4_GOOD_OLD_INODE_SIZE as u32
+        || !inode_size.is_power_of_two()
+        || inode_size > block_size
+    {
+        return corrupted("bad inode size");
+    }
+    if inodes_per_group < block_size / inode_size {
+        return corrupted("inodes_per_group smaller than one inode table block");
+    }
+    if rev_level >= EXT4_DYNAMIC_REV {
+        let first_ino = u32::from_le(sb.first_ino);
+        if first_ino < EXT4_GOOD_OLD_FIRST_INO || first_ino > inodes_count {
+            return corrupted("bad first_ino");
+        }
+    }
+    if ext4_sb_feature_incom(sb, EXT4_FINCOM_64BIT) {
+        let desc_size = u16::from_le(sb.desc_size) as u32;
+        if desc_size < 64 || desc_size > block_size || !desc_size.is_power_of_two() {
+            return corrupted("bad group descriptor size");
+        }
+    }
+
+    let blocks_count = ext4_sb_get_blocks_cnt(sb);
+    let first_data_block = u32::from_le(sb.first_data_block) as u64;
+    if first_data_block >= blocks_count || (block_size == 1024 && first_data_block == 0) {
+        return corrupted("bad first_data_block");
+    }
+    let groups = (blocks_count - first_data_block).div_ceil(blocks_per_group as u64);
+    if groups * inodes_per_group as u64 != inodes_count as u64 {
+        return corrupted("inodes_count does not match group count");
+    }
+
+    if !ext4_sb_verify_csum(sb) {
+        ext4_dbg!(DEBUG_SUPER, Warn, "superblock checksum failed");
+        return EIO;
+    }
+    EOK
 }
@@ -0,0 +1,543 @@
+//! 文件系统参数调整模块
+//!
+//! lwext4 中没有对应实现，功能上是 tune2fs 的一个子集：修改卷标、UUID、保留块比例、
+//! 默认挂载选项、出错处理方式、定期检查策略，以及在已挂载的文件系统上启用/关闭 metadata_csum。
+//! 所有修改立即写回 superblock；事务进行中时返回 EBUSY。
+
+use core::ptr;
+
+use crate::balloc::*;
+use crate::bitmap::*;
+use crate::block::*;
+use crate::block_group::*;
+use crate::crc::ext4_sb_csum_seed;
+use crate::dir::*;
+use crate::extent::*;
+use crate::ialloc::*;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4BlockGroupRef, Ext4Filesystem, Ext4InodeRef, Ext4Superblock};
+
+/// 卷标最大长度
+pub const EXT4_LABEL_MAX: usize = 16;
+
+/// 保留块比例上限（与 tune2fs 相同）
+pub const EXT4_RESERVED_PERCENT_MAX: u32 = 50;
+
+/// 检查文件系统当前是否允许修改参数
+unsafe fn ext4_tune_check(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe {
+        if (*fs).read_only {
+            return EROFS;
+        }
+        if (*(*(*fs).bdev).bc).in_trans {
+            return EBUSY;
+        }
+    }
+    EOK
+}
+
+/// 写回修改后的 superblock
+unsafe fn ext4_tune_write_sb(fs: *mut Ext4Filesystem) -> i32 {
+    unsafe { ext4_sb_write((*fs).bdev, &mut (*fs).sb) }
+}
+
+/// 设置卷标（最多16字节，不足部分补0）
+pub unsafe fn ext4_tune_set_label(fs: *mut Ext4Filesystem, label: &[u8]) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        if label.len() > EXT4_LABEL_MAX {
+            return EINVAL;
+        }
+        let sb = &mut (*fs).sb;
+        sb.volume_name = [0; EXT4_LABEL_MAX];
+        sb.volume_name[..label.len()].copy_from_slice(label);
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 设置文件系统 UUID
+///
+/// 元数据校验和的种子由 UUID 计算。启用 metadata_csum 且没有 csum_seed 特性时，
+/// 先把当前种子保存到 superblock 并启用 csum_seed，已有的校验和无需重新计算。
+pub unsafe fn ext4_tune_set_uuid(fs: *mut Ext4Filesystem, uuid: &[u8; 16]) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        let sb = &mut (*fs).sb;
+        if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) && !ext4_sb_feature_incom(sb, EXT4_FINCOM_CSUM_SEED) {
+            sb.checksum_seed = ext4_sb_csum_seed(sb).to_le();
+            sb.feature_incompat = (u32::from_le(sb.feature_incompat) | EXT4_FINCOM_CSUM_SEED).to_le();
+        }
+        sb.uuid = *uuid;
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 按总块数的百分比设置保留块数（0~50）
+pub unsafe fn ext4_tune_set_reserved_percent(fs: *mut Ext4Filesystem, percent: u32) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        if percent > EXT4_RESERVED_PERCENT_MAX {
+            return EINVAL;
+        }
+        let sb = &mut (*fs).sb;
+        let r_blocks = ext4_sb_get_blocks_cnt(sb) * percent as u64 / 100;
+        ext4_sb_set_r_blocks_cnt(sb, r_blocks);
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 设置默认挂载选项（EXT4_DEFM_*，包含未定义的位时返回 EINVAL）
+pub unsafe fn ext4_tune_set_default_mount_opts(fs: *mut Ext4Filesystem, opts: u32) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        if opts & !EXT4_DEFM_ALL != 0 {
+            return EINVAL;
+        }
+        (*fs).sb.default_mount_opts = opts.to_le();
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 设置出错时的处理方式（EXT4_ERRORS_*）
+pub unsafe fn ext4_tune_set_errors(fs: *mut Ext4Filesystem, errors: u16) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        if !(EXT4_ERRORS_CONTINUE..=EXT4_ERRORS_PANIC).contains(&errors) {
+            return EINVAL;
+        }
+        (*fs).sb.errors = errors.to_le();
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 设置挂载次数上限和检查间隔（秒），对应 tune2fs -c / -i
+///
+/// max_mnt_count 不大于0时不按挂载次数建议检查，checkinterval 为0时不按时间建议检查。
+pub unsafe fn ext4_tune_set_check_policy(fs: *mut Ext4Filesystem, max_mnt_count: i16, checkinterval: u32) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        let sb = &mut (*fs).sb;
+        sb.max_mnt_count = (max_mnt_count as u16).to_le();
+        sb.checkinterval = checkinterval.to_le();
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 记录一次通过的检查（与 e2fsck 检查通过后对 superblock 的修改相同）
+///
+/// 挂载次数清零，清除错误标志和挂载时记录的建议 fsck 原因；now（秒）不为 None 时更新最后检查时间。
+pub unsafe fn ext4_tune_mark_checked(fs: *mut Ext4Filesystem, now: Option<u64>) -> i32 {
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        let sb = &mut (*fs).sb;
+        sb.mnt_count = 0;
+        sb.state = (u16::from_le(sb.state) & !EXT4_SUPERBLOCK_STATE_ERROR_FS).to_le();
+        if let Some(now) = now {
+            sb.lastcheck = (now as u32).to_le();
+            sb.lastcheck_hi = (now >> 32) as u8;
+        }
+        (*fs).fsck_reasons = 0;
+        ext4_tune_write_sb(fs)
+    }
+}
+
+// ===== metadata_csum =====
+
+/// 对每个已使用的 inode（按 inode 位图）调用 f
+pub(crate) unsafe fn ext4_tune_for_each_inode(
+    fs: *mut Ext4Filesystem,
+    f: &mut dyn FnMut(*mut Ext4InodeRef) -> i32,
+) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let bg = &*bg_ref.block_group;
+            let uninit = ext4_bg_has_flag(bg, EXT4_BLOCK_GROUP_INODE_UNINIT);
+            let bitmap_blk = ext4_bg_get_inode_bitmap(bg, sb);
+            let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            if uninit {
+                continue;
+            }
+
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, bitmap_blk);
+            if r != EOK {
+                return r;
+            }
+            let bitmap = core::slice::from_raw_parts(b.data, ext4_sb_get_block_size(sb) as usize);
+            let mut r = EOK;
+            for idx in 0..ext4_inodes_in_group_cnt(sb, bgid) {
+                if ext4_bmap_is_bit_clr(bitmap, idx) {
+                    continue;
+                }
+                let mut inode_ref = match InodeRef::get(fs, bgid * inodes_per_group + idx + 1) {
+                    Ok(inode_ref) => inode_ref,
+                    Err(e) => {
+                        r = e;
+                        break;
+                    }
+                };
+                r = f(&mut *inode_ref);
+                let r2 = inode_ref.put();
+                if r == EOK {
+                    r = r2;
+                }
+                if r != EOK {
+                    break;
+                }
+            }
+            let r2 = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// inode 是否带有需要校验和的目录块
+unsafe fn ext4_tune_is_dir(inode_ref: *mut Ext4InodeRef) -> bool {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode = (*inode_ref).inode;
+        ext4_inode_is_type(sb, inode, EXT4_INODE_MODE_DIRECTORY)
+            && !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_INLINE_DATA)
+    }
+}
+
+/// 更新块组描述符及其位图的校验和（sb 为启用 metadata_csum 之后的 superblock）
+///
+/// 未初始化的位图不校验，保持不变。
+unsafe fn ext4_tune_csum_groups(fs: *mut Ext4Filesystem, sb: &Ext4Superblock) -> i32 {
+    unsafe {
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let bg = &mut *bg_ref.block_group;
+            let bitmaps = [
+                (EXT4_BLOCK_GROUP_BLOCK_UNINIT, ext4_bg_get_block_bitmap(bg, sb)),
+                (EXT4_BLOCK_GROUP_INODE_UNINIT, ext4_bg_get_inode_bitmap(bg, sb)),
+            ];
+            let mut r = EOK;
+            for (flag, blk) in bitmaps {
+                if ext4_bg_has_flag(bg, flag) {
+                    continue;
+                }
+                let mut b = Ext4Block::new();
+                r = ext4_block_get((*fs).bdev, &mut b, blk);
+                if r != EOK {
+                    break;
+                }
+                let bitmap = core::slice::from_raw_parts(b.data, ext4_sb_get_block_size(sb) as usize);
+                if flag == EXT4_BLOCK_GROUP_BLOCK_UNINIT {
+                    ext4_balloc_set_bitmap_csum(sb, bg, bitmap);
+                } else {
+                    ext4_ialloc_set_bitmap_csum(sb, bg, bitmap);
+                }
+                r = ext4_block_set((*fs).bdev, &mut b);
+                if r != EOK {
+                    break;
+                }
+            }
+            if r == EOK {
+                // 直接按新的 superblock 计算，释放引用时不再重新计算
+                bg.checksum = ext4_fs_bg_checksum(sb, bgid, bg).to_le();
+                ext4_block_set_dirty(&mut bg_ref.block);
+            }
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+    }
+    EOK
+}
+
+/// 更新 inode 表中全部 inode 的校验和
+unsafe fn ext4_tune_csum_inodes(fs: *mut Ext4Filesystem, had_gdt_csum: bool) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let inodes_per_group = u32::from_le(sb.inodes_per_group);
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let bg = &*bg_ref.block_group;
+            let mut count = ext4_inodes_in_group_cnt(sb, bgid);
+            if ext4_bg_has_flag(bg, EXT4_BLOCK_GROUP_INODE_UNINIT) {
+                count = 0;
+            } else if had_gdt_csum {
+                // inode 表末尾未使用的部分可能从未清零
+                count = count.saturating_sub(ext4_bg_get_itable_unused(bg, sb));
+            }
+            let r = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            for idx in 0..count {
+                let r = ext4_fs_inode_csum_init(fs, bgid * inodes_per_group + idx + 1);
+                if r != EOK {
+                    return r;
+                }
+            }
+        }
+    }
+    EOK
+}
+
+/// 更新 inode 引用的 extent 块、目录块和扩展属性块的校验和
+unsafe fn ext4_tune_csum_inode_blocks(inode_ref: *mut Ext4InodeRef) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let inode = (*inode_ref).inode;
+
+        if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) {
+            let r = ext4_extent_tree_csum_set(inode_ref);
+            if r != EOK {
+                return r;
+            }
+        }
+
+        if ext4_tune_is_dir(inode_ref) {
+            // 尚不支持 htree 索引块的校验和，与添加目录项时一样退化为线性目录
+            if ext4_inode_has_flag(inode, EXT4_INODE_FLAG_INDEX) {
+                ext4_inode_clear_flag(inode, EXT4_INODE_FLAG_INDEX);
+                (*inode_ref).dirty = true;
+            }
+            let r = ext4_dir_csum_init(inode_ref, false);
+            if r != EOK {
+                return r;
+            }
+        }
+
+        let xattr_block = ext4_inode_get_file_acl(inode, sb);
+        if xattr_block != 0 {
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get((*fs).bdev, &mut b, xattr_block);
+            if r != EOK {
+                return r;
+            }
+            if ext4_fs_xattr_block_valid(b.data) {
+                ext4_fs_xattr_block_set_csum(sb, xattr_block, b.data);
+                ext4_block_set_dirty(&mut b);
+            }
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+/// 启用 metadata_csum 并为全部元数据计算校验和
+///
+/// 文件系统需要处于干净状态：没有待回放的日志、没有孤儿 inode、没有记录错误
+/// （否则返回 EBUSY）。每个目录块需要在末尾留出12字节的校验和尾部，有目录块空间
+/// 不足时返回 ENOSPC，此时文件系统不做任何修改。htree 目录会转换为线性目录。
+pub unsafe fn ext4_tune_enable_metadata_csum(fs: *mut Ext4Filesystem) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_FS, "ext4_tune_enable_metadata_csum");
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        let sb = &(*fs).sb;
+        if ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return EOK;
+        }
+        if ext4_sb_feature_incom(sb, EXT4_FINCOM_RECOVER)
+            || u32::from_le(sb.last_orphan) != 0
+            || u16::from_le(sb.state) & EXT4_SUPERBLOCK_STATE_ERROR_FS != 0
+        {
+            ext4_dbg!(DEBUG_FS, Warn, "enable metadata_csum: filesystem is not clean");
+            return EBUSY;
+        }
+
+        // 先检查全部目录，保证后续不会因空间不足中途失败
+        let r = ext4_tune_for_each_inode(fs, &mut |inode_ref| {
+            if ext4_tune_is_dir(inode_ref) {
+                ext4_dir_csum_init(inode_ref, true)
+            } else {
+                EOK
+            }
+        });
+        if r != EOK {
+            return r;
+        }
+
+        let had_gdt_csum = ext4_sb_feature_ro_com(sb, EXT4_FRO_COM_GDT_CSUM);
+        let mut new_sb = (*fs).sb;
+        let ro_compat = u32::from_le(new_sb.feature_ro_compat) & !EXT4_FRO_COM_GDT_CSUM;
+        new_sb.feature_ro_compat = (ro_compat | EXT4_FRO_COM_METADATA_CSUM).to_le();
+        new_sb.checksum_type = EXT4_CRC32C_CHKSUM;
+
+        // 块组描述符在切换之前按新的 superblock 计算，之后读取 inode 时不会校验失败
+        let r = ext4_tune_csum_groups(fs, &new_sb);
+        if r != EOK {
+            return r;
+        }
+        (*fs).sb = new_sb;
+
+        let r = ext4_tune_csum_inodes(fs, had_gdt_csum);
+        if r != EOK {
+            return r;
+        }
+        let r = ext4_tune_for_each_inode(fs, &mut |inode_ref| ext4_tune_csum_inode_blocks(inode_ref));
+        if r != EOK {
+            return r;
+        }
+
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+        ext4_dbg!(DEBUG_FS, Info, "metadata_csum enabled");
+        ext4_tune_write_sb(fs)
+    }
+}
+
+/// 清零块组的 inode 表
+unsafe fn ext4_tune_zero_itable(fs: *mut Ext4Filesystem, bg_ref: *mut Ext4BlockGroupRef) -> i32 {
+    unsafe {
+        let sb = &(*fs).sb;
+        let block_size = ext4_sb_get_block_size(sb);
+        let first = ext4_bg_get_inode_table_first_block(&*(*bg_ref).block_group, sb);
+        let count = (u32::from_le(sb.inodes_per_group) * get_inode_size(sb) as u32).div_ceil(block_size);
+        for blk in first..first + count as u64 {
+            let mut b = Ext4Block::new();
+            let r = ext4_block_get_noread((*fs).bdev, &mut b, blk);
+            if r != EOK {
+                return r;
+            }
+            ptr::write_bytes(b.data, 0, block_size as usize);
+            ext4_block_set_dirty(&mut b);
+            let r = ext4_block_set((*fs).bdev, &mut b);
+            if r != EOK {
+                return r;
+            }
+        }
+    }
+    EOK
+}
+
+/// 关闭 metadata_csum
+///
+/// 没有块组描述符校验和时未初始化的块组标志不再可信，关闭前先初始化这些块组的
+/// 位图（必要时清零 inode 表）；htree 目录转换为线性目录。已有的校验和尾部保留在原处，
+/// 对不校验的文件系统无影响。
+pub unsafe fn ext4_tune_disable_metadata_csum(fs: *mut Ext4Filesystem) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_FS, "ext4_tune_disable_metadata_csum");
+    unsafe {
+        let r = ext4_tune_check(fs);
+        if r != EOK {
+            return r;
+        }
+        if !ext4_sb_feature_ro_com(&(*fs).sb, EXT4_FRO_COM_METADATA_CSUM) {
+            return EOK;
+        }
+
+        // htree 根节点的条目上限包含校验和尾部的空间，关闭后不再合法，转换为线性目录
+        let r = ext4_tune_for_each_inode(fs, &mut |inode_ref| {
+            if ext4_tune_is_dir(inode_ref) && ext4_inode_has_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX) {
+                ext4_inode_clear_flag((*inode_ref).inode, EXT4_INODE_FLAG_INDEX);
+                (*inode_ref).dirty = true;
+            }
+            EOK
+        });
+        if r != EOK {
+            return r;
+        }
+
+        let sb = &mut (*fs).sb;
+        sb.feature_ro_compat = (u32::from_le(sb.feature_ro_compat) & !EXT4_FRO_COM_METADATA_CSUM).to_le();
+        sb.feature_incompat = (u32::from_le(sb.feature_incompat) & !EXT4_FINCOM_CSUM_SEED).to_le();
+        sb.checksum_type = 0;
+        sb.checksum_seed = 0;
+        sb.checksum = 0;
+
+        for bgid in 0..ext4_block_group_cnt(sb) {
+            let mut bg_ref = Ext4BlockGroupRef::new();
+            let r = ext4_fs_get_block_group_ref(fs, bgid, &mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            let mut r = EOK;
+            if ext4_bg_has_flag(&*bg_ref.block_group, EXT4_BLOCK_GROUP_BLOCK_UNINIT) {
+                r = ext4_fs_init_block_bitmap(&mut bg_ref);
+                ext4_bg_clear_flag(&mut *bg_ref.block_group, EXT4_BLOCK_GROUP_BLOCK_UNINIT);
+            }
+            if r == EOK && ext4_bg_has_flag(&*bg_ref.block_group, EXT4_BLOCK_GROUP_INODE_UNINIT) {
+                if !ext4_bg_has_flag(&*bg_ref.block_group, EXT4_BLOCK_GROUP_ITABLE_ZEROED) {
+                    r = ext4_tune_zero_itable(fs, &mut bg_ref);
+                    ext4_bg_set_flag(&mut *bg_ref.block_group, EXT4_BLOCK_GROUP_ITABLE_ZEROED);
+                }
+                if r == EOK {
+                    r = ext4_fs_init_inode_bitmap(&mut bg_ref);
+                }
+                ext4_bg_clear_flag(&mut *bg_ref.block_group, EXT4_BLOCK_GROUP_INODE_UNINIT);
+            }
+            ext4_bg_set_itable_unused(&mut *bg_ref.block_group, sb, 0);
+            bg_ref.dirty = true;
+            let r2 = ext4_fs_put_block_group_ref(&mut bg_ref);
+            if r != EOK {
+                return r;
+            }
+            if r2 != EOK {
+                return r2;
+            }
+        }
+
+        let r = ext4_block_cache_flush((*fs).bdev);
+        if r != EOK {
+            return r;
+        }
+        ext4_dbg!(DEBUG_FS, Info, "metadata_csum disabled");
+        ext4_tune_write_sb(fs)
+    }
+}
@@ -9,12 +9,12 @@
 #![allow(non_camel_case_types)]
 
 use core::ptr;
-use alloc::vec::Vec;
 use crate::consts::*;
 
-/// Superblock 结构
+/// Superblock 结构（磁盘布局，共1024字节）
 ///
 /// 对应C定义: struct ext4_sblock (ext4_types.h)
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ext4_sblock {
     pub inodes_count: u32,           // 0: 总 inode 数
@@ -43,26 +43,95 @@ pub struct ext4_sblock {
     pub def_resuid: u16,             // 80: 默认保留 uid
     pub def_resgid: u16,             // 82: 默认保留 gid
 
-    // 扩展字段
+    // 扩展字段（rev_level >= 1）
     pub first_ino: u32,              // 84: 第一个非保留 inode
     pub inode_size: u16,             // 88: inode 大小
     pub block_group_nr: u16,         // 90: 本超级块所在的块组号
     pub feature_compat: u32,         // 92: 兼容特性
     pub feature_incompat: u32,       // 96: 不兼容特性
     pub feature_ro_compat: u32,      // 100: 只读兼容特性
-
-    // 更多字段（简化版 - 只保留关键字段）
     pub uuid: [u8; 16],              // 104: 128位UUID
     pub volume_name: [u8; 16],       // 120: 卷名称
     pub last_mounted: [u8; 64],      // 136: 最后挂载路径
-    pub blocks_count_hi: u32,        // 200: 总块数（高32位）
-    pub r_blocks_count_hi: u32,      // 204: 保留块数（高32位）
-    pub free_blocks_count_hi: u32,   // 208: 空闲块数（高32位）
-
-    // 填充到 1024 字节
-    pub reserved: [u8; 812],         // 212-1023: 保留
+    pub algorithm_usage_bitmap: u32, // 200: 压缩算法
+
+    // 性能提示
+    pub s_prealloc_blocks: u8,       // 204: 文件预分配块数
+    pub s_prealloc_dir_blocks: u8,   // 205: 目录预分配块数
+    pub s_reserved_gdt_blocks: u16,  // 206: 在线扩容预留的GDT块数
+
+    // 日志相关
+    pub journal_uuid: [u8; 16],      // 208: 日志超级块UUID
+    pub journal_inode_number: u32,   // 224: 日志文件inode
+    pub journal_dev: u32,            // 228: 日志设备号
+    pub last_orphan: u32,            // 232: 孤儿inode链表头
+    pub hash_seed: [u32; 4],         // 236: htree哈希种子
+    pub default_hash_version: u8,    // 252: 默认哈希版本
+    pub journal_backup_type: u8,     // 253: 日志备份类型
+    pub desc_size: u16,              // 254: 块组描述符大小
+    pub default_mount_opts: u32,     // 256: 默认挂载选项
+    pub first_meta_bg: u32,          // 260: 第一个meta块组
+    pub mkfs_time: u32,              // 264: 创建时间
+    pub journal_blocks: [u32; 17],   // 268: 日志inode的块备份
+
+    // 64bit 支持
+    pub blocks_count_hi: u32,        // 336: 总块数（高32位）
+    pub r_blocks_count_hi: u32,      // 340: 保留块数（高32位）
+    pub free_blocks_count_hi: u32,   // 344: 空闲块数（高32位）
+    pub min_extra_isize: u16,        // 348: 所有inode至少具有的额外大小
+    pub want_extra_isize: u16,       // 350: 新inode应预留的额外大小
+    pub flags: u32,                  // 352: 杂项标志
+    pub raid_stride: u16,            // 354: RAID步长
+    pub mmp_interval: u16,           // 356: MMP检查间隔（秒）
+    pub mmp_block: u64,              // 360: MMP块号
+    pub raid_stripe_width: u32,      // 368: RAID条带宽度
+    pub log_groups_per_flex: u8,     // 372: flex_bg大小（2的幂）
+    pub checksum_type: u8,           // 373: 元数据校验算法（1 = crc32c）
+    pub encryption_level: u8,        // 374: 加密版本
+    pub reserved_pad: u8,            // 375: 填充
+    pub kbytes_written: u64,         // 376: 生命周期内写入的KB数
+    pub snapshot_inum: u32,          // 384: 活动快照inode
+    pub snapshot_id: u32,            // 388: 活动快照ID
+    pub snapshot_r_blocks_count: u64, // 392: 快照预留块数
+    pub snapshot_list: u32,          // 400: 快照链表头
+    pub error_count: u32,            // 404: 错误次数
+    pub first_error_time: u32,       // 408: 第一次错误时间
+    pub first_error_ino: u32,        // 412: 第一次错误相关inode
+    pub first_error_block: u64,      // 416: 第一次错误相关块
+    pub first_error_func: [u8; 32],  // 424: 第一次错误所在函数
+    pub first_error_line: u32,       // 456: 第一次错误所在行
+    pub last_error_time: u32,        // 460: 最近错误时间
+    pub last_error_ino: u32,         // 464: 最近错误相关inode
+    pub last_error_line: u32,        // 468: 最近错误所在行
+    pub last_error_block: u64,       // 472: 最近错误相关块
+    pub last_error_func: [u8; 32],   // 480: 最近错误所在函数
+    pub mount_opts: [u8; 64],        // 512: 挂载选项字符串
+    pub usr_quota_inum: u32,         // 576: 用户配额inode
+    pub grp_quota_inum: u32,         // 580: 组配额inode
+    pub overhead_clusters: u32,      // 584: 元数据开销（簇）
+    pub backup_bgs: [u32; 2],        // 588: sparse_super2 备份块组
+    pub encrypt_algos: [u8; 4],      // 596: 加密算法
+    pub encrypt_pw_salt: [u8; 16],   // 600: 加密盐
+    pub lpf_ino: u32,                // 616: lost+found inode
+    pub prj_quota_inum: u32,         // 620: 项目配额inode
+    pub checksum_seed: u32,          // 624: crc32c(uuid)（csum_seed特性）
+    pub wtime_hi: u8,                // 628
+    pub mtime_hi: u8,                // 629
+    pub mkfs_time_hi: u8,            // 630
+    pub lastcheck_hi: u8,            // 631
+    pub first_error_time_hi: u8,     // 632
+    pub last_error_time_hi: u8,      // 633
+    pub first_error_errcode: u8,     // 634
+    pub last_error_errcode: u8,      // 635
+    pub encoding: u16,               // 636: 文件名编码
+    pub encoding_flags: u16,         // 638: 文件名编码标志
+    pub orphan_file_inum: u32,       // 640: orphan_file inode
+    pub padding: [u32; 94],          // 644: 填充
+    pub checksum: u32,               // 1020: superblock 校验和
 }
 
+const _: () = assert!(core::mem::size_of::<ext4_sblock>() == EXT4_SUPERBLOCK_SIZE);
+
 impl Default for ext4_sblock {
     fn default() -> Self {
         unsafe { core::mem::zeroed() }
@@ -72,6 +141,7 @@ impl Default for ext4_sblock {
 /// Inode 结构
 ///
 /// 对应C定义: struct ext4_inode (ext4_types.h:373-419)
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ext4_inode {
     pub mode: u16,                   // 0: 文件模式
@@ -109,6 +179,7 @@ pub struct ext4_inode {
     pub crtime: u32,                 // 144: 创建时间
     pub crtime_extra: u32,           // 148: 额外创建时间
     pub version_hi: u32,             // 152: 版本高32位
+    pub projid: u32,                 // 156: 项目ID
 }
 
 impl Default for ext4_inode {
@@ -120,9 +191,13 @@ impl Default for ext4_inode {
 /// Inode 引用
 ///
 /// 对应C定义: struct ext4_inode_ref (ext4_fs.h)
+///
+/// extent、目录、xattr 等模块统一通过它访问 inode。它不借用 ext4_fs，多个引用可以同时存在，
+/// 用完后由 ext4_fs_put_inode_ref 归还；持有所有权的封装见 inode::InodeRef。
 pub struct ext4_inode_ref {
+    pub block: ext4_block,           // inode 所在的 inode 表块（位于块缓存中）
     pub index: u32,                  // inode 编号
-    pub inode: *mut ext4_inode,      // inode 指针
+    pub inode: *mut ext4_inode,      // inode 指针（指向 block 的数据）
     pub fs: *mut ext4_fs,            // 文件系统指针
     pub dirty: bool,                 // 是否已修改
     pub block_group: u32,            // 所属块组
@@ -131,6 +206,7 @@ pub struct ext4_inode_ref {
 impl ext4_inode_ref {
     pub fn new() -> Self {
         Self {
+            block: ext4_block::new(),
             index: 0,
             inode: ptr::null_mut(),
             fs: ptr::null_mut(),
@@ -140,11 +216,189 @@ impl ext4_inode_ref {
     }
 }
 
+/// 块组描述符（磁盘布局，32字节或64字节）
+///
+/// 对应C定义: struct ext4_bgroup (ext4_types.h)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_bgroup {
+    pub block_bitmap_lo: u32,        // 0: 块位图块号（低32位）
+    pub inode_bitmap_lo: u32,        // 4: inode位图块号（低32位）
+    pub inode_table_first_block_lo: u32, // 8: inode表起始块号（低32位）
+    pub free_blocks_count_lo: u16,   // 12: 空闲块数（低16位）
+    pub free_inodes_count_lo: u16,   // 14: 空闲inode数（低16位）
+    pub used_dirs_count_lo: u16,     // 16: 目录数（低16位）
+    pub flags: u16,                  // 18: 块组标志（EXT4_BLOCK_GROUP_*）
+    pub exclude_bitmap_lo: u32,      // 20: 快照排除位图（低32位）
+    pub block_bitmap_csum_lo: u16,   // 24: 块位图校验和（低16位）
+    pub inode_bitmap_csum_lo: u16,   // 26: inode位图校验和（低16位）
+    pub itable_unused_lo: u16,       // 28: 未使用的inode数（低16位）
+    pub checksum: u16,               // 30: 描述符校验和
+
+    // 64bit 特性下的扩展字段
+    pub block_bitmap_hi: u32,        // 32
+    pub inode_bitmap_hi: u32,        // 36
+    pub inode_table_first_block_hi: u32, // 40
+    pub free_blocks_count_hi: u16,   // 44
+    pub free_inodes_count_hi: u16,   // 46
+    pub used_dirs_count_hi: u16,     // 48
+    pub itable_unused_hi: u16,       // 50
+    pub exclude_bitmap_hi: u32,      // 52
+    pub block_bitmap_csum_hi: u16,   // 56
+    pub inode_bitmap_csum_hi: u16,   // 58
+    pub reserved: u32,               // 60
+}
+
+/// 块组引用
+///
+/// 对应C定义: struct ext4_block_group_ref (ext4_fs.h)
+pub struct ext4_block_group_ref {
+    pub block: ext4_block,           // 描述符所在的GDT块
+    pub block_group: *mut ext4_bgroup, // 描述符指针（指向 block 的数据）
+    pub fs: *mut ext4_fs,            // 文件系统指针
+    pub index: u32,                  // 块组编号
+    pub dirty: bool,                 // 是否已修改
+}
+
+impl ext4_block_group_ref {
+    pub fn new() -> Self {
+        Self {
+            block: ext4_block::new(),
+            block_group: ptr::null_mut(),
+            fs: ptr::null_mut(),
+            index: 0,
+            dirty: false,
+        }
+    }
+}
+
+/// Extent 树节点头
+///
+/// 对应C定义: struct ext4_extent_header (ext4_types.h)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_extent_header {
+    pub magic: u16,                  // 魔数 0xF30A
+    pub entries_count: u16,          // 有效条目数
+    pub max_entries_count: u16,      // 最大条目数
+    pub depth: u16,                  // 树深度（0 表示叶子）
+    pub generation: u32,             // 树版本
+}
+
+/// Extent 树叶子条目
+///
+/// 对应C定义: struct ext4_extent (ext4_types.h)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_extent {
+    pub first_block: u32,            // 起始逻辑块
+    pub block_count: u16,            // 块数（> 32768 表示未写入）
+    pub start_hi: u16,               // 起始物理块（高16位）
+    pub start_lo: u32,               // 起始物理块（低32位）
+}
+
+/// Extent 树索引条目
+///
+/// 对应C定义: struct ext4_extent_index (ext4_types.h)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_extent_index {
+    pub first_block: u32,            // 覆盖的起始逻辑块
+    pub leaf_lo: u32,                // 下一级节点物理块（低32位）
+    pub leaf_hi: u16,                // 下一级节点物理块（高16位）
+    pub padding: u16,
+}
+
+/// Extent 块尾部校验和
+///
+/// 对应C定义: struct ext4_extent_tail (ext4_types.h)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_extent_tail {
+    pub et_checksum: u32,
+}
+
 /// 块设备接口（trait，由调用者实现）
+///
+/// lwext4_arce 重新导出同一个 trait，设备只需实现一次，即可同时用于 lwext4_arce 的文件系统
+/// 和本 crate 中以块设备为参数的接口（如 read_superblock）。
 pub trait BlockDevice {
-    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> crate::Ext4Result<usize>;
+    /// 从 block_id 开始写入 buf 中的块（buf 的长度是物理块大小的整数倍）
+    ///
+    /// 读写失败时返回带 errno 的错误（如 `Ext4Error::new(EIO, ..)`）；
+    /// 写入返回 EROFS 表示设备只读，文件系统随之转为只读。
     fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> crate::Ext4Result<usize>;
+
+    /// 从 block_id 开始读取块到 buf 中
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> crate::Ext4Result<usize>;
+
+    /// 设备的总块数（以物理块为单位）
     fn num_blocks(&self) -> crate::Ext4Result<u64>;
+
+    /// 设备的物理块（扇区）大小，必须是2的幂
+    ///
+    /// read_blocks/write_blocks 的块号和 num_blocks 都以此为单位。与文件系统块大小无关：
+    /// 文件系统块小于物理块时（如4Kn设备上1KiB块的文件系统）按物理块先读后写。
+    fn block_size(&self) -> usize {
+        EXT4_DEV_BSIZE
+    }
+
+    /// read_blocks/write_blocks 缓冲区的内存对齐要求（字节），必须是2的幂
+    ///
+    /// 需要DMA对齐的设备（如virtio、SD卡控制器）声明后，块缓存和物理块缓冲区都按此对齐分配，
+    /// 其余不对齐的缓冲区（如mkfs和日志的临时缓冲区、按字节读写的调用方缓冲区）由文件系统
+    /// 经对齐的缓冲区中转，驱动无需再自行准备中转缓冲区。
+    fn alignment(&self) -> usize {
+        1
+    }
+}
+
+/// 允许以可变引用作为块设备（文件系统卸载后仍可检查设备内容）
+impl<T: BlockDevice + ?Sized> BlockDevice for &mut T {
+    fn write_blocks(&mut self, block_id: u64, buf: &[u8]) -> crate::Ext4Result<usize> {
+        (**self).write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&mut self, block_id: u64, buf: &mut [u8]) -> crate::Ext4Result<usize> {
+        (**self).read_blocks(block_id, buf)
+    }
+
+    fn num_blocks(&self) -> crate::Ext4Result<u64> {
+        (**self).num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        (**self).block_size()
+    }
+
+    fn alignment(&self) -> usize {
+        (**self).alignment()
+    }
+}
+
+/// 挂载参数（lwext4 中没有对应结构，对应的是编译期的 CONFIG_* 常量）
+///
+/// 由 ext4_mount_with_config 或 ext4_fs_set_config 应用到文件系统。
+///
+/// 没有 inode 缓存、目录项缓存和预读的参数：inode 表和目录块都经由块缓存读取
+/// （由 bcache_size 控制），文件数据按连续物理块直接读写、不经过缓存，
+/// 这三种缓存在本实现中都不存在，也就没有可以调整的大小。
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ext4_mount_config {
+    pub bcache_size: u32,            // 块缓存的块数（块设备未绑定缓存时创建的动态缓存）
+    pub es_cache_size: usize,        // extent 状态缓存的最大映射条目数（0表示不缓存）
+    pub max_dirty_blocks: u32,       // 回写模式下脏块达到该数量时回写其中一半（0表示不限制）
+}
+
+impl Default for ext4_mount_config {
+    fn default() -> Self {
+        Self {
+            bcache_size: CONFIG_BLOCK_DEV_CACHE_SIZE,
+            es_cache_size: CONFIG_EXTENT_STATUS_CACHE_SIZE,
+            max_dirty_blocks: 0,
+        }
+    }
 }
 
 /// 文件系统结构
@@ -152,6 +406,8 @@ pub trait BlockDevice {
 /// 对应C定义: struct ext4_fs (ext4_fs.h:56-70)
 pub struct ext4_fs {
     pub read_only: bool,             // 只读模式
+    pub force_features: bool,        // 忽略不支持的特性强制挂载（在 ext4_fs_init 之前设置）
+    pub no_kbytes_written: bool,     // 不累计设备写入量、不更新 s_kbytes_written（生成可重现的镜像时设置）
     pub bdev: *mut ext4_blockdev,    // 块设备指针
     pub sb: ext4_sblock,             // Superblock
     pub inode_block_limits: [u64; 4], // inode 块限制
@@ -161,12 +417,45 @@ pub struct ext4_fs {
     pub inodes_per_group: u32,       // 每组 inode 数
     pub blocks_per_group: u32,       // 每组块数
     pub block_group_count: u32,      // 块组总数
+    pub last_inode_bg_id: u32,       // 上次分配inode的块组
+    pub sb_dirty: bool,              // superblock 中的计数已在内存中修改、尚未写回（见 ext4_fs_commit_sb）
+    pub bytes_written: u64,          // 挂载以来写入设备、尚未计入 s_kbytes_written 的字节数
+    pub fsck_reasons: u32,           // 挂载时建议运行 fsck 的原因（EXT4_FSCK_*，见 ext4_fs_fsck_reasons）
+    pub yield_hook: Option<fn()>,    // 长时间操作中让出 CPU 的钩子（见 ext4_fs_yield_point）
+    pub yield_interval: u32,         // 每经过多少个让出点调用一次 yield_hook
+    pub yield_ticks: u32,            // 上次调用 yield_hook 之后经过的让出点数
+    pub bg_poisoned: bool,           // 块组描述符写回失败，此后拒绝修改块组描述符（重新挂载后恢复）
+    pub metrics: ext4_metrics,       // 运行统计
+    pub es_cache: crate::extent_status::ext4_es_cache, // extent 状态缓存
+    pub prealloc: alloc::collections::BTreeMap<u32, u32>, // inode 编号 -> 追加写入时额外预分配的块数
+    pub alloc_goal: alloc::collections::BTreeMap<u32, u64>, // inode 编号 -> 没有数据块可参照时的分配目标块
+    pub quota: Option<crate::quota::ext4_quota_usage>, // 按 uid/gid 的用量统计（见 ext4_quota_enable）
+}
+
+/// 文件系统运行统计（lwext4 中没有对应结构）
+///
+/// 设备层的原始读写次数见 ext4_blockdev_iface 的 bread_ctr / bwrite_ctr。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ext4_metrics {
+    pub cache_hits: u64,        // ext4_block_get 命中缓存的次数
+    pub cache_misses: u64,      // ext4_block_get 需要从设备读取的次数
+    pub blocks_allocated: u64,  // 分配的数据/元数据块数
+    pub blocks_freed: u64,      // 释放的块数
+    pub journal_commits: u64,   // 日志事务提交次数
+    pub dirty_flushes: u64,     // 写回设备的脏缓冲区数
+    pub es_hits: u64,           // extent 映射命中状态缓存的次数
+    pub es_misses: u64,         // extent 映射需要遍历 extent 树的次数
+    pub alloc_goal_hits: u64,   // 块分配正好落在目标块上的次数
+    pub alloc_goal_group_hits: u64, // 块分配落在目标块组内其他位置的次数
+    pub alloc_goal_misses: u64, // 目标块组已满、分配到其他块组的次数
 }
 
 impl ext4_fs {
     pub fn new() -> Self {
         Self {
             read_only: false,
+            force_features: false,
+            no_kbytes_written: false,
             bdev: ptr::null_mut(),
             sb: ext4_sblock::default(),
             inode_block_limits: [0; 4],
@@ -176,6 +465,19 @@ impl ext4_fs {
             inodes_per_group: 0,
             blocks_per_group: 0,
             block_group_count: 0,
+            last_inode_bg_id: 0,
+            sb_dirty: false,
+            bytes_written: 0,
+            fsck_reasons: 0,
+            yield_hook: None,
+            yield_interval: 0,
+            yield_ticks: 0,
+            bg_poisoned: false,
+            metrics: ext4_metrics::default(),
+            es_cache: crate::extent_status::ext4_es_cache::new(),
+            prealloc: alloc::collections::BTreeMap::new(),
+            alloc_goal: alloc::collections::BTreeMap::new(),
+            quota: None,
         }
     }
 }
@@ -190,7 +492,7 @@ pub struct ext4_buf {
     pub lru_prio: u32,               // LRU优先级
     pub lru_id: u32,                 // LRU ID
     pub refctr: u32,                 // 引用计数
-    pub bc: *mut u8,                 // 块缓存指针
+    pub bc: *mut ext4_bcache,        // 块缓存指针
     pub on_dirty_list: bool,         // 是否在脏列表中
 }
 
@@ -216,6 +518,7 @@ impl ext4_block {
 /// 块设备接口结构
 ///
 /// 对应C定义: struct ext4_blockdev_iface (ext4_blockdev.h:49-103)
+#[repr(C)]
 pub struct ext4_blockdev_iface {
     // 函数指针（使用Option包装，允许为空）
     pub open: Option<unsafe extern "C" fn(*mut ext4_blockdev) -> i32>,
@@ -228,11 +531,14 @@ pub struct ext4_blockdev_iface {
     // 数据字段
     pub ph_bsize: u32,               // 物理块大小
     pub ph_bcnt: u64,                // 物理块数量
-    pub ph_bbuf: *mut u8,            // 物理块缓冲区
+    pub ph_bbuf: *mut u8,            // 物理块缓冲区（应满足ph_align）
     pub ph_refctr: u32,              // 引用计数
     pub bread_ctr: u32,              // 读计数
     pub bwrite_ctr: u32,             // 写计数
     pub p_user: *mut core::ffi::c_void,  // 用户数据指针
+
+    // 以下为 lwext4 之后追加的字段，放在末尾以保持前面字段的布局与 lwext4 一致
+    pub ph_align: u32,               // 读写缓冲区的内存对齐要求（0或1表示不要求）
 }
 
 impl ext4_blockdev_iface {
@@ -251,6 +557,7 @@ impl ext4_blockdev_iface {
             bread_ctr: 0,
             bwrite_ctr: 0,
             p_user: ptr::null_mut(),
+            ph_align: 0,
         }
     }
 }
@@ -265,8 +572,17 @@ pub struct ext4_bcache {
     pub lru_ctr: u32,                // 最近使用计数器
     pub ref_blocks: u32,             // 当前引用的数据块
     pub max_ref_blocks: u32,         // 最大引用的数据块
+    pub pinned_blocks: u32,          // 常驻缓存的块数
+    pub pin_limit: u32,              // 常驻缓存的块数上限
     pub bdev: *mut ext4_blockdev,   // 绑定到此块缓存的块设备
-    // 其他字段暂时省略（如dirty_list等）
+    pub dont_shake: bool,            // 正在回收缓存（防止重入）
+    pub ordered: bool,               // 有序数据模式：数据写完之前不回写元数据
+    pub data_writes: u32,            // 正在进行的数据写入（嵌套计数）
+    pub in_trans: bool,              // 事务进行中：脏块在提交前不写回
+    pub undo_depth: u32,             // 撤销日志嵌套深度（非零时记录缓冲区的原始内容）
+    pub max_dirty: u32,              // 脏块列表达到该长度时回写到一半（0表示不限制）
+    pub align: u32,                  // 缓冲区的内存对齐（0表示默认的8字节）
+    pub lists: *mut crate::bcache::ext4_bcache_lists, // LBA索引、LRU和脏块列表
 }
 
 impl ext4_bcache {
@@ -277,7 +593,17 @@ impl ext4_bcache {
             lru_ctr: 0,
             ref_blocks: 0,
             max_ref_blocks: 0,
+            pinned_blocks: 0,
+            pin_limit: 0,
             bdev: ptr::null_mut(),
+            dont_shake: false,
+            ordered: false,
+            data_writes: 0,
+            in_trans: false,
+            undo_depth: 0,
+            max_dirty: 0,
+            align: 0,
+            lists: ptr::null_mut(),
         }
     }
 }
@@ -285,6 +611,7 @@ impl ext4_bcache {
 /// 块设备结构
 ///
 /// 对应C定义: struct ext4_blockdev (ext4_blockdev.h:106-132)
+#[repr(C)]
 pub struct ext4_blockdev {
     pub bdif: *mut ext4_blockdev_iface,  // 块设备接口
     pub part_offset: u64,            // 分区偏移（多分区模式）
@@ -322,6 +649,7 @@ impl ext4_blockdev {
 /// 对应C定义: union ext4_dir_en_internal (ext4_types.h)
 /// C中是union，两个字段占用同一个字节
 /// Rust实现：用一个字节+访问方法
+#[repr(transparent)]
 pub struct ext4_dir_en_internal {
     /// 这个字节的两种解释：
     /// - 旧版本ext4: 存储name_length_high
@@ -355,36 +683,30 @@ impl ext4_dir_en_internal {
     }
 }
 
-/// 目录项结构
+/// 目录项结构（磁盘布局）
 ///
 /// 对应C定义: struct ext4_dir_en (ext4_types.h:825-833)
-/// C中的柔性数组成员name[]在Rust中用Vec<u8>实现
+/// C中的柔性数组成员name[]在Rust中用零长度数组表示，名称紧跟在结构体之后，
+/// 因此该结构只能通过指向目录块数据的指针访问。
+#[repr(C)]
 pub struct ext4_dir_en {
     pub inode: u32,                  // inode 编号
     pub entry_len: u16,              // 记录长度（C字段名）
     pub name_len: u8,                // 名称长度（C字段名）
     pub in_: ext4_dir_en_internal,   // union字段（C字段名）
-    name_data: Vec<u8>,              // 目录项名称（对应C的柔性数组name[]）
+    name: [u8; 0],                   // 目录项名称（对应C的柔性数组name[]）
 }
 
 impl ext4_dir_en {
-    /// 创建新的目录项
-    pub fn new(inode: u32, name: &[u8], inode_type: u8) -> Self {
-        let mut in_ = ext4_dir_en_internal::new();
-        in_.set_inode_type(inode_type);
-
-        Self {
-            inode,
-            entry_len: 0,  // 稍后计算
-            name_len: name.len() as u8,
-            in_,
-            name_data: name.to_vec(),
-        }
-    }
-
     /// 获取名称
     pub fn name(&self) -> &[u8] {
-        &self.name_data
+        // SAFETY: 目录项总是位于目录块内，名称紧随其后
+        unsafe { core::slice::from_raw_parts(self.name.as_ptr(), self.name_len as usize) }
+    }
+
+    /// 获取名称的可写指针
+    pub fn name_mut_ptr(&mut self) -> *mut u8 {
+        self.name.as_mut_ptr()
     }
 
     /// 获取完整名称长度（处理旧版本的高8位）
@@ -402,6 +724,21 @@ impl ext4_dir_en {
     }
 }
 
+/// 目录项头部大小（不含名称）
+pub const EXT4_DIR_ENTRY_HEADER_SIZE: usize = 8;
+
+/// 目录块尾部的校验和伪目录项
+///
+/// 对应C定义: struct ext4_dir_entry_tail (ext4_types.h)
+#[repr(C)]
+pub struct ext4_dir_entry_tail {
+    pub reserved_zero1: u32,         // 伪inode（0）
+    pub rec_len: u16,                // 12
+    pub reserved_zero2: u8,          // 伪名称长度（0）
+    pub reserved_ft: u8,             // 0xDE
+    pub checksum: u32,               // 目录块校验和
+}
+
 /// 目录迭代器
 ///
 /// 对应C定义: struct ext4_dir_iter (ext4_dir.h:57-62)
@@ -410,6 +747,7 @@ pub struct ext4_dir_iter {
     pub curr_blk: ext4_block,        // 当前块
     pub curr_off: u64,               // 当前偏移量（C字段名）
     pub curr: *mut ext4_dir_en,      // 当前目录项指针
+    pub hash: *mut crate::dir::ext4_dir_hash_batch, // 按哈希位置迭代的状态（线性迭代时为空）
 }
 
 impl ext4_dir_iter {
@@ -419,6 +757,7 @@ impl ext4_dir_iter {
             curr_blk: ext4_block::new(),
             curr_off: 0,
             curr: ptr::null_mut(),
+            hash: ptr::null_mut(),
         }
     }
 }
@@ -429,6 +768,7 @@ impl ext4_dir_iter {
 pub struct ext4_dir_search_result {
     pub block: ext4_block,          // 块
     pub dentry: *mut ext4_dir_en,   // 目录项指针
+    pub dentry_ino: u32,            // 查找时目录项的inode编号（用于检测调用者的修改）
 }
 
 impl ext4_dir_search_result {
@@ -436,10 +776,34 @@ impl ext4_dir_search_result {
         Self {
             block: ext4_block::new(),
             dentry: ptr::null_mut(),
+            dentry_ino: 0,
         }
     }
 }
 
+/// 格式化参数
+///
+/// 对应C定义: struct ext4_mkfs_info (ext4_mkfs.h)。
+/// 为0的字段由 ext4_mkfs 按设备大小选择默认值，并写回实际使用的值。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ext4_mkfs_info {
+    pub len: u64,                    // 文件系统大小（字节，0 表示整个设备）
+    pub block_size: u32,             // 块大小
+    pub blocks_per_group: u32,       // 每组块数
+    pub inodes_per_group: u32,       // 每组 inode 数
+    pub inode_size: u16,             // inode 大小
+    pub inodes: u32,                 // 总 inode 数
+    pub journal_blocks: u32,         // 日志块数
+    pub feat_ro_compat: u32,         // 只读兼容特性
+    pub feat_compat: u32,            // 兼容特性
+    pub feat_incompat: u32,          // 不兼容特性
+    pub dsc_size: u16,               // 块组描述符大小
+    pub uuid: [u8; 16],              // 文件系统 UUID
+    pub hash_seed: [u8; 16],         // htree 哈希种子
+    pub journal: bool,               // 是否创建日志
+    pub label: [u8; 16],             // 卷标
+}
+
 // ===== Type Aliases =====
 // 提供Rust风格的别名，方便使用
 
@@ -455,6 +819,12 @@ pub type Ext4InodeRef = ext4_inode_ref;
 /// Rust风格别名：文件系统
 pub type Ext4Filesystem = ext4_fs;
 
+/// 文件系统运行统计
+pub type Ext4Metrics = ext4_metrics;
+
+/// Rust风格别名：挂载参数
+pub type Ext4MountConfig = ext4_mount_config;
+
 /// Rust风格别名：块设备
 pub type Ext4BlockDevice = ext4_blockdev;
 
@@ -476,8 +846,29 @@ pub type Ext4DirEntry = ext4_dir_en;
 /// Rust风格别名：目录项内部字段
 pub type Ext4DirEntryInternal = ext4_dir_en_internal;
 
+/// Rust风格别名：目录块尾部
+pub type Ext4DirEntryTail = ext4_dir_entry_tail;
+
+/// Rust风格别名：块组描述符
+pub type Ext4BlockGroup = ext4_bgroup;
+
+/// Rust风格别名：块组引用
+pub type Ext4BlockGroupRef = ext4_block_group_ref;
+
+/// Rust风格别名：Extent 树节点头
+pub type Ext4ExtentHeader = ext4_extent_header;
+
+/// Rust风格别名：Extent 条目
+pub type Ext4Extent = ext4_extent;
+
+/// Rust风格别名：Extent 索引条目
+pub type Ext4ExtentIndex = ext4_extent_index;
+
 /// Rust风格别名：目录迭代器
 pub type Ext4DirIterator = ext4_dir_iter;
 
 /// Rust风格别名：目录搜索结果
 pub type Ext4DirSearchResult = ext4_dir_search_result;
+
+/// Rust风格别名：格式化参数
+pub type Ext4MkfsInfo = ext4_mkfs_info;
@@ -0,0 +1,122 @@
+//! 撤销日志模块
+//!
+//! lwext4 中没有对应实现。创建文件、重命名等高层操作由多个步骤组成，中途失败时
+//! 之前的步骤可能已经分配了 inode 和块、修改了目录，留下泄漏的资源。撤销日志在操作
+//! 期间记录每个缓存块第一次被访问时的内容（见 bcache 模块），并保存 superblock；
+//! 操作失败时把这些块和 superblock 全部恢复，分配过的 inode 和块随位图一起回到空闲状态。
+//! 撤销不依赖日志，未启用日志的文件系统同样适用；直接写入设备的文件数据不在撤销范围内。
+
+use core::ptr;
+
+use crate::bcache::*;
+use crate::block::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::extent_status::ext4_es_clear;
+use crate::{Ext4Block, Ext4Filesystem, Ext4Superblock};
+
+/// 撤销点
+///
+/// 嵌套的撤销点并入最外层：只有最外层的撤销点记录和恢复状态。
+#[derive(Default)]
+pub struct Ext4Undo {
+    active: bool,                    // 是否为最外层撤销点
+    sb: Ext4Superblock,              // 开始时的 superblock
+    last_inode_bg_id: u32,           // 开始时的 inode 分配位置
+}
+
+impl Ext4Undo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// 开始记录撤销日志
+pub unsafe fn ext4_undo_begin(fs: *mut Ext4Filesystem, undo: *mut Ext4Undo) {
+    unsafe {
+        let bc = (*(*fs).bdev).bc;
+        (*bc).undo_depth += 1;
+        if (*bc).undo_depth > 1 {
+            (*undo).active = false;
+            return;
+        }
+        *undo = Ext4Undo {
+            active: true,
+            sb: (*fs).sb,
+            last_inode_bg_id: (*fs).last_inode_bg_id,
+        };
+    }
+}
+
+/// 操作成功：丢弃撤销日志
+pub unsafe fn ext4_undo_commit(fs: *mut Ext4Filesystem, undo: *mut Ext4Undo) {
+    unsafe {
+        let bc = (*(*fs).bdev).bc;
+        (*bc).undo_depth -= 1;
+        if (*undo).active {
+            ext4_bcache_undo_take(bc);
+            (*undo).active = false;
+        }
+    }
+}
+
+/// 操作失败：恢复撤销点之后修改过的全部块和 superblock
+///
+/// 嵌套的撤销点不做任何事，由最外层撤销点统一恢复。
+pub unsafe fn ext4_undo_rollback(fs: *mut Ext4Filesystem, undo: *mut Ext4Undo) -> i32 {
+    let _span = ext4_dbg_span!(DEBUG_FS, "ext4_undo_rollback");
+    unsafe {
+        let bdev = (*fs).bdev;
+        let bc = (*bdev).bc;
+        (*bc).undo_depth -= 1;
+        if !(*undo).active {
+            return EOK;
+        }
+        (*undo).active = false;
+
+        let log = ext4_bcache_undo_take(bc);
+        let mut ret = EOK;
+        let mut restored = 0;
+        for (lba, entry) in log {
+            let mut b = Ext4Block::new();
+            let buf = ext4_bcache_find_get(bc, &mut b, lba);
+            let Some(data) = entry.data else {
+                // 操作前没有有效内容（新分配的块），丢弃缓存中的修改即可
+                if !buf.is_null() {
+                    ext4_bcache_invalidate_buf(bc, buf);
+                    ext4_bcache_free(bc, &mut b);
+                }
+                continue;
+            };
+            // 缓存中的内容与原始内容相同、且期间没有写回设备时无需恢复
+            if !buf.is_null()
+                && !entry.written
+                && ext4_bcache_test_flag(buf, BC_UPTODATE)
+                && core::slice::from_raw_parts(b.data, data.len()) == &data[..]
+            {
+                ext4_bcache_free(bc, &mut b);
+                continue;
+            }
+            if buf.is_null() {
+                let r = ext4_block_get_noread(bdev, &mut b, lba);
+                if r != EOK {
+                    ret = r;
+                    continue;
+                }
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), b.data, data.len());
+            ext4_block_set_dirty(&mut b);
+            let r = ext4_block_set(bdev, &mut b);
+            if r != EOK {
+                ret = r;
+            }
+            restored += 1;
+        }
+
+        (*fs).sb = (*undo).sb;
+        (*fs).last_inode_bg_id = (*undo).last_inode_bg_id;
+        ext4_es_clear(&mut (*fs).es_cache);
+        ext4_dbg!(DEBUG_FS, Debug, "ext4_undo_rollback: {} blocks restored", restored);
+        ret
+    }
+}
@@ -0,0 +1,110 @@
+//! fs-verity 元数据读取（对应 Linux fs/ext4/verity.c 的只读部分，lwext4 中没有对应实现）
+//!
+//! Merkle 树从 i_size 向上按 64KiB 对齐的位置开始存放，描述符紧随其后；
+//! 最后一个已分配块的末尾4字节记录描述符长度，描述符从该位置之前的块边界开始。
+//! 这些数据位于 i_size 之后，普通读取不会返回它们。
+
+use crate::block::*;
+use crate::extent::*;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4InodeRef};
+
+/// Merkle 树起始位置的对齐粒度
+pub const EXT4_VERITY_METADATA_ALIGN: u64 = 65536;
+/// 描述符（含签名）的最大长度
+pub const FS_VERITY_MAX_DESCRIPTOR_SIZE: u32 = 16384;
+
+/// fs-verity 元数据（Merkle 树）的起始偏移
+pub fn ext4_verity_metadata_pos(size: u64) -> u64 {
+    size.next_multiple_of(EXT4_VERITY_METADATA_ALIGN)
+}
+
+/// 读取文件中任意偏移处的数据，不受 i_size 限制（空洞读为0）
+pub unsafe fn ext4_verity_read(inode_ref: *mut Ext4InodeRef, pos: u64, buf: &mut [u8]) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let block_size = ext4_sb_get_block_size(&(*fs).sb) as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let off = pos + done as u64;
+            let in_block = (off % block_size) as usize;
+            let len = (block_size as usize - in_block).min(buf.len() - done);
+            let dst = &mut buf[done..done + len];
+
+            let mut fblock = 0;
+            let r = ext4_fs_get_inode_dblk_idx(inode_ref, (off / block_size) as u32, &mut fblock, false);
+            if r != EOK {
+                return r;
+            }
+            if fblock == 0 {
+                dst.fill(0);
+            } else {
+                let mut b = Ext4Block::new();
+                let r = ext4_block_get((*fs).bdev, &mut b, fblock);
+                if r != EOK {
+                    return r;
+                }
+                dst.copy_from_slice(core::slice::from_raw_parts(b.data.add(in_block), len));
+                let r = ext4_block_set((*fs).bdev, &mut b);
+                if r != EOK {
+                    return r;
+                }
+            }
+            done += len;
+        }
+    }
+    EOK
+}
+
+/// 定位 fs-verity 描述符，得到其偏移和长度（含签名）
+///
+/// inode 未设置 VERITY 标志时返回 ENODATA；位置或长度不合理时返回 EIO。
+pub unsafe fn ext4_verity_descriptor_location(
+    inode_ref: *mut Ext4InodeRef,
+    desc_pos: &mut u64,
+    desc_size: &mut u32,
+) -> i32 {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode = (*inode_ref).inode;
+        if !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_VERITY) {
+            return ENODATA;
+        }
+        // 与 Linux 相同，只支持使用 extent 的文件
+        if !ext4_inode_has_flag(inode, EXT4_INODE_FLAG_EXTENTS) {
+            return EIO;
+        }
+
+        let mut end = 0;
+        let r = ext4_extent_logical_end(inode_ref, &mut end);
+        if r != EOK {
+            return r;
+        }
+        let block_size = ext4_sb_get_block_size(sb) as u64;
+        let size_pos = (end as u64 * block_size).saturating_sub(4);
+        if size_pos == 0 {
+            return EIO;
+        }
+        let mut raw = [0u8; 4];
+        let r = ext4_verity_read(inode_ref, size_pos, &mut raw);
+        if r != EOK {
+            return r;
+        }
+        let size = u32::from_le_bytes(raw);
+        if size == 0 || size > FS_VERITY_MAX_DESCRIPTOR_SIZE || size as u64 > size_pos {
+            ext4_dbg!(DEBUG_INODE, Warn, "bad verity descriptor size {}: inode {}", size, (*inode_ref).index);
+            return EIO;
+        }
+        let pos = (size_pos - size as u64) / block_size * block_size;
+        if pos < ext4_verity_metadata_pos(ext4_inode_get_size(sb, inode)) {
+            ext4_dbg!(DEBUG_INODE, Warn, "verity descriptor overlaps file data: inode {}", (*inode_ref).index);
+            return EIO;
+        }
+        *desc_pos = pos;
+        *desc_size = size;
+    }
+    EOK
+}
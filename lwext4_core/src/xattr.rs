@@ -0,0 +1,341 @@
+//! 扩展属性读写（对应 ext4_xattr.c）
+//!
+//! 属性依次从 inode 内扩展属性区（128 + extra_isize 之后）和扩展属性块中查找。
+//! 值存放在独立 inode 中（ea_inode 特性）的属性不支持，读取时返回 ENOTSUP。
+//! 写入只修改 inode 内扩展属性区：放不下时返回 ENOSPC，修改扩展属性块中已有的属性返回 ENOTSUP。
+
+use core::cell::Cell;
+
+use alloc::vec::Vec;
+
+use crate::block::*;
+use crate::inode::*;
+use crate::superblock::*;
+use crate::consts::*;
+use crate::debug::*;
+use crate::{Ext4Block, Ext4InodeRef};
+
+/// 扩展属性块头部大小
+const EXT4_XATTR_BLOCK_HDR_SIZE: usize = 32;
+/// inode 内扩展属性区头部大小（只有魔数）
+const EXT4_XATTR_IBODY_HDR_SIZE: usize = 4;
+/// 扩展属性项的固定部分大小（名称紧随其后，按4字节对齐）
+const EXT4_XATTR_ENTRY_SIZE: usize = 16;
+
+/// 属性项回调：参数为 (名称索引, 名称, 值)，值在独立 inode 中时为 Err(ENOTSUP)；
+/// 返回 true 时停止遍历
+type XattrVisitor<'a> = dyn FnMut(u8, &[u8], Result<&[u8], i32>) -> bool + 'a;
+
+/// 遍历一个扩展属性区域中的属性项
+///
+/// entries 从第一个属性项开始，values 是值偏移的基准（inode 内为第一个属性项，块内为块首）。
+fn ext4_xattr_walk(
+    entries: &[u8],
+    values: &[u8],
+    f: &mut XattrVisitor<'_>,
+) -> i32 {
+    let mut off = 0;
+    loop {
+        if off + 4 > entries.len() {
+            return EIO;
+        }
+        // 属性表以4个0字节结束
+        if entries[off..off + 4] == [0; 4] {
+            return EOK;
+        }
+        if off + EXT4_XATTR_ENTRY_SIZE > entries.len() {
+            return EIO;
+        }
+        let e = &entries[off..];
+        let name_len = e[0] as usize;
+        let index = e[1];
+        let value_offs = u16::from_le_bytes([e[2], e[3]]) as usize;
+        let value_inum = u32::from_le_bytes([e[4], e[5], e[6], e[7]]);
+        let value_size = u32::from_le_bytes([e[8], e[9], e[10], e[11]]) as usize;
+        let name_end = EXT4_XATTR_ENTRY_SIZE + name_len;
+        if name_end > e.len() {
+            return EIO;
+        }
+        let name = &e[EXT4_XATTR_ENTRY_SIZE..name_end];
+
+        let value = if value_inum != 0 {
+            Err(ENOTSUP)
+        } else if value_offs + value_size <= values.len() {
+            Ok(&values[value_offs..value_offs + value_size])
+        } else {
+            return EIO;
+        };
+        if f(index, name, value) {
+            return EOK;
+        }
+        off += (name_end + 3) & !3;
+    }
+}
+
+/// 依次遍历 inode 内扩展属性区和扩展属性块
+unsafe fn ext4_xattr_iterate(
+    inode_ref: *mut Ext4InodeRef,
+    f: &mut XattrVisitor<'_>,
+) -> i32 {
+    unsafe {
+        let fs = (*inode_ref).fs;
+        let sb = &(*fs).sb;
+        let inode = (*inode_ref).inode;
+        let stop = Cell::new(false);
+        let mut walk = |index: u8, name: &[u8], value: Result<&[u8], i32>| {
+            stop.set(f(index, name, value));
+            stop.get()
+        };
+
+        // inode 内扩展属性区
+        let inode_size = get_inode_size(sb) as usize;
+        let start = EXT4_GOOD_OLD_INODE_SIZE as usize + ext4_inode_get_extra_isize(sb, inode) as usize;
+        if start + EXT4_XATTR_IBODY_HDR_SIZE <= inode_size {
+            let raw = core::slice::from_raw_parts(inode as *const u8, inode_size);
+            let magic = u32::from_le_bytes([raw[start], raw[start + 1], raw[start + 2], raw[start + 3]]);
+            if magic == EXT4_XATTR_MAGIC {
+                let area = &raw[start + EXT4_XATTR_IBODY_HDR_SIZE..];
+                let r = ext4_xattr_walk(area, area, &mut walk);
+                if r != EOK {
+                    ext4_dbg!(DEBUG_XATTR, Warn, "bad in-inode xattr area: inode {}", (*inode_ref).index);
+                    return r;
+                }
+                if stop.get() {
+                    return EOK;
+                }
+            }
+        }
+
+        // 扩展属性块
+        let xattr_block = ext4_inode_get_file_acl(inode, sb);
+        if xattr_block == 0 {
+            return EOK;
+        }
+        let mut b = Ext4Block::new();
+        let r = ext4_block_get((*fs).bdev, &mut b, xattr_block);
+        if r != EOK {
+            return r;
+        }
+        let r = if ext4_fs_xattr_block_valid(b.data) {
+            let block_size = ext4_sb_get_block_size(sb) as usize;
+            let data = core::slice::from_raw_parts(b.data as *const u8, block_size);
+            ext4_xattr_walk(&data[EXT4_XATTR_BLOCK_HDR_SIZE..], data, &mut walk)
+        } else {
+            EIO
+        };
+        if r != EOK {
+            ext4_dbg!(DEBUG_XATTR, Warn, "bad xattr block {}: inode {}", xattr_block, (*inode_ref).index);
+        }
+        let r2 = ext4_block_set((*fs).bdev, &mut b);
+        if r != EOK { r } else { r2 }
+    }
+}
+
+/// 读取扩展属性值
+///
+/// 属性不存在时返回 ENODATA。*size 设置为值的长度；buf 为空时只查询长度，
+/// 非空但放不下时返回 ERANGE。
+pub unsafe fn ext4_xattr_get(
+    inode_ref: *mut Ext4InodeRef,
+    name_index: u8,
+    name: &[u8],
+    buf: &mut [u8],
+    size: &mut usize,
+) -> i32 {
+    let mut result = ENODATA;
+    let r = unsafe {
+        ext4_xattr_iterate(inode_ref, &mut |index, entry_name, value| {
+            if index != name_index || entry_name != name {
+                return false;
+            }
+            result = match value {
+                Ok(value) => {
+                    *size = value.len();
+                    if buf.is_empty() {
+                        EOK
+                    } else if buf.len() < value.len() {
+                        ERANGE
+                    } else {
+                        buf[..value.len()].copy_from_slice(value);
+                        EOK
+                    }
+                }
+                Err(r) => r,
+            };
+            true
+        })
+    };
+    if r != EOK { r } else { result }
+}
+
+/// 列出 inode 的全部扩展属性（名称索引和不含前缀的名称）
+pub unsafe fn ext4_xattr_list(inode_ref: *mut Ext4InodeRef, list: &mut Vec<(u8, Vec<u8>)>) -> i32 {
+    unsafe {
+        ext4_xattr_iterate(inode_ref, &mut |index, name, _| {
+            list.push((index, name.to_vec()));
+            false
+        })
+    }
+}
+
+/// 属性项的哈希（对应C定义: ext4_xattr_compute_hash）
+fn ext4_xattr_hash(name: &[u8], value: &[u8]) -> u32 {
+    let mut hash: u32 = 0;
+    for &c in name {
+        hash = (hash << 5) ^ (hash >> 27) ^ c as u32;
+    }
+    for chunk in value.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash = (hash << 16) ^ (hash >> 16) ^ u32::from_le_bytes(word);
+    }
+    hash
+}
+
+/// 读出 inode 内扩展属性区的全部属性（名称索引, 名称, 值），返回区域起始偏移和大小
+///
+/// 没有扩展属性区（魔数不匹配）时列表为空；区域内有值存放在独立 inode 中的属性时返回 ENOTSUP。
+unsafe fn ext4_xattr_ibody_load(
+    inode_ref: *mut Ext4InodeRef,
+    attrs: &mut Vec<(u8, Vec<u8>, Vec<u8>)>,
+) -> Result<(usize, usize), i32> {
+    unsafe {
+        let sb = &(*(*inode_ref).fs).sb;
+        let inode_size = get_inode_size(sb) as usize;
+        let start = EXT4_GOOD_OLD_INODE_SIZE as usize + ext4_inode_get_extra_isize(sb, (*inode_ref).inode) as usize;
+        if start + EXT4_XATTR_IBODY_HDR_SIZE + 4 > inode_size {
+            return Err(ENOSPC);
+        }
+        let raw = core::slice::from_raw_parts((*inode_ref).inode as *const u8, inode_size);
+        let magic = u32::from_le_bytes([raw[start], raw[start + 1], raw[start + 2], raw[start + 3]]);
+        if magic == EXT4_XATTR_MAGIC {
+            let area = &raw[start + EXT4_XATTR_IBODY_HDR_SIZE..];
+            let mut err = EOK;
+            let r = ext4_xattr_walk(area, area, &mut |index, name, value| match value {
+                Ok(value) => {
+                    attrs.push((index, name.to_vec(), value.to_vec()));
+                    false
+                }
+                Err(r) => {
+                    err = r;
+                    true
+                }
+            });
+            if r != EOK {
+                return Err(r);
+            }
+            if err != EOK {
+                return Err(err);
+            }
+        }
+        Ok((start, inode_size - start))
+    }
+}
+
+/// 按属性列表重写 inode 内扩展属性区：属性项从前向后排列，值从区域末尾向前存放（与 Linux 相同）
+unsafe fn ext4_xattr_ibody_store(
+    inode_ref: *mut Ext4InodeRef,
+    start: usize,
+    size: usize,
+    attrs: &[(u8, Vec<u8>, Vec<u8>)],
+) -> i32 {
+    let entries_len: usize = attrs
+        .iter()
+        .map(|(_, name, _)| (EXT4_XATTR_ENTRY_SIZE + name.len() + 3) & !3)
+        .sum();
+    let values_len: usize = attrs.iter().map(|(_, _, value)| (value.len() + 3) & !3).sum();
+    // 头部魔数 + 属性项 + 4字节结束标记 + 值
+    let area_len = size - EXT4_XATTR_IBODY_HDR_SIZE;
+    if entries_len + 4 + values_len > area_len {
+        return ENOSPC;
+    }
+
+    unsafe {
+        let raw = core::slice::from_raw_parts_mut(((*inode_ref).inode as *mut u8).add(start), size);
+        raw.fill(0);
+        if attrs.is_empty() {
+            (*inode_ref).dirty = true;
+            return EOK;
+        }
+        raw[..4].copy_from_slice(&EXT4_XATTR_MAGIC.to_le_bytes());
+        let area = &mut raw[EXT4_XATTR_IBODY_HDR_SIZE..];
+        let mut off = 0;
+        let mut value_end = area_len;
+        for (index, name, value) in attrs {
+            let value_offs = if value.is_empty() {
+                0
+            } else {
+                value_end -= (value.len() + 3) & !3;
+                area[value_end..value_end + value.len()].copy_from_slice(value);
+                value_end
+            };
+            let e = &mut area[off..];
+            e[0] = name.len() as u8;
+            e[1] = *index;
+            e[2..4].copy_from_slice(&(value_offs as u16).to_le_bytes());
+            e[4..8].copy_from_slice(&0u32.to_le_bytes());
+            e[8..12].copy_from_slice(&(value.len() as u32).to_le_bytes());
+            e[12..16].copy_from_slice(&ext4_xattr_hash(name, value).to_le_bytes());
+            e[EXT4_XATTR_ENTRY_SIZE..EXT4_XATTR_ENTRY_SIZE + name.len()].copy_from_slice(name);
+            off += (EXT4_XATTR_ENTRY_SIZE + name.len() + 3) & !3;
+        }
+        (*inode_ref).dirty = true;
+    }
+    EOK
+}
+
+/// 设置扩展属性值（不存在时创建，存在时替换）
+///
+/// 只写入 inode 内扩展属性区：放不下时返回 ENOSPC，属性已存在于扩展属性块中时返回 ENOTSUP。
+/// 名称为空或超过255字节时返回 EINVAL。
+pub unsafe fn ext4_xattr_set(
+    inode_ref: *mut Ext4InodeRef,
+    name_index: u8,
+    name: &[u8],
+    value: &[u8],
+) -> i32 {
+    if name.is_empty() || name.len() > u8::MAX as usize {
+        return EINVAL;
+    }
+    unsafe {
+        let mut attrs = Vec::new();
+        let (start, size) = match ext4_xattr_ibody_load(inode_ref, &mut attrs) {
+            Ok(area) => area,
+            Err(r) => return r,
+        };
+        match attrs.iter_mut().find(|(i, n, _)| *i == name_index && n == name) {
+            Some(attr) => attr.2 = value.to_vec(),
+            None => {
+                // 同名属性不能同时出现在 inode 内和扩展属性块中
+                let mut size = 0;
+                match ext4_xattr_get(inode_ref, name_index, name, &mut [], &mut size) {
+                    ENODATA => {}
+                    EOK | ENOTSUP => return ENOTSUP,
+                    r => return r,
+                }
+                attrs.push((name_index, name.to_vec(), value.to_vec()));
+            }
+        }
+        ext4_xattr_ibody_store(inode_ref, start, size, &attrs)
+    }
+}
+
+/// 删除扩展属性
+///
+/// 属性不存在时返回 ENODATA，存在于扩展属性块中时返回 ENOTSUP。
+pub unsafe fn ext4_xattr_remove(inode_ref: *mut Ext4InodeRef, name_index: u8, name: &[u8]) -> i32 {
+    unsafe {
+        let mut attrs = Vec::new();
+        if let Ok((start, size)) = ext4_xattr_ibody_load(inode_ref, &mut attrs) {
+            if let Some(pos) = attrs.iter().position(|(i, n, _)| *i == name_index && n == name) {
+                attrs.remove(pos);
+                return ext4_xattr_ibody_store(inode_ref, start, size, &attrs);
+            }
+        }
+        let mut size = 0;
+        match ext4_xattr_get(inode_ref, name_index, name, &mut [], &mut size) {
+            EOK | ENOTSUP => ENOTSUP,
+            r => r,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! 块缓存接口测试：缓存已满时的 ext4_block_try_get 和丢弃修改的 ext4_block_reload
+
+mod common;
+
+use common::*;
+use lwext4_core::*;
+
+const BLOCK: usize = 1024;
+const CACHE_BLOCKS: u32 = 4;
+
+/// 内存中的块设备（1KiB 逻辑块，缓存 CACHE_BLOCKS 个块，写回模式）
+struct Device {
+    _bbuf: Box<[u8; SECTOR]>,
+    _iface: Box<ext4_blockdev_iface>,
+    bc: Box<ext4_bcache>,
+    bdev: Box<ext4_blockdev>,
+}
+
+impl Device {
+    fn new(blocks: usize) -> Self {
+        DISK.set((0..blocks * BLOCK).map(|i| (i / BLOCK) as u8).collect());
+        let mut bbuf = Box::new([0u8; SECTOR]);
+        let mut iface = Box::new(disk_iface(&mut bbuf, (blocks * BLOCK / SECTOR) as u64));
+        let mut bdev = Box::new(ext4_blockdev::new());
+        bdev.bdif = iface.as_mut();
+        bdev.part_size = (blocks * BLOCK) as u64;
+        let mut bc = Box::new(ext4_bcache::new());
+        unsafe {
+            assert_eq!(ext4_block_init(bdev.as_mut()), EOK);
+            assert_eq!(ext4_bcache_init_dynamic(bc.as_mut(), CACHE_BLOCKS, BLOCK as u32), EOK);
+            assert_eq!(ext4_block_bind_bcache(bdev.as_mut(), bc.as_mut()), EOK);
+            ext4_block_set_lb_size(bdev.as_mut(), BLOCK as u32);
+            assert_eq!(ext4_block_cache_write_back(bdev.as_mut(), 1), EOK);
+        }
+        Self { _bbuf: bbuf, _iface: iface, bc, bdev }
+    }
+
+    /// 获取块，写入 fill 并标记为脏后释放
+    fn write(&mut self, lba: u64, fill: u8) {
+        unsafe {
+            let mut b = ext4_block::new();
+            assert_eq!(ext4_block_get(self.bdev.as_mut(), &mut b, lba), EOK);
+            std::slice::from_raw_parts_mut(b.data, BLOCK).fill(fill);
+            ext4_block_set_dirty(&mut b);
+            assert_eq!(ext4_block_set(self.bdev.as_mut(), &mut b), EOK);
+        }
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            ext4_bcache_cleanup(self.bc.as_mut());
+            ext4_bcache_fini_dynamic(self.bc.as_mut());
+            ext4_block_fini(self.bdev.as_mut());
+        }
+    }
+}
+
+/// 设备上第 lba 块的内容
+fn disk_block(lba: usize) -> Vec<u8> {
+    DISK.with_borrow(|disk| disk[lba * BLOCK..(lba + 1) * BLOCK].to_vec())
+}
+
+#[test]
+fn test_block_try_get_full_dirty_cache() {
+    let mut dev = Device::new(16);
+    for lba in 0..CACHE_BLOCKS as u64 {
+        dev.write(lba, 0xd0 + lba as u8);
+    }
+    unsafe {
+        let bdev = dev.bdev.as_mut();
+        // 缓存已满且全部是脏块：不写回，返回 EBUSY
+        let mut b = ext4_block::new();
+        assert_eq!(ext4_block_try_get(bdev, &mut b, 8), EBUSY);
+        assert!(b.buf.is_null());
+        assert_eq!(disk_block(0), vec![0; BLOCK]);
+        // 已在缓存中的块不受影响
+        assert_eq!(ext4_block_try_get(bdev, &mut b, 2), EOK);
+        assert_eq!(*b.data, 0xd2);
+        assert_eq!(ext4_block_set(bdev, &mut b), EOK);
+
+        // 写回之后可以回收干净的缓冲区
+        assert_eq!(ext4_block_cache_flush(bdev), EOK);
+        assert_eq!(disk_block(0), vec![0xd0; BLOCK]);
+        assert_eq!(ext4_block_try_get(bdev, &mut b, 8), EOK);
+        assert_eq!(std::slice::from_raw_parts(b.data, BLOCK), disk_block(8));
+        assert_eq!(ext4_block_set(bdev, &mut b), EOK);
+    }
+}
+
+#[test]
+fn test_block_reload() {
+    let mut dev = Device::new(16);
+    unsafe {
+        let bdev = dev.bdev.as_mut();
+        let mut b = ext4_block::new();
+        assert_eq!(ext4_block_get(bdev, &mut b, 3), EOK);
+        let data = b.data;
+        std::slice::from_raw_parts_mut(data, BLOCK).fill(0xee);
+        ext4_block_set_dirty(&mut b);
+
+        // 事务、推迟的元数据回写或撤销日志还会用到缓冲区中的修改
+        let bc = dev.bc.as_mut() as *mut ext4_bcache;
+        (*bc).in_trans = true;
+        assert_eq!(ext4_block_reload(bdev, &mut b), EBUSY);
+        (*bc).in_trans = false;
+        (*bc).ordered = true;
+        (*bc).data_writes = 1;
+        assert_eq!(ext4_block_reload(bdev, &mut b), EBUSY);
+        (*bc).ordered = false;
+        (*bc).data_writes = 0;
+        (*bc).undo_depth = 1;
+        assert_eq!(ext4_block_reload(bdev, &mut b), EBUSY);
+        (*bc).undo_depth = 0;
+        assert_eq!(std::slice::from_raw_parts(data, BLOCK), vec![0xee; BLOCK]);
+
+        // 重新读取设备上的内容，修改被丢弃，不再写回
+        assert_eq!(ext4_block_reload(bdev, &mut b), EOK);
+        assert_eq!(std::slice::from_raw_parts(data, BLOCK), disk_block(3));
+        assert_eq!(*data, 3);
+        assert!(!ext4_bcache_test_flag(b.buf, BC_DIRTY));
+        assert_eq!(ext4_block_set(bdev, &mut b), EOK);
+        assert_eq!(ext4_block_cache_flush(bdev), EOK);
+        assert_eq!(disk_block(3), vec![3; BLOCK]);
+    }
+}
@@ -0,0 +1,105 @@
+//! C 接口测试：通过与 lwext4 相同的函数绑定设备、扫描分区和挂载
+
+#![cfg(feature = "c-api")]
+
+use std::process::Command;
+
+mod common;
+
+use common::*;
+use lwext4_core::c_api::*;
+use lwext4_core::{ext4_blockdev, ENOENT, EOK};
+
+/// 写入一个 MBR 分区表项
+fn set_part_entry(disk: &mut [u8], index: usize, part_type: u8, first_lba: u32, sectors: u32) {
+    let e = &mut disk[446 + index * 16..446 + (index + 1) * 16];
+    e[4] = part_type;
+    e[8..12].copy_from_slice(&first_lba.to_le_bytes());
+    e[12..16].copy_from_slice(&sectors.to_le_bytes());
+}
+
+/// 用 mke2fs 生成指定大小的 ext4 分区内容（未安装 e2fsprogs 时返回 None）
+fn make_ext4(size: usize) -> Option<Vec<u8>> {
+    let path = std::env::temp_dir().join(format!("lwext4-core-mbr-{}.ext4", std::process::id()));
+    std::fs::write(&path, vec![0u8; size]).unwrap();
+    let status = Command::new("mke2fs").args(["-q", "-F", "-t", "ext4"]).arg(&path).status();
+    let data = match status {
+        Ok(status) if status.success() => Some(std::fs::read(&path).unwrap()),
+        _ => None,
+    };
+    std::fs::remove_file(&path).unwrap();
+    data
+}
+
+#[test]
+fn test_mbr_scan() {
+    // 两个分区：FAT32（0x0c）和 Linux 原生分区（0x83），第三个表项超出设备范围
+    const FAT_LBA: u32 = 2048;
+    const FAT_SECTORS: u32 = 2048;
+    const LINUX_LBA: u32 = 4096;
+    const LINUX_SIZE: usize = 4 << 20;
+    let linux_sectors = (LINUX_SIZE / SECTOR) as u32;
+    let mut disk = vec![0u8; LINUX_LBA as usize * SECTOR];
+    set_part_entry(&mut disk, 0, 0x0c, FAT_LBA, FAT_SECTORS);
+    set_part_entry(&mut disk, 1, 0x83, LINUX_LBA, linux_sectors);
+    set_part_entry(&mut disk, 2, 0x83, LINUX_LBA + linux_sectors, 1);
+    disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+    let partition = make_ext4(LINUX_SIZE);
+    disk.extend_from_slice(partition.as_deref().unwrap_or(&vec![0u8; LINUX_SIZE]));
+    let disk_size = disk.len() as u64;
+    DISK.set(disk);
+
+    let mut bbuf = [0u8; SECTOR];
+    let mut iface = disk_iface(&mut bbuf, disk_size / SECTOR as u64);
+    let mut parent = ext4_blockdev::new();
+    parent.bdif = &mut iface;
+    parent.part_size = disk_size;
+
+    unsafe {
+        let mut bdevs: ext4_mbr_bdevs = std::mem::zeroed();
+        assert_eq!(ext4_mbr_scan(&mut parent, &mut bdevs), EOK);
+        // 非空表项都被报告，类型交给调用者判断
+        assert_eq!(bdevs.types, [0x0c, 0x83, 0, 0]);
+        let fat = &bdevs.partitions[0];
+        assert_eq!(fat.bdif, &mut iface as *mut _);
+        assert_eq!(fat.part_offset, FAT_LBA as u64 * SECTOR as u64);
+        assert_eq!(fat.part_size, FAT_SECTORS as u64 * SECTOR as u64);
+        let linux = &bdevs.partitions[1];
+        assert_eq!(linux.part_offset, LINUX_LBA as u64 * SECTOR as u64);
+        assert_eq!(linux.part_size, LINUX_SIZE as u64);
+        assert!(bdevs.partitions[2].bdif.is_null());
+        assert!(bdevs.partitions[3].bdif.is_null());
+        assert_eq!(iface.ph_refctr, 0);
+
+        // 按类型选出 Linux 分区，注册后挂载并写入文件
+        if partition.is_some() {
+            let linux = bdevs.types.iter().position(|&t| t == 0x83).unwrap();
+            assert_eq!(ext4_device_register(&mut bdevs.partitions[linux], c"sda1".as_ptr()), EOK);
+            assert_eq!(ext4_mount(c"sda1".as_ptr(), c"/mp/".as_ptr(), false), EOK);
+            let mut file: ext4_file = std::mem::zeroed();
+            assert_eq!(ext4_fopen(&mut file, c"/mp/hello".as_ptr(), c"w".as_ptr()), EOK);
+            let mut written = 0;
+            assert_eq!(ext4_fwrite(&mut file, b"hello".as_ptr() as _, 5, &mut written), EOK);
+            assert_eq!(written, 5);
+            assert_eq!(ext4_fclose(&mut file), EOK);
+            assert_eq!(ext4_umount(c"/mp/".as_ptr()), EOK);
+            assert_eq!(ext4_device_unregister(c"sda1".as_ptr()), EOK);
+
+            // 分区之外的数据（分区表）没有被改动，分区内容由 e2fsck 检查
+            DISK.with_borrow(|disk| {
+                assert_eq!(disk[446 + 4], 0x0c);
+                let path = std::env::temp_dir().join(format!("lwext4-core-sda1-{}.ext4", std::process::id()));
+                std::fs::write(&path, &disk[LINUX_LBA as usize * SECTOR..]).unwrap();
+                let output = Command::new("e2fsck").arg("-fn").arg(&path).output().unwrap();
+                std::fs::remove_file(&path).unwrap();
+                assert!(output.status.success(), "e2fsck failed:\n{}", String::from_utf8_lossy(&output.stdout));
+            });
+        }
+
+        // 没有 MBR 签名
+        DISK.with_borrow_mut(|disk| disk[510..512].fill(0));
+        assert_eq!(ext4_mbr_scan(&mut parent, &mut bdevs), ENOENT);
+        assert!(bdevs.partitions.iter().all(|p| p.bdif.is_null()));
+        assert_eq!(bdevs.types, [0; 4]);
+    }
+}
@@ -0,0 +1,58 @@
+//! 测试共用的内存块设备：扇区大小 SECTOR，内容保存在线程局部的 DISK 中
+
+use std::cell::RefCell;
+use std::ffi::c_void;
+
+use lwext4_core::{ext4_blockdev, ext4_blockdev_iface, EINVAL, EOK};
+
+pub const SECTOR: usize = 512;
+
+thread_local! {
+    // 块设备回调没有上下文参数可用，设备内容按线程保存
+    pub static DISK: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+unsafe extern "C" fn disk_open(_: *mut ext4_blockdev) -> i32 {
+    EOK
+}
+
+unsafe extern "C" fn disk_close(_: *mut ext4_blockdev) -> i32 {
+    EOK
+}
+
+unsafe extern "C" fn disk_bread(_: *mut ext4_blockdev, buf: *mut c_void, blk_id: u64, blk_cnt: u32) -> i32 {
+    DISK.with_borrow(|disk| {
+        let start = blk_id as usize * SECTOR;
+        let len = blk_cnt as usize * SECTOR;
+        let Some(src) = disk.get(start..start + len) else {
+            return EINVAL;
+        };
+        unsafe { std::ptr::copy_nonoverlapping(src.as_ptr(), buf as *mut u8, len) };
+        EOK
+    })
+}
+
+unsafe extern "C" fn disk_bwrite(_: *mut ext4_blockdev, buf: *const c_void, blk_id: u64, blk_cnt: u32) -> i32 {
+    DISK.with_borrow_mut(|disk| {
+        let start = blk_id as usize * SECTOR;
+        let len = blk_cnt as usize * SECTOR;
+        let Some(dst) = disk.get_mut(start..start + len) else {
+            return EINVAL;
+        };
+        unsafe { std::ptr::copy_nonoverlapping(buf as *const u8, dst.as_mut_ptr(), len) };
+        EOK
+    })
+}
+
+/// 构造读写 DISK 的设备接口；bbuf 必须在接口使用期间保持有效
+pub fn disk_iface(bbuf: &mut [u8; SECTOR], ph_bcnt: u64) -> ext4_blockdev_iface {
+    let mut iface = ext4_blockdev_iface::new();
+    iface.open = Some(disk_open);
+    iface.bread = Some(disk_bread);
+    iface.bwrite = Some(disk_bwrite);
+    iface.close = Some(disk_close);
+    iface.ph_bsize = SECTOR as u32;
+    iface.ph_bcnt = ph_bcnt;
+    iface.ph_bbuf = bbuf.as_mut_ptr();
+    iface
+}
@@ -0,0 +1,102 @@
+//! 拥有所有权的 inode 引用（InodeRef）测试：在 mke2fs 生成的镜像上同时持有、修改和归还
+
+use std::process::Command;
+
+mod common;
+
+use common::*;
+use lwext4_core::*;
+
+const IMAGE_SIZE: usize = 4 << 20;
+
+/// 在临时文件上运行 e2fsprogs 的工具（未安装时返回 None），返回是否成功和运行后的文件内容
+fn e2fsprogs(tool: &str, args: &[&str], image: &[u8]) -> Option<(bool, Vec<u8>)> {
+    let path = std::env::temp_dir().join(format!("lwext4-core-inode-{}.ext4", std::process::id()));
+    std::fs::write(&path, image).unwrap();
+    let output = Command::new(tool).args(args).arg(&path).output();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output.ok().map(|output| (output.status.success(), data))
+}
+
+#[test]
+fn test_inode_ref_handle() {
+    let Some((true, image)) = e2fsprogs("mke2fs", &["-q", "-F", "-t", "ext4"], &vec![0u8; IMAGE_SIZE]) else {
+        return;
+    };
+    DISK.set(image);
+
+    let mut bbuf = [0u8; SECTOR];
+    let mut iface = disk_iface(&mut bbuf, (IMAGE_SIZE / SECTOR) as u64);
+    let mut bdev = ext4_blockdev::new();
+    bdev.bdif = &mut iface;
+    bdev.part_size = IMAGE_SIZE as u64;
+    let mut bc = ext4_bcache::new();
+    let mut fs = Box::new(Ext4Filesystem::new());
+    let fs: *mut Ext4Filesystem = fs.as_mut();
+
+    unsafe {
+        assert_eq!(ext4_block_init(&mut bdev), EOK);
+        assert_eq!(ext4_fs_init(fs, &mut bdev, false), EOK);
+        let bsize = ext4_sb_get_block_size(&(*fs).sb);
+        ext4_block_set_lb_size(&mut bdev, bsize);
+        assert_eq!(ext4_bcache_init_dynamic(&mut bc, 8, bsize), EOK);
+        assert_eq!(ext4_block_bind_bcache(&mut bdev, &mut bc), EOK);
+
+        assert_eq!(InodeRef::get(fs, 0).err(), Some(EINVAL));
+
+        // 同一 inode 的两个引用同时有效，共享块缓存中的数据
+        let mut a = InodeRef::get(fs, EXT4_INODE_ROOT_INDEX).unwrap();
+        let b = InodeRef::get(fs, EXT4_INODE_ROOT_INDEX).unwrap();
+        assert_eq!(b.ino(), EXT4_INODE_ROOT_INDEX);
+        assert!(ext4_inode_is_type(&(*fs).sb, b.inode, EXT4_INODE_MODE_DIRECTORY));
+        let buf = a.block.buf;
+        assert_eq!((*buf).refctr, 2);
+        ext4_inode_set_access_time(a.inode, 0x1234);
+        a.dirty = true;
+        assert_eq!(ext4_inode_get_access_time(b.inode), 0x1234);
+
+        // Drop 归还引用；put 归还后 Drop 不再重复归还
+        drop(b);
+        assert_eq!((*buf).refctr, 1);
+        assert_eq!(a.put(), EOK);
+        assert_eq!((*buf).refctr, 0);
+        drop(InodeRef::new());
+
+        assert_eq!(ext4_fs_fini(fs), EOK);
+        ext4_bcache_cleanup(&mut bc);
+        ext4_bcache_fini_dynamic(&mut bc);
+        assert_eq!(ext4_block_fini(&mut bdev), EOK);
+    }
+
+    // 归还时更新了 inode 校验和，修改写回设备后 e2fsck 检查通过
+    let image = DISK.take();
+    if let Some((clean, _)) = e2fsprogs("e2fsck", &["-fn"], &image) {
+        assert!(clean, "e2fsck reported errors");
+    }
+}
+
+#[test]
+fn test_inode_max_size() {
+    // 期望值按 Linux 的 ext4_max_bitmap_size / ext4_max_size 计算
+    let cases = [
+        // (log_block_size, huge_file, 间接映射文件的最大大小, extent 文件的最大大小)
+        (0, false, 17_247_252_480, 2_199_023_255_040),
+        (2, false, 2_196_873_666_560, 2_199_023_255_040),
+        (2, true, 4_402_345_721_856, 17_592_186_040_320),
+    ];
+    for (log_block_size, huge_file, bitmap_max, extent_max) in cases {
+        let mut sb: Ext4Superblock = unsafe { std::mem::zeroed() };
+        sb.log_block_size = (log_block_size as u32).to_le();
+        if huge_file {
+            sb.feature_ro_compat = EXT4_FRO_COM_HUGE_FILE.to_le();
+        }
+        let mut inode: Ext4Inode = unsafe { std::mem::zeroed() };
+        unsafe {
+            // 没有 huge_file 时，间接块也计入32位的 i_blocks，最大大小小于 2^32 个扇区
+            assert_eq!(ext4_inode_max_size(&sb, &inode), bitmap_max, "{log_block_size} {huge_file}");
+            ext4_inode_set_flag(&mut inode, EXT4_INODE_FLAG_EXTENTS);
+            assert_eq!(ext4_inode_max_size(&sb, &inode), extent_max, "{log_block_size} {huge_file}");
+        }
+    }
+}